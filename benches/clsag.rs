@@ -0,0 +1,60 @@
+// Measures signing and verification across ring sizes and key vector sizes.
+// Throughput is reported in ring members per second.
+// Run with `cargo bench --features test-utils --bench clsag`
+use clsag::clsag::Clsag;
+use clsag::test_utils::*;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+const RING_SIZES: [usize; 6] = [2, 11, 16, 64, 128, 256];
+const NUM_KEYS: [usize; 3] = [1, 2, 4];
+
+fn generate_ring(ring_size: usize, num_keys: usize) -> Clsag {
+    let mut clsag = generate_clsag_with(ring_size - 1, num_keys);
+    clsag.add_member(generate_signer(num_keys)).unwrap();
+    clsag
+}
+
+fn bench_sign(c: &mut Criterion) {
+    let msg = b"hello world";
+
+    for num_keys in NUM_KEYS {
+        let mut group = c.benchmark_group(format!("sign/{}_keys", num_keys));
+        for ring_size in RING_SIZES {
+            let clsag = generate_ring(ring_size, num_keys);
+
+            group.throughput(Throughput::Elements(ring_size as u64));
+            group.bench_with_input(
+                BenchmarkId::from_parameter(ring_size),
+                &clsag,
+                |b, clsag| b.iter(|| clsag.sign(msg)),
+            );
+        }
+        group.finish();
+    }
+}
+
+fn bench_verify(c: &mut Criterion) {
+    let msg = b"hello world";
+
+    for num_keys in NUM_KEYS {
+        let mut group = c.benchmark_group(format!("verify/{}_keys", num_keys));
+        for ring_size in RING_SIZES {
+            let (signature, public_keys) = generate_ring(ring_size, num_keys).sign(msg).unwrap();
+
+            group.throughput(Throughput::Elements(ring_size as u64));
+            group.bench_with_input(
+                BenchmarkId::from_parameter(ring_size),
+                &public_keys,
+                |b, public_keys| b.iter(|| signature.optimised_verify(public_keys, msg)),
+            );
+        }
+        group.finish();
+    }
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(10);
+    targets = bench_sign, bench_verify
+}
+criterion_main!(benches);
@@ -0,0 +1,53 @@
+// Compares the multiplications of the signer with the precomputed tables against the default
+// multiplications without them, along with computing the key images of a signer.
+// Run with `cargo bench --features precompute,test-utils --bench precompute`
+use clsag::constants::BASEPOINT;
+use clsag::member::Member;
+use clsag::precompute::FixedBaseTable;
+use clsag::test_utils::*;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use curve25519_dalek::constants::RISTRETTO_BASEPOINT_TABLE;
+
+const NUM_KEYS: [usize; 3] = [1, 4, 16];
+
+fn bench_mul(c: &mut Criterion) {
+    let scalar = generate_rand_scalars(1)[0];
+    let point = generate_rand_points(1)[0];
+    let table = FixedBaseTable::new(&point);
+
+    let mut group = c.benchmark_group("precompute/mul");
+    group.bench_function("default", |b| b.iter(|| scalar * point));
+    group.bench_function("table", |b| b.iter(|| table.mul(&scalar)));
+    group.bench_function("basepoint_default", |b| b.iter(|| scalar * BASEPOINT));
+    group.bench_function("basepoint_table", |b| {
+        b.iter(|| &RISTRETTO_BASEPOINT_TABLE * &scalar)
+    });
+    group.bench_function("create_table", |b| b.iter(|| FixedBaseTable::new(&point)));
+    group.finish();
+}
+
+fn bench_key_images(c: &mut Criterion) {
+    let mut group = c.benchmark_group("precompute/key_images");
+    for num_keys in NUM_KEYS {
+        let private_set = generate_private_set(num_keys);
+        let signer = Member::new_signer(generate_rand_scalars(num_keys));
+        let hashed_pubkey = generate_rand_points(1)[0];
+
+        group.bench_with_input(
+            BenchmarkId::new("default", num_keys),
+            &private_set,
+            |b, private_set| b.iter(|| private_set.compute_key_images(&hashed_pubkey)),
+        );
+        group.bench_with_input(BenchmarkId::new("table", num_keys), &signer, |b, signer| {
+            b.iter(|| signer.compute_key_images())
+        });
+    }
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(10);
+    targets = bench_mul, bench_key_images
+}
+criterion_main!(benches);
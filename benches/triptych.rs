@@ -0,0 +1,74 @@
+// Compares signing and verifying with Triptych proofs against clsag signatures, over rings
+// of one key per member. The size of each proof is printed alongside.
+// Run with `cargo bench --features experimental,test-utils --bench triptych`
+use clsag::member::Member;
+use clsag::test_utils::*;
+use clsag::triptych::TriptychProof;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+const RING_SIZES: [usize; 4] = [16, 64, 128, 256];
+
+fn generate_members(ring_size: usize) -> Vec<Member> {
+    let mut members = generate_decoys(ring_size - 1, 1);
+    members.push(generate_signer(1));
+    members
+}
+
+fn bench_sign(c: &mut Criterion) {
+    let msg = b"hello world";
+
+    let mut group = c.benchmark_group("triptych/sign");
+    for ring_size in RING_SIZES {
+        let members = generate_members(ring_size);
+        group.bench_with_input(
+            BenchmarkId::new("triptych", ring_size),
+            &members,
+            |b, members| b.iter(|| TriptychProof::sign(&mut rand::thread_rng(), members, msg)),
+        );
+
+        let mut clsag = generate_clsag_with(ring_size - 1, 1);
+        clsag.add_member(generate_signer(1)).unwrap();
+        group.bench_with_input(BenchmarkId::new("clsag", ring_size), &clsag, |b, clsag| {
+            b.iter(|| clsag.sign(msg))
+        });
+    }
+    group.finish();
+}
+
+fn bench_verify(c: &mut Criterion) {
+    let msg = b"hello world";
+
+    let mut group = c.benchmark_group("triptych/verify");
+    for ring_size in RING_SIZES {
+        let (proof, ring) =
+            TriptychProof::sign(&mut rand::thread_rng(), &generate_members(ring_size), msg)
+                .unwrap();
+        group.bench_with_input(BenchmarkId::new("triptych", ring_size), &ring, |b, ring| {
+            b.iter(|| proof.verify(ring, msg))
+        });
+
+        let mut clsag = generate_clsag_with(ring_size - 1, 1);
+        clsag.add_member(generate_signer(1)).unwrap();
+        let (signature, public_keys) = clsag.sign(msg).unwrap();
+        group.bench_with_input(
+            BenchmarkId::new("clsag", ring_size),
+            &public_keys,
+            |b, public_keys| b.iter(|| signature.optimised_verify(public_keys, msg)),
+        );
+
+        println!(
+            "ring size {}: triptych proof {} bytes, clsag signature {} bytes",
+            ring_size,
+            proof.size(),
+            signature.to_bytes().len()
+        );
+    }
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(10);
+    targets = bench_sign, bench_verify
+}
+criterion_main!(benches);
@@ -0,0 +1,90 @@
+// Compares the reference verifier, which computes every point with its own scalar
+// multiplications, against the multiscalar verifier, and verifying a batch of signatures
+// one by one against `batch_verify`, which shares the work on the rings of the batch.
+// Run with `cargo bench --features test-utils --bench verify`
+use clsag::clsag::{batch_verify, Clsag, PublicKeys};
+use clsag::ring::PreparedRing;
+use clsag::signature::Signature;
+use clsag::test_utils::*;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+fn bench_verify(c: &mut Criterion) {
+    let num_keys = 2;
+    let msg = b"hello world";
+
+    let mut group = c.benchmark_group("verify");
+    for ring_size in [11usize, 16, 64] {
+        let mut clsag = generate_clsag_with(ring_size - 1, num_keys);
+        clsag.add_member(generate_signer(num_keys)).unwrap();
+        let (signature, public_keys) = clsag.sign(msg).unwrap();
+        let ring = PreparedRing::new(&public_keys).unwrap();
+
+        group.bench_with_input(
+            BenchmarkId::new("reference", ring_size),
+            &public_keys,
+            |b, public_keys| b.iter(|| signature.verify(public_keys, msg)),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("multiscalar", ring_size),
+            &public_keys,
+            |b, public_keys| b.iter(|| signature.optimised_verify(public_keys, msg)),
+        );
+        group.bench_with_input(BenchmarkId::new("prepared", ring_size), &ring, |b, ring| {
+            b.iter(|| signature.verify_prepared(ring, msg))
+        });
+    }
+    group.finish();
+}
+
+// Signs a batch of signatures over rings of 11 members, whose decoys are picked from a pool
+// of outputs, with every ring signed by `signers_per_ring` signers, as the inputs of a
+// transaction are
+fn signed_batch(
+    num_rings: usize,
+    pool_size: usize,
+    signers_per_ring: usize,
+    msg: &[u8],
+) -> Vec<(Signature, PublicKeys, &[u8])> {
+    let num_keys = 2;
+    let outputs = generate_decoys(pool_size, num_keys);
+    let mut batch = Vec::new();
+    for i in 0..num_rings {
+        let mut clsag = Clsag::new();
+        for decoy in outputs.iter().cycle().skip(i * 3).take(10) {
+            clsag.add_member(decoy.clone()).unwrap();
+        }
+        clsag.add_member(generate_signer(num_keys)).unwrap();
+        clsag.set_shuffle_members(false);
+        for _ in 0..signers_per_ring {
+            let (signature, public_keys) = clsag.sign(msg).unwrap();
+            batch.push((signature, public_keys, msg));
+        }
+    }
+    batch
+}
+
+fn bench_batch_verify(c: &mut Criterion) {
+    let msg = b"hello world";
+
+    let mut group = c.benchmark_group("batch_verify");
+    let batches = [
+        ("overlapping_rings", signed_batch(16, 32, 1, msg)),
+        ("shared_rings", signed_batch(4, 32, 4, msg)),
+    ];
+    for (name, batch) in batches.iter() {
+        group.bench_with_input(BenchmarkId::new("one_by_one", name), batch, |b, batch| {
+            b.iter(|| {
+                batch.iter().all(|(signature, public_keys, msg)| {
+                    signature.optimised_verify(public_keys, msg).is_ok()
+                })
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("batch", name), batch, |b, batch| {
+            b.iter(|| batch_verify(batch))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_verify, bench_batch_verify);
+criterion_main!(benches);
@@ -0,0 +1,105 @@
+#ifndef CLSAG_H
+#define CLSAG_H
+
+/* Generated by cbindgen from the clsag-capi crate, do not edit by hand */
+
+#include <stddef.h>
+#include <stdint.h>
+
+// The result of every function in the C interface
+typedef enum ClsagResult {
+  // The function succeeded
+  CLSAG_RESULT_OK = 0,
+  // A pointer which must not be null was null
+  CLSAG_RESULT_NULL_POINTER = 1,
+  // A private key is not canonical, or a public key is not a valid point
+  CLSAG_RESULT_INVALID_KEY = 2,
+  // An output buffer is smaller than the output
+  CLSAG_RESULT_BUFFER_TOO_SMALL = 3,
+  // The ring could not be signed, as it is too small or has duplicate keys
+  CLSAG_RESULT_SIGN_FAILED = 4,
+  // The signature could not be decoded, or does not verify
+  CLSAG_RESULT_INVALID_SIGNATURE = 5,
+} ClsagResult;
+
+// Returns the size of the encoding of a signature over a ring of `ring_size` members,
+// who each have `num_keys` keys
+size_t clsag_signature_size(size_t ring_size, size_t num_keys);
+
+// Generates `num_keys` random private keys, writing them to `private_keys_out`
+// and the corresponding public keys to `public_keys_out`.
+//
+// # Safety
+//
+// Both output buffers must be valid for writes of `num_keys * 32` bytes.
+ClsagResult clsag_keygen(size_t num_keys, uint8_t *private_keys_out, uint8_t *public_keys_out);
+
+// Computes the public keys of `num_keys` private keys, writing them to `public_keys_out`.
+//
+// # Safety
+//
+// `private_keys` must be valid for reads of `num_keys * 32` bytes, and `public_keys_out`
+// must be valid for writes of `num_keys * 32` bytes.
+ClsagResult clsag_public_keys(const uint8_t *private_keys,
+                              size_t num_keys,
+                              uint8_t *public_keys_out);
+
+// Computes the key images of a signer with `num_keys` private keys, writing them
+// to `key_images_out`. The first key image is the one signatures are linked by.
+//
+// # Safety
+//
+// `private_keys` must be valid for reads of `num_keys * 32` bytes, and `key_images_out`
+// must be valid for writes of `num_keys * 32` bytes.
+ClsagResult clsag_key_images(const uint8_t *private_keys, size_t num_keys, uint8_t *key_images_out);
+
+// Writes the linking key image of an encoded signature, which is its first key image,
+// to `key_image_out`.
+//
+// # Safety
+//
+// `signature` must be valid for reads of `signature_len` bytes, and `key_image_out`
+// must be valid for writes of 32 bytes.
+ClsagResult clsag_signature_key_image(const uint8_t *signature,
+                                      size_t signature_len,
+                                      uint8_t *key_image_out);
+
+// Signs the message with a signer holding `num_keys` private keys, hidden in a ring with
+// `num_decoys` decoys who each have `num_keys` public keys. The encoded signature is written
+// to `signature_out`, and the public keys of the ring in the shuffled order it was signed in
+// are written to `ring_out`, which is the ring the signature verifies against.
+//
+// # Safety
+//
+// `private_keys` must be valid for reads of `num_keys * 32` bytes, `decoy_keys` for reads of
+// `num_decoys * num_keys * 32` bytes and `msg` for reads of `msg_len` bytes.
+// `signature_out` must be valid for writes of `signature_out_len` bytes, which must be at
+// least `clsag_signature_size(num_decoys + 1, num_keys)`, and `ring_out` must be valid for
+// writes of `(num_decoys + 1) * num_keys * 32` bytes.
+ClsagResult clsag_sign(const uint8_t *private_keys,
+                       size_t num_keys,
+                       const uint8_t *decoy_keys,
+                       size_t num_decoys,
+                       const uint8_t *msg,
+                       size_t msg_len,
+                       uint8_t *signature_out,
+                       size_t signature_out_len,
+                       uint8_t *ring_out);
+
+// Verifies the encoded signature on the message over a ring of `num_members` members,
+// whose public keys are given in ring order. Each member has as many keys as the signature
+// has key images. Returns `Ok` if the signature verifies, and `InvalidSignature` if not.
+//
+// # Safety
+//
+// `signature` must be valid for reads of `signature_len` bytes, `public_keys` for reads of
+// `num_members * num_keys * 32` bytes where `num_keys` is the number of key images of the
+// signature, and `msg` for reads of `msg_len` bytes.
+ClsagResult clsag_verify(const uint8_t *signature,
+                         size_t signature_len,
+                         const uint8_t *public_keys,
+                         size_t num_members,
+                         const uint8_t *msg,
+                         size_t msg_len);
+
+#endif /* CLSAG_H */
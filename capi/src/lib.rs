@@ -0,0 +1,420 @@
+// A C interface to key generation, signing and verification over the clsag crate.
+//
+// Every function takes byte buffers as a pointer and a length, and returns a `ClsagResult`.
+// Keys use the same encodings as the wasm bindings: private keys are 32 byte canonical
+// scalars, public keys and key images are 32 byte compressed ristretto points, and signatures
+// use the `Signature::to_bytes` encoding. A list of keys is the concatenation of each key,
+// and a ring is the concatenation of the keys of each member in ring order.
+// Output buffers are allocated by the caller, so no memory is ever freed across the boundary.
+//
+// The crate is built as a `cdylib` and a `staticlib`, `libclsag_capi`, to link against. The
+// header at `include/clsag.h` is generated from this crate by cbindgen, from this directory:
+//
+//   cbindgen --config cbindgen.toml --output include/clsag.h
+use clsag::clsag::Clsag;
+use clsag::keys::{PublicKey, SecretKey};
+use clsag::member::Member;
+use clsag::signature::Signature;
+use std::slice;
+
+const KEY_SIZE: usize = 32;
+
+/// The result of every function in the C interface
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClsagResult {
+    /// The function succeeded
+    Ok = 0,
+    /// A pointer which must not be null was null
+    NullPointer = 1,
+    /// A private key is not canonical, or a public key is not a valid point
+    InvalidKey = 2,
+    /// An output buffer is smaller than the output
+    BufferTooSmall = 3,
+    /// The ring could not be signed, as it is too small or has duplicate keys
+    SignFailed = 4,
+    /// The signature could not be decoded, or does not verify
+    InvalidSignature = 5,
+}
+
+// Returns the input buffer as a slice, allowing a null pointer only for an empty buffer
+unsafe fn input<'a>(ptr: *const u8, len: usize) -> Result<&'a [u8], ClsagResult> {
+    if len == 0 {
+        return Ok(&[]);
+    }
+    if ptr.is_null() {
+        return Err(ClsagResult::NullPointer);
+    }
+    Ok(slice::from_raw_parts(ptr, len))
+}
+
+// Returns the output buffer as a slice, if it is not null
+unsafe fn output<'a>(ptr: *mut u8, len: usize) -> Result<&'a mut [u8], ClsagResult> {
+    if ptr.is_null() {
+        return Err(ClsagResult::NullPointer);
+    }
+    Ok(slice::from_raw_parts_mut(ptr, len))
+}
+
+// Splits the buffer into the 32 byte keys it holds
+fn split_keys(bytes: &[u8]) -> Vec<[u8; 32]> {
+    bytes
+        .chunks_exact(KEY_SIZE)
+        .map(|chunk| {
+            let mut key = [0u8; 32];
+            key.copy_from_slice(chunk);
+            key
+        })
+        .collect()
+}
+
+// Decodes the private keys of a signer
+fn decode_secret_keys(bytes: &[u8]) -> Result<Vec<SecretKey>, ClsagResult> {
+    split_keys(bytes)
+        .iter()
+        .map(|key| SecretKey::from_bytes(key).map_err(|_| ClsagResult::InvalidKey))
+        .collect()
+}
+
+// Runs the body of a function, returning its result code
+fn status(result: Result<(), ClsagResult>) -> ClsagResult {
+    match result {
+        Ok(()) => ClsagResult::Ok,
+        Err(code) => code,
+    }
+}
+
+/// Returns the size of the encoding of a signature over a ring of `ring_size` members,
+/// who each have `num_keys` keys
+#[no_mangle]
+pub extern "C" fn clsag_signature_size(ring_size: usize, num_keys: usize) -> usize {
+    Signature::serialized_size(ring_size, num_keys)
+}
+
+/// Generates `num_keys` random private keys, writing them to `private_keys_out`
+/// and the corresponding public keys to `public_keys_out`.
+///
+/// # Safety
+///
+/// Both output buffers must be valid for writes of `num_keys * 32` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn clsag_keygen(
+    num_keys: usize,
+    private_keys_out: *mut u8,
+    public_keys_out: *mut u8,
+) -> ClsagResult {
+    status((|| {
+        let private_keys_out = output(private_keys_out, num_keys * KEY_SIZE)?;
+        let public_keys_out = output(public_keys_out, num_keys * KEY_SIZE)?;
+
+        let mut rng = rand::thread_rng();
+        for (private_key, public_key) in private_keys_out
+            .chunks_exact_mut(KEY_SIZE)
+            .zip(public_keys_out.chunks_exact_mut(KEY_SIZE))
+        {
+            let secret_key = SecretKey::random(&mut rng);
+            private_key.copy_from_slice(&secret_key.to_bytes());
+            public_key.copy_from_slice(secret_key.public_key().as_bytes());
+        }
+        Ok(())
+    })())
+}
+
+/// Computes the public keys of `num_keys` private keys, writing them to `public_keys_out`.
+///
+/// # Safety
+///
+/// `private_keys` must be valid for reads of `num_keys * 32` bytes, and `public_keys_out`
+/// must be valid for writes of `num_keys * 32` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn clsag_public_keys(
+    private_keys: *const u8,
+    num_keys: usize,
+    public_keys_out: *mut u8,
+) -> ClsagResult {
+    status((|| {
+        let secret_keys = decode_secret_keys(input(private_keys, num_keys * KEY_SIZE)?)?;
+
+        let public_keys_out = output(public_keys_out, num_keys * KEY_SIZE)?;
+        for (out, secret_key) in public_keys_out.chunks_exact_mut(KEY_SIZE).zip(&secret_keys) {
+            out.copy_from_slice(secret_key.public_key().as_bytes());
+        }
+        Ok(())
+    })())
+}
+
+/// Computes the key images of a signer with `num_keys` private keys, writing them
+/// to `key_images_out`. The first key image is the one signatures are linked by.
+///
+/// # Safety
+///
+/// `private_keys` must be valid for reads of `num_keys * 32` bytes, and `key_images_out`
+/// must be valid for writes of `num_keys * 32` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn clsag_key_images(
+    private_keys: *const u8,
+    num_keys: usize,
+    key_images_out: *mut u8,
+) -> ClsagResult {
+    status((|| {
+        let secret_keys = decode_secret_keys(input(private_keys, num_keys * KEY_SIZE)?)?;
+        let signer =
+            Member::signer_from_secret_keys(&secret_keys).map_err(|_| ClsagResult::InvalidKey)?;
+        let key_images = signer
+            .compute_key_images()
+            .map_err(|_| ClsagResult::InvalidKey)?;
+
+        let key_images_out = output(key_images_out, num_keys * KEY_SIZE)?;
+        for (out, key_image) in key_images_out.chunks_exact_mut(KEY_SIZE).zip(&key_images) {
+            out.copy_from_slice(key_image.as_bytes());
+        }
+        Ok(())
+    })())
+}
+
+/// Writes the linking key image of an encoded signature, which is its first key image,
+/// to `key_image_out`.
+///
+/// # Safety
+///
+/// `signature` must be valid for reads of `signature_len` bytes, and `key_image_out`
+/// must be valid for writes of 32 bytes.
+#[no_mangle]
+pub unsafe extern "C" fn clsag_signature_key_image(
+    signature: *const u8,
+    signature_len: usize,
+    key_image_out: *mut u8,
+) -> ClsagResult {
+    status((|| {
+        let signature = Signature::from_bytes(input(signature, signature_len)?)
+            .map_err(|_| ClsagResult::InvalidSignature)?;
+        let key_image = signature.key_images()[0];
+
+        output(key_image_out, KEY_SIZE)?.copy_from_slice(key_image.as_bytes());
+        Ok(())
+    })())
+}
+
+/// Signs the message with a signer holding `num_keys` private keys, hidden in a ring with
+/// `num_decoys` decoys who each have `num_keys` public keys. The encoded signature is written
+/// to `signature_out`, and the public keys of the ring in the shuffled order it was signed in
+/// are written to `ring_out`, which is the ring the signature verifies against.
+///
+/// # Safety
+///
+/// `private_keys` must be valid for reads of `num_keys * 32` bytes, `decoy_keys` for reads of
+/// `num_decoys * num_keys * 32` bytes and `msg` for reads of `msg_len` bytes.
+/// `signature_out` must be valid for writes of `signature_out_len` bytes, which must be at
+/// least `clsag_signature_size(num_decoys + 1, num_keys)`, and `ring_out` must be valid for
+/// writes of `(num_decoys + 1) * num_keys * 32` bytes.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn clsag_sign(
+    private_keys: *const u8,
+    num_keys: usize,
+    decoy_keys: *const u8,
+    num_decoys: usize,
+    msg: *const u8,
+    msg_len: usize,
+    signature_out: *mut u8,
+    signature_out_len: usize,
+    ring_out: *mut u8,
+) -> ClsagResult {
+    status((|| {
+        if num_keys == 0 {
+            return Err(ClsagResult::InvalidKey);
+        }
+        let secret_keys = decode_secret_keys(input(private_keys, num_keys * KEY_SIZE)?)?;
+        let decoy_keys = split_keys(input(decoy_keys, num_decoys * num_keys * KEY_SIZE)?);
+        let msg = input(msg, msg_len)?;
+
+        let ring_size = num_decoys + 1;
+        if signature_out_len < Signature::serialized_size(ring_size, num_keys) {
+            return Err(ClsagResult::BufferTooSmall);
+        }
+
+        let mut clsag = Clsag::new();
+        for member_keys in decoy_keys.chunks_exact(num_keys) {
+            let decoy =
+                Member::from_compressed_bytes(member_keys).map_err(|_| ClsagResult::InvalidKey)?;
+            clsag
+                .add_member(decoy)
+                .map_err(|_| ClsagResult::SignFailed)?;
+        }
+        let signer =
+            Member::signer_from_secret_keys(&secret_keys).map_err(|_| ClsagResult::InvalidKey)?;
+        clsag
+            .add_member(signer)
+            .map_err(|_| ClsagResult::SignFailed)?;
+
+        let (signature, public_keys) = clsag.sign(msg).map_err(|_| ClsagResult::SignFailed)?;
+        let signature = signature.to_bytes();
+        output(signature_out, signature.len())?.copy_from_slice(&signature);
+
+        let ring_out = output(ring_out, ring_size * num_keys * KEY_SIZE)?;
+        for (out, key) in ring_out
+            .chunks_exact_mut(KEY_SIZE)
+            .zip(public_keys.iter().flatten())
+        {
+            out.copy_from_slice(key.as_bytes());
+        }
+        Ok(())
+    })())
+}
+
+/// Verifies the encoded signature on the message over a ring of `num_members` members,
+/// whose public keys are given in ring order. Each member has as many keys as the signature
+/// has key images. Returns `Ok` if the signature verifies, and `InvalidSignature` if not.
+///
+/// # Safety
+///
+/// `signature` must be valid for reads of `signature_len` bytes, `public_keys` for reads of
+/// `num_members * num_keys * 32` bytes where `num_keys` is the number of key images of the
+/// signature, and `msg` for reads of `msg_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn clsag_verify(
+    signature: *const u8,
+    signature_len: usize,
+    public_keys: *const u8,
+    num_members: usize,
+    msg: *const u8,
+    msg_len: usize,
+) -> ClsagResult {
+    status((|| {
+        let signature = Signature::from_bytes(input(signature, signature_len)?)
+            .map_err(|_| ClsagResult::InvalidSignature)?;
+        let num_keys = signature.num_layers();
+        let keys = split_keys(input(public_keys, num_members * num_keys * KEY_SIZE)?);
+        let msg = input(msg, msg_len)?;
+
+        // A key which is not a valid point cannot be part of a ring the signature verifies over
+        let ring = keys
+            .chunks_exact(num_keys)
+            .map(|member_keys| member_keys.iter().map(PublicKey::from_bytes).collect())
+            .collect::<Result<Vec<Vec<PublicKey>>, _>>()
+            .map_err(|_| ClsagResult::InvalidSignature)?;
+
+        signature
+            .optimised_verify(&ring, msg)
+            .map_err(|_| ClsagResult::InvalidSignature)
+    })())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::ptr;
+
+    const NUM_DECOYS: usize = 10;
+
+    // Generates the keys of a member, returning the private and public keys
+    fn keygen(num_keys: usize) -> (Vec<u8>, Vec<u8>) {
+        let mut private_keys = vec![0u8; num_keys * KEY_SIZE];
+        let mut public_keys = vec![0u8; num_keys * KEY_SIZE];
+        let result = unsafe {
+            clsag_keygen(
+                num_keys,
+                private_keys.as_mut_ptr(),
+                public_keys.as_mut_ptr(),
+            )
+        };
+        assert_eq!(ClsagResult::Ok, result);
+
+        let mut derived = vec![0u8; num_keys * KEY_SIZE];
+        let result =
+            unsafe { clsag_public_keys(private_keys.as_ptr(), num_keys, derived.as_mut_ptr()) };
+        assert_eq!(ClsagResult::Ok, result);
+        assert_eq!(public_keys, derived);
+        (private_keys, public_keys)
+    }
+
+    #[test]
+    fn test_ffi_sign_and_verify() {
+        let num_keys = 2;
+        let msg = b"hello world";
+        let (private_keys, _) = keygen(num_keys);
+        let decoy_keys: Vec<u8> = (0..NUM_DECOYS).flat_map(|_| keygen(num_keys).1).collect();
+
+        let mut signature = vec![0u8; clsag_signature_size(NUM_DECOYS + 1, num_keys)];
+        let mut ring = vec![0u8; (NUM_DECOYS + 1) * num_keys * KEY_SIZE];
+        let result = unsafe {
+            clsag_sign(
+                private_keys.as_ptr(),
+                num_keys,
+                decoy_keys.as_ptr(),
+                NUM_DECOYS,
+                msg.as_ptr(),
+                msg.len(),
+                signature.as_mut_ptr(),
+                signature.len(),
+                ring.as_mut_ptr(),
+            )
+        };
+        assert_eq!(ClsagResult::Ok, result);
+
+        let verify = |msg: &[u8]| unsafe {
+            clsag_verify(
+                signature.as_ptr(),
+                signature.len(),
+                ring.as_ptr(),
+                NUM_DECOYS + 1,
+                msg.as_ptr(),
+                msg.len(),
+            )
+        };
+        assert_eq!(ClsagResult::Ok, verify(msg));
+        assert_eq!(ClsagResult::InvalidSignature, verify(b"another message"));
+
+        // The linking key image of the signature is the first key image of the signer
+        let mut key_images = vec![0u8; num_keys * KEY_SIZE];
+        let mut key_image = [0u8; KEY_SIZE];
+        unsafe {
+            let result = clsag_key_images(private_keys.as_ptr(), num_keys, key_images.as_mut_ptr());
+            assert_eq!(ClsagResult::Ok, result);
+            let result = clsag_signature_key_image(
+                signature.as_ptr(),
+                signature.len(),
+                key_image.as_mut_ptr(),
+            );
+            assert_eq!(ClsagResult::Ok, result);
+        }
+        assert_eq!(&key_images[..KEY_SIZE], &key_image[..]);
+    }
+
+    #[test]
+    fn test_ffi_errors() {
+        let num_keys = 1;
+        let msg = b"hello world";
+        let (private_keys, _) = keygen(num_keys);
+        let decoy_keys: Vec<u8> = (0..NUM_DECOYS).flat_map(|_| keygen(num_keys).1).collect();
+        let mut ring = vec![0u8; (NUM_DECOYS + 1) * KEY_SIZE];
+
+        let mut sign = |signature: &mut [u8], num_decoys: usize| unsafe {
+            clsag_sign(
+                private_keys.as_ptr(),
+                num_keys,
+                decoy_keys.as_ptr(),
+                num_decoys,
+                msg.as_ptr(),
+                msg.len(),
+                signature.as_mut_ptr(),
+                signature.len(),
+                ring.as_mut_ptr(),
+            )
+        };
+        let mut short = vec![0u8; clsag_signature_size(NUM_DECOYS + 1, num_keys) - 1];
+        assert_eq!(ClsagResult::BufferTooSmall, sign(&mut short, NUM_DECOYS));
+        // The default ring policy rejects rings which are too small to hide the signer
+        let mut signature = vec![0u8; clsag_signature_size(3, num_keys)];
+        assert_eq!(ClsagResult::SignFailed, sign(&mut signature, 2));
+
+        let result = unsafe { clsag_keygen(1, ptr::null_mut(), ptr::null_mut()) };
+        assert_eq!(ClsagResult::NullPointer, result);
+
+        let non_canonical = [0xffu8; KEY_SIZE];
+        let mut key_images = [0u8; KEY_SIZE];
+        let result =
+            unsafe { clsag_key_images(non_canonical.as_ptr(), 1, key_images.as_mut_ptr()) };
+        assert_eq!(ClsagResult::InvalidKey, result);
+    }
+}
@@ -0,0 +1,38 @@
+// Feeds arbitrary bytes to the public key decoders, both as raw 32 byte keys
+// and as a serialized `PublicSet`
+#![no_main]
+use clsag::keys::{PublicKey, PublicSet};
+use clsag::member::Member;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let keys: Vec<[u8; 32]> = data
+        .chunks_exact(32)
+        .map(|chunk| {
+            let mut key = [0u8; 32];
+            key.copy_from_slice(chunk);
+            key
+        })
+        .collect();
+
+    let decoded: Vec<Option<PublicKey>> = keys
+        .iter()
+        .map(|key| PublicKey::from_bytes(key).ok())
+        .collect();
+    for (key, public_key) in keys.iter().zip(decoded.iter()) {
+        if let Some(public_key) = public_key {
+            assert_eq!(key, public_key.as_bytes());
+        }
+    }
+
+    // A member can only be made from keys which all decode
+    let all_decode = !keys.is_empty() && decoded.iter().all(Option::is_some);
+    assert_eq!(all_decode, Member::from_compressed_bytes(&keys).is_ok());
+
+    if let Ok(public_set) = serde_json::from_slice::<PublicSet>(data) {
+        let json = serde_json::to_vec(&public_set).unwrap();
+        let decoded: PublicSet = serde_json::from_slice(&json).unwrap();
+        assert_eq!(public_set.points(), decoded.points());
+        assert_eq!(public_set.keys(), decoded.keys());
+    }
+});
@@ -0,0 +1,18 @@
+// Feeds arbitrary bytes to `Signature::from_bytes`.
+// Any signature which decodes must encode back to the same bytes,
+// as every element of the encoding is canonical
+#![no_main]
+use clsag::signature::Signature;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(signature) = Signature::from_bytes(data) {
+        assert_eq!(data, signature.to_bytes().as_slice());
+    }
+
+    if let Ok(signature) = serde_json::from_slice::<Signature>(data) {
+        let json = serde_json::to_vec(&signature).unwrap();
+        let decoded: Signature = serde_json::from_slice(&json).unwrap();
+        assert_eq!(signature.to_bytes(), decoded.to_bytes());
+    }
+});
@@ -0,0 +1,44 @@
+// Signs over a ring built from the input and checks that no perturbation of the signature
+// or the ring verifies, with `Signature::assert_non_malleable_under`.
+// The input is the ring size, the number of keys, the position of the signer,
+// a 32 byte seed the keys and nonces are drawn from, and the message
+#![no_main]
+use clsag::clsag::Clsag;
+use clsag::keys::SecretKey;
+use clsag::member::Member;
+use clsag::policy::RingPolicy;
+use libfuzzer_sys::fuzz_target;
+use rand::SeedableRng;
+use rand_chacha::ChaChaRng;
+
+fuzz_target!(|data: &[u8]| {
+    if data.len() < 35 {
+        return;
+    }
+    let ring_size = 2 + data[0] as usize % 7;
+    let num_keys = 1 + data[1] as usize % 3;
+    let signer_index = data[2] as usize % ring_size;
+    let mut seed = [0u8; 32];
+    seed.copy_from_slice(&data[3..35]);
+    let msg = &data[35..];
+
+    let mut rng = ChaChaRng::from_seed(seed);
+    let mut clsag = Clsag::new();
+    clsag.set_ring_policy(RingPolicy::permissive());
+    for index in 0..ring_size {
+        let secret_keys: Vec<SecretKey> =
+            (0..num_keys).map(|_| SecretKey::random(&mut rng)).collect();
+        let member = if index == signer_index {
+            Member::signer_from_secret_keys(&secret_keys).unwrap()
+        } else {
+            let public_keys: Vec<_> = secret_keys.iter().map(|key| key.public_key()).collect();
+            Member::decoy_from_public_keys(&public_keys).unwrap()
+        };
+        clsag.add_member(member).unwrap();
+    }
+    let (signature, ring) = clsag.sign_with_rng(&mut rng, msg).unwrap();
+
+    signature.assert_non_malleable_under(&ring, msg, |sig, keys, msg| sig.verify(keys, msg));
+    signature
+        .assert_non_malleable_under(&ring, msg, |sig, keys, msg| sig.optimised_verify(keys, msg));
+});
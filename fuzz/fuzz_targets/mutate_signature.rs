@@ -0,0 +1,53 @@
+// Mutates a valid signature and checks that the mutated signature never verifies.
+// The input is a list of (position, xor) byte pairs which are applied to the encoded signature,
+// any input which leaves the signature unchanged is skipped
+#![no_main]
+use clsag::clsag::{Clsag, PublicKeys};
+use clsag::keys::SecretKey;
+use clsag::member::Member;
+use clsag::signature::Signature;
+use libfuzzer_sys::fuzz_target;
+use rand::SeedableRng;
+use rand_chacha::ChaChaRng;
+use std::sync::OnceLock;
+
+const MSG: &[u8] = b"fuzz";
+
+// A signature over a fixed ring of 11 members with 2 keys each
+fn valid_signature() -> &'static (Vec<u8>, PublicKeys) {
+    static SIGNATURE: OnceLock<(Vec<u8>, PublicKeys)> = OnceLock::new();
+    SIGNATURE.get_or_init(|| {
+        let mut rng = ChaChaRng::from_seed([7u8; 32]);
+        let mut clsag = Clsag::new();
+        for index in 0..11 {
+            let secret_keys: Vec<SecretKey> = (0..2).map(|_| SecretKey::random(&mut rng)).collect();
+            let member = if index == 5 {
+                Member::signer_from_secret_keys(&secret_keys).unwrap()
+            } else {
+                let public_keys: Vec<_> = secret_keys.iter().map(|key| key.public_key()).collect();
+                Member::decoy_from_public_keys(&public_keys).unwrap()
+            };
+            clsag.add_member(member).unwrap();
+        }
+        let (signature, ring) = clsag.sign_with_rng(&mut rng, MSG).unwrap();
+        (signature.to_bytes(), ring)
+    })
+}
+
+fuzz_target!(|data: &[u8]| {
+    let (bytes, ring) = valid_signature();
+
+    let mut mutated = bytes.clone();
+    for pair in data.chunks_exact(2) {
+        let position = pair[0] as usize * mutated.len() / 256;
+        mutated[position] ^= pair[1];
+    }
+    if mutated == *bytes {
+        return;
+    }
+
+    if let Ok(signature) = Signature::from_bytes(&mutated) {
+        assert!(signature.verify(ring, MSG).is_err());
+        assert!(signature.optimised_verify(ring, MSG).is_err());
+    }
+});
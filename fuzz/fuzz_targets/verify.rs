@@ -0,0 +1,54 @@
+// Verifies an arbitrary signature over an arbitrary ring and message.
+// The first byte gives the number of keys per member, the next two bytes give the length of the
+// encoded signature, which is followed by the ring and then the message.
+// Verification must never panic, whatever the input
+#![no_main]
+use clsag::clsag::PublicKeys;
+use clsag::keys::PublicKey;
+use clsag::ring::PreparedRing;
+use clsag::signature::Signature;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if data.len() < 3 {
+        return;
+    }
+    let num_keys = (data[0] as usize % 4) + 1;
+    let sig_len = u16::from_le_bytes([data[1], data[2]]) as usize;
+    let data = &data[3..];
+    if data.len() < sig_len {
+        return;
+    }
+    let (sig_bytes, rest) = data.split_at(sig_len);
+    let signature = match Signature::from_bytes(sig_bytes) {
+        Ok(signature) => signature,
+        Err(_) => return,
+    };
+
+    let ring_len = (rest.len() / (32 * num_keys)).min(64);
+    let (ring_bytes, msg) = rest.split_at(ring_len * 32 * num_keys);
+    let ring: Option<PublicKeys> = ring_bytes
+        .chunks_exact(32 * num_keys)
+        .map(|member| {
+            member
+                .chunks_exact(32)
+                .map(|chunk| {
+                    let mut key = [0u8; 32];
+                    key.copy_from_slice(chunk);
+                    PublicKey::from_bytes(&key).ok()
+                })
+                .collect()
+        })
+        .collect();
+    let ring = match ring {
+        Some(ring) => ring,
+        None => return,
+    };
+
+    let reference = signature.verify(&ring, msg).is_ok();
+    let optimised = signature.optimised_verify(&ring, msg).is_ok();
+    assert_eq!(reference, optimised);
+    if let Ok(prepared) = PreparedRing::new(&ring) {
+        assert_eq!(optimised, signature.verify_prepared(&prepared, msg).is_ok());
+    }
+});
@@ -0,0 +1,296 @@
+// Adaptor signatures, for atomic swaps between a chain using clsag and another chain.
+//
+// The counterparty of the signer holds an adaptor secret `t`, and gives the signer the
+// adaptor point `T = t * G` along with `t * H(P)`, where `P` is the first key of the signer.
+// `Clsag::sign_adaptor` closes the ring with the signers nonce commitment shifted by both
+// points, giving a pre-signature which is not a valid signature, but which the counterparty
+// can check with `PreSignature::verify`. Adding `t` to the signers response completes the
+// pre-signature into an ordinary signature, and anyone holding both the pre-signature and
+// the completed signature recovers `t` with `extract_secret`.
+//
+// The pre-signature holds the position of the signer, so the counterparty learns which member
+// signed, while the completed signature is as anonymous as any other signature. The adaptor
+// point is computed over the basepoint and the default hash construction, so the ring must not
+// be signed over another generator or hash construction.
+use crate::clsag::calc_aggregation_coefficients;
+use crate::constants::BASEPOINT;
+use crate::error::ClsagError;
+use crate::hash::hash_pubkey;
+use crate::keys::{KeyImage, PublicKey, SecretKey};
+use crate::member::{round_challenge, round_transcript};
+use crate::ring::PreparedRing;
+use crate::signature::Signature;
+use crate::transcript::default_transcript;
+use curve25519_dalek::ristretto::RistrettoPoint;
+use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::traits::VartimeMultiscalarMul;
+use std::iter;
+
+// The adaptor point `T = t * G`, along with `t * H(P)` for the first key `P` of the signer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AdaptorPoint {
+    signer_key: PublicKey,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::point"))]
+    pub(crate) point: RistrettoPoint,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::point"))]
+    pub(crate) hashed_point: RistrettoPoint,
+}
+
+// A signature which only verifies once it is completed with the adaptor secret
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PreSignature {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::scalar"))]
+    challenge: Scalar,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::scalars"))]
+    responses: Vec<Scalar>,
+    key_images: Vec<KeyImage>,
+    signer_index: usize,
+    adaptor: AdaptorPoint,
+}
+
+impl AdaptorPoint {
+    // Creates the adaptor point of the adaptor secret, for a signer whose first key is `signer_key`
+    pub fn new(adaptor_secret: &SecretKey, signer_key: &PublicKey) -> Self {
+        AdaptorPoint {
+            signer_key: *signer_key,
+            point: adaptor_secret.0 * BASEPOINT,
+            hashed_point: adaptor_secret.0 * hash_pubkey(signer_key.as_bytes()),
+        }
+    }
+    // Returns the adaptor point `T = t * G`
+    pub fn point(&self) -> &RistrettoPoint {
+        &self.point
+    }
+    // Returns the first key of the signer the adaptor point was made for
+    pub fn signer_key(&self) -> &PublicKey {
+        &self.signer_key
+    }
+}
+
+impl PreSignature {
+    pub(crate) fn new(signature: Signature, signer_index: usize, adaptor: AdaptorPoint) -> Self {
+        PreSignature {
+            challenge: signature.challenge,
+            responses: signature.responses,
+            key_images: signature.key_images,
+            signer_index,
+            adaptor,
+        }
+    }
+    // Returns the key images of the signer, which are the key images of the completed signature
+    pub fn key_images(&self) -> &[KeyImage] {
+        &self.key_images
+    }
+    // Returns the position of the signer in the ring
+    pub fn signer_index(&self) -> usize {
+        self.signer_index
+    }
+    // Returns the adaptor point the pre-signature was made with
+    pub fn adaptor(&self) -> &AdaptorPoint {
+        &self.adaptor
+    }
+    // Verifies that the pre-signature on the message over the ring is completed
+    // into a valid signature by the secret of its adaptor point.
+    // Returns an error if the ring is not the size of the ring the pre-signature was made over,
+    // if the adaptor point was not made for the member at the signers position,
+    // if a key image is not valid, or if the pre-signature does not verify
+    pub fn verify(&self, public_keys: &[Vec<PublicKey>], msg: &[u8]) -> Result<(), ClsagError> {
+        let ring = PreparedRing::new(public_keys)?;
+        if ring.len() != self.responses.len() || self.signer_index >= ring.len() {
+            return Err(ClsagError::RingSizeMismatch);
+        }
+        if ring.num_layers() != self.key_images.len() {
+            return Err(ClsagError::KeyCountMismatch);
+        }
+        // The hash of the signers key in the adaptor point is only
+        // known to be correct for the key it was made for
+        if public_keys[self.signer_index][0] != self.adaptor.signer_key {
+            return Err(ClsagError::AdaptorMismatch);
+        }
+        let key_images = self
+            .key_images
+            .iter()
+            .map(KeyImage::check)
+            .collect::<Result<Vec<RistrettoPoint>, ClsagError>>()?;
+
+        let transcript = default_transcript();
+        let agg_coeffs =
+            calc_aggregation_coefficients(&transcript, ring.pubkey_matrix(), &self.key_images, msg);
+        let round = round_transcript(&transcript, ring.pubkey_matrix(), &self.key_images, msg);
+
+        let mut challenge = self.challenge;
+        for (index, ((response, hashed_pubkey), points)) in self
+            .responses
+            .iter()
+            .zip(ring.hashed_pubkeys())
+            .zip(ring.points())
+            .enumerate()
+        {
+            let scalars: Vec<Scalar> = iter::once(*response)
+                .chain(agg_coeffs.iter().map(|mu| mu * challenge))
+                .collect();
+            let mut l = RistrettoPoint::vartime_multiscalar_mul(
+                &scalars,
+                iter::once(&BASEPOINT).chain(points.iter()),
+            );
+            let mut r = RistrettoPoint::vartime_multiscalar_mul(
+                &scalars,
+                iter::once(hashed_pubkey).chain(key_images.iter()),
+            );
+
+            // The signers response is missing the adaptor secret,
+            // which is made up for by adding the adaptor points
+            if index == self.signer_index {
+                l += self.adaptor.point;
+                r += self.adaptor.hashed_point;
+            }
+
+            challenge = round_challenge(&round, &l, &r);
+        }
+
+        if challenge != self.challenge {
+            return Err(ClsagError::ChallengeMismatch);
+        }
+        Ok(())
+    }
+    // Completes the pre-signature into a signature with the adaptor secret.
+    // Returns an error if the secret is not the secret of the adaptor point
+    pub fn adapt(&self, adaptor_secret: &SecretKey) -> Result<Signature, ClsagError> {
+        if self.signer_index >= self.responses.len() {
+            return Err(ClsagError::RingSizeMismatch);
+        }
+        if adaptor_secret.0 * BASEPOINT != self.adaptor.point {
+            return Err(ClsagError::AdaptorMismatch);
+        }
+
+        let mut responses = self.responses.clone();
+        responses[self.signer_index] += adaptor_secret.0;
+
+        Ok(Signature {
+            challenge: self.challenge,
+            responses,
+            key_images: self.key_images.clone(),
+        })
+    }
+}
+
+// Recovers the adaptor secret from a pre-signature and the signature it was completed into.
+// Returns an error if the signature was not completed from the pre-signature
+pub fn extract_secret(
+    pre_signature: &PreSignature,
+    signature: &Signature,
+) -> Result<SecretKey, ClsagError> {
+    let signer_index = pre_signature.signer_index;
+    let same_responses = pre_signature.responses.len() == signature.responses.len()
+        && pre_signature
+            .responses
+            .iter()
+            .zip(&signature.responses)
+            .enumerate()
+            .all(|(index, (a, b))| index == signer_index || a == b);
+    if !same_responses
+        || pre_signature.challenge != signature.challenge
+        || pre_signature.key_images != signature.key_images
+        || signer_index >= signature.responses.len()
+    {
+        return Err(ClsagError::AdaptorMismatch);
+    }
+
+    let adaptor_secret =
+        SecretKey(signature.responses[signer_index] - pre_signature.responses[signer_index]);
+    if adaptor_secret.0 * BASEPOINT != pre_signature.adaptor.point {
+        return Err(ClsagError::AdaptorMismatch);
+    }
+    Ok(adaptor_secret)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::member::Member;
+    use crate::test_utils::*;
+
+    #[test]
+    fn test_adaptor_swap() {
+        let msg = b"hello world";
+        let mut rng = rand::thread_rng();
+        let signer_key = SecretKey::random(&mut rng);
+
+        let mut clsag = generate_clsag_with(6, 1);
+        let signer = Member::signer_from_secret_keys(std::slice::from_ref(&signer_key)).unwrap();
+        clsag.add_member(signer).unwrap();
+
+        // The counterparty creates the adaptor point for the signers key
+        let adaptor_secret = SecretKey::random(&mut rng);
+        let adaptor = AdaptorPoint::new(&adaptor_secret, &signer_key.public_key());
+        assert_eq!(adaptor_secret.0 * BASEPOINT, *adaptor.point());
+
+        let (pre_signature, public_keys) = clsag.sign_adaptor(msg, &adaptor).unwrap();
+        assert!(pre_signature.verify(&public_keys, msg).is_ok());
+        assert!(pre_signature
+            .verify(&public_keys, b"another message")
+            .is_err());
+        assert_eq!(
+            vec![signer_key.public_key()],
+            public_keys[pre_signature.signer_index()]
+        );
+
+        // The pre-signature is not a valid signature until it is completed
+        let incomplete = Signature {
+            challenge: pre_signature.challenge,
+            responses: pre_signature.responses.clone(),
+            key_images: pre_signature.key_images.clone(),
+        };
+        assert!(incomplete.verify(&public_keys, msg).is_err());
+        let signature = pre_signature.adapt(&adaptor_secret).unwrap();
+        assert!(signature.verify(&public_keys, msg).is_ok());
+        assert_eq!(pre_signature.key_images(), signature.key_images());
+
+        // Publishing the signature reveals the adaptor secret
+        let extracted = extract_secret(&pre_signature, &signature).unwrap();
+        assert_eq!(adaptor_secret.to_bytes(), extracted.to_bytes());
+    }
+
+    #[test]
+    fn test_adaptor_rejects_mismatches() {
+        let msg = b"hello world";
+        let mut rng = rand::thread_rng();
+        let signer_key = SecretKey::random(&mut rng);
+
+        let mut clsag = generate_clsag_with(4, 1);
+        let signer = Member::signer_from_secret_keys(std::slice::from_ref(&signer_key)).unwrap();
+        clsag.add_member(signer).unwrap();
+        let adaptor_secret = SecretKey::random(&mut rng);
+        let adaptor = AdaptorPoint::new(&adaptor_secret, &signer_key.public_key());
+        let (pre_signature, public_keys) = clsag.sign_adaptor(msg, &adaptor).unwrap();
+
+        let other_secret = SecretKey::random(&mut rng);
+        match pre_signature.adapt(&other_secret) {
+            Err(ClsagError::AdaptorMismatch) => {}
+            _ => panic!("expected an adaptor mismatch error"),
+        }
+
+        // An adaptor point made for another key cannot be signed with,
+        // and a pre-signature which claims another signer does not verify
+        let wrong_key = AdaptorPoint::new(&adaptor_secret, &generate_rand_public_keys(1)[0]);
+        match clsag.sign_adaptor(msg, &wrong_key) {
+            Err(ClsagError::AdaptorMismatch) => {}
+            _ => panic!("expected an adaptor mismatch error"),
+        }
+        let mut moved = pre_signature.clone();
+        moved.signer_index = (moved.signer_index + 1) % public_keys.len();
+        match moved.verify(&public_keys, msg) {
+            Err(ClsagError::AdaptorMismatch) => {}
+            _ => panic!("expected an adaptor mismatch error"),
+        }
+
+        // A signature which was not completed from the pre-signature gives no secret
+        let (other_signature, _) = clsag.sign(msg).unwrap();
+        match extract_secret(&pre_signature, &other_signature) {
+            Err(ClsagError::AdaptorMismatch) => {}
+            _ => panic!("expected an adaptor mismatch error"),
+        }
+    }
+}
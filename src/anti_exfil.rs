@@ -0,0 +1,295 @@
+// An anti-exfiltration protocol for signing on a hardware device, which stops a device with
+// malicious firmware from leaking its keys through the values it chooses for a signature.
+//
+// A device which chooses its own nonce and decoy responses can pick them so that the signature
+// encodes bits of its keys. In this protocol the host contributes the randomness instead:
+//
+//   1. The host calls `HostSession::new`, and sends the commitment to its nonce to the device.
+//   2. The device calls `DeviceSession::new`, which fixes the order of the ring and its own nonce
+//      before it has seen the host nonce, and sends back the commitment to both.
+//   3. The host calls `HostSession::reveal` with the commitment of the device,
+//      and sends the host nonce to the device.
+//   4. The device checks the host nonce against its commitment, and signs with
+//      `DeviceSession::sign`. Its nonce is tweaked by the host nonce as in the contract module,
+//      and the responses of the decoys are derived from the host nonce.
+//   5. The host checks the signature with `HostVerifier::verify`, which proves that both
+//      the nonce and the decoy responses depend on the host nonce.
+//
+// The device learns nothing about the host nonce until it has committed to its own, so every
+// value in the signature is either fixed before the host nonce is known or derived from it.
+// A device can still refuse to sign, which leaks at most one bit per session.
+// The commitment of the device holds the position of the signer, so the host learns which
+// member signed, while the signature is as anonymous as any other signature to everyone else.
+use crate::clsag::{Clsag, PublicKeys};
+use crate::contract::{contract_tweak, CommitmentOpening};
+use crate::error::ClsagError;
+use crate::keys::{zeroize_scalar, PublicKey};
+use crate::signature::Signature;
+use crate::transcript::SigningTranscript;
+use curve25519_dalek::scalar::Scalar;
+use merlin::Transcript;
+use rand::{CryptoRng, RngCore};
+
+// The random nonce the host contributes to a signature
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HostNonce([u8; 32]);
+
+// The commitment to the host nonce, which is sent to the device before the nonce itself
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HostCommitment([u8; 32]);
+
+// The commitment of the device to the ring and its nonce, made before it sees the host nonce
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DeviceCommitment {
+    ring: PublicKeys,
+    opening: CommitmentOpening,
+}
+
+// The state the host keeps until it receives the commitment of the device
+pub struct HostSession {
+    host_nonce: HostNonce,
+}
+
+// The state the host keeps to check the signature the device returns
+#[derive(Debug, Clone)]
+pub struct HostVerifier {
+    host_nonce: HostNonce,
+    device_commitment: DeviceCommitment,
+}
+
+// The state the device keeps between sending its commitment and receiving the host nonce.
+// The session is consumed by signing, so that its nonce is never used for two signatures
+pub struct DeviceSession {
+    ring: Clsag,
+    msg: Vec<u8>,
+    host_commitment: HostCommitment,
+    nonce: Scalar,
+    opening: CommitmentOpening,
+}
+
+impl HostNonce {
+    // Returns the commitment to the host nonce
+    pub fn commitment(&self) -> HostCommitment {
+        let mut transcript = Transcript::new(b"clsag_anti_exfil_host");
+        transcript.append_message(b"host_nonce", &self.0);
+
+        let mut commitment = [0u8; 32];
+        transcript.challenge_bytes(b"commitment", &mut commitment);
+        HostCommitment(commitment)
+    }
+}
+
+impl DeviceCommitment {
+    // Returns the ring the device will sign over, in the order it will be signed in
+    pub fn ring(&self) -> &[Vec<PublicKey>] {
+        &self.ring
+    }
+}
+
+impl HostSession {
+    // Starts a session with a fresh host nonce, returning the commitment to send to the device
+    pub fn new<R: RngCore + CryptoRng>(rng: &mut R) -> (HostSession, HostCommitment) {
+        let mut bytes = [0u8; 32];
+        rng.fill_bytes(&mut bytes);
+        let host_nonce = HostNonce(bytes);
+
+        (HostSession { host_nonce }, host_nonce.commitment())
+    }
+    // Takes the commitment of the device, returning the host nonce to send to the device
+    // and the verifier for the signature it returns
+    pub fn reveal(self, device_commitment: DeviceCommitment) -> (HostVerifier, HostNonce) {
+        let verifier = HostVerifier {
+            host_nonce: self.host_nonce,
+            device_commitment,
+        };
+        (verifier, self.host_nonce)
+    }
+}
+
+impl HostVerifier {
+    // Returns the ring the signature is made over, in the order it is signed in
+    pub fn public_keys(&self) -> &[Vec<PublicKey>] {
+        &self.device_commitment.ring
+    }
+    // Verifies the signature on the message over the ring the device committed to, and checks
+    // that the signers nonce and the decoys responses were derived from the host nonce.
+    // Returns an error if the signature does not verify, or if it does not use the host nonce
+    pub fn verify(&self, signature: &Signature, msg: &[u8]) -> Result<(), ClsagError> {
+        let opening = &self.device_commitment.opening;
+        signature
+            .extract_commitment_proof(&self.device_commitment.ring, msg, opening)?
+            .verify(&self.host_nonce.0)?;
+
+        let responses_derived = signature
+            .responses
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| *index != opening.signer_index)
+            .all(|(index, response)| *response == decoy_response(&self.host_nonce, opening, index));
+        if !responses_derived {
+            return Err(ClsagError::CommitmentProofMismatch);
+        }
+        Ok(())
+    }
+}
+
+impl DeviceSession {
+    // Starts a session on the device for signing the message over the ring, fixing the order
+    // of the ring and the signers nonce. Returns the commitment to send to the host, or an
+    // error if the ring cannot be signed
+    pub fn new<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        clsag: &Clsag,
+        msg: &[u8],
+        host_commitment: HostCommitment,
+    ) -> Result<(DeviceSession, DeviceCommitment), ClsagError> {
+        clsag.check_format()?;
+        clsag.check_policy()?;
+
+        let ring = clsag.shuffled(rng);
+        let nonce = Scalar::random(rng);
+        let opening = CommitmentOpening {
+            signer_index: ring.find_signer()?,
+            nonce_point: nonce * clsag.generator,
+            generator: clsag.generator,
+        };
+        let device_commitment = DeviceCommitment {
+            ring: ring.public_keys().to_vec(),
+            opening,
+        };
+
+        let session = DeviceSession {
+            ring,
+            msg: msg.to_vec(),
+            host_commitment,
+            nonce,
+            opening,
+        };
+        Ok((session, device_commitment))
+    }
+    // Signs the message with the host nonce, once it has been checked against its commitment.
+    // Returns an error if the host nonce does not match the commitment the session started with
+    pub fn sign(self, host_nonce: &HostNonce) -> Result<Signature, ClsagError> {
+        if host_nonce.commitment() != self.host_commitment {
+            return Err(ClsagError::HostNonceMismatch);
+        }
+
+        self.ring.traced(|| {
+            let signer_index = self.opening.signer_index;
+            let mut tweaked_nonce =
+                self.nonce + contract_tweak(&self.opening.nonce_point, &host_nonce.0);
+            let signature =
+                self.ring
+                    .sign_in_order_with(&self.msg, |index| match index == signer_index {
+                        true => tweaked_nonce,
+                        false => decoy_response(host_nonce, &self.opening, index),
+                    });
+            zeroize_scalar(&mut tweaked_nonce);
+
+            signature
+        })
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for DeviceSession {
+    fn drop(&mut self) {
+        zeroize_scalar(&mut self.nonce);
+    }
+}
+
+// Returns the response of the decoy at the index, which is derived from the host nonce
+// and the commitment of the device, so that the device cannot choose it
+fn decoy_response(host_nonce: &HostNonce, opening: &CommitmentOpening, index: usize) -> Scalar {
+    let mut transcript = Transcript::new(b"clsag_anti_exfil_response");
+    transcript.append_message(b"host_nonce", &host_nonce.0);
+    transcript.append_point(b"nonce_point", &opening.nonce_point);
+    transcript.append_u64(b"index", index as u64);
+    transcript.challenge_scalar(b"response")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::*;
+
+    // Runs the protocol up to the point where the device signs
+    fn start_sessions(clsag: &Clsag, msg: &[u8]) -> (DeviceSession, HostVerifier, HostNonce) {
+        let mut rng = rand::thread_rng();
+        let (host, host_commitment) = HostSession::new(&mut rng);
+        let (device, device_commitment) =
+            DeviceSession::new(&mut rng, clsag, msg, host_commitment).unwrap();
+        let (verifier, host_nonce) = host.reveal(device_commitment);
+        (device, verifier, host_nonce)
+    }
+
+    #[test]
+    fn test_anti_exfil_sign_over_generator() {
+        use crate::hash::hash_pubkey;
+        use crate::member::Member;
+        use crate::policy::RingPolicy;
+
+        let msg = b"hello world";
+        let generator = hash_pubkey(b"another generator for the keys!!");
+        let mut clsag = Clsag::with_generator(generator);
+        clsag.set_ring_policy(RingPolicy::permissive());
+        for decoy in generate_decoys(6, 1) {
+            clsag.add_member(decoy).unwrap();
+        }
+        clsag
+            .add_member(Member::new_signer(generate_rand_scalars(1)))
+            .unwrap();
+
+        let (device, verifier, host_nonce) = start_sessions(&clsag, msg);
+        let signature = device.sign(&host_nonce).unwrap();
+        assert!(verifier.verify(&signature, msg).is_ok());
+        assert!(signature
+            .verify_with_generator(verifier.public_keys(), msg, &generator)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_anti_exfil_sign() {
+        let msg = b"hello world";
+        let mut clsag = generate_clsag_with(6, 2);
+        clsag.add_member(generate_signer(2)).unwrap();
+
+        let (device, verifier, host_nonce) = start_sessions(&clsag, msg);
+        let signature = device.sign(&host_nonce).unwrap();
+        assert!(verifier.verify(&signature, msg).is_ok());
+        assert!(signature.verify(verifier.public_keys(), msg).is_ok());
+        assert!(verifier.verify(&signature, b"another message").is_err());
+    }
+
+    #[test]
+    fn test_anti_exfil_detects_device_randomness() {
+        let msg = b"hello world";
+        let mut clsag = generate_clsag_with(6, 1);
+        clsag.add_member(generate_signer(1)).unwrap();
+
+        // A signature made without the host nonce is rejected, even over the committed ring
+        let (mut device, verifier, _) = start_sessions(&clsag, msg);
+        device.ring.set_shuffle_members(false);
+        let (signature, public_keys) = device.ring.sign(msg).unwrap();
+        assert_eq!(verifier.public_keys(), &public_keys[..]);
+        assert!(verifier.verify(&signature, msg).is_err());
+
+        // A signature whose decoy responses were chosen by the device is rejected
+        let (device, verifier, host_nonce) = start_sessions(&clsag, msg);
+        let signer_index = device.opening.signer_index;
+        let mut signature = device.sign(&host_nonce).unwrap();
+        let decoy_index = (signer_index + 1) % signature.responses.len();
+        signature.responses[decoy_index] += Scalar::one();
+        assert!(verifier.verify(&signature, msg).is_err());
+
+        // The device refuses a host nonce which does not match its commitment
+        let (device, _, _) = start_sessions(&clsag, msg);
+        match device.sign(&HostNonce([7u8; 32])) {
+            Err(ClsagError::HostNonceMismatch) => {}
+            _ => panic!("expected a host nonce mismatch error"),
+        }
+    }
+}
@@ -0,0 +1,389 @@
+// Experimental signing over a large anonymity set committed to by a Merkle root,
+// behind the experimental feature.
+//
+// An `AnonymitySet` holds every member a signer may be hidden among, such as every output
+// of a chain, and commits to them with a Merkle tree whose root is small enough to publish
+// in a block header. To sign, the signer samples a ring of `ring_size` members from the set
+// uniformly at random, makes a clsag signature over the ring bound to the root, and attaches
+// a `MembershipProof` for each member of the ring. A verifier holding only the root checks
+// that every member of the ring is in the set, without fetching the set.
+//
+// This is not a zero-knowledge membership proof in the style of Lelantus or Triptych. The
+// proofs reveal the positions of the ring members in the set, so the signer is hidden among
+// the members of the ring, not among the whole set, and the size of the signature grows
+// linearly with the ring size. The module shares its key types with the rest of the crate
+// so that it can be replaced by a logarithmic size proof over the same `MerkleRoot`.
+use crate::clsag::{Clsag, PublicKeys};
+use crate::error::ClsagError;
+use crate::keys::{KeyImage, PublicKey, SecretKey};
+use crate::member::Member;
+use crate::signature::Signature;
+use merlin::Transcript;
+use rand::{CryptoRng, RngCore};
+
+// The root of the Merkle tree over an anonymity set, which commits to its size
+pub type MerkleRoot = [u8; 32];
+
+type Node = [u8; 32];
+
+// The members of an anonymity set, along with every level of the Merkle tree over them
+#[derive(Debug, Clone)]
+pub struct AnonymitySet {
+    members: PublicKeys,
+
+    // The hashes of the leaves, followed by each level up to the top node
+    levels: Vec<Vec<Node>>,
+}
+
+// A proof that a member is the leaf at `index` of the tree with a given root
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MembershipProof {
+    index: u64,
+    num_leaves: u64,
+
+    // The sibling of each node on the path from the leaf to the top node,
+    // skipping the levels where the node has no sibling
+    siblings: Vec<Node>,
+}
+
+// A clsag signature over a ring sampled from an anonymity set, along with the proofs that
+// each member of the ring is in the set
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BigRingSignature {
+    signature: Signature,
+    ring: PublicKeys,
+    proofs: Vec<MembershipProof>,
+}
+
+fn hash_leaf(keys: &[PublicKey]) -> Node {
+    let mut transcript = Transcript::new(b"clsag_bigring_leaf");
+    for key in keys {
+        transcript.append_message(b"key", key.as_bytes());
+    }
+    let mut node = [0u8; 32];
+    transcript.challenge_bytes(b"node", &mut node);
+    node
+}
+
+fn hash_node(left: &Node, right: &Node) -> Node {
+    let mut transcript = Transcript::new(b"clsag_bigring_node");
+    transcript.append_message(b"left", left);
+    transcript.append_message(b"right", right);
+    let mut node = [0u8; 32];
+    transcript.challenge_bytes(b"node", &mut node);
+    node
+}
+
+// The root binds the top node to the number of leaves, so that a tree cannot be
+// passed off as a tree of another size with the same top node
+fn hash_root(num_leaves: u64, top: &Node) -> MerkleRoot {
+    let mut transcript = Transcript::new(b"clsag_bigring_root");
+    transcript.append_u64(b"num_leaves", num_leaves);
+    transcript.append_message(b"top", top);
+    let mut root = [0u8; 32];
+    transcript.challenge_bytes(b"root", &mut root);
+    root
+}
+
+// Returns the transcript a signature over a ring sampled from the set with the root is made with
+fn bigring_transcript(root: &MerkleRoot) -> Transcript {
+    let mut transcript = Transcript::new(b"clsag_bigring");
+    transcript.append_message(b"root", root);
+    transcript
+}
+
+impl AnonymitySet {
+    // Commits to the members of the set, in the order they are given in.
+    // Returns an error if there are no members, or if the members do not all
+    // have the same number of keys
+    pub fn new(members: PublicKeys) -> Result<Self, ClsagError> {
+        let num_keys = members.first().ok_or(ClsagError::EmptyRing)?.len();
+        if num_keys == 0 || members.iter().any(|keys| keys.len() != num_keys) {
+            return Err(ClsagError::KeyCountMismatch);
+        }
+
+        // Each node is the hash of its two children, and a node without a sibling
+        // is carried up to the next level unchanged
+        let mut levels = vec![members
+            .iter()
+            .map(|keys| hash_leaf(keys))
+            .collect::<Vec<Node>>()];
+        while levels[levels.len() - 1].len() > 1 {
+            let level = levels[levels.len() - 1]
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => hash_node(left, right),
+                    _ => pair[0],
+                })
+                .collect();
+            levels.push(level);
+        }
+
+        Ok(AnonymitySet { members, levels })
+    }
+    // Returns the root of the Merkle tree over the set
+    pub fn root(&self) -> MerkleRoot {
+        hash_root(
+            self.members.len() as u64,
+            &self.levels[self.levels.len() - 1][0],
+        )
+    }
+    // Returns the members of the set
+    pub fn members(&self) -> &[Vec<PublicKey>] {
+        &self.members
+    }
+    // Returns the number of members in the set
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+    // Returns true if the set has no members, which a set built with `new` never is
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+    // Returns the proof that the member at `index` is in the set,
+    // or None if there is no member at `index`
+    pub fn membership_proof(&self, index: usize) -> Option<MembershipProof> {
+        if index >= self.members.len() {
+            return None;
+        }
+
+        let mut position = index;
+        let mut siblings = Vec::new();
+        for level in &self.levels[..self.levels.len() - 1] {
+            if let Some(sibling) = level.get(position ^ 1) {
+                siblings.push(*sibling);
+            }
+            position /= 2;
+        }
+
+        Some(MembershipProof {
+            index: index as u64,
+            num_leaves: self.members.len() as u64,
+            siblings,
+        })
+    }
+    // Signs the message over a ring of `ring_size` members sampled from the set, one of which
+    // is the member with the public keys of the secret keys, under the default ring policy.
+    // The members of the ring are kept in the order they are in the set,
+    // which the membership proofs reveal anyway.
+    // Returns an error if the signer is not in the set, if the ring size is larger than the set,
+    // or if the ring policy does not allow the ring size
+    pub fn sign<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        secret_keys: &[SecretKey],
+        ring_size: usize,
+        msg: &[u8],
+    ) -> Result<BigRingSignature, ClsagError> {
+        let signer_keys: Vec<PublicKey> = secret_keys.iter().map(SecretKey::public_key).collect();
+        let signer_index = self
+            .members
+            .iter()
+            .position(|keys| *keys == signer_keys)
+            .ok_or(ClsagError::NoSigner)?;
+        if ring_size > self.members.len() {
+            return Err(ClsagError::RingTooLarge);
+        }
+        if ring_size < 2 {
+            return Err(ClsagError::RingTooSmall);
+        }
+
+        // Sample the decoys from every member other than the signer
+        let mut indices: Vec<usize> =
+            rand::seq::index::sample(rng, self.members.len() - 1, ring_size - 1)
+                .into_iter()
+                .map(|index| {
+                    if index >= signer_index {
+                        index + 1
+                    } else {
+                        index
+                    }
+                })
+                .collect();
+        indices.push(signer_index);
+        indices.sort_unstable();
+
+        let mut clsag = Clsag::new();
+        clsag.set_shuffle_members(false);
+        for &index in &indices {
+            let member = match index == signer_index {
+                true => Member::signer_from_secret_keys(secret_keys)?,
+                false => Member::decoy_from_public_keys(&self.members[index])?,
+            };
+            clsag.add_member(member)?;
+        }
+        let (signature, ring) =
+            clsag.sign_with_transcript(&bigring_transcript(&self.root()), msg)?;
+
+        let proofs = indices
+            .iter()
+            .map(|&index| self.membership_proof(index).ok_or(ClsagError::NoSigner))
+            .collect::<Result<Vec<MembershipProof>, ClsagError>>()?;
+
+        Ok(BigRingSignature {
+            signature,
+            ring,
+            proofs,
+        })
+    }
+}
+
+impl MembershipProof {
+    // Returns the position of the member in the set
+    pub fn index(&self) -> u64 {
+        self.index
+    }
+    // Verifies that the member with the given public keys is in the set with the root.
+    // Returns an error if the proof does not verify
+    pub fn verify(&self, root: &MerkleRoot, keys: &[PublicKey]) -> Result<(), ClsagError> {
+        if self.index >= self.num_leaves {
+            return Err(ClsagError::InvalidMembershipProof);
+        }
+
+        let mut node = hash_leaf(keys);
+        let mut position = self.index;
+        let mut level_size = self.num_leaves;
+        let mut siblings = self.siblings.iter();
+        while level_size > 1 {
+            // The last node of a level with an odd number of nodes has no sibling
+            if position ^ 1 < level_size {
+                let sibling = siblings.next().ok_or(ClsagError::InvalidMembershipProof)?;
+                node = match position % 2 {
+                    0 => hash_node(&node, sibling),
+                    _ => hash_node(sibling, &node),
+                };
+            }
+            position /= 2;
+            level_size = level_size.div_ceil(2);
+        }
+
+        if siblings.next().is_some() || hash_root(self.num_leaves, &node) != *root {
+            return Err(ClsagError::InvalidMembershipProof);
+        }
+        Ok(())
+    }
+}
+
+impl BigRingSignature {
+    // Returns the clsag signature over the ring
+    pub fn signature(&self) -> &Signature {
+        &self.signature
+    }
+    // Returns the ring the signature was made over, in the order the members are in the set
+    pub fn ring(&self) -> &[Vec<PublicKey>] {
+        &self.ring
+    }
+    // Returns the membership proof of each member of the ring
+    pub fn proofs(&self) -> &[MembershipProof] {
+        &self.proofs
+    }
+    // Returns the key images of the signature, the first of which links signatures
+    pub fn key_images(&self) -> &[KeyImage] {
+        &self.signature.key_images
+    }
+    // Verifies the signature on the message, and that every member of the ring is a distinct
+    // member of the set with the root.
+    // Returns an error if a membership proof does not verify, if two members of the ring are
+    // the same member of the set, or if the signature does not verify
+    pub fn verify(&self, root: &MerkleRoot, msg: &[u8]) -> Result<(), ClsagError> {
+        if self.proofs.len() != self.ring.len() {
+            return Err(ClsagError::InvalidMembershipProof);
+        }
+        for (proof, keys) in self.proofs.iter().zip(&self.ring) {
+            proof.verify(root, keys)?;
+        }
+        // The members are in the order of the set, so repeated members are next to each other
+        let distinct = self
+            .proofs
+            .windows(2)
+            .all(|pair| pair[0].index < pair[1].index);
+        if !distinct {
+            return Err(ClsagError::InvalidMembershipProof);
+        }
+
+        self.signature
+            .verify_with_transcript(&bigring_transcript(root), &self.ring, msg)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::*;
+
+    // Returns a set of `size` members with one key, with the signer at `signer_index`
+    fn set_with_signer(size: usize, signer_index: usize) -> (AnonymitySet, SecretKey) {
+        let secret_key = SecretKey::random(&mut rand::thread_rng());
+        let mut members: PublicKeys = generate_rand_public_keys(size - 1)
+            .into_iter()
+            .map(|key| vec![key])
+            .collect();
+        members.insert(signer_index, vec![secret_key.public_key()]);
+        (AnonymitySet::new(members).unwrap(), secret_key)
+    }
+
+    #[test]
+    fn test_membership_proofs() {
+        for &size in &[1, 2, 7, 16, 33] {
+            let (set, _) = set_with_signer(size, 0);
+            let root = set.root();
+            for index in 0..size {
+                let proof = set.membership_proof(index).unwrap();
+                assert!(proof.verify(&root, &set.members()[index]).is_ok());
+
+                let other = (index + 1) % size;
+                if other != index {
+                    match proof.verify(&root, &set.members()[other]) {
+                        Err(ClsagError::InvalidMembershipProof) => {}
+                        _ => panic!("expected an invalid membership proof error"),
+                    }
+                }
+            }
+            assert!(set.membership_proof(size).is_none());
+        }
+
+        // A proof does not verify against the root of another set
+        let (set, _) = set_with_signer(8, 3);
+        let (other_set, _) = set_with_signer(8, 3);
+        let proof = set.membership_proof(2).unwrap();
+        assert!(proof.verify(&other_set.root(), &set.members()[2]).is_err());
+
+        match AnonymitySet::new(Vec::new()) {
+            Err(ClsagError::EmptyRing) => {}
+            _ => panic!("expected an empty ring error"),
+        }
+    }
+
+    #[test]
+    fn test_sign_over_anonymity_set() {
+        let mut rng = rand::thread_rng();
+        let msg = b"hello world";
+        let (set, secret_key) = set_with_signer(1000, 617);
+        let root = set.root();
+
+        let signature = set
+            .sign(&mut rng, std::slice::from_ref(&secret_key), 16, msg)
+            .unwrap();
+        assert_eq!(16, signature.ring().len());
+        assert!(signature.ring().contains(&vec![secret_key.public_key()]));
+        assert!(signature.verify(&root, msg).is_ok());
+        assert!(signature.verify(&root, b"another message").is_err());
+
+        // The signature is bound to the root of the set
+        let (other_set, _) = set_with_signer(1000, 0);
+        assert!(signature.verify(&other_set.root(), msg).is_err());
+
+        // The signer must be in the set, and the ring cannot be larger than the set
+        let outsider = SecretKey::random(&mut rng);
+        match set.sign(&mut rng, &[outsider], 16, msg) {
+            Err(ClsagError::NoSigner) => {}
+            _ => panic!("expected a no signer error"),
+        }
+        match set.sign(&mut rng, &[secret_key], 1001, msg) {
+            Err(ClsagError::RingTooLarge) => {}
+            _ => panic!("expected a ring too large error"),
+        }
+    }
+}
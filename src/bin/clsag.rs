@@ -0,0 +1,188 @@
+// A command line tool for generating keys, signing and verifying.
+//
+//   clsag-cli keygen [--keys N]
+//   clsag-cli sign --ring ring.json --key key.hex --msg file
+//   clsag-cli verify --ring ring.json --signature sig.hex --msg file
+//
+// A ring file is a JSON array holding the public keys of each member, in hex:
+// `[["<hex>", "<hex>"], ["<hex>", "<hex>"], ...]`. The signers public keys must be in the ring.
+// A key file holds the hex of the signers secret keys, concatenated in order.
+// `keygen` prints the secret key as it is stored in a key file, along with the public keys
+// as they are listed in a ring file.
+// `sign` prints the hex signature along with the ring in the order it was signed in,
+// which is the ring the signature must be verified against.
+use clsag::clsag::{Clsag, PublicKeys};
+use clsag::keys::{PublicKey, SecretKey};
+use clsag::member::Member;
+use clsag::signature::Signature;
+use std::env;
+use std::fs;
+use std::process;
+
+const USAGE: &str = "usage:
+  clsag-cli keygen [--keys N]
+  clsag-cli sign --ring ring.json --key key.hex --msg file
+  clsag-cli verify --ring ring.json --signature sig.hex --msg file";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("keygen") => keygen(&args[1..]),
+        Some("sign") => sign(&args[1..]),
+        Some("verify") => verify(&args[1..]),
+        _ => Err(USAGE.to_string()),
+    };
+
+    match result {
+        Ok(true) => {}
+        Ok(false) => process::exit(1),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            process::exit(2);
+        }
+    }
+}
+
+// Returns the value following `--name`
+fn flag<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|arg| arg == name)
+        .and_then(|i| args.get(i + 1))
+        .map(String::as_str)
+}
+
+fn required_flag<'a>(args: &'a [String], name: &str) -> Result<&'a str, String> {
+    flag(args, name).ok_or_else(|| format!("missing {}\n{}", name, USAGE))
+}
+
+fn read_string(path: &str) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|e| format!("could not read {}: {}", path, e))
+}
+
+fn read_bytes(path: &str) -> Result<Vec<u8>, String> {
+    fs::read(path).map_err(|e| format!("could not read {}: {}", path, e))
+}
+
+fn read_ring(path: &str) -> Result<PublicKeys, String> {
+    let members: Vec<Vec<String>> = serde_json::from_str(&read_string(path)?)
+        .map_err(|e| format!("could not parse the ring: {}", e))?;
+
+    members
+        .iter()
+        .map(|keys| {
+            keys.iter()
+                .map(|key| key.parse::<PublicKey>())
+                .collect::<Result<Vec<PublicKey>, _>>()
+        })
+        .collect::<Result<PublicKeys, _>>()
+        .map_err(|e| format!("invalid public key in the ring: {}", e))
+}
+
+fn read_secret_keys(path: &str) -> Result<Vec<SecretKey>, String> {
+    let bytes = hex::decode(read_string(path)?.trim())
+        .map_err(|e| format!("the key file is not hex: {}", e))?;
+    if bytes.is_empty() || bytes.len() % 32 != 0 {
+        return Err("the key file must hold one or more 32 byte keys".to_string());
+    }
+
+    bytes
+        .chunks(32)
+        .map(|chunk| {
+            let mut key = [0u8; 32];
+            key.copy_from_slice(chunk);
+            SecretKey::from_bytes(&key)
+        })
+        .collect::<Result<Vec<SecretKey>, _>>()
+        .map_err(|e| format!("invalid secret key: {}", e))
+}
+
+fn ring_to_json(ring: &[Vec<PublicKey>]) -> serde_json::Value {
+    ring.iter()
+        .map(|keys| keys.iter().map(|key| key.to_string()).collect::<Vec<_>>())
+        .collect()
+}
+
+fn keygen(args: &[String]) -> Result<bool, String> {
+    let num_keys = match flag(args, "--keys") {
+        Some(n) => n
+            .parse::<usize>()
+            .ok()
+            .filter(|&n| n > 0)
+            .ok_or("--keys must be a positive number")?,
+        None => 1,
+    };
+
+    let mut rng = rand::thread_rng();
+    let secret_keys: Vec<SecretKey> = (0..num_keys).map(|_| SecretKey::random(&mut rng)).collect();
+    let public_keys: Vec<String> = secret_keys
+        .iter()
+        .map(|key| key.public_key().to_string())
+        .collect();
+
+    let secret_key: String = secret_keys
+        .iter()
+        .map(|key| hex::encode(key.to_bytes()))
+        .collect();
+    let output = serde_json::json!({
+        "secret_key": secret_key,
+        "public_keys": public_keys,
+    });
+    println!("{}", output);
+
+    Ok(true)
+}
+
+fn sign(args: &[String]) -> Result<bool, String> {
+    let ring = read_ring(required_flag(args, "--ring")?)?;
+    let secret_keys = read_secret_keys(required_flag(args, "--key")?)?;
+    let msg = read_bytes(required_flag(args, "--msg")?)?;
+
+    let signers_keys: Vec<PublicKey> = secret_keys.iter().map(|key| key.public_key()).collect();
+    let signer_index = ring
+        .iter()
+        .position(|keys| *keys == signers_keys)
+        .ok_or("the signers public keys are not in the ring")?;
+
+    let mut clsag = Clsag::new();
+    for (index, keys) in ring.iter().enumerate() {
+        let member = if index == signer_index {
+            Member::signer_from_secret_keys(&secret_keys)
+        } else {
+            Member::decoy_from_public_keys(keys)
+        };
+        clsag
+            .add_member(member.map_err(|e| e.to_string())?)
+            .map_err(|e| format!("could not add a member to the ring: {}", e))?;
+    }
+
+    let (signature, ring) = clsag
+        .sign(&msg)
+        .map_err(|e| format!("could not sign: {}", e))?;
+    let output = serde_json::json!({
+        "signature": signature.to_string(),
+        "ring": ring_to_json(&ring),
+    });
+    println!("{}", output);
+
+    Ok(true)
+}
+
+fn verify(args: &[String]) -> Result<bool, String> {
+    let ring = read_ring(required_flag(args, "--ring")?)?;
+    let signature: Signature = read_string(required_flag(args, "--signature")?)?
+        .trim()
+        .parse()
+        .map_err(|e| format!("invalid signature: {}", e))?;
+    let msg = read_bytes(required_flag(args, "--msg")?)?;
+
+    match signature.optimised_verify(&ring, &msg) {
+        Ok(()) => {
+            println!("valid");
+            Ok(true)
+        }
+        Err(e) => {
+            println!("invalid: {}", e);
+            Ok(false)
+        }
+    }
+}
@@ -0,0 +1,6 @@
+// Generates the Swift and Kotlin bindings of the uniffi feature from the built library.
+//
+//   uniffi-bindgen generate --library target/release/libclsag.so --language kotlin --out-dir out
+fn main() {
+    uniffi::uniffi_bindgen_main()
+}
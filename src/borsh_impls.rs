@@ -0,0 +1,143 @@
+// Borsh implementations for the public types of a signature.
+// Public keys and key images are encoded as their 32 byte compressed points, and a signature
+// with the same layout as `Signature::to_bytes`: the number of responses and key images
+// as little-endian u32s, followed by the challenge, the responses and the key images.
+// The layout is fixed, so the same signature always has the same encoding, and decoding
+// rejects non-canonical scalars and invalid points as `Signature::from_bytes` does.
+use crate::error::ClsagError;
+use crate::keys::{KeyImage, PublicKey};
+use crate::policy::VerificationLimits;
+use crate::signature::Signature;
+use borsh::io::{Error, ErrorKind, Read, Result, Write};
+use borsh::{BorshDeserialize, BorshSerialize};
+use curve25519_dalek::scalar::Scalar;
+
+fn invalid_data(err: ClsagError) -> Error {
+    Error::new(ErrorKind::InvalidData, err)
+}
+
+fn read_bytes<R: Read>(reader: &mut R) -> Result<[u8; 32]> {
+    let mut bytes = [0u8; 32];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_scalar<R: Read>(reader: &mut R) -> Result<Scalar> {
+    Scalar::from_canonical_bytes(read_bytes(reader)?)
+        .ok_or_else(|| invalid_data(ClsagError::NonCanonicalScalar))
+}
+
+impl BorshSerialize for PublicKey {
+    fn serialize<W: Write>(&self, writer: &mut W) -> Result<()> {
+        writer.write_all(self.as_bytes())
+    }
+}
+
+impl BorshDeserialize for PublicKey {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> Result<Self> {
+        PublicKey::from_bytes(&read_bytes(reader)?).map_err(invalid_data)
+    }
+}
+
+impl BorshSerialize for KeyImage {
+    fn serialize<W: Write>(&self, writer: &mut W) -> Result<()> {
+        writer.write_all(self.as_bytes())
+    }
+}
+
+impl BorshDeserialize for KeyImage {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> Result<Self> {
+        KeyImage::from_bytes(&read_bytes(reader)?).map_err(invalid_data)
+    }
+}
+
+impl BorshSerialize for Signature {
+    fn serialize<W: Write>(&self, writer: &mut W) -> Result<()> {
+        writer.write_all(&self.to_bytes())
+    }
+}
+
+impl BorshDeserialize for Signature {
+    // The elements are read one at a time, so that a bogus header
+    // cannot be used to make us allocate large amounts of memory
+    fn deserialize_reader<R: Read>(reader: &mut R) -> Result<Self> {
+        let num_responses = u32::deserialize_reader(reader)?;
+        let num_key_images = u32::deserialize_reader(reader)?;
+        if num_responses == 0 || num_key_images == 0 {
+            return Err(invalid_data(ClsagError::DecodeError));
+        }
+        VerificationLimits::default()
+            .check(num_responses as usize, num_key_images as usize)
+            .map_err(invalid_data)?;
+
+        let challenge = read_scalar(reader)?;
+        let responses = (0..num_responses)
+            .map(|_| read_scalar(reader))
+            .collect::<Result<Vec<Scalar>>>()?;
+        let key_images = (0..num_key_images)
+            .map(|_| KeyImage::deserialize_reader(reader))
+            .collect::<Result<Vec<KeyImage>>>()?;
+
+        Ok(Signature {
+            challenge,
+            responses,
+            key_images,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn test_borsh_round_trip() {
+        let mut clsag = generate_clsag_with(4, 2);
+        clsag.add_member(generate_signer(2)).unwrap();
+        let (signature, public_keys) = clsag.sign(b"hello world").unwrap();
+
+        let bytes = borsh::to_vec(&signature).unwrap();
+        assert_eq!(signature.to_bytes(), bytes);
+        let decoded = Signature::try_from_slice(&bytes).unwrap();
+        assert!(decoded.verify(&public_keys, b"hello world").is_ok());
+
+        let key = public_keys[0][0];
+        let bytes = borsh::to_vec(&key).unwrap();
+        assert_eq!(key.as_bytes().to_vec(), bytes);
+        assert_eq!(key, PublicKey::try_from_slice(&bytes).unwrap());
+
+        let key_image = signature.key_images()[0];
+        let bytes = borsh::to_vec(&key_image).unwrap();
+        assert_eq!(key_image, KeyImage::try_from_slice(&bytes).unwrap());
+    }
+
+    #[test]
+    fn test_borsh_rejects_invalid() {
+        let mut clsag = generate_clsag_with(2, 1);
+        clsag.add_member(generate_signer(1)).unwrap();
+        let bytes = borsh::to_vec(&clsag.sign(b"msg").unwrap().0).unwrap();
+
+        // Trailing and missing bytes
+        let mut extended = bytes.clone();
+        extended.push(0);
+        assert!(Signature::try_from_slice(&extended).is_err());
+        assert!(Signature::try_from_slice(&bytes[..bytes.len() - 1]).is_err());
+
+        // A header claiming a huge number of responses
+        let mut huge = bytes.clone();
+        huge[0..4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(Signature::try_from_slice(&huge).is_err());
+
+        // A non-canonical challenge
+        let mut non_canonical = bytes;
+        non_canonical[8 + 31] = 0xff;
+        match Signature::try_from_slice(&non_canonical) {
+            Err(err) => assert_eq!(ErrorKind::InvalidData, err.kind()),
+            Ok(_) => panic!("expected an invalid data error"),
+        }
+
+        assert!(KeyImage::try_from_slice(&[0u8; 32]).is_err());
+        assert!(PublicKey::try_from_slice(&[0xff; 32]).is_err());
+    }
+}
@@ -0,0 +1,345 @@
+use crate::clsag::{Clsag, PublicKeys};
+use crate::device::SignerDevice;
+use crate::error::ClsagError;
+use crate::hash::HashToPoint;
+use crate::keys::PublicKey;
+use crate::member::Member;
+use crate::policy::RingPolicy;
+use crate::provider::RingMemberProvider;
+use crate::signature::Signature;
+use crate::snapshot::ClsagSnapshot;
+use crate::transcript::SigningTranscript;
+use curve25519_dalek::ristretto::RistrettoPoint;
+use rand::{CryptoRng, RngCore};
+
+// A ClsagBuilder collects the decoys of a ring.
+// Once every decoy has been added, the signer is set with `signer`, after which no more
+// members can be added, and the ring is checked with `finalize`.
+// Only a finalized ring can be signed, so a ring without a signer or with
+// more than one signer cannot be built
+pub struct ClsagBuilder {
+    clsag: Clsag,
+}
+
+// A ring which has its signer, and is waiting to be checked by `finalize`
+pub struct WithSigner {
+    clsag: Clsag,
+}
+
+// A ring which satisfies its ring policy and has exactly one signer
+pub struct ReadyToSign {
+    clsag: Clsag,
+}
+
+// A ring whose signer is a device, waiting to be checked by `finalize`
+pub struct WithDevice<D: SignerDevice> {
+    clsag: Clsag,
+    device: D,
+    signer_keys: Vec<PublicKey>,
+}
+
+// A ring which satisfies its ring policy and is signed by a device
+pub struct ReadyToSignWithDevice<D: SignerDevice> {
+    clsag: Clsag,
+    device: D,
+    signer_keys: Vec<PublicKey>,
+}
+
+impl Default for ClsagBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ClsagBuilder {
+    // Starts building a ring, using the default ring policy
+    pub fn new() -> Self {
+        ClsagBuilder {
+            clsag: Clsag::new(),
+        }
+    }
+    // Starts building a ring for members holding commitments
+    pub fn with_pseudo_out(pseudo_out: RistrettoPoint) -> Self {
+        ClsagBuilder {
+            clsag: Clsag::with_pseudo_out(pseudo_out),
+        }
+    }
+    // Sets the policy the ring must satisfy.
+    // This should be set before any decoys are added, as decoys are checked against
+    // the policy as they are added
+    pub fn ring_policy(mut self, policy: RingPolicy) -> Self {
+        self.clsag.set_ring_policy(policy);
+        self
+    }
+    // Sets the construction the first key of each member is hashed to a point with,
+    // see `Clsag::set_hash_to_point`
+    pub fn hash_to_point<H: HashToPoint + Send + Sync + 'static>(
+        mut self,
+        hash_to_point: H,
+    ) -> Self {
+        self.clsag.set_hash_to_point(hash_to_point);
+        self
+    }
+    // Sets whether the members are shuffled before signing, which is on by default
+    pub fn shuffle_members(mut self, shuffle_members: bool) -> Self {
+        self.clsag.set_shuffle_members(shuffle_members);
+        self
+    }
+    // Adds a decoy to the ring.
+    // Returns an error if the member holds private keys,
+    // if the ring policy does not allow the member to be added,
+    // or if the member does not have as many keys as the members already added
+    pub fn add_decoy(mut self, decoy: Member) -> Result<Self, ClsagError> {
+        if decoy.is_signer() {
+            return Err(ClsagError::NotADecoy);
+        }
+        self.clsag.add_member(decoy)?;
+        Ok(self)
+    }
+    // Fetches the outputs at the given indices from the provider, and adds them as decoys.
+    // Returns an error if an output cannot be fetched,
+    // or if the ring policy does not allow the decoys to be added
+    pub fn add_decoys_from<P: RingMemberProvider + ?Sized>(
+        mut self,
+        provider: &P,
+        indices: &[u64],
+    ) -> Result<Self, ClsagError> {
+        for decoy in provider.get_decoys(indices)? {
+            self = self.add_decoy(decoy)?;
+        }
+        Ok(self)
+    }
+    // Adds the signer to the ring, after which no more members can be added.
+    // Returns an error if the member does not hold private keys,
+    // if the ring policy does not allow the member to be added,
+    // or if the member does not have as many keys as the members already added
+    pub fn signer(mut self, signer: Member) -> Result<WithSigner, ClsagError> {
+        if !signer.is_signer() {
+            return Err(ClsagError::NotASigner);
+        }
+        self.clsag.add_member(signer)?;
+        Ok(WithSigner { clsag: self.clsag })
+    }
+    // Adds a device holding the signers keys as the signer, after which no more members
+    // can be added. The keys stay on the device, see the device module.
+    // Returns an error if the public keys cannot be fetched from the device,
+    // or if the ring policy does not allow the member to be added
+    pub fn device_signer<D: SignerDevice>(
+        mut self,
+        mut device: D,
+    ) -> Result<WithDevice<D>, ClsagError> {
+        let signer_keys = device.public_keys(self.clsag.generator())?;
+        self.clsag
+            .add_member(Member::decoy_from_public_keys(&signer_keys)?)?;
+        Ok(WithDevice {
+            clsag: self.clsag,
+            device,
+            signer_keys,
+        })
+    }
+}
+
+impl ClsagBuilder {
+    // Takes a snapshot of the decoys collected so far, see `Clsag::snapshot`
+    pub fn snapshot(&self) -> ClsagSnapshot {
+        self.clsag.snapshot()
+    }
+    // Continues building a ring from a snapshot.
+    // Returns `NotADecoy` if the snapshot was taken after the signer was added
+    pub fn resume(snapshot: &ClsagSnapshot) -> Result<ClsagBuilder, ClsagError> {
+        if snapshot.signer_index().is_some() {
+            return Err(ClsagError::NotADecoy);
+        }
+        Ok(ClsagBuilder {
+            clsag: snapshot.resume()?,
+        })
+    }
+}
+
+impl WithSigner {
+    // Checks the ring, returning a ring which can be signed.
+    // Returns an error if the ring is smaller than the ring policy allows,
+    // or if the members do not all have the same number of keys and commitments
+    pub fn finalize(self) -> Result<ReadyToSign, ClsagError> {
+        self.clsag.check_format()?;
+        self.clsag.check_policy()?;
+        Ok(ReadyToSign { clsag: self.clsag })
+    }
+}
+
+impl<D: SignerDevice> WithDevice<D> {
+    // Checks the ring, returning a ring which can be signed by the device.
+    // Returns an error if the ring is smaller than the ring policy allows,
+    // or if the members do not all have the same number of keys
+    pub fn finalize(self) -> Result<ReadyToSignWithDevice<D>, ClsagError> {
+        self.clsag.check_device_format(&self.signer_keys)?;
+        self.clsag.check_policy()?;
+        Ok(ReadyToSignWithDevice {
+            clsag: self.clsag,
+            device: self.device,
+            signer_keys: self.signer_keys,
+        })
+    }
+}
+
+impl<D: SignerDevice> ReadyToSignWithDevice<D> {
+    // Returns public keys from all known members, in the order they were added in
+    pub fn public_keys(&self) -> &[Vec<PublicKey>] {
+        self.clsag.public_keys()
+    }
+    // Signs the message with the device, see `Clsag::sign`
+    pub fn sign(&mut self, msg: &[u8]) -> Result<(Signature, PublicKeys), ClsagError> {
+        self.sign_with_rng(&mut rand::thread_rng(), msg)
+    }
+    // Signs the message with the device, drawing the order of the ring
+    // and the decoys responses from the given rng
+    pub fn sign_with_rng<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+        msg: &[u8],
+    ) -> Result<(Signature, PublicKeys), ClsagError> {
+        self.clsag
+            .sign_with_device(rng, &mut self.device, &self.signer_keys, msg)
+    }
+}
+
+impl ReadyToSign {
+    // Returns public keys from all known members, in the order they were added in
+    pub fn public_keys(&self) -> &[Vec<PublicKey>] {
+        self.clsag.public_keys()
+    }
+    // Signs the message, see `Clsag::sign`
+    pub fn sign(&self, msg: &[u8]) -> Result<(Signature, PublicKeys), ClsagError> {
+        self.clsag.sign(msg)
+    }
+    // Signs the message using the given rng, see `Clsag::sign_with_rng`
+    pub fn sign_with_rng<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        msg: &[u8],
+    ) -> Result<(Signature, PublicKeys), ClsagError> {
+        self.clsag.sign_with_rng(rng, msg)
+    }
+    // Signs the message using the given transcript, see `Clsag::sign_with_transcript`
+    pub fn sign_with_transcript<T: SigningTranscript>(
+        &self,
+        transcript: &T,
+        msg: &[u8],
+    ) -> Result<(Signature, PublicKeys), ClsagError> {
+        self.clsag.sign_with_transcript(transcript, msg)
+    }
+    // Signs the message bound to a context, see `Clsag::sign_with_context`
+    pub fn sign_with_context(
+        &self,
+        msg: &[u8],
+        context: &[u8],
+    ) -> Result<(Signature, PublicKeys), ClsagError> {
+        self.clsag.sign_with_context(msg, context)
+    }
+    // Signs the digest of a message, see `Clsag::sign_digest`
+    pub fn sign_digest(&self, digest: &[u8; 64]) -> Result<(Signature, PublicKeys), ClsagError> {
+        self.clsag.sign_digest(digest)
+    }
+    // Signs the message without an rng, see `Clsag::sign_deterministic`
+    pub fn sign_deterministic(&self, msg: &[u8]) -> Result<Signature, ClsagError> {
+        self.clsag.sign_deterministic(msg)
+    }
+    // Signs the message in constant time, see `Clsag::sign_ct`
+    pub fn sign_ct(&self, msg: &[u8]) -> Result<(Signature, PublicKeys), ClsagError> {
+        self.clsag.sign_ct(msg)
+    }
+    // Signs the message in constant time using the given rng, see `Clsag::sign_ct_with_rng`
+    pub fn sign_ct_with_rng<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        msg: &[u8],
+    ) -> Result<(Signature, PublicKeys), ClsagError> {
+        self.clsag.sign_ct_with_rng(rng, msg)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn test_build_and_sign() {
+        let num_keys = 2;
+        let msg = b"hello world";
+
+        let mut builder = ClsagBuilder::new();
+        for decoy in generate_decoys(10, num_keys) {
+            builder = builder.add_decoy(decoy).unwrap();
+        }
+        let ring = builder
+            .signer(generate_signer(num_keys))
+            .unwrap()
+            .finalize()
+            .unwrap();
+        assert_eq!(11, ring.public_keys().len());
+
+        let (signature, public_keys) = ring.sign(msg).unwrap();
+        assert!(signature.verify(&public_keys, msg).is_ok());
+        let (signature, public_keys) = ring.sign_ct(msg).unwrap();
+        assert!(signature.optimised_verify(&public_keys, msg).is_ok());
+    }
+
+    #[test]
+    fn test_build_from_provider() {
+        let outputs = generate_rand_public_keys(20);
+
+        let ring = ClsagBuilder::new()
+            .add_decoys_from(&outputs[..], &[0, 3, 4, 7, 8, 10, 12, 15, 16, 19])
+            .unwrap()
+            .signer(generate_signer(1))
+            .unwrap()
+            .finalize()
+            .unwrap();
+        assert_eq!(vec![outputs[3]], ring.public_keys()[1]);
+
+        let (signature, public_keys) = ring.sign(b"hello world").unwrap();
+        assert!(signature.verify(&public_keys, b"hello world").is_ok());
+
+        match ClsagBuilder::new().add_decoys_from(&outputs[..], &[1, 20]) {
+            Err(ClsagError::OutputUnavailable) => {}
+            _ => panic!("expected an output unavailable error"),
+        };
+    }
+
+    #[test]
+    fn test_build_rejects_bad_rings() {
+        let num_keys = 2;
+
+        // A signer cannot be added as a decoy, nor a decoy as the signer
+        match ClsagBuilder::new().add_decoy(generate_signer(num_keys)) {
+            Err(ClsagError::NotADecoy) => {}
+            _ => panic!("expected a not a decoy error"),
+        };
+        match ClsagBuilder::new().signer(generate_decoy(num_keys)) {
+            Err(ClsagError::NotASigner) => {}
+            _ => panic!("expected a not a signer error"),
+        };
+
+        // The ring is checked against the policy when it is finalized
+        let with_signer = ClsagBuilder::new()
+            .add_decoy(generate_decoy(num_keys))
+            .unwrap()
+            .signer(generate_signer(num_keys))
+            .unwrap();
+        match with_signer.finalize() {
+            Err(ClsagError::RingTooSmall) => {}
+            _ => panic!("expected a ring too small error"),
+        };
+
+        // Every member must have the same number of keys
+        let with_decoy = ClsagBuilder::new()
+            .ring_policy(RingPolicy::permissive())
+            .add_decoy(generate_decoy(num_keys + 1))
+            .unwrap();
+        match with_decoy.signer(generate_signer(num_keys)) {
+            Err(ClsagError::MemberKeyCountMismatch { index: 1, .. }) => {}
+            _ => panic!("expected a member key count mismatch error"),
+        };
+    }
+}
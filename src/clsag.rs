@@ -1,45 +1,91 @@
-use crate::member::Member;
+use crate::adaptor::{AdaptorPoint, PreSignature};
+use crate::commitment::commit;
+use crate::constants::BASEPOINT;
+use crate::contract::{contract_tweak, CommitmentOpening};
+use crate::device::{key_image_statements, key_image_transcript, SignerDevice};
+use crate::error::ClsagError;
+use crate::hash::HashToPoint;
+use crate::keys::{zeroize_scalar, KeyImage, PrivateSet, PublicKey};
+use crate::member::{round_challenge, round_transcript, Member};
+use crate::metrics::{Metrics, MetricsHook};
+use crate::policy::RingPolicy;
+use crate::range::{range_transcript, RangeProofSystem};
+use crate::ring::{canonical_order, BatchRings};
 use crate::signature::Signature;
-use curve25519_dalek::ristretto::CompressedRistretto;
+#[cfg(feature = "trace")]
+use crate::trace;
+use curve25519_dalek::ristretto::RistrettoPoint;
 use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::traits::{Identity, MultiscalarMul};
+use rand::seq::SliceRandom;
+use rand::{CryptoRng, RngCore};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use std::collections::HashSet;
+use std::iter;
+use std::sync::Arc;
+use subtle::{Choice, ConditionallySelectable, ConstantTimeEq};
 
-use crate::transcript::TranscriptProtocol;
+use crate::transcript::{
+    context_transcript, default_transcript, digest_transcript, SigningTranscript,
+};
 use merlin::Transcript;
 
-// This module will pull together all of the necessary things
-// Setting up everything we need
+// The public keys of every member in a ring, in ring order
+pub type PublicKeys = Vec<Vec<PublicKey>>;
+
+// This error is returned from `batch_verify` and holds the position
+// in the batch of every signature which failed to verify, along with the reason
 #[derive(Debug)]
-pub enum Error {
-    // This error occurs when the sign method is called
-    // without a signer being in the ring
-    NoSigner,
-    // This error occurs if there are less than 2 members in the ring
-    NotEnoughMembers,
-    // This error occurs if all members do not have the same number of keys
-    NumberOfKeysMismatch,
-    // This error occurs if there is more than one signer in the ring
-    MoreThanOneSigner,
-    // This error occurs if a member in the ring has duplicate keys
-    DuplicateKeysExist,
-    // This error occurs when an underlying module produces an error
-    UnderlyingErr(String),
+pub struct BatchError {
+    pub failures: Vec<(usize, ClsagError)>,
 }
 
-impl From<crate::member::Error> for crate::clsag::Error {
-    fn from(e: crate::member::Error) -> crate::clsag::Error {
-        match e {
-            crate::member::Error::NotASigner => Error::UnderlyingErr(String::from(
-                "Tried to use a method specific to a signer in the clsag module",
-            )),
-            crate::member::Error::NotADecoy => Error::UnderlyingErr(String::from(
-                "Tried to use a method specific to a decoy in the clsag module",
-            )),
+impl BatchError {
+    pub(crate) fn from_failures(failures: Vec<(usize, ClsagError)>) -> Result<(), BatchError> {
+        if !failures.is_empty() {
+            return Err(BatchError { failures });
         }
+
+        Ok(())
     }
 }
+
 // This struct is used to construct the clsag signature
+#[derive(Clone)]
 pub struct Clsag {
     members: Vec<Member>,
+
+    // The commitment subtracted from each members commitment to form
+    // the commitments to zero, when the members hold commitments
+    pub(crate) pseudo_out: Option<RistrettoPoint>,
+
+    // The generator every key in the ring is a multiple of,
+    // which is the basepoint unless the ring was created with `with_generator`
+    pub(crate) generator: RistrettoPoint,
+
+    // The compressed keys of every member, in the same order as the members.
+    // These are computed once when the member is added, so that the ring
+    // can be handed out without compressing every key again
+    ring: PublicKeys,
+
+    // When set, the members are put in a random order before signing,
+    // so that the position of the signer does not depend on the order they were added in
+    pub(crate) shuffle_members: bool,
+
+    // The limits on the size of the ring and the number of keys of each member
+    policy: RingPolicy,
+
+    // The construction the first key of each member is hashed to a point with,
+    // when it is not the default construction
+    hash_to_point: Option<Arc<dyn HashToPoint + Send + Sync>>,
+
+    // Every key of every member, so that a member with a key
+    // which is already in the ring is rejected when it is added
+    keys: HashSet<PublicKey>,
+
+    // The metrics every signature made over the ring is reported to, if any are installed
+    metrics: MetricsHook,
 }
 
 impl Default for Clsag {
@@ -53,29 +99,523 @@ impl Clsag {
     pub fn new() -> Self {
         Clsag {
             members: Vec::new(),
+            pseudo_out: None,
+            generator: BASEPOINT,
+            ring: Vec::new(),
+            shuffle_members: true,
+            policy: RingPolicy::default(),
+            hash_to_point: None,
+            keys: HashSet::new(),
+            metrics: MetricsHook::default(),
+        }
+    }
+    // Creates a new clsag component for members holding commitments.
+    // The signer proves that their commitment and the pseudo output commit to the same amount
+    pub fn with_pseudo_out(pseudo_out: RistrettoPoint) -> Self {
+        Clsag {
+            members: Vec::new(),
+            pseudo_out: Some(pseudo_out),
+            generator: BASEPOINT,
+            ring: Vec::new(),
+            shuffle_members: true,
+            policy: RingPolicy::default(),
+            hash_to_point: None,
+            keys: HashSet::new(),
+            metrics: MetricsHook::default(),
+        }
+    }
+    // Creates a new clsag component whose keys are multiples of the given generator instead
+    // of the basepoint, for protocols which keep their keys apart by using their own generator.
+    // The public keys of each signer are computed over the generator when they are added,
+    // and the signature must be verified with `Signature::verify_with_generator`
+    pub fn with_generator(generator: RistrettoPoint) -> Self {
+        Clsag {
+            generator,
+            ..Self::new()
         }
     }
     // Adds a member to the clsag component
-    // Use this method to add decoys and signers to the struct
-    pub fn add_member(&mut self, member: Member) {
+    // Use this method to add decoys and signers to the struct.
+    // Returns an error if the ring policy does not allow the member to be added,
+    // either because the ring is full or the member has the wrong number of keys,
+    // if the member does not have as many keys as the members already in the ring,
+    // or if the member has the same key twice or a key another member already has
+    pub fn add_member(&mut self, mut member: Member) -> Result<(), ClsagError> {
+        self.policy
+            .check_member(self.members.len(), member.num_keys())?;
+        self.check_key_count(&member, self.members.len())?;
+
+        let keys = self.member_keys(&mut member, self.members.len(), &[])?;
+        self.keys.extend(keys[..member.num_keys()].iter().cloned());
+        self.ring.push(keys);
         self.members.push(member);
+
+        Ok(())
+    }
+    // Removes the member at the index from the ring and returns it,
+    // moving the members after it down by one.
+    // Returns an error if there is no member at the index
+    pub fn remove_member(&mut self, index: usize) -> Result<Member, ClsagError> {
+        if index >= self.members.len() {
+            return Err(ClsagError::MemberNotFound);
+        }
+
+        let member = self.members.remove(index);
+        for key in &self.ring.remove(index)[..member.num_keys()] {
+            self.keys.remove(key);
+        }
+
+        Ok(member)
+    }
+    // Replaces the member at the index with another member and returns the member it replaced,
+    // such as a decoy which turns out to have been spent.
+    // Returns an error if there is no member at the index, if the ring policy does not allow
+    // the members number of keys, if the member does not have as many keys as the other
+    // members, or if the member has the same key twice or a key another member already has
+    pub fn replace_member(
+        &mut self,
+        index: usize,
+        mut member: Member,
+    ) -> Result<Member, ClsagError> {
+        if index >= self.members.len() {
+            return Err(ClsagError::MemberNotFound);
+        }
+        self.policy.check_num_keys(member.num_keys())?;
+        self.check_key_count(&member, index)?;
+
+        let num_replaced_keys = self.members[index].num_keys();
+        let keys = self.member_keys(&mut member, index, &self.ring[index][..num_replaced_keys])?;
+        for key in &self.ring[index][..num_replaced_keys] {
+            self.keys.remove(key);
+        }
+        self.keys.extend(keys[..member.num_keys()].iter().cloned());
+        self.ring[index] = keys;
+
+        Ok(std::mem::replace(&mut self.members[index], member))
+    }
+    // Returns the members of the ring, in the order they were added
+    pub fn members(&self) -> &[Member] {
+        &self.members
+    }
+    // Checks that the member which will be at the index has as many keys as the other members
+    fn check_key_count(&self, member: &Member, index: usize) -> Result<(), ClsagError> {
+        let other = self.members.iter().enumerate().find(|(i, _)| *i != index);
+        let expected = match other {
+            Some((_, other)) => other.num_keys(),
+            None => return Ok(()),
+        };
+
+        if member.num_keys() != expected {
+            return Err(ClsagError::MemberKeyCountMismatch {
+                expected,
+                got: member.num_keys(),
+                index,
+            });
+        }
+        Ok(())
+    }
+    // Returns the keys the member signs with, and hashes their first key with the construction
+    // set for the ring. Returns an error if the member, which will be at the index, has the
+    // same key twice or a key another member has, other than the keys of the member it replaces.
+    // When one of the two members with the key is the signer, the error holds the index of the other
+    fn member_keys(
+        &self,
+        member: &mut Member,
+        index: usize,
+        replaced: &[PublicKey],
+    ) -> Result<Vec<PublicKey>, ClsagError> {
+        if self.generator != BASEPOINT {
+            member.set_generator(&self.generator);
+        }
+        let keys = member.ring_keys(&self.pseudo_out()).to_keys();
+        // The commitment to zero is not checked, as only the members own keys must be unique
+        let mut new_keys = HashSet::with_capacity(member.num_keys());
+        for key in &keys[..member.num_keys()] {
+            if self.keys.contains(key) && !replaced.contains(key) {
+                return Err(self.duplicate_key_error(member, index, key));
+            }
+            if !new_keys.insert(*key) {
+                return Err(ClsagError::DuplicateKey(index));
+            }
+        }
+
+        if let Some(hash_to_point) = &self.hash_to_point {
+            member.set_hashed_pubkey(hash_to_point.hash_to_point(keys[0].as_bytes()));
+        }
+        Ok(keys)
+    }
+    // Returns the error for a member which will be at the index having a key already in the ring
+    fn duplicate_key_error(&self, member: &Member, index: usize, key: &PublicKey) -> ClsagError {
+        let holder = self
+            .members
+            .iter()
+            .zip(&self.ring)
+            .enumerate()
+            .find(|(i, (other, keys))| *i != index && keys[..other.num_keys()].contains(key));
+        match holder {
+            Some((i, (other, _))) if member.is_signer() && !other.is_signer() => {
+                ClsagError::DecoyEqualsSigner { index: i }
+            }
+            Some((_, (other, _))) if !member.is_signer() && other.is_signer() => {
+                ClsagError::DecoyEqualsSigner { index }
+            }
+            _ => ClsagError::DuplicateKey(index),
+        }
+    }
+    // Sets the policy the ring must satisfy, which by default requires at least 11 members,
+    // see `RingPolicy::permissive` to sign over smaller rings.
+    // The policy is only checked against members added afterwards,
+    // although the whole ring is checked against it again when signing
+    pub fn set_ring_policy(&mut self, policy: RingPolicy) {
+        self.policy = policy;
+    }
+    // Returns the generator every key in the ring is a multiple of
+    pub fn generator(&self) -> &RistrettoPoint {
+        &self.generator
+    }
+    // Returns the policy the ring must satisfy
+    pub fn ring_policy(&self) -> &RingPolicy {
+        &self.policy
+    }
+    // Sets the construction the first key of each member is hashed to a point with,
+    // which by default is the domain separated SHA-512 hash in the hash module.
+    // The members already in the ring are hashed again, and the signature must be verified
+    // over a ring prepared with `PreparedRing::with_hash_to_point` and the same construction
+    pub fn set_hash_to_point<H: HashToPoint + Send + Sync + 'static>(&mut self, hash_to_point: H) {
+        for (member, keys) in self.members.iter_mut().zip(&self.ring) {
+            member.set_hashed_pubkey(hash_to_point.hash_to_point(keys[0].as_bytes()));
+        }
+        self.hash_to_point = Some(Arc::new(hash_to_point));
+    }
+    // Sets whether the members are shuffled before signing, which is on by default.
+    // The signature is made over the shuffled ring, so the public keys returned alongside
+    // the signature must be used to verify it. Only turn this off if the ring order is
+    // already random, as a signer which is always added last reveals their position
+    pub fn set_shuffle_members(&mut self, shuffle_members: bool) {
+        self.shuffle_members = shuffle_members;
+    }
+    // Installs the metrics which are told how long each signature made
+    // with `sign`, `sign_ct` and `sign_deterministic` and their variants took
+    pub fn set_metrics(&mut self, metrics: Arc<dyn Metrics>) {
+        self.metrics = MetricsHook::new(metrics);
+    }
+    // Puts the members in canonical order, sorted by the bytes of their keys, and returns
+    // the index of the signer. Shuffling is turned off, so the ring is signed in this order
+    // and the signature can be verified with `Signature::verify_canonical`.
+    // Returns an error if there is no signer in the ring
+    pub fn canonicalize_members(&mut self) -> Result<usize, ClsagError> {
+        let order = canonical_order(&self.ring);
+        self.members = order.iter().map(|&i| self.members[i].clone()).collect();
+        self.ring = order.iter().map(|&i| self.ring[i].clone()).collect();
+        self.shuffle_members = false;
+
+        self.find_signer()
     }
     // Returns public keys from all known members as vector of bytes
     pub fn public_keys_bytes(&self) -> Vec<u8> {
-        self.members
+        self.ring
             .iter()
-            .flat_map(|member| member.public_set.to_bytes())
+            .flatten()
+            .flat_map(|key| key.to_bytes().to_vec())
             .collect()
     }
     // Returns public keys from all known members
-    pub fn public_keys(&self) -> Vec<Vec<CompressedRistretto>> {
-        self.members
+    // If the members hold commitments, the commitment to zero is the last key of each member
+    pub fn public_keys(&self) -> &[Vec<PublicKey>] {
+        &self.ring
+    }
+    // Returns the number of keys every member of the ring has, which is 0 for an empty ring.
+    // The commitments to zero are not counted, see `num_layers`
+    pub fn key_count(&self) -> usize {
+        self.members.first().map_or(0, Member::num_keys)
+    }
+    // Returns the number of layers in the ring, which is the number of keys each member
+    // signs with, including the commitment to zero when the members hold commitments
+    pub fn num_layers(&self) -> usize {
+        match self.members.first() {
+            Some(member) => member.num_keys() + member.commitment().map_or(0, |_| 1),
+            None => 0,
+        }
+    }
+    // Returns the pseudo output, which is the identity if none was given
+    fn pseudo_out(&self) -> RistrettoPoint {
+        self.pseudo_out.unwrap_or_else(RistrettoPoint::identity)
+    }
+    // Returns a copy of the ring where the commitments to zero have been
+    // added to the members keys, so that they can be signed over like any other key
+    fn expanded(&self) -> Clsag {
+        let pseudo_out = self.pseudo_out();
+        let members = self
+            .members
             .iter()
-            .map(|member| member.public_set.to_keys())
-            .collect()
+            .map(|member| member.with_commitment_to_zero(&pseudo_out))
+            .collect();
+
+        Clsag {
+            members,
+            pseudo_out: None,
+            generator: self.generator,
+            ring: self.ring.clone(),
+            shuffle_members: self.shuffle_members,
+            policy: self.policy,
+            hash_to_point: self.hash_to_point.clone(),
+            keys: self.keys.clone(),
+            metrics: self.metrics.clone(),
+        }
+    }
+    // Returns a copy of the ring, where the members have been put
+    // in a random order if shuffling is enabled
+    pub(crate) fn shuffled<R: RngCore>(&self, rng: &mut R) -> Clsag {
+        let mut order: Vec<usize> = (0..self.members.len()).collect();
+        if self.shuffle_members {
+            order.shuffle(rng);
+        }
+
+        Clsag {
+            members: order.iter().map(|&i| self.members[i].clone()).collect(),
+            pseudo_out: self.pseudo_out,
+            generator: self.generator,
+            ring: order.iter().map(|&i| self.ring[i].clone()).collect(),
+            shuffle_members: self.shuffle_members,
+            policy: self.policy,
+            hash_to_point: self.hash_to_point.clone(),
+            keys: self.keys.clone(),
+            metrics: self.metrics.clone(),
+        }
+    }
+    // sign produces a clsag signature, using the thread rng for
+    // the signers nonce and the decoys responses.
+    // The public keys of the ring are returned alongside the signature, in the order
+    // they were signed in, which is a random order unless shuffling has been turned off
+    pub fn sign(&self, msg: &[u8]) -> Result<(Signature, PublicKeys), ClsagError> {
+        self.sign_with_rng(&mut rand::thread_rng(), msg)
+    }
+    // sign_with_rng produces a clsag signature, drawing the order of the ring,
+    // the signers nonce and the decoys responses from the given rng
+    pub fn sign_with_rng<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        msg: &[u8],
+    ) -> Result<(Signature, PublicKeys), ClsagError> {
+        self.sign_with_rng_and_transcript(rng, &default_transcript(), msg)
+    }
+    // sign_with_transcript produces a clsag signature, deriving every challenge from
+    // a clone of the given transcript instead of the default one.
+    // Any context appended to the transcript beforehand is bound into the signature,
+    // so the signature will only verify with a transcript holding the same context
+    pub fn sign_with_transcript<T: SigningTranscript>(
+        &self,
+        transcript: &T,
+        msg: &[u8],
+    ) -> Result<(Signature, PublicKeys), ClsagError> {
+        self.sign_with_rng_and_transcript(&mut rand::thread_rng(), transcript, msg)
+    }
+    fn sign_with_rng_and_transcript<R: RngCore + CryptoRng, T: SigningTranscript>(
+        &self,
+        rng: &mut R,
+        transcript: &T,
+        msg: &[u8],
+    ) -> Result<(Signature, PublicKeys), ClsagError> {
+        self.traced(|| self.sign_untraced(rng, transcript, msg))
+    }
+    // Signs without reporting the operation, for the signing paths which report
+    // the whole of their operation themselves
+    fn sign_untraced<R: RngCore + CryptoRng, T: SigningTranscript>(
+        &self,
+        rng: &mut R,
+        transcript: &T,
+        msg: &[u8],
+    ) -> Result<(Signature, PublicKeys), ClsagError> {
+        self.check_format()?;
+        self.check_policy()?;
+
+        let ring = self.shuffled(rng);
+        let signature = ring
+            .expanded()
+            .with_nonces(|_| Scalar::random(rng))
+            .sign_with_nonces(transcript, msg, None)?;
+
+        Ok((signature, ring.ring))
+    }
+    // Runs the signing operation, reporting it to the installed metrics,
+    // and to the subscriber with the trace feature
+    pub(crate) fn traced<T, F: FnOnce() -> Result<T, ClsagError>>(
+        &self,
+        sign: F,
+    ) -> Result<T, ClsagError> {
+        self.metrics.observe_sign(|| {
+            #[cfg(feature = "trace")]
+            return trace::instrument(
+                trace::Operation::Sign,
+                self.members.len(),
+                self.members.first().map_or(0, Member::num_keys),
+                sign,
+            );
+            #[cfg(not(feature = "trace"))]
+            sign()
+        })
+    }
+    // sign_with_context produces a clsag signature which is bound to an application specific
+    // context, such as the name and version of the protocol the signature is made for.
+    // The signature only verifies with `verify_with_context` and the same context,
+    // so it cannot be replayed in a protocol using a different context
+    pub fn sign_with_context(
+        &self,
+        msg: &[u8],
+        context: &[u8],
+    ) -> Result<(Signature, PublicKeys), ClsagError> {
+        self.sign_with_transcript(&context_transcript(context), msg)
+    }
+    // sign_digest produces a clsag signature over the 64 byte SHA-512 digest of a message,
+    // as computed by `hash_message` or `hash_reader` in the digest module, so that the
+    // message never needs to be held in memory. The digest is signed with its own transcript,
+    // so the signature only verifies with `verify_digest`
+    pub fn sign_digest(&self, digest: &[u8; 64]) -> Result<(Signature, PublicKeys), ClsagError> {
+        self.sign_with_transcript(&digest_transcript(), digest)
+    }
+    // sign_adaptor produces a pre-signature, which only becomes a valid signature once it is
+    // completed with the secret of the adaptor point, for atomic swaps. The adaptor point must
+    // be made for the first key of the signer, and anyone holding both the pre-signature and
+    // the completed signature can recover the adaptor secret with `adaptor::extract_secret`.
+    // Returns an error if the adaptor point was made for another key
+    pub fn sign_adaptor(
+        &self,
+        msg: &[u8],
+        adaptor: &AdaptorPoint,
+    ) -> Result<(PreSignature, PublicKeys), ClsagError> {
+        self.traced(|| {
+            self.check_format()?;
+            self.check_policy()?;
+
+            let mut rng = rand::thread_rng();
+            let ring = self.shuffled(&mut rng);
+            let signer_index = ring.find_signer()?;
+            if ring.ring[signer_index][0] != *adaptor.signer_key() {
+                return Err(ClsagError::AdaptorMismatch);
+            }
+            let signature = ring
+                .expanded()
+                .with_nonces(|_| Scalar::random(&mut rng))
+                .sign_with_nonces(&default_transcript(), msg, Some(adaptor))?;
+
+            let pre_signature = PreSignature::new(signature, signer_index, *adaptor);
+            Ok((pre_signature, ring.ring))
+        })
+    }
+    // sign_with_commitment produces a clsag signature whose nonce commits to the data, as
+    // described in the contract module. The signature verifies like any other signature, and
+    // the returned opening proves that it commits to the data with
+    // `Signature::extract_commitment_proof`. The opening reveals the position of the signer
+    pub fn sign_with_commitment(
+        &self,
+        msg: &[u8],
+        data: &[u8],
+    ) -> Result<(Signature, PublicKeys, CommitmentOpening), ClsagError> {
+        self.traced(|| {
+            self.check_format()?;
+            self.check_policy()?;
+
+            let mut rng = rand::thread_rng();
+            let ring = self.shuffled(&mut rng);
+            let signer_index = ring.find_signer()?;
+
+            let mut nonce = Scalar::random(&mut rng);
+            let nonce_point = nonce * self.generator;
+            let mut tweaked_nonce = nonce + contract_tweak(&nonce_point, data);
+            let signature = ring.sign_in_order_with(msg, |index| match index == signer_index {
+                true => tweaked_nonce,
+                false => Scalar::random(&mut rng),
+            });
+            zeroize_scalar(&mut nonce);
+            zeroize_scalar(&mut tweaked_nonce);
+
+            let opening = CommitmentOpening {
+                signer_index,
+                nonce_point,
+                generator: self.generator,
+            };
+            Ok((signature?, ring.ring, opening))
+        })
+    }
+    // sign_with_range_proof produces a clsag signature over a ring of commitments, along with
+    // a range proof for the amount of the pseudo output from the given range proof system.
+    // The amount and blinding factor are the opening of the pseudo output.
+    // Returns `PseudoOutMismatch` if the ring has no pseudo output, or if the amount and
+    // blinding factor do not open it, and an error if the ring cannot be signed
+    // or the range proof cannot be made
+    pub fn sign_with_range_proof<S: RangeProofSystem>(
+        &self,
+        msg: &[u8],
+        system: &S,
+        amount: u64,
+        blinding: &Scalar,
+    ) -> Result<(Signature, PublicKeys, S::Proof), ClsagError> {
+        self.traced(|| {
+            let pseudo_out = self.pseudo_out.ok_or(ClsagError::PseudoOutMismatch)?;
+            if commit(amount, blinding) != pseudo_out {
+                return Err(ClsagError::PseudoOutMismatch);
+            }
+
+            let (signature, public_keys) =
+                self.sign_untraced(&mut rand::thread_rng(), &default_transcript(), msg)?;
+            let proof = system.prove(&mut range_transcript(msg), amount, blinding)?;
+            Ok((signature, public_keys, proof))
+        })
+    }
+    // Signs the ring in the order it is in, with the signers nonce and the decoys responses
+    // produced by `next_value`, which is called once per member in ring order
+    pub(crate) fn sign_in_order_with<F: FnMut(usize) -> Scalar>(
+        &self,
+        msg: &[u8],
+        next_value: F,
+    ) -> Result<Signature, ClsagError> {
+        self.expanded()
+            .with_nonces(next_value)
+            .sign_with_nonces(&default_transcript(), msg, None)
+    }
+    // Returns a copy of the ring, where the signers nonce and the decoys responses have been
+    // replaced by the values produced by `next_value`, which is called once per member in ring order.
+    // A fresh nonce must be used for every signature, as two signatures with the same nonce
+    // and different challenges reveal the signers private keys
+    fn with_nonces<F: FnMut(usize) -> Scalar>(&self, mut next_value: F) -> Clsag {
+        let members = self
+            .members
+            .iter()
+            .enumerate()
+            .map(|(index, member)| {
+                let value = next_value(index);
+
+                let mut member = member.clone();
+                if member.is_signer() {
+                    member.nonce = Some(value);
+                } else {
+                    member.response = Some(value);
+                }
+                member
+            })
+            .collect();
+
+        Clsag {
+            members,
+            pseudo_out: self.pseudo_out,
+            generator: self.generator,
+            ring: self.ring.clone(),
+            shuffle_members: self.shuffle_members,
+            policy: self.policy,
+            hash_to_point: self.hash_to_point.clone(),
+            keys: self.keys.clone(),
+            metrics: self.metrics.clone(),
+        }
     }
-    // sign produces a clsag signature
-    pub fn sign(&self, msg: &[u8]) -> Result<Signature, Error> {
+    // Produces a clsag signature using the nonce and responses stored in the members.
+    // When an adaptor point is given, the signers nonce commitment is shifted by it,
+    // and the signature only verifies once the adaptor secret is added to the signers response
+    fn sign_with_nonces<T: SigningTranscript>(
+        &self,
+        transcript: &T,
+        msg: &[u8],
+        adaptor: Option<&AdaptorPoint>,
+    ) -> Result<Signature, ClsagError> {
         self.check_format()?;
 
         let num_members = self.members.len();
@@ -92,10 +632,21 @@ impl Clsag {
         let key_images = signer.compute_key_images()?;
 
         // Calculate aggrgation co-efficients
-        let aggregation_cooeff = calc_aggregation_coefficients(&pubkey_matrix, &key_images, msg);
+        let aggregation_cooeff =
+            calc_aggregation_coefficients(transcript, &pubkey_matrix, &key_images, msg);
+
+        // The ring, the key images and the message are hashed once,
+        // and every round continues from a copy of the transcript
+        let round = round_transcript(transcript, &pubkey_matrix, &key_images, msg);
 
         // Calculate first challenge
-        let mut challenge = signer.compute_challenge_commitment(&pubkey_matrix)?;
+        let mut challenge = match adaptor {
+            None => signer.challenge_commitment(&round, &self.generator)?,
+            Some(adaptor) => {
+                let (l, r) = signer.nonce_commitment(&self.generator)?;
+                round_challenge(&round, &(l + adaptor.point), &(r + adaptor.hashed_point))
+            }
+        };
         all_challenges.push(challenge);
 
         // seed challenge into for loop starting from member after signer
@@ -106,11 +657,12 @@ impl Clsag {
             .skip(signer_index + 1)
             .take(num_members - 1)
         {
-            challenge = decoy.compute_decoy_challenge(
+            challenge = decoy.decoy_challenge(
+                &round,
                 &challenge,
                 &key_images,
                 &aggregation_cooeff,
-                &pubkey_matrix,
+                &self.generator,
             )?;
             all_challenges.push(challenge);
         }
@@ -131,9 +683,7 @@ impl Clsag {
                     let mem_response =
                         member
                             .response
-                            .ok_or(Error::UnderlyingErr(String::from(
-                                "member does not have a response value",
-                            )))?;
+                            .ok_or(ClsagError::NotADecoy)?;
                     all_responses.push(mem_response);
                 }
             }
@@ -153,42 +703,440 @@ impl Clsag {
             key_images,
         })
     }
+    // sign_with_device produces a clsag signature where the signers keys are held by a device,
+    // see the device module. The device is a member of the ring with `signer_keys` as its
+    // public keys, and the host computes every challenge, only asking the device for its key
+    // images, its nonce commitments and its response.
+    // Returns an error if the key images from the device do not match its public keys
+    pub(crate) fn sign_with_device<R: RngCore + CryptoRng, D: SignerDevice + ?Sized>(
+        &self,
+        rng: &mut R,
+        device: &mut D,
+        signer_keys: &[PublicKey],
+        msg: &[u8],
+    ) -> Result<(Signature, PublicKeys), ClsagError> {
+        self.traced(|| {
+            self.check_device_format(signer_keys)?;
+            self.check_policy()?;
+
+            let shuffled = self.shuffled(rng);
+            let ring = shuffled.with_nonces(|_| Scalar::random(rng));
+            let num_members = ring.members.len();
+            let mut all_challenges: Vec<Scalar> = Vec::with_capacity(num_members);
+
+            // Find the member of the device
+            let signer_index = ring
+                .ring
+                .iter()
+                .position(|keys| keys[..] == *signer_keys)
+                .ok_or(ClsagError::NoSigner)?;
+            let signer = &ring.members[signer_index];
+            let hashed_pubkey = signer.hashed_pubkey();
+            let pubkey_matrix = ring.public_keys_bytes();
+            let transcript = default_transcript();
+
+            // Fetch the key images from the device, and check them against its public keys
+            let (key_images, proof) = device.key_images(&self.generator, &hashed_pubkey)?;
+            let statements = key_image_statements(
+                &self.generator,
+                &hashed_pubkey,
+                signer.public_set.points(),
+                &key_images,
+            )?;
+            proof.verify(&key_image_transcript(), &statements)?;
+
+            let aggregation_cooeff =
+                calc_aggregation_coefficients(&transcript, &pubkey_matrix, &key_images, msg);
+
+            // The first challenge commits to the nonce of the device
+            let (l, r) = device.commit_nonce(&self.generator, &hashed_pubkey)?;
+            let round = round_transcript(&transcript, &pubkey_matrix, &key_images, msg);
+            let mut challenge = round_challenge(&round, &l, &r);
+            all_challenges.push(challenge);
+
+            for decoy in ring
+                .members
+                .iter()
+                .cycle()
+                .skip(signer_index + 1)
+                .take(num_members - 1)
+            {
+                challenge = decoy.decoy_challenge(
+                    &round,
+                    &challenge,
+                    &key_images,
+                    &aggregation_cooeff,
+                    &self.generator,
+                )?;
+                all_challenges.push(challenge);
+            }
+
+            let signers_response = device.respond(&challenge, &aggregation_cooeff)?;
+            let all_responses = ring
+                .members
+                .iter()
+                .enumerate()
+                .map(|(index, member)| match index == signer_index {
+                    true => Ok(signers_response),
+                    false => member.response.ok_or(ClsagError::NotADecoy),
+                })
+                .collect::<Result<Vec<Scalar>, ClsagError>>()?;
+
+            // Walk back to the challenge of the first member, as in `sign_with_nonces`
+            all_challenges.reverse();
+            let first_challenge = all_challenges[signer_index];
+
+            let signature = Signature {
+                challenge: first_challenge,
+                responses: all_responses,
+                key_images,
+            };
+            Ok((signature, shuffled.ring))
+        })
+    }
+    // sign_deterministic produces a clsag signature where the signers nonce and the
+    // decoys responses are derived from a hash of the signers private keys, the ring and
+    // the message, instead of from the random number generator.
+    // Signing the same message over the same ring will therefore always produce the same
+    // signature, and the security of the signature does not rely on the quality of the rng.
+    // The members are never shuffled, so the signature is made over the ring in the order
+    // the members were added in.
+    pub fn sign_deterministic(&self, msg: &[u8]) -> Result<Signature, ClsagError> {
+        self.traced(|| {
+            self.check_format()?;
+            self.check_policy()?;
+
+            let ring = self.expanded();
+            let signer_index = ring.find_signer()?;
+            let private_keys = ring.members[signer_index]
+                .private_keys()
+                .ok_or(ClsagError::NoSigner)?;
+
+            let mut transcript = Transcript::new(b"clsag_deterministic_nonce");
+            transcript.append_message(b"ring", &ring.public_keys_bytes());
+            transcript.append_message(b"msg", msg);
+            for private_key in private_keys {
+                transcript.append_message(b"private_key", private_key.as_bytes());
+            }
+
+            // Each member is given a value derived from the transcript and their position,
+            // which is the nonce for the signer and the response for the decoys
+            ring.with_nonces(|index| {
+                let mut member_transcript = transcript.clone();
+                member_transcript.append_u64(b"index", index as u64);
+                member_transcript.challenge_scalar(b"nonce")
+            })
+            .sign_with_nonces(&default_transcript(), msg, None)
+        })
+    }
+    // sign_ct produces a clsag signature in the same form as `sign`, without branching on or
+    // indexing by the position of the signer, and only using constant time operations on the
+    // signers private keys and nonce.
+    //
+    // The signers secrets are read by scanning every member and conditionally selecting them.
+    // The ring is then walked twice in order; at every member both the decoy round and the
+    // signers commitment are computed, and the one which continues the ring is selected in
+    // constant time. All scalar multiplications are constant time, including those on public
+    // values, so that the work done at the signers position cannot be told apart from the work
+    // done for the decoys. This makes signing roughly twice as slow as `sign`.
+    // The signers nonce and the decoys responses are drawn from the thread rng.
+    pub fn sign_ct(&self, msg: &[u8]) -> Result<(Signature, PublicKeys), ClsagError> {
+        self.sign_ct_with_rng(&mut rand::thread_rng(), msg)
+    }
+    // sign_ct_with_rng is `sign_ct`, drawing the order of the ring,
+    // the signers nonce and the decoys responses from the given rng
+    pub fn sign_ct_with_rng<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        msg: &[u8],
+    ) -> Result<(Signature, PublicKeys), ClsagError> {
+        self.traced(|| {
+            // One value is drawn per member, which is used as the nonce if the
+            // member is the signer and as the response otherwise
+            self.check_format_ct()?;
+            self.check_policy()?;
+
+            let ring = self.shuffled(rng);
+            let values: Vec<Scalar> = (0..ring.members.len())
+                .map(|_| Scalar::random(rng))
+                .collect();
+            let signature =
+                ring.expanded()
+                    .sign_ct_with_values(&default_transcript(), msg, &values)?;
+
+            Ok((signature, ring.ring))
+        })
+    }
+    fn sign_ct_with_values<T: SigningTranscript>(
+        &self,
+        transcript: &T,
+        msg: &[u8],
+        values: &[Scalar],
+    ) -> Result<Signature, ClsagError> {
+        self.check_format_ct()?;
+
+        let num_members = self.members.len();
+        let num_keys = self.members[0].num_keys();
+
+        // Select the signers position and secrets without branching on which member is the signer
+        let zero_keys = PrivateSet::new(vec![Scalar::zero(); num_keys]);
+        let mut signer_index = 0u64;
+        let mut private_keys = vec![Scalar::zero(); num_keys];
+        let mut nonce = Scalar::zero();
+        let mut signers_hashed_pubkey = RistrettoPoint::identity();
+        for (index, member) in self.members.iter().enumerate() {
+            let (is_signer, member_keys) = member.private_keys_ct(&zero_keys);
+
+            signer_index.conditional_assign(&(index as u64), is_signer);
+            for (key, member_key) in private_keys.iter_mut().zip(member_keys.iter()) {
+                key.conditional_assign(member_key, is_signer);
+            }
+            nonce.conditional_assign(&values[index], is_signer);
+            signers_hashed_pubkey.conditional_assign(&member.hashed_pubkey(), is_signer);
+        }
+
+        // Fetch public key matrix
+        let pubkey_matrix = self.public_keys_bytes();
+
+        // Compute key images for signer
+        let key_image_points: Vec<RistrettoPoint> = private_keys
+            .iter()
+            .map(|x| x * signers_hashed_pubkey)
+            .collect();
+        let key_images: Vec<KeyImage> = key_image_points
+            .iter()
+            .map(|ki| KeyImage(ki.compress()))
+            .collect();
+
+        // Calculate aggregation co-efficients
+        let aggregation_cooeff =
+            calc_aggregation_coefficients(transcript, &pubkey_matrix, &key_images, msg);
+
+        let rounds = round_transcript(transcript, &pubkey_matrix, &key_images, msg);
+
+        // The signers commitment to their nonce
+        let signers_l = nonce * self.generator;
+        let signers_r = nonce * signers_hashed_pubkey;
+
+        let mut challenge = Scalar::zero();
+        let mut first_challenge = Scalar::zero();
+        let mut signers_challenge = Scalar::zero();
+        // Set once the signers commitment has been added to the ring
+        let mut started = Choice::from(0);
+        // Set once the ring has come back around to the signer
+        let mut closed = Choice::from(0);
+
+        for round in 0..2 * num_members {
+            let index = round % num_members;
+            let member = &self.members[index];
+            let is_signer = (index as u64).ct_eq(&signer_index);
+
+            // L = response * generator + challenge * sum(mu_j * public_keys)
+            // R = response * hashed_pubkey + challenge * sum(mu_j * key_images)
+            let response = values[index];
+            let challenge_agg_coeffs: Vec<Scalar> =
+                aggregation_cooeff.iter().map(|mu| mu * challenge).collect();
+            let decoys_l = RistrettoPoint::multiscalar_mul(
+                iter::once(&response).chain(challenge_agg_coeffs.iter()),
+                iter::once(&self.generator).chain(member.public_set.points().iter()),
+            );
+            let decoys_r = RistrettoPoint::multiscalar_mul(
+                iter::once(&response).chain(challenge_agg_coeffs.iter()),
+                iter::once(&member.hashed_pubkey()).chain(key_image_points.iter()),
+            );
+
+            let l = RistrettoPoint::conditional_select(&decoys_l, &signers_l, is_signer);
+            let r = RistrettoPoint::conditional_select(&decoys_r, &signers_r, is_signer);
+            let next_challenge = round_challenge(&rounds, &l, &r);
+
+            // On the first pass, the ring starts at the signer.
+            // On the second pass, it continues until it is back at the signer
+            let update = if round < num_members {
+                started |= is_signer;
+                started
+            } else {
+                signers_challenge.conditional_assign(&challenge, is_signer);
+                closed |= is_signer;
+                !closed
+            };
+            challenge.conditional_assign(&next_challenge, update);
+
+            // The challenge produced by the last member is the first members challenge
+            if index == num_members - 1 {
+                first_challenge.conditional_assign(&challenge, update);
+            }
+        }
+
+        // response = nonce - challenge * sum(mu_j * private_keys)
+        let mut sum_aux: Scalar = private_keys
+            .iter()
+            .zip(aggregation_cooeff.iter())
+            .map(|(x, mu)| x * mu)
+            .sum();
+        let signers_response = nonce - signers_challenge * sum_aux;
+
+        zeroize_scalar(&mut sum_aux);
+        zeroize_scalar(&mut nonce);
+        for key in private_keys.iter_mut() {
+            zeroize_scalar(key);
+        }
+
+        let responses: Vec<Scalar> = values
+            .iter()
+            .enumerate()
+            .map(|(index, response)| {
+                let is_signer = (index as u64).ct_eq(&signer_index);
+                Scalar::conditional_select(response, &signers_response, is_signer)
+            })
+            .collect();
+
+        Ok(Signature {
+            challenge: first_challenge,
+            responses,
+            key_images,
+        })
+    }
     // Returns the position of the signer in the ring
     // If this call is completed after check_format, it should not fail
     // as check_format ensures there is one signer. This method has been
     // added for a cleaner API. The alternative would be for the method which checks
     // that clsag is formatted correctly, to also return the signer.
-    pub fn find_signer(&self) -> Result<usize, Error> {
+    pub fn find_signer(&self) -> Result<usize, ClsagError> {
         let signer_index = self
             .members
             .iter()
             .position(|member| member.is_signer())
-            .ok_or(Error::NoSigner)?;
+            .ok_or(ClsagError::NoSigner)?;
 
         Ok(signer_index)
     }
-    // Returns the number of signers in the ring
-    pub fn num_signers(&self) -> usize {
-        let signers: Vec<&Member> = self
-            .members
+    // Returns the number of members in the ring which hold private keys.
+    // A ring can only be signed if this is exactly one
+    pub fn signer_count(&self) -> usize {
+        self.members
             .iter()
             .filter(|member| member.is_signer())
-            .collect();
-        signers.len()
+            .count()
+    }
+    // Checks that the ring satisfies the ring policy.
+    // This is only checked on the ring given by the caller, as the copies made while signing
+    // hold the commitment to zero as an extra key
+    pub(crate) fn check_policy(&self) -> Result<(), ClsagError> {
+        self.policy.check_ring_size(self.members.len())?;
+        self.members
+            .iter()
+            .try_for_each(|member| self.policy.check_num_keys(member.num_keys()))
+    }
+    // Checks that a ring signed by a device is correctly constructed. The device is
+    // a member without private keys, so the ring must not hold any other signer.
+    // Rings signed by a device do not hold commitments
+    pub(crate) fn check_device_format(&self, signer_keys: &[PublicKey]) -> Result<(), ClsagError> {
+        match self.members.len() {
+            0 => return Err(ClsagError::EmptyRing),
+            1 => return Err(ClsagError::RingTooSmall),
+            _ => (),
+        };
+        if self.signer_count() != 0 {
+            return Err(ClsagError::MultipleSignersNotSupported);
+        }
+        if self.pseudo_out.is_some() {
+            return Err(ClsagError::CommitmentMismatch);
+        }
+        let all_same_num_keys = self
+            .members
+            .iter()
+            .all(|member| member.num_keys() == signer_keys.len());
+        if !all_same_num_keys {
+            return Err(ClsagError::KeyCountMismatch);
+        }
+        Ok(())
     }
     // Checks that the clsag is correctly constructed
-    fn check_format(&self) -> Result<(), Error> {
+    pub(crate) fn check_format(&self) -> Result<(), ClsagError> {
         // Check that we have more than one member
-        if self.members.len() < 2 {
-            return Err(Error::NotEnoughMembers);
+        match self.members.len() {
+            0 => return Err(ClsagError::EmptyRing),
+            1 => return Err(ClsagError::RingTooSmall),
+            _ => (),
+        };
+
+        // Check there is only one signer in the ring
+        match self.signer_count() {
+            0 => return Err(ClsagError::NoSigner),
+            1 => (),
+            _ => return Err(ClsagError::MultipleSignersNotSupported),
+        };
+
+        // Check that each member has the same number of keys
+        let first_member_num_keys = self.members[0].num_keys();
+        let all_same_num_keys = self
+            .members
+            .iter()
+            .all(|member| member.num_keys() == first_member_num_keys);
+        if !all_same_num_keys {
+            return Err(ClsagError::KeyCountMismatch);
+        }
+
+        // Check that no decoy has a key of the signer. `add_member` already rejects these,
+        // this is checked again as a ring with the key of the signer reveals who signed
+        let signer_index = self.find_signer()?;
+        let signer_keys = &self.ring[signer_index][..self.members[signer_index].num_keys()];
+        let decoy_equals_signer = self
+            .members
+            .iter()
+            .zip(&self.ring)
+            .position(|(member, keys)| {
+                !member.is_signer()
+                    && keys[..member.num_keys()]
+                        .iter()
+                        .any(|key| signer_keys.contains(key))
+            });
+        if let Some(index) = decoy_equals_signer {
+            return Err(ClsagError::DecoyEqualsSigner { index });
         }
 
+        // Check that either every member holds a commitment or none of them do
+        let num_commitments = self
+            .members
+            .iter()
+            .filter(|member| member.commitment().is_some())
+            .count();
+        if num_commitments != 0 && num_commitments != self.members.len() {
+            return Err(ClsagError::CommitmentMismatch);
+        }
+
+        // Check that the signer can open their commitment to zero
+        let pseudo_out = self.pseudo_out();
+        let opens_commitment = self
+            .members
+            .iter()
+            .all(|member| member.opens_commitment(&pseudo_out, &self.generator));
+        if !opens_commitment {
+            return Err(ClsagError::BadCommitmentKey);
+        }
+        Ok(())
+    }
+    // Checks that the clsag is correctly constructed, as `check_format` does, for the
+    // constant time signing path. The checks which involve the signer visit every member
+    // and accumulate their result with `Choice`, so they never stop early at the signer
+    pub(crate) fn check_format_ct(&self) -> Result<(), ClsagError> {
+        // Check that we have more than one member
+        match self.members.len() {
+            0 => return Err(ClsagError::EmptyRing),
+            1 => return Err(ClsagError::RingTooSmall),
+            _ => (),
+        };
+
         // Check there is only one signer in the ring
-        let num_signers = self.num_signers();
+        let num_signers: u64 = self
+            .members
+            .iter()
+            .map(|member| Choice::from(member.is_signer() as u8).unwrap_u8() as u64)
+            .sum();
         match num_signers {
-            0 => return Err(Error::NoSigner),
+            0 => return Err(ClsagError::NoSigner),
             1 => (),
-            _ => return Err(Error::MoreThanOneSigner),
+            _ => return Err(ClsagError::MultipleSignersNotSupported),
         };
 
         // Check that each member has the same number of keys
@@ -198,56 +1146,167 @@ impl Clsag {
             .iter()
             .all(|member| member.num_keys() == first_member_num_keys);
         if !all_same_num_keys {
-            return Err(Error::NumberOfKeysMismatch);
+            return Err(ClsagError::KeyCountMismatch);
+        }
+
+        // Check that no decoy has a key of the signer, comparing the keys of every pair of
+        // members and keeping the first decoy which shares a key with the signer
+        let mut decoy_equals_signer = Choice::from(0);
+        let mut decoy_index = 0u64;
+        for (signer, signer_keys) in self.members.iter().zip(&self.ring) {
+            let is_signer = Choice::from(signer.is_signer() as u8);
+            for (index, (decoy, decoy_keys)) in self.members.iter().zip(&self.ring).enumerate() {
+                let is_decoy = Choice::from(!decoy.is_signer() as u8);
+                let mut shares_key = Choice::from(0);
+                for decoy_key in &decoy_keys[..decoy.num_keys()] {
+                    for signer_key in &signer_keys[..signer.num_keys()] {
+                        shares_key |= decoy_key.as_bytes().ct_eq(signer_key.as_bytes());
+                    }
+                }
+                let found = is_signer & is_decoy & shares_key & !decoy_equals_signer;
+                decoy_index.conditional_assign(&(index as u64), found);
+                decoy_equals_signer |= found;
+            }
+        }
+        if bool::from(decoy_equals_signer) {
+            let index = decoy_index as usize;
+            return Err(ClsagError::DecoyEqualsSigner { index });
         }
 
-        // Check that each member has no duplicates
-        let no_duplicates_exists = self
+        // Check that either every member holds a commitment or none of them do
+        let num_commitments = self
             .members
             .iter()
-            .all(|member| !member.public_set.duplicates_exist());
-        if !no_duplicates_exists {
-            return Err(Error::DuplicateKeysExist);
+            .filter(|member| member.commitment().is_some())
+            .count();
+        if num_commitments != 0 && num_commitments != self.members.len() {
+            return Err(ClsagError::CommitmentMismatch);
+        }
+
+        // Check that the signer can open their commitment to zero
+        let pseudo_out = self.pseudo_out();
+        let opens_commitment = self.members.iter().fold(Choice::from(1), |opens, member| {
+            opens & member.opens_commitment_ct(&pseudo_out, &self.generator)
+        });
+        if !bool::from(opens_commitment) {
+            return Err(ClsagError::BadCommitmentKey);
         }
         Ok(())
     }
 }
-// Calculates the aggregation co-efficients mu_x and [mu_j]
-pub fn calc_aggregation_coefficients(
-    pubkey_matrix: &[u8],
-    key_images: &[CompressedRistretto],
-    message: &[u8],
-) -> Vec<Scalar> {
-    // precompute (pubkey_matrix || keyimages)
-    let key_images_bytes: Vec<u8> = key_images
+// Verifies a batch of signatures, each with its own ring and message
+//
+// Unlike Schnorr signatures, the verification equations of a CLSAG signature cannot be
+// combined using random weights. Each challenge is the hash of the points computed in
+// the previous round, so every L_i and R_i has to be computed explicitly before the next
+// challenge is known. What the batch does share is the work on the rings: every key is
+// decompressed and hashed once for the whole batch, however many rings it is a member of,
+// and a ring shared by several signatures is prepared once, see `PreparedRing`.
+// Rather than stopping at the first bad signature, the index of every signature that
+// failed is returned so that callers syncing many signatures can discard only those.
+pub fn batch_verify(batch: &[(Signature, PublicKeys, &[u8])]) -> Result<(), BatchError> {
+    let mut rings = BatchRings::default();
+    let failures: Vec<(usize, ClsagError)> = batch
         .iter()
-        .flat_map(|key_image| key_image.to_bytes().to_vec())
+        .enumerate()
+        .filter_map(|(index, (signature, public_keys, msg))| {
+            rings
+                .verify(signature, public_keys, msg)
+                .err()
+                .map(|err| (index, err))
+        })
         .collect();
 
-    let num_keys_per_user = key_images.len();
+    BatchError::from_failures(failures)
+}
 
-    let mut agg_coef = Vec::with_capacity(num_keys_per_user);
+// Verifies a batch of signatures like `batch_verify`, spreading the signatures
+// over the rayon thread pool. Every signature prepares its own ring, so the work on
+// the rings is not shared between threads. Failures are reported in the order of the batch
+#[cfg(feature = "parallel")]
+pub fn verify_all_par(batch: &[(Signature, PublicKeys, &[u8])]) -> Result<(), BatchError> {
+    let failures: Vec<(usize, ClsagError)> = batch
+        .par_iter()
+        .enumerate()
+        .filter_map(|(index, (signature, public_keys, msg))| {
+            signature
+                .optimised_verify(public_keys, msg)
+                .err()
+                .map(|err| (index, err))
+        })
+        .collect();
 
-    for i in 0..num_keys_per_user {
-        let mut transcript = Transcript::new(b"clsag");
-        transcript.append_message(b"msg", message);
+    BatchError::from_failures(failures)
+}
 
-        transcript.append_u64(b"", i as u64);
-        transcript.append_message(b"", pubkey_matrix);
-        transcript.append_message(b"", &key_images_bytes);
+// Calculates the aggregation co-efficients [mu_j], one for each layer of keys.
+// Each co-efficient is computed from its own clone of the transcript, which is domain
+// separated by the total number of layers and the index of the layer it is for
+pub fn calc_aggregation_coefficients<T: SigningTranscript>(
+    transcript: &T,
+    pubkey_matrix: &[u8],
+    key_images: &[KeyImage],
+    message: &[u8],
+) -> Vec<Scalar> {
+    let transcript = aggregation_transcript(transcript, pubkey_matrix, key_images.len());
+    aggregation_coefficients(&transcript, key_images, message)
+}
 
-        agg_coef.push(transcript.challenge_scalar(b""));
-    }
+// Appends the ring to a clone of the transcript, which the aggregation co-efficients
+// of every signature over the ring are computed from
+pub(crate) fn aggregation_transcript<T: SigningTranscript>(
+    transcript: &T,
+    pubkey_matrix: &[u8],
+    num_layers: usize,
+) -> T {
+    let mut transcript = transcript.clone();
+    transcript.append_domain(b"clsag_agg");
+    transcript.append_ring(num_layers, pubkey_matrix);
+    transcript
+}
 
+// Calculates the aggregation co-efficients as `calc_aggregation_coefficients` does,
+// with a transcript the ring has been appended to by `aggregation_transcript`
+pub(crate) fn aggregation_coefficients<T: SigningTranscript>(
+    aggregation_transcript: &T,
+    key_images: &[KeyImage],
+    message: &[u8],
+) -> Vec<Scalar> {
+    let mut agg_coef = vec![Scalar::zero(); key_images.len()];
+    aggregation_coefficients_into(aggregation_transcript, key_images, message, &mut agg_coef);
     agg_coef
 }
 
+// Calculates the aggregation co-efficients as `aggregation_coefficients` does,
+// writing one co-efficient per key image into `agg_coef` instead of allocating them
+pub(crate) fn aggregation_coefficients_into<T: SigningTranscript>(
+    aggregation_transcript: &T,
+    key_images: &[KeyImage],
+    message: &[u8],
+    agg_coef: &mut [Scalar],
+) {
+    // The key images and the message are the same for every layer,
+    // so they are hashed once and only the layer is hashed per co-efficient
+    let mut signed = aggregation_transcript.clone();
+    signed.append_key_images(key_images);
+    signed.append_msg(message);
+
+    for (layer, mu) in agg_coef.iter_mut().enumerate().take(key_images.len()) {
+        let mut transcript = signed.clone();
+        transcript.append_layer(layer);
+
+        *mu = transcript.challenge_scalar(b"mu");
+    }
+}
+
 #[cfg(test)]
 mod test {
     extern crate test;
 
     use super::*;
-    use crate::tests_helper::*;
+    use crate::test_utils::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaChaRng;
     use test::Bencher;
 
     #[test]
@@ -257,53 +1316,153 @@ mod test {
         let mut clsag = generate_clsag_with(num_decoys, num_keys);
         let msg = b"hello world";
         // No signer in the ring
+        assert_eq!(0, clsag.signer_count());
         match clsag.sign(msg) {
             Ok(_) => panic!("expected an error as there is no signer in the ring"),
-            Err(Error::NoSigner) => {}
+            Err(ClsagError::NoSigner) => {}
             Err(_) => panic!("got an error, however we expected no signer error"),
         }
 
         // Add a signer
-        clsag.add_member(generate_signer(num_keys));
+        clsag.add_member(generate_signer(num_keys)).unwrap();
         // Another one
-        clsag.add_member(generate_signer(num_keys));
+        clsag.add_member(generate_signer(num_keys)).unwrap();
 
         // More than one signer in the ring
         match clsag.sign(msg) {
             Ok(_) => panic!("expected an error as there are too many signers in the ring"),
-            Err(Error::MoreThanOneSigner) => {}
+            Err(ClsagError::MultipleSignersNotSupported) => {}
             Err(_) => panic!("got an error, however we expected a more than one signer error"),
         }
+        assert_eq!(2, clsag.signer_count());
+        match clsag.sign_deterministic(msg) {
+            Err(ClsagError::MultipleSignersNotSupported) => {}
+            _ => panic!("expected a more than one signer error"),
+        }
 
         clsag = generate_clsag_with(num_decoys, num_keys);
-        // Add different number of keys
-        clsag.add_member(generate_decoy(num_keys + 1));
-
-        // Add correct signer
-        clsag.add_member(generate_signer(num_keys));
-
-        // One member has a different number of keys
-        match clsag.sign(msg) {
+        assert_eq!(num_keys, clsag.key_count());
+        // A member with a different number of keys is rejected when it is added
+        match clsag.add_member(generate_decoy(num_keys + 1)) {
             Ok(_) => {
-                panic!("expected an error as one member has more keys than another in the ring")
+                panic!("expected an error as the member has more keys than the others in the ring")
             }
-            Err(Error::NumberOfKeysMismatch) => {}
-            Err(_) => panic!("got an error, however we expected a `number of keys mismatch` error"),
+            Err(err) => {
+                assert_eq!(
+                    ClsagError::MemberKeyCountMismatch {
+                        expected: num_keys,
+                        got: num_keys + 1,
+                        index: num_decoys,
+                    },
+                    err
+                );
+                assert_eq!(
+                    "the member at index 10 has 4 keys, the ring has 3 keys per member",
+                    err.to_string()
+                );
+            }
+        };
+        match clsag.replace_member(3, generate_signer(num_keys - 1)) {
+            Err(ClsagError::MemberKeyCountMismatch { index: 3, .. }) => {}
+            _ => panic!("expected a member key count mismatch error"),
         };
+        assert_eq!(num_decoys, clsag.members().len());
+        assert_eq!(0, Clsag::new().key_count());
 
         clsag = generate_clsag_with(num_decoys, num_keys);
         // Add correct signer
-        clsag.add_member(generate_signer(num_keys));
+        clsag.add_member(generate_signer(num_keys)).unwrap();
+
+        // A member whose first key is the same as its last key
+        let mut public_keys = generate_decoy(num_keys).public_set.points().to_vec();
+        public_keys[0] = *public_keys.last().unwrap();
+        match clsag.add_member(Member::new_decoy(public_keys)) {
+            Ok(_) => panic!("expected an error as the member has a duplicate key"),
+            Err(ClsagError::DuplicateKey(index)) => assert_eq!(num_decoys + 1, index),
+            Err(_) => panic!("got an error, however we expected a `duplicate key` error"),
+        };
+
+        // A member with a key another member already has
+        let mut public_keys = generate_decoy(num_keys).public_set.points().to_vec();
+        public_keys[1] = clsag.members[2].public_set.points()[0];
+        match clsag.add_member(Member::new_decoy(public_keys)) {
+            Err(err) => {
+                assert_eq!(ClsagError::DuplicateKey(num_decoys + 1), err);
+                assert_eq!(
+                    "the member at index 11 has a duplicate key",
+                    err.to_string()
+                );
+            }
+            Ok(_) => panic!("expected an error as another member has the key"),
+        };
+        assert_eq!(num_decoys + 1, clsag.public_keys().len());
+        assert!(clsag.sign(msg).is_ok());
+
+        // An empty ring and a ring with only the signer
+        clsag = Clsag::new();
+        match clsag.sign(msg) {
+            Err(ClsagError::EmptyRing) => {}
+            _ => panic!("expected an empty ring error"),
+        };
+        clsag.add_member(generate_signer(num_keys)).unwrap();
+        match clsag.sign(msg) {
+            Err(err) => {
+                assert_eq!(ClsagError::RingTooSmall, err);
+                assert_eq!("the ring has too few members", err.to_string());
+            }
+            Ok(_) => panic!("expected a ring too small error"),
+        };
+    }
 
-        // Set the first key in members key set to the value of the last key
-        let first_member = &mut clsag.members[0];
-        let first_member_last_element = &mut first_member.public_set.0.last().unwrap();
-        first_member.public_set.0[0] = first_member_last_element.clone();
+    #[test]
+    fn test_ring_policy() {
+        let num_keys = 2;
+        let msg = b"hello world";
 
+        // The default policy requires at least 11 members
+        let mut clsag = Clsag::new();
+        for decoy in generate_decoys(9, num_keys) {
+            clsag.add_member(decoy).unwrap();
+        }
+        clsag.add_member(generate_signer(num_keys)).unwrap();
         match clsag.sign(msg) {
-            Ok(_) => panic!("expected an error as one member has a duplicate key"),
-            Err(Error::DuplicateKeysExist) => {}
-            Err(_) => panic!("got an error, however we expected a `duplicate keys` error"),
+            Err(ClsagError::RingTooSmall) => {}
+            _ => panic!("expected a ring too small error"),
+        };
+        match clsag.sign_deterministic(msg) {
+            Err(ClsagError::RingTooSmall) => {}
+            _ => panic!("expected a ring too small error"),
+        };
+        clsag.add_member(generate_decoy(num_keys)).unwrap();
+        assert!(clsag.sign(msg).is_ok());
+
+        // Members are rejected once the ring is full, or if they have the wrong number of keys
+        let mut clsag = Clsag::new();
+        clsag.set_ring_policy(RingPolicy {
+            min_ring_size: 2,
+            max_ring_size: Some(3),
+            num_keys: Some(num_keys),
+        });
+        assert_eq!(
+            Err(ClsagError::KeyCountMismatch),
+            clsag.add_member(generate_decoy(num_keys + 1))
+        );
+        for decoy in generate_decoys(2, num_keys) {
+            clsag.add_member(decoy).unwrap();
+        }
+        clsag.add_member(generate_signer(num_keys)).unwrap();
+        assert_eq!(
+            Err(ClsagError::RingTooLarge),
+            clsag.add_member(generate_decoy(num_keys))
+        );
+        assert_eq!(3, clsag.public_keys().len());
+        assert!(clsag.sign(msg).is_ok());
+
+        // A stricter policy set after the members were added is checked when signing
+        clsag.set_ring_policy(RingPolicy::default());
+        match clsag.sign_ct(msg) {
+            Err(ClsagError::RingTooSmall) => {}
+            _ => panic!("expected a ring too small error"),
         };
     }
 
@@ -315,10 +1474,11 @@ mod test {
         let msg = b"hello world";
 
         // Add a signer
-        clsag.add_member(generate_signer(num_keys));
+        clsag.add_member(generate_signer(num_keys)).unwrap();
 
         // Should produce no error
-        let signature = clsag.sign(msg).unwrap();
+        let (signature, public_keys) = clsag.sign(msg).unwrap();
+        assert_eq!(num_decoys + 1, public_keys.len());
 
         // number of key images should equal number of keys
         assert_eq!(num_keys, signature.key_images.len());
@@ -329,6 +1489,813 @@ mod test {
         assert_eq!(num_members, signature.responses.len());
     }
 
+    #[test]
+    fn test_batch_verify() {
+        let num_keys = 2;
+        let num_decoys = 4;
+
+        let messages: Vec<Vec<u8>> = (0..5u8).map(|i| vec![i; 32]).collect();
+        let mut batch = Vec::new();
+        for msg in &messages {
+            let mut clsag = generate_clsag_with(num_decoys, num_keys);
+            clsag.add_member(generate_signer(num_keys)).unwrap();
+            let (signature, public_keys) = clsag.sign(msg).unwrap();
+            batch.push((signature, public_keys, msg.as_slice()));
+        }
+
+        assert!(batch_verify(&batch).is_ok());
+
+        // Swap the messages of the second and last entries
+        batch[1].2 = &messages[4];
+        batch[4].2 = &messages[1];
+
+        match batch_verify(&batch) {
+            Ok(_) => panic!("expected an error as two signatures have the wrong message"),
+            Err(err) => {
+                let failed: Vec<usize> = err.failures.iter().map(|(index, _)| *index).collect();
+                assert_eq!(failed, vec![1, 4]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_batch_verify_shared_rings() {
+        let num_keys = 2;
+        let msg = b"hello world";
+
+        // Every ring picks its decoys from the same outputs, and the first ring is signed twice
+        let outputs = generate_decoys(8, num_keys);
+        let mut batch = Vec::new();
+        for i in 0..4 {
+            let mut clsag = Clsag::new();
+            clsag.set_ring_policy(RingPolicy::permissive());
+            for decoy in outputs.iter().skip(i).take(4) {
+                clsag.add_member(decoy.clone()).unwrap();
+            }
+            clsag.add_member(generate_signer(num_keys)).unwrap();
+            clsag.set_shuffle_members(false);
+            for _ in 0..(if i == 0 { 2 } else { 1 }) {
+                let (signature, public_keys) = clsag.sign(msg).unwrap();
+                batch.push((signature, public_keys, &msg[..]));
+            }
+        }
+        assert_eq!(batch[0].1, batch[1].1);
+        assert!(batch_verify(&batch).is_ok());
+
+        // Signatures over a shared ring fail on their own, with the errors of `optimised_verify`
+        batch[1].2 = b"another message";
+        batch[3].1.pop();
+        let expected: Vec<(usize, ClsagError)> = batch
+            .iter()
+            .enumerate()
+            .filter_map(|(index, (signature, public_keys, msg))| {
+                signature
+                    .optimised_verify(public_keys, msg)
+                    .err()
+                    .map(|err| (index, err))
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                (1, ClsagError::ChallengeMismatch),
+                (3, ClsagError::RingSizeMismatch)
+            ],
+            expected
+        );
+        match batch_verify(&batch) {
+            Err(err) => assert_eq!(expected, err.failures),
+            Ok(_) => panic!("expected two signatures to fail"),
+        }
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_verify_all_par() {
+        let num_keys = 2;
+        let num_decoys = 4;
+
+        let messages: Vec<Vec<u8>> = (0..8u8).map(|i| vec![i; 32]).collect();
+        let mut batch = Vec::new();
+        for msg in &messages {
+            let mut clsag = generate_clsag_with(num_decoys, num_keys);
+            clsag.add_member(generate_signer(num_keys)).unwrap();
+            let (signature, public_keys) = clsag.sign(msg).unwrap();
+            batch.push((signature, public_keys, msg.as_slice()));
+        }
+
+        assert!(verify_all_par(&batch).is_ok());
+
+        batch[2].2 = &messages[5];
+        batch[6].2 = &messages[0];
+
+        match verify_all_par(&batch) {
+            Ok(_) => panic!("expected an error as two signatures have the wrong message"),
+            Err(err) => {
+                let failed: Vec<usize> = err.failures.iter().map(|(index, _)| *index).collect();
+                assert_eq!(failed, vec![2, 6]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_aggregation_coefficients_per_layer() {
+        let num_keys = 3;
+        let mut clsag = generate_clsag_with(4, num_keys);
+        clsag.add_member(generate_signer(num_keys)).unwrap();
+        assert_eq!(num_keys, clsag.num_layers());
+
+        let (signature, public_keys) = clsag.sign(b"hello world").unwrap();
+        assert_eq!(num_keys, signature.num_layers());
+
+        // Every layer gets its own co-efficient
+        let pubkey_matrix: Vec<u8> = public_keys
+            .iter()
+            .flatten()
+            .flat_map(|key| key.to_bytes().to_vec())
+            .collect();
+        let transcript = default_transcript();
+        let agg_coeffs = calc_aggregation_coefficients(
+            &transcript,
+            &pubkey_matrix,
+            &signature.key_images,
+            b"hello world",
+        );
+        assert_eq!(num_keys, agg_coeffs.len());
+        assert_ne!(agg_coeffs[0], agg_coeffs[1]);
+        assert_ne!(agg_coeffs[1], agg_coeffs[2]);
+
+        // The co-efficients depend on the number of layers,
+        // not just on the bytes of the key images
+        let fewer_layers = calc_aggregation_coefficients(
+            &transcript,
+            &pubkey_matrix,
+            &signature.key_images[..2],
+            b"hello world",
+        );
+        assert_ne!(agg_coeffs[0], fewer_layers[0]);
+    }
+
+    #[test]
+    fn test_sign_with_transcript() {
+        let num_decoys = 6;
+        let num_keys = 2;
+        let msg = b"hello world";
+        let mut clsag = generate_clsag_with(num_decoys, num_keys);
+        clsag.add_member(generate_signer(num_keys)).unwrap();
+
+        let mut transcript = default_transcript();
+        transcript.append_context(b"app", b"payments v1");
+        let (signature, public_keys) = clsag.sign_with_transcript(&transcript, msg).unwrap();
+        assert!(signature
+            .verify_with_transcript(&transcript, &public_keys, msg)
+            .is_ok());
+
+        // The context is bound into the signature
+        let mut other = default_transcript();
+        other.append_context(b"app", b"payments v2");
+        assert!(signature
+            .verify_with_transcript(&other, &public_keys, msg)
+            .is_err());
+        assert!(signature.optimised_verify(&public_keys, msg).is_err());
+
+        // The default transcript is the one used by `sign`
+        let (signature, public_keys) = clsag.sign(msg).unwrap();
+        assert!(signature
+            .verify_with_transcript(&default_transcript(), &public_keys, msg)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_sign_with_context() {
+        let num_decoys = 6;
+        let num_keys = 2;
+        let msg = b"hello world";
+        let mut clsag = generate_clsag_with(num_decoys, num_keys);
+        clsag.add_member(generate_signer(num_keys)).unwrap();
+
+        let (signature, public_keys) = clsag.sign_with_context(msg, b"protocol a").unwrap();
+        assert!(signature
+            .verify_with_context(&public_keys, msg, b"protocol a")
+            .is_ok());
+
+        // The signature cannot be replayed under another context, or without one
+        assert!(signature
+            .verify_with_context(&public_keys, msg, b"protocol b")
+            .is_err());
+        assert!(signature
+            .verify_with_context(&public_keys, msg, b"")
+            .is_err());
+        assert!(signature.optimised_verify(&public_keys, msg).is_err());
+    }
+
+    #[test]
+    fn test_sign_with_hash_to_point() {
+        use crate::hash::DomainSeparated;
+        use crate::ring::PreparedRing;
+        use blake2::Blake2b;
+
+        let num_decoys = 6;
+        let num_keys = 2;
+        let msg = b"hello world";
+        let mut clsag = generate_clsag_with(num_decoys, num_keys);
+        clsag.set_hash_to_point(DomainSeparated::<Blake2b>::new());
+        clsag.add_member(generate_signer(num_keys)).unwrap();
+
+        // Both the members added before and after the construction is set are hashed with it
+        let (signature, public_keys) = clsag.sign(msg).unwrap();
+        let ring =
+            PreparedRing::with_hash_to_point(&public_keys, &DomainSeparated::<Blake2b>::new())
+                .unwrap();
+        assert!(signature.verify_prepared(&ring, msg).is_ok());
+        let (signature, public_keys) = clsag.sign_ct(msg).unwrap();
+        let ring =
+            PreparedRing::with_hash_to_point(&public_keys, &DomainSeparated::<Blake2b>::new())
+                .unwrap();
+        assert!(signature.verify_prepared(&ring, msg).is_ok());
+
+        // The signature does not verify with the default construction
+        assert!(signature.optimised_verify(&public_keys, msg).is_err());
+    }
+
+    #[test]
+    fn test_sign_with_generator() {
+        use crate::hash::hash_pubkey;
+        use crate::keys::{compute_key_image, SecretKey};
+
+        let msg = b"hello world";
+        let generator = hash_pubkey(b"another generator for the keys!!");
+        let secret_key = SecretKey::random(&mut rand::thread_rng());
+
+        let mut clsag = Clsag::with_generator(generator);
+        clsag.set_ring_policy(RingPolicy::permissive());
+        for decoy in generate_decoys(5, 1) {
+            clsag.add_member(decoy).unwrap();
+        }
+        clsag
+            .add_member(Member::signer_from_secret_keys(std::slice::from_ref(&secret_key)).unwrap())
+            .unwrap();
+        assert_eq!(&generator, clsag.generator());
+
+        // The signers key is computed over the generator, and their key image over that key
+        let public_key = secret_key.public_key_with_generator(&generator);
+        assert_eq!(vec![public_key], clsag.public_keys()[5]);
+        let (signature, public_keys) = clsag.sign(msg).unwrap();
+        assert_eq!(
+            compute_key_image(&secret_key, &public_key),
+            signature.key_images()[0]
+        );
+        assert!(signature
+            .verify_with_generator(&public_keys, msg, &generator)
+            .is_ok());
+        let (signature, public_keys) = clsag.sign_ct(msg).unwrap();
+        assert!(signature
+            .verify_with_generator(&public_keys, msg, &generator)
+            .is_ok());
+        let signature = clsag.sign_deterministic(msg).unwrap();
+        assert!(signature
+            .verify_with_generator(clsag.public_keys(), msg, &generator)
+            .is_ok());
+
+        // The signature does not verify over the basepoint
+        assert!(signature
+            .optimised_verify(clsag.public_keys(), msg)
+            .is_err());
+    }
+
+    #[test]
+    fn test_sign_shuffles_members() {
+        let num_decoys = 10;
+        let num_keys = 2;
+        let msg = b"hello world";
+        let mut clsag = generate_clsag_with(num_decoys, num_keys);
+        clsag.add_member(generate_signer(num_keys)).unwrap();
+        let ring = clsag.public_keys().to_vec();
+
+        // The signature only verifies against the shuffled ring returned with it
+        let (signature, mut public_keys) = clsag
+            .sign_with_rng(&mut ChaChaRng::from_seed([1u8; 32]), msg)
+            .unwrap();
+        assert_ne!(ring, public_keys);
+        assert!(signature.optimised_verify(&public_keys, msg).is_ok());
+        assert!(signature.optimised_verify(&ring, msg).is_err());
+
+        // Every member is still in the ring
+        let mut sorted_ring = ring.clone();
+        sorted_ring.sort_by_key(|keys| keys[0].to_bytes());
+        public_keys.sort_by_key(|keys| keys[0].to_bytes());
+        assert_eq!(sorted_ring, public_keys);
+
+        // Without shuffling, the ring is signed in the order the members were added in
+        clsag.set_shuffle_members(false);
+        let (signature, public_keys) = clsag.sign(msg).unwrap();
+        assert_eq!(ring, public_keys);
+        assert!(signature.optimised_verify(&public_keys, msg).is_ok());
+    }
+
+    #[test]
+    fn test_edit_members() {
+        let num_keys = 2;
+        let mut clsag = generate_clsag_with(4, num_keys);
+        clsag.add_member(generate_signer(num_keys)).unwrap();
+        assert_eq!(5, clsag.members().len());
+
+        // Removing a member frees its keys to be added again
+        let removed = clsag.remove_member(1).unwrap();
+        assert_eq!(4, clsag.public_keys().len());
+        assert_eq!(4, clsag.members().len());
+        clsag.add_member(removed.clone()).unwrap();
+        assert_eq!(removed.public_set.to_keys(), clsag.public_keys()[4]);
+        match clsag.remove_member(5) {
+            Err(ClsagError::MemberNotFound) => {}
+            _ => panic!("expected a member not found error"),
+        }
+
+        // A decoy can be replaced by a member with its own keys or new keys,
+        // but not with the keys of another member
+        match clsag.replace_member(0, removed.clone()) {
+            Err(ClsagError::DuplicateKey(0)) => {}
+            _ => panic!("expected a duplicate key error"),
+        }
+        let decoy = clsag.members()[0].clone();
+        clsag.replace_member(0, decoy).unwrap();
+        let new_decoy = generate_decoy(num_keys);
+        let new_keys = new_decoy.public_set.to_keys();
+        clsag.replace_member(0, new_decoy).unwrap();
+        assert_eq!(new_keys, clsag.public_keys()[0]);
+        clsag.set_ring_policy(RingPolicy {
+            num_keys: Some(num_keys),
+            ..RingPolicy::permissive()
+        });
+        match clsag.replace_member(0, generate_decoy(num_keys + 1)) {
+            Err(ClsagError::KeyCountMismatch) => {}
+            _ => panic!("expected a key count mismatch error"),
+        }
+
+        let (signature, public_keys) = clsag.sign(b"hello").unwrap();
+        assert!(signature.optimised_verify(&public_keys, b"hello").is_ok());
+    }
+
+    #[test]
+    fn test_decoy_equals_signer() {
+        let num_keys = 2;
+        let signer = generate_signer(num_keys);
+        let mut public_keys = generate_decoy(num_keys).public_set.points().to_vec();
+        public_keys[1] = signer.public_set.points()[0];
+        let decoy = Member::new_decoy(public_keys);
+
+        // The decoy is added after the signer
+        let mut clsag = generate_clsag_with(3, num_keys);
+        clsag.add_member(signer.clone()).unwrap();
+        match clsag.add_member(decoy.clone()) {
+            Err(ClsagError::DecoyEqualsSigner { index: 4 }) => {}
+            _ => panic!("expected a decoy equals signer error"),
+        }
+        match clsag.replace_member(0, decoy.clone()) {
+            Err(ClsagError::DecoyEqualsSigner { index: 0 }) => {}
+            _ => panic!("expected a decoy equals signer error"),
+        }
+
+        // The decoy is added before the signer
+        let mut clsag = generate_clsag_with(3, num_keys);
+        clsag.add_member(decoy).unwrap();
+        match clsag.add_member(signer) {
+            Err(err) => {
+                assert_eq!(ClsagError::DecoyEqualsSigner { index: 3 }, err);
+                assert_eq!(
+                    "the decoy at index 3 has a key of the signer",
+                    err.to_string()
+                );
+            }
+            Ok(_) => panic!("expected a decoy equals signer error"),
+        }
+    }
+
+    #[test]
+    fn test_canonicalize_members() {
+        let mut clsag = generate_clsag_with(6, 2);
+        let signer = generate_signer(2);
+        let signer_keys = signer.public_set.to_keys();
+        clsag.add_member(signer).unwrap();
+
+        let signer_index = clsag.canonicalize_members().unwrap();
+        assert_eq!(signer_keys, clsag.public_keys()[signer_index]);
+        assert!(crate::ring::is_canonical(clsag.public_keys()));
+
+        // The ring is signed in canonical order
+        let (signature, public_keys) = clsag.sign(b"hello").unwrap();
+        assert_eq!(clsag.public_keys(), &public_keys[..]);
+        assert!(signature.verify_canonical(&public_keys, b"hello").is_ok());
+
+        let mut reordered = public_keys.clone();
+        reordered.swap(0, 1);
+        match signature.verify_canonical(&reordered, b"hello") {
+            Err(ClsagError::RingNotCanonical) => {}
+            _ => panic!("expected a ring not canonical error"),
+        }
+    }
+
+    #[test]
+    fn test_sign_deterministic() {
+        let num_decoys = 6;
+        let num_keys = 2;
+        let mut clsag = generate_clsag_with(num_decoys, num_keys);
+        clsag.add_member(generate_signer(num_keys)).unwrap();
+
+        let first = clsag.sign_deterministic(b"hello world").unwrap();
+        let second = clsag.sign_deterministic(b"hello world").unwrap();
+        assert_eq!(first.to_bytes(), second.to_bytes());
+        assert!(first
+            .optimised_verify(clsag.public_keys(), b"hello world")
+            .is_ok());
+
+        // A different message should give different nonces and responses
+        let other = clsag.sign_deterministic(b"another message").unwrap();
+        assert_ne!(first.challenge, other.challenge);
+        assert!(first
+            .responses
+            .iter()
+            .zip(other.responses.iter())
+            .all(|(a, b)| a != b));
+
+        // The key images only depend on the private keys
+        assert_eq!(first.key_images, other.key_images);
+    }
+
+    #[test]
+    fn test_sign_with_rng() {
+        let num_decoys = 6;
+        let num_keys = 2;
+        let msg = b"hello world";
+        let mut clsag = generate_clsag_with(num_decoys, num_keys);
+        clsag.add_member(generate_signer(num_keys)).unwrap();
+
+        // The same seed gives the same signature over the same ring order
+        let (first, public_keys) = clsag
+            .sign_with_rng(&mut ChaChaRng::from_seed([7u8; 32]), msg)
+            .unwrap();
+        let (second, second_keys) = clsag
+            .sign_with_rng(&mut ChaChaRng::from_seed([7u8; 32]), msg)
+            .unwrap();
+        assert_eq!(first.to_bytes(), second.to_bytes());
+        assert_eq!(public_keys, second_keys);
+        assert!(first.optimised_verify(&public_keys, msg).is_ok());
+
+        let (ct, ct_keys) = clsag
+            .sign_ct_with_rng(&mut ChaChaRng::from_seed([7u8; 32]), msg)
+            .unwrap();
+        assert_eq!(first.to_bytes(), ct.to_bytes());
+        assert_eq!(public_keys, ct_keys);
+
+        // Signing twice must not reuse the nonce
+        let (first, _) = clsag.sign(msg).unwrap();
+        let (second, _) = clsag.sign(msg).unwrap();
+        assert_ne!(first.challenge, second.challenge);
+        let (first, _) = clsag.sign_ct(msg).unwrap();
+        let (second, _) = clsag.sign_ct(msg).unwrap();
+        assert_ne!(first.challenge, second.challenge);
+    }
+
+    #[test]
+    fn test_sign_with_commitments() {
+        let num_decoys = 6;
+        let num_keys = 2;
+        let msg = b"hello world";
+
+        let blinding = generate_rand_scalars(1)[0];
+        let pseudo_blinding = generate_rand_scalars(1)[0];
+        let pseudo_out = commit(100, &pseudo_blinding);
+
+        let mut clsag = Clsag::with_pseudo_out(pseudo_out);
+        clsag.set_ring_policy(RingPolicy::permissive());
+        let mut commitments = Vec::new();
+        for decoy in generate_decoys(num_decoys, num_keys) {
+            let commitment = commit(42, &generate_rand_scalars(1)[0]);
+            commitments.push(commitment.compress());
+            clsag
+                .add_member(Member::new_decoy_with_commitment(
+                    decoy.public_set.points().to_vec(),
+                    commitment,
+                ))
+                .unwrap();
+        }
+        let commitment = commit(100, &blinding);
+        commitments.push(commitment.compress());
+        clsag
+            .add_member(Member::new_signer_with_commitment(
+                generate_rand_scalars(num_keys),
+                commitment,
+                blinding - pseudo_blinding,
+            ))
+            .unwrap();
+        // Keep the ring in order, so that the commitments line up with the members
+        clsag.set_shuffle_members(false);
+
+        for signature in [
+            clsag.sign(msg).unwrap().0,
+            clsag.sign_ct(msg).unwrap().0,
+            clsag.sign_deterministic(msg).unwrap(),
+        ] {
+            // One key image per key and one for the commitment to zero
+            assert_eq!(num_keys + 1, signature.key_images.len());
+            assert!(signature.optimised_verify(clsag.public_keys(), msg).is_ok());
+
+            let public_keys: PublicKeys = clsag
+                .members
+                .iter()
+                .map(|member| member.public_set.to_keys())
+                .collect();
+            assert!(signature
+                .verify_with_commitments(&public_keys, &commitments, &pseudo_out, msg)
+                .is_ok());
+
+            // A pseudo output for a different amount should not verify
+            let other_pseudo_out = commit(101, &pseudo_blinding);
+            assert!(signature
+                .verify_with_commitments(&public_keys, &commitments, &other_pseudo_out, msg)
+                .is_err());
+        }
+    }
+
+    #[test]
+    fn test_commitment_format() {
+        let num_keys = 2;
+        let blinding = Scalar::from(9u64);
+
+        // The signer does not commit to the same amount as the pseudo output
+        let mut clsag = Clsag::with_pseudo_out(commit(5, &Scalar::from(4u64)));
+        clsag
+            .add_member(Member::new_decoy_with_commitment(
+                generate_rand_points(num_keys),
+                commit(1, &blinding),
+            ))
+            .unwrap();
+        clsag
+            .add_member(Member::new_signer_with_commitment(
+                generate_rand_scalars(num_keys),
+                commit(6, &blinding),
+                blinding - Scalar::from(4u64),
+            ))
+            .unwrap();
+        match clsag.sign(b"hello world") {
+            Err(ClsagError::BadCommitmentKey) => {}
+            _ => panic!("expected a bad commitment key error"),
+        }
+
+        // Not every member holds a commitment
+        let mut clsag = Clsag::with_pseudo_out(commit(5, &Scalar::from(4u64)));
+        clsag.add_member(generate_decoy(num_keys)).unwrap();
+        clsag
+            .add_member(Member::new_signer_with_commitment(
+                generate_rand_scalars(num_keys),
+                commit(5, &blinding),
+                blinding - Scalar::from(4u64),
+            ))
+            .unwrap();
+        match clsag.sign(b"hello world") {
+            Err(ClsagError::CommitmentMismatch) => {}
+            _ => panic!("expected a commitment mismatch error"),
+        }
+    }
+
+    // Returns a ring with the signer at the given position
+    fn generate_clsag_with_signer_at(
+        signer_index: usize,
+        num_members: usize,
+        num_keys: usize,
+    ) -> Clsag {
+        let mut members = generate_decoys(num_members - 1, num_keys);
+        members.insert(signer_index, generate_signer(num_keys));
+
+        let mut clsag = Clsag::new();
+        clsag.set_ring_policy(RingPolicy::permissive());
+        for member in members {
+            clsag.add_member(member).unwrap();
+        }
+        clsag.set_shuffle_members(false);
+        clsag
+    }
+
+    #[test]
+    fn test_sign_ct_every_position() {
+        let num_members = 6;
+        let num_keys = 2;
+        let msg = b"hello world";
+
+        for signer_index in 0..num_members {
+            let clsag = generate_clsag_with_signer_at(signer_index, num_members, num_keys);
+
+            let (signature, public_keys) = clsag.sign_ct(msg).unwrap();
+            assert_eq!(num_members, signature.responses.len());
+            assert_eq!(clsag.public_keys(), public_keys);
+            assert!(signature.optimised_verify(&public_keys, msg).is_ok());
+
+            // The constant time and the regular signer should agree,
+            // when they use the same nonce and decoy responses
+            let values = generate_rand_scalars(num_members);
+            let transcript = default_transcript();
+            let signature = clsag
+                .sign_ct_with_values(&transcript, msg, &values)
+                .unwrap();
+            let expected = clsag
+                .with_nonces(|index| values[index])
+                .sign_with_nonces(&transcript, msg, None)
+                .unwrap();
+            assert_eq!(expected.challenge, signature.challenge);
+            assert_eq!(expected.responses, signature.responses);
+            assert_eq!(expected.key_images, signature.key_images);
+        }
+    }
+
+    #[test]
+    fn test_sign_ct_check_format() {
+        let mut clsag = generate_clsag_with(4, 2);
+        match clsag.sign_ct(b"hello world") {
+            Err(ClsagError::NoSigner) => {}
+            _ => panic!("expected a no signer error"),
+        }
+
+        clsag.add_member(generate_signer(2)).unwrap();
+        clsag.add_member(generate_signer(2)).unwrap();
+        match clsag.sign_ct(b"hello world") {
+            Err(ClsagError::MultipleSignersNotSupported) => {}
+            _ => panic!("expected a more than one signer error"),
+        }
+    }
+
+    #[test]
+    fn test_check_format_ct() {
+        let num_keys = 2;
+        let blinding = Scalar::from(9u64);
+        let mut rings = vec![generate_clsag_with(4, num_keys)];
+
+        let mut clsag = generate_clsag_with(4, num_keys);
+        clsag.add_member(generate_signer(num_keys)).unwrap();
+        clsag.add_member(generate_signer(num_keys)).unwrap();
+        rings.push(clsag);
+
+        // A decoy with a key of the signer, before and after the signer, pushed onto the ring
+        // directly as `add_member` rejects it
+        for signer_index in 0..3 {
+            let mut clsag = generate_clsag_with_signer_at(signer_index, 3, num_keys);
+            let signer_keys = clsag.ring[signer_index].clone();
+            let mut public_keys = generate_decoy(num_keys).public_set.points().to_vec();
+            public_keys[1] = clsag.members[signer_index].public_set.points()[0];
+            clsag.members.push(Member::new_decoy(public_keys));
+            clsag.ring.push(signer_keys);
+            rings.push(clsag);
+        }
+
+        let mut clsag = Clsag::with_pseudo_out(commit(5, &Scalar::from(4u64)));
+        clsag.set_ring_policy(RingPolicy::permissive());
+        clsag
+            .add_member(Member::new_decoy_with_commitment(
+                generate_rand_points(num_keys),
+                commit(1, &blinding),
+            ))
+            .unwrap();
+        clsag
+            .add_member(Member::new_signer_with_commitment(
+                generate_rand_scalars(num_keys),
+                commit(6, &blinding),
+                blinding - Scalar::from(4u64),
+            ))
+            .unwrap();
+        rings.push(clsag);
+        rings.push(generate_clsag_with_signer_at(2, 5, num_keys));
+
+        // The constant time checks return the same result as the regular checks
+        for clsag in &rings {
+            assert_eq!(clsag.check_format().err(), clsag.check_format_ct().err());
+        }
+        match rings[3].check_format_ct() {
+            Err(ClsagError::DecoyEqualsSigner { index: 3 }) => {}
+            _ => panic!("expected a decoy equals signer error"),
+        }
+        assert!(rings.last().unwrap().check_format_ct().is_ok());
+    }
+
+    #[test]
+    fn test_every_signing_path_is_observed() {
+        use crate::anti_exfil::{DeviceSession, HostSession};
+        use crate::device::LocalDevice;
+        use crate::keys::SecretKey;
+        use crate::metrics::Counters;
+        use crate::range::RangeProofSystem;
+        use merlin::Transcript;
+
+        // A range proof system whose proofs prove nothing, as only the signature matters here
+        struct NoRange;
+        impl RangeProofSystem for NoRange {
+            type Proof = ();
+            fn prove(&self, _: &mut Transcript, _: u64, _: &Scalar) -> Result<(), ClsagError> {
+                Ok(())
+            }
+            fn verify(
+                &self,
+                _: &mut Transcript,
+                _: &RistrettoPoint,
+                _: &(),
+            ) -> Result<(), ClsagError> {
+                Ok(())
+            }
+        }
+
+        let msg = b"hello world";
+        let mut rng = rand::thread_rng();
+        let counters = Arc::new(Counters::default());
+
+        let signer_key = SecretKey::random(&mut rng);
+        let mut clsag = generate_clsag_with(4, 1);
+        clsag
+            .add_member(Member::signer_from_secret_keys(std::slice::from_ref(&signer_key)).unwrap())
+            .unwrap();
+        clsag.set_metrics(counters.clone());
+
+        let blinding = Scalar::random(&mut rng);
+        let pseudo_blinding = Scalar::random(&mut rng);
+        let mut ct_clsag = Clsag::with_pseudo_out(commit(100, &pseudo_blinding));
+        ct_clsag.set_ring_policy(RingPolicy::permissive());
+        for decoy in generate_decoys(4, 1) {
+            let commitment = commit(42, &Scalar::random(&mut rng));
+            ct_clsag
+                .add_member(Member::new_decoy_with_commitment(
+                    decoy.public_set.points().to_vec(),
+                    commitment,
+                ))
+                .unwrap();
+        }
+        ct_clsag
+            .add_member(Member::new_signer_with_commitment(
+                generate_rand_scalars(1),
+                commit(100, &blinding),
+                blinding - pseudo_blinding,
+            ))
+            .unwrap();
+        ct_clsag.set_metrics(counters.clone());
+
+        let mut device = LocalDevice::new(generate_private_set(1));
+        let mut device_clsag = generate_clsag_with(4, 1);
+        let device_keys = device.public_keys(&device_clsag.generator).unwrap();
+        device_clsag
+            .add_member(Member::decoy_from_public_keys(&device_keys).unwrap())
+            .unwrap();
+        device_clsag.set_metrics(counters.clone());
+
+        let sign_every_way = || {
+            clsag.sign(msg).unwrap();
+            clsag.sign_deterministic(msg).unwrap();
+            clsag.sign_ct(msg).unwrap();
+            let adaptor_secret = SecretKey::random(&mut rng);
+            let adaptor = AdaptorPoint::new(&adaptor_secret, &signer_key.public_key());
+            clsag.sign_adaptor(msg, &adaptor).unwrap();
+            clsag.sign_with_commitment(msg, b"contract").unwrap();
+
+            let (host, host_commitment) = HostSession::new(&mut rng);
+            let (session, device_commitment) =
+                DeviceSession::new(&mut rng, &clsag, msg, host_commitment).unwrap();
+            let (_, host_nonce) = host.reveal(device_commitment);
+            session.sign(&host_nonce).unwrap();
+
+            ct_clsag
+                .sign_with_range_proof(msg, &NoRange, 100, &pseudo_blinding)
+                .unwrap();
+            device_clsag
+                .sign_with_device(&mut rng, &mut device, &device_keys, msg)
+                .unwrap();
+        };
+
+        // Returns the number of signing spans emitted by the closure, with the trace feature
+        #[cfg(feature = "trace")]
+        fn count_sign_spans<F: FnOnce()>(f: F) -> Option<usize> {
+            use std::sync::atomic::{AtomicUsize, Ordering};
+
+            #[derive(Default)]
+            struct SignSpans(AtomicUsize);
+            impl trace::Subscriber for SignSpans {
+                fn on_span(&self, span: &trace::Span) {
+                    if span.operation == trace::Operation::Sign {
+                        self.0.fetch_add(1, Ordering::SeqCst);
+                    }
+                }
+            }
+
+            let spans = Arc::new(SignSpans::default());
+            trace::with_subscriber(spans.clone(), f);
+            Some(spans.0.load(Ordering::SeqCst))
+        }
+        #[cfg(not(feature = "trace"))]
+        fn count_sign_spans<F: FnOnce()>(f: F) -> Option<usize> {
+            f();
+            None
+        }
+
+        let num_paths = 8;
+        if let Some(spans) = count_sign_spans(sign_every_way) {
+            assert_eq!(num_paths, spans);
+        }
+        assert_eq!(num_paths as u64, counters.signs());
+    }
+
     #[bench]
     fn bench_sign(b: &mut Bencher) {
         // One time setup code here
@@ -336,8 +2303,34 @@ mod test {
         let num_decoys = 11;
         let msg = b"hello world";
         let mut clsag = generate_clsag_with(num_decoys, num_keys);
-        clsag.add_member(generate_signer(num_keys));
+        clsag.add_member(generate_signer(num_keys)).unwrap();
 
         b.iter(|| clsag.sign(msg));
     }
+
+    // The time taken should not depend on the signers position in the ring
+    #[bench]
+    fn bench_sign_ct_signer_first(b: &mut Bencher) {
+        let clsag = generate_clsag_with_signer_at(0, 12, 2);
+        b.iter(|| clsag.sign_ct(b"hello world"));
+    }
+
+    #[bench]
+    fn bench_sign_ct_signer_last(b: &mut Bencher) {
+        let clsag = generate_clsag_with_signer_at(11, 12, 2);
+        b.iter(|| clsag.sign_ct(b"hello world"));
+    }
+
+    // The checks of the constant time path should not depend on the signers position either
+    #[bench]
+    fn bench_check_format_ct_signer_first(b: &mut Bencher) {
+        let clsag = generate_clsag_with_signer_at(0, 12, 2);
+        b.iter(|| clsag.check_format_ct());
+    }
+
+    #[bench]
+    fn bench_check_format_ct_signer_last(b: &mut Bencher) {
+        let clsag = generate_clsag_with_signer_at(11, 12, 2);
+        b.iter(|| clsag.check_format_ct());
+    }
 }
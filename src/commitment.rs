@@ -0,0 +1,38 @@
+use crate::constants::BASEPOINT;
+use crate::hash::DefaultHash;
+use curve25519_dalek::ristretto::RistrettoPoint;
+use curve25519_dalek::scalar::Scalar;
+
+// Pedersen commitments are of the form `amount * H + blinding * G`
+// where G is the basepoint and H is a generator with an unknown discrete log relative to G.
+// Two commitments to the same amount differ by a multiple of G, so a signer who knows
+// both blinding factors knows the private key to the commitment to zero `C - C'`.
+
+// Returns the generator H used for the amount, which is a hash of a fixed string
+pub fn value_generator() -> RistrettoPoint {
+    RistrettoPoint::hash_from_bytes::<DefaultHash>(b"clsag_pedersen_value_generator")
+}
+
+// Commits to the amount with the given blinding factor
+pub fn commit(amount: u64, blinding: &Scalar) -> RistrettoPoint {
+    Scalar::from(amount) * value_generator() + blinding * BASEPOINT
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_commitment_to_zero() {
+        let blinding = Scalar::from(7u64);
+        let other_blinding = Scalar::from(2u64);
+
+        // Commitments to the same amount differ by a multiple of the basepoint
+        let difference = commit(100, &blinding) - commit(100, &other_blinding);
+        assert_eq!(difference, (blinding - other_blinding) * BASEPOINT);
+
+        // Commitments to different amounts do not
+        let difference = commit(100, &blinding) - commit(99, &other_blinding);
+        assert_ne!(difference, (blinding - other_blinding) * BASEPOINT);
+    }
+}
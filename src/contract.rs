@@ -0,0 +1,153 @@
+// Sign-to-contract commitments, which embed a commitment to some data in the signers nonce.
+//
+// The signer picks a nonce `k` and tweaks it by `e = H(k * G, data)`, signing with the nonce
+// `k + e`. The signature looks like any other signature, but the signers nonce commitment is
+// `L = k * G + e * G`, which can only be opened to the data it was made for. `G` is the
+// generator of the ring, which is the basepoint unless it was set with `Clsag::with_generator`. This lets a
+// signature double as a timestamp of the data, without making the signature any larger.
+//
+// `Clsag::sign_with_commitment` returns the opening of the commitment alongside the signature,
+// which holds the position of the signer and `k * G`, so revealing it reveals the signer.
+// Anyone holding the opening recomputes the signers nonce commitment from the signature with
+// `Signature::extract_commitment_proof`, and checks the proof against the data.
+use crate::error::ClsagError;
+use crate::transcript::SigningTranscript;
+use curve25519_dalek::ristretto::RistrettoPoint;
+use curve25519_dalek::scalar::Scalar;
+use merlin::Transcript;
+
+// The opening of the commitment in a signature made with `Clsag::sign_with_commitment`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CommitmentOpening {
+    pub(crate) signer_index: usize,
+    // The signers nonce commitment before it was tweaked, `k * G`
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::point"))]
+    pub(crate) nonce_point: RistrettoPoint,
+    // The generator of the ring the signature was made over, `G`
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::point"))]
+    pub(crate) generator: RistrettoPoint,
+}
+
+// The signers nonce commitment recomputed from a signature, along with its opening
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommitmentProof {
+    opening: CommitmentOpening,
+    // The nonce commitment of the signer in the signature, `(k + e) * G`
+    committed_nonce: RistrettoPoint,
+}
+
+impl CommitmentOpening {
+    // Returns the position of the signer in the ring the signature was made over
+    pub fn signer_index(&self) -> usize {
+        self.signer_index
+    }
+}
+
+impl CommitmentProof {
+    pub(crate) fn new(opening: CommitmentOpening, committed_nonce: RistrettoPoint) -> Self {
+        CommitmentProof {
+            opening,
+            committed_nonce,
+        }
+    }
+    // Returns the opening the proof was extracted with
+    pub fn opening(&self) -> &CommitmentOpening {
+        &self.opening
+    }
+    // Checks that the signers nonce commitment is a commitment to the data.
+    // Returns an error if the commitment was made to other data
+    pub fn verify(&self, data: &[u8]) -> Result<(), ClsagError> {
+        let tweak = contract_tweak(&self.opening.nonce_point, data);
+        if self.opening.nonce_point + tweak * self.opening.generator != self.committed_nonce {
+            return Err(ClsagError::CommitmentProofMismatch);
+        }
+        Ok(())
+    }
+}
+
+// Returns the tweak `e = H(k * G, data)` which is added to the signers nonce
+pub(crate) fn contract_tweak(nonce_point: &RistrettoPoint, data: &[u8]) -> Scalar {
+    let mut transcript = Transcript::new(b"clsag_sign_to_contract");
+    transcript.append_point(b"nonce_point", nonce_point);
+    transcript.append_message(b"data", data);
+    transcript.challenge_scalar(b"tweak")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn test_sign_with_commitment() {
+        let msg = b"hello world";
+        let data = b"the hash of a document to timestamp";
+
+        let mut clsag = generate_clsag_with(5, 2);
+        clsag.add_member(generate_signer(2)).unwrap();
+        let (signature, public_keys, opening) = clsag.sign_with_commitment(msg, data).unwrap();
+        assert!(signature.verify(&public_keys, msg).is_ok());
+        assert_eq!(clsag.public_keys()[5], public_keys[opening.signer_index()]);
+
+        let proof = signature
+            .extract_commitment_proof(&public_keys, msg, &opening)
+            .unwrap();
+        assert_eq!(&opening, proof.opening());
+        assert!(proof.verify(data).is_ok());
+        match proof.verify(b"other data") {
+            Err(ClsagError::CommitmentProofMismatch) => {}
+            _ => panic!("expected a commitment proof mismatch error"),
+        }
+
+        // The proof of a signature without a commitment does not open to the data
+        let (other_signature, other_public_keys) = clsag.sign(msg).unwrap();
+        let other_opening = CommitmentOpening {
+            signer_index: other_public_keys
+                .iter()
+                .position(|keys| keys == &clsag.public_keys()[5])
+                .unwrap(),
+            nonce_point: opening.nonce_point,
+            generator: opening.generator,
+        };
+        let proof = other_signature
+            .extract_commitment_proof(&other_public_keys, msg, &other_opening)
+            .unwrap();
+        assert!(proof.verify(data).is_err());
+
+        // A proof is only extracted from a valid signature
+        assert!(signature
+            .extract_commitment_proof(&public_keys, b"another message", &opening)
+            .is_err());
+    }
+
+    #[test]
+    fn test_sign_with_commitment_over_generator() {
+        use crate::clsag::Clsag;
+        use crate::hash::hash_pubkey;
+        use crate::member::Member;
+        use crate::policy::RingPolicy;
+
+        let msg = b"hello world";
+        let data = b"the hash of a document to timestamp";
+        let generator = hash_pubkey(b"another generator for the keys!!");
+
+        let mut clsag = Clsag::with_generator(generator);
+        clsag.set_ring_policy(RingPolicy::permissive());
+        for decoy in generate_decoys(5, 1) {
+            clsag.add_member(decoy).unwrap();
+        }
+        clsag
+            .add_member(Member::new_signer(generate_rand_scalars(1)))
+            .unwrap();
+
+        // The nonce of the signer is committed to over the generator of the ring
+        let (signature, public_keys, opening) = clsag.sign_with_commitment(msg, data).unwrap();
+        assert_eq!(generator, opening.generator);
+        let proof = signature
+            .extract_commitment_proof(&public_keys, msg, &opening)
+            .unwrap();
+        assert!(proof.verify(data).is_ok());
+        assert!(proof.verify(b"other data").is_err());
+    }
+}
@@ -0,0 +1,373 @@
+// Two party signing, where a hot wallet and a signing device each hold half of the signers
+// keys and produce a clsag signature together, without either of them ever holding the keys.
+//
+// Each party generates their own half of the keys with `CosignerSetup::new`, and the parties
+// exchange setup messages, which prove that the sender knows their half of the keys.
+// The signers keys are the sum of both halves, and neither party learns the other half.
+//
+// Signing then takes two messages:
+//
+//   1. The initiator, usually the hot wallet which builds the ring, calls
+//      `CosignerSession::start` and sends the commitment message to the responder.
+//   2. The responder, usually the signing device, checks the ring and the message it is
+//      asked to sign, and calls `respond`, which returns the response message.
+//
+// The initiator finishes the session with the response, which gives the signature.
+// This is the threshold protocol in the multisig module run with two of two participants,
+// where the responder builds the signing package.
+use crate::clsag::PublicKeys;
+use crate::constants::BASEPOINT;
+use crate::error::ClsagError;
+use crate::keys::{zeroize_scalar, PublicKey};
+use crate::multisig::{KeyShare, NonceCommitment, PartialSignature, SigningNonces, SigningPackage};
+use crate::shamir::lagrange_coefficient;
+use crate::signature::Signature;
+use crate::transcript::SigningTranscript;
+use curve25519_dalek::ristretto::RistrettoPoint;
+use curve25519_dalek::scalar::Scalar;
+use merlin::Transcript;
+use rand::{CryptoRng, RngCore};
+use std::fmt;
+
+// The role of a party, which must be different for the two parties
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Role {
+    // The party which starts each signing session
+    Initiator,
+    // The party which responds to the initiator
+    Responder,
+}
+
+impl Role {
+    // Returns the index of the party in the threshold protocol
+    fn index(self) -> u32 {
+        match self {
+            Role::Initiator => 1,
+            Role::Responder => 2,
+        }
+    }
+}
+
+// A party's half of the signers keys, before the setup messages have been exchanged
+pub struct CosignerSetup {
+    role: Role,
+    secret_keys: Vec<Scalar>,
+}
+
+// The setup message holds the public keys of a party's half of the keys,
+// along with a proof that they know the private keys, so that neither party can
+// choose their half after seeing the other half
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SetupMessage {
+    role: Role,
+    public_keys: Vec<PublicKey>,
+
+    // A schnorr proof of each private key, as the challenge and response
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::scalars"))]
+    challenges: Vec<Scalar>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::scalars"))]
+    responses: Vec<Scalar>,
+}
+
+// The message sent by the initiator to start signing
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CommitmentMessage {
+    ring: PublicKeys,
+    signer_index: usize,
+    msg: Vec<u8>,
+    commitment: NonceCommitment,
+}
+
+// The message sent by the responder to the initiator, holding the signing package
+// and the responders partial signature
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ResponseMessage {
+    package: SigningPackage,
+    partial: PartialSignature,
+}
+
+// A signing session of the initiator, which is waiting for the response.
+// A session can only be finished once, as the nonces it holds must never be reused
+pub struct CosignerSession {
+    share: KeyShare,
+    nonces: SigningNonces,
+    request: CommitmentMessage,
+}
+
+// Returns the challenge of the proof of the private key of `public_key`
+fn proof_challenge(role: Role, public_key: &PublicKey, nonce_point: &RistrettoPoint) -> Scalar {
+    let mut transcript = Transcript::new(b"clsag_cosigner_setup");
+    transcript.append_u64(b"index", u64::from(role.index()));
+    transcript.append_message(b"public_key", public_key.as_bytes());
+    transcript.append_point(b"R", nonce_point);
+    transcript.challenge_scalar(b"c")
+}
+
+impl CosignerSetup {
+    // Generates the party's half of `num_keys` keys, returning the setup message
+    // which is sent to the other party
+    pub fn new<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        role: Role,
+        num_keys: usize,
+    ) -> (CosignerSetup, SetupMessage) {
+        let secret_keys: Vec<Scalar> = (0..num_keys).map(|_| Scalar::random(rng)).collect();
+
+        let mut public_keys = Vec::with_capacity(num_keys);
+        let mut challenges = Vec::with_capacity(num_keys);
+        let mut responses = Vec::with_capacity(num_keys);
+        for secret_key in &secret_keys {
+            let public_key = PublicKey::from_point(&(secret_key * BASEPOINT));
+
+            let mut nonce = Scalar::random(rng);
+            let challenge = proof_challenge(role, &public_key, &(nonce * BASEPOINT));
+            responses.push(nonce - challenge * secret_key);
+            zeroize_scalar(&mut nonce);
+
+            public_keys.push(public_key);
+            challenges.push(challenge);
+        }
+
+        let message = SetupMessage {
+            role,
+            public_keys,
+            challenges,
+            responses,
+        };
+        (CosignerSetup { role, secret_keys }, message)
+    }
+    // Finishes the setup with the other party's setup message, returning this party's
+    // share of the signers keys. The public keys of the signer are the same for both parties.
+    // Returns an error if the other party has the same role, if there are no keys or the
+    // other party has a different number of keys, or if their proof does not verify
+    pub fn finish(self, other: &SetupMessage) -> Result<KeyShare, ClsagError> {
+        if other.role == self.role {
+            return Err(ClsagError::ParticipantMismatch);
+        }
+        if self.secret_keys.is_empty()
+            || other.public_keys.len() != self.secret_keys.len()
+            || other.challenges.len() != self.secret_keys.len()
+            || other.responses.len() != self.secret_keys.len()
+        {
+            return Err(ClsagError::KeyCountMismatch);
+        }
+
+        let mut public_keys = Vec::with_capacity(self.secret_keys.len());
+        for ((secret_key, other_key), (challenge, response)) in self
+            .secret_keys
+            .iter()
+            .zip(&other.public_keys)
+            .zip(other.challenges.iter().zip(&other.responses))
+        {
+            let other_point = other_key.decompress().ok_or(ClsagError::InvalidPoint)?;
+            let nonce_point = response * BASEPOINT + challenge * other_point;
+            if proof_challenge(other.role, other_key, &nonce_point) != *challenge {
+                return Err(ClsagError::InvalidKeyProof);
+            }
+
+            public_keys.push(PublicKey::from_point(
+                &(secret_key * BASEPOINT + other_point),
+            ));
+        }
+
+        // The threshold protocol weights each share by its lagrange coefficient,
+        // so each half is divided by the coefficient for the two parties
+        let indices = [Role::Initiator.index(), Role::Responder.index()];
+        let weight = lagrange_coefficient(self.role.index(), indices.iter().copied()).invert();
+        let secret_shares = self
+            .secret_keys
+            .iter()
+            .map(|secret_key| secret_key * weight)
+            .collect();
+
+        Ok(KeyShare::new(
+            self.role.index(),
+            2,
+            secret_shares,
+            public_keys,
+        ))
+    }
+}
+
+impl fmt::Debug for CosignerSetup {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "CosignerSetup({:?})", self.role)
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for CosignerSetup {
+    fn drop(&mut self) {
+        for secret_key in self.secret_keys.iter_mut() {
+            zeroize_scalar(secret_key);
+        }
+    }
+}
+
+impl CosignerSession {
+    // Starts signing the message over the ring, where the signer is the member at `signer_index`.
+    // Returns the session, along with the commitment message which is sent to the responder
+    pub fn start<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        share: &KeyShare,
+        ring: PublicKeys,
+        signer_index: usize,
+        msg: &[u8],
+    ) -> (CosignerSession, CommitmentMessage) {
+        let (nonces, commitment) = share.commit(rng);
+        let request = CommitmentMessage {
+            ring,
+            signer_index,
+            msg: msg.to_vec(),
+            commitment,
+        };
+
+        let session = CosignerSession {
+            share: share.clone(),
+            nonces,
+            request: request.clone(),
+        };
+        (session, request)
+    }
+    // Finishes the session with the responders response, returning the signature.
+    // Returns an error if the response is for a different ring or message,
+    // or if the combined signature does not verify
+    pub fn finish(self, response: &ResponseMessage) -> Result<Signature, ClsagError> {
+        let package = &response.package;
+        if package.ring() != self.request.ring.as_slice()
+            || package.signer_index() != self.request.signer_index
+            || package.msg() != self.request.msg.as_slice()
+        {
+            return Err(ClsagError::SessionMismatch);
+        }
+
+        let partial = self.share.sign(self.nonces, package)?;
+        package.combine(&[partial, response.partial])
+    }
+}
+
+impl fmt::Debug for CosignerSession {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CosignerSession")
+            .field("share", &self.share)
+            .field("request", &self.request)
+            .finish()
+    }
+}
+
+impl CommitmentMessage {
+    // Returns the ring the initiator is asking to sign over
+    pub fn ring(&self) -> &[Vec<PublicKey>] {
+        &self.ring
+    }
+    // Returns the message the initiator is asking to sign
+    pub fn msg(&self) -> &[u8] {
+        &self.msg
+    }
+}
+
+// Responds to the initiators commitment message, by building the signing package
+// and signing it. The ring and the message should be checked before responding,
+// for example by showing the message on the signing device.
+// Returns an error if the ring cannot be signed, or the share is not for the signer in the ring
+pub fn respond<R: RngCore + CryptoRng>(
+    rng: &mut R,
+    share: &KeyShare,
+    request: &CommitmentMessage,
+) -> Result<ResponseMessage, ClsagError> {
+    let (nonces, commitment) = share.commit(rng);
+    let package = SigningPackage::new(
+        rng,
+        request.ring.clone(),
+        request.signer_index,
+        &request.msg,
+        vec![request.commitment.clone(), commitment],
+    )?;
+    let partial = share.sign(nonces, &package)?;
+
+    Ok(ResponseMessage { package, partial })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::*;
+
+    // Runs the setup for both parties, returning the share of the initiator and the responder
+    fn setup(num_keys: usize) -> (KeyShare, KeyShare) {
+        let mut rng = rand::thread_rng();
+        let (initiator, initiator_message) =
+            CosignerSetup::new(&mut rng, Role::Initiator, num_keys);
+        let (responder, responder_message) =
+            CosignerSetup::new(&mut rng, Role::Responder, num_keys);
+
+        let initiator_share = initiator.finish(&responder_message).unwrap();
+        let responder_share = responder.finish(&initiator_message).unwrap();
+        assert_eq!(initiator_share.public_keys(), responder_share.public_keys());
+
+        (initiator_share, responder_share)
+    }
+
+    fn ring_with_signer(share: &KeyShare, signer_index: usize) -> PublicKeys {
+        let num_keys = share.public_keys().len();
+        let mut ring: PublicKeys = (0..10)
+            .map(|_| generate_rand_public_keys(num_keys))
+            .collect();
+        ring.insert(signer_index, share.public_keys().to_vec());
+        ring
+    }
+
+    #[test]
+    fn test_cosign() {
+        let mut rng = rand::thread_rng();
+        let msg = b"hello world";
+
+        for num_keys in 1..3 {
+            let (hot_wallet, device) = setup(num_keys);
+            let ring = ring_with_signer(&hot_wallet, 3);
+
+            let (session, request) =
+                CosignerSession::start(&mut rng, &hot_wallet, ring.clone(), 3, msg);
+            assert_eq!(msg, request.msg());
+            let response = respond(&mut rng, &device, &request).unwrap();
+            let signature = session.finish(&response).unwrap();
+
+            assert!(signature.verify(&ring, msg).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_cosign_errors() {
+        let mut rng = rand::thread_rng();
+
+        // The parties must have different roles, and their proofs must verify
+        let (first, _) = CosignerSetup::new(&mut rng, Role::Initiator, 1);
+        let (_, second_message) = CosignerSetup::new(&mut rng, Role::Initiator, 1);
+        match first.finish(&second_message) {
+            Err(ClsagError::ParticipantMismatch) => {}
+            _ => panic!("expected a participant mismatch error"),
+        }
+        let (first, _) = CosignerSetup::new(&mut rng, Role::Initiator, 1);
+        let (_, mut second_message) = CosignerSetup::new(&mut rng, Role::Responder, 1);
+        second_message.public_keys = generate_rand_public_keys(1);
+        match first.finish(&second_message) {
+            Err(ClsagError::InvalidKeyProof) => {}
+            _ => panic!("expected an invalid key proof error"),
+        }
+
+        // The response must be for the ring and message the initiator asked to sign
+        let (hot_wallet, device) = setup(1);
+        let ring = ring_with_signer(&hot_wallet, 0);
+        let (session, _) = CosignerSession::start(&mut rng, &hot_wallet, ring.clone(), 0, b"msg");
+        let (_, other_request) = CosignerSession::start(&mut rng, &hot_wallet, ring, 0, b"other");
+        let response = respond(&mut rng, &device, &other_request).unwrap();
+        match session.finish(&response) {
+            Err(ClsagError::SessionMismatch) => {}
+            _ => panic!("expected a session mismatch error"),
+        }
+    }
+}
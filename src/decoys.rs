@@ -0,0 +1,225 @@
+// Selection of the decoys which hide the signer in a ring.
+//
+// Outputs are usually spent soon after they are created, so decoys picked uniformly from
+// every output are older than the real output, and the real output stands out as the newest
+// member in the ring. Monero instead samples the age of each decoy from a gamma distribution
+// fitted to the ages of the outputs which are actually spent, which is what `GammaSelector` does.
+use crate::error::ClsagError;
+use crate::member::Member;
+use crate::provider::RingMemberProvider;
+use rand::distributions::{Distribution, Gamma};
+use rand::{CryptoRng, RngCore};
+
+// The shape and scale of the gamma distribution over the log of the age of an output
+// in seconds, as used by Monero
+pub const GAMMA_SHAPE: f64 = 19.28;
+pub const GAMMA_SCALE: f64 = 1.0 / 1.61;
+
+// The most times a decoy is sampled for each decoy which is selected, before giving up
+const MAX_ATTEMPTS_PER_DECOY: usize = 100;
+
+// CandidateOutputs are the outputs decoys can be selected from.
+// Outputs are indexed in the order they were created, from 0 for the oldest output,
+// so the age of an output never increases with its index
+pub trait CandidateOutputs {
+    // Returns the number of outputs
+    fn num_outputs(&self) -> u64;
+    // Returns the age of the output at `index` in seconds
+    fn output_age(&self, index: u64) -> u64;
+}
+
+// The ages of each output in seconds, from the oldest output to the newest
+impl CandidateOutputs for [u64] {
+    fn num_outputs(&self) -> u64 {
+        self.len() as u64
+    }
+    fn output_age(&self, index: u64) -> u64 {
+        self[index as usize]
+    }
+}
+
+// A DecoySelector chooses which outputs are used as the decoys in a ring
+pub trait DecoySelector {
+    // Returns the indices of `num_decoys` distinct outputs, in ascending order,
+    // none of which is the signers output at `real_index`.
+    // Returns an error if there are not enough outputs to select the decoys from
+    fn select_decoys<R: RngCore + CryptoRng, C: CandidateOutputs + ?Sized>(
+        &self,
+        rng: &mut R,
+        outputs: &C,
+        real_index: u64,
+        num_decoys: usize,
+    ) -> Result<Vec<u64>, ClsagError>;
+
+    // Selects the decoys as `select_decoys` does, and fetches them from the provider.
+    // The decoys are returned in ascending order of their output index
+    fn select_decoy_members<
+        R: RngCore + CryptoRng,
+        C: CandidateOutputs + ?Sized,
+        P: RingMemberProvider + ?Sized,
+    >(
+        &self,
+        rng: &mut R,
+        outputs: &C,
+        provider: &P,
+        real_index: u64,
+        num_decoys: usize,
+    ) -> Result<Vec<Member>, ClsagError> {
+        let indices = self.select_decoys(rng, outputs, real_index, num_decoys)?;
+        provider.get_decoys(&indices)
+    }
+}
+
+// A GammaSelector samples the age of each decoy from a gamma distribution over the
+// log of the age in seconds, and selects the newest output which is at least that old
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GammaSelector {
+    pub shape: f64,
+    pub scale: f64,
+}
+
+impl Default for GammaSelector {
+    // The default selector uses the distribution Monero uses
+    fn default() -> Self {
+        GammaSelector {
+            shape: GAMMA_SHAPE,
+            scale: GAMMA_SCALE,
+        }
+    }
+}
+
+impl GammaSelector {
+    // Returns the index of the newest output which is at least `age` seconds old,
+    // or None if every output is newer than that
+    fn output_with_age<C: CandidateOutputs + ?Sized>(outputs: &C, age: u64) -> Option<u64> {
+        let num_outputs = outputs.num_outputs();
+        if num_outputs == 0 || outputs.output_age(0) < age {
+            return None;
+        }
+
+        // The oldest output is at least `age` old, so find the last output
+        // which is, knowing that the age of outputs never increases with their index
+        let (mut low, mut high) = (0, num_outputs - 1);
+        while low < high {
+            let mid = low + (high - low).div_ceil(2);
+            if outputs.output_age(mid) >= age {
+                low = mid;
+            } else {
+                high = mid - 1;
+            }
+        }
+
+        Some(low)
+    }
+}
+
+impl DecoySelector for GammaSelector {
+    fn select_decoys<R: RngCore + CryptoRng, C: CandidateOutputs + ?Sized>(
+        &self,
+        rng: &mut R,
+        outputs: &C,
+        real_index: u64,
+        num_decoys: usize,
+    ) -> Result<Vec<u64>, ClsagError> {
+        let num_outputs = outputs.num_outputs();
+        let num_candidates = num_outputs.saturating_sub((real_index < num_outputs) as u64);
+        if num_candidates < num_decoys as u64 {
+            return Err(ClsagError::NotEnoughOutputs);
+        }
+
+        let gamma = Gamma::new(self.shape, self.scale);
+        let mut decoys: Vec<u64> = Vec::with_capacity(num_decoys);
+        let mut attempts = 0;
+        while decoys.len() < num_decoys {
+            if attempts == num_decoys * MAX_ATTEMPTS_PER_DECOY {
+                return Err(ClsagError::NotEnoughOutputs);
+            }
+            attempts += 1;
+
+            let age = gamma.sample(rng).exp();
+            let index = match Self::output_with_age(outputs, age as u64) {
+                Some(index) => index,
+                None => continue,
+            };
+            if index != real_index && !decoys.contains(&index) {
+                decoys.push(index);
+            }
+        }
+
+        decoys.sort_unstable();
+        Ok(decoys)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::*;
+
+    // The ages of `num_outputs` outputs, with one output created every `interval` seconds
+    fn ages(num_outputs: u64, interval: u64) -> Vec<u64> {
+        (0..num_outputs).rev().map(|i| i * interval).collect()
+    }
+
+    #[test]
+    fn test_select_decoys() {
+        let mut rng = rand::thread_rng();
+        let outputs = ages(100_000, 60);
+        let real_index = 99_990;
+
+        let decoys = GammaSelector::default()
+            .select_decoys(&mut rng, &outputs[..], real_index, 10)
+            .unwrap();
+        assert_eq!(10, decoys.len());
+        assert!(!decoys.contains(&real_index));
+        assert!(decoys.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(decoys.iter().all(|&index| index < 100_000));
+
+        // Decoys are mostly recent outputs, rather than uniformly spread over every output
+        let decoys = GammaSelector::default()
+            .select_decoys(&mut rng, &outputs[..], real_index, 200)
+            .unwrap();
+        let recent = decoys.iter().filter(|&&index| index >= 50_000).count();
+        assert!(recent > 150);
+    }
+
+    #[test]
+    fn test_select_decoy_members() {
+        let mut rng = rand::thread_rng();
+        let outputs = ages(1000, 600);
+        let public_keys = generate_rand_public_keys(1000);
+
+        let decoys = GammaSelector::default()
+            .select_decoy_members(&mut rng, &outputs[..], &public_keys[..], 999, 10)
+            .unwrap();
+        assert_eq!(10, decoys.len());
+        assert!(decoys.iter().all(|decoy| !decoy.is_signer()));
+    }
+
+    #[test]
+    fn test_output_with_age() {
+        let outputs = ages(10, 100);
+        assert_eq!(Some(9), GammaSelector::output_with_age(&outputs[..], 0));
+        assert_eq!(Some(7), GammaSelector::output_with_age(&outputs[..], 150));
+        assert_eq!(Some(7), GammaSelector::output_with_age(&outputs[..], 200));
+        assert_eq!(Some(0), GammaSelector::output_with_age(&outputs[..], 900));
+        assert_eq!(None, GammaSelector::output_with_age(&outputs[..], 901));
+    }
+
+    #[test]
+    fn test_not_enough_outputs() {
+        let mut rng = rand::thread_rng();
+        let outputs = ages(10, 3600);
+
+        // Every output other than the real output is needed
+        let decoys = GammaSelector::default()
+            .select_decoys(&mut rng, &outputs[..], 9, 9)
+            .unwrap();
+        assert_eq!((0..9).collect::<Vec<u64>>(), decoys);
+
+        match GammaSelector::default().select_decoys(&mut rng, &outputs[..], 9, 10) {
+            Err(ClsagError::NotEnoughOutputs) => {}
+            _ => panic!("expected a not enough outputs error"),
+        }
+    }
+}
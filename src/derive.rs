@@ -0,0 +1,250 @@
+// Derivation of one-time keys from the view and spend keys of an account,
+// in the style of Monero's stealth addresses and subaddresses.
+//
+// An account has a private view key `a` and a private spend key `b`. Its address is the pair
+// of public keys `(A, B) = (a * G, b * G)`. To pay the address, the sender picks a transaction key `r`,
+// publishes the transaction public key `R = r * G` and derives the shared secret `r * A = a * R`.
+// The one-time key of the output at `output_index` is `P = H(r * A, output_index) * G + B`,
+// whose private key `H(a * R, output_index) + b` can only be computed by the owner of the account.
+//
+// Subaddress `i` has the spend key `D = B + m * G` and view key `C = a * D`, where
+// `m = H(a, i)`. Paying a subaddress uses the transaction public key `R = r * D` instead,
+// so that `r * C = a * R` is still the shared secret, and the one-time key is `H(r * C, output_index) * G + D`.
+// Subaddress 0 is the address of the account.
+use crate::constants::BASEPOINT;
+use crate::error::ClsagError;
+use crate::keys::{PublicKey, SecretKey};
+use crate::member::Member;
+use crate::transcript::SigningTranscript;
+use curve25519_dalek::ristretto::RistrettoPoint;
+use curve25519_dalek::scalar::Scalar;
+use merlin::Transcript;
+use rand::{CryptoRng, RngCore};
+
+// The private view and spend keys of an account
+#[derive(Debug, Clone)]
+pub struct AccountKeys {
+    pub view_key: SecretKey,
+    pub spend_key: SecretKey,
+}
+
+// The public view and spend keys of an address, which outputs can be sent to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Address {
+    pub view_key: PublicKey,
+    pub spend_key: PublicKey,
+    // Set for every subaddress other than subaddress 0
+    pub is_subaddress: bool,
+}
+
+// Derives the scalar `H(shared_secret, output_index)` which is added to the spend key
+pub(crate) fn derivation_scalar(shared_secret: &RistrettoPoint, output_index: u64) -> Scalar {
+    let mut transcript = Transcript::new(b"clsag_derive");
+    transcript.append_point(b"shared_secret", shared_secret);
+    transcript.append_u64(b"output_index", output_index);
+    transcript.challenge_scalar(b"derivation")
+}
+
+// Returns the scalar `m = H(a, i)` which is added to the spend key of subaddress `i`.
+// Only the private view key is needed, so a view only wallet can compute its subaddresses
+pub(crate) fn subaddress_scalar(view_key: &SecretKey, index: u32) -> Scalar {
+    if index == 0 {
+        return Scalar::zero();
+    }
+
+    let mut transcript = Transcript::new(b"clsag_subaddress");
+    transcript.append_message(b"view_key", &view_key.to_bytes());
+    transcript.append_u64(b"index", u64::from(index));
+    transcript.challenge_scalar(b"subaddress")
+}
+
+pub(crate) fn decompress(key: &PublicKey) -> Result<RistrettoPoint, ClsagError> {
+    key.decompress().ok_or(ClsagError::InvalidPoint)
+}
+
+impl AccountKeys {
+    // Generates the keys of a new account from the given rng
+    pub fn random<R: RngCore + CryptoRng>(rng: &mut R) -> Self {
+        AccountKeys {
+            view_key: SecretKey::random(rng),
+            spend_key: SecretKey::random(rng),
+        }
+    }
+    // Returns the address of the account, which is subaddress 0
+    pub fn address(&self) -> Address {
+        Address {
+            view_key: self.view_key.public_key(),
+            spend_key: self.spend_key.public_key(),
+            is_subaddress: false,
+        }
+    }
+    // Returns the scalar `m` which is added to the spend key of the subaddress
+    fn subaddress_scalar(&self, index: u32) -> Scalar {
+        subaddress_scalar(&self.view_key, index)
+    }
+    // Returns the subaddress with the given index
+    pub fn subaddress(&self, index: u32) -> Address {
+        if index == 0 {
+            return self.address();
+        }
+
+        let spend_key = (self.spend_key.0 + self.subaddress_scalar(index)) * BASEPOINT;
+        let view_key = self.view_key.0 * spend_key;
+        Address {
+            view_key: PublicKey::from_point(&view_key),
+            spend_key: PublicKey::from_point(&spend_key),
+            is_subaddress: true,
+        }
+    }
+    // Returns the private key of the one-time key in the output at `output_index` of the
+    // transaction with the given public key, which was sent to the given subaddress
+    pub fn one_time_secret_key(
+        &self,
+        tx_public_key: &PublicKey,
+        output_index: u64,
+        subaddress_index: u32,
+    ) -> Result<SecretKey, ClsagError> {
+        let shared_secret = self.view_key.0 * decompress(tx_public_key)?;
+        let secret_key = derivation_scalar(&shared_secret, output_index)
+            + self.spend_key.0
+            + self.subaddress_scalar(subaddress_index);
+
+        Ok(SecretKey(secret_key))
+    }
+    // Returns true if the one-time key was sent to the given subaddress in the output
+    // at `output_index` of the transaction with the given public key.
+    // Only the private view key is used, so this can be run by a view only wallet
+    pub fn owns_output(
+        &self,
+        tx_public_key: &PublicKey,
+        output_index: u64,
+        subaddress_index: u32,
+        one_time_key: &PublicKey,
+    ) -> bool {
+        let (tx_public_key, one_time_key) =
+            match (decompress(tx_public_key), decompress(one_time_key)) {
+                (Ok(tx_public_key), Ok(one_time_key)) => (tx_public_key, one_time_key),
+                _ => return false,
+            };
+        let spend_key = match decompress(&self.subaddress(subaddress_index).spend_key) {
+            Ok(spend_key) => spend_key,
+            Err(_) => return false,
+        };
+
+        let shared_secret = self.view_key.0 * tx_public_key;
+        one_time_key - derivation_scalar(&shared_secret, output_index) * BASEPOINT == spend_key
+    }
+    // Returns the signer for the one-time key in the output at `output_index` of the
+    // transaction with the given public key, which was sent to the given subaddress
+    pub fn signer(
+        &self,
+        tx_public_key: &PublicKey,
+        output_index: u64,
+        subaddress_index: u32,
+    ) -> Result<Member, ClsagError> {
+        let secret_key = self.one_time_secret_key(tx_public_key, output_index, subaddress_index)?;
+        Member::signer_from_secret_keys(&[secret_key])
+    }
+}
+
+impl Address {
+    // Returns the transaction public key for the transaction key `r`,
+    // which is `r * G` for an address and `r * D` for a subaddress
+    pub fn tx_public_key(&self, tx_key: &SecretKey) -> Result<PublicKey, ClsagError> {
+        if !self.is_subaddress {
+            return Ok(tx_key.public_key());
+        }
+
+        Ok(PublicKey::from_point(
+            &(tx_key.0 * decompress(&self.spend_key)?),
+        ))
+    }
+    // Returns the one-time key for the output at `output_index` of a transaction
+    // with the transaction key `r`
+    pub fn one_time_key(
+        &self,
+        tx_key: &SecretKey,
+        output_index: u64,
+    ) -> Result<PublicKey, ClsagError> {
+        let shared_secret = tx_key.0 * decompress(&self.view_key)?;
+        let one_time_key = derivation_scalar(&shared_secret, output_index) * BASEPOINT
+            + decompress(&self.spend_key)?;
+
+        Ok(PublicKey::from_point(&one_time_key))
+    }
+}
+
+// Returns a decoy for the one-time key of another output
+pub fn decoy(one_time_key: &PublicKey) -> Result<Member, ClsagError> {
+    Member::decoy_from_public_keys(&[*one_time_key])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn test_one_time_keys() {
+        let mut rng = rand::thread_rng();
+        let account = AccountKeys::random(&mut rng);
+
+        for subaddress_index in [0, 1, 7] {
+            let address = account.subaddress(subaddress_index);
+            assert_eq!(subaddress_index != 0, address.is_subaddress);
+
+            let tx_key = SecretKey::random(&mut rng);
+            let tx_public_key = address.tx_public_key(&tx_key).unwrap();
+            for output_index in 0..3 {
+                let one_time_key = address.one_time_key(&tx_key, output_index).unwrap();
+                let secret_key = account
+                    .one_time_secret_key(&tx_public_key, output_index, subaddress_index)
+                    .unwrap();
+                assert_eq!(one_time_key, secret_key.public_key());
+
+                assert!(account.owns_output(
+                    &tx_public_key,
+                    output_index,
+                    subaddress_index,
+                    &one_time_key
+                ));
+                assert!(!account.owns_output(
+                    &tx_public_key,
+                    output_index + 1,
+                    subaddress_index,
+                    &one_time_key
+                ));
+                assert!(!AccountKeys::random(&mut rng).owns_output(
+                    &tx_public_key,
+                    output_index,
+                    subaddress_index,
+                    &one_time_key
+                ));
+            }
+        }
+
+        // Different subaddresses have different keys
+        assert_ne!(account.subaddress(1), account.subaddress(2));
+    }
+
+    #[test]
+    fn test_sign_with_derived_keys() {
+        let mut rng = rand::thread_rng();
+        let account = AccountKeys::random(&mut rng);
+        let address = account.subaddress(3);
+        let tx_key = SecretKey::random(&mut rng);
+        let tx_public_key = address.tx_public_key(&tx_key).unwrap();
+
+        let mut clsag = generate_clsag_with(0, 1);
+        for key in generate_rand_public_keys(10) {
+            clsag.add_member(decoy(&key).unwrap()).unwrap();
+        }
+        clsag
+            .add_member(account.signer(&tx_public_key, 1, 3).unwrap())
+            .unwrap();
+
+        let (signature, public_keys) = clsag.sign(b"hello world").unwrap();
+        assert!(signature.verify(&public_keys, b"hello world").is_ok());
+        assert!(public_keys.contains(&vec![address.one_time_key(&tx_key, 1).unwrap()]));
+    }
+}
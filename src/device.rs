@@ -0,0 +1,257 @@
+// Signing with keys held on a device, such as a hardware wallet, which never hands them out.
+//
+// A `SignerDevice` does the three steps of signing which need the signers private keys,
+// while the host builds the ring and computes every challenge. The device is asked for
+// its public keys, then for its key images along with a proof that they were made with
+// the same keys as its public keys, then for the commitment to a fresh nonce, and once the
+// host has gone around the ring, for its response to the challenge of its member.
+// The transport to the device, such as USB HID or a websocket, is up to the implementor.
+//
+// The device only ever sees hashes of the ring, so it cannot check what it is signing.
+// Each nonce is used for a single response, and a device must refuse a second response
+// for the same nonce, as two responses reveal its private keys.
+//
+// `LocalDevice` implements the trait over a `PrivateSet`, for testing an integration
+// without a device attached.
+use crate::dleq::{DleqProof, DleqStatement};
+use crate::error::ClsagError;
+use crate::keys::{zeroize_scalar, KeyImage, PrivateSet, PublicKey, SecretKey};
+use curve25519_dalek::ristretto::RistrettoPoint;
+use curve25519_dalek::scalar::Scalar;
+use merlin::Transcript;
+
+// A device which holds the private keys of a signer
+pub trait SignerDevice {
+    // Returns the public keys of the signer over the generator
+    fn public_keys(&mut self, generator: &RistrettoPoint) -> Result<Vec<PublicKey>, ClsagError>;
+
+    // Returns the key images of the signer over the hash of its first public key, along with
+    // a proof made with `key_image_transcript` that each key image has the same private key
+    // as the public key of the same layer
+    fn key_images(
+        &mut self,
+        generator: &RistrettoPoint,
+        hashed_pubkey: &RistrettoPoint,
+    ) -> Result<(Vec<KeyImage>, DleqProof), ClsagError>;
+
+    // Draws a fresh nonce, returning its commitments over the generator
+    // and over the hash of the first public key
+    fn commit_nonce(
+        &mut self,
+        generator: &RistrettoPoint,
+        hashed_pubkey: &RistrettoPoint,
+    ) -> Result<(RistrettoPoint, RistrettoPoint), ClsagError>;
+
+    // Returns the response of the signer to the challenge with the aggregation coefficients,
+    // using up the committed nonce.
+    // Returns an error if no nonce has been committed since the last response
+    fn respond(&mut self, challenge: &Scalar, agg_coeffs: &[Scalar]) -> Result<Scalar, ClsagError>;
+}
+
+// Returns the transcript the proof of the key images of a device is made with
+pub fn key_image_transcript() -> Transcript {
+    Transcript::new(b"clsag_device_key_images")
+}
+
+// Returns the statements the key images of a device are proven over,
+// one for each public key of the device
+pub(crate) fn key_image_statements(
+    generator: &RistrettoPoint,
+    hashed_pubkey: &RistrettoPoint,
+    public_keys: &[RistrettoPoint],
+    key_images: &[KeyImage],
+) -> Result<Vec<DleqStatement>, ClsagError> {
+    if public_keys.len() != key_images.len() {
+        return Err(ClsagError::KeyCountMismatch);
+    }
+    public_keys
+        .iter()
+        .zip(key_images)
+        .map(|(public_key, key_image)| {
+            Ok(DleqStatement {
+                base_a: *generator,
+                point_a: *public_key,
+                base_b: *hashed_pubkey,
+                point_b: key_image.check()?,
+            })
+        })
+        .collect()
+}
+
+// A signer device over private keys held in memory
+pub struct LocalDevice {
+    private_set: PrivateSet,
+    nonce: Option<Scalar>,
+}
+
+impl LocalDevice {
+    pub fn new(private_set: PrivateSet) -> Self {
+        LocalDevice {
+            private_set,
+            nonce: None,
+        }
+    }
+
+    fn secret_keys(&self) -> Vec<SecretKey> {
+        self.private_set.0.iter().map(|x| SecretKey(*x)).collect()
+    }
+}
+
+impl SignerDevice for LocalDevice {
+    fn public_keys(&mut self, generator: &RistrettoPoint) -> Result<Vec<PublicKey>, ClsagError> {
+        Ok(self
+            .secret_keys()
+            .iter()
+            .map(|secret_key| secret_key.public_key_with_generator(generator))
+            .collect())
+    }
+
+    fn key_images(
+        &mut self,
+        generator: &RistrettoPoint,
+        hashed_pubkey: &RistrettoPoint,
+    ) -> Result<(Vec<KeyImage>, DleqProof), ClsagError> {
+        let secret_keys = self.secret_keys();
+        let statements: Vec<DleqStatement> = secret_keys
+            .iter()
+            .map(|secret_key| DleqStatement::new(secret_key, *generator, *hashed_pubkey))
+            .collect();
+        let key_images = self.private_set.compute_key_images(hashed_pubkey);
+
+        let mut rng = rand::thread_rng();
+        let proof = DleqProof::prove(&mut rng, &key_image_transcript(), &statements, &secret_keys)?;
+        Ok((key_images, proof))
+    }
+
+    fn commit_nonce(
+        &mut self,
+        generator: &RistrettoPoint,
+        hashed_pubkey: &RistrettoPoint,
+    ) -> Result<(RistrettoPoint, RistrettoPoint), ClsagError> {
+        let nonce = Scalar::random(&mut rand::thread_rng());
+        let commitments = (nonce * generator, nonce * hashed_pubkey);
+        self.nonce = Some(nonce);
+        Ok(commitments)
+    }
+
+    fn respond(&mut self, challenge: &Scalar, agg_coeffs: &[Scalar]) -> Result<Scalar, ClsagError> {
+        if agg_coeffs.len() != self.private_set.len() {
+            return Err(ClsagError::KeyCountMismatch);
+        }
+        let mut nonce = self.nonce.take().ok_or(ClsagError::NonceNotCommitted)?;
+
+        let mut sum: Scalar = self
+            .private_set
+            .0
+            .iter()
+            .zip(agg_coeffs)
+            .map(|(x, mu)| x * mu)
+            .sum();
+        let response = nonce - challenge * sum;
+
+        zeroize_scalar(&mut sum);
+        zeroize_scalar(&mut nonce);
+        Ok(response)
+    }
+}
+
+impl Drop for LocalDevice {
+    fn drop(&mut self) {
+        if let Some(nonce) = self.nonce.as_mut() {
+            zeroize_scalar(nonce);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::builder::{ClsagBuilder, ReadyToSignWithDevice};
+    use crate::constants::BASEPOINT;
+    use crate::policy::RingPolicy;
+    use crate::test_utils::*;
+
+    // Builds a ring of decoys with a device as the signer
+    fn device_ring<D: SignerDevice>(device: D) -> ReadyToSignWithDevice<D> {
+        let mut builder = ClsagBuilder::new().ring_policy(RingPolicy::permissive());
+        for decoy in generate_decoys(4, 2) {
+            builder = builder.add_decoy(decoy).unwrap();
+        }
+        builder.device_signer(device).unwrap().finalize().unwrap()
+    }
+
+    #[test]
+    fn test_sign_with_device() {
+        let msg = b"hello world";
+        let mut ring = device_ring(LocalDevice::new(generate_private_set(2)));
+        assert_eq!(5, ring.public_keys().len());
+
+        let (signature, public_keys) = ring.sign(msg).unwrap();
+        assert!(signature.verify(&public_keys, msg).is_ok());
+        assert!(signature.optimised_verify(&public_keys, msg).is_ok());
+
+        // Every signature from the device links by its key images
+        let (other, _) = ring.sign(b"another message").unwrap();
+        assert_eq!(signature.key_images, other.key_images);
+    }
+
+    #[test]
+    fn test_device_key_images_are_checked() {
+        // A device which returns key images for other keys than its public keys
+        struct WrongKeyImages(LocalDevice, LocalDevice);
+
+        impl SignerDevice for WrongKeyImages {
+            fn public_keys(
+                &mut self,
+                generator: &RistrettoPoint,
+            ) -> Result<Vec<PublicKey>, ClsagError> {
+                self.0.public_keys(generator)
+            }
+            fn key_images(
+                &mut self,
+                generator: &RistrettoPoint,
+                hashed_pubkey: &RistrettoPoint,
+            ) -> Result<(Vec<KeyImage>, DleqProof), ClsagError> {
+                self.1.key_images(generator, hashed_pubkey)
+            }
+            fn commit_nonce(
+                &mut self,
+                generator: &RistrettoPoint,
+                hashed_pubkey: &RistrettoPoint,
+            ) -> Result<(RistrettoPoint, RistrettoPoint), ClsagError> {
+                self.0.commit_nonce(generator, hashed_pubkey)
+            }
+            fn respond(
+                &mut self,
+                challenge: &Scalar,
+                agg_coeffs: &[Scalar],
+            ) -> Result<Scalar, ClsagError> {
+                self.0.respond(challenge, agg_coeffs)
+            }
+        }
+
+        let mut ring = device_ring(WrongKeyImages(
+            LocalDevice::new(generate_private_set(2)),
+            LocalDevice::new(generate_private_set(2)),
+        ));
+        match ring.sign(b"hello world") {
+            Err(ClsagError::InvalidDleqProof) => {}
+            _ => panic!("expected an invalid dleq proof error"),
+        }
+    }
+
+    #[test]
+    fn test_device_nonce_is_used_once() {
+        let mut device = LocalDevice::new(generate_private_set(1));
+        let challenge = generate_rand_scalars(1)[0];
+        let agg_coeffs = generate_rand_scalars(1);
+
+        let hashed_pubkey = generate_rand_points(1)[0];
+        device.commit_nonce(&BASEPOINT, &hashed_pubkey).unwrap();
+        assert!(device.respond(&challenge, &agg_coeffs).is_ok());
+        match device.respond(&challenge, &agg_coeffs) {
+            Err(ClsagError::NonceNotCommitted) => {}
+            _ => panic!("expected a nonce not committed error"),
+        }
+    }
+}
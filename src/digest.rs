@@ -0,0 +1,66 @@
+// Hashing of messages for `Clsag::sign_digest` and `Signature::verify_digest`.
+//
+// Signing the digest of a message, rather than the message, means the message never needs
+// to be held in memory, so payloads of any size can be signed by hashing them from a reader.
+// It also lets a transaction hash computed elsewhere be signed directly.
+// The digest is the 64 byte SHA-512 hash of the message.
+use sha2::{Digest, Sha512};
+use std::io::{self, Read};
+
+// The size of the buffer messages are read into while they are hashed
+const READ_BUFFER_SIZE: usize = 8192;
+
+// Returns the 64 byte SHA-512 digest of the message
+pub fn hash_message(msg: &[u8]) -> [u8; 64] {
+    let mut digest = [0u8; 64];
+    digest.copy_from_slice(&Sha512::digest(msg));
+    digest
+}
+
+// Returns the 64 byte SHA-512 digest of everything read from the reader,
+// hashing it as it is read so that only a small buffer is held in memory.
+// Returns an error if reading fails
+pub fn hash_reader<R: Read>(mut reader: R) -> io::Result<[u8; 64]> {
+    let mut hasher = Sha512::new();
+    let mut buffer = [0u8; READ_BUFFER_SIZE];
+    loop {
+        match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => hasher.input(&buffer[..n]),
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    let mut digest = [0u8; 64];
+    digest.copy_from_slice(&hasher.result());
+    Ok(digest)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn test_sign_digest() {
+        let mut clsag = generate_clsag_with(4, 2);
+        clsag.add_member(generate_signer(2)).unwrap();
+
+        // A message larger than the read buffer, hashed from a reader
+        let msg: Vec<u8> = (0..3 * READ_BUFFER_SIZE + 17).map(|i| i as u8).collect();
+        let digest = hash_reader(&msg[..]).unwrap();
+        assert_eq!(hash_message(&msg), digest);
+
+        let (signature, public_keys) = clsag.sign_digest(&digest).unwrap();
+        assert!(signature.verify_digest(&public_keys, &digest).is_ok());
+        assert!(signature
+            .verify_digest(&public_keys, &hash_message(b"another message"))
+            .is_err());
+
+        // A signature over a digest is not a signature over the digest bytes, nor the reverse
+        assert!(signature.verify(&public_keys, &digest).is_err());
+        let (signature, public_keys) = clsag.sign(&digest).unwrap();
+        assert!(signature.verify_digest(&public_keys, &digest).is_err());
+    }
+}
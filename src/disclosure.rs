@@ -0,0 +1,373 @@
+// Disclosure proofs, which let the signer of a signature prove which member of the ring signed,
+// for audits of signatures which are otherwise anonymous.
+//
+// The key images of a signature are `I_j = x_j * H(P)`, where `x_j` are the private keys of
+// the signer and `P` is their first key. A disclosure proof is a proof that the keys of the
+// member at a position in the ring have the same discrete logs over the basepoint as the key
+// images have over the hash of that members first key, which only the signer can make.
+// The proof is bound to the signature it discloses, so it cannot be moved to another signature,
+// and it reveals nothing about the private keys.
+//
+// The signer chooses when to disclose: the signature stays anonymous to everyone who is not
+// given the proof. Anyone who is given the proof can show it to others, so a disclosure cannot
+// be taken back.
+//
+// A member who did not sign can prove so with a non-signer proof, for disputes over who made
+// a signature. The member reveals the key image their first key would have made, `x * H(P)`,
+// along with a proof that it was made with the key of their position in the ring. The key image
+// of a signature can only be made by its signer, so a different key image proves that the
+// member did not sign, while revealing nothing about which member did. Revealing the key image
+// links every signature the member makes with that key, so it should only be used for keys
+// which are not used for anonymous signing again.
+//
+// The proofs are computed over the basepoint and the default hash construction,
+// so they cannot be made for a ring signed over another generator or hash construction.
+use crate::constants::BASEPOINT;
+use crate::dleq::{DleqProof, DleqStatement};
+use crate::error::ClsagError;
+use crate::keys::{KeyImage, PublicKey, SecretKey};
+use crate::ring::PreparedRing;
+use crate::signature::Signature;
+use curve25519_dalek::ristretto::RistrettoPoint;
+use merlin::Transcript;
+use rand::{CryptoRng, RngCore};
+
+// A proof that the member at a position in the ring made a signature
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DisclosureProof {
+    signer_index: usize,
+    // One statement for each layer of keys, that the key and the key image have the same log
+    proof: DleqProof,
+}
+
+// A proof that the member at a position in the ring did not make a signature
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NonSignerProof {
+    member_index: usize,
+    // The key image the first key of the member makes, which is not the key image of the signature
+    key_image: KeyImage,
+    proof: DleqProof,
+}
+
+impl DisclosureProof {
+    // Returns the position in the ring of the member the proof discloses as the signer
+    pub fn signer_index(&self) -> usize {
+        self.signer_index
+    }
+    // Verifies that the signature on the message over the ring was made by the member
+    // at the position of the proof.
+    // Returns an error if the signature does not verify, if the position is not in the ring,
+    // or if the proof does not verify for the signature
+    pub fn verify(
+        &self,
+        signature: &Signature,
+        public_keys: &[Vec<PublicKey>],
+        msg: &[u8],
+    ) -> Result<(), ClsagError> {
+        let ring = PreparedRing::new(public_keys)?;
+        signature.verify_prepared(&ring, msg)?;
+        if self.signer_index >= ring.len() {
+            return Err(ClsagError::MemberNotFound);
+        }
+
+        let hashed_pubkey = ring.hashed_pubkeys()[self.signer_index];
+        let key_images = signature
+            .key_images
+            .iter()
+            .map(KeyImage::check)
+            .collect::<Result<Vec<RistrettoPoint>, ClsagError>>()?;
+        let statements: Vec<DleqStatement> = ring.points()[self.signer_index]
+            .iter()
+            .zip(&key_images)
+            .map(|(point, key_image)| DleqStatement {
+                base_a: BASEPOINT,
+                point_a: *point,
+                base_b: hashed_pubkey,
+                point_b: *key_image,
+            })
+            .collect();
+
+        let transcript = disclosure_transcript(b"clsag_disclosure", signature, self.signer_index);
+        self.proof
+            .verify(&transcript, &statements)
+            .map_err(|_| ClsagError::InvalidDisclosure)
+    }
+}
+
+impl NonSignerProof {
+    // Returns the position in the ring of the member the proof excludes as the signer
+    pub fn member_index(&self) -> usize {
+        self.member_index
+    }
+    // Returns the key image of the first key of the member
+    pub fn key_image(&self) -> &KeyImage {
+        &self.key_image
+    }
+    // Verifies that the signature on the message over the ring was not made by the member
+    // at the position of the proof.
+    // Returns an error if the signature does not verify, if the position is not in the ring,
+    // if the key image of the member is the key image of the signature,
+    // or if the proof does not verify for the signature
+    pub fn verify(
+        &self,
+        signature: &Signature,
+        public_keys: &[Vec<PublicKey>],
+        msg: &[u8],
+    ) -> Result<(), ClsagError> {
+        let ring = PreparedRing::new(public_keys)?;
+        signature.verify_prepared(&ring, msg)?;
+        if self.member_index >= ring.len() {
+            return Err(ClsagError::MemberNotFound);
+        }
+        if signature.key_images.first() == Some(&self.key_image) {
+            return Err(ClsagError::SignerNotExcluded);
+        }
+
+        let statement = DleqStatement {
+            base_a: BASEPOINT,
+            point_a: ring.points()[self.member_index][0],
+            base_b: ring.hashed_pubkeys()[self.member_index],
+            point_b: self.key_image.check()?,
+        };
+        let transcript = disclosure_transcript(b"clsag_non_signer", signature, self.member_index);
+        self.proof
+            .verify(&transcript, &[statement])
+            .map_err(|_| ClsagError::InvalidDisclosure)
+    }
+}
+
+// Proves that the holder of the private keys made the signature on the message over the ring.
+// Returns an error if the signature does not verify, if no member of the ring has the keys,
+// or if the key images of the signature were not made with the keys
+pub fn disclose<R: RngCore + CryptoRng>(
+    rng: &mut R,
+    signature: &Signature,
+    public_keys: &[Vec<PublicKey>],
+    msg: &[u8],
+    secret_keys: &[SecretKey],
+) -> Result<DisclosureProof, ClsagError> {
+    let ring = PreparedRing::new(public_keys)?;
+    signature.verify_prepared(&ring, msg)?;
+
+    let signer_keys: Vec<PublicKey> = secret_keys.iter().map(SecretKey::public_key).collect();
+    let signer_index = public_keys
+        .iter()
+        .position(|keys| keys == &signer_keys)
+        .ok_or(ClsagError::NoSigner)?;
+
+    let hashed_pubkey = ring.hashed_pubkeys()[signer_index];
+    let statements: Vec<DleqStatement> = secret_keys
+        .iter()
+        .map(|secret_key| DleqStatement::new(secret_key, BASEPOINT, hashed_pubkey))
+        .collect();
+    let key_images: Vec<KeyImage> = statements
+        .iter()
+        .map(|statement| KeyImage(statement.point_b.compress()))
+        .collect();
+    if key_images != signature.key_images {
+        return Err(ClsagError::KeyImageMismatch);
+    }
+
+    let transcript = disclosure_transcript(b"clsag_disclosure", signature, signer_index);
+    let proof = DleqProof::prove(rng, &transcript, &statements, secret_keys)?;
+    Ok(DisclosureProof {
+        signer_index,
+        proof,
+    })
+}
+
+// Proves that the member of the ring whose first key is the public key of the secret key
+// did not make the signature on the message over the ring.
+// Returns an error if the signature does not verify, if no member of the ring has the key
+// as their first key, or if the member made the signature
+pub fn prove_non_signer<R: RngCore + CryptoRng>(
+    rng: &mut R,
+    signature: &Signature,
+    public_keys: &[Vec<PublicKey>],
+    msg: &[u8],
+    secret_key: &SecretKey,
+) -> Result<NonSignerProof, ClsagError> {
+    let ring = PreparedRing::new(public_keys)?;
+    signature.verify_prepared(&ring, msg)?;
+
+    let public_key = secret_key.public_key();
+    let member_index = public_keys
+        .iter()
+        .position(|keys| keys.first() == Some(&public_key))
+        .ok_or(ClsagError::MemberNotFound)?;
+
+    let statement = DleqStatement::new(secret_key, BASEPOINT, ring.hashed_pubkeys()[member_index]);
+    let key_image = KeyImage(statement.point_b.compress());
+    if signature.key_images.first() == Some(&key_image) {
+        return Err(ClsagError::SignerNotExcluded);
+    }
+
+    let transcript = disclosure_transcript(b"clsag_non_signer", signature, member_index);
+    let proof = DleqProof::prove(
+        rng,
+        &transcript,
+        &[statement],
+        std::slice::from_ref(secret_key),
+    )?;
+    Ok(NonSignerProof {
+        member_index,
+        key_image,
+        proof,
+    })
+}
+
+// Returns the transcript of a proof, which binds it to the signature and the position
+// of the member, with each kind of proof domain separated by its label
+fn disclosure_transcript(
+    label: &'static [u8],
+    signature: &Signature,
+    member_index: usize,
+) -> Transcript {
+    let mut transcript = Transcript::new(label);
+    transcript.append_message(b"signature", &signature.to_bytes());
+    transcript.append_u64(b"index", member_index as u64);
+    transcript
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::member::Member;
+    use crate::test_utils::*;
+
+    #[test]
+    fn test_disclose_signer() {
+        let msg = b"hello world";
+        let mut rng = rand::thread_rng();
+        let secret_keys = vec![SecretKey::random(&mut rng), SecretKey::random(&mut rng)];
+
+        let mut clsag = generate_clsag_with(6, 2);
+        clsag
+            .add_member(Member::signer_from_secret_keys(&secret_keys).unwrap())
+            .unwrap();
+        let (signature, public_keys) = clsag.sign(msg).unwrap();
+
+        let proof = disclose(&mut rng, &signature, &public_keys, msg, &secret_keys).unwrap();
+        assert_eq!(clsag.public_keys()[6], public_keys[proof.signer_index()]);
+        assert!(proof.verify(&signature, &public_keys, msg).is_ok());
+        assert!(proof
+            .verify(&signature, &public_keys, b"another message")
+            .is_err());
+
+        // The proof does not disclose another member as the signer
+        let mut moved = proof.clone();
+        moved.signer_index = (moved.signer_index + 1) % public_keys.len();
+        match moved.verify(&signature, &public_keys, msg) {
+            Err(ClsagError::InvalidDisclosure) => {}
+            _ => panic!("expected an invalid disclosure error"),
+        }
+        moved.signer_index = public_keys.len();
+        match moved.verify(&signature, &public_keys, msg) {
+            Err(ClsagError::MemberNotFound) => {}
+            _ => panic!("expected a member not found error"),
+        }
+
+        // The proof is bound to the signature it was made for
+        let (other_signature, other_public_keys) = clsag.sign(msg).unwrap();
+        let mut other_proof = proof.clone();
+        other_proof.signer_index = other_public_keys
+            .iter()
+            .position(|keys| keys == &clsag.public_keys()[6])
+            .unwrap();
+        match other_proof.verify(&other_signature, &other_public_keys, msg) {
+            Err(ClsagError::InvalidDisclosure) => {}
+            _ => panic!("expected an invalid disclosure error"),
+        }
+    }
+
+    #[test]
+    fn test_disclose_rejects_other_keys() {
+        let msg = b"hello world";
+        let mut rng = rand::thread_rng();
+        let secret_key = SecretKey::random(&mut rng);
+        let decoy_key = SecretKey::random(&mut rng);
+
+        let mut clsag = generate_clsag_with(4, 1);
+        let signer = Member::signer_from_secret_keys(std::slice::from_ref(&secret_key)).unwrap();
+        clsag.add_member(signer).unwrap();
+        clsag
+            .add_member(Member::new_decoy(vec![decoy_key.0 * BASEPOINT]))
+            .unwrap();
+        let (signature, public_keys) = clsag.sign(msg).unwrap();
+
+        // Keys which are not in the ring cannot disclose the signer
+        let outsider = SecretKey::random(&mut rng);
+        match disclose(&mut rng, &signature, &public_keys, msg, &[outsider]) {
+            Err(ClsagError::NoSigner) => {}
+            _ => panic!("expected a no signer error"),
+        }
+
+        // A decoy cannot claim the signature, as its keys did not make the key images
+        match disclose(&mut rng, &signature, &public_keys, msg, &[decoy_key]) {
+            Err(ClsagError::KeyImageMismatch) => {}
+            _ => panic!("expected a key image mismatch error"),
+        }
+    }
+
+    #[test]
+    fn test_prove_non_signer() {
+        let msg = b"hello world";
+        let mut rng = rand::thread_rng();
+        let secret_key = SecretKey::random(&mut rng);
+        let decoy_key = SecretKey::random(&mut rng);
+
+        let mut clsag = generate_clsag_with(4, 2);
+        clsag.add_member(generate_signer(2)).unwrap();
+        let decoy_keys = vec![decoy_key.0 * BASEPOINT, secret_key.0 * BASEPOINT];
+        clsag.add_member(Member::new_decoy(decoy_keys)).unwrap();
+        let (signature, public_keys) = clsag.sign(msg).unwrap();
+
+        let proof = prove_non_signer(&mut rng, &signature, &public_keys, msg, &decoy_key).unwrap();
+        assert_eq!(clsag.public_keys()[5], public_keys[proof.member_index()]);
+        assert!(proof.verify(&signature, &public_keys, msg).is_ok());
+        assert!(proof
+            .verify(&signature, &public_keys, b"another message")
+            .is_err());
+
+        // The proof does not exclude another member
+        let mut moved = proof.clone();
+        moved.member_index = (moved.member_index + 1) % public_keys.len();
+        match moved.verify(&signature, &public_keys, msg) {
+            Err(ClsagError::InvalidDisclosure) => {}
+            _ => panic!("expected an invalid disclosure error"),
+        }
+
+        // A proof claiming the key image of the signature does not exclude the signer
+        let mut claimed = proof.clone();
+        claimed.key_image = signature.key_images[0];
+        match claimed.verify(&signature, &public_keys, msg) {
+            Err(ClsagError::SignerNotExcluded) => {}
+            _ => panic!("expected a signer not excluded error"),
+        }
+
+        // Only the first key of a member identifies it
+        match prove_non_signer(&mut rng, &signature, &public_keys, msg, &secret_key) {
+            Err(ClsagError::MemberNotFound) => {}
+            _ => panic!("expected a member not found error"),
+        }
+    }
+
+    #[test]
+    fn test_signer_cannot_prove_non_signer() {
+        let msg = b"hello world";
+        let mut rng = rand::thread_rng();
+        let secret_key = SecretKey::random(&mut rng);
+
+        let mut clsag = generate_clsag_with(4, 1);
+        let signer = Member::signer_from_secret_keys(std::slice::from_ref(&secret_key)).unwrap();
+        clsag.add_member(signer).unwrap();
+        let (signature, public_keys) = clsag.sign(msg).unwrap();
+
+        match prove_non_signer(&mut rng, &signature, &public_keys, msg, &secret_key) {
+            Err(ClsagError::SignerNotExcluded) => {}
+            _ => panic!("expected a signer not excluded error"),
+        }
+    }
+}
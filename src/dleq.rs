@@ -0,0 +1,189 @@
+// Proofs of equality of discrete logs, which prove that `x * A = X` and `x * B = Y` for the same
+// secret `x`, without revealing it. This is the argument behind the proofs that a key image was
+// made with the key of a member, and is exported so that protocols built on this crate
+// can use the same transcript conventions.
+//
+// A proof covers a batch of statements, each with their own secret and their own pair of
+// generators, with a single challenge for the whole batch. The challenge is derived from the
+// transcript the proof is made with, so the transcript binds the proof to its context:
+// the prover and the verifier must append the same context to the transcript.
+use crate::error::ClsagError;
+use crate::keys::{zeroize_scalar, SecretKey};
+use crate::transcript::SigningTranscript;
+use curve25519_dalek::ristretto::RistrettoPoint;
+use curve25519_dalek::scalar::Scalar;
+use rand::{CryptoRng, RngCore};
+
+// The statement that the discrete log of `point_a` over `base_a` is the discrete log
+// of `point_b` over `base_b`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DleqStatement {
+    pub base_a: RistrettoPoint,
+    pub point_a: RistrettoPoint,
+    pub base_b: RistrettoPoint,
+    pub point_b: RistrettoPoint,
+}
+
+// A proof of a batch of statements, with one response for each statement
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DleqProof {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::scalar"))]
+    challenge: Scalar,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::scalars"))]
+    responses: Vec<Scalar>,
+}
+
+impl DleqStatement {
+    // Returns the statement for the secret, which holds by construction
+    pub fn new(secret: &SecretKey, base_a: RistrettoPoint, base_b: RistrettoPoint) -> Self {
+        DleqStatement {
+            base_a,
+            point_a: secret.0 * base_a,
+            base_b,
+            point_b: secret.0 * base_b,
+        }
+    }
+}
+
+impl DleqProof {
+    // Proves the statements with their secrets, in the same order.
+    // Returns an error if there are no statements or the number of secrets is different
+    pub fn prove<R: RngCore + CryptoRng, T: SigningTranscript>(
+        rng: &mut R,
+        transcript: &T,
+        statements: &[DleqStatement],
+        secrets: &[SecretKey],
+    ) -> Result<DleqProof, ClsagError> {
+        if statements.is_empty() || statements.len() != secrets.len() {
+            return Err(ClsagError::KeyCountMismatch);
+        }
+
+        let mut nonces: Vec<Scalar> = secrets.iter().map(|_| Scalar::random(rng)).collect();
+        let nonce_points: Vec<(RistrettoPoint, RistrettoPoint)> = nonces
+            .iter()
+            .zip(statements)
+            .map(|(nonce, statement)| (nonce * statement.base_a, nonce * statement.base_b))
+            .collect();
+        let challenge = dleq_challenge(transcript, statements, &nonce_points);
+
+        let responses = nonces
+            .iter()
+            .zip(secrets)
+            .map(|(nonce, secret)| nonce - challenge * secret.0)
+            .collect();
+        nonces.iter_mut().for_each(zeroize_scalar);
+
+        Ok(DleqProof {
+            challenge,
+            responses,
+        })
+    }
+    // Verifies the proof of the statements, made with the same transcript.
+    // Returns an error if the number of statements is not the number the proof was made for,
+    // or if the proof does not verify
+    pub fn verify<T: SigningTranscript>(
+        &self,
+        transcript: &T,
+        statements: &[DleqStatement],
+    ) -> Result<(), ClsagError> {
+        if statements.is_empty() || statements.len() != self.responses.len() {
+            return Err(ClsagError::KeyCountMismatch);
+        }
+
+        // A = response * base_a + challenge * point_a
+        // B = response * base_b + challenge * point_b
+        let nonce_points: Vec<(RistrettoPoint, RistrettoPoint)> = self
+            .responses
+            .iter()
+            .zip(statements)
+            .map(|(response, statement)| {
+                (
+                    response * statement.base_a + self.challenge * statement.point_a,
+                    response * statement.base_b + self.challenge * statement.point_b,
+                )
+            })
+            .collect();
+
+        if dleq_challenge(transcript, statements, &nonce_points) != self.challenge {
+            return Err(ClsagError::InvalidDleqProof);
+        }
+        Ok(())
+    }
+}
+
+// Returns the challenge of the proof, which binds it to the context in the transcript,
+// every statement in the batch and the nonce points
+fn dleq_challenge<T: SigningTranscript>(
+    transcript: &T,
+    statements: &[DleqStatement],
+    nonce_points: &[(RistrettoPoint, RistrettoPoint)],
+) -> Scalar {
+    let mut transcript = transcript.clone();
+    transcript.append_domain(b"dleq");
+    for (statement, (nonce_a, nonce_b)) in statements.iter().zip(nonce_points) {
+        transcript.append_point(b"base_a", &statement.base_a);
+        transcript.append_point(b"point_a", &statement.point_a);
+        transcript.append_point(b"base_b", &statement.base_b);
+        transcript.append_point(b"point_b", &statement.point_b);
+        transcript.append_point(b"A", nonce_a);
+        transcript.append_point(b"B", nonce_b);
+    }
+    transcript.challenge_scalar(b"c")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::constants::BASEPOINT;
+    use crate::hash::hash_pubkey;
+    use merlin::Transcript;
+
+    #[test]
+    fn test_dleq_batch() {
+        let mut rng = rand::thread_rng();
+        let transcript = Transcript::new(b"test_dleq");
+        let hashed_point = hash_pubkey(&[1u8; 32]);
+
+        let secrets = vec![SecretKey::random(&mut rng), SecretKey::random(&mut rng)];
+        let statements: Vec<DleqStatement> = secrets
+            .iter()
+            .map(|secret| DleqStatement::new(secret, BASEPOINT, hashed_point))
+            .collect();
+        let proof = DleqProof::prove(&mut rng, &transcript, &statements, &secrets).unwrap();
+        assert!(proof.verify(&transcript, &statements).is_ok());
+
+        // The proof is bound to the context of the transcript
+        let other_transcript = Transcript::new(b"other_context");
+        match proof.verify(&other_transcript, &statements) {
+            Err(ClsagError::InvalidDleqProof) => {}
+            _ => panic!("expected an invalid dleq proof error"),
+        }
+        match proof.verify(&transcript, &statements[..1]) {
+            Err(ClsagError::KeyCountMismatch) => {}
+            _ => panic!("expected a key count mismatch error"),
+        }
+    }
+
+    #[test]
+    fn test_dleq_rejects_different_logs() {
+        let mut rng = rand::thread_rng();
+        let transcript = Transcript::new(b"test_dleq");
+        let hashed_point = hash_pubkey(&[2u8; 32]);
+
+        let secret = SecretKey::random(&mut rng);
+        let other_secret = SecretKey::random(&mut rng);
+        let statement = DleqStatement {
+            base_a: BASEPOINT,
+            point_a: secret.0 * BASEPOINT,
+            base_b: hashed_point,
+            point_b: other_secret.0 * hashed_point,
+        };
+
+        let proof = DleqProof::prove(&mut rng, &transcript, &[statement], &[secret]).unwrap();
+        match proof.verify(&transcript, &[statement]) {
+            Err(ClsagError::InvalidDleqProof) => {}
+            _ => panic!("expected an invalid dleq proof error"),
+        }
+    }
+}
@@ -0,0 +1,165 @@
+// Text encodings for keys, key images and signatures.
+// `Display` and `FromStr` use lowercase hex of the byte encoding, which is the
+// 32 byte compressed point for keys and key images and `Signature::to_bytes` for signatures.
+// Base58 uses the bitcoin alphabet over the same bytes.
+use crate::error::ClsagError;
+use crate::keys::{KeyImage, PublicKey};
+use crate::signature::Signature;
+use std::fmt;
+use std::str::FromStr;
+
+// Decodes exactly 32 bytes from the hex string
+fn decode_hex_32(s: &str) -> Result<[u8; 32], ClsagError> {
+    let mut bytes = [0u8; 32];
+    hex::decode_to_slice(s, &mut bytes).map_err(|_| ClsagError::DecodeError)?;
+    Ok(bytes)
+}
+
+// Decodes exactly 32 bytes from the base58 string
+fn decode_base58_32(s: &str) -> Result<[u8; 32], ClsagError> {
+    let mut bytes = [0u8; 32];
+    match bs58::decode(s).onto(&mut bytes) {
+        Ok(32) => Ok(bytes),
+        _ => Err(ClsagError::DecodeError),
+    }
+}
+
+impl fmt::Display for PublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", hex::encode(self.as_bytes()))
+    }
+}
+
+impl FromStr for PublicKey {
+    type Err = ClsagError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PublicKey::from_bytes(&decode_hex_32(s)?)
+    }
+}
+
+impl PublicKey {
+    // Returns the base58 encoding of the public key
+    pub fn to_base58(&self) -> String {
+        bs58::encode(self.as_bytes()).into_string()
+    }
+    // Decodes a public key from its base58 encoding
+    pub fn from_base58(s: &str) -> Result<Self, ClsagError> {
+        PublicKey::from_bytes(&decode_base58_32(s)?)
+    }
+}
+
+impl fmt::Display for KeyImage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", hex::encode(self.as_bytes()))
+    }
+}
+
+impl FromStr for KeyImage {
+    type Err = ClsagError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        KeyImage::from_bytes(&decode_hex_32(s)?)
+    }
+}
+
+impl KeyImage {
+    // Returns the base58 encoding of the key image
+    pub fn to_base58(&self) -> String {
+        bs58::encode(self.as_bytes()).into_string()
+    }
+    // Decodes a key image from its base58 encoding
+    pub fn from_base58(s: &str) -> Result<Self, ClsagError> {
+        KeyImage::from_bytes(&decode_base58_32(s)?)
+    }
+}
+
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", hex::encode(self.to_bytes()))
+    }
+}
+
+impl FromStr for Signature {
+    type Err = ClsagError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = hex::decode(s).map_err(|_| ClsagError::DecodeError)?;
+        Signature::from_bytes(&bytes)
+    }
+}
+
+impl Signature {
+    // Returns the base58 encoding of `to_bytes`
+    pub fn to_base58(&self) -> String {
+        bs58::encode(self.to_bytes()).into_string()
+    }
+    // Decodes a signature from the base58 encoding of `to_bytes`
+    pub fn from_base58(s: &str) -> Result<Self, ClsagError> {
+        let bytes = bs58::decode(s)
+            .into_vec()
+            .map_err(|_| ClsagError::DecodeError)?;
+        Signature::from_bytes(&bytes)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn test_key_round_trip() {
+        let signer = generate_signer(2);
+        let public_key = signer.public_set.to_keys()[0];
+        let key_image = signer.compute_key_images().unwrap()[0];
+
+        let hex = public_key.to_string();
+        assert_eq!(64, hex.len());
+        assert_eq!(public_key, hex.parse().unwrap());
+        assert_eq!(
+            public_key,
+            PublicKey::from_base58(&public_key.to_base58()).unwrap()
+        );
+
+        assert_eq!(key_image, key_image.to_string().parse().unwrap());
+        assert_eq!(
+            key_image,
+            KeyImage::from_base58(&key_image.to_base58()).unwrap()
+        );
+
+        // Strings which are not hex, or not exactly 32 bytes, are rejected
+        for bad in ["zz", "00", &hex[..62], &format!("{}00", hex)] {
+            match bad.parse::<PublicKey>() {
+                Err(ClsagError::DecodeError) => {}
+                _ => panic!("expected a decode error"),
+            }
+        }
+        match "ff".repeat(32).parse::<KeyImage>() {
+            Err(ClsagError::InvalidKeyImage) => {}
+            _ => panic!("expected an invalid key image error"),
+        }
+        match PublicKey::from_base58("0OIl") {
+            Err(ClsagError::DecodeError) => {}
+            _ => panic!("expected a decode error"),
+        }
+    }
+
+    #[test]
+    fn test_signature_round_trip() {
+        let mut clsag = generate_clsag_with(3, 2);
+        clsag.add_member(generate_signer(2)).unwrap();
+        let (signature, _) = clsag.sign(b"hello world").unwrap();
+
+        let decoded: Signature = signature.to_string().parse().unwrap();
+        assert_eq!(signature.to_bytes(), decoded.to_bytes());
+
+        let decoded = Signature::from_base58(&signature.to_base58()).unwrap();
+        assert_eq!(signature.to_bytes(), decoded.to_bytes());
+
+        match "not hex".parse::<Signature>() {
+            Err(ClsagError::DecodeError) => {}
+            _ => panic!("expected a decode error"),
+        }
+    }
+}
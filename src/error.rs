@@ -0,0 +1,238 @@
+use std::fmt;
+
+// The error returned when a ring cannot be signed, a signature does not verify
+// or a signature cannot be decoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Error), uniffi(flat_error))]
+pub enum ClsagError {
+    // This error occurs when the ring has no members
+    EmptyRing,
+    // This error occurs if there are less than 2 members in the ring,
+    // or fewer than the ring policy requires
+    RingTooSmall,
+    // This error occurs if there are more members in the ring than the ring policy allows
+    RingTooLarge,
+    // This error occurs when the sign method is called
+    // without a signer being in the ring
+    NoSigner,
+    // This error occurs if more than one member in the ring holds private keys,
+    // as a signature can only be made by a single signer
+    MultipleSignersNotSupported,
+    // This error occurs if all members do not have the same number of keys,
+    // or if the number of keys does not match the number of key images in the signature
+    KeyCountMismatch,
+    // This error occurs if the member added at the given index has the same key twice,
+    // or a key which another member in the ring already has
+    DuplicateKey(usize),
+    // This error occurs if the decoy at the given index has a key of the signer,
+    // whichever of the two was added to the ring first
+    DecoyEqualsSigner {
+        index: usize,
+    },
+    // This error occurs if the member added at the given index does not have
+    // the same number of keys as the members already in the ring
+    MemberKeyCountMismatch {
+        expected: usize,
+        got: usize,
+        index: usize,
+    },
+    // This error occurs if some members in the ring hold a commitment and others do not
+    CommitmentMismatch,
+    // This error occurs if the signers commitment key does not open their commitment to zero
+    BadCommitmentKey,
+    // Occurs when you try to use a method specific to
+    // a signer as a decoy
+    NotASigner,
+    // Occurs when you try to use a method specific to
+    // a decoy as a signer
+    NotADecoy,
+    // This error occurs if the number of members in the ring
+    // does not match the number of responses in the signature
+    RingSizeMismatch,
+    // This error occurs when one of the key images cannot be decompressed
+    InvalidKeyImage,
+    // This error occurs when a key image is the identity, which no signer can produce
+    IdentityKeyImage,
+    // This error occurs when a public key or commitment cannot be decompressed
+    InvalidPoint,
+    // This error occurs when the key images of the signature are not the expected key images
+    KeyImageMismatch,
+    // This error occurs when the calculated challenge is different from the challenge in the signature
+    ChallengeMismatch,
+    // This error occurs when the bytes are not the length given by the header of the encoding
+    DecodeError,
+    // This error occurs when a scalar in the encoding is not reduced
+    NonCanonicalScalar,
+    // This error occurs when there are not enough outputs to select the decoys from
+    NotEnoughOutputs,
+    // This error occurs when a ring member provider cannot fetch an output
+    OutputUnavailable,
+    // This error occurs when the threshold is zero or more than the number of participants
+    InvalidThreshold,
+    // This error occurs when fewer participants than the threshold take part in signing
+    NotEnoughParticipants,
+    // This error occurs when the participants of a signing round do not match
+    // the participants who committed to their nonces
+    ParticipantMismatch,
+    // This error occurs when secret shares are not all from the same split,
+    // or two of them have the same index
+    ShareMismatch,
+    // This error occurs when a cosigners proof that they know their keys does not verify
+    InvalidKeyProof,
+    // This error occurs when a cosigners message is for a different signing session
+    SessionMismatch,
+    // This error occurs when the members of a ring are not in canonical order
+    RingNotCanonical,
+    // This error occurs when there is no member at the given index in the ring
+    MemberNotFound,
+    // This error occurs when an adaptor point was made for another signer, when a signature
+    // was not completed from the pre-signature, or the secret does not open the adaptor point
+    AdaptorMismatch,
+    // This error occurs when the nonce of a signature does not commit to the given data,
+    // or the decoy responses of a signature were not derived from the host nonce
+    CommitmentProofMismatch,
+    // This error occurs when the nonce a host reveals does not match the commitment it sent
+    HostNonceMismatch,
+    // This error occurs when a disclosure proof does not prove that the member
+    // at its position made the signature, or a non-signer proof does not prove that they did not
+    InvalidDisclosure,
+    // This error occurs when the key image of a member who claims not to have made
+    // a signature is the key image of the signature
+    SignerNotExcluded,
+    // This error occurs when a proof of equality of discrete logs does not verify
+    InvalidDleqProof,
+    // This error occurs when a range proof does not verify
+    InvalidRangeProof,
+    // This error occurs when the input at the given index of a transaction has the same
+    // key image as an earlier input
+    DuplicateKeyImage(usize),
+    // This error occurs when a signature is submitted to a verifier pool which has stopped
+    VerifierPoolClosed,
+    // This error occurs when a signer device is asked for a response without a committed nonce
+    NonceNotCommitted,
+    // This error occurs when a member is not proven to be in the anonymity set with a root
+    InvalidMembershipProof,
+    // This error occurs when a proof needs a ring whose size is a power of two
+    InvalidRingSize,
+    // This error occurs when memory for a secret key cannot be locked into ram
+    MemoryLockFailed,
+    // This error occurs if the members of a ring have more keys than the verification limits allow
+    TooManyKeys,
+    // This error occurs if a scratch buffer given to verification is too small for the ring
+    BufferTooSmall,
+    // This error occurs if the known answer test with the given name does not give
+    // the expected answer when the self test is run
+    SelfTestFailed(&'static str),
+    // This error occurs when the bytes are not an encoded snapshot of a ring
+    InvalidSnapshot,
+    // This error occurs when the bytes are not a private set sealed by `PrivateSet::seal`
+    InvalidKeystore,
+    // This error occurs when a sealed private set cannot be opened,
+    // either because the passphrase is wrong or because the blob was modified
+    UnsealFailed,
+    // This error occurs when a wordlist does not have 2048 distinct words
+    InvalidWordlist,
+    // This error occurs when a mnemonic phrase does not have 24 words from the wordlist
+    InvalidMnemonic,
+    // This error occurs when the checksum of a mnemonic phrase does not match its words
+    MnemonicChecksumMismatch,
+    // This error occurs when a range proof is made over a ring without a pseudo output,
+    // or for an amount and blinding factor which do not open the pseudo output
+    PseudoOutMismatch,
+}
+
+impl fmt::Display for ClsagError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self {
+            ClsagError::DuplicateKey(index) => {
+                return write!(f, "the member at index {} has a duplicate key", index)
+            }
+            ClsagError::DecoyEqualsSigner { index } => {
+                return write!(f, "the decoy at index {} has a key of the signer", index)
+            }
+            ClsagError::MemberKeyCountMismatch {
+                expected,
+                got,
+                index,
+            } => {
+                return write!(
+                    f,
+                    "the member at index {} has {} keys, the ring has {} keys per member",
+                    index, got, expected
+                )
+            }
+            ClsagError::SelfTestFailed(name) => return write!(f, "the {} self test failed", name),
+            ClsagError::DuplicateKeyImage(index) => {
+                return write!(f, "the input at index {} has a duplicate key image", index)
+            }
+            ClsagError::EmptyRing => "the ring has no members",
+            ClsagError::RingTooSmall => "the ring has too few members",
+            ClsagError::RingTooLarge => "the ring has too many members",
+            ClsagError::NoSigner => "the ring has no signer",
+            ClsagError::MultipleSignersNotSupported => {
+                "the ring has more than one signer, which is not supported"
+            }
+            ClsagError::KeyCountMismatch => "the members do not have the same number of keys",
+            ClsagError::CommitmentMismatch => "only some members hold a commitment",
+            ClsagError::BadCommitmentKey => {
+                "the commitment key does not open the commitment to zero"
+            }
+            ClsagError::NotASigner => "the member is not a signer",
+            ClsagError::NotADecoy => "the member is not a decoy",
+            ClsagError::RingSizeMismatch => "the ring size does not match the number of responses",
+            ClsagError::InvalidKeyImage => "a key image is not a valid point",
+            ClsagError::IdentityKeyImage => "a key image is the identity",
+            ClsagError::InvalidPoint => "a public key is not a valid point",
+            ClsagError::KeyImageMismatch => "the key images are not the expected key images",
+            ClsagError::ChallengeMismatch => "the challenge does not match",
+            ClsagError::DecodeError => "the bytes are not an encoded signature",
+            ClsagError::NonCanonicalScalar => "a scalar is not canonically encoded",
+            ClsagError::NotEnoughOutputs => {
+                "there are not enough outputs to select the decoys from"
+            }
+            ClsagError::OutputUnavailable => "an output could not be fetched",
+            ClsagError::InvalidThreshold => {
+                "the threshold must be between one and the number of participants"
+            }
+            ClsagError::NotEnoughParticipants => "there are fewer participants than the threshold",
+            ClsagError::ShareMismatch => "the secret shares are not from the same split",
+            ClsagError::ParticipantMismatch => {
+                "the participants do not match the participants who committed to their nonces"
+            }
+            ClsagError::InvalidKeyProof => "the proof of a cosigners keys does not verify",
+            ClsagError::SessionMismatch => "the message is for a different signing session",
+            ClsagError::RingNotCanonical => "the members of the ring are not in canonical order",
+            ClsagError::MemberNotFound => "there is no member at the index",
+            ClsagError::CommitmentProofMismatch => "the signature does not commit to the data",
+            ClsagError::HostNonceMismatch => "the host nonce does not match its commitment",
+            ClsagError::InvalidDisclosure => "the disclosure proof does not verify",
+            ClsagError::SignerNotExcluded => "the member made the key image of the signature",
+            ClsagError::InvalidDleqProof => "the proof of equal discrete logs does not verify",
+            ClsagError::InvalidRangeProof => "the range proof does not verify",
+            ClsagError::VerifierPoolClosed => "the verifier pool has stopped",
+            ClsagError::NonceNotCommitted => "the device has no committed nonce to respond with",
+            ClsagError::InvalidMembershipProof => "the member is not in the anonymity set",
+            ClsagError::InvalidRingSize => "the ring size is not a power of two",
+            ClsagError::MemoryLockFailed => "the memory for the secret key could not be locked",
+            ClsagError::TooManyKeys => "the members of the ring have too many keys",
+            ClsagError::BufferTooSmall => "a scratch buffer is too small for the ring",
+            ClsagError::InvalidSnapshot => "the bytes are not an encoded snapshot of a ring",
+            ClsagError::InvalidKeystore => "the bytes are not a sealed private set",
+            ClsagError::InvalidWordlist => "the wordlist does not have 2048 distinct words",
+            ClsagError::InvalidMnemonic => "the phrase does not have 24 words from the wordlist",
+            ClsagError::MnemonicChecksumMismatch => "the checksum of the phrase does not match",
+            ClsagError::PseudoOutMismatch => {
+                "the ring has no pseudo output, or the amount and blinding factor do not open it"
+            }
+            ClsagError::UnsealFailed => {
+                "the passphrase is wrong or the sealed private set was modified"
+            }
+            ClsagError::AdaptorMismatch => {
+                "the adaptor does not match the signer or the signature it was completed into"
+            }
+        };
+        write!(f, "{}", msg)
+    }
+}
+
+impl std::error::Error for ClsagError {}
@@ -0,0 +1,396 @@
+// Signing and verification over rings whose size and number of keys are fixed at compile time.
+//
+// A `FixedRing<N, D>` is a ring of `N` members with `D` keys each, a `FixedSigner<D>` is a
+// signer with `D` keys, and a `FixedSignature<N, D>` is the signature it makes over the ring.
+// Every list is an array of the size the layout fixes, so a member cannot have the wrong
+// number of keys and a signature cannot have the wrong number of responses or key images,
+// and rings with fewer than two members or members without keys do not compile.
+//
+// Signing and verifying never allocate. The sums over the keys of a member are computed key
+// by key, rather than with a multiscalar multiplication, which is as fast for the one or two
+// keys of such layouts, and the ring is hashed from the arrays of key bytes held by the ring.
+// This suits embedded signers with no allocator to spare, with `D` = 1 for plain keys and
+// `D` = 2 for a key and a commitment to zero.
+//
+// A fixed signature is the signature `Clsag::sign` makes over the same ring in the same order
+// with the default transcript and hash to point, so `to_signature` gives a signature which
+// verifies with `Signature::verify`, and `from_signature` takes one which was made by `Clsag`.
+// Unlike `Clsag`, the ring is signed in the order it is given in, so the caller must put the
+// signer at a random position.
+use crate::clsag::{aggregation_coefficients_into, aggregation_transcript, PublicKeys};
+use crate::constants::BASEPOINT;
+use crate::error::ClsagError;
+use crate::hash::hash_pubkey;
+use crate::keys::{zeroize_scalar, KeyImage, PublicKey, SecretKey};
+use crate::member::{round_challenge, round_transcript};
+use crate::signature::Signature;
+use crate::transcript::{default_transcript, SigningTranscript};
+use curve25519_dalek::ristretto::RistrettoPoint;
+use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::traits::Identity;
+use rand::{CryptoRng, RngCore};
+use std::array;
+
+// A ring of `N` members with `D` keys each, prepared for signing and verifying
+#[derive(Debug, Clone)]
+pub struct FixedRing<const N: usize, const D: usize> {
+    public_keys: [[PublicKey; D]; N],
+
+    // The bytes of every key, in ring order, which are hashed into every challenge
+    key_bytes: [[[u8; 32]; D]; N],
+
+    points: [[RistrettoPoint; D]; N],
+
+    // The hash of the first public key of each member
+    hashed_pubkeys: [RistrettoPoint; N],
+}
+
+impl<const N: usize, const D: usize> FixedRing<N, D> {
+    // Rings of fewer than two members, or of members without keys, cannot be signed
+    const VALID_LAYOUT: () = assert!(N >= 2 && D >= 1, "a ring needs two members with keys");
+
+    // Prepares the ring of public keys, in ring order.
+    // Returns an error if one of the keys is not a valid point
+    pub fn new(public_keys: [[PublicKey; D]; N]) -> Result<Self, ClsagError> {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::VALID_LAYOUT;
+
+        let mut key_bytes = [[[0u8; 32]; D]; N];
+        let mut points = [[RistrettoPoint::identity(); D]; N];
+        for (member, keys) in public_keys.iter().enumerate() {
+            for (layer, key) in keys.iter().enumerate() {
+                key_bytes[member][layer] = key.to_bytes();
+                points[member][layer] = key.decompress().ok_or(ClsagError::InvalidPoint)?;
+            }
+        }
+        let hashed_pubkeys =
+            array::from_fn(|member| hash_pubkey(public_keys[member][0].as_bytes()));
+
+        Ok(FixedRing {
+            public_keys,
+            key_bytes,
+            points,
+            hashed_pubkeys,
+        })
+    }
+    // Prepares a ring of public keys held in vectors, such as the ring returned by `Clsag::sign`.
+    // Returns an error if the ring does not have `N` members with `D` keys each,
+    // or if one of the keys is not a valid point
+    pub fn from_public_keys(public_keys: &[Vec<PublicKey>]) -> Result<Self, ClsagError> {
+        if public_keys.len() != N {
+            return Err(ClsagError::RingSizeMismatch);
+        }
+        if public_keys.iter().any(|keys| keys.len() != D) {
+            return Err(ClsagError::KeyCountMismatch);
+        }
+
+        Self::new(array::from_fn(|member| {
+            array::from_fn(|layer| public_keys[member][layer])
+        }))
+    }
+    // Returns the public keys of every member in ring order
+    pub fn public_keys(&self) -> &[[PublicKey; D]; N] {
+        &self.public_keys
+    }
+    // Returns the public keys of every member in vectors, as the rest of the crate takes them
+    pub fn to_public_keys(&self) -> PublicKeys {
+        self.public_keys.iter().map(|keys| keys.to_vec()).collect()
+    }
+
+    // Returns the bytes of every key, which are the bytes of the public key matrix
+    fn pubkey_matrix(&self) -> &[u8] {
+        self.key_bytes.as_flattened().as_flattened()
+    }
+    // Returns the transcripts the aggregation co-efficients and the round challenges
+    // are computed from, with the ring appended, and the key images and the message
+    // appended to the transcript of the round challenges
+    fn transcripts<T: SigningTranscript>(
+        &self,
+        transcript: &T,
+        key_images: &[KeyImage],
+        msg: &[u8],
+    ) -> (T, T) {
+        (
+            aggregation_transcript(transcript, self.pubkey_matrix(), D),
+            round_transcript(transcript, self.pubkey_matrix(), key_images, msg),
+        )
+    }
+    // Computes the L and R points of a member from its response and the challenge:
+    // L = response * G + sum(challenge * mu_j * public_keys)
+    // R = response * H(first public key) + sum(challenge * mu_j * key_images)
+    fn round_points(
+        &self,
+        member: usize,
+        response: &Scalar,
+        challenge: &Scalar,
+        agg_coeffs: &[Scalar; D],
+        key_images: &[RistrettoPoint; D],
+    ) -> (RistrettoPoint, RistrettoPoint) {
+        let mut l = response * BASEPOINT;
+        let mut r = response * self.hashed_pubkeys[member];
+        for ((mu, point), key_image) in agg_coeffs
+            .iter()
+            .zip(self.points[member].iter())
+            .zip(key_images.iter())
+        {
+            let scalar = challenge * mu;
+            l += scalar * point;
+            r += scalar * key_image;
+        }
+        (l, r)
+    }
+}
+
+// A signer with `D` keys, who signs rings of members with `D` keys
+pub struct FixedSigner<const D: usize> {
+    private_keys: [Scalar; D],
+    public_keys: [PublicKey; D],
+}
+
+impl<const D: usize> FixedSigner<D> {
+    // Creates the signer from their secret keys, the first of which is their signing key
+    pub fn new(secret_keys: &[SecretKey; D]) -> Self {
+        FixedSigner {
+            private_keys: array::from_fn(|layer| secret_keys[layer].0),
+            public_keys: array::from_fn(|layer| secret_keys[layer].public_key()),
+        }
+    }
+    // Returns the public keys of the signer, which must be one of the members of the ring
+    pub fn public_keys(&self) -> &[PublicKey; D] {
+        &self.public_keys
+    }
+    // Signs the message over the ring, with the nonce and the responses of the other members
+    // drawn from the rng. Returns an error if the signer is not a member of the ring
+    pub fn sign<R: RngCore + CryptoRng, const N: usize>(
+        &self,
+        rng: &mut R,
+        ring: &FixedRing<N, D>,
+        msg: &[u8],
+    ) -> Result<FixedSignature<N, D>, ClsagError> {
+        let signer_index = ring
+            .public_keys
+            .iter()
+            .position(|keys| *keys == self.public_keys)
+            .ok_or(ClsagError::NoSigner)?;
+
+        let hashed_pubkey = ring.hashed_pubkeys[signer_index];
+        let key_image_points: [RistrettoPoint; D] =
+            array::from_fn(|layer| self.private_keys[layer] * hashed_pubkey);
+        let key_images = key_image_points.map(|point| KeyImage(point.compress()));
+
+        let (aggregation, round) = ring.transcripts(&default_transcript(), &key_images, msg);
+        let mut agg_coeffs = [Scalar::zero(); D];
+        aggregation_coefficients_into(&aggregation, &key_images, msg, &mut agg_coeffs);
+
+        // The signer commits to their nonce, and the ring is walked from the member after them
+        let mut nonce = Scalar::random(rng);
+        let mut challenge = round_challenge(&round, &(nonce * BASEPOINT), &(nonce * hashed_pubkey));
+        let mut first_challenge = challenge;
+        let mut responses = [Scalar::zero(); N];
+        for offset in 1..N {
+            let member = (signer_index + offset) % N;
+            if member == 0 {
+                first_challenge = challenge;
+            }
+
+            responses[member] = Scalar::random(rng);
+            let (l, r) = ring.round_points(
+                member,
+                &responses[member],
+                &challenge,
+                &agg_coeffs,
+                &key_image_points,
+            );
+            challenge = round_challenge(&round, &l, &r);
+        }
+        if signer_index == 0 {
+            first_challenge = challenge;
+        }
+
+        // response = nonce - challenge * sum(mu_j * private_keys)
+        let mut aggregate_key = Scalar::zero();
+        for (mu, private_key) in agg_coeffs.iter().zip(self.private_keys.iter()) {
+            aggregate_key += mu * private_key;
+        }
+        responses[signer_index] = nonce - challenge * aggregate_key;
+        zeroize_scalar(&mut nonce);
+        zeroize_scalar(&mut aggregate_key);
+
+        Ok(FixedSignature {
+            challenge: first_challenge,
+            responses,
+            key_images,
+        })
+    }
+}
+
+// The private keys are cleared with the zeroize feature, as they are by `PrivateSet`
+#[cfg(feature = "zeroize")]
+impl<const D: usize> Drop for FixedSigner<D> {
+    fn drop(&mut self) {
+        self.private_keys.iter_mut().for_each(zeroize_scalar);
+    }
+}
+
+// A signature over a ring of `N` members with `D` keys each
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedSignature<const N: usize, const D: usize> {
+    challenge: Scalar,
+    responses: [Scalar; N],
+    key_images: [KeyImage; D],
+}
+
+impl<const N: usize, const D: usize> FixedSignature<N, D> {
+    // Returns the key images of the signer, one for each of their keys
+    pub fn key_images(&self) -> &[KeyImage; D] {
+        &self.key_images
+    }
+    // Verifies the signature over the ring, without allocating
+    pub fn verify(&self, ring: &FixedRing<N, D>, msg: &[u8]) -> Result<(), ClsagError> {
+        let mut key_image_points = [RistrettoPoint::identity(); D];
+        for (point, key_image) in key_image_points.iter_mut().zip(self.key_images.iter()) {
+            *point = key_image.check()?;
+        }
+
+        let (aggregation, round) = ring.transcripts(&default_transcript(), &self.key_images, msg);
+        let mut agg_coeffs = [Scalar::zero(); D];
+        aggregation_coefficients_into(&aggregation, &self.key_images, msg, &mut agg_coeffs);
+
+        let mut challenge = self.challenge;
+        for (member, response) in self.responses.iter().enumerate() {
+            let (l, r) =
+                ring.round_points(member, response, &challenge, &agg_coeffs, &key_image_points);
+            challenge = round_challenge(&round, &l, &r);
+        }
+
+        if challenge != self.challenge {
+            return Err(ClsagError::ChallengeMismatch);
+        }
+        Ok(())
+    }
+    // Returns the signature with its responses and key images in vectors,
+    // which verifies with `Signature::verify` over the ring
+    pub fn to_signature(&self) -> Signature {
+        Signature {
+            challenge: self.challenge,
+            responses: self.responses.to_vec(),
+            key_images: self.key_images.to_vec(),
+        }
+    }
+    // Takes a signature over a ring of `N` members with `D` keys each.
+    // Returns an error if the signature is over a ring of another size or number of keys,
+    // or if it is not canonically encoded
+    pub fn from_signature(signature: &Signature) -> Result<Self, ClsagError> {
+        if signature.responses.len() != N {
+            return Err(ClsagError::RingSizeMismatch);
+        }
+        if signature.key_images.len() != D {
+            return Err(ClsagError::KeyCountMismatch);
+        }
+        signature.check_canonical()?;
+
+        Ok(FixedSignature {
+            challenge: signature.challenge,
+            responses: array::from_fn(|member| signature.responses[member]),
+            key_images: array::from_fn(|layer| signature.key_images[layer]),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::member::Member;
+    use crate::test_utils::*;
+
+    // Returns a ring with the signer with `D` keys at `signer_index`
+    fn generate_ring<const N: usize, const D: usize>(
+        signer_index: usize,
+    ) -> (FixedSigner<D>, FixedRing<N, D>) {
+        let mut rng = rand::thread_rng();
+        let secret_keys: [SecretKey; D] = array::from_fn(|_| SecretKey::random(&mut rng));
+        let signer = FixedSigner::new(&secret_keys);
+
+        let mut public_keys: [[PublicKey; D]; N] = array::from_fn(|_| {
+            let keys = generate_rand_public_keys(D);
+            array::from_fn(|layer| keys[layer])
+        });
+        public_keys[signer_index] = *signer.public_keys();
+
+        (signer, FixedRing::new(public_keys).unwrap())
+    }
+
+    #[test]
+    fn test_sign_and_verify_without_allocating() {
+        let mut rng = rand::thread_rng();
+        for signer_index in 0..4 {
+            let (signer, ring) = generate_ring::<4, 1>(signer_index);
+
+            let before = allocations();
+            let signature = signer.sign(&mut rng, &ring, b"hello world").unwrap();
+            assert!(signature.verify(&ring, b"hello world").is_ok());
+            assert_eq!(before, allocations());
+
+            assert!(signature.verify(&ring, b"another message").is_err());
+        }
+
+        let (signer, ring) = generate_ring::<11, 2>(5);
+        let before = allocations();
+        let signature = signer.sign(&mut rng, &ring, b"hello world").unwrap();
+        assert!(signature.verify(&ring, b"hello world").is_ok());
+        assert_eq!(before, allocations());
+    }
+
+    #[test]
+    fn test_same_signatures_as_clsag() {
+        // A fixed signature verifies as a signature, and the other way around
+        let mut rng = rand::thread_rng();
+        let (signer, ring) = generate_ring::<5, 2>(3);
+        let signature = signer.sign(&mut rng, &ring, b"hello world").unwrap();
+        let expanded = signature.to_signature();
+        assert!(expanded
+            .verify(&ring.to_public_keys(), b"hello world")
+            .is_ok());
+        assert_eq!(
+            signature,
+            FixedSignature::from_signature(&expanded).unwrap()
+        );
+
+        let mut clsag = generate_clsag_with(4, 2);
+        clsag.add_member(generate_signer(2)).unwrap();
+        let (signature, public_keys) = clsag.sign(b"hello world").unwrap();
+        let ring = FixedRing::<5, 2>::from_public_keys(&public_keys).unwrap();
+        let fixed = FixedSignature::<5, 2>::from_signature(&signature).unwrap();
+        assert!(fixed.verify(&ring, b"hello world").is_ok());
+        assert_eq!(signature.key_images(), fixed.key_images());
+    }
+
+    #[test]
+    fn test_fixed_errors() {
+        let mut rng = rand::thread_rng();
+        let (_, ring) = generate_ring::<3, 1>(0);
+        let (outsider, _) = generate_ring::<3, 1>(0);
+        match outsider.sign(&mut rng, &ring, b"hello world") {
+            Err(ClsagError::NoSigner) => {}
+            _ => panic!("expected a no signer error"),
+        }
+
+        let mut clsag = generate_clsag_with(2, 1);
+        clsag
+            .add_member(Member::new_signer(generate_rand_scalars(1)))
+            .unwrap();
+        let (signature, public_keys) = clsag.sign(b"hello world").unwrap();
+        match FixedSignature::<4, 1>::from_signature(&signature) {
+            Err(ClsagError::RingSizeMismatch) => {}
+            _ => panic!("expected a ring size mismatch error"),
+        }
+        match FixedSignature::<3, 2>::from_signature(&signature) {
+            Err(ClsagError::KeyCountMismatch) => {}
+            _ => panic!("expected a key count mismatch error"),
+        }
+        match FixedRing::<3, 2>::from_public_keys(&public_keys) {
+            Err(ClsagError::KeyCountMismatch) => {}
+            _ => panic!("expected a key count mismatch error"),
+        }
+    }
+}
@@ -0,0 +1,168 @@
+// Hashing to points on the curve.
+//
+// Key images are computed from the hash of the signers first public key, and every member
+// of the ring is hashed in the same way when signing and verifying. By default the key is
+// hashed with SHA-512, prefixed with the `HASH_TO_POINT_DOMAIN` tag so that the hash cannot
+// collide with a hash of the same bytes for another purpose, and the 64 byte output is mapped
+// to a point with the ristretto elligator map.
+//
+// Other constructions, such as one over raw ed25519 points for compatibility with Monero,
+// implement `HashToPoint` and are set with `Clsag::set_hash_to_point` when signing and
+// `PreparedRing::with_hash_to_point` when verifying. Challenges are hashed by the transcript,
+// see `DigestTranscript` for hashing challenges with another hash function.
+use crate::keys::wipe_bytes;
+use curve25519_dalek::ristretto::RistrettoPoint;
+use sha2::digest::generic_array::typenum::U64;
+use sha2::{Digest, Sha512};
+use std::marker::PhantomData;
+
+// The hash function used to hash to points, and by `DigestTranscript` unless another is given
+pub type DefaultHash = Sha512;
+
+// The tag prefixed to the bytes of every public key before it is hashed to a point
+pub const HASH_TO_POINT_DOMAIN: &[u8] = b"CLSAG_HASH_TO_POINT";
+
+// A HashToPoint hashes the first public key of a member to the point
+// its key images are computed from
+pub trait HashToPoint {
+    // Hashes the bytes of a public key to a point
+    fn hash_to_point(&self, bytes: &[u8]) -> RistrettoPoint;
+}
+
+// Hashes the domain tag followed by the bytes with the 64 byte output hash function `D`,
+// and maps the hash to a point. This is the construction used by default, with SHA-512
+pub struct DomainSeparated<D = DefaultHash>(PhantomData<D>);
+
+impl<D> DomainSeparated<D> {
+    // Creates the construction for the hash function `D`
+    pub fn new() -> Self {
+        DomainSeparated(PhantomData)
+    }
+}
+
+impl<D> Default for DomainSeparated<D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<D> Clone for DomainSeparated<D> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<D> Copy for DomainSeparated<D> {}
+
+impl<D: Digest<OutputSize = U64> + Default> HashToPoint for DomainSeparated<D> {
+    fn hash_to_point(&self, bytes: &[u8]) -> RistrettoPoint {
+        let mut hasher = D::default();
+        hasher.input(HASH_TO_POINT_DOMAIN);
+        hasher.input(bytes);
+        RistrettoPoint::from_hash(hasher)
+    }
+}
+
+// Hashes the bytes to a point with the domain separated construction,
+// using the 64 byte output hash function `D`
+pub fn hash_to_point<D: Digest<OutputSize = U64> + Default>(bytes: &[u8]) -> RistrettoPoint {
+    DomainSeparated::<D>::new().hash_to_point(bytes)
+}
+
+// Hashes the first public key of a member to the point its key images are computed from,
+// with the default construction
+pub(crate) fn hash_pubkey(pubkey: &[u8; 32]) -> RistrettoPoint {
+    hash_to_point::<DefaultHash>(pubkey)
+}
+
+// SHA-512 processes its input in blocks of 128 bytes, which is the block size of HMAC
+const HMAC_BLOCK_SIZE: usize = 128;
+
+// Returns the HMAC-SHA512 of the concatenation of the messages
+pub(crate) fn hmac_sha512(key: &[u8], messages: &[&[u8]]) -> [u8; 64] {
+    // Keys longer than a block are hashed first
+    let mut padded_key = [0u8; HMAC_BLOCK_SIZE];
+    if key.len() > HMAC_BLOCK_SIZE {
+        padded_key[..64].copy_from_slice(&Sha512::digest(key));
+    } else {
+        padded_key[..key.len()].copy_from_slice(key);
+    }
+
+    let mut inner_pad = [0x36u8; HMAC_BLOCK_SIZE];
+    let mut outer_pad = [0x5cu8; HMAC_BLOCK_SIZE];
+    for ((inner, outer), key_byte) in inner_pad
+        .iter_mut()
+        .zip(outer_pad.iter_mut())
+        .zip(padded_key.iter())
+    {
+        *inner ^= key_byte;
+        *outer ^= key_byte;
+    }
+
+    let mut inner = Sha512::default();
+    inner.input(&inner_pad[..]);
+    for message in messages {
+        inner.input(message);
+    }
+    let mut outer = Sha512::default();
+    outer.input(&outer_pad[..]);
+    outer.input(inner.result());
+
+    let mut mac = [0u8; 64];
+    mac.copy_from_slice(&outer.result());
+    wipe_bytes(&mut padded_key);
+    wipe_bytes(&mut inner_pad);
+    wipe_bytes(&mut outer_pad);
+    mac
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::*;
+    use blake2::Blake2b;
+
+    #[test]
+    fn test_hash_to_point() {
+        let key = generate_rand_public_keys(1)[0];
+
+        // The key is hashed with the domain tag, rather than on its own
+        assert_ne!(
+            RistrettoPoint::hash_from_bytes::<Sha512>(key.as_bytes()),
+            hash_pubkey(key.as_bytes())
+        );
+        let tagged = [HASH_TO_POINT_DOMAIN, key.as_bytes()].concat();
+        assert_eq!(
+            RistrettoPoint::hash_from_bytes::<Sha512>(&tagged),
+            hash_pubkey(key.as_bytes())
+        );
+
+        assert_ne!(
+            hash_to_point::<Blake2b>(key.as_bytes()),
+            hash_pubkey(key.as_bytes())
+        );
+    }
+
+    #[test]
+    fn test_hmac_sha512() {
+        // The test cases of RFC 4231
+        let mac = hmac_sha512(b"Jefe", &[b"what do ya want ", b"for nothing?"]);
+        assert_eq!(
+            "164b7a7bfcf819e2e395fbe73b56e0a387bd64222e831fd610270cd7ea250554\
+             9758bf75c05a994a6d034f65f8f0e6fdcaeab1a34d4a6b4b636e070a38bce737",
+            hex::encode(&mac[..])
+        );
+
+        // A key longer than a block is hashed first
+        let long_key = [0xaau8; 131];
+        let mac = hmac_sha512(
+            &long_key,
+            &[b"Test Using Larger Than Block-Size Key - Hash Key First"],
+        );
+        assert_eq!(
+            "80b24263c7c1a3ebb71493c1dd7be8b49b46d1f41b4aeec1121b013783f8f352\
+             6b56d037e05f2598bd0fd2215d6a1e5295e64f73f63f0aec8b915a985d786598",
+            hex::encode(&mac[..])
+        );
+    }
+}
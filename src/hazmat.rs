@@ -0,0 +1,167 @@
+// The primitives signing and verification are built from, behind the hazmat feature.
+//
+// These are the exact functions the crate signs and verifies with, exposed so that modified
+// schemes, such as new adaptor or multisig rounds, can be built on them rather than on a copy
+// of the crate internals which drifts from the real protocol. Nothing here checks that it is
+// used correctly: the functions do not check the lengths of their inputs against each other,
+// the key images are not checked to be valid points and the transcripts are not domain
+// separated from any other use of the caller. A scheme built on these functions does not
+// inherit the security of CLSAG and needs its own analysis.
+//
+// The challenge of each member is computed from a transcript prefix, the transcript the ring,
+// the key images and the message have been appended to by `round_transcript`, which is
+// computed once per signature:
+//
+//   prefix = round_transcript(transcript, pubkey_matrix, key_images, msg)
+//   c_{i+1} = compute_round_challenge(prefix, L_i, R_i)
+//
+// where L_i and R_i are computed by `compute_member_challenge` for a member whose response is
+// known. The aggregation co-efficients are computed in the same way, from the prefix returned
+// by `aggregation_transcript`.
+use crate::error::ClsagError;
+use crate::keys::{KeyImage, PublicKey};
+use crate::member::{ring_challenge, round_challenge};
+use crate::signature::Signature;
+use crate::transcript::SigningTranscript;
+use curve25519_dalek::ristretto::RistrettoPoint;
+use curve25519_dalek::scalar::Scalar;
+
+// Appends the public key matrix, the number of keys of each member, the key images and the
+// message to a clone of the transcript, returning the prefix every round challenge of the
+// signature is computed from
+pub fn round_transcript<T: SigningTranscript>(
+    transcript: &T,
+    pubkey_matrix: &[u8],
+    key_images: &[KeyImage],
+    msg: &[u8],
+) -> T {
+    crate::member::round_transcript(transcript, pubkey_matrix, key_images, msg)
+}
+
+// Appends the public key matrix and the number of keys of each member to a clone of the
+// transcript, returning the prefix the aggregation co-efficients are computed from
+pub fn aggregation_transcript<T: SigningTranscript>(
+    transcript: &T,
+    pubkey_matrix: &[u8],
+    num_layers: usize,
+) -> T {
+    crate::clsag::aggregation_transcript(transcript, pubkey_matrix, num_layers)
+}
+
+// Hashes the L and R points of a member into the transcript prefix returned by
+// `round_transcript`, returning the challenge of the next member in the ring
+pub fn compute_round_challenge<T: SigningTranscript>(
+    transcript_prefix: &T,
+    l: &RistrettoPoint,
+    r: &RistrettoPoint,
+) -> Scalar {
+    round_challenge(transcript_prefix, l, r)
+}
+
+// Computes the L and R points of a member from their response and the challenge
+// passed to them, and returns the challenge of the next member in the ring.
+// Returns an error if the member does not have a key for every key image and co-efficient,
+// or if a key or key image is not a valid point
+#[allow(clippy::too_many_arguments)]
+pub fn compute_member_challenge<T: SigningTranscript>(
+    transcript_prefix: &T,
+    public_keys: &[PublicKey],
+    challenge: &Scalar,
+    key_images: &[KeyImage],
+    response: &Scalar,
+    agg_coeffs: &[Scalar],
+    hashed_pubkey: &RistrettoPoint,
+    generator: &RistrettoPoint,
+) -> Result<Scalar, ClsagError> {
+    ring_challenge(
+        transcript_prefix,
+        public_keys,
+        challenge,
+        key_images,
+        response,
+        agg_coeffs,
+        hashed_pubkey,
+        generator,
+    )
+}
+
+// Returns one aggregation co-efficient per key image, from the transcript prefix
+// returned by `aggregation_transcript`
+pub fn compute_aggregation_coefficients<T: SigningTranscript>(
+    transcript_prefix: &T,
+    key_images: &[KeyImage],
+    msg: &[u8],
+) -> Vec<Scalar> {
+    crate::clsag::aggregation_coefficients(transcript_prefix, key_images, msg)
+}
+
+// Hashes the first public key of a member to the point its key images are computed from,
+// with the default construction
+pub fn hash_pubkey(public_key: &PublicKey) -> RistrettoPoint {
+    crate::hash::hash_pubkey(public_key.as_bytes())
+}
+
+impl Signature {
+    // Returns the challenge of the first member of the ring
+    pub fn challenge(&self) -> &Scalar {
+        &self.challenge
+    }
+    // Returns the response of each member of the ring, in ring order
+    pub fn responses(&self) -> &[Scalar] {
+        &self.responses
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::constants::BASEPOINT;
+    use crate::test_utils::*;
+    use crate::transcript::default_transcript;
+
+    #[test]
+    fn test_recompute_challenges() {
+        let num_keys = 2;
+        let msg = b"hello world";
+
+        let mut clsag = generate_clsag_with(4, num_keys);
+        clsag.add_member(generate_signer(num_keys)).unwrap();
+        let (signature, public_keys) = clsag.sign(msg).unwrap();
+
+        // Walk the ring with the primitives, which ends at the challenge it started from
+        let pubkey_matrix: Vec<u8> = public_keys
+            .iter()
+            .flatten()
+            .flat_map(|key| key.to_bytes().to_vec())
+            .collect();
+        let transcript = default_transcript();
+        let aggregation = aggregation_transcript(&transcript, &pubkey_matrix, num_keys);
+        let agg_coeffs =
+            compute_aggregation_coefficients(&aggregation, signature.key_images(), msg);
+        let round = round_transcript(&transcript, &pubkey_matrix, signature.key_images(), msg);
+
+        let mut challenge = *signature.challenge();
+        for (keys, response) in public_keys.iter().zip(signature.responses()) {
+            challenge = compute_member_challenge(
+                &round,
+                keys,
+                &challenge,
+                signature.key_images(),
+                response,
+                &agg_coeffs,
+                &hash_pubkey(&keys[0]),
+                &BASEPOINT,
+            )
+            .unwrap();
+        }
+        assert_eq!(signature.challenge(), &challenge);
+
+        // The challenge depends on the order the points are hashed in
+        let l = generate_rand_points(1)[0];
+        let r = generate_rand_points(1)[0];
+        assert_ne!(
+            compute_round_challenge(&round, &l, &r),
+            compute_round_challenge(&round, &r, &l)
+        );
+    }
+}
@@ -1,9 +1,23 @@
 use crate::constants::BASEPOINT;
-use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
+use crate::error::ClsagError;
+use crate::hash::hash_pubkey;
+use crate::ring::canonical_order;
+use curve25519_dalek::ristretto::{
+    CompressedRistretto, RistrettoPoint, VartimeRistrettoPrecomputation,
+};
 use curve25519_dalek::scalar::Scalar;
-use sha2::Sha512;
+use curve25519_dalek::traits::{
+    IsIdentity, VartimeMultiscalarMul, VartimePrecomputedMultiscalarMul,
+};
+use rand::{CryptoRng, RngCore};
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
 
 use std::collections::HashSet;
+
+#[cfg(feature = "zeroize")]
+use zeroize::{Zeroize, ZeroizeOnDrop};
 // Public key set represents a set of public keys
 // note that this is not a `tuple`. A tuple allows duplicates while a set
 // does not. While this is not a limitation placed upon the protocol by the
@@ -11,18 +25,66 @@ use std::collections::HashSet;
 // to proving that you own the same key twice. This restriction will be placed
 // onto the protocol at this level, as the author cannot think of a
 // context where proving you own the same key twice would be useful.
+// The compressed encoding of each key is kept alongside the key, computed when the key is
+// added, so that hashing and comparing the set never compresses the same point twice
 #[derive(Debug, Clone)]
-pub struct PublicSet(pub Vec<RistrettoPoint>);
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(from = "SerdePublicSet", into = "SerdePublicSet")
+)]
+pub struct PublicSet {
+    points: Vec<RistrettoPoint>,
+    keys: Vec<PublicKey>,
+}
+
+// A public set is serialized as its points alone, and the keys are compressed again
+// when it is deserialized
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct SerdePublicSet(#[serde(with = "crate::serde_helpers::points")] Vec<RistrettoPoint>);
+
+#[cfg(feature = "serde")]
+impl From<SerdePublicSet> for PublicSet {
+    fn from(set: SerdePublicSet) -> Self {
+        PublicSet::new(set.0)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<PublicSet> for SerdePublicSet {
+    fn from(set: PublicSet) -> Self {
+        SerdePublicSet(set.points)
+    }
+}
 
 impl PublicSet {
+    // Creates a public set from the public keys, compressing each of them once
+    pub fn new(points: Vec<RistrettoPoint>) -> Self {
+        let keys = points.iter().map(PublicKey::from_point).collect();
+        PublicSet { points, keys }
+    }
+    // Adds a public key to the end of the set
+    pub fn push(&mut self, point: RistrettoPoint) {
+        self.keys.push(PublicKey::from_point(&point));
+        self.points.push(point);
+    }
+    // Returns the public keys in the set
+    pub fn points(&self) -> &[RistrettoPoint] {
+        &self.points
+    }
+    // Returns the compressed public keys in the set
+    pub fn keys(&self) -> &[PublicKey] {
+        &self.keys
+    }
     // Returns the number of public keys in the set
     pub fn len(&self) -> usize {
-        self.0.len()
+        self.points.len()
     }
 
     // Returns true if the set is empty, else false
     pub fn is_empty(&self) -> bool {
-        if self.0.len() == 0{
+        if self.points.len() == 0{
             return true;
         }
         false
@@ -30,56 +92,123 @@ impl PublicSet {
 
     // Checks if the public set contains any duplicate keys
     pub fn duplicates_exist(&self) -> bool {
-        // XXX: Very in-efficient way to do this.
-        // We can wait for upstream crate to implement Hash and use a HashSet instead
-
-        let compressed_points: Vec<CompressedRistretto> =
-            self.0.iter().map(|point| point.compress()).collect();
-
-        let hashable_slice: Vec<&[u8; 32]> =
-            compressed_points.iter().map(|cp| cp.as_bytes()).collect();
-
-        let uniques: HashSet<_> = hashable_slice.iter().collect();
-
-        self.0.len() != uniques.len()
+        let mut uniques = HashSet::with_capacity(self.keys.len());
+        !self.keys.iter().all(|key| uniques.insert(key))
+    }
+    // Returns true if the key is in the public set
+    pub fn contains(&self, key: &PublicKey) -> bool {
+        self.keys.contains(key)
     }
     // Returns the Hash_to_point of the first public key in the set
     // This point is used extensively during the protocol for each member
     pub fn hashed_pubkey(&self) -> RistrettoPoint {
-        let first_pubkey = &self.0[0].compress();
-        RistrettoPoint::hash_from_bytes::<Sha512>(first_pubkey.as_bytes())
+        hash_pubkey(self.keys[0].as_bytes())
     }
     // Copies the public key set into a vector of bytes
     pub fn to_bytes(&self) -> Vec<u8> {
-        self.0
+        self.keys
             .iter()
-            .flat_map(|point| point.compress().to_bytes().to_vec())
+            .flat_map(|key| key.to_bytes().to_vec())
             .collect()
     }
 
-    pub fn to_keys(&self) -> Vec<CompressedRistretto> {
-        self.0.iter().map(|key| key.compress()).collect()
+    // Returns the compressed public keys in the set
+    pub fn to_keys(&self) -> Vec<PublicKey> {
+        self.keys.clone()
+    }
+    // Puts the public sets of a ring in canonical order, sorted by the bytes of their keys,
+    // and returns the new index of the signers set. See `ring::canonicalize`.
+    // Returns an error, and leaves the sets as they are, if the signer index is not in the ring
+    pub fn canonicalize(sets: &mut [PublicSet], signer_index: usize) -> Result<usize, ClsagError> {
+        let keys: Vec<Vec<PublicKey>> = sets.iter().map(|set| set.to_keys()).collect();
+        let order = canonical_order(&keys);
+        let new_index = order
+            .iter()
+            .position(|&i| i == signer_index)
+            .ok_or(ClsagError::NoSigner)?;
+        let sorted: Vec<PublicSet> = order.iter().map(|&i| sets[i].clone()).collect();
+        sets.clone_from_slice(&sorted);
+        Ok(new_index)
+    }
+    // Returns the aggregate key of the set, the sum of each key weighted by its aggregation
+    // co-efficient, which is the W_i of the member in the CLSAG paper. The co-efficients of a
+    // ring are returned by `clsag::calc_aggregation_coefficients`, so that verifiers can
+    // recompute each step of the aggregation on their own.
+    // Returns an error if there is not one co-efficient per key
+    pub fn aggregate(&self, mu: &[Scalar]) -> Result<RistrettoPoint, ClsagError> {
+        if self.len() != mu.len() {
+            return Err(ClsagError::KeyCountMismatch);
+        }
+        Ok(RistrettoPoint::vartime_multiscalar_mul(mu, &self.points))
+    }
+    // Precomputes the multiples of the keys of the set, so that the aggregate key can be
+    // computed faster for many different co-efficients, such as by an auditor recomputing
+    // every ring the set was a member of
+    pub fn aggregation_table(&self) -> AggregationTable {
+        AggregationTable {
+            table: VartimeRistrettoPrecomputation::new(&self.points),
+            len: self.len(),
+        }
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct PrivateSet(pub(crate) Vec<Scalar>);
+// The precomputed multiples of the keys of a public set, see `PublicSet::aggregation_table`.
+// The keys are public, so the aggregate keys are computed in variable time
+pub struct AggregationTable {
+    table: VartimeRistrettoPrecomputation,
+    len: usize,
+}
+
+impl AggregationTable {
+    // Returns the aggregate key of the set, see `PublicSet::aggregate`.
+    // Returns an error if there is not one co-efficient per key
+    pub fn aggregate(&self, mu: &[Scalar]) -> Result<RistrettoPoint, ClsagError> {
+        if self.len != mu.len() {
+            return Err(ClsagError::KeyCountMismatch);
+        }
+        Ok(self.table.vartime_multiscalar_mul(mu))
+    }
+}
+
+impl fmt::Debug for AggregationTable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "AggregationTable({} keys)", self.len)
+    }
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PrivateSet(
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::scalars"))]
+    pub(crate) Vec<Scalar>,
+);
 
 impl PrivateSet {
     pub fn new(scalars: Vec<Scalar>) -> Self {
         PrivateSet(scalars)
     }
+    // Generates a set of `num_keys` private keys from the given rng.
+    // Each key is a uniformly random scalar, reduced from 64 bytes of the rng,
+    // so the keys are not biased as keys reduced from 32 random bytes would be
+    pub fn generate<R: RngCore + CryptoRng>(num_keys: usize, rng: &mut R) -> Self {
+        PrivateSet((0..num_keys).map(|_| Scalar::random(rng)).collect())
+    }
     // Takes a set of private keys
     // and returns the corresponding public key set
     // along with the basepoint used in calculating the key images
     pub fn to_public_set(&self) -> PublicSet {
+        self.to_public_set_with_generator(&BASEPOINT)
+    }
+    // Returns the public key set over another generator than the basepoint,
+    // for rings signed with `Clsag::with_generator`
+    pub fn to_public_set_with_generator(&self, generator: &RistrettoPoint) -> PublicSet {
         let public_keys = self
             .0
             .iter()
-            .map(|&x| x * BASEPOINT)
+            .map(|x| x * generator)
             .collect::<Vec<RistrettoPoint>>();
 
-        PublicSet(public_keys)
+        PublicSet::new(public_keys)
     }
 
     // Returns all of the keyImages for a specific private key set
@@ -87,13 +216,10 @@ impl PrivateSet {
     // The difference here is that we compute the key images with respect to the hash of the
     // public key corresponding to the signing key
     // Note that the HashToPoint must not allow the basepoint in the public key to be factored out
-    pub fn compute_key_images(
-        &self,
-        signers_basepoint: &RistrettoPoint,
-    ) -> Vec<CompressedRistretto> {
+    pub fn compute_key_images(&self, signers_basepoint: &RistrettoPoint) -> Vec<KeyImage> {
         self.0
             .iter()
-            .map(|priv_key| (priv_key * signers_basepoint).compress())
+            .map(|priv_key| KeyImage((priv_key * signers_basepoint).compress()))
             .collect()
     }
 
@@ -111,10 +237,294 @@ impl PrivateSet {
     }
 }
 
+// A public key of a ring member, held as a 32 byte compressed ristretto point.
+// A public key can only be made from a valid point, so it always decompresses.
+// Keys are equal, ordered and hashed by their compressed bytes, so they can be
+// compared and held in sets without being compressed again
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PublicKey(
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::serde_helpers::compressed_point")
+    )]
+    pub(crate) CompressedRistretto,
+);
+
+impl PublicKey {
+    // Decodes a public key from its 32 byte compressed encoding.
+    // Returns an error if the bytes are not a valid point
+    pub fn from_bytes(bytes: &[u8; 32]) -> Result<Self, ClsagError> {
+        let key = CompressedRistretto(*bytes);
+        key.decompress().ok_or(ClsagError::InvalidPoint)?;
+        Ok(PublicKey(key))
+    }
+    // Returns the 32 byte compressed encoding of the public key
+    pub fn to_bytes(&self) -> [u8; 32] {
+        self.0.to_bytes()
+    }
+    // Returns a reference to the 32 byte compressed encoding of the public key
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        self.0.as_bytes()
+    }
+    pub(crate) fn from_point(point: &RistrettoPoint) -> Self {
+        PublicKey(point.compress())
+    }
+    pub(crate) fn decompress(&self) -> Option<RistrettoPoint> {
+        self.0.decompress()
+    }
+}
+
+impl Hash for PublicKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_bytes().hash(state);
+    }
+}
+
+impl PartialOrd for PublicKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for PublicKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_bytes().cmp(other.as_bytes())
+    }
+}
+
+// A secret key of the signer, held as a canonical scalar
+#[derive(Clone)]
+pub struct SecretKey(pub(crate) Scalar);
+
+impl SecretKey {
+    // Generates a secret key from the given rng
+    pub fn random<R: RngCore + CryptoRng>(rng: &mut R) -> Self {
+        SecretKey(Scalar::random(rng))
+    }
+    // Decodes a secret key from its 32 byte canonical encoding.
+    // Returns an error if the bytes are not reduced modulo the group order
+    pub fn from_bytes(bytes: &[u8; 32]) -> Result<Self, ClsagError> {
+        Scalar::from_canonical_bytes(*bytes)
+            .map(SecretKey)
+            .ok_or(ClsagError::NonCanonicalScalar)
+    }
+    // Returns the 32 byte canonical encoding of the secret key
+    pub fn to_bytes(&self) -> [u8; 32] {
+        self.0.to_bytes()
+    }
+    // Returns the public key corresponding to the secret key
+    pub fn public_key(&self) -> PublicKey {
+        self.public_key_with_generator(&BASEPOINT)
+    }
+    // Returns the public key of the secret key over another generator than the basepoint
+    pub fn public_key_with_generator(&self, generator: &RistrettoPoint) -> PublicKey {
+        PublicKey::from_point(&(self.0 * generator))
+    }
+}
+
+// The private keys are never printed, only how many there are
+impl fmt::Debug for PrivateSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "PrivateSet({} keys)", self.0.len())
+    }
+}
+
+// The secret key is never printed, so that it cannot end up in logs
+impl fmt::Debug for SecretKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SecretKey(..)")
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for SecretKey {
+    fn drop(&mut self) {
+        zeroize_scalar(&mut self.0);
+    }
+}
+
+// A key image of the signer, held as a 32 byte compressed ristretto point.
+// A key image can only be made from a valid point, so it always decompresses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct KeyImage(
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::serde_helpers::compressed_point")
+    )]
+    pub(crate) CompressedRistretto,
+);
+
+impl KeyImage {
+    // Decodes a key image from its 32 byte compressed encoding.
+    // Returns an error if the bytes are not the canonical encoding of a point,
+    // or if the point is the identity
+    pub fn from_bytes(bytes: &[u8; 32]) -> Result<Self, ClsagError> {
+        let key_image = KeyImage(CompressedRistretto(*bytes));
+        key_image.check()?;
+        Ok(key_image)
+    }
+    // Returns true if the key image is the canonical encoding of a point in the prime order
+    // subgroup other than the identity, which are the only key images a signer can produce.
+    // Ristretto decompression rejects every non-canonical encoding, and the ristretto group
+    // has prime order, so there are no torsion components to check for and the identity is
+    // the only point of small order. Verification rejects signatures with invalid key images
+    pub fn is_valid(&self) -> bool {
+        self.check().is_ok()
+    }
+    // Checks that the key image is valid, returning the decompressed point
+    pub(crate) fn check(&self) -> Result<RistrettoPoint, ClsagError> {
+        let point = self.decompress().ok_or(ClsagError::InvalidKeyImage)?;
+        if point.is_identity() {
+            return Err(ClsagError::IdentityKeyImage);
+        }
+        Ok(point)
+    }
+    // Returns the 32 byte compressed encoding of the key image
+    pub fn to_bytes(&self) -> [u8; 32] {
+        self.0.to_bytes()
+    }
+    // Returns a reference to the 32 byte compressed encoding of the key image
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        self.0.as_bytes()
+    }
+    pub(crate) fn decompress(&self) -> Option<RistrettoPoint> {
+        self.0.decompress()
+    }
+}
+
+// Generates a secret key from the given rng, along with its public key over the basepoint.
+// Use `PrivateSet::generate` for a signer with more than one key
+pub fn generate_keypair<R: RngCore + CryptoRng>(rng: &mut R) -> (SecretKey, PublicKey) {
+    let secret_key = SecretKey::random(rng);
+    let public_key = secret_key.public_key();
+    (secret_key, public_key)
+}
+
+// Returns the key image of the secret key for an output with the given public key,
+// without signing, so that a wallet can index the key images of its outputs while scanning.
+// The public key is the first key of the member, as the key image of each key of a member
+// is computed over the hash of its first key. The key image is computed with the default
+// construction in the hash module, as it is for signatures verified with `optimised_verify`
+pub fn compute_key_image(secret: &SecretKey, public: &PublicKey) -> KeyImage {
+    KeyImage((secret.0 * hash_pubkey(public.as_bytes())).compress())
+}
+
+// Conversions to and from the curve25519-dalek types, for callers which already work
+// with dalek directly. These are behind the `dalek` feature, as they tie the caller
+// to the version of curve25519-dalek used by this crate
+#[cfg(feature = "dalek")]
+mod dalek_conversions {
+    use super::*;
+
+    impl From<RistrettoPoint> for PublicKey {
+        fn from(point: RistrettoPoint) -> Self {
+            PublicKey::from_point(&point)
+        }
+    }
+
+    impl From<PublicKey> for CompressedRistretto {
+        fn from(key: PublicKey) -> Self {
+            key.0
+        }
+    }
+
+    impl From<Scalar> for SecretKey {
+        fn from(scalar: Scalar) -> Self {
+            SecretKey(scalar)
+        }
+    }
+
+    impl From<RistrettoPoint> for KeyImage {
+        fn from(point: RistrettoPoint) -> Self {
+            KeyImage(point.compress())
+        }
+    }
+
+    impl From<KeyImage> for CompressedRistretto {
+        fn from(key_image: KeyImage) -> Self {
+            key_image.0
+        }
+    }
+}
+
+// Overwrites a secret scalar with zero when the `zeroize` feature is enabled.
+// curve25519-dalek does not implement Zeroize for scalars, so we overwrite
+// the scalar with a volatile write which the compiler will not optimise away
+pub(crate) fn zeroize_scalar(scalar: &mut Scalar) {
+    #[cfg(feature = "zeroize")]
+    unsafe {
+        std::ptr::write_volatile(scalar, Scalar::zero());
+        std::sync::atomic::compiler_fence(std::sync::atomic::Ordering::SeqCst);
+    }
+    #[cfg(not(feature = "zeroize"))]
+    let _ = scalar;
+}
+
+// Overwrites key material with zeroes, with volatile writes which the compiler will not
+// optimise away. Unlike `zeroize_scalar`, this is done whether or not the zeroize feature
+// is enabled, as it is used on the intermediate keys of derivations and encryption
+pub(crate) fn wipe_bytes(bytes: &mut [u8]) {
+    for byte in bytes.iter_mut() {
+        unsafe { std::ptr::write_volatile(byte, 0) };
+    }
+    std::sync::atomic::compiler_fence(std::sync::atomic::Ordering::SeqCst);
+}
+
+#[cfg(feature = "zeroize")]
+impl Zeroize for PrivateSet {
+    fn zeroize(&mut self) {
+        for scalar in self.0.iter_mut() {
+            zeroize_scalar(scalar);
+        }
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for PrivateSet {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl ZeroizeOnDrop for PrivateSet {}
+
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::tests_helper::*;
+    use crate::test_utils::*;
+    #[test]
+    fn aggregate_public_set() {
+        let private_set = generate_private_set(3);
+        let public_set = private_set.to_public_set();
+        let mu = generate_rand_scalars(3);
+
+        // The aggregate key of a signer is their aggregate private key times the basepoint
+        let aggregate_private_key: Scalar = private_set.0.iter().zip(&mu).map(|(x, m)| x * m).sum();
+        let expected = aggregate_private_key * BASEPOINT;
+        assert_eq!(expected, public_set.aggregate(&mu).unwrap());
+
+        let table = public_set.aggregation_table();
+        assert_eq!(expected, table.aggregate(&mu).unwrap());
+        let other_mu = generate_rand_scalars(3);
+        assert_eq!(public_set.aggregate(&other_mu), table.aggregate(&other_mu));
+        assert_eq!("AggregationTable(3 keys)", format!("{:?}", table));
+    }
+    #[test]
+    fn aggregate_needs_a_coefficient_per_key() {
+        let public_set = generate_private_set(3).to_public_set();
+        match public_set.aggregate(&generate_rand_scalars(2)) {
+            Err(ClsagError::KeyCountMismatch) => {}
+            _ => panic!("expected a key count mismatch error"),
+        }
+        let table = public_set.aggregation_table();
+        match table.aggregate(&generate_rand_scalars(4)) {
+            Err(ClsagError::KeyCountMismatch) => {}
+            _ => panic!("expected a key count mismatch error"),
+        }
+    }
     // This test is a sanity check for private to public key sets.
     // The iter method is used when converting from a set of private keys
     // to a set of public keys. In the test, we use a for loop and check that both
@@ -127,9 +537,9 @@ mod test {
         assert_eq!(private_set.len(), public_set.len());
 
         for i in 0..private_set.len() {
-            match (private_set.0.get(i), public_set.0.get(i)) {
+            match (private_set.0.get(i), public_set.points().get(i)) {
                 (Some(private_key), Some(expected_public_key)) => {
-                    let public_key = private_key * &BASEPOINT;
+                    let public_key = private_key * BASEPOINT;
                     assert_eq!(public_key, *expected_public_key);
                 }
                 _ => panic!("could not get the private/public key at index {} ", i),
@@ -144,10 +554,178 @@ mod test {
         let dup_exists = public_set.duplicates_exist();
         assert!(!dup_exists);
 
-        let last_element = public_set.0.last().unwrap().clone();
-        public_set.0[0] = last_element;
+        let mut points = public_set.points().to_vec();
+        points[0] = *points.last().unwrap();
+        public_set = PublicSet::new(points);
 
         let dup_exists = public_set.duplicates_exist();
         assert!(dup_exists);
     }
+    #[test]
+    fn check_canonicalize() {
+        let mut sets: Vec<PublicSet> = (0..6)
+            .map(|_| generate_private_set(2).to_public_set())
+            .collect();
+        let signer_bytes = sets[4].to_bytes();
+
+        let signer_index = PublicSet::canonicalize(&mut sets, 4).unwrap();
+        assert_eq!(signer_bytes, sets[signer_index].to_bytes());
+        assert!(sets
+            .windows(2)
+            .all(|pair| pair[0].to_bytes() <= pair[1].to_bytes()));
+
+        // A signer index outside of the ring is rejected, and the sets are left as they are
+        let mut other = sets.clone();
+        other.reverse();
+        match PublicSet::canonicalize(&mut other, 6) {
+            Err(ClsagError::NoSigner) => {}
+            _ => panic!("expected a no signer error"),
+        }
+        assert_eq!(
+            signer_bytes,
+            other[sets.len() - 1 - signer_index].to_bytes()
+        );
+    }
+    #[test]
+    fn check_public_key_ordering() {
+        let mut keys = generate_rand_public_keys(8);
+        keys.sort();
+        assert!(keys
+            .windows(2)
+            .all(|pair| pair[0].as_bytes() < pair[1].as_bytes()));
+
+        let uniques: HashSet<PublicKey> = keys.iter().chain(keys.iter()).cloned().collect();
+        assert_eq!(keys.len(), uniques.len());
+
+        let public_set = generate_private_set(4).to_public_set();
+        assert!(public_set.contains(&public_set.to_keys()[2]));
+        assert!(!public_set.contains(&keys[0]));
+    }
+    #[test]
+    fn check_key_image_is_valid() {
+        let private_set = generate_private_set(1);
+        let hashed_pubkey = private_set.to_public_set().hashed_pubkey();
+        let key_image = private_set.compute_key_images(&hashed_pubkey)[0];
+        assert!(key_image.is_valid());
+
+        assert!(!KeyImage(CompressedRistretto([0u8; 32])).is_valid());
+        assert!(!KeyImage(CompressedRistretto([0xff; 32])).is_valid());
+    }
+    #[test]
+    fn check_compute_key_image() {
+        let mut rng = rand::thread_rng();
+        let secret_keys = vec![SecretKey::random(&mut rng), SecretKey::random(&mut rng)];
+        let public_key = secret_keys[0].public_key();
+
+        let signer = crate::member::Member::signer_from_secret_keys(&secret_keys).unwrap();
+        let key_images = signer.compute_key_images().unwrap();
+        for (key_image, secret_key) in key_images.iter().zip(&secret_keys) {
+            assert_eq!(*key_image, compute_key_image(secret_key, &public_key));
+        }
+    }
+    #[test]
+    fn check_generate_keys() {
+        let (secret_key, public_key) = generate_keypair(&mut seeded_rng(1));
+        assert_eq!(public_key, secret_key.public_key());
+        let (other, _) = generate_keypair(&mut seeded_rng(1));
+        assert_eq!(secret_key.to_bytes(), other.to_bytes());
+
+        let private_set = PrivateSet::generate(3, &mut seeded_rng(2));
+        assert_eq!(3, private_set.len());
+        assert!(!private_set.to_public_set().duplicates_exist());
+        assert_eq!(private_set.0, PrivateSet::generate(3, &mut seeded_rng(2)).0);
+        assert!(PrivateSet::generate(0, &mut seeded_rng(2)).is_empty());
+        assert_eq!("PrivateSet(3 keys)", format!("{:?}", private_set));
+    }
+    #[test]
+    fn key_bytes_round_trip() {
+        let secret_key = SecretKey::random(&mut rand::thread_rng());
+        let decoded_secret_key = SecretKey::from_bytes(&secret_key.to_bytes()).unwrap();
+        assert_eq!(secret_key.to_bytes(), decoded_secret_key.to_bytes());
+        assert_eq!("SecretKey(..)", format!("{:?}", secret_key));
+
+        let public_key = secret_key.public_key();
+        assert_eq!(
+            public_key,
+            PublicKey::from_bytes(public_key.as_bytes()).unwrap()
+        );
+        assert_eq!(
+            (secret_key.0 * BASEPOINT).compress().to_bytes(),
+            public_key.to_bytes()
+        );
+
+        let private_set = PrivateSet::new(vec![secret_key.0]);
+        let key_image = private_set.compute_key_images(&BASEPOINT)[0];
+        assert_eq!(
+            key_image,
+            KeyImage::from_bytes(&key_image.to_bytes()).unwrap()
+        );
+
+        match PublicKey::from_bytes(&[0xff; 32]) {
+            Err(ClsagError::InvalidPoint) => {}
+            _ => panic!("expected an invalid point error"),
+        };
+        match KeyImage::from_bytes(&[0xff; 32]) {
+            Err(ClsagError::InvalidKeyImage) => {}
+            _ => panic!("expected an invalid key image error"),
+        };
+        match SecretKey::from_bytes(&[0xff; 32]) {
+            Err(ClsagError::NonCanonicalScalar) => {}
+            _ => panic!("expected a non canonical scalar error"),
+        };
+    }
+    #[cfg(feature = "dalek")]
+    #[test]
+    fn dalek_conversions() {
+        let point = generate_rand_points(1)[0];
+        let public_key = PublicKey::from(point);
+        assert_eq!(point.compress(), CompressedRistretto::from(public_key));
+
+        let key_image = KeyImage::from(point);
+        assert_eq!(point.compress(), CompressedRistretto::from(key_image));
+
+        let scalar = generate_rand_scalars(1)[0];
+        assert_eq!(scalar * BASEPOINT, SecretKey::from(scalar).0 * BASEPOINT);
+    }
+    #[cfg(feature = "zeroize")]
+    #[test]
+    fn zeroize_private_set() {
+        let mut private_set = generate_private_set(5);
+        private_set.zeroize();
+
+        assert_eq!(private_set.len(), 5);
+        assert!(private_set.0.iter().all(|x| *x == Scalar::zero()));
+    }
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let private_set = generate_private_set(4);
+        let public_set = private_set.to_public_set();
+
+        let json = serde_json::to_string(&private_set).unwrap();
+        let decoded_private_set: PrivateSet = serde_json::from_str(&json).unwrap();
+        assert_eq!(private_set.0, decoded_private_set.0);
+
+        let json = serde_json::to_string(&public_set).unwrap();
+        let decoded_public_set: PublicSet = serde_json::from_str(&json).unwrap();
+        assert_eq!(public_set.points(), decoded_public_set.points());
+        assert_eq!(public_set.keys(), decoded_public_set.keys());
+    }
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_encodes_compressed_bytes() {
+        // Points should be encoded as their 32 byte compressed form
+        // and scalars as their 32 byte canonical form
+        let private_set = generate_private_set(1);
+        let public_set = private_set.to_public_set();
+
+        let point_json = serde_json::to_string(&public_set.points()[0]).unwrap();
+        let expected_point_json =
+            serde_json::to_string(&public_set.points()[0].compress().to_bytes()).unwrap();
+        assert_eq!(point_json, expected_point_json);
+
+        let scalar_json = serde_json::to_string(&private_set.0[0]).unwrap();
+        let expected_scalar_json = serde_json::to_string(&private_set.0[0].to_bytes()).unwrap();
+        assert_eq!(scalar_json, expected_scalar_json);
+    }
 }
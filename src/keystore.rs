@@ -0,0 +1,300 @@
+// Encrypting private key sets at rest, behind the keystore feature.
+//
+// `PrivateSet::seal` encrypts the private keys of a signer under a passphrase, into a blob
+// which can be written to disk and opened with `PrivateSet::unseal` on any platform. The
+// passphrase is stretched with Argon2id over a random salt into a key, and the private keys
+// are encrypted with XChaCha20-Poly1305 under a random nonce, with the header of the blob as
+// associated data. A wrong passphrase or a modified blob fails authentication, so nothing is
+// decrypted from it. A fresh salt and nonce are drawn for every seal, so the keys of two blobs
+// sealed under the same passphrase are unrelated.
+//
+// The version, the KDF and its costs are recorded in the blob, so that the costs can be raised
+// and other constructions added as new versions without breaking blobs already sealed.
+use crate::error::ClsagError;
+use crate::keys::{wipe_bytes, PrivateSet};
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use curve25519_dalek::scalar::Scalar;
+use rand::{CryptoRng, RngCore};
+
+// The most memory, in KiB, `PrivateSet::unseal` lets Argon2id use, so that
+// a blob from an untrusted source cannot make us allocate more than a GiB
+pub const MAX_MEMORY_KIB: u32 = 1 << 20;
+// The most passes over the memory `PrivateSet::unseal` accepts
+pub const MAX_ITERATIONS: u32 = 64;
+// The most lanes `PrivateSet::unseal` accepts
+pub const MAX_PARALLELISM: u32 = 16;
+
+const VERSION: u8 = 1;
+const KDF_ARGON2ID: u8 = 1;
+
+const SALT_SIZE: usize = 16;
+const NONCE_SIZE: usize = 24;
+const TAG_SIZE: usize = 16;
+const KEY_SIZE: usize = 32;
+const HEADER_SIZE: usize = 1 + 1 + 4 + 4 + 4 + SALT_SIZE + NONCE_SIZE;
+
+// The costs of stretching the passphrase with Argon2id, which are recorded in the blob.
+// The default is the cost recommended by the argon2 crate, 19 MiB with two passes and one lane
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KdfParams {
+    // The memory used, in KiB
+    pub memory_kib: u32,
+    // The number of passes over the memory
+    pub iterations: u32,
+    // The number of lanes
+    pub parallelism: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        KdfParams {
+            memory_kib: Params::DEFAULT_M_COST,
+            iterations: Params::DEFAULT_T_COST,
+            parallelism: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+impl KdfParams {
+    // Derives the key the private keys are encrypted under from the passphrase.
+    // Returns `InvalidKeystore` if the costs are above the maximums or are not valid for Argon2id
+    fn derive_key(&self, passphrase: &[u8], salt: &[u8]) -> Result<[u8; KEY_SIZE], ClsagError> {
+        if self.memory_kib > MAX_MEMORY_KIB
+            || self.iterations > MAX_ITERATIONS
+            || self.parallelism > MAX_PARALLELISM
+        {
+            return Err(ClsagError::InvalidKeystore);
+        }
+        let params = Params::new(
+            self.memory_kib,
+            self.iterations,
+            self.parallelism,
+            Some(KEY_SIZE),
+        )
+        .map_err(|_| ClsagError::InvalidKeystore)?;
+
+        let mut key = [0u8; KEY_SIZE];
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase, salt, &mut key)
+            .map_err(|_| ClsagError::InvalidKeystore)?;
+        Ok(key)
+    }
+}
+
+impl PrivateSet {
+    // Encrypts the private keys under the passphrase, with the default costs
+    pub fn seal(&self, passphrase: &[u8]) -> Result<Vec<u8>, ClsagError> {
+        self.seal_with_rng(&mut rand::thread_rng(), passphrase, KdfParams::default())
+    }
+    // Encrypts the private keys under the passphrase, drawing the salt and nonce from the rng.
+    // The blob has the following layout, all integers little-endian:
+    //
+    // | version (u8) | kdf (u8) | memory_kib (u32) | iterations (u32) | parallelism (u32) |
+    // | salt (16) | nonce (24) | encrypted keys (32 * num_keys) | tag (16) |
+    //
+    // Returns `InvalidKeystore` if the costs are above the maximums or are not valid for
+    // Argon2id, as `unseal` does, since the blob could not be unsealed
+    pub fn seal_with_rng<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        passphrase: &[u8],
+        params: KdfParams,
+    ) -> Result<Vec<u8>, ClsagError> {
+        let mut salt = [0u8; SALT_SIZE];
+        rng.fill_bytes(&mut salt);
+        let mut nonce = [0u8; NONCE_SIZE];
+        rng.fill_bytes(&mut nonce);
+
+        let mut key = params.derive_key(passphrase, &salt)?;
+
+        let mut blob = Vec::with_capacity(HEADER_SIZE + KEY_SIZE * self.0.len() + TAG_SIZE);
+        blob.push(VERSION);
+        blob.push(KDF_ARGON2ID);
+        blob.extend_from_slice(&params.memory_kib.to_le_bytes());
+        blob.extend_from_slice(&params.iterations.to_le_bytes());
+        blob.extend_from_slice(&params.parallelism.to_le_bytes());
+        blob.extend_from_slice(&salt);
+        blob.extend_from_slice(&nonce);
+
+        let mut plaintext: Vec<u8> = self.0.iter().flat_map(|x| x.to_bytes().to_vec()).collect();
+        let payload = Payload {
+            msg: &plaintext,
+            aad: &blob,
+        };
+        let ciphertext = XChaCha20Poly1305::new(Key::from_slice(&key))
+            .encrypt(XNonce::from_slice(&nonce), payload);
+        wipe_bytes(&mut plaintext);
+        wipe_bytes(&mut key);
+
+        blob.extend_from_slice(&ciphertext.map_err(|_| ClsagError::InvalidKeystore)?);
+        Ok(blob)
+    }
+    // Decrypts private keys sealed by `seal`.
+    // Returns `InvalidKeystore` if the blob is malformed, has an unknown version or KDF,
+    // or costs above the maximums, and `UnsealFailed` if the passphrase is wrong
+    // or the blob was modified
+    pub fn unseal(blob: &[u8], passphrase: &[u8]) -> Result<PrivateSet, ClsagError> {
+        if blob.len() < HEADER_SIZE + TAG_SIZE
+            || !(blob.len() - HEADER_SIZE - TAG_SIZE).is_multiple_of(KEY_SIZE)
+            || blob[0] != VERSION
+            || blob[1] != KDF_ARGON2ID
+        {
+            return Err(ClsagError::InvalidKeystore);
+        }
+        let params = KdfParams {
+            memory_kib: read_u32(&blob[2..6]),
+            iterations: read_u32(&blob[6..10]),
+            parallelism: read_u32(&blob[10..14]),
+        };
+        let salt = &blob[14..14 + SALT_SIZE];
+        let nonce = &blob[14 + SALT_SIZE..HEADER_SIZE];
+        let (header, ciphertext) = blob.split_at(HEADER_SIZE);
+
+        let mut key = params.derive_key(passphrase, salt)?;
+        let payload = Payload {
+            msg: ciphertext,
+            aad: header,
+        };
+        let plaintext = XChaCha20Poly1305::new(Key::from_slice(&key))
+            .decrypt(XNonce::from_slice(nonce), payload);
+        wipe_bytes(&mut key);
+        let mut plaintext = plaintext.map_err(|_| ClsagError::UnsealFailed)?;
+
+        let scalars = plaintext
+            .chunks_exact(KEY_SIZE)
+            .map(|chunk| {
+                let mut bytes = [0u8; KEY_SIZE];
+                bytes.copy_from_slice(chunk);
+                let scalar = Scalar::from_canonical_bytes(bytes);
+                wipe_bytes(&mut bytes);
+                scalar.ok_or(ClsagError::NonCanonicalScalar)
+            })
+            .collect::<Result<Vec<Scalar>, ClsagError>>();
+        wipe_bytes(&mut plaintext);
+
+        Ok(PrivateSet::new(scalars?))
+    }
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    let mut buf = [0u8; 4];
+    buf.copy_from_slice(bytes);
+    u32::from_le_bytes(buf)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::*;
+
+    // The cheapest costs Argon2id accepts, so that the tests run quickly
+    const TEST_PARAMS: KdfParams = KdfParams {
+        memory_kib: 8,
+        iterations: 1,
+        parallelism: 1,
+    };
+
+    #[test]
+    fn test_seal_roundtrip() {
+        let private_set = generate_private_set(3);
+        let blob = private_set
+            .seal_with_rng(&mut seeded_rng(1), b"correct horse", TEST_PARAMS)
+            .unwrap();
+        assert_eq!(HEADER_SIZE + 3 * KEY_SIZE + TAG_SIZE, blob.len());
+
+        let unsealed = PrivateSet::unseal(&blob, b"correct horse").unwrap();
+        assert_eq!(private_set.0, unsealed.0);
+
+        // The keys do not appear in the blob
+        for scalar in &private_set.0 {
+            assert!(!blob.windows(32).any(|window| window == scalar.as_bytes()));
+        }
+
+        // Sealing again draws a new salt and nonce, so the blobs differ
+        let other = private_set
+            .seal_with_rng(&mut seeded_rng(2), b"correct horse", TEST_PARAMS)
+            .unwrap();
+        assert_ne!(blob, other);
+    }
+
+    #[test]
+    fn test_seal_default_params() {
+        let private_set = generate_private_set(1);
+        let blob = private_set.seal(b"passphrase").unwrap();
+        let params = KdfParams::default();
+        assert_eq!(params.memory_kib.to_le_bytes(), blob[2..6]);
+        assert_eq!(params.iterations.to_le_bytes(), blob[6..10]);
+        assert_eq!(params.parallelism.to_le_bytes(), blob[10..14]);
+        assert_eq!(
+            private_set.0,
+            PrivateSet::unseal(&blob, b"passphrase").unwrap().0
+        );
+    }
+
+    #[test]
+    fn test_unseal_errors() {
+        let blob = generate_private_set(2)
+            .seal_with_rng(&mut seeded_rng(3), b"passphrase", TEST_PARAMS)
+            .unwrap();
+
+        match PrivateSet::unseal(&blob, b"wrong passphrase") {
+            Err(ClsagError::UnsealFailed) => {}
+            _ => panic!("expected a wrong passphrase to be rejected"),
+        }
+        // Every byte is authenticated, including the header
+        for index in 0..blob.len() {
+            let mut modified = blob.clone();
+            modified[index] ^= 1;
+            match PrivateSet::unseal(&modified, b"passphrase") {
+                Err(ClsagError::UnsealFailed) | Err(ClsagError::InvalidKeystore) => {}
+                _ => panic!("expected a modified blob to be rejected"),
+            }
+        }
+
+        let mut too_much_memory = blob.clone();
+        too_much_memory[2..6].copy_from_slice(&(MAX_MEMORY_KIB + 1).to_le_bytes());
+        let mut too_many_iterations = blob.clone();
+        too_many_iterations[6..10].copy_from_slice(&(MAX_ITERATIONS + 1).to_le_bytes());
+        let mut no_lanes = blob.clone();
+        no_lanes[10..14].copy_from_slice(&0u32.to_le_bytes());
+        let mut unknown_version = blob.clone();
+        unknown_version[0] = VERSION + 1;
+        let mut trailing = blob.clone();
+        trailing.push(0);
+        for malformed in &[
+            too_much_memory,
+            too_many_iterations,
+            no_lanes,
+            unknown_version,
+            trailing,
+            blob[..blob.len() - 1].to_vec(),
+            Vec::new(),
+        ] {
+            match PrivateSet::unseal(malformed, b"passphrase") {
+                Err(ClsagError::InvalidKeystore) => {}
+                _ => panic!("expected a malformed blob to be rejected"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_seal_invalid_params() {
+        let too_many_iterations = KdfParams {
+            iterations: MAX_ITERATIONS + 1,
+            ..TEST_PARAMS
+        };
+        let too_little_memory = KdfParams {
+            memory_kib: 1,
+            ..TEST_PARAMS
+        };
+        for params in &[too_many_iterations, too_little_memory] {
+            let private_set = generate_private_set(1);
+            match private_set.seal_with_rng(&mut seeded_rng(4), b"passphrase", *params) {
+                Err(ClsagError::InvalidKeystore) => {}
+                _ => panic!("expected the costs to be rejected"),
+            }
+        }
+    }
+}
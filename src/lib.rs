@@ -1,9 +1,85 @@
-#![feature(test)]
+#![cfg_attr(test, feature(test))]
+pub mod adaptor;
+pub mod anti_exfil;
+#[cfg(feature = "experimental")]
+pub mod bigring;
+#[cfg(feature = "borsh")]
+mod borsh_impls;
+pub mod builder;
 pub mod clsag;
+pub mod commitment;
 #[allow(non_snake_case)]
 pub mod constants;
+pub mod contract;
+pub mod cosigner;
+pub mod decoys;
+pub mod derive;
+pub mod device;
+pub mod digest;
+pub mod disclosure;
+pub mod dleq;
+#[cfg(feature = "encoding")]
+mod encoding;
+pub mod error;
+pub mod fixed;
+pub mod hash;
+#[cfg(feature = "hazmat")]
+pub mod hazmat;
 pub mod keys;
+#[cfg(feature = "keystore")]
+pub mod keystore;
+pub mod linkability;
+#[cfg(all(feature = "mlock", unix))]
+pub mod locked;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod malleability;
 pub mod member;
+pub mod metrics;
+pub mod mlsag;
+#[cfg(feature = "mnemonic")]
+pub mod mnemonic;
+#[cfg(feature = "uniffi")]
+pub mod mobile;
+#[cfg(feature = "monero")]
+pub mod monero;
+pub mod multisig;
+pub mod policy;
+#[cfg(feature = "async")]
+pub mod pool;
+#[cfg(feature = "precompute")]
+pub mod precompute;
+pub mod provider;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod range;
+pub mod remote;
+pub mod report;
+pub mod ring;
+pub mod sag;
+pub mod scan;
+#[cfg(feature = "experimental")]
+pub mod secp256k1;
+pub mod seed;
+pub mod selftest;
+#[cfg(feature = "serde")]
+mod serde_helpers;
+pub mod shamir;
 pub mod signature;
-pub mod tests_helper;
-mod transcript;
+pub mod snapshot;
+pub mod suite;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
+#[cfg(feature = "trace")]
+pub mod trace;
+pub mod traceable;
+pub mod transcript;
+#[cfg(feature = "experimental")]
+pub mod triptych;
+pub mod tx;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use crate::selftest::self_test;
+
+#[cfg(feature = "uniffi")]
+uniffi::setup_scaffolding!();
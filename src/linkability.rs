@@ -0,0 +1,189 @@
+use crate::clsag::PublicKeys;
+use crate::error::ClsagError;
+use crate::keys::KeyImage;
+use crate::signature::Signature;
+use std::collections::HashSet;
+
+// Two signatures are linked when they were produced by the same signing key.
+// Each key in a members key vector produces a key image, however only the key image
+// of the first (signing) key is used for linking. This is the key image that
+// stays the same across signatures, auxiliary keys can change per signature.
+
+#[derive(Debug)]
+pub enum Error {
+    // This error occurs when the signature does not verify
+    InvalidSignature(ClsagError),
+    // This error occurs when the signature's key image has already been recorded,
+    // meaning the signing key has been used before
+    AlreadyLinked,
+}
+
+impl From<ClsagError> for Error {
+    fn from(e: ClsagError) -> Error {
+        Error::InvalidSignature(e)
+    }
+}
+
+// A KeyImageStore keeps track of the key images that have been seen.
+// Implement this trait to back the store with a database or any other storage.
+pub trait KeyImageStore {
+    // Returns true if the key image has been recorded
+    fn contains_key_image(&self, key_image: &KeyImage) -> bool;
+
+    // Records the key image
+    fn insert_key_image(&mut self, key_image: KeyImage);
+
+    // Returns true if the signature was produced by a key
+    // which has already been recorded in the store
+    fn is_linked(&self, signature: &Signature) -> bool {
+        match signature.key_images.first() {
+            Some(key_image) => self.contains_key_image(key_image),
+            None => false,
+        }
+    }
+
+    // Records the signing key image of the signature
+    fn record(&mut self, signature: &Signature) {
+        if let Some(key_image) = signature.key_images.first() {
+            self.insert_key_image(*key_image);
+        }
+    }
+
+    // Verifies the signature and records its key image
+    // Returns an error if the signature is invalid or the key image has already been recorded;
+    // in both cases the store is left unchanged
+    fn verify_and_record(
+        &mut self,
+        signature: &Signature,
+        public_keys: &PublicKeys,
+        msg: &[u8],
+    ) -> Result<(), Error> {
+        signature.optimised_verify(public_keys, msg)?;
+
+        if self.is_linked(signature) {
+            return Err(Error::AlreadyLinked);
+        }
+        self.record(signature);
+
+        Ok(())
+    }
+}
+
+// An in-memory key image store
+#[derive(Debug, Clone, Default)]
+pub struct MemoryKeyImageStore {
+    key_images: HashSet<[u8; 32]>,
+}
+
+impl MemoryKeyImageStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Returns the number of key images recorded
+    pub fn len(&self) -> usize {
+        self.key_images.len()
+    }
+
+    // Returns true if no key images have been recorded, else false
+    pub fn is_empty(&self) -> bool {
+        self.key_images.is_empty()
+    }
+}
+
+impl KeyImageStore for MemoryKeyImageStore {
+    fn contains_key_image(&self, key_image: &KeyImage) -> bool {
+        self.key_images.contains(key_image.as_bytes())
+    }
+
+    fn insert_key_image(&mut self, key_image: KeyImage) {
+        self.key_images.insert(key_image.to_bytes());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::member::Member;
+    use crate::test_utils::*;
+
+    use curve25519_dalek::scalar::Scalar;
+
+    fn sign_with(private_keys: Vec<Scalar>, msg: &[u8]) -> (Signature, PublicKeys) {
+        let num_keys = private_keys.len();
+        let mut clsag = generate_clsag_with(4, num_keys);
+        clsag.add_member(Member::new_signer(private_keys)).unwrap();
+        clsag.sign(msg).unwrap()
+    }
+
+    #[test]
+    fn test_detects_double_signing() {
+        let num_keys = 2;
+        let private_keys = generate_rand_scalars(num_keys);
+
+        let (first_sig, first_ring) = sign_with(private_keys.clone(), b"first");
+        let (second_sig, second_ring) = sign_with(private_keys, b"second");
+
+        let mut store = MemoryKeyImageStore::new();
+        assert!(!store.is_linked(&first_sig));
+
+        store
+            .verify_and_record(&first_sig, &first_ring, b"first")
+            .unwrap();
+        assert_eq!(store.len(), 1);
+
+        // The same key signed over a different ring and message
+        assert!(store.is_linked(&second_sig));
+        match store.verify_and_record(&second_sig, &second_ring, b"second") {
+            Err(Error::AlreadyLinked) => {}
+            _ => panic!("expected an already linked error"),
+        }
+        assert_eq!(store.len(), 1);
+    }
+
+    #[test]
+    fn test_different_signers_not_linked() {
+        let mut store = MemoryKeyImageStore::new();
+
+        let (first_sig, _) = sign_with(generate_rand_scalars(2), b"hello world");
+        let (second_sig, _) = sign_with(generate_rand_scalars(2), b"hello world");
+
+        store.record(&first_sig);
+        assert!(!store.is_linked(&second_sig));
+    }
+
+    #[test]
+    fn test_links_with() {
+        let private_keys = generate_rand_scalars(2);
+        let (first_sig, _) = sign_with(private_keys.clone(), b"first");
+        let (second_sig, _) = sign_with(private_keys.clone(), b"second");
+        let (other_sig, _) = sign_with(generate_rand_scalars(2), b"first");
+
+        assert!(first_sig.links_with(&second_sig));
+        assert!(second_sig.links_with(&first_sig));
+        assert!(!first_sig.links_with(&other_sig));
+
+        // Only the signing key links, the auxiliary key can change
+        let mut aux_keys = private_keys;
+        aux_keys[1] = generate_rand_scalars(1)[0];
+        let (aux_sig, _) = sign_with(aux_keys, b"third");
+        assert!(first_sig.links_with(&aux_sig));
+
+        assert!(first_sig.links_any(&[other_sig, second_sig]));
+        let (other_sig, _) = sign_with(generate_rand_scalars(2), b"first");
+        assert!(!first_sig.links_any(&[other_sig]));
+        assert!(!first_sig.links_any(&[]));
+    }
+
+    #[test]
+    fn test_invalid_signature_not_recorded() {
+        let mut store = MemoryKeyImageStore::new();
+        let (signature, ring) = sign_with(generate_rand_scalars(2), b"hello world");
+
+        match store.verify_and_record(&signature, &ring, b"another message") {
+            Err(Error::InvalidSignature(_)) => {}
+            _ => panic!("expected an invalid signature error"),
+        }
+        assert!(store.is_empty());
+    }
+}
@@ -0,0 +1,188 @@
+// Secret keys held in locked memory, behind the mlock feature, on unix.
+//
+// A `SecretKeyBox` keeps the bytes of a secret key on a page of its own, mapped for the box
+// alone and locked with `mlock` so that it is never written to swap. On linux the page is
+// also left out of core dumps. When the box is dropped the page is overwritten with zeroes,
+// unlocked and unmapped, so the key does not outlive the box.
+//
+// A signer is built from boxes with `Member::signer_from_boxes`. The keys are copied out of
+// the boxes into the member for as long as the member exists, as signing works over scalars,
+// so the member should be dropped once it has signed, with the zeroize feature enabled to
+// clear its copy of the keys.
+use crate::error::ClsagError;
+use crate::keys::{PrivateSet, PublicKey, SecretKey};
+use crate::member::Member;
+use curve25519_dalek::scalar::Scalar;
+use std::fmt;
+use std::ptr::{self, NonNull};
+
+// The size of the secret key held in the box
+const SECRET_KEY_SIZE: usize = 32;
+
+// A secret key held on a locked page of memory
+pub struct SecretKeyBox {
+    page: NonNull<u8>,
+    page_size: usize,
+}
+
+// The box owns its page, which is only read through a shared reference
+unsafe impl Send for SecretKeyBox {}
+unsafe impl Sync for SecretKeyBox {}
+
+impl SecretKeyBox {
+    // Moves the secret key into locked memory.
+    // Returns an error if the memory could not be mapped or locked
+    pub fn new(secret_key: SecretKey) -> Result<Self, ClsagError> {
+        Self::from_scalar(&secret_key.0)
+    }
+    // Decodes the 32 byte canonical encoding of a secret key into locked memory.
+    // Returns an error if the bytes are not a canonical scalar,
+    // or if the memory could not be mapped or locked
+    pub fn from_bytes(bytes: &[u8; 32]) -> Result<Self, ClsagError> {
+        let secret_key = SecretKey::from_bytes(bytes)?;
+        Self::new(secret_key)
+    }
+
+    fn from_scalar(scalar: &Scalar) -> Result<Self, ClsagError> {
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+        if page_size < SECRET_KEY_SIZE as libc::c_long {
+            return Err(ClsagError::MemoryLockFailed);
+        }
+        let page_size = page_size as usize;
+
+        let page = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                page_size,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        if page == libc::MAP_FAILED {
+            return Err(ClsagError::MemoryLockFailed);
+        }
+        if unsafe { libc::mlock(page, page_size) } != 0 {
+            unsafe { libc::munmap(page, page_size) };
+            return Err(ClsagError::MemoryLockFailed);
+        }
+        #[cfg(target_os = "linux")]
+        unsafe {
+            libc::madvise(page, page_size, libc::MADV_DONTDUMP);
+        }
+
+        let page = NonNull::new(page as *mut u8).ok_or(ClsagError::MemoryLockFailed)?;
+        unsafe {
+            ptr::copy_nonoverlapping(scalar.as_bytes().as_ptr(), page.as_ptr(), SECRET_KEY_SIZE);
+        }
+        Ok(SecretKeyBox { page, page_size })
+    }
+
+    // Returns a copy of the secret key, which lives outside of the locked memory
+    pub(crate) fn scalar(&self) -> Scalar {
+        let mut bytes = [0u8; SECRET_KEY_SIZE];
+        unsafe {
+            ptr::copy_nonoverlapping(self.page.as_ptr(), bytes.as_mut_ptr(), SECRET_KEY_SIZE);
+        }
+        let scalar = Scalar::from_bits(bytes);
+        unsafe { clear(bytes.as_mut_ptr()) };
+        scalar
+    }
+    // Returns the public key of the secret key
+    pub fn public_key(&self) -> PublicKey {
+        SecretKey(self.scalar()).public_key()
+    }
+}
+
+impl Drop for SecretKeyBox {
+    fn drop(&mut self) {
+        unsafe {
+            clear(self.page.as_ptr());
+            let page = self.page.as_ptr() as *mut libc::c_void;
+            libc::munlock(page, self.page_size);
+            libc::munmap(page, self.page_size);
+        }
+    }
+}
+
+// Overwrites the bytes of a secret key with zeroes,
+// with writes which are not optimised away
+unsafe fn clear(bytes: *mut u8) {
+    for i in 0..SECRET_KEY_SIZE {
+        ptr::write_volatile(bytes.add(i), 0);
+    }
+}
+
+// The key is never printed, so that it cannot end up in logs
+impl fmt::Debug for SecretKeyBox {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SecretKeyBox(..)")
+    }
+}
+
+impl PrivateSet {
+    // Creates a private set from secret keys held in locked memory
+    pub fn from_boxes(boxes: &[SecretKeyBox]) -> Self {
+        PrivateSet::new(boxes.iter().map(SecretKeyBox::scalar).collect())
+    }
+}
+
+impl Member {
+    // Creates the signer from secret keys held in locked memory.
+    // Returns an error if there are no keys
+    pub fn signer_from_boxes(boxes: &[SecretKeyBox]) -> Result<Self, ClsagError> {
+        if boxes.is_empty() {
+            return Err(ClsagError::KeyCountMismatch);
+        }
+
+        Ok(Self::new_signer(
+            boxes.iter().map(SecretKeyBox::scalar).collect(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn test_secret_key_box() {
+        let secret_key = SecretKey::random(&mut rand::thread_rng());
+        let public_key = secret_key.public_key();
+        let bytes = secret_key.to_bytes();
+
+        let boxed = SecretKeyBox::new(secret_key).unwrap();
+        assert_eq!(public_key, boxed.public_key());
+        assert_eq!(bytes, *boxed.scalar().as_bytes());
+        assert_eq!("SecretKeyBox(..)", format!("{:?}", boxed));
+
+        let decoded = SecretKeyBox::from_bytes(&bytes).unwrap();
+        assert_eq!(public_key, decoded.public_key());
+        match SecretKeyBox::from_bytes(&[0xff; 32]) {
+            Err(ClsagError::NonCanonicalScalar) => {}
+            _ => panic!("expected a non canonical scalar error"),
+        }
+    }
+
+    #[test]
+    fn test_sign_with_boxes() {
+        let mut rng = rand::thread_rng();
+        let boxes: Vec<SecretKeyBox> = (0..2)
+            .map(|_| SecretKeyBox::new(SecretKey::random(&mut rng)).unwrap())
+            .collect();
+
+        let mut clsag = generate_clsag_with(3, 2);
+        clsag
+            .add_member(Member::signer_from_boxes(&boxes).unwrap())
+            .unwrap();
+        let (signature, public_keys) = clsag.sign(b"hello world").unwrap();
+        assert!(signature.verify(&public_keys, b"hello world").is_ok());
+
+        match Member::signer_from_boxes(&[]) {
+            Err(ClsagError::KeyCountMismatch) => {}
+            _ => panic!("expected a key count mismatch error"),
+        }
+    }
+}
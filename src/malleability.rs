@@ -0,0 +1,183 @@
+// Checking that a signature cannot be rerandomized, behind the test-utils feature.
+//
+// A signature is malleable if a different signature, or the same signature over a different
+// ring, can be made from it without the private keys and still verifies. A malleable signature
+// lets anyone who sees a transaction change its hash, or replace a member of its ring.
+// `Signature::assert_non_malleable_under` perturbs each field of a valid signature in turn,
+// the challenge, each response and each key image, and each key of its ring, and panics if
+// the verifier accepts any of them.
+//
+// The verifier is given as a closure, so that integrators can check the path they verify
+// with, such as a prepared ring or their own decoding, and not only `Signature::verify`.
+// The fuzz target in `fuzz/fuzz_targets/malleability.rs` runs the check over random rings.
+use crate::constants::BASEPOINT;
+use crate::error::ClsagError;
+use crate::keys::{KeyImage, PublicKey};
+use crate::signature::Signature;
+use curve25519_dalek::scalar::Scalar;
+use std::fmt;
+
+// A field of a signature, or a key of its ring, which is perturbed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Perturbation {
+    Challenge,
+    Response(usize),
+    KeyImage(usize),
+    RingKey { member: usize, key: usize },
+}
+
+impl fmt::Display for Perturbation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Perturbation::Challenge => write!(f, "the challenge"),
+            Perturbation::Response(index) => write!(f, "the response at index {}", index),
+            Perturbation::KeyImage(index) => write!(f, "the key image at index {}", index),
+            Perturbation::RingKey { member, key } => {
+                write!(f, "key {} of the member at index {}", key, member)
+            }
+        }
+    }
+}
+
+impl Signature {
+    // Returns every perturbation of the signature and the ring,
+    // one for each field of the signature and each key of the ring
+    pub fn perturbations(&self, public_keys: &[Vec<PublicKey>]) -> Vec<Perturbation> {
+        let mut perturbations = vec![Perturbation::Challenge];
+        perturbations.extend((0..self.responses.len()).map(Perturbation::Response));
+        perturbations.extend((0..self.key_images.len()).map(Perturbation::KeyImage));
+        for (member, keys) in public_keys.iter().enumerate() {
+            perturbations.extend((0..keys.len()).map(|key| Perturbation::RingKey { member, key }));
+        }
+        perturbations
+    }
+    // Returns a copy of the signature and the ring with the field perturbed. Scalars have one
+    // added to them and points have the basepoint added to them, so the copy always differs
+    pub fn perturbed(
+        &self,
+        public_keys: &[Vec<PublicKey>],
+        perturbation: Perturbation,
+    ) -> (Signature, Vec<Vec<PublicKey>>) {
+        let mut signature = Signature {
+            challenge: self.challenge,
+            responses: self.responses.clone(),
+            key_images: self.key_images.clone(),
+        };
+        let mut public_keys = public_keys.to_vec();
+
+        match perturbation {
+            Perturbation::Challenge => signature.challenge += Scalar::one(),
+            Perturbation::Response(index) => signature.responses[index] += Scalar::one(),
+            Perturbation::KeyImage(index) => {
+                let key_image = &mut signature.key_images[index];
+                // A key image which is not a point is replaced by the basepoint
+                let point = key_image.0.decompress().unwrap_or_default() + BASEPOINT;
+                *key_image = KeyImage(point.compress());
+            }
+            Perturbation::RingKey { member, key } => {
+                let public_key = &mut public_keys[member][key];
+                let point = public_key.decompress().unwrap_or_default() + BASEPOINT;
+                *public_key = PublicKey::from_point(&point);
+            }
+        }
+        (signature, public_keys)
+    }
+    // Returns the first perturbation of the signature and the ring which the verifier accepts,
+    // or None if the verifier rejects all of them
+    pub fn find_malleability<F>(
+        &self,
+        public_keys: &[Vec<PublicKey>],
+        msg: &[u8],
+        verify: F,
+    ) -> Option<Perturbation>
+    where
+        F: Fn(&Signature, &[Vec<PublicKey>], &[u8]) -> Result<(), ClsagError>,
+    {
+        self.perturbations(public_keys)
+            .into_iter()
+            .find(|&perturbation| {
+                let (signature, public_keys) = self.perturbed(public_keys, perturbation);
+                verify(&signature, &public_keys, msg).is_ok()
+            })
+    }
+    // Checks that the verifier accepts the signature, and rejects every perturbation of the
+    // signature and the ring. Panics naming the perturbation if the verifier accepts one,
+    // or if it does not accept the signature itself
+    pub fn assert_non_malleable_under<F>(
+        &self,
+        public_keys: &[Vec<PublicKey>],
+        msg: &[u8],
+        verify: F,
+    ) where
+        F: Fn(&Signature, &[Vec<PublicKey>], &[u8]) -> Result<(), ClsagError>,
+    {
+        if let Err(err) = verify(self, public_keys, msg) {
+            panic!("the signature does not verify: {}", err);
+        }
+        if let Some(perturbation) = self.find_malleability(public_keys, msg, &verify) {
+            panic!("the signature verifies with {} perturbed", perturbation);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ring::PreparedRing;
+    use crate::test_utils::*;
+
+    #[test]
+    fn test_non_malleable() {
+        let num_keys = 2;
+        let msg = b"hello world";
+
+        let mut clsag = generate_clsag_with(3, num_keys);
+        clsag.add_member(generate_signer(num_keys)).unwrap();
+        let (signature, public_keys) = clsag.sign(msg).unwrap();
+
+        assert_eq!(
+            1 + 4 + 2 + 4 * 2,
+            signature.perturbations(&public_keys).len()
+        );
+        signature
+            .assert_non_malleable_under(&public_keys, msg, |sig, keys, msg| sig.verify(keys, msg));
+        signature.assert_non_malleable_under(&public_keys, msg, |sig, keys, msg| {
+            sig.verify_prepared(&PreparedRing::new(keys)?, msg)
+        });
+    }
+
+    #[test]
+    fn test_perturbed() {
+        let mut clsag = generate_clsag_with(1, 1);
+        clsag.add_member(generate_signer(1)).unwrap();
+        let (signature, public_keys) = clsag.sign(b"hello world").unwrap();
+
+        let (perturbed, keys) = signature.perturbed(&public_keys, Perturbation::Response(1));
+        assert_eq!(
+            signature.responses[1] + Scalar::one(),
+            perturbed.responses[1]
+        );
+        assert_eq!(signature.responses[0], perturbed.responses[0]);
+        assert_eq!(public_keys, keys);
+
+        let ring_key = Perturbation::RingKey { member: 1, key: 0 };
+        let (perturbed, keys) = signature.perturbed(&public_keys, ring_key);
+        assert_eq!(signature.challenge, perturbed.challenge);
+        assert_eq!(
+            public_keys[1][0].decompress().unwrap() + BASEPOINT,
+            keys[1][0].decompress().unwrap()
+        );
+        assert_eq!("key 0 of the member at index 1", ring_key.to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "the signature verifies with the challenge perturbed")]
+    fn test_malleable_verifier() {
+        let mut clsag = generate_clsag_with(1, 1);
+        clsag.add_member(generate_signer(1)).unwrap();
+        let (signature, public_keys) = clsag.sign(b"hello world").unwrap();
+
+        // A verifier which accepts everything finds the first perturbation
+        signature.assert_non_malleable_under(&public_keys, b"hello world", |_, _, _| Ok(()));
+    }
+}
@@ -1,25 +1,36 @@
-use crate::constants::BASEPOINT;
-use crate::keys::{PrivateSet, PublicSet};
-use crate::transcript::TranscriptProtocol;
+use crate::error::ClsagError;
+use crate::keys::{KeyImage, PrivateSet, PublicKey, PublicSet, SecretKey};
+use crate::transcript::SigningTranscript;
 use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
 use curve25519_dalek::scalar::Scalar;
 use curve25519_dalek::traits::VartimeMultiscalarMul;
-use merlin::Transcript;
 use rand;
-
-#[derive(Debug)]
-pub enum Error {
-    // Occurs when you try to use a method specific to
-    // a signer as a decoy
-    NotASigner,
-    // Occurs when you try to use a method specific to
-    // a decoy as a signer
-    NotADecoy,
+use subtle::{Choice, ConstantTimeEq};
+
+use crate::keys::zeroize_scalar;
+#[cfg(feature = "precompute")]
+use crate::precompute::{mul_generator, FixedBaseTable};
+#[cfg(feature = "zeroize")]
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+// Where the output of a member can be found on chain, used by decoy selection,
+// by encodings which reference members by their output index and by audits.
+// The metadata is never part of a signature. The commitment of the member is held
+// on the member itself, see `Member::commitment`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MemberMetadata {
+    // The global index of the output among every output on chain
+    pub global_index: Option<u64>,
+
+    // The height of the block the output was created in
+    pub block_height: Option<u64>,
 }
 
 // A member represents a member in the ring
 // This includes the signer of the ring
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Member {
     // The signer is the only member with a set of private keys
     private_set: Option<PrivateSet>,
@@ -28,15 +39,40 @@ pub struct Member {
 
     // This is the hash of the first public key
     // in the public set.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::point"))]
     hashed_pubkey_basepoint: RistrettoPoint,
 
+    // The table of multiples of the hashed public key, which the signer
+    // multiplies by its nonce and private keys, see the precompute module
+    #[cfg(feature = "precompute")]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    hashed_pubkey_table: Option<FixedBaseTable>,
+
     // The signing member will have a nonce.
     // In an sigma protocol, this nonce would signify the commit phase.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::option_scalar"))]
     pub(crate) nonce: Option<Scalar>,
 
     // Each member will have a response value.
     // In an sigma protocol, this would signify the reponse phase.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::option_scalar"))]
     pub(crate) response: Option<Scalar>,
+
+    // A pedersen commitment to the amount held by the member.
+    // When signing, the commitment to zero `commitment - pseudo_out` is
+    // added to the members keys as an auxiliary key.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::option_point"))]
+    commitment: Option<RistrettoPoint>,
+
+    // The signer will have the private key to their commitment to zero,
+    // such that `commitment - pseudo_out = commitment_key * G`.
+    // This is the difference between the blinding factors of the two commitments.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::option_scalar"))]
+    commitment_key: Option<Scalar>,
+
+    // Where the output of the member can be found on chain, if known
+    #[cfg_attr(feature = "serde", serde(default))]
+    metadata: MemberMetadata,
 }
 
 impl Member {
@@ -58,11 +94,32 @@ impl Member {
 
             hashed_pubkey_basepoint: hashed_pubkey,
 
+            #[cfg(feature = "precompute")]
+            hashed_pubkey_table: Some(FixedBaseTable::new(&hashed_pubkey)),
+
             private_set: Some(private_set),
 
             response: None,
+
+            commitment: None,
+
+            commitment_key: None,
+
+            metadata: MemberMetadata::default(),
         }
     }
+    // Creates a member who will be the signer of the ring, holding a commitment
+    // The commitment key opens the commitment to zero, `commitment - pseudo_out`
+    pub fn new_signer_with_commitment(
+        private_keys: Vec<Scalar>,
+        commitment: RistrettoPoint,
+        commitment_key: Scalar,
+    ) -> Self {
+        let mut signer = Self::new_signer(private_keys);
+        signer.commitment = Some(commitment);
+        signer.commitment_key = Some(commitment_key);
+        signer
+    }
     // Creates a member who will be a decoy in the ring
     pub fn new_decoy(public_keys: Vec<RistrettoPoint>) -> Self {
         let response = generate_rand_scalar();
@@ -70,12 +127,78 @@ impl Member {
         Self::new_decoy_with_responses(public_keys, response)
     }
 
+    // Creates a member who will be a decoy in the ring, holding a commitment
+    pub fn new_decoy_with_commitment(
+        public_keys: Vec<RistrettoPoint>,
+        commitment: RistrettoPoint,
+    ) -> Self {
+        let mut decoy = Self::new_decoy(public_keys);
+        decoy.commitment = Some(commitment);
+        decoy
+    }
+
+    // Creates a decoy from the 32 byte compressed encodings of their public keys,
+    // such as the keys of a decoy received from a daemon.
+    // Returns an error if there are no keys or if one of them is not a valid point
+    pub fn from_compressed_bytes(public_keys: &[[u8; 32]]) -> Result<Self, ClsagError> {
+        if public_keys.is_empty() {
+            return Err(ClsagError::KeyCountMismatch);
+        }
+        let public_keys = public_keys
+            .iter()
+            .map(|bytes| {
+                CompressedRistretto(*bytes)
+                    .decompress()
+                    .ok_or(ClsagError::InvalidPoint)
+            })
+            .collect::<Result<Vec<RistrettoPoint>, ClsagError>>()?;
+
+        Ok(Self::new_decoy(public_keys))
+    }
+    // Creates the signer from the 32 byte canonical encodings of their private keys.
+    // Returns an error if there are no keys or if one of them is not reduced modulo the group order
+    pub fn signer_from_scalar_bytes(private_keys: &[[u8; 32]]) -> Result<Self, ClsagError> {
+        if private_keys.is_empty() {
+            return Err(ClsagError::KeyCountMismatch);
+        }
+        let private_keys = private_keys
+            .iter()
+            .map(|bytes| Scalar::from_canonical_bytes(*bytes).ok_or(ClsagError::NonCanonicalScalar))
+            .collect::<Result<Vec<Scalar>, ClsagError>>()?;
+
+        Ok(Self::new_signer(private_keys))
+    }
+    // Creates a decoy from their public keys.
+    // Returns an error if there are no keys
+    pub fn decoy_from_public_keys(public_keys: &[PublicKey]) -> Result<Self, ClsagError> {
+        if public_keys.is_empty() {
+            return Err(ClsagError::KeyCountMismatch);
+        }
+        let public_keys = public_keys
+            .iter()
+            .map(|key| key.decompress().ok_or(ClsagError::InvalidPoint))
+            .collect::<Result<Vec<RistrettoPoint>, ClsagError>>()?;
+
+        Ok(Self::new_decoy(public_keys))
+    }
+    // Creates the signer from their secret keys.
+    // Returns an error if there are no keys
+    pub fn signer_from_secret_keys(secret_keys: &[SecretKey]) -> Result<Self, ClsagError> {
+        if secret_keys.is_empty() {
+            return Err(ClsagError::KeyCountMismatch);
+        }
+
+        Ok(Self::new_signer(
+            secret_keys.iter().map(|key| key.0).collect(),
+        ))
+    }
+
     // Creates a member who will be used for verification in a signature
     pub(crate) fn new_decoy_with_responses(
         public_keys: Vec<RistrettoPoint>,
         response: Scalar,
     ) -> Self {
-        let public_set = PublicSet(public_keys);
+        let public_set = PublicSet::new(public_keys);
         let hashed_pubkey = public_set.hashed_pubkey();
 
         Member {
@@ -85,9 +208,18 @@ impl Member {
 
             hashed_pubkey_basepoint: hashed_pubkey,
 
+            #[cfg(feature = "precompute")]
+            hashed_pubkey_table: None,
+
             private_set: None,
 
             response: Some(response),
+
+            commitment: None,
+
+            commitment_key: None,
+
+            metadata: MemberMetadata::default(),
         }
     }
     // Returns true if the member has a set of private keys
@@ -98,11 +230,136 @@ impl Member {
     pub fn num_keys(&self) -> usize {
         self.public_set.len()
     }
+    // Returns the commitment held by the member, if any
+    pub fn commitment(&self) -> Option<RistrettoPoint> {
+        self.commitment
+    }
+    // Sets where the output of the member can be found on chain.
+    // The metadata is carried through the ring, so it can be read back from `Clsag::members`
+    pub fn with_metadata(mut self, metadata: MemberMetadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+    // Returns where the output of the member can be found on chain
+    pub fn metadata(&self) -> &MemberMetadata {
+        &self.metadata
+    }
+    // Returns the keys the member signs with, which are the members public keys
+    // followed by the commitment to zero, if the member holds a commitment
+    pub(crate) fn ring_keys(&self, pseudo_out: &RistrettoPoint) -> PublicSet {
+        let mut ring_keys = self.public_set.clone();
+        if let Some(commitment) = self.commitment {
+            ring_keys.push(commitment - pseudo_out);
+        }
+        ring_keys
+    }
+    // Returns true unless the member is a signer, whose commitment key
+    // does not open their commitment to zero over the generator
+    pub(crate) fn opens_commitment(
+        &self,
+        pseudo_out: &RistrettoPoint,
+        generator: &RistrettoPoint,
+    ) -> bool {
+        if !self.is_signer() {
+            return true;
+        }
+        match (self.commitment, self.commitment_key) {
+            (Some(commitment), Some(commitment_key)) => {
+                commitment - pseudo_out == commitment_key * generator
+            }
+            (None, _) => true,
+            (Some(_), None) => false,
+        }
+    }
+    // Returns whether the member opens their commitment to zero, as `opens_commitment` does,
+    // for the constant time signing path. The opening is computed for every member,
+    // so the work done does not depend on which member is the signer
+    pub(crate) fn opens_commitment_ct(
+        &self,
+        pseudo_out: &RistrettoPoint,
+        generator: &RistrettoPoint,
+    ) -> Choice {
+        let is_signer = Choice::from(self.is_signer() as u8);
+        let has_commitment = Choice::from(self.commitment.is_some() as u8);
+        let commitment = self.commitment.unwrap_or(*pseudo_out);
+        let zero = Scalar::zero();
+        let (has_key, commitment_key) = select_or(&self.commitment_key, &zero);
+
+        let opens = (commitment - pseudo_out)
+            .compress()
+            .ct_eq(&(commitment_key * generator).compress());
+        !is_signer | !has_commitment | (has_key & opens)
+    }
+    // Returns a copy of the member where the commitment to zero has been added to
+    // the members public keys, and the commitment key to the signers private keys
+    pub(crate) fn with_commitment_to_zero(&self, pseudo_out: &RistrettoPoint) -> Member {
+        let mut member = self.clone();
+        member.public_set = self.ring_keys(pseudo_out);
+        if let (Some(private_set), Some(commitment_key)) =
+            (member.private_set.as_mut(), member.commitment_key)
+        {
+            private_set.0.push(commitment_key);
+        }
+        member.commitment = None;
+        member.commitment_key = None;
+        member
+    }
+    // Returns the private keys if the member is a signer
+    pub(crate) fn private_keys(&self) -> Option<&[Scalar]> {
+        self.private_set.as_ref().map(|set| set.0.as_slice())
+    }
+    // Returns whether the member is the signer, along with its private keys for the signer
+    // and `zero_keys` for a decoy. The keys are picked without branching on which
+    // member is the signer, for the constant time signing path
+    pub(crate) fn private_keys_ct<'a>(
+        &'a self,
+        zero_keys: &'a PrivateSet,
+    ) -> (Choice, &'a [Scalar]) {
+        let (is_signer, private_set) = select_or(&self.private_set, zero_keys);
+        (is_signer, &private_set.0)
+    }
+    // Returns the hash of the first public key in the members public set
+    pub(crate) fn hashed_pubkey(&self) -> RistrettoPoint {
+        self.hashed_pubkey_basepoint
+    }
+    // Computes the public keys of a signer over the generator, along with the hash of their
+    // first key, for rings signed over another generator than the basepoint.
+    // The public keys of a decoy are given, so they are left as they are
+    pub(crate) fn set_generator(&mut self, generator: &RistrettoPoint) {
+        if let Some(private_set) = &self.private_set {
+            self.public_set = private_set.to_public_set_with_generator(generator);
+            self.set_hashed_pubkey(self.public_set.hashed_pubkey());
+        }
+    }
+    // Replaces the hash of the first public key, when it is hashed with another construction
+    pub(crate) fn set_hashed_pubkey(&mut self, hashed_pubkey: RistrettoPoint) {
+        self.hashed_pubkey_basepoint = hashed_pubkey;
+        #[cfg(feature = "precompute")]
+        {
+            if self.is_signer() {
+                self.hashed_pubkey_table = Some(FixedBaseTable::new(&hashed_pubkey));
+            }
+        }
+    }
+    // Multiplies the hash of the first public key by a secret scalar of the signer
+    fn mul_hashed_pubkey(&self, scalar: &Scalar) -> RistrettoPoint {
+        #[cfg(feature = "precompute")]
+        {
+            if let Some(table) = &self.hashed_pubkey_table {
+                return table.mul(scalar);
+            }
+        }
+        scalar * self.hashed_pubkey_basepoint
+    }
     // Computes the key images if the member is a signer
-    pub fn compute_key_images(&self) -> Result<Vec<CompressedRistretto>, Error> {
+    pub fn compute_key_images(&self) -> Result<Vec<KeyImage>, ClsagError> {
         match &self.private_set {
-            Some(priv_set) => Ok(priv_set.compute_key_images(&self.hashed_pubkey_basepoint)),
-            None => Err(Error::NotASigner),
+            Some(priv_set) => Ok(priv_set
+                .0
+                .iter()
+                .map(|priv_key| KeyImage(self.mul_hashed_pubkey(priv_key).compress()))
+                .collect()),
+            None => Err(ClsagError::NotASigner),
         }
     }
 
@@ -112,33 +369,54 @@ impl Member {
     // responses per nonce, which can only be done if the current member possess
     // the discrete log to the public keys corresponding to his position in the ring.
     // returns a challenge scalar or an error if the user is not a signer
-    pub fn compute_challenge_commitment(&self, pubkey_matrix: &[u8]) -> Result<Scalar, Error> {
+    pub fn compute_challenge_commitment<T: SigningTranscript>(
+        &self,
+        transcript: &T,
+        pubkey_matrix: &[u8],
+        key_images: &[KeyImage],
+        msg: &[u8],
+        generator: &RistrettoPoint,
+    ) -> Result<Scalar, ClsagError> {
+        let round = round_transcript(transcript, pubkey_matrix, key_images, msg);
+        self.challenge_commitment(&round, generator)
+    }
+    // Calculates the first challenge as `compute_challenge_commitment` does,
+    // with a transcript returned by `round_transcript`
+    pub(crate) fn challenge_commitment<T: SigningTranscript>(
+        &self,
+        round_transcript: &T,
+        generator: &RistrettoPoint,
+    ) -> Result<Scalar, ClsagError> {
+        let (l, r) = self.nonce_commitment(generator)?;
+
+        // H(pubkey_matrix || key_images || m || L || R)
+        Ok(round_challenge(round_transcript, &l, &r))
+    }
+    // Returns the signers commitment to their nonce, `L = nonce * generator`
+    // and `R = nonce * hashed_pubkey`, or an error if the member is not a signer
+    pub(crate) fn nonce_commitment(
+        &self,
+        generator: &RistrettoPoint,
+    ) -> Result<(RistrettoPoint, RistrettoPoint), ClsagError> {
         if !self.is_signer() {
-            return Err(Error::NotASigner);
+            return Err(ClsagError::NotASigner);
         }
 
         let nonce = match &self.nonce {
             Some(x) => Ok(x),
-            _ => Err(Error::NotASigner),
+            _ => Err(ClsagError::NotASigner),
         }?;
 
-        let mut transcript = Transcript::new(b"clsag");
-
-        // L = nonce * basepoint
-        let l = nonce * BASEPOINT;
-        //let l = nonce * &BASEPOINT;
+        // L = nonce * generator
+        #[cfg(feature = "precompute")]
+        let l = mul_generator(nonce, generator);
+        #[cfg(not(feature = "precompute"))]
+        let l = nonce * generator;
 
         // R = nonce * hashed_pubkey
-        let r = nonce * self.hashed_pubkey_basepoint;
-
-        // Add elements to transcript
-        // H(pubkey_matrix || m || L ||R)
-        // XXX: Note m is omitted and will be added in a later iteration
-        transcript.append_message(b"", pubkey_matrix);
-        transcript.append_point(b"", &l);
-        transcript.append_point(b"", &r);
+        let r = self.mul_hashed_pubkey(nonce);
 
-        Ok(transcript.challenge_scalar(b""))
+        Ok((l, r))
     }
     // This function is for the signer and will use the signers
     // private set to calculate the correct response value
@@ -148,15 +426,15 @@ impl Member {
         &self,
         challenge: Scalar,
         agg_coeff: &[Scalar],
-    ) -> Result<Scalar, Error> {
-        let private_set = self.private_set.as_ref().ok_or(Error::NotASigner)?;
-        let nonce = self.nonce.as_ref().ok_or(Error::NotASigner)?;
+    ) -> Result<Scalar, ClsagError> {
+        let private_set = self.private_set.as_ref().ok_or(ClsagError::NotASigner)?;
+        let nonce = self.nonce.as_ref().ok_or(ClsagError::NotASigner)?;
 
         // t = mu_x * signing_priv_key[0]
         //sum_aux = sum(mu_j * auxilary_priv_keys)
         // response = nonce - challenge(t + sum_aux)
         // let t = mu_x * private_set.0[0];
-        let sum_aux: Scalar = private_set
+        let mut sum_aux: Scalar = private_set
             .0
             .iter()
             .zip(agg_coeff.iter())
@@ -164,82 +442,214 @@ impl Member {
             .sum();
         let response = nonce - challenge * (sum_aux);
 
+        zeroize_scalar(&mut sum_aux);
+
         Ok(response)
     }
     // This function is ran by all members who did not compute the challenge commitment (decoys)
     // Each member that runs this function, will link themselves to the ring using the challenge
     // passed to them by the newest member of the ring.
     // returns a challenge scalar, to be used by the next member who wants to join the ring
-    pub fn compute_decoy_challenge(
+    #[allow(clippy::too_many_arguments)]
+    pub fn compute_decoy_challenge<T: SigningTranscript>(
         &self,
+        transcript: &T,
         challenge: &Scalar,
-        key_images: &[CompressedRistretto],
+        key_images: &[KeyImage],
         agg_coeffs: &[Scalar],
         pubkey_matrix: &[u8],
-    ) -> Result<Scalar, Error> {
+        msg: &[u8],
+        generator: &RistrettoPoint,
+    ) -> Result<Scalar, ClsagError> {
+        let round = round_transcript(transcript, pubkey_matrix, key_images, msg);
+        self.decoy_challenge(&round, challenge, key_images, agg_coeffs, generator)
+    }
+    // Calculates the challenge of the next member as `compute_decoy_challenge` does,
+    // with a transcript returned by `round_transcript`
+    pub(crate) fn decoy_challenge<T: SigningTranscript>(
+        &self,
+        round_transcript: &T,
+        challenge: &Scalar,
+        key_images: &[KeyImage],
+        agg_coeffs: &[Scalar],
+        generator: &RistrettoPoint,
+    ) -> Result<Scalar, ClsagError> {
         if self.private_set.is_some() {
-            return Err(Error::NotADecoy);
+            return Err(ClsagError::NotADecoy);
         }
 
-        let response = self.response.as_ref().ok_or(Error::NotASigner)?;
+        let response = self.response.as_ref().ok_or(ClsagError::NotADecoy)?;
 
-        assert_eq!(self.public_set.len(), key_images.len());
-
-        let challenge = compute_challenge_ring(
-            &self.public_set.to_keys(),
+        let challenge = ring_challenge(
+            round_transcript,
+            self.public_set.keys(),
             challenge,
             key_images,
             response,
             agg_coeffs,
             &self.hashed_pubkey_basepoint,
-            pubkey_matrix,
-        );
+            generator,
+        )?;
 
         Ok(challenge)
     }
 }
+// Returns whether the option holds a value, along with the value or the default, without
+// branching on which it is. The option is read as a slice of zero or one values,
+// and the slice to read from is picked by indexing with its length
+fn select_or<'a, T>(option: &'a Option<T>, default: &'a T) -> (Choice, &'a T) {
+    let values = option.as_slice();
+    let candidates = [std::slice::from_ref(default), values];
+    let is_some = values.len();
+    (Choice::from(is_some as u8), &candidates[is_some][0])
+}
+// Zeroizes the members private keys and nonce
+#[cfg(feature = "zeroize")]
+impl Zeroize for Member {
+    fn zeroize(&mut self) {
+        if let Some(private_set) = self.private_set.as_mut() {
+            private_set.zeroize();
+        }
+        if let Some(nonce) = self.nonce.as_mut() {
+            zeroize_scalar(nonce);
+        }
+        if let Some(commitment_key) = self.commitment_key.as_mut() {
+            zeroize_scalar(commitment_key);
+        }
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for Member {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl ZeroizeOnDrop for Member {}
+
 // A generic function to calculate the challenge for any member in the ring
 // While signing, this function will be used by the decoys
-// When verifying this function will be used by all members
-pub fn compute_challenge_ring(
-    public_keys: &[CompressedRistretto],
+// When verifying this function will be used by all members.
+// Returns an error if the member does not have as many keys as there are key images
+// and aggregation co-efficients, or if a key or key image is not a valid point
+#[allow(clippy::too_many_arguments)]
+pub fn compute_challenge_ring<T: SigningTranscript>(
+    transcript: &T,
+    public_keys: &[PublicKey],
     challenge: &Scalar,
-    key_images: &[CompressedRistretto],
+    key_images: &[KeyImage],
     response: &Scalar,
     agg_coeffs: &[Scalar],
     hashed_pubkey_point: &RistrettoPoint,
     pubkey_matrix: &[u8],
-) -> Scalar {
+    msg: &[u8],
+    generator: &RistrettoPoint,
+) -> Result<Scalar, ClsagError> {
+    ring_challenge(
+        &round_transcript(transcript, pubkey_matrix, key_images, msg),
+        public_keys,
+        challenge,
+        key_images,
+        response,
+        agg_coeffs,
+        hashed_pubkey_point,
+        generator,
+    )
+}
+// Calculates the challenge for the next member as `compute_challenge_ring` does,
+// with a transcript returned by `round_transcript`
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn ring_challenge<T: SigningTranscript>(
+    round_transcript: &T,
+    public_keys: &[PublicKey],
+    challenge: &Scalar,
+    key_images: &[KeyImage],
+    response: &Scalar,
+    agg_coeffs: &[Scalar],
+    hashed_pubkey_point: &RistrettoPoint,
+    generator: &RistrettoPoint,
+) -> Result<Scalar, ClsagError> {
+    if public_keys.len() != key_images.len() || agg_coeffs.len() != key_images.len() {
+        return Err(ClsagError::KeyCountMismatch);
+    }
+    let public_keys = public_keys
+        .iter()
+        .map(|key| key.decompress().ok_or(ClsagError::InvalidPoint))
+        .collect::<Result<Vec<RistrettoPoint>, ClsagError>>()?;
+    let key_images = key_images
+        .iter()
+        .map(|key_image| key_image.decompress().ok_or(ClsagError::InvalidKeyImage))
+        .collect::<Result<Vec<RistrettoPoint>, ClsagError>>()?;
+
     let challenge_agg_coeffs: Vec<Scalar> = agg_coeffs.iter().map(|ac| ac * challenge).collect();
 
     //sum_aux_point = sum(mu_j * auxilary_public_keys)
-    // L =response * G + challenge (sum_aux_point)
-    let sum_aux_point = RistrettoPoint::optional_multiscalar_mul(
-        &challenge_agg_coeffs,
-        public_keys.iter().map(|pt| pt.decompress()),
-    )
-    .unwrap();
-    let l = (response * BASEPOINT) + sum_aux_point;
+    // L =response * generator + challenge (sum_aux_point)
+    let sum_aux_point =
+        RistrettoPoint::vartime_multiscalar_mul(&challenge_agg_coeffs, &public_keys);
+    let l = (response * generator) + sum_aux_point;
 
     // K = response * hashed_pubkey_point
     //sum_aux_images = sum(mu_j * aux_key_images)
     // R = K  + challenge (sum_aux_images)
     let k = response * hashed_pubkey_point;
-    let sum_aux_images = RistrettoPoint::optional_multiscalar_mul(
-        &challenge_agg_coeffs,
-        key_images.iter().map(|pt| pt.decompress()),
-    )
-    .unwrap();
+    let sum_aux_images =
+        RistrettoPoint::vartime_multiscalar_mul(&challenge_agg_coeffs, &key_images);
     let r = k + sum_aux_images;
 
-    let mut transcript = Transcript::new(b"clsag");
-
-    // Add elements to transcript
-    transcript.append_message(b"", pubkey_matrix);
-    transcript.append_point(b"", &l);
-    transcript.append_point(b"", &r);
+    Ok(round_challenge(round_transcript, &l, &r))
+}
+// Appends the public key matrix, the key images and the message to a clone of the transcript,
+// each with its own label. Every round challenge of a signature continues from the returned
+// transcript, so they are hashed once per signature rather than once per member
+pub(crate) fn round_transcript<T: SigningTranscript>(
+    transcript: &T,
+    pubkey_matrix: &[u8],
+    key_images: &[KeyImage],
+    msg: &[u8],
+) -> T {
+    let ring = ring_round_transcript(transcript, pubkey_matrix, key_images.len());
+    signed_round_transcript(&ring, key_images, msg)
+}
+// Appends the public key matrix to a clone of the transcript, which is shared by every
+// signature over the ring. The number of layers is included, so that the same public
+// key bytes split into a different number of keys per member give different challenges
+pub(crate) fn ring_round_transcript<T: SigningTranscript>(
+    transcript: &T,
+    pubkey_matrix: &[u8],
+    num_layers: usize,
+) -> T {
+    let mut transcript = transcript.clone();
+    transcript.append_domain(b"clsag_round");
+    transcript.append_ring(num_layers, pubkey_matrix);
+    transcript
+}
+// Appends the key images and the message of a signature to a clone of a transcript
+// returned by `ring_round_transcript`
+pub(crate) fn signed_round_transcript<T: SigningTranscript>(
+    ring_transcript: &T,
+    key_images: &[KeyImage],
+    msg: &[u8],
+) -> T {
+    let mut transcript = ring_transcript.clone();
+    transcript.append_key_images(key_images);
+    transcript.append_msg(msg);
+    transcript
+}
+// Hashes the L and R points of a member into a transcript returned by `round_transcript`
+// to produce the challenge for the next member in the ring
+pub(crate) fn round_challenge<T: SigningTranscript>(
+    round_transcript: &T,
+    l: &RistrettoPoint,
+    r: &RistrettoPoint,
+) -> Scalar {
+    let mut transcript = round_transcript.clone();
+    transcript.append_point(b"L", l);
+    transcript.append_point(b"R", r);
 
-    transcript.challenge_scalar(b"")
+    transcript.challenge_scalar(b"c")
 }
 
 fn generate_rand_scalar() -> Scalar {
@@ -250,7 +660,12 @@ fn generate_rand_scalar() -> Scalar {
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::tests_helper::generate_rand_scalars;
+    use crate::commitment::commit;
+    use crate::constants::BASEPOINT;
+    use crate::test_utils::{
+        generate_clsag_with, generate_decoy, generate_rand_scalars, generate_signer,
+    };
+    use crate::transcript::default_transcript;
 
     // Simple tests to check that when the members are instantiated
     // We have the correct number of values
@@ -271,7 +686,7 @@ mod test {
 
         // The number of private keys argument we passed in as an argument
         //should equal the length of the private key set
-        match signer.private_set {
+        match &signer.private_set {
             Some(priv_set) => {
                 assert_eq!(priv_set.len(), num_private_keys);
             }
@@ -282,4 +697,181 @@ mod test {
         //should equal the length of the public key set
         assert_eq!(signer.public_set.len(), num_private_keys)
     }
+
+    #[test]
+    fn test_from_bytes() {
+        let scalars = generate_rand_scalars(2);
+        let scalar_bytes: Vec<[u8; 32]> = scalars.iter().map(|scalar| scalar.to_bytes()).collect();
+        let signer = Member::signer_from_scalar_bytes(&scalar_bytes).unwrap();
+        assert!(signer.is_signer());
+        assert_eq!(
+            Member::new_signer(scalars).public_set.points(),
+            signer.public_set.points()
+        );
+
+        let key_bytes: Vec<[u8; 32]> = signer
+            .public_set
+            .to_keys()
+            .iter()
+            .map(|key| key.to_bytes())
+            .collect();
+        let decoy = Member::from_compressed_bytes(&key_bytes).unwrap();
+        assert!(!decoy.is_signer());
+        assert_eq!(signer.public_set.points(), decoy.public_set.points());
+
+        match Member::from_compressed_bytes(&[[0xff; 32]]) {
+            Err(ClsagError::InvalidPoint) => {}
+            _ => panic!("expected an invalid point error"),
+        };
+        match Member::signer_from_scalar_bytes(&[[0xff; 32]]) {
+            Err(ClsagError::NonCanonicalScalar) => {}
+            _ => panic!("expected a non canonical scalar error"),
+        };
+        match Member::from_compressed_bytes(&[]) {
+            Err(ClsagError::KeyCountMismatch) => {}
+            _ => panic!("expected a key count mismatch error"),
+        };
+    }
+
+    #[test]
+    fn test_from_keys() {
+        let mut rng = rand::thread_rng();
+        let secret_keys: Vec<SecretKey> = (0..2).map(|_| SecretKey::random(&mut rng)).collect();
+        let public_keys: Vec<PublicKey> = secret_keys.iter().map(|key| key.public_key()).collect();
+
+        let signer = Member::signer_from_secret_keys(&secret_keys).unwrap();
+        assert!(signer.is_signer());
+        assert_eq!(public_keys, signer.public_set.to_keys());
+
+        let decoy = Member::decoy_from_public_keys(&public_keys).unwrap();
+        assert!(!decoy.is_signer());
+        assert_eq!(public_keys, decoy.public_set.to_keys());
+
+        match Member::signer_from_secret_keys(&[]) {
+            Err(ClsagError::KeyCountMismatch) => {}
+            _ => panic!("expected a key count mismatch error"),
+        };
+    }
+
+    #[test]
+    fn test_commitment_to_zero() {
+        let num_private_keys = 2;
+        let blinding = Scalar::from(5u64);
+        let pseudo_blinding = Scalar::from(3u64);
+        let commitment = commit(10, &blinding);
+        let pseudo_out = commit(10, &pseudo_blinding);
+
+        let signer = Member::new_signer_with_commitment(
+            generate_rand_scalars(num_private_keys),
+            commitment,
+            blinding - pseudo_blinding,
+        );
+        assert!(signer.opens_commitment(&pseudo_out, &BASEPOINT));
+        // A pseudo output for a different amount cannot be opened
+        assert!(!signer.opens_commitment(&commit(11, &pseudo_blinding), &BASEPOINT));
+
+        let expanded = signer.with_commitment_to_zero(&pseudo_out);
+        assert_eq!(expanded.num_keys(), num_private_keys + 1);
+        assert_eq!(expanded.private_keys().unwrap().len(), num_private_keys + 1);
+        assert_eq!(
+            expanded.public_set.points()[num_private_keys],
+            commitment - pseudo_out
+        );
+        assert!(expanded.commitment().is_none());
+
+        // The expanded private keys should still match the expanded public keys
+        let private_set = PrivateSet::new(expanded.private_keys().unwrap().to_vec());
+        assert_eq!(
+            private_set.to_public_set().points(),
+            expanded.public_set.points()
+        );
+    }
+
+    #[test]
+    fn test_round_challenge_hashes_the_ring() {
+        // A round challenge continued from the hashed ring, key images and message
+        // is the same as hashing them for every round
+        let transcript = default_transcript();
+        let pubkey_matrix = [7u8; 64];
+        let key_images = vec![KeyImage(BASEPOINT.compress()); 2];
+        let (l, r) = (BASEPOINT, Scalar::from(3u64) * BASEPOINT);
+
+        let mut expected = transcript.clone();
+        expected.append_domain(b"clsag_round");
+        expected.append_ring(2, &pubkey_matrix);
+        expected.append_key_images(&key_images);
+        expected.append_msg(b"msg");
+        expected.append_point(b"L", &l);
+        expected.append_point(b"R", &r);
+
+        let round = round_transcript(&transcript, &pubkey_matrix, &key_images, b"msg");
+        assert_eq!(
+            expected.challenge_scalar(b"c"),
+            round_challenge(&round, &l, &r)
+        );
+
+        // The number of layers, the key images and the message each change the challenge
+        let one_layer = round_transcript(&transcript, &pubkey_matrix, &key_images[1..], b"msg");
+        let other_key_images = vec![KeyImage(r.compress()); 2];
+        let other_images = round_transcript(&transcript, &pubkey_matrix, &other_key_images, b"msg");
+        let other_msg = round_transcript(&transcript, &pubkey_matrix, &key_images, b"other");
+        for other in &[one_layer, other_images, other_msg] {
+            assert_ne!(
+                round_challenge(other, &l, &r),
+                round_challenge(&round, &l, &r)
+            );
+        }
+    }
+
+    #[cfg(feature = "zeroize")]
+    #[test]
+    fn test_zeroize() {
+        let mut signer = Member::new_signer(generate_rand_scalars(3));
+        signer.zeroize();
+
+        assert_eq!(signer.nonce, Some(Scalar::zero()));
+        assert!(signer
+            .private_keys()
+            .unwrap()
+            .iter()
+            .all(|x| *x == Scalar::zero()));
+    }
+
+    #[test]
+    fn test_metadata_carried_through_ring() {
+        let metadata = MemberMetadata {
+            global_index: Some(1234),
+            block_height: Some(56),
+        };
+        let mut clsag = generate_clsag_with(3, 1);
+        clsag
+            .add_member(generate_decoy(1).with_metadata(metadata))
+            .unwrap();
+        clsag.add_member(generate_signer(1)).unwrap();
+
+        assert_eq!(&metadata, clsag.members()[3].metadata());
+        assert_eq!(&MemberMetadata::default(), clsag.members()[4].metadata());
+        let shuffled = clsag.shuffled(&mut rand::thread_rng());
+        assert!(shuffled
+            .members()
+            .iter()
+            .any(|member| member.metadata() == &metadata));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let signer = Member::new_signer(generate_rand_scalars(3));
+
+        let json = serde_json::to_string(&signer).unwrap();
+        let decoded: Member = serde_json::from_str(&json).unwrap();
+
+        assert!(decoded.is_signer());
+        assert_eq!(signer.nonce, decoded.nonce);
+        assert_eq!(signer.public_set.points(), decoded.public_set.points());
+        assert_eq!(
+            signer.compute_key_images().unwrap(),
+            decoded.compute_key_images().unwrap()
+        );
+    }
 }
@@ -0,0 +1,188 @@
+// Hooks for exporting metrics about signing and verification.
+//
+// A `Metrics` implementation installed on a `Clsag` with `set_metrics` is told how long each
+// signature took to make, and one installed on a `PreparedRing` is told how long each
+// signature verified over the ring took and why it failed, if it failed. The crate does not
+// depend on any metrics library, the implementation forwards the values to the counters
+// and histograms of the library the integrator exports metrics with, such as Prometheus.
+// Every method has a default implementation which does nothing.
+//
+// `Counters` is an implementation which counts the operations and adds up their durations
+// with atomics, for integrators who only want the totals.
+use crate::error::ClsagError;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// Metrics receives the duration of every signature made or verified.
+// It is shared between the threads signing and verifying, so it must be Send and Sync
+pub trait Metrics: Send + Sync {
+    // Called when a signature has been made
+    fn on_sign(&self, _duration: Duration) {}
+    // Called when a signature has been verified successfully
+    fn on_verify(&self, _duration: Duration) {}
+    // Called when a signature has failed to verify, with the reason it failed
+    fn on_verify_failure(&self, _duration: Duration, _error: ClsagError) {}
+}
+
+// Counts the signatures made, verified and failed, along with the total time spent on each
+#[derive(Debug, Default)]
+pub struct Counters {
+    signs: AtomicU64,
+    verifies: AtomicU64,
+    failures: AtomicU64,
+    sign_nanos: AtomicU64,
+    verify_nanos: AtomicU64,
+}
+
+impl Counters {
+    // Returns the number of signatures made
+    pub fn signs(&self) -> u64 {
+        self.signs.load(Ordering::Relaxed)
+    }
+    // Returns the number of signatures which verified
+    pub fn verifies(&self) -> u64 {
+        self.verifies.load(Ordering::Relaxed)
+    }
+    // Returns the number of signatures which failed to verify
+    pub fn failures(&self) -> u64 {
+        self.failures.load(Ordering::Relaxed)
+    }
+    // Returns the total time spent making signatures
+    pub fn sign_time(&self) -> Duration {
+        Duration::from_nanos(self.sign_nanos.load(Ordering::Relaxed))
+    }
+    // Returns the total time spent verifying signatures, whether they verified or not
+    pub fn verify_time(&self) -> Duration {
+        Duration::from_nanos(self.verify_nanos.load(Ordering::Relaxed))
+    }
+}
+
+fn add_duration(total: &AtomicU64, duration: Duration) {
+    total.fetch_add(duration.as_nanos() as u64, Ordering::Relaxed);
+}
+
+impl Metrics for Counters {
+    fn on_sign(&self, duration: Duration) {
+        self.signs.fetch_add(1, Ordering::Relaxed);
+        add_duration(&self.sign_nanos, duration);
+    }
+    fn on_verify(&self, duration: Duration) {
+        self.verifies.fetch_add(1, Ordering::Relaxed);
+        add_duration(&self.verify_nanos, duration);
+    }
+    fn on_verify_failure(&self, duration: Duration, _error: ClsagError) {
+        self.failures.fetch_add(1, Ordering::Relaxed);
+        add_duration(&self.verify_nanos, duration);
+    }
+}
+
+// The metrics installed on a ring, if there are any.
+// Operations are only timed when metrics are installed
+#[derive(Clone, Default)]
+pub(crate) struct MetricsHook(Option<Arc<dyn Metrics>>);
+
+impl MetricsHook {
+    pub(crate) fn new(metrics: Arc<dyn Metrics>) -> Self {
+        MetricsHook(Some(metrics))
+    }
+    // Runs the signing operation, reporting its duration if it succeeds
+    pub(crate) fn observe_sign<T, F: FnOnce() -> Result<T, ClsagError>>(
+        &self,
+        sign: F,
+    ) -> Result<T, ClsagError> {
+        let metrics = match &self.0 {
+            Some(metrics) => metrics,
+            None => return sign(),
+        };
+
+        let start = Instant::now();
+        let result = sign();
+        if result.is_ok() {
+            metrics.on_sign(start.elapsed());
+        }
+        result
+    }
+    // Runs the verification, reporting its duration and whether it failed
+    pub(crate) fn observe_verify<F: FnOnce() -> Result<(), ClsagError>>(
+        &self,
+        verify: F,
+    ) -> Result<(), ClsagError> {
+        let metrics = match &self.0 {
+            Some(metrics) => metrics,
+            None => return verify(),
+        };
+
+        let start = Instant::now();
+        let result = verify();
+        match result {
+            Ok(()) => metrics.on_verify(start.elapsed()),
+            Err(err) => metrics.on_verify_failure(start.elapsed(), err),
+        }
+        result
+    }
+}
+
+impl fmt::Debug for MetricsHook {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Some(_) => write!(f, "MetricsHook(..)"),
+            None => write!(f, "MetricsHook(None)"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ring::PreparedRing;
+    use crate::test_utils::*;
+
+    #[test]
+    fn test_counters() {
+        let counters = Arc::new(Counters::default());
+
+        let mut clsag = generate_clsag_with(3, 2);
+        clsag.add_member(generate_signer(2)).unwrap();
+        clsag.set_metrics(counters.clone());
+        let (signature, public_keys) = clsag.sign(b"hello world").unwrap();
+        clsag.sign_ct(b"hello world").unwrap();
+
+        let mut ring = PreparedRing::new(&public_keys).unwrap();
+        ring.set_metrics(counters.clone());
+        assert!(signature.verify_prepared(&ring, b"hello world").is_ok());
+        assert!(ring.verify_many(&[signature], b"another message").is_err());
+
+        assert_eq!(2, counters.signs());
+        assert_eq!(1, counters.verifies());
+        assert_eq!(1, counters.failures());
+        assert!(counters.sign_time() > Duration::from_nanos(0));
+        assert!(counters.verify_time() > Duration::from_nanos(0));
+    }
+
+    #[test]
+    fn test_failures_are_reported() {
+        #[derive(Default)]
+        struct LastFailure(std::sync::Mutex<Option<ClsagError>>);
+        impl Metrics for LastFailure {
+            fn on_verify_failure(&self, _duration: Duration, error: ClsagError) {
+                *self.0.lock().unwrap() = Some(error);
+            }
+        }
+
+        let mut clsag = generate_clsag_with(2, 1);
+        clsag.add_member(generate_signer(1)).unwrap();
+        let (signature, mut public_keys) = clsag.sign(b"hello world").unwrap();
+        public_keys.swap(0, 1);
+
+        let metrics = Arc::new(LastFailure::default());
+        let mut ring = PreparedRing::new(&public_keys).unwrap();
+        ring.set_metrics(metrics.clone());
+        assert!(signature.verify_prepared(&ring, b"hello world").is_err());
+        assert_eq!(
+            Some(ClsagError::ChallengeMismatch),
+            *metrics.0.lock().unwrap()
+        );
+    }
+}
@@ -0,0 +1,356 @@
+// Multilayered linkable spontaneous anonymous group signatures, the scheme CLSAG replaced.
+//
+// Like CLSAG, an MLSAG signature is made over a ring of members which each have the same number
+// of keys, and the signer knows the secret key of every key of one member. Unlike CLSAG, the keys
+// are not aggregated, so the signature has one response for every key of every member, instead
+// of one for every member. Every key of the signer has its own key image, computed over the hash
+// of that key, where CLSAG computes every key image over the hash of the first key.
+//
+// For the member at index `i`, with the challenge `c_i`, each layer `j` computes
+// `L_j = r_ij * G + c_i * P_ij` and `R_j = r_ij * H(P_ij) + c_i * I_j`, and the challenge of the
+// next member is computed from all of the `L_j` and `R_j`.
+//
+// These signatures use the hashes and transcripts of this crate, so they verify signatures made
+// by this module, and cannot verify MLSAG signatures made by Monero, which are over ed25519
+// and Keccak and leave the last layer without a key image.
+use crate::constants::BASEPOINT;
+use crate::error::ClsagError;
+use crate::hash::hash_pubkey;
+use crate::keys::{KeyImage, PublicKey, SecretKey};
+use crate::ring::PreparedRing;
+use crate::transcript::{default_transcript, SigningTranscript};
+use curve25519_dalek::ristretto::RistrettoPoint;
+use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::traits::VartimeMultiscalarMul;
+use merlin::Transcript;
+use rand::{CryptoRng, RngCore};
+
+// An MLSAG signature over a ring of members
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MlsagSignature {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::scalar"))]
+    challenge: Scalar,
+    // The responses of every member in ring order, with the responses of each member in layer order
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::scalars"))]
+    responses: Vec<Scalar>,
+    key_images: Vec<KeyImage>,
+}
+
+impl MlsagSignature {
+    // Signs the message with the secret keys, whose public keys must be the keys of a member
+    // of the ring, in layer order.
+    // Returns an error if the ring has fewer than two members, if the members do not all have
+    // one key for each secret key, or if no member has the public keys of the secret keys
+    pub fn sign<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        public_keys: &[Vec<PublicKey>],
+        secret_keys: &[SecretKey],
+        msg: &[u8],
+    ) -> Result<MlsagSignature, ClsagError> {
+        let ring = PreparedRing::new(public_keys)?;
+        if ring.len() < 2 {
+            return Err(ClsagError::RingTooSmall);
+        }
+        if ring.num_layers() != secret_keys.len() {
+            return Err(ClsagError::KeyCountMismatch);
+        }
+        let signer_keys: Vec<PublicKey> = secret_keys.iter().map(SecretKey::public_key).collect();
+        let signer_index = public_keys
+            .iter()
+            .position(|keys| *keys == signer_keys)
+            .ok_or(ClsagError::NotASigner)?;
+
+        let hashed_pubkeys = hash_pubkeys(&ring);
+        let key_image_points: Vec<RistrettoPoint> = secret_keys
+            .iter()
+            .zip(&hashed_pubkeys[signer_index])
+            .map(|(secret_key, hashed_pubkey)| secret_key.0 * hashed_pubkey)
+            .collect();
+        let key_images: Vec<KeyImage> = key_image_points
+            .iter()
+            .map(|key_image| KeyImage(key_image.compress()))
+            .collect();
+        let transcript = transcript(&ring, &key_images, msg);
+
+        let n = ring.len();
+        let num_layers = ring.num_layers();
+        let nonces: Vec<Scalar> = (0..num_layers).map(|_| Scalar::random(rng)).collect();
+        let mut responses: Vec<Scalar> = (0..n * num_layers).map(|_| Scalar::random(rng)).collect();
+        let mut challenges = vec![Scalar::zero(); n];
+
+        // The challenge of the member after the signer is computed from the nonces,
+        // and each challenge after it from the responses of the member before
+        let commitments: Vec<(RistrettoPoint, RistrettoPoint)> = nonces
+            .iter()
+            .zip(&hashed_pubkeys[signer_index])
+            .map(|(nonce, hashed_pubkey)| (nonce * BASEPOINT, nonce * hashed_pubkey))
+            .collect();
+        let mut challenge = round_challenge(&transcript, &commitments);
+        for offset in 1..n {
+            let i = (signer_index + offset) % n;
+            challenges[i] = challenge;
+            let commitments = member_commitments(
+                &responses[i * num_layers..(i + 1) * num_layers],
+                &challenge,
+                &ring.points()[i],
+                &hashed_pubkeys[i],
+                &key_image_points,
+            );
+            challenge = round_challenge(&transcript, &commitments);
+        }
+        challenges[signer_index] = challenge;
+        for (layer, (nonce, secret_key)) in nonces.iter().zip(secret_keys).enumerate() {
+            responses[signer_index * num_layers + layer] = nonce - challenge * secret_key.0;
+        }
+
+        Ok(MlsagSignature {
+            challenge: challenges[0],
+            responses,
+            key_images,
+        })
+    }
+    // Verifies the signature on the message over the ring.
+    // Returns an error if the ring is not the size of the ring the signature was made over,
+    // if the members do not have one key for each key image, or if the signature does not verify
+    pub fn verify(&self, public_keys: &[Vec<PublicKey>], msg: &[u8]) -> Result<(), ClsagError> {
+        let ring = PreparedRing::new(public_keys)?;
+        let num_layers = ring.num_layers();
+        if num_layers != self.key_images.len() {
+            return Err(ClsagError::KeyCountMismatch);
+        }
+        if self.responses.len() != ring.len() * num_layers {
+            return Err(ClsagError::RingSizeMismatch);
+        }
+        let key_image_points: Vec<RistrettoPoint> = self
+            .key_images
+            .iter()
+            .map(KeyImage::check)
+            .collect::<Result<_, ClsagError>>()?;
+
+        let hashed_pubkeys = hash_pubkeys(&ring);
+        let transcript = transcript(&ring, &self.key_images, msg);
+
+        let mut challenge = self.challenge;
+        for ((responses, points), hashed_pubkeys) in self
+            .responses
+            .chunks(num_layers)
+            .zip(ring.points())
+            .zip(&hashed_pubkeys)
+        {
+            let commitments = member_commitments(
+                responses,
+                &challenge,
+                points,
+                hashed_pubkeys,
+                &key_image_points,
+            );
+            challenge = round_challenge(&transcript, &commitments);
+        }
+
+        if challenge != self.challenge {
+            return Err(ClsagError::ChallengeMismatch);
+        }
+        Ok(())
+    }
+    // Returns the key images of the signer, one for each layer
+    pub fn key_images(&self) -> &[KeyImage] {
+        &self.key_images
+    }
+    // Returns true if both signatures were made by the same signer, as their first key images
+    // are the same. The signatures are not verified, so this should only be called on
+    // signatures which have already been verified
+    pub fn links_with(&self, other: &MlsagSignature) -> bool {
+        self.key_images.first() == other.key_images.first()
+    }
+    // Returns the number of members in the ring the signature was made over
+    pub fn len(&self) -> usize {
+        match self.key_images.len() {
+            0 => 0,
+            num_layers => self.responses.len() / num_layers,
+        }
+    }
+    // Returns true if the signature has no responses, which a valid signature never does
+    pub fn is_empty(&self) -> bool {
+        self.responses.is_empty()
+    }
+}
+
+// Returns the hash of every key of every member, which each key image is computed over
+fn hash_pubkeys(ring: &PreparedRing) -> Vec<Vec<RistrettoPoint>> {
+    ring.public_keys()
+        .iter()
+        .map(|keys| keys.iter().map(|key| hash_pubkey(key.as_bytes())).collect())
+        .collect()
+}
+
+// Returns the transcript every challenge of a signature on the message is computed from
+fn transcript(ring: &PreparedRing, key_images: &[KeyImage], msg: &[u8]) -> Transcript {
+    let mut transcript = default_transcript();
+    transcript.append_domain(b"mlsag_round");
+    transcript.append_ring(ring.num_layers(), ring.pubkey_matrix());
+    transcript.append_key_images(key_images);
+    transcript.append_msg(msg);
+    transcript
+}
+
+// Returns `L_j = r_j * G + c * P_j` and `R_j = r_j * H(P_j) + c * I_j` for every layer of a member
+fn member_commitments(
+    responses: &[Scalar],
+    challenge: &Scalar,
+    points: &[RistrettoPoint],
+    hashed_pubkeys: &[RistrettoPoint],
+    key_images: &[RistrettoPoint],
+) -> Vec<(RistrettoPoint, RistrettoPoint)> {
+    responses
+        .iter()
+        .zip(points)
+        .zip(hashed_pubkeys)
+        .zip(key_images)
+        .map(|(((response, point), hashed_pubkey), key_image)| {
+            let l = RistrettoPoint::vartime_multiscalar_mul(
+                &[*response, *challenge],
+                &[BASEPOINT, *point],
+            );
+            let r = RistrettoPoint::vartime_multiscalar_mul(
+                &[*response, *challenge],
+                &[*hashed_pubkey, *key_image],
+            );
+            (l, r)
+        })
+        .collect()
+}
+
+fn round_challenge(
+    transcript: &Transcript,
+    commitments: &[(RistrettoPoint, RistrettoPoint)],
+) -> Scalar {
+    let mut transcript = transcript.clone();
+    for (l, r) in commitments {
+        transcript.append_point(b"L", l);
+        transcript.append_point(b"R", r);
+    }
+    transcript.challenge_scalar(b"challenge")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::*;
+    use curve25519_dalek::traits::Identity;
+
+    fn generate_ring(
+        num_decoys: usize,
+        secret_keys: &[SecretKey],
+        signer_index: usize,
+    ) -> Vec<Vec<PublicKey>> {
+        let mut ring: Vec<Vec<PublicKey>> = (0..num_decoys)
+            .map(|_| generate_rand_public_keys(secret_keys.len()))
+            .collect();
+        ring.insert(
+            signer_index,
+            secret_keys.iter().map(SecretKey::public_key).collect(),
+        );
+        ring
+    }
+
+    #[test]
+    fn test_sign_verify() {
+        let mut rng = rand::thread_rng();
+        let msg = b"hello world";
+
+        for num_layers in 1..4 {
+            let secret_keys: Vec<SecretKey> = (0..num_layers)
+                .map(|_| SecretKey::random(&mut rng))
+                .collect();
+            for signer_index in [0, 3, 6] {
+                let ring = generate_ring(6, &secret_keys, signer_index);
+                let signature = MlsagSignature::sign(&mut rng, &ring, &secret_keys, msg).unwrap();
+                assert_eq!(ring.len(), signature.len());
+                assert_eq!(num_layers, signature.key_images().len());
+                assert!(signature.verify(&ring, msg).is_ok());
+                assert!(signature.verify(&ring, b"another message").is_err());
+
+                // The signature does not verify over another ring
+                let mut other_ring = ring.clone();
+                other_ring.swap(1, 2);
+                assert!(signature.verify(&other_ring, msg).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn test_key_images_link() {
+        let mut rng = rand::thread_rng();
+        let secret_keys: Vec<SecretKey> = (0..2).map(|_| SecretKey::random(&mut rng)).collect();
+
+        // Each key image is computed over the hash of its own key
+        let ring = generate_ring(4, &secret_keys, 2);
+        let signature = MlsagSignature::sign(&mut rng, &ring, &secret_keys, b"first").unwrap();
+        for (secret_key, key_image) in secret_keys.iter().zip(signature.key_images()) {
+            let public_key = secret_key.public_key();
+            let expected = KeyImage((secret_key.0 * hash_pubkey(public_key.as_bytes())).compress());
+            assert_eq!(expected, *key_image);
+        }
+
+        // The same signer in another ring is linked, another signer is not
+        let other_ring = generate_ring(4, &secret_keys, 0);
+        let other = MlsagSignature::sign(&mut rng, &other_ring, &secret_keys, b"second").unwrap();
+        assert!(signature.links_with(&other));
+        let other_keys: Vec<SecretKey> = (0..2).map(|_| SecretKey::random(&mut rng)).collect();
+        let other_ring = generate_ring(4, &other_keys, 1);
+        let other = MlsagSignature::sign(&mut rng, &other_ring, &other_keys, b"first").unwrap();
+        assert!(!signature.links_with(&other));
+    }
+
+    #[test]
+    fn test_sign_rejects_bad_rings() {
+        let mut rng = rand::thread_rng();
+        let secret_keys: Vec<SecretKey> = (0..2).map(|_| SecretKey::random(&mut rng)).collect();
+
+        let ring = generate_ring(0, &secret_keys, 0);
+        match MlsagSignature::sign(&mut rng, &ring, &secret_keys, b"msg") {
+            Err(ClsagError::RingTooSmall) => {}
+            _ => panic!("expected a ring too small error"),
+        }
+
+        let ring = generate_ring(3, &secret_keys, 1);
+        match MlsagSignature::sign(&mut rng, &ring, &secret_keys[..1], b"msg") {
+            Err(ClsagError::KeyCountMismatch) => {}
+            _ => panic!("expected a key count mismatch error"),
+        }
+
+        let outsider: Vec<SecretKey> = (0..2).map(|_| SecretKey::random(&mut rng)).collect();
+        match MlsagSignature::sign(&mut rng, &ring, &outsider, b"msg") {
+            Err(ClsagError::NotASigner) => {}
+            _ => panic!("expected a not a signer error"),
+        }
+    }
+
+    #[test]
+    fn test_verify_rejects_bad_signatures() {
+        let mut rng = rand::thread_rng();
+        let secret_keys: Vec<SecretKey> = (0..2).map(|_| SecretKey::random(&mut rng)).collect();
+        let ring = generate_ring(4, &secret_keys, 2);
+        let signature = MlsagSignature::sign(&mut rng, &ring, &secret_keys, b"msg").unwrap();
+
+        match signature.verify(&ring[1..], b"msg") {
+            Err(ClsagError::RingSizeMismatch) => {}
+            _ => panic!("expected a ring size mismatch error"),
+        }
+
+        let mut identity = signature.clone();
+        identity.key_images[1] = KeyImage(RistrettoPoint::identity().compress());
+        match identity.verify(&ring, b"msg") {
+            Err(ClsagError::IdentityKeyImage) => {}
+            _ => panic!("expected an identity key image error"),
+        }
+
+        let mut tampered = signature;
+        tampered.responses[3] += Scalar::one();
+        match tampered.verify(&ring, b"msg") {
+            Err(ClsagError::ChallengeMismatch) => {}
+            _ => panic!("expected a challenge mismatch error"),
+        }
+    }
+}
@@ -0,0 +1,417 @@
+// Mnemonic phrases for seeds, as BIP39 writes them, behind the mnemonic feature.
+//
+// A `Mnemonic` writes the 32 byte seed of `PrivateSet::from_seed` as 24 words, which users can
+// copy down as a backup and type back in to restore their keys. As in BIP39, the SHA-256 hash
+// of the seed is appended to it, and the first 264 bits are split into 24 indices of 11 bits,
+// each the position of a word in a list of 2048 words. The last 8 bits are a checksum, which
+// catches all but about 1 in 256 mistyped or swapped words when the phrase is parsed.
+//
+// The BIP39 wordlists of every `Language` are bundled, from the `bip39` crate, and other lists
+// can be loaded with `Wordlist::from_text`. Phrases are compared word for word, so a phrase
+// must use the same unicode normalization as its wordlist.
+//
+// A phrase derives keys in one of two ways. `Mnemonic::to_private_set` derives them from the
+// 32 bytes the words encode. `Mnemonic::to_bip39_private_set` first stretches the phrase and a
+// passphrase into the 64 byte seed of BIP39 with PBKDF2, which is the seed every BIP39 wallet
+// computes from the same words, and derives the keys from it as `PrivateSet::from_seed` does.
+use crate::error::ClsagError;
+use crate::keys::{wipe_bytes, PrivateSet};
+use rand::{CryptoRng, RngCore};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt;
+
+// The languages of the BIP39 wordlists
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Language {
+    English,
+    SimplifiedChinese,
+    TraditionalChinese,
+    Czech,
+    French,
+    Italian,
+    Japanese,
+    Korean,
+    Portuguese,
+    Spanish,
+}
+
+impl Language {
+    // Every language with a bundled wordlist
+    pub const ALL: [Language; 10] = [
+        Language::English,
+        Language::SimplifiedChinese,
+        Language::TraditionalChinese,
+        Language::Czech,
+        Language::French,
+        Language::Italian,
+        Language::Japanese,
+        Language::Korean,
+        Language::Portuguese,
+        Language::Spanish,
+    ];
+
+    fn to_bip39(self) -> bip39::Language {
+        match self {
+            Language::English => bip39::Language::English,
+            Language::SimplifiedChinese => bip39::Language::SimplifiedChinese,
+            Language::TraditionalChinese => bip39::Language::TraditionalChinese,
+            Language::Czech => bip39::Language::Czech,
+            Language::French => bip39::Language::French,
+            Language::Italian => bip39::Language::Italian,
+            Language::Japanese => bip39::Language::Japanese,
+            Language::Korean => bip39::Language::Korean,
+            Language::Portuguese => bip39::Language::Portuguese,
+            Language::Spanish => bip39::Language::Spanish,
+        }
+    }
+}
+
+// The number of words in a wordlist
+pub const WORDLIST_SIZE: usize = 2048;
+// The number of words in a phrase
+pub const PHRASE_LENGTH: usize = 24;
+
+// Each word encodes 11 bits, the index of the word in the wordlist
+const BITS_PER_WORD: usize = 11;
+
+// The 2048 words of a language, which phrases are written in
+#[derive(Debug, Clone)]
+pub struct Wordlist {
+    words: Vec<String>,
+    indices: HashMap<String, u16>,
+}
+
+impl Wordlist {
+    // Returns the BIP39 wordlist of the language
+    pub fn bip39(language: Language) -> Wordlist {
+        Self::new(language.to_bip39().word_list().iter().cloned())
+            .expect("the BIP39 wordlists have 2048 distinct words")
+    }
+    // Reads a wordlist with one word on each line, as the BIP39 wordlists are written.
+    // Returns `InvalidWordlist` if there are not exactly 2048 words, or if a word appears twice
+    pub fn from_text(text: &str) -> Result<Wordlist, ClsagError> {
+        Self::new(text.lines().map(str::trim).filter(|word| !word.is_empty()))
+    }
+    // Creates a wordlist from its words, in order.
+    // Returns `InvalidWordlist` if there are not exactly 2048 words, or if a word appears twice
+    pub fn new<'a, I: IntoIterator<Item = &'a str>>(words: I) -> Result<Wordlist, ClsagError> {
+        let words: Vec<String> = words.into_iter().map(str::to_string).collect();
+        if words.len() != WORDLIST_SIZE {
+            return Err(ClsagError::InvalidWordlist);
+        }
+
+        let mut indices = HashMap::with_capacity(WORDLIST_SIZE);
+        for (index, word) in words.iter().enumerate() {
+            if word.is_empty() || indices.insert(word.clone(), index as u16).is_some() {
+                return Err(ClsagError::InvalidWordlist);
+            }
+        }
+        Ok(Wordlist { words, indices })
+    }
+    // Returns the word at the index
+    pub fn word(&self, index: u16) -> &str {
+        &self.words[index as usize]
+    }
+    // Returns the index of the word, or None if it is not in the wordlist
+    pub fn index(&self, word: &str) -> Option<u16> {
+        self.indices.get(word).cloned()
+    }
+}
+
+// A seed written as a phrase. The seed is overwritten with zeroes when the mnemonic is dropped
+#[derive(Clone)]
+pub struct Mnemonic {
+    seed: [u8; 32],
+}
+
+impl Mnemonic {
+    // Generates a mnemonic for a new seed from the given rng
+    pub fn generate<R: RngCore + CryptoRng>(rng: &mut R) -> Mnemonic {
+        let mut seed = [0u8; 32];
+        rng.fill_bytes(&mut seed);
+        Mnemonic { seed }
+    }
+    // Returns the mnemonic of an existing seed
+    pub fn from_seed(seed: &[u8; 32]) -> Mnemonic {
+        Mnemonic { seed: *seed }
+    }
+    // Parses a phrase in the language of the wordlist, with the words separated by whitespace.
+    // Returns `InvalidMnemonic` if the phrase does not have 24 words or has a word which is
+    // not in the wordlist, and `MnemonicChecksumMismatch` if the checksum does not match
+    pub fn from_phrase(phrase: &str, wordlist: &Wordlist) -> Result<Mnemonic, ClsagError> {
+        let indices = phrase
+            .split_whitespace()
+            .map(|word| wordlist.index(word).ok_or(ClsagError::InvalidMnemonic))
+            .collect::<Result<Vec<u16>, ClsagError>>()?;
+        if indices.len() != PHRASE_LENGTH {
+            return Err(ClsagError::InvalidMnemonic);
+        }
+
+        // The 24 indices of 11 bits are the 32 bytes of the seed followed by the checksum
+        let mut bytes = [0u8; 33];
+        for (position, index) in indices.iter().enumerate() {
+            for bit in 0..BITS_PER_WORD {
+                if index >> (BITS_PER_WORD - 1 - bit) & 1 == 1 {
+                    let offset = position * BITS_PER_WORD + bit;
+                    bytes[offset / 8] |= 1 << (7 - offset % 8);
+                }
+            }
+        }
+
+        let mut mnemonic = Mnemonic { seed: [0u8; 32] };
+        mnemonic.seed.copy_from_slice(&bytes[..32]);
+        let checksum_matches = mnemonic.checksum() == bytes[32];
+        wipe_bytes(&mut bytes);
+        if !checksum_matches {
+            return Err(ClsagError::MnemonicChecksumMismatch);
+        }
+        Ok(mnemonic)
+    }
+    // Writes the seed as 24 words of the wordlist, separated by spaces
+    pub fn to_phrase(&self, wordlist: &Wordlist) -> String {
+        self.indices()
+            .iter()
+            .map(|&index| wordlist.word(index))
+            .collect::<Vec<&str>>()
+            .join(" ")
+    }
+    // Returns the seed the phrase encodes
+    pub fn seed(&self) -> &[u8; 32] {
+        &self.seed
+    }
+    // Derives the set of private keys at the path from the seed, see `PrivateSet::from_seed`
+    pub fn to_private_set(&self, path: &[u32], num_keys: usize) -> PrivateSet {
+        PrivateSet::from_seed(&self.seed, path, num_keys)
+    }
+    // Stretches the phrase in the language and the passphrase into the 64 byte BIP39 seed,
+    // with 2048 rounds of PBKDF2-HMAC-SHA512 over the NFKD normalized phrase, salted with
+    // "mnemonic" followed by the NFKD normalized passphrase
+    pub fn to_bip39_seed(&self, language: Language, passphrase: &str) -> [u8; 64] {
+        bip39::Mnemonic::from_entropy_in(language.to_bip39(), &self.seed)
+            .expect("32 bytes is a valid BIP39 entropy length")
+            .to_seed(passphrase)
+    }
+    // Derives the set of private keys at the path from the BIP39 seed of the phrase in the
+    // language and the passphrase, see `PrivateSet::from_bip39_seed`
+    pub fn to_bip39_private_set(
+        &self,
+        language: Language,
+        passphrase: &str,
+        path: &[u32],
+        num_keys: usize,
+    ) -> PrivateSet {
+        let mut seed = self.to_bip39_seed(language, passphrase);
+        let private_set = PrivateSet::from_bip39_seed(&seed, path, num_keys);
+        wipe_bytes(&mut seed);
+        private_set
+    }
+
+    // The first byte of the SHA-256 hash of the seed
+    fn checksum(&self) -> u8 {
+        Sha256::digest(&self.seed)[0]
+    }
+    // Returns the index in the wordlist of each word of the phrase
+    fn indices(&self) -> [u16; PHRASE_LENGTH] {
+        let mut bytes = [0u8; 33];
+        bytes[..32].copy_from_slice(&self.seed);
+        bytes[32] = self.checksum();
+
+        let mut indices = [0u16; PHRASE_LENGTH];
+        for (position, index) in indices.iter_mut().enumerate() {
+            for bit in 0..BITS_PER_WORD {
+                let offset = position * BITS_PER_WORD + bit;
+                let value = bytes[offset / 8] >> (7 - offset % 8) & 1;
+                *index = *index << 1 | u16::from(value);
+            }
+        }
+        wipe_bytes(&mut bytes);
+        indices
+    }
+}
+
+impl PartialEq for Mnemonic {
+    fn eq(&self, other: &Mnemonic) -> bool {
+        use subtle::ConstantTimeEq;
+        self.seed.ct_eq(&other.seed).into()
+    }
+}
+
+impl Eq for Mnemonic {}
+
+// The seed is never printed, as anyone who sees it can derive every key of the wallet
+impl fmt::Debug for Mnemonic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Mnemonic(..)")
+    }
+}
+
+impl Drop for Mnemonic {
+    fn drop(&mut self) {
+        wipe_bytes(&mut self.seed);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::*;
+
+    // A wordlist of made up words, so that the encoding can be checked word index by word index
+    fn test_wordlist() -> Wordlist {
+        let text: String = (0..WORDLIST_SIZE)
+            .map(|index| format!("word{}\n", index))
+            .collect();
+        Wordlist::from_text(&text).unwrap()
+    }
+
+    // Returns the index of each word of a phrase written with the test wordlist
+    fn word_indices(phrase: &str) -> Vec<u16> {
+        let wordlist = test_wordlist();
+        phrase
+            .split(' ')
+            .map(|word| wordlist.index(word).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_phrase_roundtrip() {
+        let wordlist = test_wordlist();
+        let mnemonic = Mnemonic::generate(&mut seeded_rng(1));
+        let phrase = mnemonic.to_phrase(&wordlist);
+        assert_eq!(PHRASE_LENGTH, phrase.split(' ').count());
+
+        let parsed = Mnemonic::from_phrase(&phrase, &wordlist).unwrap();
+        assert_eq!(mnemonic, parsed);
+        assert_eq!(mnemonic.seed(), parsed.seed());
+        assert_eq!(
+            PrivateSet::from_seed(mnemonic.seed(), &[0], 2).0,
+            parsed.to_private_set(&[0], 2).0
+        );
+
+        // Any whitespace separates the words
+        let spaced = phrase.replace(' ', "\n  ");
+        assert_eq!(mnemonic, Mnemonic::from_phrase(&spaced, &wordlist).unwrap());
+        assert_eq!("Mnemonic(..)", format!("{:?}", mnemonic));
+    }
+
+    #[test]
+    fn test_bip39_indices() {
+        // The words of the BIP39 vectors for the seeds of all zeroes and all ones,
+        // which are "abandon" 23 times then "art", and "zoo" 23 times then "vote"
+        let zeroes = Mnemonic::from_seed(&[0; 32]).to_phrase(&test_wordlist());
+        let mut expected = vec![0; 23];
+        expected.push(102);
+        assert_eq!(expected, word_indices(&zeroes));
+
+        let ones = Mnemonic::from_seed(&[0xff; 32]).to_phrase(&test_wordlist());
+        let mut expected = vec![2047; 23];
+        expected.push(1967);
+        assert_eq!(expected, word_indices(&ones));
+    }
+
+    #[test]
+    fn test_bip39_vectors() {
+        // The 256 bit vectors of the BIP39 reference implementation, with the passphrase "TREZOR"
+        let vectors = [
+            (
+                [0x00; 32],
+                "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art",
+                "bda85446c68413707090a52022edd26a1c9462295029f2e60cd7c4f2bbd3097170af7a4d73245cafa9c3cca8d561a7c3de6f5d4a10be8ed2a5e608d68f92fcc8",
+            ),
+            (
+                [0x7f; 32],
+                "legal winner thank year wave sausage worth useful legal winner thank year wave sausage worth useful legal winner thank year wave sausage worth title",
+                "bc09fca1804f7e69da93c2f2028eb238c227f2e9dda30cd63699232578480a4021b146ad717fbb7e451ce9eb835f43620bf5c514db0f8add49f5d121449d3e87",
+            ),
+            (
+                [0x80; 32],
+                "letter advice cage absurd amount doctor acoustic avoid letter advice cage absurd amount doctor acoustic avoid letter advice cage absurd amount doctor acoustic bless",
+                "c0c519bd0e91a2ed54357d9d1ebef6f5af218a153624cf4f2da911a0ed8f7a09e2ef61af0aca007096df430022f7a2b6fb91661a9589097069720d015e4e982f",
+            ),
+        ];
+        let english = Wordlist::bip39(Language::English);
+        for (seed, phrase, bip39_seed) in vectors.iter() {
+            let mnemonic = Mnemonic::from_seed(seed);
+            assert_eq!(*phrase, mnemonic.to_phrase(&english));
+            assert_eq!(mnemonic, Mnemonic::from_phrase(phrase, &english).unwrap());
+
+            let stretched = mnemonic.to_bip39_seed(Language::English, "TREZOR");
+            assert_eq!(*bip39_seed, hex::encode(&stretched[..]));
+            assert_eq!(
+                PrivateSet::from_bip39_seed(&stretched, &[0], 2).0,
+                mnemonic
+                    .to_bip39_private_set(Language::English, "TREZOR", &[0], 2)
+                    .0
+            );
+        }
+
+        // The passphrase and the language change the BIP39 seed, but not the words
+        let mnemonic = Mnemonic::from_seed(&[0x7f; 32]);
+        assert_ne!(
+            mnemonic.to_bip39_seed(Language::English, "TREZOR")[..],
+            mnemonic.to_bip39_seed(Language::English, "")[..]
+        );
+        assert_ne!(
+            mnemonic.to_bip39_seed(Language::English, "")[..],
+            mnemonic.to_bip39_seed(Language::French, "")[..]
+        );
+    }
+
+    #[test]
+    fn test_bundled_wordlists() {
+        for language in Language::ALL.iter() {
+            let wordlist = Wordlist::bip39(*language);
+            let mnemonic = Mnemonic::generate(&mut seeded_rng(3));
+            let phrase = mnemonic.to_phrase(&wordlist);
+            assert_eq!(mnemonic, Mnemonic::from_phrase(&phrase, &wordlist).unwrap());
+        }
+        let english = Wordlist::bip39(Language::English);
+        assert_eq!("abandon", english.word(0));
+        assert_eq!(Some(2047), english.index("zoo"));
+    }
+
+    #[test]
+    fn test_invalid_phrase() {
+        let wordlist = test_wordlist();
+        let phrase = Mnemonic::generate(&mut seeded_rng(2)).to_phrase(&wordlist);
+        let mut words: Vec<&str> = phrase.split(' ').collect();
+
+        let unknown_word = phrase.replacen("word", "sword", 1);
+        let too_short = words[1..].join(" ");
+        let too_long = format!("{} {}", phrase, words[0]);
+        for phrase in &[unknown_word, too_short, too_long, String::new()] {
+            match Mnemonic::from_phrase(phrase, &wordlist) {
+                Err(ClsagError::InvalidMnemonic) => {}
+                _ => panic!("expected the phrase to be rejected"),
+            }
+        }
+
+        // Swapping these two words changes the seed, which no longer matches the checksum
+        let (first, second) = (1..PHRASE_LENGTH)
+            .map(|index| (0, index))
+            .find(|&(first, second)| words[first] != words[second])
+            .unwrap();
+        words.swap(first, second);
+        match Mnemonic::from_phrase(&words.join(" "), &wordlist) {
+            Err(ClsagError::MnemonicChecksumMismatch) => {}
+            _ => panic!("expected the checksum to not match"),
+        }
+    }
+
+    #[test]
+    fn test_invalid_wordlist() {
+        let text: String = (0..WORDLIST_SIZE - 1)
+            .map(|index| format!("word{}\n", index))
+            .collect();
+        let duplicate = format!("{}word0\n", text);
+        for text in &[text, duplicate] {
+            match Wordlist::from_text(text) {
+                Err(ClsagError::InvalidWordlist) => {}
+                _ => panic!("expected the wordlist to be rejected"),
+            }
+        }
+        assert_eq!("word5", test_wordlist().word(5));
+        assert_eq!(None, test_wordlist().index("word2048"));
+    }
+}
@@ -0,0 +1,195 @@
+// Bindings for Swift and Kotlin through UniFFI, behind the uniffi feature.
+//
+// The functions are exported with the UniFFI proc macros, and the scaffolding is set up at the
+// root of the crate, so no UDL file or build script is needed. The Swift and Kotlin sources are
+// generated from the library built with the feature, using the `uniffi-bindgen` binary:
+//
+//   cargo rustc --release --features uniffi --crate-type cdylib
+//   cargo run --features uniffi-cli --bin uniffi-bindgen -- generate \
+//       --library target/release/libclsag.so --language swift --out-dir out
+//
+// As with the wasm bindings, keys and signatures cross the boundary as bytes: private keys are
+// 32 byte canonical scalars, public keys and key images are 32 byte compressed ristretto points
+// and signatures use the `Signature::to_bytes` encoding. A list of keys is the concatenation of
+// the encoding of each key. Errors are thrown as `ClsagError`, with a case for each variant.
+use crate::clsag::{Clsag, PublicKeys};
+use crate::error::ClsagError;
+use crate::keys::{PublicKey, SecretKey};
+use crate::member::Member;
+use crate::signature::Signature;
+
+const KEY_SIZE: usize = 32;
+
+// A signature together with the public keys of the ring it verifies against
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct SignedRing {
+    // The encoded signature
+    pub signature: Vec<u8>,
+    // The public keys of every member in the order the ring was signed in
+    pub public_keys: Vec<u8>,
+}
+
+// Splits the bytes into 32 byte keys, refusing empty or partial input
+fn split_keys(bytes: &[u8]) -> Result<Vec<[u8; 32]>, ClsagError> {
+    if bytes.is_empty() || !bytes.len().is_multiple_of(KEY_SIZE) {
+        return Err(ClsagError::DecodeError);
+    }
+    Ok(bytes
+        .chunks_exact(KEY_SIZE)
+        .map(|chunk| {
+            let mut key = [0u8; 32];
+            key.copy_from_slice(chunk);
+            key
+        })
+        .collect())
+}
+
+fn decode_secret_keys(bytes: &[u8]) -> Result<Vec<SecretKey>, ClsagError> {
+    split_keys(bytes)?
+        .iter()
+        .map(SecretKey::from_bytes)
+        .collect()
+}
+
+// Generates `num_keys` random private keys
+#[uniffi::export]
+pub fn generate_private_keys(num_keys: u32) -> Vec<u8> {
+    let mut rng = rand::thread_rng();
+    (0..num_keys)
+        .flat_map(|_| SecretKey::random(&mut rng).to_bytes().to_vec())
+        .collect()
+}
+
+// Returns the public keys corresponding to the given private keys
+#[uniffi::export]
+pub fn public_keys_from_private(private_keys: Vec<u8>) -> Result<Vec<u8>, ClsagError> {
+    Ok(decode_secret_keys(&private_keys)?
+        .iter()
+        .flat_map(|key| key.public_key().to_bytes().to_vec())
+        .collect())
+}
+
+// Returns the key images of a signer with the given private keys.
+// The first key image is the one signatures are linked by
+#[uniffi::export]
+pub fn key_images(private_keys: Vec<u8>) -> Result<Vec<u8>, ClsagError> {
+    let signer = Member::signer_from_secret_keys(&decode_secret_keys(&private_keys)?)?;
+    Ok(signer
+        .compute_key_images()?
+        .iter()
+        .flat_map(|key_image| key_image.as_bytes().to_vec())
+        .collect())
+}
+
+// Signs the message with the private keys of the signer, hidden in a ring with the decoys.
+// The decoy keys are the keys of each decoy in turn, each with as many keys as the signer
+#[uniffi::export]
+pub fn sign(
+    private_keys: Vec<u8>,
+    decoy_keys: Vec<u8>,
+    msg: Vec<u8>,
+) -> Result<SignedRing, ClsagError> {
+    let secret_keys = decode_secret_keys(&private_keys)?;
+    let decoy_keys = split_keys(&decoy_keys)?;
+    if !decoy_keys.len().is_multiple_of(secret_keys.len()) {
+        return Err(ClsagError::KeyCountMismatch);
+    }
+
+    let mut clsag = Clsag::new();
+    for member_keys in decoy_keys.chunks_exact(secret_keys.len()) {
+        clsag.add_member(Member::from_compressed_bytes(member_keys)?)?;
+    }
+    clsag.add_member(Member::signer_from_secret_keys(&secret_keys)?)?;
+
+    let (signature, public_keys) = clsag.sign(&msg)?;
+    Ok(SignedRing {
+        signature: signature.to_bytes(),
+        public_keys: public_keys
+            .iter()
+            .flatten()
+            .flat_map(|key| key.to_bytes().to_vec())
+            .collect(),
+    })
+}
+
+// Verifies the signature over the ring of public keys, given as the keys of each member in
+// ring order. Returns false if the signature does not verify, and an error if the signature
+// cannot be decoded or the number of keys does not match the signature
+#[uniffi::export]
+pub fn verify(signature: Vec<u8>, public_keys: Vec<u8>, msg: Vec<u8>) -> Result<bool, ClsagError> {
+    let signature = Signature::from_bytes(&signature)?;
+    let num_keys = signature.num_layers();
+    let keys = split_keys(&public_keys)?;
+    if !keys.len().is_multiple_of(num_keys) {
+        return Err(ClsagError::KeyCountMismatch);
+    }
+
+    // A key which is not a valid point cannot be part of a ring the signature verifies over
+    let ring = match keys
+        .chunks(num_keys)
+        .map(|member_keys| member_keys.iter().map(PublicKey::from_bytes).collect())
+        .collect::<Result<PublicKeys, ClsagError>>()
+    {
+        Ok(ring) => ring,
+        Err(_) => return Ok(false),
+    };
+
+    Ok(signature.optimised_verify(&ring, &msg).is_ok())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const NUM_DECOYS: u32 = 10;
+
+    #[test]
+    fn test_mobile_sign_and_verify() {
+        let msg = b"hello world".to_vec();
+        let private_keys = generate_private_keys(2);
+        let decoy_keys = public_keys_from_private(generate_private_keys(2 * NUM_DECOYS)).unwrap();
+
+        let signed = sign(private_keys.clone(), decoy_keys, msg.clone()).unwrap();
+        assert_eq!(
+            (NUM_DECOYS as usize + 1) * 2 * KEY_SIZE,
+            signed.public_keys.len()
+        );
+        assert!(verify(signed.signature.clone(), signed.public_keys.clone(), msg).unwrap());
+        assert!(!verify(
+            signed.signature.clone(),
+            signed.public_keys.clone(),
+            b"another message".to_vec()
+        )
+        .unwrap());
+
+        // The linking key image of the signature is the first key image of the signer
+        let signature = Signature::from_bytes(&signed.signature).unwrap();
+        let key_images = key_images(private_keys).unwrap();
+        assert_eq!(signature.key_images[0].as_bytes(), &key_images[..KEY_SIZE]);
+    }
+
+    #[test]
+    fn test_mobile_errors() {
+        let private_keys = generate_private_keys(1);
+        let decoy_keys = public_keys_from_private(generate_private_keys(NUM_DECOYS)).unwrap();
+
+        match public_keys_from_private(vec![0xff; 32]) {
+            Err(ClsagError::NonCanonicalScalar) => {}
+            _ => panic!("expected a non canonical scalar error"),
+        }
+        match sign(private_keys.clone(), decoy_keys[1..].to_vec(), vec![]) {
+            Err(ClsagError::DecodeError) => {}
+            _ => panic!("expected a decode error"),
+        }
+        let odd_keys = public_keys_from_private(generate_private_keys(NUM_DECOYS + 1)).unwrap();
+        match sign(generate_private_keys(2), odd_keys, vec![]) {
+            Err(ClsagError::KeyCountMismatch) => {}
+            _ => panic!("expected a key count mismatch error"),
+        }
+        let signed = sign(private_keys, decoy_keys, vec![]).unwrap();
+        match verify(signed.signature[1..].to_vec(), signed.public_keys, vec![]) {
+            Err(ClsagError::DecodeError) => {}
+            _ => panic!("expected a decode error"),
+        }
+    }
+}
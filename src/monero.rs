@@ -0,0 +1,579 @@
+// CLSAG as Monero signs its transaction inputs, behind the monero feature.
+//
+// The rest of the crate works over ristretto255 with merlin transcripts, which Monero cannot
+// verify. This module follows `CLSAG_Gen` and `verRctCLSAGSimple` of Monero's rctSigs.cpp
+// instead, so signatures made here verify in monerod and signatures from Monero verify here:
+//
+// - Points are on Ed25519, and the ring holds the one-time key `P_i` and the amount
+//   commitment `C_i` of each output, which is offset by the pseudo output commitment.
+// - Keys are hashed to the curve with Monero's `hash_to_ec`, the Keccak-256 hash of the key
+//   mapped with `ge_fromfe_frombytes_vartime` and multiplied by the cofactor.
+// - Challenges are `hash_to_scalar`, the Keccak-256 hash of the concatenated 32 byte values
+//   reduced modulo the group order, with the domain tags `CLSAG_agg_0`, `CLSAG_agg_1` and
+//   `CLSAG_round` padded with zeroes to 32 bytes.
+// - The signature holds the responses, the first challenge and the commitment key image `D`
+//   multiplied by the inverse of eight. The key image and the pseudo output are held by the
+//   transaction input, so they are passed to `verify` rather than kept in the signature.
+//
+// The message is the 32 byte prehash of the transaction, which is computed by the wallet.
+use crate::error::ClsagError;
+use curve25519_dalek_4::constants::ED25519_BASEPOINT_TABLE;
+use curve25519_dalek_4::edwards::{CompressedEdwardsY, EdwardsPoint};
+use curve25519_dalek_4::scalar::Scalar;
+use curve25519_dalek_4::traits::{IsIdentity, VartimeMultiscalarMul};
+use rand::{CryptoRng, RngCore};
+use sha3::{Digest, Keccak256};
+
+const HASH_KEY_CLSAG_AGG_0: &[u8] = b"CLSAG_agg_0";
+const HASH_KEY_CLSAG_AGG_1: &[u8] = b"CLSAG_agg_1";
+const HASH_KEY_CLSAG_ROUND: &[u8] = b"CLSAG_round";
+
+// The inverse of eight modulo the group order, which `D` is multiplied by in the signature
+const INV_EIGHT: [u8; 32] = [
+    121, 47, 220, 226, 41, 229, 6, 97, 208, 218, 28, 125, 179, 157, 211, 7, 0, 0, 0, 0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0, 0, 0, 6,
+];
+
+// A member of the ring, as Monero's `ctkey`: the one-time public key of an output
+// and the commitment to its amount
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CtKey {
+    pub dest: CompressedEdwardsY,
+    pub mask: CompressedEdwardsY,
+}
+
+// A CLSAG signature as serialized in a Monero transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RingSignature {
+    // The response of each member of the ring
+    pub s: Vec<Scalar>,
+    // The challenge of the first member of the ring
+    pub c1: Scalar,
+    // The commitment key image, multiplied by the inverse of eight
+    pub d: CompressedEdwardsY,
+}
+
+// Hashes the public key to the point its key image is made over, with Monero's `hash_to_ec`
+pub fn hash_to_ec(key: &CompressedEdwardsY) -> EdwardsPoint {
+    let hashed = monero_ed25519::Point::biased_hash(key.to_bytes());
+    CompressedEdwardsY(hashed.compress().to_bytes())
+        .decompress()
+        .expect("hash_to_ec returns a valid point")
+}
+
+// Hashes the bytes to a scalar, with Monero's `hash_to_scalar`
+pub fn hash_to_scalar(bytes: &[u8]) -> Scalar {
+    Scalar::from_bytes_mod_order(Keccak256::digest(bytes).into())
+}
+
+// Returns the key image of the secret key, `x * hash_to_ec(x * G)`
+pub fn key_image(secret_key: &Scalar) -> EdwardsPoint {
+    let public_key = (ED25519_BASEPOINT_TABLE * secret_key).compress();
+    hash_to_ec(&public_key) * secret_key
+}
+
+impl RingSignature {
+    // Signs the 32 byte prehash of a transaction as the member of the ring at the index,
+    // returning the signature and the key image of the signer. `secret_key` is the secret
+    // key of the output and `commitment_mask` is the mask of its commitment minus the mask
+    // of the pseudo output, so that `C_l - pseudo_out = commitment_mask * G`.
+    // Returns an error if the ring is empty, if the index is not in the ring, or if the
+    // secret key or commitment mask do not open the member at the index
+    pub fn sign<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        msg: &[u8; 32],
+        ring: &[CtKey],
+        pseudo_out: &CompressedEdwardsY,
+        signer_index: usize,
+        secret_key: &Scalar,
+        commitment_mask: &Scalar,
+    ) -> Result<(Self, CompressedEdwardsY), ClsagError> {
+        let points = RingPoints::decompress(ring, pseudo_out)?;
+        let signer = ring.get(signer_index).ok_or(ClsagError::NotASigner)?;
+        if (ED25519_BASEPOINT_TABLE * secret_key).compress() != signer.dest {
+            return Err(ClsagError::NotASigner);
+        }
+        if ED25519_BASEPOINT_TABLE * commitment_mask != points.offset_commitments[signer_index] {
+            return Err(ClsagError::BadCommitmentKey);
+        }
+
+        let hashed_pubkey = hash_to_ec(&signer.dest);
+        let key_image = hashed_pubkey * secret_key;
+        let d = hashed_pubkey * commitment_mask;
+        let d_inv_eight = (d * Scalar::from_bytes_mod_order(INV_EIGHT)).compress();
+
+        let transcript = Transcript::new(ring, pseudo_out, &key_image.compress(), &d_inv_eight);
+        let (mu_p, mu_c) = transcript.aggregation_coefficients();
+
+        let nonce = random_scalar(rng);
+        let mut challenge = transcript.round_challenge(
+            msg,
+            &(ED25519_BASEPOINT_TABLE * &nonce),
+            &(hashed_pubkey * nonce),
+        );
+
+        let mut s = vec![Scalar::ZERO; ring.len()];
+        let mut c1 = challenge;
+        for offset in 1..ring.len() {
+            let i = (signer_index + offset) % ring.len();
+            if i == 0 {
+                c1 = challenge;
+            }
+            s[i] = random_scalar(rng);
+            let (l, r) = points.round_points(i, &s[i], &challenge, mu_p, mu_c, &key_image, &d);
+            challenge = transcript.round_challenge(msg, &l, &r);
+        }
+        if signer_index == 0 {
+            c1 = challenge;
+        }
+        s[signer_index] = nonce - challenge * (mu_p * secret_key + mu_c * commitment_mask);
+
+        let signature = RingSignature {
+            s,
+            c1,
+            d: d_inv_eight,
+        };
+        Ok((signature, key_image.compress()))
+    }
+
+    // Verifies the signature over the 32 byte prehash of a transaction, for the input
+    // with the key image and the pseudo output commitment
+    pub fn verify(
+        &self,
+        msg: &[u8; 32],
+        ring: &[CtKey],
+        key_image: &CompressedEdwardsY,
+        pseudo_out: &CompressedEdwardsY,
+    ) -> Result<(), ClsagError> {
+        let points = RingPoints::decompress(ring, pseudo_out)?;
+        if ring.len() != self.s.len() {
+            return Err(ClsagError::RingSizeMismatch);
+        }
+        let image = key_image
+            .decompress()
+            .filter(EdwardsPoint::is_torsion_free)
+            .ok_or(ClsagError::InvalidKeyImage)?;
+        if image.is_identity() {
+            return Err(ClsagError::IdentityKeyImage);
+        }
+        let d = self
+            .d
+            .decompress()
+            .ok_or(ClsagError::InvalidPoint)?
+            .mul_by_cofactor();
+        if d.is_identity() {
+            return Err(ClsagError::IdentityKeyImage);
+        }
+
+        let transcript = Transcript::new(ring, pseudo_out, key_image, &self.d);
+        let (mu_p, mu_c) = transcript.aggregation_coefficients();
+
+        let mut challenge = self.c1;
+        for (i, s) in self.s.iter().enumerate() {
+            let (l, r) = points.round_points(i, s, &challenge, mu_p, mu_c, &image, &d);
+            challenge = transcript.round_challenge(msg, &l, &r);
+        }
+        if challenge != self.c1 {
+            return Err(ClsagError::ChallengeMismatch);
+        }
+
+        Ok(())
+    }
+
+    // Returns the signature as serialized in a transaction, the responses followed by `c1` and `D`
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(32 * (self.s.len() + 2));
+        for s in &self.s {
+            bytes.extend_from_slice(s.as_bytes());
+        }
+        bytes.extend_from_slice(self.c1.as_bytes());
+        bytes.extend_from_slice(self.d.as_bytes());
+        bytes
+    }
+
+    // Decodes a signature over a ring with the given number of members.
+    // Returns an error if the length does not match, or if a scalar is not canonical
+    pub fn from_bytes(bytes: &[u8], ring_size: usize) -> Result<Self, ClsagError> {
+        if ring_size == 0 || bytes.len() != 32 * (ring_size + 2) {
+            return Err(ClsagError::DecodeError);
+        }
+        let mut scalars = bytes[..32 * (ring_size + 1)]
+            .chunks_exact(32)
+            .map(|chunk| {
+                let mut scalar = [0u8; 32];
+                scalar.copy_from_slice(chunk);
+                Option::from(Scalar::from_canonical_bytes(scalar))
+                    .ok_or(ClsagError::NonCanonicalScalar)
+            })
+            .collect::<Result<Vec<Scalar>, ClsagError>>()?;
+        let c1 = scalars.pop().expect("the signature has a challenge");
+
+        let mut d = [0u8; 32];
+        d.copy_from_slice(&bytes[32 * (ring_size + 1)..]);
+        Ok(RingSignature {
+            s: scalars,
+            c1,
+            d: CompressedEdwardsY(d),
+        })
+    }
+}
+
+// The decompressed keys of the ring, and the commitments offset by the pseudo output
+struct RingPoints {
+    keys: Vec<EdwardsPoint>,
+    hashed_keys: Vec<EdwardsPoint>,
+    offset_commitments: Vec<EdwardsPoint>,
+}
+
+impl RingPoints {
+    fn decompress(ring: &[CtKey], pseudo_out: &CompressedEdwardsY) -> Result<Self, ClsagError> {
+        if ring.is_empty() {
+            return Err(ClsagError::EmptyRing);
+        }
+        let pseudo_out = pseudo_out.decompress().ok_or(ClsagError::InvalidPoint)?;
+        let mut points = RingPoints {
+            keys: Vec::with_capacity(ring.len()),
+            hashed_keys: Vec::with_capacity(ring.len()),
+            offset_commitments: Vec::with_capacity(ring.len()),
+        };
+        for member in ring {
+            let key = member.dest.decompress().ok_or(ClsagError::InvalidPoint)?;
+            let commitment = member.mask.decompress().ok_or(ClsagError::InvalidPoint)?;
+            points.keys.push(key);
+            points.hashed_keys.push(hash_to_ec(&member.dest));
+            points.offset_commitments.push(commitment - pseudo_out);
+        }
+        Ok(points)
+    }
+
+    // Computes the L and R points of the member from its response and the challenge:
+    // L = s * G + c * mu_P * P_i + c * mu_C * (C_i - pseudo_out)
+    // R = s * hash_to_ec(P_i) + c * mu_P * I + c * mu_C * D
+    #[allow(clippy::too_many_arguments)]
+    fn round_points(
+        &self,
+        index: usize,
+        response: &Scalar,
+        challenge: &Scalar,
+        mu_p: Scalar,
+        mu_c: Scalar,
+        key_image: &EdwardsPoint,
+        d: &EdwardsPoint,
+    ) -> (EdwardsPoint, EdwardsPoint) {
+        let c_p = challenge * mu_p;
+        let c_c = challenge * mu_c;
+        let l = EdwardsPoint::vartime_multiscalar_mul(
+            &[c_p, c_c],
+            &[self.keys[index], self.offset_commitments[index]],
+        ) + ED25519_BASEPOINT_TABLE * response;
+        let r = EdwardsPoint::vartime_multiscalar_mul(
+            &[*response, c_p, c_c],
+            &[self.hashed_keys[index], *key_image, *d],
+        );
+        (l, r)
+    }
+}
+
+// The values every challenge of a signature is hashed from, in the order Monero hashes them
+struct Transcript {
+    // The keys and then the commitments of the ring
+    ring: Vec<u8>,
+    key_image: [u8; 32],
+    d_inv_eight: [u8; 32],
+    pseudo_out: [u8; 32],
+}
+
+impl Transcript {
+    fn new(
+        ring: &[CtKey],
+        pseudo_out: &CompressedEdwardsY,
+        key_image: &CompressedEdwardsY,
+        d_inv_eight: &CompressedEdwardsY,
+    ) -> Self {
+        let keys = ring.iter().map(|member| member.dest.as_bytes());
+        let commitments = ring.iter().map(|member| member.mask.as_bytes());
+        Transcript {
+            ring: keys.chain(commitments).flatten().cloned().collect(),
+            key_image: key_image.to_bytes(),
+            d_inv_eight: d_inv_eight.to_bytes(),
+            pseudo_out: pseudo_out.to_bytes(),
+        }
+    }
+
+    // Returns `mu_P` and `mu_C`, which aggregate the key and the commitment of each member
+    fn aggregation_coefficients(&self) -> (Scalar, Scalar) {
+        let aggregation_coefficient = |domain: &[u8]| {
+            let mut to_hash = domain_tag(domain).to_vec();
+            to_hash.extend_from_slice(&self.ring);
+            to_hash.extend_from_slice(&self.key_image);
+            to_hash.extend_from_slice(&self.d_inv_eight);
+            to_hash.extend_from_slice(&self.pseudo_out);
+            hash_to_scalar(&to_hash)
+        };
+        (
+            aggregation_coefficient(HASH_KEY_CLSAG_AGG_0),
+            aggregation_coefficient(HASH_KEY_CLSAG_AGG_1),
+        )
+    }
+
+    // Returns the challenge of the next member from the L and R points of a member
+    fn round_challenge(&self, msg: &[u8; 32], l: &EdwardsPoint, r: &EdwardsPoint) -> Scalar {
+        let mut to_hash = domain_tag(HASH_KEY_CLSAG_ROUND).to_vec();
+        to_hash.extend_from_slice(&self.ring);
+        to_hash.extend_from_slice(&self.pseudo_out);
+        to_hash.extend_from_slice(msg);
+        to_hash.extend_from_slice(l.compress().as_bytes());
+        to_hash.extend_from_slice(r.compress().as_bytes());
+        hash_to_scalar(&to_hash)
+    }
+}
+
+// Pads the domain tag with zeroes to 32 bytes, as Monero hashes it as a key
+fn domain_tag(domain: &[u8]) -> [u8; 32] {
+    let mut tag = [0u8; 32];
+    tag[..domain.len()].copy_from_slice(domain);
+    tag
+}
+
+// Returns a uniformly random scalar, from 64 bytes of the rng reduced modulo the order
+fn random_scalar<R: RngCore + CryptoRng>(rng: &mut R) -> Scalar {
+    let mut bytes = [0u8; 64];
+    rng.fill_bytes(&mut bytes);
+    Scalar::from_bytes_mod_order_wide(&bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::seeded_rng;
+    use curve25519_dalek_4::constants::ED25519_BASEPOINT_POINT;
+    use curve25519_dalek_4::traits::Identity;
+
+    // Pairs of a key and its `hash_to_ec` from the `hash_to_ec` vectors of Monero's
+    // tests/crypto/tests.txt
+    const HASH_TO_EC_VECTORS: [(&str, &str); 4] = [
+        (
+            "da66e9ba613919dec28ef367a125bb310d6d83fb9052e71034164b6dc4f392d0",
+            "52b3f38753b4e13b74624862e253072cf12f745d43fcfafbe8c217701a6e5875",
+        ),
+        (
+            "a7fbdeeccb597c2d5fdaf2ea2e10cbfcd26b5740903e7f6d46bcbf9a90384fc6",
+            "f055ba2d0d9828ce2e203d9896bfda494d7830e7e3a27fa27d5eaa825a79a19c",
+        ),
+        (
+            "ed6e6579368caba2cc4851672972e949c0ee586fee4d6d6a9476d4a908f64070",
+            "da3ceda9a2ef6316bf9272566e6dffd785ac71f57855c0202f422bbb86af4ec0",
+        ),
+        (
+            "9ae78e5620f1c4e6b29d03da006869465b3b16dae87ab0a51f4e1b74bc8aa48b",
+            "72d8720da66f797f55fbb7fa538af0b4a4f5930c8289c991472c37dc5ec16853",
+        ),
+    ];
+
+    // A ring of random outputs, the secret key and commitment mask of the output at
+    // the index, and the pseudo output commitment the signature is made against
+    fn generate_ring(
+        size: usize,
+        index: usize,
+    ) -> (Vec<CtKey>, Scalar, Scalar, CompressedEdwardsY) {
+        let mut rng = seeded_rng(size as u64);
+        let amount_generator = hash_to_ec(&ED25519_BASEPOINT_POINT.compress());
+        let commit = |mask: &Scalar, amount: u64| {
+            ED25519_BASEPOINT_TABLE * mask + amount_generator * Scalar::from(amount)
+        };
+
+        let mut ring = Vec::with_capacity(size);
+        let mut secrets = (Scalar::ZERO, Scalar::ZERO);
+        for i in 0..size {
+            let secret_key = random_scalar(&mut rng);
+            let mask = random_scalar(&mut rng);
+            if i == index {
+                secrets = (secret_key, mask);
+            }
+            ring.push(CtKey {
+                dest: (ED25519_BASEPOINT_TABLE * &secret_key).compress(),
+                mask: commit(&mask, 1000).compress(),
+            });
+        }
+        let pseudo_mask = random_scalar(&mut rng);
+        let pseudo_out = commit(&pseudo_mask, 1000).compress();
+        (ring, secrets.0, secrets.1 - pseudo_mask, pseudo_out)
+    }
+
+    #[test]
+    fn test_hash_to_ec_vectors() {
+        for (key, expected) in HASH_TO_EC_VECTORS.iter() {
+            let mut bytes = [0u8; 32];
+            bytes.copy_from_slice(&hex::decode(key).unwrap());
+            let hashed = hash_to_ec(&CompressedEdwardsY(bytes));
+            assert_eq!(*expected, hex::encode(hashed.compress().as_bytes()));
+        }
+        assert_eq!(
+            Scalar::from(8u64).invert(),
+            Scalar::from_bytes_mod_order(INV_EIGHT)
+        );
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let msg = [7u8; 32];
+        for (size, index) in &[(1, 0), (11, 0), (11, 5), (11, 10), (16, 3)] {
+            let (ring, secret_key, mask, pseudo_out) = generate_ring(*size, *index);
+            let (signature, image) = RingSignature::sign(
+                &mut seeded_rng(1),
+                &msg,
+                &ring,
+                &pseudo_out,
+                *index,
+                &secret_key,
+                &mask,
+            )
+            .unwrap();
+            assert_eq!(key_image(&secret_key).compress(), image);
+            assert!(signature.verify(&msg, &ring, &image, &pseudo_out).is_ok());
+
+            let bytes = signature.to_bytes();
+            assert_eq!(32 * (size + 2), bytes.len());
+            assert_eq!(signature, RingSignature::from_bytes(&bytes, *size).unwrap());
+
+            match signature.verify(&[8u8; 32], &ring, &image, &pseudo_out) {
+                Err(ClsagError::ChallengeMismatch) => {}
+                _ => panic!("expected a challenge mismatch error"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_monero_interop() {
+        use monero_clsag::{Clsag, ClsagContext, Decoys};
+        use monero_ed25519::{Commitment, CompressedPoint, Scalar as MoneroScalar};
+        use rand_core_06::OsRng;
+        use zeroize::Zeroizing;
+
+        let to_monero_scalar = |scalar: &Scalar| MoneroScalar::read(&mut &scalar.as_bytes()[..]);
+        let to_monero_point = |point: &CompressedEdwardsY| CompressedPoint::from(point.to_bytes());
+        let msg = [3u8; 32];
+
+        // Monero's implementation verifies the signatures made here
+        let (ring, secret_key, mask, pseudo_out) = generate_ring(11, 4);
+        let (signature, image) = RingSignature::sign(
+            &mut seeded_rng(2),
+            &msg,
+            &ring,
+            &pseudo_out,
+            4,
+            &secret_key,
+            &mask,
+        )
+        .unwrap();
+        let mut serialized = signature.to_bytes();
+        let clsag = Clsag::read(ring.len(), &mut &serialized[..]).unwrap();
+        let monero_ring: Vec<[CompressedPoint; 2]> = ring
+            .iter()
+            .map(|member| [to_monero_point(&member.dest), to_monero_point(&member.mask)])
+            .collect();
+        assert!(clsag
+            .verify(
+                monero_ring.clone(),
+                &to_monero_point(&image),
+                &to_monero_point(&pseudo_out),
+                &msg
+            )
+            .is_ok());
+
+        // And the signatures made by Monero's implementation verify here
+        let mask = random_scalar(&mut seeded_rng(3));
+        let amount = 1000;
+        let mut ring = Vec::new();
+        let mut signer_key = Scalar::ZERO;
+        for i in 0..11 {
+            let key = random_scalar(&mut seeded_rng(10 + i));
+            let commitment = Commitment::new(to_monero_scalar(&mask).unwrap(), amount);
+            if i == 6 {
+                signer_key = key;
+            }
+            let dest = to_monero_point(&(ED25519_BASEPOINT_TABLE * &key).compress());
+            ring.push([dest.decompress().unwrap(), commitment.commit()]);
+        }
+        let decoys = Decoys::new((1..=11).collect(), 6, ring.clone()).unwrap();
+        let context = ClsagContext::new(
+            decoys,
+            Commitment::new(to_monero_scalar(&mask).unwrap(), amount),
+        )
+        .unwrap();
+        let sum_outputs = to_monero_scalar(&random_scalar(&mut seeded_rng(4))).unwrap();
+        let (clsag, pseudo_out) = Clsag::sign(
+            &mut OsRng,
+            vec![(
+                Zeroizing::new(to_monero_scalar(&signer_key).unwrap()),
+                context,
+            )],
+            sum_outputs,
+            msg,
+        )
+        .unwrap()
+        .swap_remove(0);
+
+        serialized.clear();
+        clsag.write(&mut serialized).unwrap();
+        let signature = RingSignature::from_bytes(&serialized, 11).unwrap();
+        let ring: Vec<CtKey> = ring
+            .iter()
+            .map(|member| CtKey {
+                dest: CompressedEdwardsY(member[0].compress().to_bytes()),
+                mask: CompressedEdwardsY(member[1].compress().to_bytes()),
+            })
+            .collect();
+        let pseudo_out = CompressedEdwardsY(pseudo_out.compress().to_bytes());
+        let image = key_image(&signer_key).compress();
+        assert!(signature.verify(&msg, &ring, &image, &pseudo_out).is_ok());
+    }
+
+    #[test]
+    fn test_sign_errors() {
+        let msg = [0u8; 32];
+        let (ring, secret_key, mask, pseudo_out) = generate_ring(4, 1);
+        let mut rng = seeded_rng(5);
+
+        match RingSignature::sign(&mut rng, &msg, &ring, &pseudo_out, 2, &secret_key, &mask) {
+            Err(ClsagError::NotASigner) => {}
+            _ => panic!("expected a not a signer error"),
+        }
+        match RingSignature::sign(&mut rng, &msg, &ring, &pseudo_out, 4, &secret_key, &mask) {
+            Err(ClsagError::NotASigner) => {}
+            _ => panic!("expected a not a signer error"),
+        }
+        match RingSignature::sign(
+            &mut rng,
+            &msg,
+            &ring,
+            &pseudo_out,
+            1,
+            &secret_key,
+            &secret_key,
+        ) {
+            Err(ClsagError::BadCommitmentKey) => {}
+            _ => panic!("expected a bad commitment key error"),
+        }
+        match RingSignature::sign(&mut rng, &msg, &[], &pseudo_out, 0, &secret_key, &mask) {
+            Err(ClsagError::EmptyRing) => {}
+            _ => panic!("expected an empty ring error"),
+        }
+
+        let (signature, image) =
+            RingSignature::sign(&mut rng, &msg, &ring, &pseudo_out, 1, &secret_key, &mask).unwrap();
+        match signature.verify(&msg, &ring[1..], &image, &pseudo_out) {
+            Err(ClsagError::RingSizeMismatch) => {}
+            _ => panic!("expected a ring size mismatch error"),
+        }
+        let identity = CompressedEdwardsY::identity();
+        match signature.verify(&msg, &ring, &identity, &pseudo_out) {
+            Err(ClsagError::IdentityKeyImage) => {}
+            _ => panic!("expected an identity key image error"),
+        }
+        match RingSignature::from_bytes(&signature.to_bytes()[1..], ring.len()) {
+            Err(ClsagError::DecodeError) => {}
+            _ => panic!("expected a decode error"),
+        }
+    }
+}
@@ -0,0 +1,835 @@
+// A t-of-n threshold signing protocol, where any `threshold` of the `n` holders of a
+// share of the signers keys can produce a clsag signature together, without any of them
+// learning the signers keys. The signature is an ordinary clsag signature, and verifies
+// with the same verifier as a signature made by a single signer.
+//
+// The protocol runs in three steps:
+//
+//   1. Each participant calls `KeyShare::commit`, keeping the returned nonces to themselves
+//      and sending the nonce commitment to a coordinator.
+//   2. The coordinator builds a `SigningPackage` from the ring, the message and the nonce
+//      commitments of every participant, and sends the package to each participant,
+//      who calls `KeyShare::sign` to produce their partial signature.
+//   3. The coordinator combines the partial signatures into a signature with
+//      `SigningPackage::combine`, which is verified before it is returned.
+//
+// The keys are split with Shamir secret sharing by `split_secret_keys`, which trusts
+// whoever runs it with the signers keys. Keys split one at a time with the shamir module
+// are turned into key shares with `KeyShare::from_secret_shares`. Each participant commits
+// to two nonces, which are combined with a binding factor derived from the whole signing
+// package, as is done in FROST, so that commitments cannot be chosen after seeing the
+// commitments of other participants.
+// A fresh pair of nonces must be used for every signature, which is why `KeyShare::sign`
+// consumes them.
+//
+// Members and the signer cannot hold commitments, and a participant who sends a bad
+// share of the key images or a bad partial signature is only caught when the combined
+// signature fails to verify.
+use crate::clsag::{calc_aggregation_coefficients, PublicKeys};
+use crate::constants::BASEPOINT;
+use crate::error::ClsagError;
+use crate::hash::hash_pubkey;
+use crate::keys::{zeroize_scalar, KeyImage, PublicKey, SecretKey};
+use crate::member::{ring_challenge, round_challenge, round_transcript};
+use crate::policy::RingPolicy;
+use crate::ring::PreparedRing;
+use crate::shamir::{lagrange_coefficient, split_secret, SecretShare};
+use crate::signature::Signature;
+use crate::transcript::{default_transcript, SigningTranscript};
+use curve25519_dalek::ristretto::RistrettoPoint;
+use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::traits::Identity;
+use merlin::Transcript;
+use rand::{CryptoRng, RngCore};
+#[cfg(feature = "serde")]
+use std::convert::TryFrom;
+use std::fmt;
+
+// A KeyShare is one participants share of the signers keys
+#[derive(Clone)]
+pub struct KeyShare {
+    // The index of the participant, starting from 1
+    index: u32,
+
+    // The number of participants needed to sign
+    threshold: usize,
+
+    // The participants share of each of the signers private keys
+    secret_shares: Vec<Scalar>,
+
+    // The public keys of the signer, which is the member of the ring being signed for
+    public_keys: Vec<PublicKey>,
+
+    // The hash of the signers first public key, which key images are computed from
+    hashed_pubkey: RistrettoPoint,
+}
+
+// The secret nonces of a participant for a single signature, which must never be reused
+pub struct SigningNonces {
+    index: u32,
+    hiding: Scalar,
+    binding: Scalar,
+}
+
+// The nonce commitment is sent by each participant in the first round,
+// along with their share of the key images
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NonceCommitment {
+    index: u32,
+
+    // The hiding and binding nonces multiplied by the basepoint
+    // and by the hash of the signers first public key
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::point"))]
+    hiding_basepoint: RistrettoPoint,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::point"))]
+    hiding_hashed_pubkey: RistrettoPoint,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::point"))]
+    binding_basepoint: RistrettoPoint,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::point"))]
+    binding_hashed_pubkey: RistrettoPoint,
+
+    // The participants share of each key image, before it is weighted
+    // by their lagrange coefficient
+    key_image_shares: Vec<KeyImage>,
+}
+
+// The signing package is sent by the coordinator to each participant in the second round.
+// A package is checked with `validate` when it is built and when it is deserialized,
+// so that a package from a peer cannot make a participant index past the end of the ring
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "SerdeSigningPackage")
+)]
+pub struct SigningPackage {
+    ring: PublicKeys,
+    signer_index: usize,
+    msg: Vec<u8>,
+
+    // The response of every decoy, with zero in the position of the signer
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::scalars"))]
+    responses: Vec<Scalar>,
+
+    // The nonce commitment of each participant, ordered by their index
+    commitments: Vec<NonceCommitment>,
+}
+
+// A signing package as it is deserialized, before it is validated
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct SerdeSigningPackage {
+    ring: PublicKeys,
+    signer_index: usize,
+    msg: Vec<u8>,
+    #[serde(with = "crate::serde_helpers::scalars")]
+    responses: Vec<Scalar>,
+    commitments: Vec<NonceCommitment>,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<SerdeSigningPackage> for SigningPackage {
+    type Error = ClsagError;
+
+    fn try_from(package: SerdeSigningPackage) -> Result<Self, ClsagError> {
+        let package = SigningPackage {
+            ring: package.ring,
+            signer_index: package.signer_index,
+            msg: package.msg,
+            responses: package.responses,
+            commitments: package.commitments,
+        };
+        package.validate()?;
+        Ok(package)
+    }
+}
+
+// The partial signature is sent by each participant to the coordinator in the second round
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PartialSignature {
+    index: u32,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::scalar"))]
+    response: Scalar,
+}
+
+// Everything the participants and the coordinator derive from the signing package
+struct Challenges {
+    key_images: Vec<KeyImage>,
+    agg_coeffs: Vec<Scalar>,
+    signer_challenge: Scalar,
+    first_challenge: Scalar,
+}
+
+// Splits the signers keys into `num_participants` shares, such that any `threshold`
+// of them can sign for the signer.
+// Returns an error if there are no keys, or the threshold is zero or more than the
+// number of participants
+pub fn split_secret_keys<R: RngCore + CryptoRng>(
+    rng: &mut R,
+    secret_keys: &[SecretKey],
+    threshold: usize,
+    num_participants: usize,
+) -> Result<Vec<KeyShare>, ClsagError> {
+    if secret_keys.is_empty() {
+        return Err(ClsagError::KeyCountMismatch);
+    }
+    if threshold == 0 || threshold > num_participants || num_participants > u32::MAX as usize {
+        return Err(ClsagError::InvalidThreshold);
+    }
+
+    let public_keys: Vec<PublicKey> = secret_keys.iter().map(|key| key.public_key()).collect();
+
+    // Each key is split on its own, and participant `i` holds the share at index `i`
+    // of every key
+    let key_shares = secret_keys
+        .iter()
+        .map(|key| split_secret(rng, key, threshold, num_participants))
+        .collect::<Result<Vec<Vec<SecretShare>>, ClsagError>>()?;
+
+    (0..num_participants)
+        .map(|participant| {
+            let shares: Vec<SecretShare> = key_shares
+                .iter()
+                .map(|shares| shares[participant].clone())
+                .collect();
+            KeyShare::from_secret_shares(&shares, public_keys.clone())
+        })
+        .collect()
+}
+
+impl KeyShare {
+    // Creates the share of participant `index` from their share of each private key,
+    // for the signer with the given public keys
+    pub(crate) fn new(
+        index: u32,
+        threshold: usize,
+        secret_shares: Vec<Scalar>,
+        public_keys: Vec<PublicKey>,
+    ) -> Self {
+        let hashed_pubkey = hash_pubkey(public_keys[0].as_bytes());
+
+        KeyShare {
+            index,
+            threshold,
+            secret_shares,
+            public_keys,
+            hashed_pubkey,
+        }
+    }
+    // Creates a participants key share from their Shamir share of each of the signers
+    // private keys, in the same order as the public keys, so that the participants can sign
+    // without the keys being reconstructed.
+    // Returns an error if there is not a share for every public key,
+    // or if the shares do not all have the same index and threshold
+    pub fn from_secret_shares(
+        shares: &[SecretShare],
+        public_keys: Vec<PublicKey>,
+    ) -> Result<KeyShare, ClsagError> {
+        if shares.is_empty() || shares.len() != public_keys.len() {
+            return Err(ClsagError::KeyCountMismatch);
+        }
+        let (index, threshold) = (shares[0].index(), shares[0].threshold());
+        let consistent = shares
+            .iter()
+            .all(|share| share.index() == index && share.threshold() == threshold);
+        if !consistent {
+            return Err(ClsagError::ShareMismatch);
+        }
+
+        let secret_shares = shares.iter().map(|share| *share.value()).collect();
+        Ok(KeyShare::new(index, threshold, secret_shares, public_keys))
+    }
+    // Returns the index of the participant holding the share, starting from 1
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+    // Returns the number of participants needed to sign
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+    // Returns the public keys of the signer, which must be a member of the ring
+    pub fn public_keys(&self) -> &[PublicKey] {
+        &self.public_keys
+    }
+    // Starts the first round, drawing a fresh pair of nonces from the rng.
+    // The nonces are kept for the second round, while the commitment is sent to the coordinator
+    pub fn commit<R: RngCore + CryptoRng>(&self, rng: &mut R) -> (SigningNonces, NonceCommitment) {
+        let nonces = SigningNonces {
+            index: self.index,
+            hiding: Scalar::random(rng),
+            binding: Scalar::random(rng),
+        };
+        let commitment = self.nonce_commitment(&nonces);
+
+        (nonces, commitment)
+    }
+    fn nonce_commitment(&self, nonces: &SigningNonces) -> NonceCommitment {
+        NonceCommitment {
+            index: self.index,
+            hiding_basepoint: nonces.hiding * BASEPOINT,
+            hiding_hashed_pubkey: nonces.hiding * self.hashed_pubkey,
+            binding_basepoint: nonces.binding * BASEPOINT,
+            binding_hashed_pubkey: nonces.binding * self.hashed_pubkey,
+            key_image_shares: self
+                .secret_shares
+                .iter()
+                .map(|share| KeyImage((share * self.hashed_pubkey).compress()))
+                .collect(),
+        }
+    }
+    // Produces the participants partial signature over the signing package,
+    // consuming the nonces committed to in the first round.
+    // Returns an error if the signer in the package is not the signer the share is for,
+    // if the package does not hold the commitment to these nonces,
+    // or if there are fewer participants than the threshold
+    pub fn sign(
+        &self,
+        nonces: SigningNonces,
+        package: &SigningPackage,
+    ) -> Result<PartialSignature, ClsagError> {
+        package.validate()?;
+        if package.ring[package.signer_index] != self.public_keys {
+            return Err(ClsagError::NotASigner);
+        }
+        if package.commitments.len() < self.threshold {
+            return Err(ClsagError::NotEnoughParticipants);
+        }
+        match package.commitment(self.index) {
+            Some(commitment) if nonces.index == self.index => {
+                if *commitment != self.nonce_commitment(&nonces) {
+                    return Err(ClsagError::ParticipantMismatch);
+                }
+            }
+            _ => return Err(ClsagError::ParticipantMismatch),
+        }
+
+        let challenges = package.challenges()?;
+        let lagrange_coefficient = package.lagrange_coefficient(self.index);
+        let binding_factor = package.binding_factor(self.index);
+
+        let mut weighted_key: Scalar = self
+            .secret_shares
+            .iter()
+            .zip(challenges.agg_coeffs.iter())
+            .map(|(share, mu)| share * mu)
+            .sum();
+        weighted_key *= lagrange_coefficient;
+        let response = nonces.hiding + binding_factor * nonces.binding
+            - challenges.signer_challenge * weighted_key;
+
+        zeroize_scalar(&mut weighted_key);
+
+        Ok(PartialSignature {
+            index: self.index,
+            response,
+        })
+    }
+}
+
+impl fmt::Debug for KeyShare {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("KeyShare")
+            .field("index", &self.index)
+            .field("threshold", &self.threshold)
+            .field("public_keys", &self.public_keys)
+            .finish()
+    }
+}
+
+impl fmt::Debug for SigningNonces {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SigningNonces(..)")
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for KeyShare {
+    fn drop(&mut self) {
+        for share in self.secret_shares.iter_mut() {
+            zeroize_scalar(share);
+        }
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for SigningNonces {
+    fn drop(&mut self) {
+        zeroize_scalar(&mut self.hiding);
+        zeroize_scalar(&mut self.binding);
+    }
+}
+
+impl NonceCommitment {
+    // Returns the index of the participant who sent the commitment
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+}
+
+impl PartialSignature {
+    // Returns the index of the participant who sent the partial signature
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+}
+
+impl SigningPackage {
+    // Builds the package for signing the message over the ring, where the signer whose keys
+    // were split is the member at `signer_index`, from the commitment of every participant.
+    // The ring must satisfy the default ring policy, and the decoys responses are drawn from the rng
+    pub fn new<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        ring: PublicKeys,
+        signer_index: usize,
+        msg: &[u8],
+        commitments: Vec<NonceCommitment>,
+    ) -> Result<Self, ClsagError> {
+        Self::with_policy(
+            rng,
+            &RingPolicy::default(),
+            ring,
+            signer_index,
+            msg,
+            commitments,
+        )
+    }
+    // Builds the package as `new` does, checking the ring against the given policy.
+    // Returns an error if the ring cannot be signed, if the signer is not in the ring,
+    // or if two commitments are from the same participant
+    pub fn with_policy<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        policy: &RingPolicy,
+        ring: PublicKeys,
+        signer_index: usize,
+        msg: &[u8],
+        mut commitments: Vec<NonceCommitment>,
+    ) -> Result<Self, ClsagError> {
+        let prepared = PreparedRing::new(&ring)?;
+        policy.check_ring_size(prepared.len())?;
+        policy.check_num_keys(prepared.num_layers())?;
+
+        commitments.sort_by_key(|commitment| commitment.index);
+        let responses = (0..prepared.len())
+            .map(|index| match index == signer_index {
+                true => Scalar::zero(),
+                false => Scalar::random(rng),
+            })
+            .collect();
+
+        let package = SigningPackage {
+            ring,
+            signer_index,
+            msg: msg.to_vec(),
+            responses,
+            commitments,
+        };
+        package.validate()?;
+        Ok(package)
+    }
+    // Checks that the package can be signed: that the ring has at least two members with
+    // the same number of keys, that the signer is in the ring, that there is a response for
+    // every member, and that the commitments are from distinct participants, ordered by
+    // their index, each with a share of every key image.
+    // Packages are validated when they are built and deserialized, and again by the functions
+    // signing and combining them, as the fields of a package are never changed
+    pub fn validate(&self) -> Result<(), ClsagError> {
+        let prepared = PreparedRing::new(&self.ring)?;
+        if prepared.len() < 2 {
+            return Err(ClsagError::RingTooSmall);
+        }
+        if self.signer_index >= prepared.len() {
+            return Err(ClsagError::NoSigner);
+        }
+        if self.responses.len() != prepared.len() {
+            return Err(ClsagError::RingSizeMismatch);
+        }
+
+        let ordered = self
+            .commitments
+            .windows(2)
+            .all(|pair| pair[0].index < pair[1].index);
+        if self.commitments.is_empty() || self.commitments[0].index == 0 || !ordered {
+            return Err(ClsagError::ParticipantMismatch);
+        }
+        if self
+            .commitments
+            .iter()
+            .any(|commitment| commitment.key_image_shares.len() != prepared.num_layers())
+        {
+            return Err(ClsagError::KeyCountMismatch);
+        }
+        Ok(())
+    }
+    // Returns the ring in the order it is signed in
+    pub fn ring(&self) -> &[Vec<PublicKey>] {
+        &self.ring
+    }
+    // Returns the position of the signer in the ring
+    pub fn signer_index(&self) -> usize {
+        self.signer_index
+    }
+    // Returns the message being signed
+    pub fn msg(&self) -> &[u8] {
+        &self.msg
+    }
+    // Combines the partial signature of every participant into a signature,
+    // which is verified against the ring before it is returned.
+    // Returns an error if there is not a partial signature from every participant,
+    // or if the combined signature does not verify
+    pub fn combine(&self, partials: &[PartialSignature]) -> Result<Signature, ClsagError> {
+        self.validate()?;
+        let mut indices: Vec<u32> = partials.iter().map(|partial| partial.index).collect();
+        indices.sort_unstable();
+        if !indices
+            .iter()
+            .eq(self.commitments.iter().map(|commitment| &commitment.index))
+        {
+            return Err(ClsagError::ParticipantMismatch);
+        }
+
+        let challenges = self.challenges()?;
+        let mut responses = self.responses.clone();
+        responses[self.signer_index] = partials.iter().map(|partial| partial.response).sum();
+
+        let signature = Signature {
+            challenge: challenges.first_challenge,
+            responses,
+            key_images: challenges.key_images,
+        };
+        signature.optimised_verify(&self.ring, &self.msg)?;
+
+        Ok(signature)
+    }
+    fn commitment(&self, index: u32) -> Option<&NonceCommitment> {
+        self.commitments
+            .iter()
+            .find(|commitment| commitment.index == index)
+    }
+    // Returns the lagrange coefficient of the participant at `index` in the signing set
+    fn lagrange_coefficient(&self, index: u32) -> Scalar {
+        lagrange_coefficient(
+            index,
+            self.commitments.iter().map(|commitment| commitment.index),
+        )
+    }
+    // Returns the factor the binding nonce of the participant at `index` is multiplied by,
+    // which binds their nonce to the ring, the message and every other commitment
+    fn binding_factor(&self, index: u32) -> Scalar {
+        let mut transcript = Transcript::new(b"clsag_multisig_binding");
+        for keys in &self.ring {
+            for key in keys {
+                transcript.append_message(b"public_key", key.as_bytes());
+            }
+        }
+        transcript.append_u64(b"signer_index", self.signer_index as u64);
+        transcript.append_message(b"msg", &self.msg);
+        for response in &self.responses {
+            transcript.append_message(b"response", response.as_bytes());
+        }
+        for commitment in &self.commitments {
+            transcript.append_u64(b"participant", u64::from(commitment.index));
+            transcript.append_point(b"hiding_G", &commitment.hiding_basepoint);
+            transcript.append_point(b"hiding_H", &commitment.hiding_hashed_pubkey);
+            transcript.append_point(b"binding_G", &commitment.binding_basepoint);
+            transcript.append_point(b"binding_H", &commitment.binding_hashed_pubkey);
+            for key_image_share in &commitment.key_image_shares {
+                transcript.append_message(b"key_image_share", key_image_share.as_bytes());
+            }
+        }
+        transcript.append_u64(b"index", u64::from(index));
+
+        transcript.challenge_scalar(b"binding")
+    }
+    // Combines the commitments into the key images and the signers nonce commitment,
+    // and walks the ring from the signer to find the challenge of each member
+    fn challenges(&self) -> Result<Challenges, ClsagError> {
+        self.validate()?;
+        let ring = PreparedRing::new(&self.ring)?;
+        let transcript = default_transcript();
+        let num_members = ring.len();
+
+        let mut key_images = vec![RistrettoPoint::identity(); ring.num_layers()];
+        let mut l = RistrettoPoint::identity();
+        let mut r = RistrettoPoint::identity();
+        for commitment in &self.commitments {
+            let lagrange_coefficient = self.lagrange_coefficient(commitment.index);
+            for (key_image, share) in key_images.iter_mut().zip(&commitment.key_image_shares) {
+                *key_image +=
+                    lagrange_coefficient * share.decompress().ok_or(ClsagError::InvalidKeyImage)?;
+            }
+
+            let binding_factor = self.binding_factor(commitment.index);
+            l += commitment.hiding_basepoint + binding_factor * commitment.binding_basepoint;
+            r +=
+                commitment.hiding_hashed_pubkey + binding_factor * commitment.binding_hashed_pubkey;
+        }
+        let key_images: Vec<KeyImage> = key_images
+            .iter()
+            .map(|key_image| KeyImage(key_image.compress()))
+            .collect();
+
+        let agg_coeffs = calc_aggregation_coefficients(
+            &transcript,
+            ring.pubkey_matrix(),
+            &key_images,
+            &self.msg,
+        );
+
+        let mut challenges = vec![Scalar::zero(); num_members];
+        let round = round_transcript(&transcript, ring.pubkey_matrix(), &key_images, &self.msg);
+        let mut challenge = round_challenge(&round, &l, &r);
+        challenges[(self.signer_index + 1) % num_members] = challenge;
+        for offset in 1..num_members {
+            let index = (self.signer_index + offset) % num_members;
+            challenge = ring_challenge(
+                &round,
+                &self.ring[index],
+                &challenge,
+                &key_images,
+                &self.responses[index],
+                &agg_coeffs,
+                &ring.hashed_pubkeys()[index],
+                &BASEPOINT,
+            )?;
+            challenges[(index + 1) % num_members] = challenge;
+        }
+
+        Ok(Challenges {
+            key_images,
+            agg_coeffs,
+            signer_challenge: challenges[self.signer_index],
+            first_challenge: challenges[0],
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::member::Member;
+    use crate::test_utils::*;
+
+    // Returns a ring of 11 members, with the signer at `signer_index`
+    fn ring_with_signer(signer_keys: &[PublicKey], signer_index: usize) -> PublicKeys {
+        let mut ring: PublicKeys = (0..10)
+            .map(|_| generate_rand_public_keys(signer_keys.len()))
+            .collect();
+        ring.insert(signer_index, signer_keys.to_vec());
+        ring
+    }
+
+    // Runs both rounds of the protocol with the given participants
+    fn sign_with(
+        shares: &[&KeyShare],
+        ring: PublicKeys,
+        signer_index: usize,
+        msg: &[u8],
+    ) -> Result<Signature, ClsagError> {
+        let mut rng = rand::thread_rng();
+
+        let (nonces, commitments): (Vec<SigningNonces>, Vec<NonceCommitment>) =
+            shares.iter().map(|share| share.commit(&mut rng)).unzip();
+        let package = SigningPackage::new(&mut rng, ring, signer_index, msg, commitments)?;
+
+        let partials = shares
+            .iter()
+            .zip(nonces)
+            .map(|(share, nonces)| share.sign(nonces, &package))
+            .collect::<Result<Vec<PartialSignature>, ClsagError>>()?;
+        package.combine(&partials)
+    }
+
+    #[test]
+    fn test_threshold_sign() {
+        let mut rng = rand::thread_rng();
+        let msg = b"hello world";
+
+        for &(threshold, num_participants, num_keys) in &[(2, 3, 1), (3, 5, 2), (1, 1, 1)] {
+            let secret_keys: Vec<SecretKey> =
+                (0..num_keys).map(|_| SecretKey::random(&mut rng)).collect();
+            let shares =
+                split_secret_keys(&mut rng, &secret_keys, threshold, num_participants).unwrap();
+            assert_eq!(num_participants, shares.len());
+
+            let ring = ring_with_signer(shares[0].public_keys(), 4);
+
+            // Any `threshold` of the participants can sign, as can all of them
+            let participants: Vec<&KeyShare> = shares.iter().rev().take(threshold).collect();
+            let signature = sign_with(&participants, ring.clone(), 4, msg).unwrap();
+            assert!(signature.verify(&ring, msg).is_ok());
+            let all: Vec<&KeyShare> = shares.iter().collect();
+            let signature = sign_with(&all, ring.clone(), 4, msg).unwrap();
+            assert!(signature.verify(&ring, msg).is_ok());
+
+            // The key images are the key images of the signer
+            let signer = Member::signer_from_secret_keys(&secret_keys).unwrap();
+            assert_eq!(signer.compute_key_images().unwrap(), signature.key_images());
+        }
+    }
+
+    #[test]
+    fn test_sign_with_secret_shares() {
+        let mut rng = rand::thread_rng();
+        let msg = b"hello world";
+        let secret_keys = [SecretKey::random(&mut rng), SecretKey::random(&mut rng)];
+        let public_keys: Vec<PublicKey> = secret_keys.iter().map(SecretKey::public_key).collect();
+
+        // Each key is split on its own, as a custody provider holding the keys separately would
+        let key_shares: Vec<Vec<SecretShare>> = secret_keys
+            .iter()
+            .map(|key| split_secret(&mut rng, key, 2, 3).unwrap())
+            .collect();
+        let shares: Vec<KeyShare> = (0..3)
+            .map(|participant| {
+                let shares: Vec<SecretShare> = key_shares
+                    .iter()
+                    .map(|shares| shares[participant].clone())
+                    .collect();
+                KeyShare::from_secret_shares(&shares, public_keys.clone()).unwrap()
+            })
+            .collect();
+
+        let ring = ring_with_signer(&public_keys, 2);
+        let signature = sign_with(&[&shares[0], &shares[2]], ring.clone(), 2, msg).unwrap();
+        assert!(signature.verify(&ring, msg).is_ok());
+
+        // The shares of a participant must all have the same index
+        let mixed = vec![key_shares[0][0].clone(), key_shares[1][1].clone()];
+        match KeyShare::from_secret_shares(&mixed, public_keys.clone()) {
+            Err(ClsagError::ShareMismatch) => {}
+            _ => panic!("expected a share mismatch error"),
+        }
+        match KeyShare::from_secret_shares(&key_shares[0][..1], public_keys) {
+            Err(ClsagError::KeyCountMismatch) => {}
+            _ => panic!("expected a key count mismatch error"),
+        }
+    }
+
+    #[test]
+    fn test_threshold_errors() {
+        let mut rng = rand::thread_rng();
+        let secret_keys = vec![SecretKey::random(&mut rng)];
+
+        for &(threshold, num_participants) in &[(0, 3), (4, 3)] {
+            match split_secret_keys(&mut rng, &secret_keys, threshold, num_participants) {
+                Err(ClsagError::InvalidThreshold) => {}
+                _ => panic!("expected an invalid threshold error"),
+            }
+        }
+
+        let shares = split_secret_keys(&mut rng, &secret_keys, 2, 3).unwrap();
+        let ring = ring_with_signer(shares[0].public_keys(), 0);
+
+        // Fewer participants than the threshold cannot sign
+        match sign_with(&[&shares[0]], ring.clone(), 0, b"msg") {
+            Err(ClsagError::NotEnoughParticipants) => {}
+            _ => panic!("expected a not enough participants error"),
+        }
+
+        // The signer must be the member the shares are for
+        match sign_with(&[&shares[0], &shares[1]], ring.clone(), 1, b"msg") {
+            Err(ClsagError::NotASigner) => {}
+            _ => panic!("expected a not a signer error"),
+        }
+
+        let (nonces_0, commitment_0) = shares[0].commit(&mut rng);
+        let (nonces_1, commitment_1) = shares[1].commit(&mut rng);
+        let package = SigningPackage::new(
+            &mut rng,
+            ring,
+            0,
+            b"msg",
+            vec![commitment_0.clone(), commitment_1],
+        )
+        .unwrap();
+
+        // A participant can only sign with the nonces they committed to
+        let (other_nonces, _) = shares[0].commit(&mut rng);
+        match shares[0].sign(other_nonces, &package) {
+            Err(ClsagError::ParticipantMismatch) => {}
+            _ => panic!("expected a participant mismatch error"),
+        }
+
+        // Every participant must send a partial signature, and a bad partial
+        // signature gives a signature which does not verify
+        let partial_0 = shares[0].sign(nonces_0, &package).unwrap();
+        let partial_1 = shares[1].sign(nonces_1, &package).unwrap();
+        match package.combine(&[partial_0]) {
+            Err(ClsagError::ParticipantMismatch) => {}
+            _ => panic!("expected a participant mismatch error"),
+        }
+        let bad_partial = PartialSignature {
+            index: partial_1.index,
+            response: partial_1.response + Scalar::one(),
+        };
+        match package.combine(&[partial_0, bad_partial]) {
+            Err(ClsagError::ChallengeMismatch) => {}
+            _ => panic!("expected a challenge mismatch error"),
+        }
+        assert!(package.combine(&[partial_1, partial_0]).is_ok());
+
+        // Two commitments cannot come from the same participant
+        let ring = package.ring().to_vec();
+        match SigningPackage::new(
+            &mut rng,
+            ring,
+            0,
+            b"msg",
+            vec![commitment_0.clone(), commitment_0],
+        ) {
+            Err(ClsagError::ParticipantMismatch) => {}
+            _ => panic!("expected a participant mismatch error"),
+        }
+    }
+
+    #[test]
+    fn test_tampered_package() {
+        let mut rng = rand::thread_rng();
+        let secret_keys = vec![SecretKey::random(&mut rng)];
+        let shares = split_secret_keys(&mut rng, &secret_keys, 2, 3).unwrap();
+        let ring = ring_with_signer(shares[0].public_keys(), 0);
+
+        let (_, commitment_0) = shares[0].commit(&mut rng);
+        let (_, commitment_1) = shares[1].commit(&mut rng);
+        let package =
+            SigningPackage::new(&mut rng, ring, 0, b"msg", vec![commitment_0, commitment_1])
+                .unwrap();
+        assert!(package.validate().is_ok());
+
+        // A package whose signer is past the end of the ring
+        let mut past_the_end = package.clone();
+        past_the_end.signer_index = package.ring.len();
+        // A package with fewer responses than members
+        let mut short_responses = package.clone();
+        short_responses.responses.pop();
+        // A package whose commitments are out of order
+        let mut unordered = package.clone();
+        unordered.commitments.reverse();
+
+        let tampered = [
+            (past_the_end, ClsagError::NoSigner),
+            (short_responses, ClsagError::RingSizeMismatch),
+            (unordered, ClsagError::ParticipantMismatch),
+        ];
+        for (package, expected) in tampered.iter() {
+            assert_eq!(Err(*expected), package.validate());
+            let (nonces, _) = shares[0].commit(&mut rng);
+            assert_eq!(Err(*expected), shares[0].sign(nonces, package));
+            assert_eq!(Some(*expected), package.combine(&[]).err());
+
+            // A tampered package from a peer is refused when it is deserialized
+            #[cfg(feature = "serde")]
+            {
+                let json = serde_json::to_string(package).unwrap();
+                assert!(serde_json::from_str::<SigningPackage>(&json).is_err());
+            }
+        }
+        #[cfg(feature = "serde")]
+        {
+            let json = serde_json::to_string(&package).unwrap();
+            assert_eq!(package, serde_json::from_str(&json).unwrap());
+        }
+    }
+}
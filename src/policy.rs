@@ -0,0 +1,171 @@
+use crate::error::ClsagError;
+
+// The smallest ring the default policy allows, which is the ring size used by Monero
+pub const DEFAULT_MIN_RING_SIZE: usize = 11;
+// The largest ring the default verification limits allow
+pub const DEFAULT_MAX_RING_SIZE: usize = 1024;
+// The most keys per member the default verification limits allow
+pub const DEFAULT_MAX_KEYS: usize = 16;
+
+// A RingPolicy holds the limits a ring must satisfy before it can be signed.
+// The maximum ring size and the number of keys are checked as members are added,
+// while the minimum ring size is checked when signing, once every member has been added
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RingPolicy {
+    // The least number of members a ring must have, including the signer
+    pub min_ring_size: usize,
+    // The most members a ring may have, including the signer, if there is a limit
+    pub max_ring_size: Option<usize>,
+    // The number of keys each member must have, not counting their commitment,
+    // if the number is fixed
+    pub num_keys: Option<usize>,
+}
+
+impl Default for RingPolicy {
+    // The default policy requires at least 11 members, as Monero does,
+    // and places no limit on the number of keys
+    fn default() -> Self {
+        RingPolicy {
+            min_ring_size: DEFAULT_MIN_RING_SIZE,
+            max_ring_size: None,
+            num_keys: None,
+        }
+    }
+}
+
+impl RingPolicy {
+    // Returns a policy which allows any ring that can be signed, which is
+    // any ring with at least two members. Small rings give the signer little anonymity,
+    // so this should only be used where the ring size is checked elsewhere
+    pub fn permissive() -> Self {
+        RingPolicy {
+            min_ring_size: 2,
+            max_ring_size: None,
+            num_keys: None,
+        }
+    }
+    // Checks that a member with `num_keys` keys can be added to a ring
+    // which already has `ring_size` members
+    pub fn check_member(&self, ring_size: usize, num_keys: usize) -> Result<(), ClsagError> {
+        if let Some(max_ring_size) = self.max_ring_size {
+            if ring_size >= max_ring_size {
+                return Err(ClsagError::RingTooLarge);
+            }
+        }
+        self.check_num_keys(num_keys)
+    }
+    // Checks that a member with `num_keys` keys has the number of keys the policy requires
+    pub fn check_num_keys(&self, num_keys: usize) -> Result<(), ClsagError> {
+        match self.num_keys {
+            Some(required) if num_keys != required => Err(ClsagError::KeyCountMismatch),
+            _ => Ok(()),
+        }
+    }
+    // Checks that a ring with `ring_size` members can be signed
+    pub fn check_ring_size(&self, ring_size: usize) -> Result<(), ClsagError> {
+        if ring_size < self.min_ring_size {
+            return Err(ClsagError::RingTooSmall);
+        }
+        if let Some(max_ring_size) = self.max_ring_size {
+            if ring_size > max_ring_size {
+                return Err(ClsagError::RingTooLarge);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// VerificationLimits bound the size of the rings and signatures which are decoded and
+// verified, so that a signature or ring from an untrusted peer claiming a huge ring
+// cannot make a node allocate a large amount of memory or spend a long time verifying.
+// Decoding and verification use the default limits, the `_with_limits` variants of
+// `Signature::from_bytes`, `Signature::verify` and `PreparedRing::new` take other limits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VerificationLimits {
+    // The most members a ring may have
+    pub max_ring_size: usize,
+    // The most keys each member of a ring may have, which is the most key images
+    // a signature may have
+    pub max_keys: usize,
+}
+
+impl Default for VerificationLimits {
+    // The default limits allow rings of up to 1024 members with up to 16 keys each
+    fn default() -> Self {
+        VerificationLimits {
+            max_ring_size: DEFAULT_MAX_RING_SIZE,
+            max_keys: DEFAULT_MAX_KEYS,
+        }
+    }
+}
+
+impl VerificationLimits {
+    // Returns limits which allow any ring, for signatures and rings from trusted sources
+    pub fn unlimited() -> Self {
+        VerificationLimits {
+            max_ring_size: usize::MAX,
+            max_keys: usize::MAX,
+        }
+    }
+    // Checks that a ring of `ring_size` members with `num_keys` keys each is within the limits
+    pub fn check(&self, ring_size: usize, num_keys: usize) -> Result<(), ClsagError> {
+        if ring_size > self.max_ring_size {
+            return Err(ClsagError::RingTooLarge);
+        }
+        if num_keys > self.max_keys {
+            return Err(ClsagError::TooManyKeys);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_check_ring_size() {
+        let policy = RingPolicy {
+            min_ring_size: 4,
+            max_ring_size: Some(8),
+            num_keys: Some(2),
+        };
+
+        assert_eq!(Err(ClsagError::RingTooSmall), policy.check_ring_size(3));
+        assert!(policy.check_ring_size(4).is_ok());
+        assert!(policy.check_ring_size(8).is_ok());
+        assert_eq!(Err(ClsagError::RingTooLarge), policy.check_ring_size(9));
+
+        assert!(policy.check_member(7, 2).is_ok());
+        assert_eq!(Err(ClsagError::RingTooLarge), policy.check_member(8, 2));
+        assert_eq!(Err(ClsagError::KeyCountMismatch), policy.check_member(0, 3));
+
+        assert_eq!(
+            Err(ClsagError::RingTooSmall),
+            RingPolicy::default().check_ring_size(10)
+        );
+        assert!(RingPolicy::default().check_ring_size(11).is_ok());
+        assert!(RingPolicy::permissive().check_ring_size(2).is_ok());
+    }
+
+    #[test]
+    fn test_verification_limits() {
+        let limits = VerificationLimits::default();
+        assert!(limits
+            .check(DEFAULT_MAX_RING_SIZE, DEFAULT_MAX_KEYS)
+            .is_ok());
+        assert_eq!(
+            Err(ClsagError::RingTooLarge),
+            limits.check(DEFAULT_MAX_RING_SIZE + 1, 1)
+        );
+        assert_eq!(
+            Err(ClsagError::TooManyKeys),
+            limits.check(11, DEFAULT_MAX_KEYS + 1)
+        );
+        assert!(VerificationLimits::unlimited()
+            .check(1 << 20, DEFAULT_MAX_KEYS + 1)
+            .is_ok());
+    }
+}
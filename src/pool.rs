@@ -0,0 +1,136 @@
+// A pool of workers for verifying signatures from async code, behind the async feature.
+//
+// Verifying a signature takes a scalar multiplication per member of the ring, which would
+// block the executor if it were run on an async task. `VerifierPool` takes verification
+// jobs over a bounded channel, and runs each one on the blocking thread pool of the tokio
+// runtime, with at most `concurrency` jobs running at once. The result of each job is sent
+// back through a oneshot channel, so node software can await verification like any other io.
+//
+// The pool must be created from within a tokio runtime, and stops once it is dropped
+// and every job it has accepted has finished.
+use crate::clsag::PublicKeys;
+use crate::error::ClsagError;
+use crate::signature::Signature;
+use std::sync::Arc;
+use tokio::sync::{mpsc, oneshot, Semaphore};
+
+// The receiver of the result of a verification job
+pub type VerifyReceiver = oneshot::Receiver<Result<(), ClsagError>>;
+
+// A signature waiting to be verified, along with where to send the result
+struct VerifyJob {
+    signature: Signature,
+    public_keys: PublicKeys,
+    msg: Vec<u8>,
+    reply: oneshot::Sender<Result<(), ClsagError>>,
+}
+
+// A handle for submitting signatures to a pool of verification workers
+#[derive(Debug, Clone)]
+pub struct VerifierPool {
+    jobs: mpsc::Sender<VerifyJob>,
+}
+
+impl VerifierPool {
+    // Starts a pool which verifies at most `concurrency` signatures at once, and holds at most
+    // `queue_size` signatures waiting to be verified. At least one of each is used.
+    // Panics if it is not called from within a tokio runtime
+    pub fn new(concurrency: usize, queue_size: usize) -> Self {
+        let (jobs, mut receiver) = mpsc::channel::<VerifyJob>(queue_size.max(1));
+        let workers = Arc::new(Semaphore::new(concurrency.max(1)));
+
+        tokio::spawn(async move {
+            while let Some(job) = receiver.recv().await {
+                let worker = match workers.clone().acquire_owned().await {
+                    Ok(worker) => worker,
+                    Err(_) => break,
+                };
+                tokio::task::spawn_blocking(move || {
+                    let result = job.signature.optimised_verify(&job.public_keys, &job.msg);
+                    // The submitter may have stopped waiting for the result
+                    let _ = job.reply.send(result);
+                    drop(worker);
+                });
+            }
+        });
+
+        VerifierPool { jobs }
+    }
+    // Submits the signature on the message over the ring to be verified, waiting for space
+    // in the queue, and returns the receiver of the result.
+    // Returns an error if the pool has stopped
+    pub async fn submit(
+        &self,
+        signature: Signature,
+        public_keys: PublicKeys,
+        msg: &[u8],
+    ) -> Result<VerifyReceiver, ClsagError> {
+        let (reply, receiver) = oneshot::channel();
+        let job = VerifyJob {
+            signature,
+            public_keys,
+            msg: msg.to_vec(),
+            reply,
+        };
+        self.jobs
+            .send(job)
+            .await
+            .map_err(|_| ClsagError::VerifierPoolClosed)?;
+        Ok(receiver)
+    }
+    // Verifies the signature on the message over the ring in the pool, waiting for the result.
+    // Returns an error if the signature does not verify, or if the pool stopped before
+    // the signature was verified
+    pub async fn verify(
+        &self,
+        signature: Signature,
+        public_keys: PublicKeys,
+        msg: &[u8],
+    ) -> Result<(), ClsagError> {
+        self.submit(signature, public_keys, msg)
+            .await?
+            .await
+            .map_err(|_| ClsagError::VerifierPoolClosed)?
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::*;
+
+    fn runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_verifier_pool() {
+        let msg = b"hello world";
+        let mut clsag = generate_clsag_with(4, 2);
+        clsag.add_member(generate_signer(2)).unwrap();
+
+        runtime().block_on(async {
+            let pool = VerifierPool::new(2, 4);
+
+            let mut receivers = Vec::new();
+            for _ in 0..6 {
+                let (signature, public_keys) = clsag.sign(msg).unwrap();
+                receivers.push(pool.submit(signature, public_keys, msg).await.unwrap());
+            }
+            for receiver in receivers {
+                assert!(receiver.await.unwrap().is_ok());
+            }
+
+            let (signature, public_keys) = clsag.sign(msg).unwrap();
+            match pool
+                .verify(signature, public_keys, b"another message")
+                .await
+            {
+                Err(ClsagError::ChallengeMismatch) => {}
+                _ => panic!("expected a challenge mismatch error"),
+            }
+        });
+    }
+}
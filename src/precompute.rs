@@ -0,0 +1,83 @@
+// Precomputed tables for the multiplications of the signer, behind the precompute feature.
+//
+// Every time it signs, the signer multiplies the same two points by secret scalars: the
+// generator by its nonce, and the hash of its first public key by its nonce and by each of
+// its private keys for the key images. With the feature, a `FixedBaseTable` of multiples of
+// the hash is built when the signer is created and kept with the member, and the basepoint
+// is multiplied with the table built into curve25519-dalek.
+//
+// A multiplication with a table is a fixed sequence of constant time lookups and additions,
+// with no doublings, so it is both faster and does less work on the secret path than
+// multiplying a point without a table, which curve25519-dalek also does in constant time.
+// Building a table costs about as much as a dozen multiplications and each table takes about
+// 30KB, so the feature only pays off for signers with many keys or which sign many times,
+// and is off by default. Compare both with
+// `cargo bench --features precompute,test-utils --bench precompute`.
+use crate::constants::BASEPOINT;
+use curve25519_dalek::constants::RISTRETTO_BASEPOINT_TABLE;
+use curve25519_dalek::ristretto::{RistrettoBasepointTable, RistrettoPoint};
+use curve25519_dalek::scalar::Scalar;
+use std::sync::Arc;
+
+// A table of multiples of a point, which is shared between the copies of the member holding it
+#[derive(Clone)]
+pub struct FixedBaseTable(Arc<RistrettoBasepointTable>);
+
+impl FixedBaseTable {
+    // Builds the table of multiples of the point
+    pub fn new(point: &RistrettoPoint) -> Self {
+        FixedBaseTable(Arc::new(RistrettoBasepointTable::create(point)))
+    }
+    // Returns the point the table was built for
+    pub fn point(&self) -> RistrettoPoint {
+        self.0.basepoint()
+    }
+    // Multiplies the point by the scalar
+    pub fn mul(&self, scalar: &Scalar) -> RistrettoPoint {
+        self.0.as_ref() * scalar
+    }
+}
+
+// Multiplies the generator by the scalar, with the table of the basepoint
+// when the generator is the basepoint
+pub(crate) fn mul_generator(scalar: &Scalar, generator: &RistrettoPoint) -> RistrettoPoint {
+    match *generator == BASEPOINT {
+        true => &RISTRETTO_BASEPOINT_TABLE * scalar,
+        false => scalar * generator,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::member::Member;
+    use crate::test_utils::*;
+
+    #[test]
+    fn test_table_matches_multiplication() {
+        let point = generate_rand_points(1)[0];
+        let table = FixedBaseTable::new(&point);
+        assert_eq!(point, table.point());
+
+        for scalar in generate_rand_scalars(4) {
+            assert_eq!(scalar * point, table.mul(&scalar));
+            assert_eq!(scalar * BASEPOINT, mul_generator(&scalar, &BASEPOINT));
+            assert_eq!(scalar * point, mul_generator(&scalar, &point));
+        }
+    }
+
+    #[test]
+    fn test_sign_with_tables() {
+        // The key images made with the table of the signer are the same as without it
+        let private_set = generate_private_set(2);
+        let signer = Member::new_signer(private_set.0.clone());
+        let expected_key_images = private_set.compute_key_images(&signer.hashed_pubkey());
+
+        let mut clsag = generate_clsag_with(3, 2);
+        clsag.add_member(signer).unwrap();
+
+        let (signature, public_keys) = clsag.sign(b"hello world").unwrap();
+        assert!(signature.verify(&public_keys, b"hello world").is_ok());
+        assert_eq!(expected_key_images, signature.key_images);
+    }
+}
@@ -0,0 +1,112 @@
+// Fetching the public keys of ring members from wherever outputs are stored.
+//
+// The crate does not know how outputs are stored, so a RingMemberProvider is implemented by
+// the integrator, for example over the RPC of their node or their own database,
+// and is used by the decoy selector and the ClsagBuilder to turn output indices into members.
+use crate::error::ClsagError;
+use crate::keys::PublicKey;
+use crate::member::{Member, MemberMetadata};
+
+// A RingMemberProvider returns the one-time public key of an output from its index
+pub trait RingMemberProvider {
+    // Returns the public key of the output at `index`.
+    // Returns an error if the output cannot be fetched
+    fn get_output(&self, index: u64) -> Result<PublicKey, ClsagError>;
+
+    // Returns the public keys of the outputs at each index, in the same order.
+    // By default the outputs are fetched one at a time, providers which can fetch
+    // several outputs at once, such as over RPC, should override this
+    fn get_outputs(&self, indices: &[u64]) -> Result<Vec<PublicKey>, ClsagError> {
+        indices
+            .iter()
+            .map(|&index| self.get_output(index))
+            .collect()
+    }
+
+    // Returns a decoy for each of the outputs at the given indices, in the same order,
+    // with the index of the output as the global index of the decoy
+    fn get_decoys(&self, indices: &[u64]) -> Result<Vec<Member>, ClsagError> {
+        self.get_outputs(indices)?
+            .iter()
+            .zip(indices)
+            .map(|(key, &index)| {
+                let metadata = MemberMetadata {
+                    global_index: Some(index),
+                    ..MemberMetadata::default()
+                };
+                Ok(Member::decoy_from_public_keys(&[*key])?.with_metadata(metadata))
+            })
+            .collect()
+    }
+}
+
+impl Member {
+    // Creates a decoy whose keys are the outputs at the given indices, fetched from the
+    // provider, with the index of the first output as the global index of the decoy.
+    // Returns an error if there are no indices or if an output cannot be fetched
+    pub fn decoy_from_provider<P: RingMemberProvider + ?Sized>(
+        provider: &P,
+        indices: &[u64],
+    ) -> Result<Self, ClsagError> {
+        let keys = provider.get_outputs(indices)?;
+        let metadata = MemberMetadata {
+            global_index: indices.first().copied(),
+            ..MemberMetadata::default()
+        };
+        Ok(Member::decoy_from_public_keys(&keys)?.with_metadata(metadata))
+    }
+}
+
+// The public key of every output, indexed by their position
+impl RingMemberProvider for [PublicKey] {
+    fn get_output(&self, index: u64) -> Result<PublicKey, ClsagError> {
+        self.get(index as usize)
+            .copied()
+            .ok_or(ClsagError::OutputUnavailable)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn test_get_outputs() {
+        let outputs = generate_rand_public_keys(5);
+
+        assert_eq!(outputs[3], outputs[..].get_output(3).unwrap());
+        assert_eq!(
+            vec![outputs[4], outputs[0], outputs[2]],
+            outputs[..].get_outputs(&[4, 0, 2]).unwrap()
+        );
+        match outputs[..].get_outputs(&[1, 5]) {
+            Err(ClsagError::OutputUnavailable) => {}
+            _ => panic!("expected an output unavailable error"),
+        }
+
+        let decoys = outputs[..].get_decoys(&[1, 2]).unwrap();
+        assert_eq!(2, decoys.len());
+        assert!(decoys.iter().all(|decoy| !decoy.is_signer()));
+        assert_eq!(Some(2), decoys[1].metadata().global_index);
+    }
+
+    #[test]
+    fn test_decoy_from_provider() {
+        let outputs = generate_rand_public_keys(5);
+
+        let decoy = Member::decoy_from_provider(&outputs[..], &[3, 1]).unwrap();
+        assert!(!decoy.is_signer());
+        assert_eq!(vec![outputs[3], outputs[1]], decoy.public_set.to_keys());
+        assert_eq!(Some(3), decoy.metadata().global_index);
+
+        match Member::decoy_from_provider(&outputs[..], &[]) {
+            Err(ClsagError::KeyCountMismatch) => {}
+            _ => panic!("expected a key count mismatch error"),
+        }
+        match Member::decoy_from_provider(&outputs[..], &[0, 5]) {
+            Err(ClsagError::OutputUnavailable) => {}
+            _ => panic!("expected an output unavailable error"),
+        }
+    }
+}
@@ -0,0 +1,418 @@
+// A Python module for key generation, signing and verification, behind the python feature.
+//
+// The module is built with PyO3 as the `clsag` extension module, for example with maturin:
+//
+//   maturin develop --features python,pyo3/extension-module
+//
+// or with `cargo rustc --release --features python,pyo3/extension-module --crate-type cdylib`,
+// copying the library to `clsag.so` on the Python path. Keys cross the boundary as `bytes` with
+// the same encodings as the C interface: private keys are 32 byte canonical scalars, public
+// keys and key images are 32 byte compressed ristretto points, and signatures use the
+// `Signature::to_bytes` encoding. A ring is a list of members, each a list of public keys.
+//
+// Errors are raised as subclasses of `clsag.ClsagError` carrying the message of the
+// `ClsagError` they were mapped from.
+use crate::clsag::{Clsag, PublicKeys};
+use crate::error::ClsagError;
+use crate::keys::{PublicKey, SecretKey};
+use crate::member::Member;
+use crate::signature::Signature;
+use exceptions::{InvalidKeyError, InvalidSignatureError, SignError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+const KEY_SIZE: usize = 32;
+
+// The exceptions of the module, named as they are in Python
+pub mod exceptions {
+    use pyo3::create_exception;
+    use pyo3::exceptions::PyException;
+
+    create_exception!(
+        clsag,
+        ClsagError,
+        PyException,
+        "The base class of every error raised by the module."
+    );
+    create_exception!(
+        clsag,
+        InvalidKeyError,
+        ClsagError,
+        "A private key is not canonical, or a public key is not a valid point."
+    );
+    create_exception!(
+        clsag,
+        SignError,
+        ClsagError,
+        "The ring could not be signed, as it is too small, has duplicate keys or has no signer."
+    );
+    create_exception!(
+        clsag,
+        InvalidSignatureError,
+        ClsagError,
+        "The signature could not be decoded, or does not verify."
+    );
+}
+
+// Raises the Python exception for the kind of the error
+impl From<ClsagError> for PyErr {
+    fn from(error: ClsagError) -> PyErr {
+        let message = error.to_string();
+        match error {
+            ClsagError::InvalidPoint | ClsagError::NonCanonicalScalar => {
+                InvalidKeyError::new_err(message)
+            }
+            ClsagError::EmptyRing
+            | ClsagError::RingTooSmall
+            | ClsagError::RingTooLarge
+            | ClsagError::NoSigner
+            | ClsagError::MultipleSignersNotSupported
+            | ClsagError::KeyCountMismatch
+            | ClsagError::DuplicateKey(_)
+            | ClsagError::DecoyEqualsSigner { .. }
+            | ClsagError::MemberKeyCountMismatch { .. }
+            | ClsagError::NotASigner => SignError::new_err(message),
+            ClsagError::DecodeError
+            | ClsagError::ChallengeMismatch
+            | ClsagError::RingSizeMismatch
+            | ClsagError::InvalidKeyImage
+            | ClsagError::IdentityKeyImage
+            | ClsagError::KeyImageMismatch => InvalidSignatureError::new_err(message),
+            _ => exceptions::ClsagError::new_err(message),
+        }
+    }
+}
+
+// Splits the bytes into 32 byte keys, refusing empty or partial input
+fn split_keys(bytes: &[u8]) -> Result<Vec<[u8; 32]>, ClsagError> {
+    if bytes.is_empty() || !bytes.len().is_multiple_of(KEY_SIZE) {
+        return Err(ClsagError::DecodeError);
+    }
+    Ok(bytes
+        .chunks_exact(KEY_SIZE)
+        .map(|chunk| {
+            let mut key = [0u8; 32];
+            key.copy_from_slice(chunk);
+            key
+        })
+        .collect())
+}
+
+// Checks that each key given as bytes is 32 bytes long
+fn keys_from_list(keys: &[Vec<u8>]) -> PyResult<Vec<[u8; 32]>> {
+    keys.iter()
+        .map(|key| {
+            let mut bytes = [0u8; 32];
+            if key.len() != KEY_SIZE {
+                return Err(InvalidKeyError::new_err("a key must be 32 bytes"));
+            }
+            bytes.copy_from_slice(key);
+            Ok(bytes)
+        })
+        .collect()
+}
+
+// Returns each public key of the ring as bytes, member by member
+fn ring_to_list<'py>(py: Python<'py>, ring: &PublicKeys) -> Vec<Vec<Bound<'py, PyBytes>>> {
+    ring.iter()
+        .map(|member| {
+            member
+                .iter()
+                .map(|key| PyBytes::new(py, key.as_bytes()))
+                .collect()
+        })
+        .collect()
+}
+
+// The private keys of a signer, one for each layer of the ring
+#[pyclass(name = "Signer", module = "clsag")]
+pub struct PySigner {
+    secret_keys: Vec<SecretKey>,
+}
+
+#[pymethods]
+impl PySigner {
+    #[new]
+    fn new(private_keys: Vec<Vec<u8>>) -> PyResult<Self> {
+        if private_keys.is_empty() {
+            return Err(InvalidKeyError::new_err("a signer needs a private key"));
+        }
+        let secret_keys = keys_from_list(&private_keys)?
+            .iter()
+            .map(SecretKey::from_bytes)
+            .collect::<Result<Vec<SecretKey>, ClsagError>>()?;
+        Ok(PySigner { secret_keys })
+    }
+
+    // Generates a signer with `num_keys` random private keys
+    #[staticmethod]
+    fn generate(num_keys: usize) -> PyResult<Self> {
+        if num_keys == 0 {
+            return Err(InvalidKeyError::new_err("a signer needs a private key"));
+        }
+        let mut rng = rand::thread_rng();
+        let secret_keys = (0..num_keys).map(|_| SecretKey::random(&mut rng)).collect();
+        Ok(PySigner { secret_keys })
+    }
+
+    // Decodes a signer from the concatenation of its private keys
+    #[staticmethod]
+    fn from_bytes(data: &[u8]) -> PyResult<Self> {
+        let secret_keys = split_keys(data)
+            .map_err(|_| InvalidKeyError::new_err("the keys must be a multiple of 32 bytes"))?
+            .iter()
+            .map(SecretKey::from_bytes)
+            .collect::<Result<Vec<SecretKey>, ClsagError>>()?;
+        Ok(PySigner { secret_keys })
+    }
+
+    fn __bytes__<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        let bytes: Vec<u8> = self
+            .secret_keys
+            .iter()
+            .flat_map(|key| key.to_bytes().to_vec())
+            .collect();
+        PyBytes::new(py, &bytes)
+    }
+
+    // The private keys are never printed, so that they cannot end up in logs
+    fn __repr__(&self) -> &'static str {
+        "Signer(..)"
+    }
+
+    // Returns the public keys of the signer
+    fn public_keys<'py>(&self, py: Python<'py>) -> Vec<Bound<'py, PyBytes>> {
+        self.secret_keys
+            .iter()
+            .map(|key| PyBytes::new(py, key.public_key().as_bytes()))
+            .collect()
+    }
+
+    // Returns the key images of the signer, the first of which links signatures
+    fn key_images<'py>(&self, py: Python<'py>) -> PyResult<Vec<Bound<'py, PyBytes>>> {
+        let signer = Member::signer_from_secret_keys(&self.secret_keys)?;
+        Ok(signer
+            .compute_key_images()?
+            .iter()
+            .map(|key_image| PyBytes::new(py, key_image.as_bytes()))
+            .collect())
+    }
+}
+
+// A clsag signature, which verifies over the ring it was signed over
+#[pyclass(name = "Signature", module = "clsag")]
+pub struct PySignature {
+    signature: Signature,
+}
+
+#[pymethods]
+impl PySignature {
+    // Decodes a signature, raising `InvalidSignatureError` if it is not a valid encoding
+    #[new]
+    fn new(data: &[u8]) -> PyResult<Self> {
+        Self::from_bytes(data)
+    }
+
+    #[staticmethod]
+    fn from_bytes(data: &[u8]) -> PyResult<Self> {
+        let signature = Signature::from_bytes(data)
+            .map_err(|error| InvalidSignatureError::new_err(error.to_string()))?;
+        Ok(PySignature { signature })
+    }
+
+    fn __bytes__<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.signature.to_bytes())
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Signature({})", hex_string(&self.signature.to_bytes()))
+    }
+
+    fn __eq__(&self, other: &PySignature) -> bool {
+        self.signature.to_bytes() == other.signature.to_bytes()
+    }
+
+    // Returns the key image the signature is linked by
+    fn key_image<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, self.signature.key_images[0].as_bytes())
+    }
+
+    // Returns true if both signatures were made by the same signer
+    fn links_with(&self, other: &PySignature) -> bool {
+        self.signature.key_images[0] == other.signature.key_images[0]
+    }
+
+    // Verifies the signature on the message over the ring, in the order it was signed in,
+    // raising `InvalidSignatureError` if it does not verify
+    fn verify(&self, ring: Vec<Vec<Vec<u8>>>, msg: &[u8]) -> PyResult<()> {
+        let ring = ring
+            .iter()
+            .map(|member| {
+                keys_from_list(member)?
+                    .iter()
+                    .map(|key| PublicKey::from_bytes(key).map_err(PyErr::from))
+                    .collect()
+            })
+            .collect::<PyResult<PublicKeys>>()?;
+        self.signature
+            .optimised_verify(&ring, msg)
+            .map_err(|error| InvalidSignatureError::new_err(error.to_string()))
+    }
+
+    // Returns true if the signature on the message verifies over the ring
+    fn is_valid(&self, py: Python, ring: Vec<Vec<Vec<u8>>>, msg: &[u8]) -> PyResult<bool> {
+        match self.verify(ring, msg) {
+            Ok(()) => Ok(true),
+            Err(error) if error.is_instance_of::<InvalidSignatureError>(py) => Ok(false),
+            Err(error) => Err(error),
+        }
+    }
+}
+
+// A ring of decoys and a signer, which hides the signer among the decoys
+#[pyclass(name = "Clsag", module = "clsag")]
+pub struct PyClsag {
+    clsag: Clsag,
+}
+
+#[pymethods]
+impl PyClsag {
+    #[new]
+    fn new() -> Self {
+        PyClsag {
+            clsag: Clsag::new(),
+        }
+    }
+
+    // Adds a decoy with the given public keys to the ring
+    fn add_decoy(&mut self, public_keys: Vec<Vec<u8>>) -> PyResult<()> {
+        let decoy = Member::from_compressed_bytes(&keys_from_list(&public_keys)?)?;
+        Ok(self.clsag.add_member(decoy)?)
+    }
+
+    // Adds the signer to the ring. A ring has exactly one signer
+    fn add_signer(&mut self, signer: &PySigner) -> PyResult<()> {
+        let signer = Member::signer_from_secret_keys(&signer.secret_keys)?;
+        Ok(self.clsag.add_member(signer)?)
+    }
+
+    // Signs the message, returning the signature and the ring in the shuffled
+    // order it was signed in, which is the ring the signature verifies over
+    fn sign<'py>(
+        &self,
+        py: Python<'py>,
+        msg: &[u8],
+    ) -> PyResult<(PySignature, Vec<Vec<Bound<'py, PyBytes>>>)> {
+        let (signature, ring) = self.clsag.sign(msg)?;
+        Ok((PySignature { signature }, ring_to_list(py, &ring)))
+    }
+}
+
+// Writes the bytes as lowercase hex
+fn hex_string(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[pymodule]
+#[pyo3(name = "clsag")]
+fn python_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = m.py();
+    m.add("KEY_SIZE", KEY_SIZE)?;
+    m.add("ClsagError", py.get_type::<exceptions::ClsagError>())?;
+    m.add("InvalidKeyError", py.get_type::<InvalidKeyError>())?;
+    m.add("SignError", py.get_type::<SignError>())?;
+    m.add(
+        "InvalidSignatureError",
+        py.get_type::<InvalidSignatureError>(),
+    )?;
+    m.add_class::<PySigner>()?;
+    m.add_class::<PySignature>()?;
+    m.add_class::<PyClsag>()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pyo3::types::PyDict;
+    use std::ffi::CString;
+
+    // Runs the Python code with the module imported as `clsag`
+    fn run_python(code: &str) {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let module = PyModule::new(py, "clsag").unwrap();
+            python_module(&module).unwrap();
+            let globals = PyDict::new(py);
+            globals.set_item("clsag", module).unwrap();
+            let code = CString::new(code).unwrap();
+            if let Err(error) = py.run(&code, Some(&globals), None) {
+                error.print(py);
+                panic!("the python code raised an exception");
+            }
+        });
+    }
+
+    #[test]
+    fn test_python_sign_and_verify() {
+        run_python(
+            r#"
+signer = clsag.Signer.generate(2)
+assert repr(signer) == "Signer(..)"
+assert clsag.Signer.from_bytes(bytes(signer)).public_keys() == signer.public_keys()
+
+ring = clsag.Clsag()
+for _ in range(10):
+    ring.add_decoy(clsag.Signer.generate(2).public_keys())
+ring.add_signer(signer)
+signature, members = ring.sign(b"hello world")
+assert len(members) == 11 and all(len(member) == 2 for member in members)
+
+signature.verify(members, b"hello world")
+assert signature.is_valid(members, b"hello world")
+assert not signature.is_valid(members, b"another message")
+assert signature.key_image() == signer.key_images()[0]
+
+decoded = clsag.Signature.from_bytes(bytes(signature))
+assert decoded == signature and decoded.links_with(signature)
+"#,
+        );
+    }
+
+    #[test]
+    fn test_python_errors() {
+        run_python(
+            r#"
+def raises(error, function, *args):
+    try:
+        function(*args)
+    except error as raised:
+        assert isinstance(raised, clsag.ClsagError) and str(raised)
+        return
+    raise AssertionError("expected {}".format(error.__name__))
+
+raises(clsag.InvalidKeyError, clsag.Signer, [b"\xff" * 32])
+raises(clsag.InvalidKeyError, clsag.Signer, [b"\x00" * 31])
+raises(clsag.InvalidKeyError, clsag.Signer.from_bytes, b"\x00" * 33)
+raises(clsag.InvalidSignatureError, clsag.Signature.from_bytes, b"\x00" * 10)
+
+signer = clsag.Signer.generate(1)
+ring = clsag.Clsag()
+raises(clsag.SignError, ring.sign, b"msg")
+ring.add_signer(signer)
+raises(clsag.SignError, ring.add_decoy, signer.public_keys())
+raises(clsag.SignError, ring.add_decoy, clsag.Signer.generate(2).public_keys())
+two_signers = clsag.Clsag()
+two_signers.add_signer(clsag.Signer.generate(1))
+two_signers.add_signer(clsag.Signer.generate(1))
+raises(clsag.SignError, two_signers.sign, b"msg")
+
+for _ in range(10):
+    ring.add_decoy(clsag.Signer.generate(1).public_keys())
+signature, members = ring.sign(b"msg")
+raises(clsag.InvalidSignatureError, signature.verify, members, b"other")
+raises(clsag.InvalidSignatureError, signature.verify, members[:1], b"msg")
+"#,
+        );
+    }
+}
@@ -0,0 +1,177 @@
+// An integration point for range proofs over the amounts of Pedersen commitments.
+//
+// A clsag signature over a ring of commitments proves that the pseudo output commits to the
+// same amount as the signers commitment, but not that the amount is in range. A transaction
+// built with the crate also needs a range proof for the pseudo output, so that the amount
+// cannot be negative modulo the group order. The range proof system is left to the integrator,
+// by implementing `RangeProofSystem` for it.
+//
+// The commitments are `amount * H + blinding * G` as in the commitment module, so an
+// implementation over bulletproofs uses `commitment::value_generator` as the value generator
+// and the basepoint as the blinding generator. The range proof is made with the transcript
+// from `range_transcript`, which binds it to the message the ring signature is made over.
+use crate::error::ClsagError;
+use curve25519_dalek::ristretto::RistrettoPoint;
+use curve25519_dalek::scalar::Scalar;
+use merlin::Transcript;
+
+// A system of range proofs for the amounts of Pedersen commitments
+pub trait RangeProofSystem {
+    // The range proof produced by the system
+    type Proof;
+
+    // Proves that the commitment to the amount with the blinding factor is in range.
+    // Returns an error if the amount is out of range
+    fn prove(
+        &self,
+        transcript: &mut Transcript,
+        amount: u64,
+        blinding: &Scalar,
+    ) -> Result<Self::Proof, ClsagError>;
+
+    // Verifies that the commitment is to an amount in range.
+    // Returns an error if the proof does not verify
+    fn verify(
+        &self,
+        transcript: &mut Transcript,
+        commitment: &RistrettoPoint,
+        proof: &Self::Proof,
+    ) -> Result<(), ClsagError>;
+}
+
+// Returns the transcript a range proof alongside a signature on the message is made with
+pub fn range_transcript(msg: &[u8]) -> Transcript {
+    let mut transcript = Transcript::new(b"clsag_range_proof");
+    transcript.append_message(b"msg", msg);
+    transcript
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::clsag::{Clsag, PublicKeys};
+    use crate::commitment::commit;
+    use crate::member::Member;
+    use crate::policy::RingPolicy;
+    use crate::test_utils::*;
+
+    // A range proof system which reveals the opening of the commitment, for testing
+    struct RevealedRange {
+        bits: u32,
+    }
+
+    impl RangeProofSystem for RevealedRange {
+        type Proof = (u64, Scalar);
+
+        fn prove(
+            &self,
+            _transcript: &mut Transcript,
+            amount: u64,
+            blinding: &Scalar,
+        ) -> Result<Self::Proof, ClsagError> {
+            if amount >> self.bits != 0 {
+                return Err(ClsagError::InvalidRangeProof);
+            }
+            Ok((amount, *blinding))
+        }
+
+        fn verify(
+            &self,
+            _transcript: &mut Transcript,
+            commitment: &RistrettoPoint,
+            proof: &Self::Proof,
+        ) -> Result<(), ClsagError> {
+            let (amount, blinding) = proof;
+            if amount >> self.bits != 0 || commit(*amount, blinding) != *commitment {
+                return Err(ClsagError::InvalidRangeProof);
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_sign_with_range_proof() {
+        let msg = b"hello world";
+        let system = RevealedRange { bits: 32 };
+
+        let blinding = generate_rand_scalars(1)[0];
+        let pseudo_blinding = generate_rand_scalars(1)[0];
+        let pseudo_out = commit(100, &pseudo_blinding);
+
+        let mut clsag = Clsag::with_pseudo_out(pseudo_out);
+        clsag.set_ring_policy(RingPolicy::permissive());
+        let mut commitments = Vec::new();
+        for decoy in generate_decoys(4, 1) {
+            let commitment = commit(42, &generate_rand_scalars(1)[0]);
+            commitments.push(commitment.compress());
+            clsag
+                .add_member(Member::new_decoy_with_commitment(
+                    decoy.public_set.points().to_vec(),
+                    commitment,
+                ))
+                .unwrap();
+        }
+        let commitment = commit(100, &blinding);
+        commitments.push(commitment.compress());
+        clsag
+            .add_member(Member::new_signer_with_commitment(
+                generate_rand_scalars(1),
+                commitment,
+                blinding - pseudo_blinding,
+            ))
+            .unwrap();
+        // Keep the ring in order, so that the commitments line up with the members
+        clsag.set_shuffle_members(false);
+
+        let (signature, _, proof) = clsag
+            .sign_with_range_proof(msg, &system, 100, &pseudo_blinding)
+            .unwrap();
+        let public_keys: PublicKeys = member_public_keys(&clsag);
+        assert!(signature
+            .verify_with_range_proof(
+                &public_keys,
+                &commitments,
+                &pseudo_out,
+                msg,
+                &system,
+                &proof
+            )
+            .is_ok());
+
+        // A range proof for another commitment does not verify alongside the signature
+        let other_proof = (7, pseudo_blinding);
+        match signature.verify_with_range_proof(
+            &public_keys,
+            &commitments,
+            &pseudo_out,
+            msg,
+            &system,
+            &other_proof,
+        ) {
+            Err(ClsagError::InvalidRangeProof) => {}
+            _ => panic!("expected an invalid range proof error"),
+        }
+
+        // The amount and blinding factor must open the pseudo output
+        match clsag.sign_with_range_proof(msg, &system, 99, &pseudo_blinding) {
+            Err(ClsagError::PseudoOutMismatch) => {}
+            _ => panic!("expected a pseudo output mismatch error"),
+        }
+        // A ring without commitments has no amount to prove the range of
+        let mut plain = generate_clsag_with(4, 1);
+        plain.add_member(generate_signer(1)).unwrap();
+        match plain.sign_with_range_proof(msg, &system, 100, &pseudo_blinding) {
+            Err(ClsagError::PseudoOutMismatch) => {}
+            _ => panic!("expected a pseudo output mismatch error"),
+        }
+    }
+
+    // Returns the public keys of the members, without the commitments to zero
+    fn member_public_keys(clsag: &Clsag) -> PublicKeys {
+        clsag
+            .members()
+            .iter()
+            .map(|member| member.public_set.to_keys())
+            .collect()
+    }
+}
@@ -0,0 +1,243 @@
+// Message types for signing on a remote signer, such as an HSM or an air gapped machine,
+// which holds the signers keys and never hands them to the application building the ring.
+//
+// The application hashes the message and sends a SignRequest holding the ring and the hash,
+// so the remote signer never needs the message itself. The request also holds a commitment
+// to the public keys of the signer it is meant for, so that a request sent to the wrong
+// signer is refused rather than signed. The remote signer returns a SignResponse holding
+// the signature, which verifies over the hash of the message with `Signature::verify_digest`.
+//
+// The remote signer signs over the ring in the order it is given, so the application
+// should shuffle the ring before sending the request.
+use crate::clsag::{Clsag, PublicKeys};
+use crate::digest::hash_message;
+use crate::error::ClsagError;
+use crate::keys::{PublicKey, SecretKey};
+use crate::member::Member;
+use crate::policy::RingPolicy;
+use crate::signature::Signature;
+use merlin::Transcript;
+
+// Returns the commitment to the signer of a request, which binds
+// the signers public keys to the ring and the hash of the message
+fn signer_commitment(
+    ring: &[Vec<PublicKey>],
+    msg_hash: &[u8; 64],
+    signer_keys: &[PublicKey],
+) -> [u8; 32] {
+    let mut transcript = Transcript::new(b"clsag_signer_commitment");
+    for key in ring.iter().flatten() {
+        transcript.append_message(b"ring_key", key.as_bytes());
+    }
+    transcript.append_message(b"msg_hash", msg_hash);
+    for key in signer_keys {
+        transcript.append_message(b"signer_key", key.as_bytes());
+    }
+
+    let mut commitment = [0u8; 32];
+    transcript.challenge_bytes(b"commitment", &mut commitment);
+    commitment
+}
+
+// A request for a remote signer to sign the hash of a message over a ring
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SignRequest {
+    ring: PublicKeys,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::digest"))]
+    msg_hash: [u8; 64],
+    signer_commitment: [u8; 32],
+}
+
+// The response of a remote signer, holding the signature over the ring in the request
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SignResponse {
+    pub signature: Signature,
+}
+
+impl SignRequest {
+    // Creates a request to sign the message over the ring, for the signer
+    // with the given public keys, who must be a member of the ring
+    pub fn new(ring: PublicKeys, msg: &[u8], signer_keys: &[PublicKey]) -> Self {
+        Self::from_hash(ring, hash_message(msg), signer_keys)
+    }
+    // Creates a request to sign a message which has already been hashed,
+    // with `hash_message` or `hash_reader` in the digest module
+    pub fn from_hash(ring: PublicKeys, msg_hash: [u8; 64], signer_keys: &[PublicKey]) -> Self {
+        let signer_commitment = signer_commitment(&ring, &msg_hash, signer_keys);
+
+        SignRequest {
+            ring,
+            msg_hash,
+            signer_commitment,
+        }
+    }
+    // Returns the ring to sign over, in the order it is signed in
+    pub fn ring(&self) -> &[Vec<PublicKey>] {
+        &self.ring
+    }
+    // Returns the hash of the message to sign
+    pub fn msg_hash(&self) -> &[u8; 64] {
+        &self.msg_hash
+    }
+    // Returns true if the request is meant for the signer with the given public keys
+    pub fn is_for_signer(&self, signer_keys: &[PublicKey]) -> bool {
+        signer_commitment(&self.ring, &self.msg_hash, signer_keys) == self.signer_commitment
+    }
+}
+
+impl SignResponse {
+    // Verifies the signature in the response against the ring and message hash of the request
+    pub fn verify(&self, request: &SignRequest) -> Result<(), ClsagError> {
+        self.signature
+            .verify_digest(&request.ring, &request.msg_hash)
+    }
+}
+
+// A RemoteSigner signs requests with keys the caller never sees,
+// for example by sending the request to an HSM and waiting for its response
+pub trait RemoteSigner {
+    // Signs the request, returning an error if the request cannot be signed
+    fn sign(&self, request: &SignRequest) -> Result<SignResponse, ClsagError>;
+}
+
+// A MockSigner is a remote signer which holds the signers keys in process.
+// It behaves as a remote signer should, and is meant for testing applications
+// which use a remote signer without needing one
+#[derive(Debug, Clone)]
+pub struct MockSigner {
+    secret_keys: Vec<SecretKey>,
+    policy: RingPolicy,
+}
+
+impl MockSigner {
+    // Creates a signer holding the given secret keys, which signs rings
+    // that satisfy the default ring policy
+    pub fn new(secret_keys: Vec<SecretKey>) -> Self {
+        MockSigner {
+            secret_keys,
+            policy: RingPolicy::default(),
+        }
+    }
+    // Sets the policy the rings in requests must satisfy
+    pub fn set_ring_policy(&mut self, policy: RingPolicy) {
+        self.policy = policy;
+    }
+    // Returns the public keys of the signer
+    pub fn public_keys(&self) -> Vec<PublicKey> {
+        self.secret_keys
+            .iter()
+            .map(|key| key.public_key())
+            .collect()
+    }
+}
+
+impl RemoteSigner for MockSigner {
+    // Refuses requests which are not meant for this signer, or where the signer is not
+    // in the ring, and otherwise signs the hash of the message over the ring as it is ordered
+    fn sign(&self, request: &SignRequest) -> Result<SignResponse, ClsagError> {
+        let public_keys = self.public_keys();
+        if !request.is_for_signer(&public_keys) {
+            return Err(ClsagError::NotASigner);
+        }
+        let signer_index = request
+            .ring
+            .iter()
+            .position(|keys| *keys == public_keys)
+            .ok_or(ClsagError::NotASigner)?;
+
+        let mut clsag = Clsag::new();
+        clsag.set_ring_policy(self.policy);
+        clsag.set_shuffle_members(false);
+        for (index, keys) in request.ring.iter().enumerate() {
+            let member = match index == signer_index {
+                true => Member::signer_from_secret_keys(&self.secret_keys)?,
+                false => Member::decoy_from_public_keys(keys)?,
+            };
+            clsag.add_member(member)?;
+        }
+
+        let (signature, _) = clsag.sign_digest(&request.msg_hash)?;
+        Ok(SignResponse { signature })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::*;
+
+    fn ring_with_signer(signer_keys: &[PublicKey], signer_index: usize) -> PublicKeys {
+        let mut ring: PublicKeys = (0..10)
+            .map(|_| generate_rand_public_keys(signer_keys.len()))
+            .collect();
+        ring.insert(signer_index, signer_keys.to_vec());
+        ring
+    }
+
+    #[test]
+    fn test_remote_sign() {
+        let mut rng = rand::thread_rng();
+        let signer = MockSigner::new(vec![
+            SecretKey::random(&mut rng),
+            SecretKey::random(&mut rng),
+        ]);
+        let ring = ring_with_signer(&signer.public_keys(), 7);
+        let msg = b"hello world";
+
+        let request = SignRequest::new(ring.clone(), msg, &signer.public_keys());
+        let response = signer.sign(&request).unwrap();
+
+        assert!(response.verify(&request).is_ok());
+        assert!(response
+            .signature
+            .verify_digest(&ring, &hash_message(msg))
+            .is_ok());
+        assert!(response.signature.verify(&ring, msg).is_err());
+        assert!(response
+            .signature
+            .verify(&ring, &hash_message(msg))
+            .is_err());
+    }
+
+    #[test]
+    fn test_remote_sign_refuses_other_signers() {
+        let mut rng = rand::thread_rng();
+        let signer = MockSigner::new(vec![SecretKey::random(&mut rng)]);
+        let other = MockSigner::new(vec![SecretKey::random(&mut rng)]);
+        let ring = ring_with_signer(&signer.public_keys(), 0);
+
+        // The request is meant for another signer
+        let request = SignRequest::new(ring.clone(), b"msg", &other.public_keys());
+        match signer.sign(&request) {
+            Err(ClsagError::NotASigner) => {}
+            _ => panic!("expected a not a signer error"),
+        }
+
+        // The signer is not in the ring
+        let request = SignRequest::new(ring, b"msg", &other.public_keys());
+        match other.sign(&request) {
+            Err(ClsagError::NotASigner) => {}
+            _ => panic!("expected a not a signer error"),
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let mut rng = rand::thread_rng();
+        let signer = MockSigner::new(vec![SecretKey::random(&mut rng)]);
+        let ring = ring_with_signer(&signer.public_keys(), 2);
+
+        let request = SignRequest::new(ring, b"msg", &signer.public_keys());
+        let json = serde_json::to_string(&request).unwrap();
+        let decoded: SignRequest = serde_json::from_str(&json).unwrap();
+        assert_eq!(request, decoded);
+
+        let response = signer.sign(&decoded).unwrap();
+        let json = serde_json::to_string(&response).unwrap();
+        let decoded: SignResponse = serde_json::from_str(&json).unwrap();
+        assert!(decoded.verify(&request).is_ok());
+    }
+}
@@ -0,0 +1,124 @@
+// A report of every step of verifying a signature, returned by `Signature::verify_detailed`.
+//
+// Verification recomputes the challenge of each member from the challenge of the member
+// before it, starting from the challenge in the signature, and the signature is valid when
+// the challenge computed by the last member is the challenge it started from. A signature
+// which does not verify only shows that the chain did not close, as the signature holds no
+// challenge to check the others against. The report therefore points at the member whose
+// challenge fails to close the chain with `diverged_at`, and holds the whole chain, which can
+// be compared with the chain computed by another implementation with `diverged_from` to find
+// the first member where the two disagree.
+use crate::error::ClsagError;
+use curve25519_dalek::scalar::Scalar;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerificationReport {
+    // The challenges of the chain, starting with the challenge in the signature.
+    // The challenge at index i + 1 is computed from member i, so a full chain has one more
+    // challenge than the ring has members. The chain is empty if it could not be computed,
+    // because the ring or the signature could not be decoded
+    pub challenges: Vec<Scalar>,
+    // Whether every key image is a valid point other than the identity
+    pub key_images_valid: bool,
+    // The index of the first member whose recomputed challenge fails to close the chain,
+    // which is the last member, as the chain can only be checked where it closes.
+    // None if the chain closes or could not be computed
+    pub diverged_at: Option<usize>,
+    // The result `optimised_verify` returns for the signature
+    pub result: Result<(), ClsagError>,
+}
+
+impl VerificationReport {
+    // Returns true if the signature is valid
+    pub fn is_valid(&self) -> bool {
+        self.result.is_ok()
+    }
+    // Returns the index of the first challenge where the chain differs from the given chain,
+    // such as the chain computed by another implementation over the same signature.
+    // A difference at index i + 1 means the implementations disagree on member i
+    pub fn diverged_from(&self, challenges: &[Scalar]) -> Option<usize> {
+        let first_difference = self
+            .challenges
+            .iter()
+            .zip(challenges)
+            .position(|(ours, theirs)| ours != theirs);
+
+        match first_difference {
+            Some(index) => Some(index),
+            None if self.challenges.len() != challenges.len() => {
+                Some(self.challenges.len().min(challenges.len()))
+            }
+            None => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::keys::KeyImage;
+    use crate::test_utils::*;
+    use curve25519_dalek::ristretto::RistrettoPoint;
+    use curve25519_dalek::traits::Identity;
+
+    #[test]
+    fn test_verify_detailed() {
+        let num_keys = 2;
+        let msg = b"hello world";
+
+        let mut clsag = generate_clsag_with(5, num_keys);
+        clsag.add_member(generate_signer(num_keys)).unwrap();
+        let (mut signature, public_keys) = clsag.sign(msg).unwrap();
+
+        let report = signature.verify_detailed(&public_keys, msg);
+        assert!(report.is_valid());
+        assert!(report.key_images_valid);
+        assert_eq!(public_keys.len() + 1, report.challenges.len());
+        assert_eq!(report.challenges[0], *report.challenges.last().unwrap());
+        assert_eq!(None, report.diverged_at);
+
+        // A chain which differs at a member is found by comparing the chains
+        let mut other = report.challenges.clone();
+        other[3] += Scalar::one();
+        assert_eq!(Some(3), report.diverged_from(&other));
+        assert_eq!(Some(2), report.diverged_from(&report.challenges[..2]));
+        assert_eq!(None, report.diverged_from(&report.challenges));
+
+        // A chain over another message does not close
+        let report = signature.verify_detailed(&public_keys, b"another message");
+        assert_eq!(Err(ClsagError::ChallengeMismatch), report.result);
+        assert_eq!(public_keys.len() + 1, report.challenges.len());
+        assert_ne!(report.challenges[0], *report.challenges.last().unwrap());
+        assert_eq!(Some(public_keys.len() - 1), report.diverged_at);
+
+        // Every challenge but the first is computed from the message, so the chain over
+        // another message differs from the valid chain from the first member onwards
+        let valid = signature.verify_detailed(&public_keys, msg);
+        assert_eq!(Some(1), report.diverged_from(&valid.challenges));
+
+        // The chain is still computed when a key image is the identity, and the key image
+        // is reported before the chain, as `optimised_verify` reports it
+        signature.key_images[1] = KeyImage(RistrettoPoint::identity().compress());
+        let report = signature.verify_detailed(&public_keys, msg);
+        assert!(!report.key_images_valid);
+        assert!(!report.is_valid());
+        assert_eq!(public_keys.len() + 1, report.challenges.len());
+        assert_eq!(
+            signature.optimised_verify(&public_keys, msg).err(),
+            report.result.err()
+        );
+        assert_eq!(Err(ClsagError::IdentityKeyImage), report.result);
+        assert_eq!(Some(public_keys.len() - 1), report.diverged_at);
+
+        // Nothing can be computed over a ring of the wrong size
+        let report = signature.verify_detailed(&public_keys[1..], msg);
+        assert_eq!(Err(ClsagError::RingSizeMismatch), report.result);
+        assert!(report.challenges.is_empty());
+        assert_eq!(None, report.diverged_at);
+
+        // Nor over a signature larger than the verification limits
+        signature.responses = vec![Scalar::zero(); crate::policy::DEFAULT_MAX_RING_SIZE + 1];
+        let report = signature.verify_detailed(&public_keys, msg);
+        assert_eq!(Err(ClsagError::RingTooLarge), report.result);
+    }
+}
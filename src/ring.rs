@@ -0,0 +1,482 @@
+use crate::clsag::{aggregation_transcript, BatchError, PublicKeys};
+use crate::constants::BASEPOINT;
+use crate::error::ClsagError;
+use crate::hash::{DefaultHash, DomainSeparated, HashToPoint};
+use crate::keys::PublicKey;
+use crate::member::ring_round_transcript;
+use crate::metrics::{Metrics, MetricsHook};
+use crate::policy::VerificationLimits;
+use crate::signature::Signature;
+use crate::transcript::{default_transcript, SigningTranscript};
+use curve25519_dalek::ristretto::RistrettoPoint;
+use merlin::Transcript;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+// A PreparedRing holds the public keys of a ring, along with everything verification
+// computes from the keys alone: the decompressed keys, the hash of each members first key
+// and the bytes of the ring which are hashed into every challenge.
+// Verifying several signatures over the same ring, or the same signature more than once,
+// with a PreparedRing skips decompressing and hashing the keys for every verification.
+#[derive(Debug, Clone)]
+pub struct PreparedRing {
+    public_keys: PublicKeys,
+
+    points: Vec<Vec<RistrettoPoint>>,
+
+    // The hash of the first public key of each member
+    hashed_pubkeys: Vec<RistrettoPoint>,
+
+    pubkey_matrix: Vec<u8>,
+
+    // The generator the keys are multiples of, which is the basepoint unless it has been set
+    generator: RistrettoPoint,
+
+    // The metrics every signature verified over the ring is reported to, if any are installed
+    metrics: MetricsHook,
+}
+
+impl PreparedRing {
+    // Prepares the ring of public keys, in ring order.
+    // Returns an error if the members do not all have the same, non-zero number of keys,
+    // if the ring is larger than the default verification limits allow,
+    // or if one of the keys is not a valid point
+    pub fn new(public_keys: &[Vec<PublicKey>]) -> Result<PreparedRing, ClsagError> {
+        Self::with_hash_to_point(public_keys, &DomainSeparated::<DefaultHash>::new())
+    }
+    // Prepares the ring as `new` does, hashing the first public key of each member
+    // with the given construction, which must be the one the ring was signed with
+    pub fn with_hash_to_point<H: HashToPoint + Sync + ?Sized>(
+        public_keys: &[Vec<PublicKey>],
+        hash_to_point: &H,
+    ) -> Result<PreparedRing, ClsagError> {
+        Self::with_limits(public_keys, hash_to_point, &VerificationLimits::default())
+    }
+    // Prepares the ring as `with_hash_to_point` does, within the given limits instead of
+    // the default ones. The size of the ring is checked before any key is decompressed
+    pub fn with_limits<H: HashToPoint + Sync + ?Sized>(
+        public_keys: &[Vec<PublicKey>],
+        hash_to_point: &H,
+        limits: &VerificationLimits,
+    ) -> Result<PreparedRing, ClsagError> {
+        check_shape(public_keys, limits)?;
+
+        // Each member is prepared independently, so with the `parallel`
+        // feature the members are prepared concurrently
+        #[cfg(feature = "parallel")]
+        let members = public_keys.par_iter();
+        #[cfg(not(feature = "parallel"))]
+        let members = public_keys.iter();
+
+        let prepared: Vec<(Vec<RistrettoPoint>, RistrettoPoint)> = members
+            .map(|keys| {
+                let points = keys
+                    .iter()
+                    .map(|key| key.decompress().ok_or(ClsagError::InvalidPoint))
+                    .collect::<Result<Vec<RistrettoPoint>, ClsagError>>()?;
+                let hashed_pubkey = hash_to_point.hash_to_point(keys[0].as_bytes());
+
+                Ok((points, hashed_pubkey))
+            })
+            .collect::<Result<_, ClsagError>>()?;
+        let (points, hashed_pubkeys) = prepared.into_iter().unzip();
+
+        Ok(Self::from_parts(public_keys, points, hashed_pubkeys))
+    }
+    // Prepares the ring as `new` does, taking the decompressed keys and the hashes of the
+    // first keys from the cache when they are in it, and adding them to it when they are not
+    fn with_cache(
+        public_keys: &[Vec<PublicKey>],
+        cache: &mut KeyCache,
+    ) -> Result<PreparedRing, ClsagError> {
+        check_shape(public_keys, &VerificationLimits::default())?;
+
+        let mut points = Vec::with_capacity(public_keys.len());
+        let mut hashed_pubkeys = Vec::with_capacity(public_keys.len());
+        for keys in public_keys {
+            points.push(
+                keys.iter()
+                    .map(|key| cache.point(key))
+                    .collect::<Result<Vec<RistrettoPoint>, ClsagError>>()?,
+            );
+            hashed_pubkeys.push(cache.hashed_pubkey(&keys[0]));
+        }
+
+        Ok(Self::from_parts(public_keys, points, hashed_pubkeys))
+    }
+    fn from_parts(
+        public_keys: &[Vec<PublicKey>],
+        points: Vec<Vec<RistrettoPoint>>,
+        hashed_pubkeys: Vec<RistrettoPoint>,
+    ) -> PreparedRing {
+        let pubkey_matrix = public_keys
+            .iter()
+            .flatten()
+            .flat_map(|key| key.to_bytes().to_vec())
+            .collect();
+
+        PreparedRing {
+            public_keys: public_keys.to_vec(),
+            points,
+            hashed_pubkeys,
+            pubkey_matrix,
+            generator: BASEPOINT,
+            metrics: MetricsHook::default(),
+        }
+    }
+    // Sets the generator the keys are multiples of, which must be the generator
+    // the ring was signed over with `Clsag::with_generator`
+    pub fn set_generator(&mut self, generator: RistrettoPoint) {
+        self.generator = generator;
+    }
+    // Returns the generator the keys are multiples of
+    pub fn generator(&self) -> &RistrettoPoint {
+        &self.generator
+    }
+    // Installs the metrics which are told how long each signature verified over the ring
+    // took, and why it failed if it did not verify
+    pub fn set_metrics(&mut self, metrics: Arc<dyn Metrics>) {
+        self.metrics = MetricsHook::new(metrics);
+    }
+    // Returns the metrics installed on the ring
+    pub(crate) fn metrics(&self) -> &MetricsHook {
+        &self.metrics
+    }
+    // Returns the public keys of every member in ring order
+    pub fn public_keys(&self) -> &[Vec<PublicKey>] {
+        &self.public_keys
+    }
+    // Returns the number of members in the ring
+    pub fn len(&self) -> usize {
+        self.public_keys.len()
+    }
+    // Returns true if the ring has no members, which a prepared ring never does
+    pub fn is_empty(&self) -> bool {
+        self.public_keys.is_empty()
+    }
+    // Returns the number of keys each member of the ring has
+    pub fn num_layers(&self) -> usize {
+        self.public_keys[0].len()
+    }
+    // Returns the decompressed keys of every member
+    pub(crate) fn points(&self) -> &[Vec<RistrettoPoint>] {
+        &self.points
+    }
+    // Returns the hash of the first public key of every member
+    pub(crate) fn hashed_pubkeys(&self) -> &[RistrettoPoint] {
+        &self.hashed_pubkeys
+    }
+    // Returns the bytes of every public key in ring order
+    pub(crate) fn pubkey_matrix(&self) -> &[u8] {
+        &self.pubkey_matrix
+    }
+
+    // Verifies several signatures over the ring and the same message, such as the inputs
+    // of a transaction which all use the same ring. The keys are decompressed and hashed
+    // when the ring is prepared, and the ring is hashed into the transcripts once for
+    // every signature, instead of once for every challenge of every signature.
+    // Returns the position of every signature which fails to verify, as `batch_verify` does
+    pub fn verify_many(&self, signatures: &[Signature], msg: &[u8]) -> Result<(), BatchError> {
+        self.verify_many_with_transcript(&default_transcript(), signatures, msg)
+    }
+    // Verifies several signatures as `verify_many` does, for signatures which were
+    // produced with `Clsag::sign_with_transcript`
+    pub fn verify_many_with_transcript<T: SigningTranscript>(
+        &self,
+        transcript: &T,
+        signatures: &[Signature],
+        msg: &[u8],
+    ) -> Result<(), BatchError> {
+        let transcripts = RingTranscripts::new(transcript, self);
+        let failures: Vec<(usize, ClsagError)> = signatures
+            .iter()
+            .enumerate()
+            .filter_map(|(index, signature)| {
+                signature
+                    .verify_with_ring_transcripts(&transcripts, self, msg, true)
+                    .err()
+                    .map(|err| (index, err))
+            })
+            .collect();
+
+        BatchError::from_failures(failures)
+    }
+}
+
+// Checks that the ring is within the limits, and that every member has the same, non-zero
+// number of keys
+fn check_shape(
+    public_keys: &[Vec<PublicKey>],
+    limits: &VerificationLimits,
+) -> Result<(), ClsagError> {
+    let num_layers = match public_keys.first() {
+        Some(keys) => keys.len(),
+        None => return Err(ClsagError::EmptyRing),
+    };
+    limits.check(public_keys.len(), num_layers)?;
+    if num_layers == 0 || public_keys.iter().any(|keys| keys.len() != num_layers) {
+        return Err(ClsagError::KeyCountMismatch);
+    }
+    Ok(())
+}
+
+// The decompressed keys and the hashes of the first keys of members, shared between
+// the rings of a batch, as the rings of a batch usually have members in common
+#[derive(Default)]
+struct KeyCache {
+    points: HashMap<PublicKey, RistrettoPoint>,
+    hashed_pubkeys: HashMap<PublicKey, RistrettoPoint>,
+    hash_to_point: DomainSeparated<DefaultHash>,
+}
+
+impl KeyCache {
+    // Returns the decompressed key, or an error if the key is not a valid point,
+    // which is not cached as the ring it is in is rejected
+    fn point(&mut self, key: &PublicKey) -> Result<RistrettoPoint, ClsagError> {
+        if let Some(point) = self.points.get(key) {
+            return Ok(*point);
+        }
+        let point = key.decompress().ok_or(ClsagError::InvalidPoint)?;
+        self.points.insert(*key, point);
+        Ok(point)
+    }
+    fn hashed_pubkey(&mut self, key: &PublicKey) -> RistrettoPoint {
+        let hash_to_point = &self.hash_to_point;
+        *self
+            .hashed_pubkeys
+            .entry(*key)
+            .or_insert_with(|| hash_to_point.hash_to_point(key.as_bytes()))
+    }
+}
+
+// The rings of a batch of signatures verified by `batch_verify`. Every key is decompressed and
+// hashed once for the whole batch, however many rings it is a member of, and a ring which is
+// shared by several signatures, such as the inputs of a transaction, is prepared and hashed
+// into the transcripts once
+#[derive(Default)]
+pub(crate) struct BatchRings<'a> {
+    cache: KeyCache,
+    rings: HashMap<&'a [Vec<PublicKey>], Result<PreparedTranscripts, ClsagError>>,
+}
+
+type PreparedTranscripts = (PreparedRing, RingTranscripts<Transcript>);
+
+impl<'a> BatchRings<'a> {
+    // Verifies the signature over the ring as `Signature::optimised_verify` does,
+    // failing with the same errors
+    pub(crate) fn verify(
+        &mut self,
+        signature: &Signature,
+        public_keys: &'a [Vec<PublicKey>],
+        msg: &[u8],
+    ) -> Result<(), ClsagError> {
+        if public_keys.len() != signature.responses.len() {
+            return Err(ClsagError::RingSizeMismatch);
+        }
+
+        let cache = &mut self.cache;
+        let prepared = self.rings.entry(public_keys).or_insert_with(|| {
+            let ring = PreparedRing::with_cache(public_keys, cache)?;
+            let transcripts = RingTranscripts::new(&default_transcript(), &ring);
+            Ok((ring, transcripts))
+        });
+        match prepared {
+            Ok((ring, transcripts)) => {
+                signature.verify_with_ring_transcripts(transcripts, ring, msg, true)
+            }
+            Err(err) => Err(*err),
+        }
+    }
+}
+
+// The transcripts every challenge over a ring is computed from, with the ring
+// already appended, so that the ring is hashed once for all of the challenges.
+// The key images and the message of each signature are appended to `round` by
+// `signed_round_transcript` before its round challenges are computed
+pub(crate) struct RingTranscripts<T> {
+    pub(crate) aggregation: T,
+    pub(crate) round: T,
+}
+
+impl<T: SigningTranscript> RingTranscripts<T> {
+    pub(crate) fn new(transcript: &T, ring: &PreparedRing) -> Self {
+        RingTranscripts {
+            aggregation: aggregation_transcript(
+                transcript,
+                ring.pubkey_matrix(),
+                ring.num_layers(),
+            ),
+            round: ring_round_transcript(transcript, ring.pubkey_matrix(), ring.num_layers()),
+        }
+    }
+}
+
+// Returns the order that puts the members of the ring in canonical order,
+// which sorts them by the bytes of their keys. Members with the same keys keep their order
+pub(crate) fn canonical_order(public_keys: &[Vec<PublicKey>]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..public_keys.len()).collect();
+    order.sort_by(|&a, &b| public_keys[a].cmp(&public_keys[b]));
+    order
+}
+
+// Puts the members of the ring in canonical order and returns the new index of the signer.
+// Two parties who put the same members in canonical order derive the same ring,
+// and the position of each member only depends on its keys.
+// Returns an error, and leaves the ring as it is, if the signer index is not in the ring
+pub fn canonicalize(
+    public_keys: &mut PublicKeys,
+    signer_index: usize,
+) -> Result<usize, ClsagError> {
+    let order = canonical_order(public_keys);
+    let new_index = order
+        .iter()
+        .position(|&i| i == signer_index)
+        .ok_or(ClsagError::NoSigner)?;
+    *public_keys = order.iter().map(|&i| public_keys[i].clone()).collect();
+    Ok(new_index)
+}
+
+// Returns true if the members of the ring are in canonical order
+pub fn is_canonical(public_keys: &[Vec<PublicKey>]) -> bool {
+    public_keys.windows(2).all(|pair| pair[0] <= pair[1])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::*;
+    use curve25519_dalek::ristretto::CompressedRistretto;
+    use curve25519_dalek::scalar::Scalar;
+
+    #[test]
+    fn test_verify_prepared() {
+        let num_keys = 2;
+        let num_decoys = 6;
+
+        let mut clsag = generate_clsag_with(num_decoys, num_keys);
+        clsag.add_member(generate_signer(num_keys)).unwrap();
+        clsag.set_shuffle_members(false);
+        let ring = PreparedRing::new(clsag.public_keys()).unwrap();
+        assert_eq!(num_decoys + 1, ring.len());
+        assert_eq!(num_keys, ring.num_layers());
+        assert_eq!(clsag.public_keys_bytes(), ring.pubkey_matrix());
+
+        // Several signatures over the same ring share the prepared ring
+        for msg in [b"first".as_ref(), b"second".as_ref()] {
+            let (signature, _) = clsag.sign(msg).unwrap();
+            assert!(signature.verify_prepared(&ring, msg).is_ok());
+            assert!(signature
+                .verify_prepared(&ring, b"another message")
+                .is_err());
+        }
+
+        // The signature does not verify against a ring of a different size
+        let mut public_keys = clsag.public_keys().to_vec();
+        public_keys.pop();
+        let smaller_ring = PreparedRing::new(&public_keys).unwrap();
+        let (signature, _) = clsag.sign(b"first").unwrap();
+        match signature.verify_prepared(&smaller_ring, b"first") {
+            Err(ClsagError::RingSizeMismatch) => {}
+            _ => panic!("expected an incorrect number of public keys error"),
+        }
+    }
+
+    #[test]
+    fn test_verify_many() {
+        let mut clsag = generate_clsag_with(5, 2);
+        clsag.add_member(generate_signer(2)).unwrap();
+        clsag.set_shuffle_members(false);
+        let ring = PreparedRing::new(clsag.public_keys()).unwrap();
+
+        let mut signatures: Vec<Signature> = (0..3)
+            .map(|_| clsag.sign(b"hello world").unwrap().0)
+            .collect();
+        assert!(ring.verify_many(&signatures, b"hello world").is_ok());
+        assert!(ring.verify_many(&[], b"hello world").is_ok());
+
+        // Every signature which fails is reported with the error verify_prepared returns
+        signatures[1].challenge += Scalar::one();
+        signatures[2].responses.pop();
+        let err = ring.verify_many(&signatures, b"hello world").unwrap_err();
+        let positions: Vec<usize> = err.failures.iter().map(|(index, _)| *index).collect();
+        assert_eq!(vec![1, 2], positions);
+        for (index, err) in err.failures {
+            assert_eq!(
+                signatures[index].verify_prepared(&ring, b"hello world"),
+                Err(err)
+            );
+        }
+
+        let err = ring
+            .verify_many(&signatures[..1], b"another message")
+            .unwrap_err();
+        assert_eq!(vec![(0, ClsagError::ChallengeMismatch)], err.failures);
+    }
+
+    #[test]
+    fn test_new_rejects_bad_rings() {
+        match PreparedRing::new(&[]) {
+            Err(ClsagError::EmptyRing) => {}
+            _ => panic!("expected an error for an empty ring"),
+        }
+
+        let mismatched = vec![generate_rand_public_keys(2), generate_rand_public_keys(3)];
+        match PreparedRing::new(&mismatched) {
+            Err(ClsagError::KeyCountMismatch) => {}
+            _ => panic!("expected an error for members with different numbers of keys"),
+        }
+
+        let mut bad_point = vec![generate_rand_public_keys(2), generate_rand_public_keys(2)];
+        bad_point[1][0] = PublicKey(CompressedRistretto([0xff; 32]));
+        match PreparedRing::new(&bad_point) {
+            Err(ClsagError::InvalidPoint) => {}
+            _ => panic!("expected a bad point error"),
+        }
+
+        // Rings beyond the verification limits are rejected before any key is decompressed
+        let too_many_keys = vec![generate_rand_public_keys(17), generate_rand_public_keys(17)];
+        match PreparedRing::new(&too_many_keys) {
+            Err(ClsagError::TooManyKeys) => {}
+            _ => panic!("expected a too many keys error"),
+        }
+        let limits = VerificationLimits {
+            max_ring_size: 1,
+            max_keys: 2,
+        };
+        let hash_to_point = DomainSeparated::<DefaultHash>::new();
+        match PreparedRing::with_limits(&bad_point, &hash_to_point, &limits) {
+            Err(ClsagError::RingTooLarge) => {}
+            _ => panic!("expected a ring too large error"),
+        }
+        let unlimited = VerificationLimits::unlimited();
+        assert!(PreparedRing::with_limits(&too_many_keys, &hash_to_point, &unlimited).is_ok());
+    }
+
+    #[test]
+    fn test_canonicalize() {
+        let signer_keys = generate_rand_public_keys(2);
+        let mut public_keys: PublicKeys = (0..8).map(|_| generate_rand_public_keys(2)).collect();
+        public_keys.insert(3, signer_keys.clone());
+
+        let mut canonical = public_keys.clone();
+        let signer_index = canonicalize(&mut canonical, 3).unwrap();
+        assert!(is_canonical(&canonical));
+        assert_eq!(signer_keys, canonical[signer_index]);
+
+        // The same members in any order give the same ring
+        public_keys.reverse();
+        let mut other = public_keys.clone();
+        assert_eq!(signer_index, canonicalize(&mut other, 5).unwrap());
+        assert_eq!(canonical, other);
+
+        // A signer index outside of the ring is rejected, and the ring is left as it is
+        let mut unchanged = public_keys.clone();
+        match canonicalize(&mut unchanged, public_keys.len()) {
+            Err(ClsagError::NoSigner) => {}
+            _ => panic!("expected a no signer error"),
+        }
+        assert_eq!(public_keys, unchanged);
+    }
+}
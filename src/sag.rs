@@ -0,0 +1,272 @@
+// Linkable spontaneous anonymous group signatures over a ring of single keys, also known as bLSAG.
+//
+// Each member of the ring has one key, so there is nothing to aggregate, and a signature is
+// the challenge of the first member, one response for every member and the key image of the
+// signer. Signatures whose signers only have one key each are smaller than CLSAG signatures,
+// as they do not carry the key images of the other layers, and the scheme has fewer steps to audit.
+//
+// For the member at index `i`, with the challenge `c_i`, verification computes
+// `L = r_i * G + c_i * P_i` and `R = r_i * H(P_i) + c_i * I`, and the challenge of the next member
+// is computed from `L` and `R`. The key image `I = x * H(P)` is the same for every signature of the
+// signer, so that two signatures by the same signer are linked.
+//
+// These signatures are a separate scheme from CLSAG, and are not verified by `signature::Signature`.
+use crate::constants::BASEPOINT;
+use crate::error::ClsagError;
+use crate::hash::hash_pubkey;
+use crate::keys::{KeyImage, PublicKey, SecretKey};
+use crate::transcript::{default_transcript, SigningTranscript};
+use curve25519_dalek::ristretto::RistrettoPoint;
+use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::traits::VartimeMultiscalarMul;
+use merlin::Transcript;
+use rand::{CryptoRng, RngCore};
+
+// A linkable ring signature over a ring of single keys
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Signature {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::scalar"))]
+    challenge: Scalar,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::scalars"))]
+    responses: Vec<Scalar>,
+    key_image: KeyImage,
+}
+
+impl Signature {
+    // Signs the message with the secret key, whose public key must be in the ring.
+    // Returns an error if the ring has fewer than two members, if one of the keys
+    // is not a valid point, or if the public key of the secret key is not in the ring
+    pub fn sign<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        ring: &[PublicKey],
+        secret_key: &SecretKey,
+        msg: &[u8],
+    ) -> Result<Signature, ClsagError> {
+        let points = decompress_ring(ring)?;
+        let signer_index = ring
+            .iter()
+            .position(|key| *key == secret_key.public_key())
+            .ok_or(ClsagError::NotASigner)?;
+        let n = ring.len();
+
+        let hashed_pubkeys: Vec<RistrettoPoint> =
+            ring.iter().map(|key| hash_pubkey(key.as_bytes())).collect();
+        let key_image_point = secret_key.0 * hashed_pubkeys[signer_index];
+        let key_image = KeyImage(key_image_point.compress());
+        let transcript = transcript(ring, &key_image, msg);
+
+        let nonce = Scalar::random(rng);
+        let mut responses: Vec<Scalar> = (0..n).map(|_| Scalar::random(rng)).collect();
+        let mut challenges = vec![Scalar::zero(); n];
+
+        // The challenge of the member after the signer is computed from the nonce,
+        // and each challenge after it from the response of the member before
+        let mut challenge = round_challenge(
+            &transcript,
+            &(nonce * BASEPOINT),
+            &(nonce * hashed_pubkeys[signer_index]),
+        );
+        for offset in 1..n {
+            let i = (signer_index + offset) % n;
+            challenges[i] = challenge;
+            let (l, r) = commitments(
+                &responses[i],
+                &challenge,
+                &points[i],
+                &hashed_pubkeys[i],
+                &key_image_point,
+            );
+            challenge = round_challenge(&transcript, &l, &r);
+        }
+        challenges[signer_index] = challenge;
+        responses[signer_index] = nonce - challenge * secret_key.0;
+
+        Ok(Signature {
+            challenge: challenges[0],
+            responses,
+            key_image,
+        })
+    }
+    // Verifies the signature on the message over the ring.
+    // Returns an error if the ring is not the size of the ring the signature was made over,
+    // if a key or the key image is not valid, or if the signature does not verify
+    pub fn verify(&self, ring: &[PublicKey], msg: &[u8]) -> Result<(), ClsagError> {
+        if self.responses.len() != ring.len() {
+            return Err(ClsagError::RingSizeMismatch);
+        }
+        let points = decompress_ring(ring)?;
+        let key_image_point = self.key_image.check()?;
+        let transcript = transcript(ring, &self.key_image, msg);
+
+        let mut challenge = self.challenge;
+        for ((response, point), key) in self.responses.iter().zip(&points).zip(ring) {
+            let hashed_pubkey = hash_pubkey(key.as_bytes());
+            let (l, r) = commitments(
+                response,
+                &challenge,
+                point,
+                &hashed_pubkey,
+                &key_image_point,
+            );
+            challenge = round_challenge(&transcript, &l, &r);
+        }
+
+        if challenge != self.challenge {
+            return Err(ClsagError::ChallengeMismatch);
+        }
+        Ok(())
+    }
+    // Returns the key image of the signer
+    pub fn key_image(&self) -> &KeyImage {
+        &self.key_image
+    }
+    // Returns true if both signatures were made by the same signer, as their key images are
+    // the same. The signatures are not verified, so this should only be called on
+    // signatures which have already been verified
+    pub fn links_with(&self, other: &Signature) -> bool {
+        self.key_image == other.key_image
+    }
+    // Returns the number of members in the ring the signature was made over
+    pub fn len(&self) -> usize {
+        self.responses.len()
+    }
+    // Returns true if the signature has no responses, which a valid signature never does
+    pub fn is_empty(&self) -> bool {
+        self.responses.is_empty()
+    }
+}
+
+// Returns the decompressed keys of the ring, or an error if the ring
+// has fewer than two members or one of its keys is not a valid point
+fn decompress_ring(ring: &[PublicKey]) -> Result<Vec<RistrettoPoint>, ClsagError> {
+    match ring.len() {
+        0 => return Err(ClsagError::EmptyRing),
+        1 => return Err(ClsagError::RingTooSmall),
+        _ => (),
+    }
+    ring.iter()
+        .map(|key| key.decompress().ok_or(ClsagError::InvalidPoint))
+        .collect()
+}
+
+// Returns the transcript every challenge of a signature on the message is computed from
+fn transcript(ring: &[PublicKey], key_image: &KeyImage, msg: &[u8]) -> Transcript {
+    let pubkey_matrix: Vec<u8> = ring.iter().flat_map(|key| key.to_bytes()).collect();
+
+    let mut transcript = default_transcript();
+    transcript.append_domain(b"sag_round");
+    transcript.append_ring(1, &pubkey_matrix);
+    transcript.append_key_images(std::slice::from_ref(key_image));
+    transcript.append_msg(msg);
+    transcript
+}
+
+// Returns `L = response * G + challenge * key` and `R = response * H(key) + challenge * I`
+fn commitments(
+    response: &Scalar,
+    challenge: &Scalar,
+    key: &RistrettoPoint,
+    hashed_pubkey: &RistrettoPoint,
+    key_image: &RistrettoPoint,
+) -> (RistrettoPoint, RistrettoPoint) {
+    let l = RistrettoPoint::vartime_multiscalar_mul(&[*response, *challenge], &[BASEPOINT, *key]);
+    let r = RistrettoPoint::vartime_multiscalar_mul(
+        &[*response, *challenge],
+        &[*hashed_pubkey, *key_image],
+    );
+    (l, r)
+}
+
+fn round_challenge(transcript: &Transcript, l: &RistrettoPoint, r: &RistrettoPoint) -> Scalar {
+    let mut transcript = transcript.clone();
+    transcript.append_point(b"L", l);
+    transcript.append_point(b"R", r);
+    transcript.challenge_scalar(b"challenge")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::keys::compute_key_image;
+    use curve25519_dalek::traits::Identity;
+
+    fn generate_ring(secret_keys: &[SecretKey]) -> Vec<PublicKey> {
+        secret_keys.iter().map(SecretKey::public_key).collect()
+    }
+
+    #[test]
+    fn test_sign_verify() {
+        let mut rng = rand::thread_rng();
+        let secret_keys: Vec<SecretKey> = (0..5).map(|_| SecretKey::random(&mut rng)).collect();
+        let ring = generate_ring(&secret_keys);
+
+        for secret_key in &secret_keys {
+            let signature = Signature::sign(&mut rng, &ring, secret_key, b"hello world").unwrap();
+            assert_eq!(ring.len(), signature.len());
+            assert!(signature.verify(&ring, b"hello world").is_ok());
+            assert!(signature.verify(&ring, b"another message").is_err());
+
+            // The signature does not verify over another ring
+            let mut other_ring = ring.clone();
+            other_ring.swap(0, 4);
+            assert!(signature.verify(&other_ring, b"hello world").is_err());
+        }
+
+        let outsider = SecretKey::random(&mut rng);
+        match Signature::sign(&mut rng, &ring, &outsider, b"hello world") {
+            Err(ClsagError::NotASigner) => {}
+            _ => panic!("expected a not a signer error"),
+        }
+        match Signature::sign(&mut rng, &ring[..1], &secret_keys[0], b"hello world") {
+            Err(ClsagError::RingTooSmall) => {}
+            _ => panic!("expected a ring too small error"),
+        }
+    }
+
+    #[test]
+    fn test_links_with() {
+        let mut rng = rand::thread_rng();
+        let secret_keys: Vec<SecretKey> = (0..4).map(|_| SecretKey::random(&mut rng)).collect();
+        let ring = generate_ring(&secret_keys);
+
+        // The key image is the key image a wallet computes for the key
+        let first = Signature::sign(&mut rng, &ring, &secret_keys[2], b"first").unwrap();
+        assert_eq!(
+            compute_key_image(&secret_keys[2], &ring[2]),
+            *first.key_image()
+        );
+
+        let second = Signature::sign(&mut rng, &ring, &secret_keys[2], b"second").unwrap();
+        assert!(first.links_with(&second));
+        let other = Signature::sign(&mut rng, &ring, &secret_keys[1], b"first").unwrap();
+        assert!(!first.links_with(&other));
+    }
+
+    #[test]
+    fn test_verify_rejects_bad_signatures() {
+        let mut rng = rand::thread_rng();
+        let secret_keys: Vec<SecretKey> = (0..4).map(|_| SecretKey::random(&mut rng)).collect();
+        let ring = generate_ring(&secret_keys);
+        let signature = Signature::sign(&mut rng, &ring, &secret_keys[0], b"msg").unwrap();
+
+        match signature.verify(&ring[1..], b"msg") {
+            Err(ClsagError::RingSizeMismatch) => {}
+            _ => panic!("expected a ring size mismatch error"),
+        }
+
+        let mut identity = signature.clone();
+        identity.key_image = KeyImage(RistrettoPoint::identity().compress());
+        match identity.verify(&ring, b"msg") {
+            Err(ClsagError::IdentityKeyImage) => {}
+            _ => panic!("expected an identity key image error"),
+        }
+
+        let mut tampered = signature;
+        tampered.responses[1] += Scalar::one();
+        match tampered.verify(&ring, b"msg") {
+            Err(ClsagError::ChallengeMismatch) => {}
+            _ => panic!("expected a challenge mismatch error"),
+        }
+    }
+}
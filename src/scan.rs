@@ -0,0 +1,182 @@
+// Scanning the outputs of transactions for the outputs owned by an account.
+//
+// A `Scanner` holds the private view key and the public spend key of an account, which is
+// all a view only wallet has. For each output, it takes the shared secret `a * R` with the
+// transaction public key, and subtracts `H(a * R, output_index) * G` from the one-time key,
+// which leaves the spend key of the subaddress the output was sent to if the output is owned
+// by the account. The spend keys of the first subaddresses are kept in a table, so each output
+// is checked against all of them at the cost of a single scalar multiplication.
+//
+// Owned outputs are spent with the private spend key, using `OwnedOutput::signer` to get
+// the signer for the output, see the derive module.
+use crate::constants::BASEPOINT;
+use crate::derive::{decompress, derivation_scalar, subaddress_scalar, AccountKeys};
+use crate::error::ClsagError;
+use crate::keys::{PublicKey, SecretKey};
+use crate::member::Member;
+use std::collections::HashMap;
+
+// An output of a transaction, as seen on chain
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TxOutput {
+    // The public key of the transaction the output is in
+    pub tx_public_key: PublicKey,
+
+    // The index of the output in the transaction
+    pub output_index: u64,
+
+    // The one-time key of the output
+    pub one_time_key: PublicKey,
+}
+
+// An output owned by the account, along with the subaddress it was sent to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OwnedOutput {
+    pub output: TxOutput,
+    pub subaddress_index: u32,
+}
+
+// Finds the outputs sent to the first subaddresses of an account
+#[derive(Debug, Clone)]
+pub struct Scanner {
+    view_key: SecretKey,
+
+    // The spend key of each subaddress scanned for, with its index
+    subaddresses: HashMap<PublicKey, u32>,
+}
+
+impl Scanner {
+    // Creates a scanner for the subaddresses `0..num_subaddresses` of the account with
+    // the given private view key and public spend key. At least subaddress 0 is scanned for.
+    // Returns an error if the spend key is not a valid point
+    pub fn new(
+        view_key: SecretKey,
+        spend_key: &PublicKey,
+        num_subaddresses: u32,
+    ) -> Result<Self, ClsagError> {
+        let spend_key = decompress(spend_key)?;
+        let subaddresses = (0..num_subaddresses.max(1))
+            .map(|index| {
+                let subaddress_key = spend_key + subaddress_scalar(&view_key, index) * BASEPOINT;
+                (PublicKey::from_point(&subaddress_key), index)
+            })
+            .collect();
+
+        Ok(Scanner {
+            view_key,
+            subaddresses,
+        })
+    }
+    // Creates a scanner for the subaddresses `0..num_subaddresses` of the account
+    pub fn from_account(account: &AccountKeys, num_subaddresses: u32) -> Self {
+        Scanner::new(
+            account.view_key.clone(),
+            &account.spend_key.public_key(),
+            num_subaddresses,
+        )
+        .expect("the public key of a secret key is a valid point")
+    }
+    // Returns the index of the subaddress the output was sent to,
+    // or None if the output is not owned by the account or has invalid keys
+    pub fn owner(&self, output: &TxOutput) -> Option<u32> {
+        let tx_public_key = decompress(&output.tx_public_key).ok()?;
+        let one_time_key = decompress(&output.one_time_key).ok()?;
+
+        let shared_secret = self.view_key.0 * tx_public_key;
+        let spend_key =
+            one_time_key - derivation_scalar(&shared_secret, output.output_index) * BASEPOINT;
+        self.subaddresses
+            .get(&PublicKey::from_point(&spend_key))
+            .copied()
+    }
+    // Returns the outputs owned by the account, in the order they were given in
+    pub fn scan(&self, outputs: &[TxOutput]) -> Vec<OwnedOutput> {
+        outputs
+            .iter()
+            .filter_map(|output| {
+                self.owner(output).map(|subaddress_index| OwnedOutput {
+                    output: *output,
+                    subaddress_index,
+                })
+            })
+            .collect()
+    }
+}
+
+impl OwnedOutput {
+    // Returns the private key of the one-time key of the output.
+    // Returns an error if the transaction public key is not a valid point
+    pub fn secret_key(&self, account: &AccountKeys) -> Result<SecretKey, ClsagError> {
+        account.one_time_secret_key(
+            &self.output.tx_public_key,
+            self.output.output_index,
+            self.subaddress_index,
+        )
+    }
+    // Returns the signer for spending the output in a ring.
+    // Returns an error if the transaction public key is not a valid point
+    pub fn signer(&self, account: &AccountKeys) -> Result<Member, ClsagError> {
+        account.signer(
+            &self.output.tx_public_key,
+            self.output.output_index,
+            self.subaddress_index,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::derive::decoy;
+    use crate::test_utils::*;
+
+    #[test]
+    fn test_scan_outputs() {
+        let mut rng = rand::thread_rng();
+        let account = AccountKeys::random(&mut rng);
+        let scanner = Scanner::from_account(&account, 10);
+
+        // A transaction paying subaddress 4 of the account and another account
+        let tx_key = SecretKey::random(&mut rng);
+        let address = account.subaddress(4);
+        let tx_public_key = address.tx_public_key(&tx_key).unwrap();
+        let other = AccountKeys::random(&mut rng).address();
+        let outputs: Vec<TxOutput> = [other, address]
+            .iter()
+            .enumerate()
+            .map(|(output_index, address)| TxOutput {
+                tx_public_key,
+                output_index: output_index as u64,
+                one_time_key: address.one_time_key(&tx_key, output_index as u64).unwrap(),
+            })
+            .collect();
+
+        let owned = scanner.scan(&outputs);
+        assert_eq!(1, owned.len());
+        assert_eq!(outputs[1], owned[0].output);
+        assert_eq!(4, owned[0].subaddress_index);
+        assert_eq!(
+            outputs[1].one_time_key,
+            owned[0].secret_key(&account).unwrap().public_key()
+        );
+
+        // Subaddresses past the table are not found
+        assert_eq!(None, Scanner::from_account(&account, 4).owner(&outputs[1]));
+
+        // A view only wallet finds the same outputs
+        let view_only =
+            Scanner::new(account.view_key.clone(), &account.address().spend_key, 10).unwrap();
+        assert_eq!(owned, view_only.scan(&outputs));
+
+        // The owned output can be spent in a ring
+        let mut clsag = generate_clsag_with(0, 1);
+        for key in generate_rand_public_keys(4) {
+            clsag.add_member(decoy(&key).unwrap()).unwrap();
+        }
+        clsag
+            .add_member(owned[0].signer(&account).unwrap())
+            .unwrap();
+        let (signature, public_keys) = clsag.sign(b"hello world").unwrap();
+        assert!(signature.verify(&public_keys, b"hello world").is_ok());
+    }
+}
@@ -0,0 +1,410 @@
+// An experimental clsag over secp256k1, behind the experimental feature, for research into
+// protocols linking Bitcoin compatible keys across chains.
+//
+// The rest of the crate, including the cipher suites of the suite module, is written against
+// ristretto points, so this module carries its own ring signature rather than being a suite.
+// It follows the construction of the clsag module: the keys of each member are aggregated
+// with one coefficient per layer, the key images are made over the hash of the first key of
+// the signer, and a single challenge and one response per member are kept.
+//
+// The curve arithmetic is that of the k256 crate. Public keys are hashed to the curve with
+// the `secp256k1_XMD:SHA-256_SSWU_RO_` suite of RFC 9380, so the discrete log of the point
+// a key image is made over is not known to anyone.
+use crate::error::ClsagError;
+use k256::elliptic_curve::bigint::U512;
+use k256::elliptic_curve::group::GroupEncoding;
+use k256::elliptic_curve::hash2curve::{ExpandMsgXmd, GroupDigest};
+use k256::elliptic_curve::ops::Reduce;
+use k256::elliptic_curve::sec1::ToEncodedPoint;
+use k256::elliptic_curve::PrimeField;
+use k256::sha2::Sha256;
+use k256::{AffinePoint, CompressedPoint, FieldBytes, Secp256k1, WideBytes, U256};
+use merlin::Transcript;
+use rand::{CryptoRng, Rng, RngCore};
+
+pub use k256::{ProjectivePoint as Point, Scalar};
+
+// The domain separation tag public keys are hashed to the curve with
+const HASH_TO_POINT_DST: &[u8] = b"CLSAG_SECP256K1_V1_XMD:SHA-256_SSWU_RO_";
+
+// Returns a uniformly random scalar, from 64 bytes of the rng reduced modulo the order
+pub fn random_scalar<R: RngCore + CryptoRng>(rng: &mut R) -> Scalar {
+    let mut bytes = WideBytes::default();
+    rng.fill_bytes(&mut bytes);
+    <Scalar as Reduce<U512>>::reduce_bytes(&bytes)
+}
+
+// Reduces the 32 big endian bytes modulo the order
+pub fn scalar_from_bytes_mod_order(bytes: &[u8; 32]) -> Scalar {
+    <Scalar as Reduce<U256>>::reduce_bytes(FieldBytes::from_slice(bytes))
+}
+
+// Decodes the 32 big endian bytes of a scalar,
+// returning None if they are not less than the order
+pub fn scalar_from_canonical_bytes(bytes: &[u8; 32]) -> Option<Scalar> {
+    Scalar::from_repr(*FieldBytes::from_slice(bytes)).into()
+}
+
+// Returns the 33 byte compressed encoding of the point,
+// which is all zeroes for the identity
+pub fn point_to_bytes(point: &Point) -> [u8; 33] {
+    let mut bytes = [0u8; 33];
+    let encoded = point.to_affine().to_encoded_point(true);
+    if encoded.len() == 33 {
+        bytes.copy_from_slice(encoded.as_bytes());
+    }
+    bytes
+}
+
+// Decodes a compressed point.
+// Returns an error if the bytes are not the encoding of a point other than the identity
+pub fn point_from_bytes(bytes: &[u8; 33]) -> Result<Point, ClsagError> {
+    let affine: Option<AffinePoint> =
+        AffinePoint::from_bytes(CompressedPoint::from_slice(bytes)).into();
+    match affine {
+        Some(point) if bytes[0] == 0x02 || bytes[0] == 0x03 => Ok(point.into()),
+        _ => Err(ClsagError::InvalidPoint),
+    }
+}
+
+// Hashes the bytes to a point, with the hash to curve suite of RFC 9380
+pub fn hash_to_point(bytes: &[u8]) -> Point {
+    hash_to_curve(bytes, HASH_TO_POINT_DST)
+}
+
+fn hash_to_curve(bytes: &[u8], dst: &[u8]) -> Point {
+    Secp256k1::hash_from_bytes::<ExpandMsgXmd<Sha256>>(&[bytes], &[dst])
+        .expect("the domain separation tag is short enough to expand")
+}
+
+// A clsag signature over secp256k1 keys
+#[derive(Debug, Clone)]
+pub struct RingSignature {
+    challenge: Scalar,
+    responses: Vec<Scalar>,
+    key_images: Vec<Point>,
+}
+
+impl RingSignature {
+    // Signs the message over a ring of the decoys with the signer put in at a random
+    // position, returning the signature along with the ring it was made over.
+    // Returns an error if there are no decoys, or if the decoys
+    // do not all have as many keys as the signer
+    pub fn sign<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        decoys: &[Vec<Point>],
+        secret_keys: &[Scalar],
+        msg: &[u8],
+    ) -> Result<(Self, Vec<Vec<Point>>), ClsagError> {
+        if decoys.is_empty() {
+            return Err(ClsagError::RingTooSmall);
+        }
+        let index = rng.gen_range(0, decoys.len() + 1);
+        let mut ring = decoys.to_vec();
+        ring.insert(
+            index,
+            secret_keys.iter().map(|x| Point::GENERATOR * x).collect(),
+        );
+
+        let signature = Self::sign_at(rng, &ring, index, secret_keys, msg)?;
+        Ok((signature, ring))
+    }
+
+    // Signs the message over the ring as the member at the index.
+    // Returns an error if the ring is empty, if the members do not all have the same number
+    // of keys, or if the keys of the member at the index are not those of the secret keys
+    pub fn sign_at<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        ring: &[Vec<Point>],
+        index: usize,
+        secret_keys: &[Scalar],
+        msg: &[u8],
+    ) -> Result<Self, ClsagError> {
+        check_ring(ring)?;
+        let signer_keys = ring.get(index).ok_or(ClsagError::NotASigner)?;
+        if secret_keys.len() != signer_keys.len() {
+            return Err(ClsagError::KeyCountMismatch);
+        }
+        let owns_keys = signer_keys
+            .iter()
+            .zip(secret_keys)
+            .all(|(key, secret_key)| *key == Point::GENERATOR * secret_key);
+        if !owns_keys {
+            return Err(ClsagError::NotASigner);
+        }
+
+        let hashed_pubkey = hash_to_point(&point_to_bytes(&signer_keys[0]));
+        let key_images: Vec<Point> = secret_keys
+            .iter()
+            .map(|secret_key| hashed_pubkey * secret_key)
+            .collect();
+
+        let transcript = base_transcript(ring, &key_images, msg);
+        let agg_coeffs = aggregation_coefficients(&transcript, secret_keys.len());
+        let aggregated_image = weighted_sum(&key_images, &agg_coeffs);
+        let aggregated_secret: Scalar = secret_keys
+            .iter()
+            .zip(&agg_coeffs)
+            .map(|(x, mu)| x * mu)
+            .sum();
+
+        let nonce = random_scalar(rng);
+        let mut challenge = round_challenge(
+            &transcript,
+            &(Point::GENERATOR * nonce),
+            &(hashed_pubkey * nonce),
+        );
+
+        let mut responses = vec![Scalar::ZERO; ring.len()];
+        let mut first_challenge = challenge;
+        for offset in 1..ring.len() {
+            let i = (index + offset) % ring.len();
+            if i == 0 {
+                first_challenge = challenge;
+            }
+            responses[i] = random_scalar(rng);
+            challenge = member_challenge(
+                &transcript,
+                &ring[i],
+                &agg_coeffs,
+                &aggregated_image,
+                &responses[i],
+                &challenge,
+            );
+        }
+        if index == 0 {
+            first_challenge = challenge;
+        }
+        responses[index] = nonce - challenge * aggregated_secret;
+
+        Ok(RingSignature {
+            challenge: first_challenge,
+            responses,
+            key_images,
+        })
+    }
+
+    // Returns the key images of the signer, one per layer
+    pub fn key_images(&self) -> &[Point] {
+        &self.key_images
+    }
+
+    // Returns true if both signatures were made by the same signer
+    pub fn links_with(&self, other: &RingSignature) -> bool {
+        self.key_images == other.key_images
+    }
+
+    // Verifies the signature over the ring
+    pub fn verify(&self, ring: &[Vec<Point>], msg: &[u8]) -> Result<(), ClsagError> {
+        check_ring(ring)?;
+        if ring.len() != self.responses.len() {
+            return Err(ClsagError::RingSizeMismatch);
+        }
+        if ring[0].len() != self.key_images.len() {
+            return Err(ClsagError::KeyCountMismatch);
+        }
+        if self.key_images.contains(&Point::IDENTITY) {
+            return Err(ClsagError::IdentityKeyImage);
+        }
+
+        let transcript = base_transcript(ring, &self.key_images, msg);
+        let agg_coeffs = aggregation_coefficients(&transcript, self.key_images.len());
+        let aggregated_image = weighted_sum(&self.key_images, &agg_coeffs);
+
+        let challenge =
+            ring.iter()
+                .zip(&self.responses)
+                .fold(self.challenge, |challenge, (keys, response)| {
+                    member_challenge(
+                        &transcript,
+                        keys,
+                        &agg_coeffs,
+                        &aggregated_image,
+                        response,
+                        &challenge,
+                    )
+                });
+        if challenge != self.challenge {
+            return Err(ClsagError::ChallengeMismatch);
+        }
+
+        Ok(())
+    }
+}
+
+// Checks that the ring is not empty and that every member has the same number of keys
+fn check_ring(ring: &[Vec<Point>]) -> Result<(), ClsagError> {
+    let num_layers = ring.first().ok_or(ClsagError::EmptyRing)?.len();
+    if num_layers == 0 || ring.iter().any(|keys| keys.len() != num_layers) {
+        return Err(ClsagError::KeyCountMismatch);
+    }
+    Ok(())
+}
+
+fn weighted_sum(points: &[Point], weights: &[Scalar]) -> Point {
+    points
+        .iter()
+        .zip(weights)
+        .map(|(point, weight)| point * weight)
+        .sum()
+}
+
+fn challenge_scalar(transcript: &mut Transcript, label: &'static [u8]) -> Scalar {
+    let mut bytes = WideBytes::default();
+    transcript.challenge_bytes(label, &mut bytes);
+    <Scalar as Reduce<U512>>::reduce_bytes(&bytes)
+}
+
+// Returns the transcript holding the ring, the key images and the message,
+// which every challenge is derived from
+fn base_transcript(ring: &[Vec<Point>], key_images: &[Point], msg: &[u8]) -> Transcript {
+    let mut transcript = Transcript::new(b"clsag_secp256k1");
+    transcript.append_u64(b"num_layers", ring[0].len() as u64);
+    for key in ring.iter().flatten() {
+        transcript.append_message(b"key", &point_to_bytes(key));
+    }
+    for key_image in key_images {
+        transcript.append_message(b"key_image", &point_to_bytes(key_image));
+    }
+    transcript.append_message(b"msg", msg);
+    transcript
+}
+
+fn aggregation_coefficients(transcript: &Transcript, num_layers: usize) -> Vec<Scalar> {
+    (0..num_layers)
+        .map(|layer| {
+            let mut transcript = transcript.clone();
+            transcript.append_u64(b"layer", layer as u64);
+            challenge_scalar(&mut transcript, b"aggregation")
+        })
+        .collect()
+}
+
+fn round_challenge(transcript: &Transcript, l: &Point, r: &Point) -> Scalar {
+    let mut transcript = transcript.clone();
+    transcript.append_message(b"L", &point_to_bytes(l));
+    transcript.append_message(b"R", &point_to_bytes(r));
+    challenge_scalar(&mut transcript, b"round")
+}
+
+// Returns the challenge of the next member, from the response and the challenge of the member
+fn member_challenge(
+    transcript: &Transcript,
+    keys: &[Point],
+    agg_coeffs: &[Scalar],
+    aggregated_image: &Point,
+    response: &Scalar,
+    challenge: &Scalar,
+) -> Scalar {
+    let aggregated_key = weighted_sum(keys, agg_coeffs);
+    let hashed_pubkey = hash_to_point(&point_to_bytes(&keys[0]));
+    let l = Point::GENERATOR * response + aggregated_key * challenge;
+    let r = hashed_pubkey * response + aggregated_image * challenge;
+    round_challenge(transcript, &l, &r)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn generate_decoys(num_decoys: usize, num_keys: usize) -> Vec<Vec<Point>> {
+        let mut rng = rand::thread_rng();
+        (0..num_decoys)
+            .map(|_| {
+                (0..num_keys)
+                    .map(|_| Point::GENERATOR * random_scalar(&mut rng))
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_group_vectors() {
+        let generator = Point::GENERATOR;
+        assert_eq!(
+            "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+            hex::encode(&point_to_bytes(&generator)[..])
+        );
+        assert_eq!([0u8; 33], point_to_bytes(&Point::IDENTITY));
+
+        let point = Point::GENERATOR * random_scalar(&mut rand::thread_rng());
+        assert_eq!(point, point_from_bytes(&point_to_bytes(&point)).unwrap());
+        match point_from_bytes(&[0u8; 33]) {
+            Err(ClsagError::InvalidPoint) => {}
+            _ => panic!("expected the identity to be rejected"),
+        }
+
+        // The first hash to curve vector of RFC 9380 for the suite, the hash of the empty message
+        let hashed = hash_to_curve(b"", b"QUUX-V01-CS02-with-secp256k1_XMD:SHA-256_SSWU_RO_");
+        assert_eq!(
+            "03c1cae290e291aee617ebaef1be6d73861479c48b841eaba9b7b5852ddfeb1346",
+            hex::encode(&point_to_bytes(&hashed)[..])
+        );
+        assert_ne!(hash_to_point(b"hello"), hash_to_point(b"world"));
+    }
+
+    #[test]
+    fn test_scalar_encoding() {
+        let order = hex::decode("fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141")
+            .unwrap();
+        let mut order_bytes = [0u8; 32];
+        order_bytes.copy_from_slice(&order);
+        assert!(scalar_from_canonical_bytes(&order_bytes).is_none());
+        assert_eq!(Scalar::ZERO, scalar_from_bytes_mod_order(&order_bytes));
+
+        let scalar = -Scalar::ONE;
+        let mut bytes = [0u8; 32];
+        bytes.copy_from_slice(&scalar.to_bytes());
+        assert_eq!(Some(scalar), scalar_from_canonical_bytes(&bytes));
+        assert_eq!(Scalar::ONE, scalar * scalar);
+        assert!(!bool::from(
+            random_scalar(&mut rand::thread_rng()).is_zero()
+        ));
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let mut rng = rand::thread_rng();
+        let secret_keys = vec![random_scalar(&mut rng), random_scalar(&mut rng)];
+        let decoys = generate_decoys(4, 2);
+        let msg = b"hello world";
+
+        let (signature, ring) = RingSignature::sign(&mut rng, &decoys, &secret_keys, msg).unwrap();
+        assert_eq!(5, ring.len());
+        assert!(signature.verify(&ring, msg).is_ok());
+
+        match signature.verify(&ring, b"another message") {
+            Err(ClsagError::ChallengeMismatch) => {}
+            _ => panic!("expected a challenge mismatch error"),
+        }
+        match signature.verify(&ring[1..], msg) {
+            Err(ClsagError::RingSizeMismatch) => {}
+            _ => panic!("expected a ring size mismatch error"),
+        }
+
+        // Signatures from the same keys link, whatever the ring
+        let (other, _) =
+            RingSignature::sign(&mut rng, &generate_decoys(2, 2), &secret_keys, msg).unwrap();
+        assert!(signature.links_with(&other));
+    }
+
+    #[test]
+    fn test_sign_errors() {
+        let mut rng = rand::thread_rng();
+        let secret_keys = vec![random_scalar(&mut rng)];
+        let ring = generate_decoys(3, 1);
+
+        match RingSignature::sign_at(&mut rng, &ring, 1, &secret_keys, b"msg") {
+            Err(ClsagError::NotASigner) => {}
+            _ => panic!("expected a not a signer error"),
+        }
+        match RingSignature::sign(&mut rng, &generate_decoys(3, 2), &secret_keys, b"msg") {
+            Err(ClsagError::KeyCountMismatch) => {}
+            _ => panic!("expected a key count mismatch error"),
+        }
+        match RingSignature::sign(&mut rng, &[], &secret_keys, b"msg") {
+            Err(ClsagError::RingTooSmall) => {}
+            _ => panic!("expected a ring too small error"),
+        }
+    }
+}
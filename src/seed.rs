@@ -0,0 +1,112 @@
+// Deriving private key sets from a single seed.
+//
+// A wallet which derives every signing key from one 32 byte seed only needs to back up the
+// seed. `PrivateSet::from_seed` derives the keys of a signer at a path of indices, such as
+// `[account, output]`, with HKDF over HMAC-SHA512:
+//
+//   node = HKDF-Extract("clsag seed", seed)
+//   node = HKDF-Expand(node, "clsag path" || index, 64) for each index in the path
+//   key_i = HKDF-Expand(node, "clsag key" || i, 64) reduced modulo the group order
+//
+// with each index encoded as a big-endian u32. `PrivateSet::from_bip39_seed` runs the same
+// derivation over the 64 byte seed a BIP39 phrase is stretched into. Every step needs the node above it, which is
+// secret, so keys cannot be derived from public keys and a leaked key does not reveal its
+// siblings or the seed. The derivation is fixed: the same seed and path give the same keys in
+// every version of this crate, which `tests/test_vectors.rs` checks against known answers.
+use crate::hash::hmac_sha512;
+use crate::keys::{wipe_bytes, PrivateSet};
+use curve25519_dalek::scalar::Scalar;
+
+const SEED_SALT: &[u8] = b"clsag seed";
+const PATH_INFO: &[u8] = b"clsag path";
+const KEY_INFO: &[u8] = b"clsag key";
+
+impl PrivateSet {
+    // Derives the set of `num_keys` private keys at the path from the seed.
+    // The empty path derives the keys directly below the seed
+    pub fn from_seed(seed: &[u8; 32], path: &[u32], num_keys: usize) -> PrivateSet {
+        derive_private_set(seed, path, num_keys)
+    }
+    // Derives the set of `num_keys` private keys at the path from the 64 byte seed
+    // which BIP39 stretches a phrase and passphrase into
+    pub fn from_bip39_seed(seed: &[u8; 64], path: &[u32], num_keys: usize) -> PrivateSet {
+        derive_private_set(seed, path, num_keys)
+    }
+}
+
+// Derives the keys at the path from a seed of any length
+fn derive_private_set(seed: &[u8], path: &[u32], num_keys: usize) -> PrivateSet {
+    let mut node = hmac_sha512(SEED_SALT, &[seed]);
+    for index in path {
+        let child = hkdf_expand(&node, PATH_INFO, *index);
+        wipe_bytes(&mut node);
+        node = child;
+    }
+
+    let keys = (0..num_keys)
+        .map(|index| {
+            let mut bytes = hkdf_expand(&node, KEY_INFO, index as u32);
+            let key = Scalar::from_bytes_mod_order_wide(&bytes);
+            wipe_bytes(&mut bytes);
+            key
+        })
+        .collect();
+    wipe_bytes(&mut node);
+
+    PrivateSet::new(keys)
+}
+
+// Returns the first 64 bytes of HKDF-Expand, with the info followed by the index
+fn hkdf_expand(prk: &[u8; 64], info: &[u8], index: u32) -> [u8; 64] {
+    hmac_sha512(prk, &[info, &index.to_be_bytes(), &[1]])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_from_seed() {
+        let seed = [7u8; 32];
+        let private_set = PrivateSet::from_seed(&seed, &[0, 1], 3);
+        assert_eq!(3, private_set.len());
+        assert!(!private_set.to_public_set().duplicates_exist());
+
+        // The same seed and path give the same keys, and the first keys of a larger set
+        // are the keys of a smaller set at the same path
+        assert_eq!(private_set.0, PrivateSet::from_seed(&seed, &[0, 1], 3).0);
+        assert_eq!(
+            private_set.0[..2],
+            PrivateSet::from_seed(&seed, &[0, 1], 2).0[..]
+        );
+
+        // Every other seed or path gives other keys
+        let mut keys = HashSet::new();
+        for (seed, path) in &[
+            ([7u8; 32], vec![0, 1]),
+            ([7u8; 32], vec![0, 2]),
+            ([7u8; 32], vec![1, 0]),
+            ([7u8; 32], vec![0]),
+            ([7u8; 32], vec![]),
+            ([8u8; 32], vec![0, 1]),
+        ] {
+            let key = PrivateSet::from_seed(seed, path, 1).0[0];
+            assert!(keys.insert(key.to_bytes()));
+        }
+        let key = PrivateSet::from_bip39_seed(&[7u8; 64], &[0, 1], 1).0[0];
+        assert!(keys.insert(key.to_bytes()));
+    }
+
+    #[test]
+    fn test_path_is_hierarchical() {
+        // A path is derived from the node of its parent path
+        let seed = [1u8; 32];
+        let root = hmac_sha512(SEED_SALT, &[&seed]);
+        let account = hkdf_expand(&root, PATH_INFO, 5);
+        let output = hkdf_expand(&account, PATH_INFO, 9);
+        let key = Scalar::from_bytes_mod_order_wide(&hkdf_expand(&output, KEY_INFO, 0));
+
+        assert_eq!(key, PrivateSet::from_seed(&seed, &[5, 9], 1).0[0]);
+    }
+}
@@ -0,0 +1,202 @@
+// Known answer tests run at runtime.
+//
+// `self_test` recomputes values whose answers are fixed in this file: the hash of a public
+// key to a point, the aggregation co-efficients and a round challenge over a fixed ring, and
+// a signature made with a fixed rng, which is then verified. A miscompiled build, a faulty
+// CPU or memory, or a dependency which changed its output, gives a different answer, so HSMs
+// and consensus nodes can call it once at startup and refuse to sign or verify if it fails.
+//
+// The answers were computed by this crate and are not vectors from another implementation,
+// see `tests/test_vectors.rs` for the vectors other implementations can check against.
+use crate::clsag::{aggregation_coefficients, aggregation_transcript, Clsag};
+use crate::constants::BASEPOINT;
+use crate::error::ClsagError;
+use crate::hash::hash_pubkey;
+use crate::keys::KeyImage;
+use crate::member::{round_challenge, round_transcript, Member};
+use crate::policy::RingPolicy;
+use crate::transcript::default_transcript;
+use curve25519_dalek::scalar::Scalar;
+use rand::{CryptoRng, RngCore};
+use sha2::{Digest, Sha512};
+
+// The hash to a point of the compressed basepoint
+const HASHED_BASEPOINT: [u8; 32] = [
+    196, 186, 234, 130, 89, 98, 80, 105, 184, 88, 98, 144, 182, 21, 231, 238, 72, 243, 249, 116,
+    77, 25, 75, 76, 217, 130, 134, 52, 222, 233, 84, 35,
+];
+
+// The aggregation co-efficient and round challenge over the ring of the basepoint and twice
+// the basepoint, with the hash of the basepoint as the key image and as the R point
+const AGGREGATION_COEFFICIENT: [u8; 32] = [
+    198, 136, 142, 170, 197, 114, 143, 79, 134, 19, 50, 158, 156, 133, 133, 221, 46, 147, 85, 154,
+    148, 128, 238, 28, 106, 70, 132, 193, 38, 166, 29, 14,
+];
+const ROUND_CHALLENGE: [u8; 32] = [
+    194, 93, 24, 138, 196, 125, 19, 165, 160, 186, 202, 175, 219, 116, 238, 127, 102, 208, 19, 81,
+    138, 108, 103, 97, 195, 186, 120, 10, 204, 191, 136, 1,
+];
+
+// The key image and challenge of the signature made by `sign_known_answer`
+const SIGNATURE_KEY_IMAGE: [u8; 32] = [
+    114, 80, 114, 221, 59, 115, 210, 33, 165, 65, 169, 229, 186, 251, 248, 27, 228, 182, 59, 44,
+    234, 248, 64, 7, 76, 228, 34, 92, 149, 222, 203, 10,
+];
+const SIGNATURE_CHALLENGE: [u8; 32] = [
+    105, 57, 177, 177, 205, 223, 207, 149, 208, 137, 204, 212, 241, 82, 178, 12, 177, 181, 172,
+    254, 65, 127, 22, 105, 163, 93, 113, 176, 146, 215, 140, 2,
+];
+
+// The message signed by `sign_known_answer`
+const MESSAGE: &[u8] = b"clsag self test";
+
+// Runs the known answer tests, returning `SelfTestFailed` with the name of
+// the first test which did not give the expected answer
+pub fn self_test() -> Result<(), ClsagError> {
+    check("hash to point", hash_to_point_known_answer())?;
+    check("challenge", challenge_known_answer())?;
+    check("sign and verify", sign_known_answer())?;
+    Ok(())
+}
+
+fn check(name: &'static str, passed: Result<bool, ClsagError>) -> Result<(), ClsagError> {
+    match passed {
+        Ok(true) => Ok(()),
+        _ => Err(ClsagError::SelfTestFailed(name)),
+    }
+}
+
+fn hash_to_point_known_answer() -> Result<bool, ClsagError> {
+    let hashed = hash_pubkey(BASEPOINT.compress().as_bytes());
+    Ok(hashed.compress().to_bytes() == HASHED_BASEPOINT)
+}
+
+fn challenge_known_answer() -> Result<bool, ClsagError> {
+    let pubkey_matrix: Vec<u8> = [BASEPOINT, BASEPOINT + BASEPOINT]
+        .iter()
+        .flat_map(|point| point.compress().to_bytes().to_vec())
+        .collect();
+    let hashed = hash_pubkey(BASEPOINT.compress().as_bytes());
+    let key_images = [KeyImage(hashed.compress())];
+    let transcript = default_transcript();
+
+    let aggregation = aggregation_transcript(&transcript, &pubkey_matrix, 1);
+    let agg_coeffs = aggregation_coefficients(&aggregation, &key_images, MESSAGE);
+    let round = round_transcript(&transcript, &pubkey_matrix, &key_images, MESSAGE);
+    let challenge = round_challenge(&round, &BASEPOINT, &hashed);
+
+    Ok(agg_coeffs[0].to_bytes() == AGGREGATION_COEFFICIENT
+        && challenge.to_bytes() == ROUND_CHALLENGE)
+}
+
+fn sign_known_answer() -> Result<bool, ClsagError> {
+    let mut clsag = Clsag::new();
+    clsag.set_ring_policy(RingPolicy::permissive());
+    clsag.add_member(Member::new_decoy(vec![Scalar::from(3u64) * BASEPOINT]))?;
+    clsag.add_member(Member::new_signer(vec![Scalar::from(5u64)]))?;
+
+    let (signature, public_keys) = clsag.sign_with_rng(&mut KnownAnswerRng::new(), MESSAGE)?;
+    if signature.key_images[0].to_bytes() != SIGNATURE_KEY_IMAGE
+        || signature.challenge.to_bytes() != SIGNATURE_CHALLENGE
+    {
+        return Ok(false);
+    }
+
+    let verifies = signature.verify(&public_keys, MESSAGE).is_ok();
+    let rejects_another_message = signature.verify(&public_keys, b"another").is_err();
+    Ok(verifies && rejects_another_message)
+}
+
+// A deterministic rng, which returns the SHA-512 hash of a counter, so that the signature
+// made by the self test is always the same. It is only used to sign with the fixed keys
+// of the self test, so it is marked as a CryptoRng although its output is predictable
+struct KnownAnswerRng {
+    counter: u64,
+    block: [u8; 64],
+    position: usize,
+}
+
+impl KnownAnswerRng {
+    fn new() -> Self {
+        KnownAnswerRng {
+            counter: 0,
+            block: [0; 64],
+            position: 64,
+        }
+    }
+}
+
+impl RngCore for KnownAnswerRng {
+    fn next_u32(&mut self) -> u32 {
+        let mut bytes = [0u8; 4];
+        self.fill_bytes(&mut bytes);
+        u32::from_le_bytes(bytes)
+    }
+    fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0u8; 8];
+        self.fill_bytes(&mut bytes);
+        u64::from_le_bytes(bytes)
+    }
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for byte in dest {
+            if self.position == self.block.len() {
+                let mut hasher = Sha512::default();
+                hasher.input(b"clsag self test rng");
+                hasher.input(self.counter.to_le_bytes());
+                self.block.copy_from_slice(&hasher.result());
+                self.counter += 1;
+                self.position = 0;
+            }
+            *byte = self.block[self.position];
+            self.position += 1;
+        }
+    }
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl CryptoRng for KnownAnswerRng {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_self_test() {
+        assert!(self_test().is_ok());
+        // The signature is the same every time it is made
+        assert!(self_test().is_ok());
+    }
+
+    #[test]
+    fn test_failed_answer() {
+        assert_eq!(Ok(()), check("hash to point", Ok(true)));
+        let err = check("challenge", Ok(false)).unwrap_err();
+        assert_eq!(ClsagError::SelfTestFailed("challenge"), err);
+        assert_eq!("the challenge self test failed", err.to_string());
+        assert_eq!(
+            Err(ClsagError::SelfTestFailed("sign and verify")),
+            check("sign and verify", Err(ClsagError::NoSigner))
+        );
+    }
+
+    #[test]
+    fn test_known_answer_rng() {
+        let mut rng = KnownAnswerRng::new();
+        let mut bytes = [0u8; 100];
+        rng.fill_bytes(&mut bytes);
+
+        let mut other = KnownAnswerRng::new();
+        assert_eq!(
+            u64::from_le_bytes([
+                bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7]
+            ]),
+            other.next_u64()
+        );
+        let mut rest = [0u8; 92];
+        other.fill_bytes(&mut rest);
+        assert_eq!(&bytes[8..], &rest[..]);
+    }
+}
@@ -0,0 +1,147 @@
+// Serde helpers for the curve types used throughout the crate.
+// curve25519-dalek serializes scalars and points with `serialize_bytes`, and will
+// only deserialize them back from a byte visitor. Formats such as JSON do not
+// have a native byte type, so these are encoded here as fixed 32 byte arrays instead.
+// The byte layout is the same as dalek's: canonical scalar bytes and
+// compressed ristretto points.
+use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
+use curve25519_dalek::scalar::Scalar;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+fn scalar_from_bytes<E: Error>(bytes: [u8; 32]) -> Result<Scalar, E> {
+    Scalar::from_canonical_bytes(bytes).ok_or_else(|| E::custom("scalar was not canonical"))
+}
+
+fn point_from_bytes<E: Error>(bytes: [u8; 32]) -> Result<RistrettoPoint, E> {
+    CompressedRistretto(bytes)
+        .decompress()
+        .ok_or_else(|| E::custom("point could not be decompressed"))
+}
+
+pub mod scalar {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(scalar: &Scalar, s: S) -> Result<S::Ok, S::Error> {
+        scalar.to_bytes().serialize(s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Scalar, D::Error> {
+        scalar_from_bytes(<[u8; 32]>::deserialize(d)?)
+    }
+}
+
+pub mod option_scalar {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(scalar: &Option<Scalar>, s: S) -> Result<S::Ok, S::Error> {
+        scalar.map(|x| x.to_bytes()).serialize(s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Scalar>, D::Error> {
+        match Option::<[u8; 32]>::deserialize(d)? {
+            Some(bytes) => scalar_from_bytes(bytes).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+pub mod scalars {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(scalars: &[Scalar], s: S) -> Result<S::Ok, S::Error> {
+        let bytes: Vec<[u8; 32]> = scalars.iter().map(|x| x.to_bytes()).collect();
+        bytes.serialize(s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<Scalar>, D::Error> {
+        Vec::<[u8; 32]>::deserialize(d)?
+            .into_iter()
+            .map(scalar_from_bytes)
+            .collect()
+    }
+}
+
+pub mod point {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(point: &RistrettoPoint, s: S) -> Result<S::Ok, S::Error> {
+        point.compress().to_bytes().serialize(s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<RistrettoPoint, D::Error> {
+        point_from_bytes(<[u8; 32]>::deserialize(d)?)
+    }
+}
+
+pub mod option_point {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        point: &Option<RistrettoPoint>,
+        s: S,
+    ) -> Result<S::Ok, S::Error> {
+        point.map(|p| p.compress().to_bytes()).serialize(s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        d: D,
+    ) -> Result<Option<RistrettoPoint>, D::Error> {
+        match Option::<[u8; 32]>::deserialize(d)? {
+            Some(bytes) => point_from_bytes(bytes).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+pub mod points {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(points: &[RistrettoPoint], s: S) -> Result<S::Ok, S::Error> {
+        let bytes: Vec<[u8; 32]> = points.iter().map(|p| p.compress().to_bytes()).collect();
+        bytes.serialize(s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<RistrettoPoint>, D::Error> {
+        Vec::<[u8; 32]>::deserialize(d)?
+            .into_iter()
+            .map(point_from_bytes)
+            .collect()
+    }
+}
+
+// Compressed points are checked for validity on the way in, so that
+// a deserialized key or signature never holds bytes which are not a point
+pub mod compressed_point {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(point: &CompressedRistretto, s: S) -> Result<S::Ok, S::Error> {
+        point.to_bytes().serialize(s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<CompressedRistretto, D::Error> {
+        let bytes = <[u8; 32]>::deserialize(d)?;
+        point_from_bytes(bytes).map(|_| CompressedRistretto(bytes))
+    }
+}
+
+// 64 byte digests are encoded as a sequence of bytes, as serde only
+// implements `Serialize` and `Deserialize` for arrays of up to 32 elements
+pub mod digest {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(digest: &[u8; 64], s: S) -> Result<S::Ok, S::Error> {
+        digest[..].serialize(s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<[u8; 64], D::Error> {
+        let bytes = Vec::<u8>::deserialize(d)?;
+        if bytes.len() != 64 {
+            return Err(D::Error::custom("digest was not 64 bytes"));
+        }
+
+        let mut digest = [0u8; 64];
+        digest.copy_from_slice(&bytes);
+        Ok(digest)
+    }
+}
@@ -0,0 +1,235 @@
+// Shamir secret sharing of secret keys.
+//
+// A secret key is split into `num_shares` shares with `split_secret`, such that any
+// `threshold` of them reconstruct the key with `reconstruct_secret`, while fewer reveal
+// nothing about it. The key is the constant term of a random polynomial of degree
+// `threshold - 1`, and the share at index `i` is the polynomial evaluated at `i`.
+//
+// Reconstructing the key puts it back on a single machine. Shares can instead sign without
+// the key ever being reconstructed, by turning the shares of each of the signers keys held
+// by a participant into a `KeyShare` with `KeyShare::from_secret_shares`, and running the
+// threshold signing protocol of the multisig module.
+use crate::error::ClsagError;
+use crate::keys::{zeroize_scalar, SecretKey};
+use curve25519_dalek::scalar::Scalar;
+use rand::{CryptoRng, RngCore};
+use std::collections::HashSet;
+use std::convert::TryInto;
+use std::fmt;
+
+// The size of an encoded share, the index and threshold followed by the value of the share
+pub const SECRET_SHARE_SIZE: usize = 40;
+
+// A share of a secret key
+#[derive(Clone)]
+pub struct SecretShare {
+    // The index of the share, starting from 1
+    index: u32,
+
+    // The number of shares needed to reconstruct the key
+    threshold: u32,
+
+    // The polynomial of the split evaluated at the index
+    value: Scalar,
+}
+
+// Splits the secret key into `num_shares` shares, such that any `threshold` of them
+// reconstruct the key.
+// Returns an error if the threshold is zero or more than the number of shares
+pub fn split_secret<R: RngCore + CryptoRng>(
+    rng: &mut R,
+    secret_key: &SecretKey,
+    threshold: usize,
+    num_shares: usize,
+) -> Result<Vec<SecretShare>, ClsagError> {
+    if threshold == 0 || threshold > num_shares || num_shares > u32::MAX as usize {
+        return Err(ClsagError::InvalidThreshold);
+    }
+
+    let mut coefficients = vec![secret_key.0];
+    coefficients.extend((1..threshold).map(|_| Scalar::random(rng)));
+
+    let shares = (1..=num_shares as u32)
+        .map(|index| {
+            let x = Scalar::from(u64::from(index));
+            let value = coefficients
+                .iter()
+                .rev()
+                .fold(Scalar::zero(), |acc, coefficient| acc * x + coefficient);
+
+            SecretShare {
+                index,
+                threshold: threshold as u32,
+                value,
+            }
+        })
+        .collect();
+
+    for coefficient in coefficients.iter_mut() {
+        zeroize_scalar(coefficient);
+    }
+
+    Ok(shares)
+}
+
+// Reconstructs the secret key from the shares.
+// Returns an error if there are fewer shares than the threshold,
+// or if the shares are not all from the same split
+pub fn reconstruct_secret(shares: &[SecretShare]) -> Result<SecretKey, ClsagError> {
+    let threshold = shares
+        .first()
+        .ok_or(ClsagError::NotEnoughParticipants)?
+        .threshold;
+    check_shares(shares, threshold)?;
+    if shares.len() < threshold as usize {
+        return Err(ClsagError::NotEnoughParticipants);
+    }
+
+    let indices = shares.iter().map(|share| share.index);
+    let secret = shares
+        .iter()
+        .map(|share| share.value * lagrange_coefficient(share.index, indices.clone()))
+        .sum();
+
+    Ok(SecretKey(secret))
+}
+
+// Checks that the shares have the threshold and distinct indices
+fn check_shares(shares: &[SecretShare], threshold: u32) -> Result<(), ClsagError> {
+    let mut indices = HashSet::new();
+    let consistent = shares
+        .iter()
+        .all(|share| share.threshold == threshold && indices.insert(share.index));
+    if !consistent {
+        return Err(ClsagError::ShareMismatch);
+    }
+    Ok(())
+}
+
+// Returns the lagrange coefficient of the share at `index`, among the shares
+// at `indices`, which weights the share so that the weighted shares sum to the key
+pub(crate) fn lagrange_coefficient<I: Iterator<Item = u32>>(index: u32, indices: I) -> Scalar {
+    let x = Scalar::from(u64::from(index));
+    let (numerator, denominator) = indices
+        .filter(|&other| other != index)
+        .map(|other| Scalar::from(u64::from(other)))
+        .fold((Scalar::one(), Scalar::one()), |(num, den), x_j| {
+            (num * x_j, den * (x_j - x))
+        });
+
+    numerator * denominator.invert()
+}
+
+impl SecretShare {
+    // Returns the index of the share, starting from 1
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+    // Returns the number of shares needed to reconstruct the key
+    pub fn threshold(&self) -> usize {
+        self.threshold as usize
+    }
+    pub(crate) fn value(&self) -> &Scalar {
+        &self.value
+    }
+    // Returns the encoding of the share, which must be kept as secret as the key
+    pub fn to_bytes(&self) -> [u8; SECRET_SHARE_SIZE] {
+        let mut bytes = [0u8; SECRET_SHARE_SIZE];
+        bytes[..4].copy_from_slice(&self.index.to_le_bytes());
+        bytes[4..8].copy_from_slice(&self.threshold.to_le_bytes());
+        bytes[8..].copy_from_slice(self.value.as_bytes());
+        bytes
+    }
+    // Decodes a share from its encoding.
+    // Returns an error if the index or the threshold is zero,
+    // or if the value is not a canonical scalar
+    pub fn from_bytes(bytes: &[u8; SECRET_SHARE_SIZE]) -> Result<Self, ClsagError> {
+        let index = u32::from_le_bytes(bytes[..4].try_into().unwrap());
+        let threshold = u32::from_le_bytes(bytes[4..8].try_into().unwrap());
+        if index == 0 || threshold == 0 {
+            return Err(ClsagError::InvalidThreshold);
+        }
+        let value = Scalar::from_canonical_bytes(bytes[8..].try_into().unwrap())
+            .ok_or(ClsagError::NonCanonicalScalar)?;
+
+        Ok(SecretShare {
+            index,
+            threshold,
+            value,
+        })
+    }
+}
+
+// The value of the share is never printed, so that it cannot end up in logs
+impl fmt::Debug for SecretShare {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SecretShare")
+            .field("index", &self.index)
+            .field("threshold", &self.threshold)
+            .finish()
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for SecretShare {
+    fn drop(&mut self) {
+        zeroize_scalar(&mut self.value);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_split_and_reconstruct() {
+        let mut rng = rand::thread_rng();
+        let secret_key = SecretKey::random(&mut rng);
+
+        for &(threshold, num_shares) in &[(1, 1), (2, 3), (3, 5)] {
+            let shares = split_secret(&mut rng, &secret_key, threshold, num_shares).unwrap();
+            assert_eq!(num_shares, shares.len());
+
+            // Any `threshold` of the shares reconstruct the key, as do all of them
+            let last: Vec<SecretShare> = shares.iter().rev().take(threshold).cloned().collect();
+            assert_eq!(secret_key.0, reconstruct_secret(&last).unwrap().0);
+            assert_eq!(secret_key.0, reconstruct_secret(&shares).unwrap().0);
+
+            let decoded: Vec<SecretShare> = shares
+                .iter()
+                .map(|share| SecretShare::from_bytes(&share.to_bytes()).unwrap())
+                .collect();
+            assert_eq!(secret_key.0, reconstruct_secret(&decoded).unwrap().0);
+        }
+    }
+
+    #[test]
+    fn test_reconstruct_errors() {
+        let mut rng = rand::thread_rng();
+        let secret_key = SecretKey::random(&mut rng);
+
+        for &(threshold, num_shares) in &[(0, 3), (4, 3)] {
+            match split_secret(&mut rng, &secret_key, threshold, num_shares) {
+                Err(ClsagError::InvalidThreshold) => {}
+                _ => panic!("expected an invalid threshold error"),
+            }
+        }
+
+        let shares = split_secret(&mut rng, &secret_key, 2, 3).unwrap();
+        match reconstruct_secret(&shares[..1]) {
+            Err(ClsagError::NotEnoughParticipants) => {}
+            _ => panic!("expected a not enough participants error"),
+        }
+        match reconstruct_secret(&[shares[0].clone(), shares[0].clone()]) {
+            Err(ClsagError::ShareMismatch) => {}
+            _ => panic!("expected a share mismatch error"),
+        }
+
+        // Shares of different splits cannot be mixed
+        let other = split_secret(&mut rng, &secret_key, 3, 3).unwrap();
+        match reconstruct_secret(&[shares[0].clone(), other[1].clone(), other[2].clone()]) {
+            Err(ClsagError::ShareMismatch) => {}
+            _ => panic!("expected a share mismatch error"),
+        }
+    }
+}
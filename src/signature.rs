@@ -1,167 +1,744 @@
-use crate::clsag::calc_aggregation_coefficients;
+use crate::clsag::{
+    aggregation_coefficients, aggregation_coefficients_into, aggregation_transcript,
+    calc_aggregation_coefficients, PublicKeys,
+};
 use crate::constants::BASEPOINT;
-use crate::member::compute_challenge_ring;
-use crate::transcript::TranscriptProtocol;
+use crate::contract::{CommitmentOpening, CommitmentProof};
+use crate::error::ClsagError;
+use crate::hash::{hash_pubkey, DefaultHash, DomainSeparated};
+use crate::keys::{KeyImage, PublicKey};
+use crate::member::{ring_challenge, round_challenge, round_transcript, signed_round_transcript};
+use crate::policy::VerificationLimits;
+use crate::range::{range_transcript, RangeProofSystem};
+use crate::report::VerificationReport;
+use crate::ring::{is_canonical, PreparedRing, RingTranscripts};
+#[cfg(feature = "trace")]
+use crate::trace;
+use crate::transcript::{
+    context_transcript, default_transcript, digest_transcript, SigningTranscript,
+};
 use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
 use curve25519_dalek::scalar::Scalar;
 use curve25519_dalek::traits::VartimeMultiscalarMul;
-use merlin::Transcript;
-use sha2::Sha512;
+#[cfg(feature = "serde")]
+use std::convert::TryFrom;
+use std::iter;
 
 #[derive(Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "SerdeSignature")
+)]
 pub struct Signature {
-    pub challenge: Scalar,
-    pub responses: Vec<Scalar>,
-    pub key_images: Vec<CompressedRistretto>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::scalar"))]
+    pub(crate) challenge: Scalar,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::scalars"))]
+    pub(crate) responses: Vec<Scalar>,
+    pub(crate) key_images: Vec<KeyImage>,
 }
 
-pub enum Error {
-    // This error occurs if the signature contains an amount of public keys
-    // that does not match the number of public keys
-    IncorrectNumOfPubKeys,
-    // This error occurs when either one of the key images supplied cannot be decompressed
-    BadKeyImages,
-    // This error occurs when the calculated challenge is different from the challenge in the signature
-    ChallengeMismatch,
-    // This error occurs when the point cannot be correctly decompressed
-    BadPoint,
-    // This error occurs when an underlying error from the member package occurs
-    MemberError(String),
+// A signature is deserialized with the same fields, and rejected if it is over a ring
+// larger than the default verification limits allow
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct SerdeSignature {
+    #[serde(with = "crate::serde_helpers::scalar")]
+    challenge: Scalar,
+    #[serde(with = "crate::serde_helpers::scalars")]
+    responses: Vec<Scalar>,
+    key_images: Vec<KeyImage>,
 }
 
-impl From<crate::member::Error> for Error {
-    fn from(e: crate::member::Error) -> Error {
-        let err_string = format!(" underlying member error {:?}", e);
-        Error::MemberError(err_string)
+#[cfg(feature = "serde")]
+impl TryFrom<SerdeSignature> for Signature {
+    type Error = ClsagError;
+
+    fn try_from(signature: SerdeSignature) -> Result<Self, Self::Error> {
+        VerificationLimits::default()
+            .check(signature.responses.len(), signature.key_images.len())?;
+        Ok(Signature {
+            challenge: signature.challenge,
+            responses: signature.responses,
+            key_images: signature.key_images,
+        })
+    }
+}
+
+// The buffers `Signature::verify_in_place` works in, allocated by the caller,
+// which may be reused for every signature verified
+pub struct VerifyScratch<'a> {
+    // At least `pubkey_matrix_len(ring_size, num_keys)` bytes, for the public key matrix
+    pub pubkey_matrix: &'a mut [u8],
+    // At least one co-efficient per key of each member
+    pub agg_coeffs: &'a mut [Scalar],
+    // At least one point per key image
+    pub key_images: &'a mut [RistrettoPoint],
+}
+
+impl VerifyScratch<'_> {
+    // Returns the number of bytes of the public key matrix of a ring
+    // of `ring_size` members, who each have `num_keys` keys
+    pub fn pubkey_matrix_len(ring_size: usize, num_keys: usize) -> usize {
+        ring_size * num_keys * ELEMENT_SIZE
     }
 }
 
+// The number of bytes used to encode a scalar or a compressed point
+const ELEMENT_SIZE: usize = 32;
+// The number of bytes used to encode the two length prefixes
+const HEADER_SIZE: usize = 8;
+
 impl Signature {
-    pub fn verify(
-        &self,
-        public_keys: &mut Vec<Vec<CompressedRistretto>>, //Doesn't need to be mutable... yet. In the future this set will get updated at one point (i think)
-        msg: &[u8],
-    ) -> Result<(), Error> {
+    // Encodes the signature with the following fixed layout, all integers little-endian:
+    //
+    // | num_responses (u32) | num_key_images (u32) | challenge (32) | responses (32 * num_responses) | key_images (32 * num_key_images) |
+    //
+    // Scalars are encoded in canonical form and key images as compressed ristretto points
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(Self::serialized_size(
+            self.responses.len(),
+            self.key_images.len(),
+        ));
+
+        bytes.extend_from_slice(&(self.responses.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&(self.key_images.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(self.challenge.as_bytes());
+        for response in &self.responses {
+            bytes.extend_from_slice(response.as_bytes());
+        }
+        for key_image in &self.key_images {
+            bytes.extend_from_slice(key_image.as_bytes());
+        }
+
+        bytes
+    }
+
+    // Decodes a signature produced by `to_bytes`
+    // Rejects encodings with trailing or missing bytes, empty response or key image lists,
+    // non-canonical scalars and key images which are not valid ristretto points,
+    // and signatures over a ring larger than the default verification limits allow
+    pub fn from_bytes(bytes: &[u8]) -> Result<Signature, ClsagError> {
+        Self::from_bytes_with_limits(bytes, &VerificationLimits::default())
+    }
+    // Decodes a signature as `from_bytes` does, within the given limits
+    pub fn from_bytes_with_limits(
+        bytes: &[u8],
+        limits: &VerificationLimits,
+    ) -> Result<Signature, ClsagError> {
+        if bytes.len() < HEADER_SIZE {
+            return Err(ClsagError::DecodeError);
+        }
+        let num_responses = read_u32(&bytes[0..4]) as usize;
+        let num_key_images = read_u32(&bytes[4..8]) as usize;
+
+        if num_responses == 0 || num_key_images == 0 {
+            return Err(ClsagError::DecodeError);
+        }
+
+        // Check the length before allocating anything, so that a bogus header
+        // cannot be used to make us allocate large amounts of memory
+        let expected_len = num_responses
+            .checked_add(num_key_images)
+            .and_then(|n| n.checked_add(1))
+            .and_then(|n| n.checked_mul(ELEMENT_SIZE))
+            .and_then(|n| n.checked_add(HEADER_SIZE))
+            .ok_or(ClsagError::DecodeError)?;
+        if bytes.len() != expected_len {
+            return Err(ClsagError::DecodeError);
+        }
+        limits.check(num_responses, num_key_images)?;
+
+        decode_elements(&bytes[HEADER_SIZE..], num_responses)
+    }
+
+    // Returns the signature in its pruned encoding, which is the encoding of `to_bytes`
+    // without the header, as the lengths are known from the ring
+    pub fn prune(&self) -> PrunedSignature {
+        PrunedSignature(self.to_bytes()[HEADER_SIZE..].to_vec())
+    }
+
+    // Returns the key images of the signer, one for each of their keys.
+    // The first key image is the one which links signatures made by the same signing key
+    pub fn key_images(&self) -> &[KeyImage] {
+        &self.key_images
+    }
+
+    // Returns true if both signatures were made by the same signing key, which is when their
+    // first key images are the same. The key images of the auxiliary keys are not compared,
+    // see the linkability module. Neither signature is verified
+    pub fn links_with(&self, other: &Signature) -> bool {
+        match (self.key_images.first(), other.key_images.first()) {
+            (Some(key_image), Some(other_key_image)) => key_image == other_key_image,
+            _ => false,
+        }
+    }
+
+    // Returns true if the signature was made by the same signing key as any of the others
+    pub fn links_any(&self, others: &[Signature]) -> bool {
+        others.iter().any(|other| self.links_with(other))
+    }
+
+    // Returns the number of layers the signature was made over,
+    // which is the number of keys each member of the ring signed with
+    pub fn num_layers(&self) -> usize {
+        self.key_images.len()
+    }
+
+    // Returns the length of the encoding from `to_bytes` of a signature over a ring
+    // of `ring_size` members, who each have `key_count` keys
+    pub fn serialized_size(ring_size: usize, key_count: usize) -> usize {
+        HEADER_SIZE + PrunedSignature::pruned_size(ring_size, key_count)
+    }
+
+    pub fn verify(&self, public_keys: &[Vec<PublicKey>], msg: &[u8]) -> Result<(), ClsagError> {
         // Skip subgroup check as ristretto points have co-factor 1.
+        self.check_canonical()?;
+        let num_keys = public_keys.first().map_or(0, Vec::len);
+        VerificationLimits::default().check(public_keys.len(), num_keys)?;
+        if public_keys.is_empty() {
+            return Err(ClsagError::EmptyRing);
+        }
+        // -- Check that each member has a key for every key image, before any member is read
+        if num_keys == 0
+            || public_keys
+                .iter()
+                .any(|keys| keys.len() != self.key_images.len())
+        {
+            return Err(ClsagError::KeyCountMismatch);
+        }
 
         let num_responses = self.responses.len();
         let num_pubkey_sets = public_keys.len();
 
         // -- Check that we have the correct amount of public keys
         if num_pubkey_sets != num_responses {
-            return Err(Error::IncorrectNumOfPubKeys);
+            return Err(ClsagError::RingSizeMismatch);
         }
 
         let pubkey_matrix_bytes: Vec<u8> = self.pubkeys_to_bytes(public_keys);
 
         // Calculate aggregation co-efficients
-        let agg_coeffs = calc_aggregation_coefficients(&pubkey_matrix_bytes, &self.key_images, msg);
+        let transcript = default_transcript();
+        let agg_coeffs =
+            calc_aggregation_coefficients(&transcript, &pubkey_matrix_bytes, &self.key_images, msg);
+
+        let round = round_transcript(&transcript, &pubkey_matrix_bytes, &self.key_images, msg);
 
         let mut challenge = self.challenge;
         for (pub_keys, response) in public_keys.iter().zip(self.responses.iter()) {
             let first_pubkey = pub_keys[0];
-            let hashed_pubkey = RistrettoPoint::hash_from_bytes::<Sha512>(first_pubkey.as_bytes());
-            challenge = compute_challenge_ring(
+            let hashed_pubkey = hash_pubkey(first_pubkey.as_bytes());
+            challenge = ring_challenge(
+                &round,
                 pub_keys,
                 &challenge,
                 &self.key_images,
                 response,
                 &agg_coeffs,
                 &hashed_pubkey,
-                &pubkey_matrix_bytes,
-            );
+                &BASEPOINT,
+            )?;
         }
 
         if self.challenge != challenge {
-            return Err(Error::ChallengeMismatch);
+            return Err(ClsagError::ChallengeMismatch);
         }
 
         Ok(())
     }
 
+    // Verifies a signature over a ring where every member holds a commitment.
+    // The commitment to zero `commitment - pseudo_out` is added to each members
+    // public keys, as it was when the ring was signed
+    pub fn verify_with_commitments(
+        &self,
+        public_keys: &PublicKeys,
+        commitments: &[CompressedRistretto],
+        pseudo_out: &RistrettoPoint,
+        msg: &[u8],
+    ) -> Result<(), ClsagError> {
+        if public_keys.len() != commitments.len() {
+            return Err(ClsagError::RingSizeMismatch);
+        }
+
+        let ring = public_keys
+            .iter()
+            .zip(commitments.iter())
+            .map(|(keys, commitment)| {
+                let commitment = commitment.decompress().ok_or(ClsagError::InvalidPoint)?;
+                let mut keys = keys.clone();
+                keys.push(PublicKey::from_point(&(commitment - pseudo_out)));
+                Ok(keys)
+            })
+            .collect::<Result<PublicKeys, ClsagError>>()?;
+
+        self.optimised_verify(&ring, msg)
+    }
+
+    // Verifies a signature over a ring of commitments as `verify_with_commitments` does,
+    // along with the range proof for the amount of the pseudo output
+    pub fn verify_with_range_proof<S: RangeProofSystem>(
+        &self,
+        public_keys: &PublicKeys,
+        commitments: &[CompressedRistretto],
+        pseudo_out: &RistrettoPoint,
+        msg: &[u8],
+        system: &S,
+        proof: &S::Proof,
+    ) -> Result<(), ClsagError> {
+        self.verify_with_commitments(public_keys, commitments, pseudo_out, msg)?;
+        system.verify(&mut range_transcript(msg), pseudo_out, proof)
+    }
+
     pub fn optimised_verify(
         &self,
-        public_keys: &mut Vec<Vec<CompressedRistretto>>,
+        public_keys: &[Vec<PublicKey>],
         msg: &[u8],
-    ) -> Result<(), Error> {
-        // Skip subgroup check as ristretto points have co-factor 1.
+    ) -> Result<(), ClsagError> {
+        self.verify_with_transcript(&default_transcript(), public_keys, msg)
+    }
 
-        let num_responses = self.responses.len();
-        let num_pubkey_sets = public_keys.len();
+    // Verifies a signature which was produced by a ring created with `Clsag::with_generator`,
+    // over a ring whose keys are multiples of the same generator
+    pub fn verify_with_generator(
+        &self,
+        public_keys: &[Vec<PublicKey>],
+        msg: &[u8],
+        generator: &RistrettoPoint,
+    ) -> Result<(), ClsagError> {
+        let mut ring = PreparedRing::new(public_keys)?;
+        ring.set_generator(*generator);
+        self.verify_prepared(&ring, msg)
+    }
+
+    // Verifies the signature over a ring which must be in canonical order,
+    // as it is after `ring::canonicalize` or `Clsag::canonicalize_members`.
+    // A ring in any other order is rejected without the signature being verified
+    pub fn verify_canonical(
+        &self,
+        public_keys: &[Vec<PublicKey>],
+        msg: &[u8],
+    ) -> Result<(), ClsagError> {
+        if !is_canonical(public_keys) {
+            return Err(ClsagError::RingNotCanonical);
+        }
+
+        self.optimised_verify(public_keys, msg)
+    }
+
+    // Verifies the signature, and checks that its key images are the expected key images,
+    // such as the key images already associated with an output.
+    // The key images are checked first, so a signature with other key images
+    // is rejected without being verified
+    pub fn verify_with_key_images(
+        &self,
+        public_keys: &[Vec<PublicKey>],
+        msg: &[u8],
+        expected_key_images: &[KeyImage],
+    ) -> Result<(), ClsagError> {
+        if self.key_images != expected_key_images {
+            return Err(ClsagError::KeyImageMismatch);
+        }
+
+        self.optimised_verify(public_keys, msg)
+    }
+
+    // Verifies a signature which was produced with `Clsag::sign_with_context`
+    pub fn verify_with_context(
+        &self,
+        public_keys: &[Vec<PublicKey>],
+        msg: &[u8],
+        context: &[u8],
+    ) -> Result<(), ClsagError> {
+        self.verify_with_transcript(&context_transcript(context), public_keys, msg)
+    }
+
+    // Verifies a signature which was produced with `Clsag::sign_digest`,
+    // over the 64 byte SHA-512 digest of the message
+    pub fn verify_digest(
+        &self,
+        public_keys: &[Vec<PublicKey>],
+        digest: &[u8; 64],
+    ) -> Result<(), ClsagError> {
+        self.verify_with_transcript(&digest_transcript(), public_keys, digest)
+    }
+
+    // Verifies a signature which was produced with `Clsag::sign_with_transcript`.
+    // The transcript must hold the same context it held when signing
+    pub fn verify_with_transcript<T: SigningTranscript>(
+        &self,
+        transcript: &T,
+        public_keys: &[Vec<PublicKey>],
+        msg: &[u8],
+    ) -> Result<(), ClsagError> {
+        // -- Check that we have the correct amount of public keys
+        if public_keys.len() != self.responses.len() {
+            return Err(ClsagError::RingSizeMismatch);
+        }
+
+        let ring = PreparedRing::new(public_keys)?;
+        self.verify_prepared_with_transcript(transcript, &ring, msg)
+    }
+
+    // Verifies the signature as `optimised_verify` does, over a ring within the given limits
+    // instead of the default ones
+    pub fn verify_with_limits(
+        &self,
+        public_keys: &[Vec<PublicKey>],
+        msg: &[u8],
+        limits: &VerificationLimits,
+    ) -> Result<(), ClsagError> {
+        let hash_to_point = DomainSeparated::<DefaultHash>::new();
+        let ring = PreparedRing::with_limits(public_keys, &hash_to_point, limits)?;
+        self.verify_prepared(&ring, msg)
+    }
+
+    // Verifies the signature as `verify` does, without allocating, for targets where the heap
+    // is small or missing. The public key matrix, the aggregation co-efficients and the key
+    // images are held in the scratch buffers, and every other value is held on the stack.
+    // Returns an error if a buffer is too small for the ring, see `VerifyScratch`
+    pub fn verify_in_place<K: AsRef<[PublicKey]>>(
+        &self,
+        public_keys: &[K],
+        msg: &[u8],
+        scratch: &mut VerifyScratch,
+    ) -> Result<(), ClsagError> {
+        // Skip subgroup check as ristretto points have co-factor 1.
+        self.check_scalars()?;
 
         // -- Check that we have the correct amount of public keys
-        if num_pubkey_sets != num_responses {
-            return Err(Error::IncorrectNumOfPubKeys);
+        let num_layers = self.key_images.len();
+        if public_keys.len() != self.responses.len() {
+            return Err(ClsagError::RingSizeMismatch);
+        }
+        // -- Check that each member has a key for every key image
+        if public_keys
+            .iter()
+            .any(|keys| keys.as_ref().len() != num_layers)
+        {
+            return Err(ClsagError::KeyCountMismatch);
         }
+        VerificationLimits::default().check(public_keys.len(), num_layers)?;
 
-        // Calculate all response * BASEPOINT
-        let response_points: Vec<RistrettoPoint> = self
-            .responses
+        let matrix_len = VerifyScratch::pubkey_matrix_len(public_keys.len(), num_layers);
+        if scratch.pubkey_matrix.len() < matrix_len
+            || scratch.agg_coeffs.len() < num_layers
+            || scratch.key_images.len() < num_layers
+        {
+            return Err(ClsagError::BufferTooSmall);
+        }
+        let pubkey_matrix = &mut scratch.pubkey_matrix[..matrix_len];
+        let agg_coeffs = &mut scratch.agg_coeffs[..num_layers];
+        let key_images = &mut scratch.key_images[..num_layers];
+
+        let keys = public_keys.iter().flat_map(|keys| keys.as_ref());
+        for (bytes, key) in pubkey_matrix.chunks_exact_mut(ELEMENT_SIZE).zip(keys) {
+            bytes.copy_from_slice(key.as_bytes());
+        }
+        for (point, key_image) in key_images.iter_mut().zip(self.key_images.iter()) {
+            *point = key_image.check()?;
+        }
+
+        let transcript = default_transcript();
+        let aggregation = aggregation_transcript(&transcript, pubkey_matrix, num_layers);
+        aggregation_coefficients_into(&aggregation, &self.key_images, msg, agg_coeffs);
+        let round = round_transcript(&transcript, pubkey_matrix, &self.key_images, msg);
+
+        let mut challenge = self.challenge;
+        for (keys, response) in public_keys.iter().zip(self.responses.iter()) {
+            let keys = keys.as_ref();
+
+            // L = response * G + sum(challenge * mu_j * public_keys)
+            // R = response * H(signingKey) + sum(challenge * mu_j * key_images)
+            let mut l = response * BASEPOINT;
+            let mut r = response * hash_pubkey(keys[0].as_bytes());
+            for ((mu, key), key_image) in agg_coeffs.iter().zip(keys).zip(key_images.iter()) {
+                let scalar = challenge * mu;
+                l += scalar * key.decompress().ok_or(ClsagError::InvalidPoint)?;
+                r += scalar * key_image;
+            }
+            challenge = round_challenge(&round, &l, &r);
+        }
+
+        if challenge != self.challenge {
+            return Err(ClsagError::ChallengeMismatch);
+        }
+        Ok(())
+    }
+
+    // Verifies the signature over a ring which has been prepared with `PreparedRing::new`
+    pub fn verify_prepared(&self, ring: &PreparedRing, msg: &[u8]) -> Result<(), ClsagError> {
+        self.verify_prepared_with_transcript(&default_transcript(), ring, msg)
+    }
+
+    // Verifies a signature which was produced with `Clsag::sign_with_transcript`,
+    // over a ring which has been prepared with `PreparedRing::new`
+    pub fn verify_prepared_with_transcript<T: SigningTranscript>(
+        &self,
+        transcript: &T,
+        ring: &PreparedRing,
+        msg: &[u8],
+    ) -> Result<(), ClsagError> {
+        self.verify_prepared_checking(transcript, ring, msg, true)
+    }
+
+    // Verifies the signature without checking that its key images are valid, so that
+    // a signature with an identity key image is accepted as it was before key images
+    // were checked. This is only for signatures which were accepted by older versions,
+    // every other signature should be verified with `optimised_verify`
+    pub fn verify_legacy(
+        &self,
+        public_keys: &[Vec<PublicKey>],
+        msg: &[u8],
+    ) -> Result<(), ClsagError> {
+        let ring = PreparedRing::new(public_keys)?;
+        self.verify_prepared_checking(&default_transcript(), &ring, msg, false)
+    }
+
+    fn verify_prepared_checking<T: SigningTranscript>(
+        &self,
+        transcript: &T,
+        ring: &PreparedRing,
+        msg: &[u8],
+        check_key_images: bool,
+    ) -> Result<(), ClsagError> {
+        // Skip subgroup check as ristretto points have co-factor 1.
+        let transcripts = RingTranscripts::new(transcript, ring);
+        self.verify_with_ring_transcripts(&transcripts, ring, msg, check_key_images)
+    }
+
+    // Verifies the signature with the transcripts of a ring, which are shared between
+    // every signature over the ring by `PreparedRing::verify_many`
+    pub(crate) fn verify_with_ring_transcripts<T: SigningTranscript>(
+        &self,
+        transcripts: &RingTranscripts<T>,
+        ring: &PreparedRing,
+        msg: &[u8],
+        check_key_images: bool,
+    ) -> Result<(), ClsagError> {
+        let verify = || {
+            // -- Check that we have the correct amount of public keys
+            if ring.len() != self.responses.len() {
+                return Err(ClsagError::RingSizeMismatch);
+            }
+            // -- Check that each member has a key for every key image
+            if ring.num_layers() != self.key_images.len() {
+                return Err(ClsagError::KeyCountMismatch);
+            }
+
+            self.check_scalars()?;
+            let key_images = self
+                .key_images
+                .iter()
+                .map(|key_image| match check_key_images {
+                    true => key_image.check(),
+                    false => key_image.decompress().ok_or(ClsagError::InvalidKeyImage),
+                })
+                .collect::<Result<Vec<RistrettoPoint>, ClsagError>>()?;
+
+            let challenge = self.challenge_chain(transcripts, ring, msg, &key_images, |_| {});
+            if challenge != self.challenge {
+                return Err(ClsagError::ChallengeMismatch);
+            }
+
+            Ok(())
+        };
+
+        ring.metrics().observe_verify(|| {
+            #[cfg(feature = "trace")]
+            return trace::instrument(
+                trace::Operation::Verify,
+                ring.len(),
+                ring.num_layers(),
+                verify,
+            );
+            #[cfg(not(feature = "trace"))]
+            verify()
+        })
+    }
+
+    // Recomputes the nonce commitment of the signer at the position in the opening, for
+    // signatures made with `Clsag::sign_with_commitment`, so that it can be checked against
+    // the data the signature commits to with `CommitmentProof::verify`.
+    // Returns an error if the signature does not verify or the position is not in the ring
+    pub fn extract_commitment_proof(
+        &self,
+        public_keys: &[Vec<PublicKey>],
+        msg: &[u8],
+        opening: &CommitmentOpening,
+    ) -> Result<CommitmentProof, ClsagError> {
+        let mut ring = PreparedRing::new(public_keys)?;
+        ring.set_generator(opening.generator);
+        self.verify_prepared(&ring, msg)?;
+        let signer_index = opening.signer_index;
+        if signer_index >= ring.len() {
+            return Err(ClsagError::MemberNotFound);
+        }
+
+        let transcript = default_transcript();
+        let key_images = self
+            .key_images
             .iter()
-            .map(|response| response * BASEPOINT)
+            .map(KeyImage::check)
+            .collect::<Result<Vec<RistrettoPoint>, ClsagError>>()?;
+        let mut challenges = vec![self.challenge];
+        let transcripts = RingTranscripts::new(&transcript, &ring);
+        self.challenge_chain(&transcripts, &ring, msg, &key_images, |challenge| {
+            challenges.push(challenge)
+        });
+
+        // L = response * G + sum(challenge * mu_j * public_keys)
+        let agg_coeffs =
+            calc_aggregation_coefficients(&transcript, ring.pubkey_matrix(), &self.key_images, msg);
+        let scalars: Vec<Scalar> = iter::once(self.responses[signer_index])
+            .chain(agg_coeffs.iter().map(|mu| mu * challenges[signer_index]))
             .collect();
+        let committed_nonce = RistrettoPoint::vartime_multiscalar_mul(
+            &scalars,
+            iter::once(ring.generator()).chain(ring.points()[signer_index].iter()),
+        );
 
-        // calculate all response * H(signingKeys)
-        let response_hashed_points: Vec<RistrettoPoint> = self
-            .responses
-            .iter()
-            .zip(public_keys.iter())
-            .map(|(response, pub_keys)| {
-                let first_pubkey = pub_keys[0];
-                let hashed_pubkey =
-                    RistrettoPoint::hash_from_bytes::<Sha512>(first_pubkey.as_bytes());
+        Ok(CommitmentProof::new(*opening, committed_nonce))
+    }
 
-                response * hashed_pubkey
+    // Verifies the signature as `optimised_verify` does, and returns a report of every step
+    // of verification instead of stopping at the first error, for debugging signatures
+    // from other CLSAG implementations
+    pub fn verify_detailed(
+        &self,
+        public_keys: &[Vec<PublicKey>],
+        msg: &[u8],
+    ) -> VerificationReport {
+        let key_images_valid = self.key_images.iter().all(KeyImage::is_valid);
+        let mut report = VerificationReport {
+            challenges: Vec::new(),
+            key_images_valid,
+            diverged_at: None,
+            result: Ok(()),
+        };
+
+        // The signature and the ring are checked in the order `optimised_verify` checks them,
+        // and nothing is decompressed until both are within the default limits.
+        // The chain is recomputed even when the key images are not valid,
+        // as long as they can be decompressed
+        let prepared = VerificationLimits::default()
+            .check(self.responses.len(), self.key_images.len())
+            .and_then(|()| match public_keys.len() == self.responses.len() {
+                true => PreparedRing::new(public_keys),
+                false => Err(ClsagError::RingSizeMismatch),
             })
-            .collect();
+            .and_then(|ring| {
+                if ring.num_layers() != self.key_images.len() {
+                    return Err(ClsagError::KeyCountMismatch);
+                }
+                self.check_scalars()?;
+                let key_images = self
+                    .key_images
+                    .iter()
+                    .map(|key_image| key_image.decompress().ok_or(ClsagError::InvalidKeyImage))
+                    .collect::<Result<Vec<RistrettoPoint>, ClsagError>>()?;
+                Ok((ring, key_images))
+            });
+        let (ring, key_images) = match prepared {
+            Ok(prepared) => prepared,
+            Err(err) => {
+                report.result = Err(err);
+                return report;
+            }
+        };
 
-        // compute the public key bytes
-        let pubkey_matrix_bytes = self.pubkeys_to_bytes(public_keys);
+        report.challenges.push(self.challenge);
+        let challenge = self.challenge_chain(
+            &RingTranscripts::new(&default_transcript(), &ring),
+            &ring,
+            msg,
+            &key_images,
+            |challenge| report.challenges.push(challenge),
+        );
 
+        // The challenge of every member is only checked where the chain closes, so a chain
+        // which does not close is reported as diverging at the member closing it
+        let closes = challenge == self.challenge;
+        if !closes {
+            report.diverged_at = Some(ring.len() - 1);
+        }
+        report.result = self.check_canonical().and(match closes {
+            true => Ok(()),
+            false => Err(ClsagError::ChallengeMismatch),
+        });
+        report
+    }
+
+    // Recomputes the challenge of every member from the challenge in the signature,
+    // passing each one to `record`, and returns the challenge computed by the last member,
+    // which is the challenge in the signature if it is valid
+    fn challenge_chain<T: SigningTranscript, F: FnMut(Scalar)>(
+        &self,
+        transcripts: &RingTranscripts<T>,
+        ring: &PreparedRing,
+        msg: &[u8],
+        key_images: &[RistrettoPoint],
+        mut record: F,
+    ) -> Scalar {
         // Calculate aggregation co-efficients
-        let agg_coeffs = calc_aggregation_coefficients(&pubkey_matrix_bytes, &self.key_images, msg);
+        let agg_coeffs = aggregation_coefficients(&transcripts.aggregation, &self.key_images, msg);
+        let round = signed_round_transcript(&transcripts.round, &self.key_images, msg);
 
         let mut challenge = self.challenge;
 
-        for ((resp_point, resp_hashed_point), pub_keys) in response_points
+        for ((response, hashed_pubkey), points) in self
+            .responses
             .iter()
-            .zip(response_hashed_points.iter())
-            .zip(public_keys.iter())
+            .zip(ring.hashed_pubkeys().iter())
+            .zip(ring.points().iter())
         {
-            let challenge_agg_coeffs: Vec<Scalar> =
-                agg_coeffs.iter().map(|ac| ac * challenge).collect();
-                //agg_coeffs.iter().map(|ac| ac * &challenge).collect();
+            // L = response * generator + sum(challenge * mu_j * public_keys)
+            // R = response * H(signingKey) + sum(challenge * mu_j * key_images)
+            // Both points are computed with a single variable time multiscalar multiplication,
+            // as every value in the signature and the ring is public
+            let scalars: Vec<Scalar> = iter::once(*response)
+                .chain(agg_coeffs.iter().map(|ac| ac * challenge))
+                .collect();
 
-            let mut l_i = RistrettoPoint::optional_multiscalar_mul(
-                &challenge_agg_coeffs,
-                pub_keys.iter().map(|pt| pt.decompress()),
-            )
-            .ok_or(Error::BadPoint)?;
-            l_i += resp_point;
+            let l_i = RistrettoPoint::vartime_multiscalar_mul(
+                &scalars,
+                iter::once(ring.generator()).chain(points.iter()),
+            );
+            let r_i = RistrettoPoint::vartime_multiscalar_mul(
+                &scalars,
+                iter::once(hashed_pubkey).chain(key_images.iter()),
+            );
 
-            let mut r_i = RistrettoPoint::optional_multiscalar_mul(
-                &challenge_agg_coeffs,
-                self.key_images.iter().map(|pt| pt.decompress()),
-            )
-            .ok_or(Error::BadPoint)?;
-            r_i += resp_hashed_point;
+            challenge = round_challenge(&round, &l_i, &r_i);
+            record(challenge);
+        }
 
-            let mut transcript = Transcript::new(b"clsag");
-            transcript.append_message(b"", &pubkey_matrix_bytes);
-            transcript.append_point(b"", &l_i);
-            transcript.append_point(b"", &r_i);
+        challenge
+    }
 
-            challenge = transcript.challenge_scalar(b"");
-        }
+    // Checks that every scalar in the signature is canonical and every key image is the
+    // canonical encoding of a point other than the identity, so that a signature has
+    // exactly one valid encoding. Signatures from `from_bytes` or serde always pass,
+    // the check guards verification against signatures built any other way
+    pub(crate) fn check_canonical(&self) -> Result<(), ClsagError> {
+        self.check_scalars()?;
+        self.key_images
+            .iter()
+            .try_for_each(|key_image| key_image.check().map(|_| ()))
+    }
 
-        if challenge != self.challenge {
-            return Err(Error::ChallengeMismatch);
+    fn check_scalars(&self) -> Result<(), ClsagError> {
+        let all_canonical = iter::once(&self.challenge)
+            .chain(self.responses.iter())
+            .all(Scalar::is_canonical);
+        if !all_canonical {
+            return Err(ClsagError::NonCanonicalScalar);
         }
-
         Ok(())
     }
 
-    fn pubkeys_to_bytes(&self, pubkey_matrix: &Vec<Vec<CompressedRistretto>>) -> Vec<u8> {
+    fn pubkeys_to_bytes(&self, pubkey_matrix: &[Vec<PublicKey>]) -> Vec<u8> {
         let mut bytes: Vec<u8> =
             Vec::with_capacity(self.key_images.len() * self.responses.len() * 64);
         for i in pubkey_matrix{
@@ -175,12 +752,98 @@ impl Signature {
     }
 }
 
+// A signature in its pruned encoding, which leaves out the number of responses
+// and key images, as they are the size of the ring and the number of keys of each member.
+// This is how blockchains store ring signatures, where the ring is stored alongside
+// the signature. The signature is expanded with the size of the ring to verify it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrunedSignature(Vec<u8>);
+
+impl PrunedSignature {
+    // Holds the bytes of a pruned signature, which are only decoded when it is expanded
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        PrunedSignature(bytes.to_vec())
+    }
+    // Returns the bytes of the pruned signature
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+    // Returns the length of a pruned signature over a ring of `ring_size` members,
+    // who each have `key_count` keys
+    pub fn pruned_size(ring_size: usize, key_count: usize) -> usize {
+        ELEMENT_SIZE * (1 + ring_size + key_count)
+    }
+    // Decodes the full signature over a ring of `ring_size` members, who each have
+    // `key_count` keys. Returns an error if the bytes are not the length of a signature
+    // over such a ring, or they hold a non-canonical scalar or an invalid key image
+    pub fn expand(&self, ring_size: usize, key_count: usize) -> Result<Signature, ClsagError> {
+        if ring_size == 0 || key_count == 0 {
+            return Err(ClsagError::DecodeError);
+        }
+        let expected_len = ring_size
+            .checked_add(key_count)
+            .and_then(|n| n.checked_add(1))
+            .and_then(|n| n.checked_mul(ELEMENT_SIZE))
+            .ok_or(ClsagError::DecodeError)?;
+        if self.0.len() != expected_len {
+            return Err(ClsagError::DecodeError);
+        }
+
+        decode_elements(&self.0, ring_size)
+    }
+}
+
+// Decodes the challenge, the responses and the key images of a signature,
+// from bytes which have already been checked to be the right length
+fn decode_elements(bytes: &[u8], num_responses: usize) -> Result<Signature, ClsagError> {
+    let mut elements = bytes.chunks_exact(ELEMENT_SIZE).map(|chunk| {
+        let mut element = [0u8; ELEMENT_SIZE];
+        element.copy_from_slice(chunk);
+        element
+    });
+
+    let challenge = decode_scalar(elements.next().ok_or(ClsagError::DecodeError)?)?;
+
+    let responses = elements
+        .by_ref()
+        .take(num_responses)
+        .map(decode_scalar)
+        .collect::<Result<Vec<Scalar>, ClsagError>>()?;
+
+    let key_images = elements
+        .map(|element| KeyImage::from_bytes(&element))
+        .collect::<Result<Vec<KeyImage>, ClsagError>>()?;
+
+    Ok(Signature {
+        challenge,
+        responses,
+        key_images,
+    })
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    let mut buf = [0u8; 4];
+    buf.copy_from_slice(bytes);
+    u32::from_le_bytes(buf)
+}
+
+fn decode_scalar(bytes: [u8; 32]) -> Result<Scalar, ClsagError> {
+    Scalar::from_canonical_bytes(bytes).ok_or(ClsagError::NonCanonicalScalar)
+}
+
 #[cfg(test)]
 mod test {
     extern crate test;
     use test::Bencher;
 
-    use crate::tests_helper::*;
+    use super::{PrunedSignature, Signature, VerifyScratch};
+    use crate::error::ClsagError;
+    use crate::keys::KeyImage;
+    use crate::policy::VerificationLimits;
+    use crate::test_utils::*;
+    use curve25519_dalek::ristretto::RistrettoPoint;
+    use curve25519_dalek::scalar::Scalar;
+    use curve25519_dalek::traits::Identity;
     use rand::seq::SliceRandom;
     use rand::thread_rng;
 
@@ -191,15 +854,115 @@ mod test {
         let msg = b"hello world";
 
         let mut clsag = generate_clsag_with(num_decoys, num_keys);
-        clsag.add_member(generate_signer(num_keys));
-        let sig = clsag.sign(msg).unwrap();
-        let mut pub_keys = clsag.public_keys();
+        clsag.add_member(generate_signer(num_keys)).unwrap();
+        clsag.set_shuffle_members(false);
+        let (sig, pub_keys) = clsag.sign(msg).unwrap();
 
         let expected_pubkey_bytes = clsag.public_keys_bytes();
         let have_pubkey_bytes = sig.pubkeys_to_bytes(&pub_keys);
 
         assert_eq!(expected_pubkey_bytes, have_pubkey_bytes);
-        assert!(sig.optimised_verify(&mut pub_keys, msg).is_ok());
+        assert!(sig.optimised_verify(&pub_keys, msg).is_ok());
+    }
+
+    #[test]
+    fn test_verify_with_key_images() {
+        let num_keys = 2;
+        let msg = b"hello world";
+
+        let signer = generate_signer(num_keys);
+        let mut clsag = generate_clsag_with(4, num_keys);
+        clsag.add_member(signer.clone()).unwrap();
+        let (sig, pub_keys) = clsag.sign(msg).unwrap();
+
+        let expected_key_images = signer.compute_key_images().unwrap();
+        assert_eq!(expected_key_images.as_slice(), sig.key_images());
+        assert!(sig
+            .verify_with_key_images(&pub_keys, msg, &expected_key_images)
+            .is_ok());
+
+        // The key images of another signer are rejected
+        let other_key_images = generate_signer(num_keys).compute_key_images().unwrap();
+        match sig.verify_with_key_images(&pub_keys, msg, &other_key_images) {
+            Err(ClsagError::KeyImageMismatch) => {}
+            _ => panic!("expected a key image mismatch error"),
+        }
+        match sig.verify_with_key_images(&pub_keys, msg, &expected_key_images[..1]) {
+            Err(ClsagError::KeyImageMismatch) => {}
+            _ => panic!("expected a key image mismatch error"),
+        }
+
+        // Matching key images do not make an invalid signature verify
+        assert!(sig
+            .verify_with_key_images(&pub_keys, b"another message", &expected_key_images)
+            .is_err());
+    }
+
+    #[test]
+    fn test_verify_in_place() {
+        let num_keys = 2;
+        let msg = b"hello world";
+
+        let mut clsag = generate_clsag_with(5, num_keys);
+        clsag.add_member(generate_signer(num_keys)).unwrap();
+        let (sig, mut pub_keys) = clsag.sign(msg).unwrap();
+
+        let mut pubkey_matrix = [0u8; 512];
+        let mut agg_coeffs = [Scalar::zero(); 4];
+        let mut key_images = [RistrettoPoint::identity(); 4];
+        let mut scratch = VerifyScratch {
+            pubkey_matrix: &mut pubkey_matrix,
+            agg_coeffs: &mut agg_coeffs,
+            key_images: &mut key_images,
+        };
+
+        let before = allocations();
+        assert!(sig.verify_in_place(&pub_keys, msg, &mut scratch).is_ok());
+        match sig.verify_in_place(&pub_keys, b"another message", &mut scratch) {
+            Err(ClsagError::ChallengeMismatch) => {}
+            _ => panic!("expected a challenge mismatch error"),
+        }
+        assert_eq!(before, allocations());
+
+        pub_keys.shuffle(&mut thread_rng());
+        assert!(sig.verify_in_place(&pub_keys, msg, &mut scratch).is_err());
+    }
+
+    #[test]
+    fn test_verify_in_place_buffer_too_small() {
+        let num_keys = 2;
+        let msg = b"hello world";
+
+        let mut clsag = generate_clsag_with(5, num_keys);
+        clsag.add_member(generate_signer(num_keys)).unwrap();
+        let (sig, pub_keys) = clsag.sign(msg).unwrap();
+
+        let matrix_len = VerifyScratch::pubkey_matrix_len(pub_keys.len(), num_keys);
+        assert_eq!(6 * 2 * 32, matrix_len);
+        let mut pubkey_matrix = vec![0u8; matrix_len - 1];
+        let mut agg_coeffs = [Scalar::zero(); 2];
+        let mut key_images = [RistrettoPoint::identity(); 2];
+        let mut scratch = VerifyScratch {
+            pubkey_matrix: &mut pubkey_matrix,
+            agg_coeffs: &mut agg_coeffs,
+            key_images: &mut key_images,
+        };
+        match sig.verify_in_place(&pub_keys, msg, &mut scratch) {
+            Err(ClsagError::BufferTooSmall) => {}
+            _ => panic!("expected a buffer too small error"),
+        }
+
+        let mut pubkey_matrix = vec![0u8; matrix_len];
+        let mut agg_coeffs = [Scalar::zero(); 1];
+        let mut scratch = VerifyScratch {
+            pubkey_matrix: &mut pubkey_matrix,
+            agg_coeffs: &mut agg_coeffs,
+            key_images: &mut key_images,
+        };
+        match sig.verify_in_place(&pub_keys, msg, &mut scratch) {
+            Err(ClsagError::BufferTooSmall) => {}
+            _ => panic!("expected a buffer too small error"),
+        }
     }
 
     #[test]
@@ -209,13 +972,12 @@ mod test {
         let msg = b"hello world";
 
         let mut clsag = generate_clsag_with(num_decoys, num_keys);
-        clsag.add_member(generate_signer(num_keys));
-        let sig = clsag.sign(msg).unwrap();
-        let mut pub_keys = clsag.public_keys();
+        clsag.add_member(generate_signer(num_keys)).unwrap();
+        let (sig, mut pub_keys) = clsag.sign(msg).unwrap();
 
         // shuffle public key ordering
         pub_keys.shuffle(&mut thread_rng());
-        assert!(sig.optimised_verify(&mut pub_keys, msg).is_err());
+        assert!(sig.optimised_verify(&pub_keys, msg).is_err());
     }
     #[test]
     fn test_verify_fail_incorrect_num_keys() {
@@ -224,22 +986,312 @@ mod test {
         let msg = b"hello world";
 
         let mut clsag = generate_clsag_with(num_decoys, num_keys);
-        clsag.add_member(generate_signer(num_keys));
-        let sig = clsag.sign(msg).unwrap();
-        let mut pub_keys = clsag.public_keys();
+        clsag.add_member(generate_signer(num_keys)).unwrap();
+        let (sig, mut pub_keys) = clsag.sign(msg).unwrap();
 
         // Add extra key
-        let extra_key = generate_rand_compressed_points(num_keys);
+        let extra_key = generate_rand_public_keys(num_keys);
         pub_keys.push(extra_key);
-        assert!(sig.optimised_verify(&mut pub_keys, msg).is_err());
+        assert!(sig.optimised_verify(&pub_keys, msg).is_err());
 
         // remove the extra key and test should pass
         pub_keys.remove(pub_keys.len() - 1);
-        assert!(sig.optimised_verify(&mut pub_keys, msg).is_ok());
+        assert!(sig.optimised_verify(&pub_keys, msg).is_ok());
 
         // remove another key and tests should fail
         pub_keys.remove(pub_keys.len() - 1);
-        assert!(sig.optimised_verify(&mut pub_keys, msg).is_err());
+        assert!(sig.optimised_verify(&pub_keys, msg).is_err());
+    }
+
+    #[test]
+    fn test_verify_ragged_ring() {
+        let msg = b"hello world";
+        let mut clsag = generate_clsag_with(2, 2);
+        clsag.add_member(generate_signer(2)).unwrap();
+        let (sig, pub_keys) = clsag.sign(msg).unwrap();
+
+        // A member with fewer keys than there are key images, or with no keys at all,
+        // is rejected by every verifier rather than making them panic
+        let mut ragged = pub_keys.clone();
+        ragged[1].truncate(1);
+        let mut empty_member = pub_keys.clone();
+        empty_member[0].clear();
+        for ring in &[ragged, empty_member] {
+            assert_eq!(Err(ClsagError::KeyCountMismatch), sig.verify(ring, msg));
+            assert_eq!(
+                Err(ClsagError::KeyCountMismatch),
+                sig.optimised_verify(ring, msg)
+            );
+        }
+        assert!(sig.verify(&[], msg).is_err());
+        assert!(sig.verify(&pub_keys, msg).is_ok());
+    }
+
+    #[test]
+    fn test_bytes_round_trip() {
+        let num_keys = 2;
+        let num_decoys = 5;
+        let msg = b"hello world";
+
+        let mut clsag = generate_clsag_with(num_decoys, num_keys);
+        clsag.add_member(generate_signer(num_keys)).unwrap();
+        let (sig, pub_keys) = clsag.sign(msg).unwrap();
+
+        let bytes = sig.to_bytes();
+        assert_eq!(bytes.len(), 8 + 32 * (1 + (num_decoys + 1) + num_keys));
+
+        let decoded = Signature::from_bytes(&bytes).unwrap();
+        assert_eq!(sig.challenge, decoded.challenge);
+        assert_eq!(sig.responses, decoded.responses);
+        assert_eq!(sig.key_images, decoded.key_images);
+        assert!(decoded.verify(&pub_keys, msg).is_ok());
+    }
+
+    #[test]
+    fn test_from_bytes_rejects_malformed() {
+        let num_keys = 2;
+        let num_decoys = 3;
+
+        let mut clsag = generate_clsag_with(num_decoys, num_keys);
+        clsag.add_member(generate_signer(num_keys)).unwrap();
+        let bytes = clsag.sign(b"hello world").unwrap().0.to_bytes();
+
+        // Truncated header
+        match Signature::from_bytes(&bytes[..4]) {
+            Err(ClsagError::DecodeError) => {}
+            _ => panic!("expected a malformed encoding error for a truncated header"),
+        }
+
+        // Missing and trailing bytes
+        match Signature::from_bytes(&bytes[..bytes.len() - 1]) {
+            Err(ClsagError::DecodeError) => {}
+            _ => panic!("expected a malformed encoding error for a truncated signature"),
+        }
+        let mut extended = bytes.clone();
+        extended.push(0);
+        match Signature::from_bytes(&extended) {
+            Err(ClsagError::DecodeError) => {}
+            _ => panic!("expected a malformed encoding error for trailing bytes"),
+        }
+
+        // Header claiming a huge number of responses
+        let mut huge = bytes.clone();
+        huge[0..4].copy_from_slice(&u32::MAX.to_le_bytes());
+        match Signature::from_bytes(&huge) {
+            Err(ClsagError::DecodeError) => {}
+            _ => panic!("expected a malformed encoding error for a bad header"),
+        }
+
+        // A well formed signature over a ring larger than the limits allow
+        let limits = VerificationLimits {
+            max_ring_size: num_decoys,
+            max_keys: num_keys,
+        };
+        match Signature::from_bytes_with_limits(&bytes, &limits) {
+            Err(ClsagError::RingTooLarge) => {}
+            _ => panic!("expected a ring too large error"),
+        }
+
+        // Empty key images
+        let mut empty = bytes.clone();
+        empty[4..8].copy_from_slice(&0u32.to_le_bytes());
+        match Signature::from_bytes(&empty) {
+            Err(ClsagError::DecodeError) => {}
+            _ => panic!("expected a malformed encoding error for no key images"),
+        }
+    }
+
+    #[test]
+    fn test_from_bytes_rejects_non_canonical() {
+        let num_keys = 1;
+        let num_decoys = 2;
+
+        let mut clsag = generate_clsag_with(num_decoys, num_keys);
+        clsag.add_member(generate_signer(num_keys)).unwrap();
+        let bytes = clsag.sign(b"hello world").unwrap().0.to_bytes();
+
+        // A challenge with the top bits set is not reduced
+        let mut non_canonical = bytes.clone();
+        non_canonical[8 + 31] = 0xff;
+        match Signature::from_bytes(&non_canonical) {
+            Err(ClsagError::NonCanonicalScalar) => {}
+            _ => panic!("expected a non-canonical scalar error"),
+        }
+
+        // All 0xff bytes is not a valid ristretto encoding
+        let mut bad_key_image = bytes.clone();
+        let len = bad_key_image.len();
+        for byte in bad_key_image[len - 32..].iter_mut() {
+            *byte = 0xff;
+        }
+        match Signature::from_bytes(&bad_key_image) {
+            Err(ClsagError::InvalidKeyImage) => {}
+            _ => panic!("expected a bad key images error"),
+        }
+
+        // All zero bytes is the encoding of the identity
+        let mut identity_key_image = bytes.clone();
+        for byte in identity_key_image[len - 32..].iter_mut() {
+            *byte = 0;
+        }
+        match Signature::from_bytes(&identity_key_image) {
+            Err(ClsagError::IdentityKeyImage) => {}
+            _ => panic!("expected an identity key image error"),
+        }
+    }
+
+    #[test]
+    fn test_verify_rejects_non_canonical() {
+        let num_keys = 2;
+        let msg = b"hello world";
+
+        let mut clsag = generate_clsag_with(3, num_keys);
+        clsag.add_member(generate_signer(num_keys)).unwrap();
+        let (mut sig, pub_keys) = clsag.sign(msg).unwrap();
+
+        // A response which is not reduced, although it is equal to the response modulo
+        // the group order, so that the signature would otherwise verify
+        let response = sig.responses[0];
+        sig.responses[0] = Scalar::from_bits(add_group_order(response.to_bytes()));
+        for result in [
+            sig.verify(&pub_keys, msg),
+            sig.optimised_verify(&pub_keys, msg),
+        ] {
+            match result {
+                Err(ClsagError::NonCanonicalScalar) => {}
+                _ => panic!("expected a non-canonical scalar error"),
+            }
+        }
+        sig.responses[0] = response;
+
+        sig.key_images[1] = KeyImage(RistrettoPoint::identity().compress());
+        for result in [
+            sig.verify(&pub_keys, msg),
+            sig.optimised_verify(&pub_keys, msg),
+        ] {
+            match result {
+                Err(ClsagError::IdentityKeyImage) => {}
+                _ => panic!("expected an identity key image error"),
+            }
+        }
+        // Legacy verification skips the key image check, so the signature
+        // is only rejected because the key image is not the signers
+        match sig.verify_legacy(&pub_keys, msg) {
+            Err(ClsagError::ChallengeMismatch) => {}
+            _ => panic!("expected a challenge mismatch error"),
+        }
+    }
+
+    #[test]
+    fn test_verify_legacy() {
+        let msg = b"hello world";
+
+        // A zero secret key gives an identity key image, which is rejected
+        // unless the key images are not checked
+        let signer = crate::member::Member::new_signer(vec![
+            Scalar::random(&mut thread_rng()),
+            Scalar::zero(),
+        ]);
+        let mut clsag = generate_clsag_with(3, 2);
+        clsag.add_member(signer).unwrap();
+        let (sig, pub_keys) = clsag.sign(msg).unwrap();
+        assert!(!sig.key_images()[1].is_valid());
+
+        match sig.optimised_verify(&pub_keys, msg) {
+            Err(ClsagError::IdentityKeyImage) => {}
+            _ => panic!("expected an identity key image error"),
+        }
+        assert!(sig.verify_legacy(&pub_keys, msg).is_ok());
+        assert!(sig.verify_legacy(&pub_keys, b"another message").is_err());
+    }
+
+    // Returns the little endian bytes of the scalar plus the group order, without reducing
+    fn add_group_order(bytes: [u8; 32]) -> [u8; 32] {
+        let order = curve25519_dalek::constants::BASEPOINT_ORDER.to_bytes();
+        let mut sum = [0u8; 32];
+        let mut carry = 0u16;
+        for i in 0..32 {
+            let digit = bytes[i] as u16 + order[i] as u16 + carry;
+            sum[i] = digit as u8;
+            carry = digit >> 8;
+        }
+        sum
+    }
+
+    #[test]
+    fn test_pruned_round_trip() {
+        let num_keys = 2;
+        let num_decoys = 5;
+        let msg = b"hello world";
+
+        let mut clsag = generate_clsag_with(num_decoys, num_keys);
+        clsag.add_member(generate_signer(num_keys)).unwrap();
+        let (sig, pub_keys) = clsag.sign(msg).unwrap();
+        let ring_size = pub_keys.len();
+
+        assert_eq!(
+            Signature::serialized_size(ring_size, num_keys),
+            sig.to_bytes().len()
+        );
+        let pruned = PrunedSignature::from_bytes(sig.prune().as_bytes());
+        assert_eq!(
+            PrunedSignature::pruned_size(ring_size, num_keys),
+            pruned.as_bytes().len()
+        );
+        assert_eq!(&sig.to_bytes()[8..], pruned.as_bytes());
+
+        let expanded = pruned.expand(ring_size, num_keys).unwrap();
+        assert_eq!(sig.to_bytes(), expanded.to_bytes());
+        assert!(expanded.optimised_verify(&pub_keys, msg).is_ok());
+
+        // The sizes must match the bytes
+        for (ring_size, num_keys) in [(ring_size + 1, num_keys), (ring_size, 0), (0, 8)] {
+            match pruned.expand(ring_size, num_keys) {
+                Err(ClsagError::DecodeError) => {}
+                _ => panic!("expected a decode error"),
+            }
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let num_keys = 2;
+        let num_decoys = 5;
+        let msg = b"hello world";
+
+        let mut clsag = generate_clsag_with(num_decoys, num_keys);
+        clsag.add_member(generate_signer(num_keys)).unwrap();
+        let (sig, pub_keys) = clsag.sign(msg).unwrap();
+
+        let json = serde_json::to_string(&sig).unwrap();
+        let decoded: Signature = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(sig.challenge, decoded.challenge);
+        assert_eq!(sig.responses, decoded.responses);
+        assert_eq!(sig.key_images, decoded.key_images);
+        assert!(decoded.verify(&pub_keys, msg).is_ok());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_bincode_round_trip() {
+        let num_keys = 2;
+        let msg = b"hello world";
+
+        let mut clsag = generate_clsag_with(5, num_keys);
+        clsag.add_member(generate_signer(num_keys)).unwrap();
+        let (sig, pub_keys) = clsag.sign(msg).unwrap();
+
+        // Each scalar and key image is encoded as a fixed 32 byte array, after the lengths
+        // of the responses and key images, which bincode encodes as u64s
+        let bytes = bincode::serialize(&sig).unwrap();
+        let expected_len = Signature::serialized_size(pub_keys.len(), num_keys) + 8;
+        assert_eq!(expected_len, bytes.len());
+
+        let decoded: Signature = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(sig.to_bytes(), decoded.to_bytes());
+        assert!(decoded.verify(&pub_keys, msg).is_ok());
     }
 
     macro_rules! param_bench_verify {
@@ -251,11 +1303,10 @@ mod test {
                 let msg = b"hello world";
 
                 let mut clsag = generate_clsag_with(num_decoys, num_keys);
-                clsag.add_member(generate_signer(num_keys));
-                let sig = clsag.sign(msg).unwrap();
-                let mut pub_keys = clsag.public_keys();
+                clsag.add_member(generate_signer(num_keys)).unwrap();
+                let (sig, pub_keys) = clsag.sign(msg).unwrap();
 
-                b.iter(|| sig.optimised_verify(&mut pub_keys, msg));
+                b.iter(|| sig.optimised_verify(&pub_keys, msg));
             }
         };
     }
@@ -0,0 +1,499 @@
+// Saving a partially built ring and resuming it later.
+//
+// Interactive flows build a ring over time, such as a wallet collecting decoys from a node
+// one request at a time, or pausing before the signer confirms on a hardware wallet. A
+// `ClsagSnapshot` holds everything needed to rebuild such a ring after the process restarts:
+// the keys, commitments and metadata of each decoy, the ring policy, the generator, the pseudo
+// output, whether the members are shuffled and the position of the signer in the ring.
+//
+// A snapshot never holds secrets. Members holding private keys are left out of the snapshot
+// and only their position is kept, so the snapshot can be written to disk or sent to another
+// process as it is. The signer is added again when resuming with `resume_with_signer`,
+// from wherever their keys are kept, such as a device or an encrypted wallet file.
+//
+// The construction set with `set_hash_to_point` and the metrics set with `set_metrics` are
+// not part of the snapshot and must be set again on the resumed ring.
+use crate::clsag::Clsag;
+use crate::error::ClsagError;
+use crate::member::{Member, MemberMetadata};
+use crate::policy::RingPolicy;
+use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
+
+// The version of the encoding written by `ClsagSnapshot::to_bytes`
+const VERSION: u8 = 1;
+
+const HAS_PSEUDO_OUT: u8 = 1;
+const SHUFFLE_MEMBERS: u8 = 1 << 1;
+const HAS_SIGNER: u8 = 1 << 2;
+const HAS_MAX_RING_SIZE: u8 = 1 << 3;
+const HAS_NUM_KEYS: u8 = 1 << 4;
+
+const HAS_COMMITMENT: u8 = 1;
+const HAS_GLOBAL_INDEX: u8 = 1 << 1;
+const HAS_BLOCK_HEIGHT: u8 = 1 << 2;
+
+// The state of a ring without its secrets, see the module documentation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClsagSnapshot {
+    generator: RistrettoPoint,
+    pseudo_out: Option<RistrettoPoint>,
+    shuffle_members: bool,
+    policy: RingPolicy,
+    decoys: Vec<DecoySnapshot>,
+    signer_index: Option<usize>,
+}
+
+// A decoy of the ring, with their keys, their commitment and where their output is on chain
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DecoySnapshot {
+    keys: Vec<RistrettoPoint>,
+    commitment: Option<RistrettoPoint>,
+    metadata: MemberMetadata,
+}
+
+impl ClsagSnapshot {
+    // Returns the number of decoys in the snapshot
+    pub fn num_decoys(&self) -> usize {
+        self.decoys.len()
+    }
+    // Returns the position the signer had in the ring when the snapshot was taken,
+    // or None if the ring had no signer yet
+    pub fn signer_index(&self) -> Option<usize> {
+        self.signer_index
+    }
+    // Rebuilds the ring from its decoys, without the signer.
+    // Returns an error if a decoy is no longer allowed in the ring,
+    // which cannot happen for a snapshot of a valid ring
+    pub fn resume(&self) -> Result<Clsag, ClsagError> {
+        let mut clsag = self.empty_ring();
+        for decoy in &self.decoys {
+            clsag.add_member(decoy.to_member())?;
+        }
+        Ok(clsag)
+    }
+    // Rebuilds the ring with the signer at the position they had when the snapshot was taken,
+    // or after every decoy if the ring had no signer yet.
+    // Returns an error if the member does not hold private keys,
+    // or if the ring does not allow the signer to be added
+    pub fn resume_with_signer(&self, signer: Member) -> Result<Clsag, ClsagError> {
+        if !signer.is_signer() {
+            return Err(ClsagError::NotASigner);
+        }
+        let signer_index = self.signer_index.unwrap_or(self.decoys.len());
+
+        let mut clsag = self.empty_ring();
+        let mut signer = Some(signer);
+        for (index, decoy) in self.decoys.iter().enumerate() {
+            if index == signer_index {
+                clsag.add_member(signer.take().expect("the signer is only added once"))?;
+            }
+            clsag.add_member(decoy.to_member())?;
+        }
+        if let Some(signer) = signer {
+            clsag.add_member(signer)?;
+        }
+        Ok(clsag)
+    }
+
+    fn empty_ring(&self) -> Clsag {
+        let mut clsag = Clsag::new();
+        clsag.generator = self.generator;
+        clsag.pseudo_out = self.pseudo_out;
+        clsag.shuffle_members = self.shuffle_members;
+        clsag.set_ring_policy(self.policy);
+        clsag
+    }
+
+    // Encodes the snapshot with the following layout, all integers little-endian:
+    //
+    // | version (u8) | flags (u8) | generator (32) | pseudo_out (32)? | min_ring_size (u32) |
+    // | max_ring_size (u32)? | num_keys (u32)? | signer_index (u32)? | num_decoys (u32) |
+    // | keys_per_decoy (u32) | decoys |
+    //
+    // where fields marked with ? are only present when their flag is set. Each decoy is
+    //
+    // | flags (u8) | keys (32 * keys_per_decoy) | commitment (32)? | global_index (u64)? |
+    // | block_height (u64)? |
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut flags = 0;
+        if self.pseudo_out.is_some() {
+            flags |= HAS_PSEUDO_OUT;
+        }
+        if self.shuffle_members {
+            flags |= SHUFFLE_MEMBERS;
+        }
+        if self.signer_index.is_some() {
+            flags |= HAS_SIGNER;
+        }
+        if self.policy.max_ring_size.is_some() {
+            flags |= HAS_MAX_RING_SIZE;
+        }
+        if self.policy.num_keys.is_some() {
+            flags |= HAS_NUM_KEYS;
+        }
+
+        let mut bytes = vec![VERSION, flags];
+        bytes.extend_from_slice(self.generator.compress().as_bytes());
+        if let Some(pseudo_out) = self.pseudo_out {
+            bytes.extend_from_slice(pseudo_out.compress().as_bytes());
+        }
+        write_u32(&mut bytes, self.policy.min_ring_size);
+        if let Some(max_ring_size) = self.policy.max_ring_size {
+            write_u32(&mut bytes, max_ring_size);
+        }
+        if let Some(num_keys) = self.policy.num_keys {
+            write_u32(&mut bytes, num_keys);
+        }
+        if let Some(signer_index) = self.signer_index {
+            write_u32(&mut bytes, signer_index);
+        }
+        write_u32(&mut bytes, self.decoys.len());
+        write_u32(&mut bytes, self.keys_per_decoy());
+
+        for decoy in &self.decoys {
+            decoy.write(&mut bytes);
+        }
+        bytes
+    }
+    // Decodes a snapshot produced by `to_bytes`.
+    // Returns `InvalidSnapshot` if the encoding has an unknown version, unknown flags,
+    // trailing or missing bytes, points which are not valid ristretto points,
+    // or a signer position outside of the ring
+    pub fn from_bytes(bytes: &[u8]) -> Result<ClsagSnapshot, ClsagError> {
+        let mut reader = Reader(bytes);
+        if reader.read_u8()? != VERSION {
+            return Err(ClsagError::InvalidSnapshot);
+        }
+        let flags = reader.read_u8()?;
+        let known_flags =
+            HAS_PSEUDO_OUT | SHUFFLE_MEMBERS | HAS_SIGNER | HAS_MAX_RING_SIZE | HAS_NUM_KEYS;
+        if flags & !known_flags != 0 {
+            return Err(ClsagError::InvalidSnapshot);
+        }
+
+        let generator = reader.read_point()?;
+        let pseudo_out = reader.read_if(flags & HAS_PSEUDO_OUT, Reader::read_point)?;
+        let policy = RingPolicy {
+            min_ring_size: reader.read_u32()?,
+            max_ring_size: reader.read_if(flags & HAS_MAX_RING_SIZE, Reader::read_u32)?,
+            num_keys: reader.read_if(flags & HAS_NUM_KEYS, Reader::read_u32)?,
+        };
+        let signer_index = reader.read_if(flags & HAS_SIGNER, Reader::read_u32)?;
+        let num_decoys = reader.read_u32()?;
+        let keys_per_decoy = reader.read_u32()?;
+
+        // Check that the decoys fit in the remaining bytes before allocating anything,
+        // so that a bogus header cannot be used to make us allocate large amounts of memory
+        let min_decoy_size = keys_per_decoy
+            .checked_mul(32)
+            .and_then(|n| n.checked_add(1))
+            .ok_or(ClsagError::InvalidSnapshot)?;
+        let min_decoys_size = num_decoys
+            .checked_mul(min_decoy_size)
+            .ok_or(ClsagError::InvalidSnapshot)?;
+        if reader.0.len() < min_decoys_size {
+            return Err(ClsagError::InvalidSnapshot);
+        }
+        if signer_index.is_some_and(|index| index > num_decoys) {
+            return Err(ClsagError::InvalidSnapshot);
+        }
+
+        let decoys = (0..num_decoys)
+            .map(|_| DecoySnapshot::read(&mut reader, keys_per_decoy))
+            .collect::<Result<Vec<DecoySnapshot>, ClsagError>>()?;
+        if !reader.0.is_empty() {
+            return Err(ClsagError::InvalidSnapshot);
+        }
+
+        Ok(ClsagSnapshot {
+            generator,
+            pseudo_out,
+            shuffle_members: flags & SHUFFLE_MEMBERS != 0,
+            policy,
+            decoys,
+            signer_index,
+        })
+    }
+
+    fn keys_per_decoy(&self) -> usize {
+        self.decoys.first().map_or(0, |decoy| decoy.keys.len())
+    }
+}
+
+impl DecoySnapshot {
+    fn to_member(&self) -> Member {
+        let member = match self.commitment {
+            Some(commitment) => Member::new_decoy_with_commitment(self.keys.clone(), commitment),
+            None => Member::new_decoy(self.keys.clone()),
+        };
+        member.with_metadata(self.metadata)
+    }
+
+    fn write(&self, bytes: &mut Vec<u8>) {
+        let mut flags = 0;
+        if self.commitment.is_some() {
+            flags |= HAS_COMMITMENT;
+        }
+        if self.metadata.global_index.is_some() {
+            flags |= HAS_GLOBAL_INDEX;
+        }
+        if self.metadata.block_height.is_some() {
+            flags |= HAS_BLOCK_HEIGHT;
+        }
+
+        bytes.push(flags);
+        for key in &self.keys {
+            bytes.extend_from_slice(key.compress().as_bytes());
+        }
+        if let Some(commitment) = self.commitment {
+            bytes.extend_from_slice(commitment.compress().as_bytes());
+        }
+        if let Some(global_index) = self.metadata.global_index {
+            bytes.extend_from_slice(&global_index.to_le_bytes());
+        }
+        if let Some(block_height) = self.metadata.block_height {
+            bytes.extend_from_slice(&block_height.to_le_bytes());
+        }
+    }
+
+    fn read(reader: &mut Reader, num_keys: usize) -> Result<DecoySnapshot, ClsagError> {
+        let flags = reader.read_u8()?;
+        if flags & !(HAS_COMMITMENT | HAS_GLOBAL_INDEX | HAS_BLOCK_HEIGHT) != 0 {
+            return Err(ClsagError::InvalidSnapshot);
+        }
+        let keys = (0..num_keys)
+            .map(|_| reader.read_point())
+            .collect::<Result<Vec<RistrettoPoint>, ClsagError>>()?;
+        let commitment = reader.read_if(flags & HAS_COMMITMENT, Reader::read_point)?;
+        let metadata = MemberMetadata {
+            global_index: reader.read_if(flags & HAS_GLOBAL_INDEX, Reader::read_u64)?,
+            block_height: reader.read_if(flags & HAS_BLOCK_HEIGHT, Reader::read_u64)?,
+        };
+
+        Ok(DecoySnapshot {
+            keys,
+            commitment,
+            metadata,
+        })
+    }
+}
+
+impl Clsag {
+    // Takes a snapshot of the ring, which the ring can be rebuilt from with
+    // `ClsagSnapshot::resume`. Members holding private keys are left out of the snapshot,
+    // and the position of the first of them is kept as the position of the signer
+    pub fn snapshot(&self) -> ClsagSnapshot {
+        let decoys = self
+            .members()
+            .iter()
+            .filter(|member| !member.is_signer())
+            .map(|member| DecoySnapshot {
+                keys: member.public_set.points().to_vec(),
+                commitment: member.commitment(),
+                metadata: *member.metadata(),
+            })
+            .collect();
+
+        ClsagSnapshot {
+            generator: self.generator,
+            pseudo_out: self.pseudo_out,
+            shuffle_members: self.shuffle_members,
+            policy: *self.ring_policy(),
+            decoys,
+            signer_index: self.find_signer().ok(),
+        }
+    }
+}
+
+fn write_u32(bytes: &mut Vec<u8>, value: usize) {
+    bytes.extend_from_slice(&(value as u32).to_le_bytes());
+}
+
+// Reads the fields of a snapshot from the front of the bytes
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ClsagError> {
+        if self.0.len() < len {
+            return Err(ClsagError::InvalidSnapshot);
+        }
+        let (head, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(head)
+    }
+    fn read_u8(&mut self) -> Result<u8, ClsagError> {
+        Ok(self.take(1)?[0])
+    }
+    fn read_u32(&mut self) -> Result<usize, ClsagError> {
+        let mut buf = [0u8; 4];
+        buf.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(buf) as usize)
+    }
+    fn read_u64(&mut self) -> Result<u64, ClsagError> {
+        let mut buf = [0u8; 8];
+        buf.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(buf))
+    }
+    fn read_point(&mut self) -> Result<RistrettoPoint, ClsagError> {
+        CompressedRistretto::from_slice(self.take(32)?)
+            .decompress()
+            .ok_or(ClsagError::InvalidSnapshot)
+    }
+    // Reads the field if its flag is set
+    fn read_if<T, F>(&mut self, flag: u8, read: F) -> Result<Option<T>, ClsagError>
+    where
+        F: FnOnce(&mut Self) -> Result<T, ClsagError>,
+    {
+        if flag == 0 {
+            return Ok(None);
+        }
+        read(self).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::builder::ClsagBuilder;
+    use crate::test_utils::*;
+    use curve25519_dalek::scalar::Scalar;
+
+    #[test]
+    fn test_resume_with_signer() {
+        let num_keys = 2;
+        let msg = b"hello world";
+
+        let mut clsag = generate_clsag_with(4, num_keys);
+        clsag.set_ring_policy(RingPolicy {
+            max_ring_size: Some(8),
+            ..RingPolicy::permissive()
+        });
+        clsag.set_shuffle_members(false);
+        let signer_keys = generate_rand_scalars(num_keys);
+        clsag
+            .replace_member(2, Member::new_signer(signer_keys.clone()))
+            .unwrap();
+
+        let snapshot = ClsagSnapshot::from_bytes(&clsag.snapshot().to_bytes()).unwrap();
+        assert_eq!(clsag.snapshot(), snapshot);
+        assert_eq!(3, snapshot.num_decoys());
+        assert_eq!(Some(2), snapshot.signer_index());
+
+        // The resumed ring is the same ring, with the signer at the same position
+        let resumed = snapshot
+            .resume_with_signer(Member::new_signer(signer_keys))
+            .unwrap();
+        assert_eq!(clsag.public_keys(), resumed.public_keys());
+        assert_eq!(clsag.ring_policy(), resumed.ring_policy());
+
+        let (signature, public_keys) = resumed.sign(msg).unwrap();
+        assert_eq!(clsag.public_keys(), &public_keys[..]);
+        assert!(signature.verify(&public_keys, msg).is_ok());
+
+        // No secrets are kept, so the resumed ring cannot be signed without the signer
+        let resumed = snapshot.resume().unwrap();
+        assert_eq!(3, resumed.members().len());
+        match resumed.sign(msg) {
+            Err(ClsagError::NoSigner) => {}
+            _ => panic!("expected the ring to have no signer"),
+        }
+        match snapshot.resume_with_signer(generate_decoy(num_keys)) {
+            Err(ClsagError::NotASigner) => {}
+            _ => panic!("expected a decoy to be rejected as the signer"),
+        }
+    }
+
+    #[test]
+    fn test_resume_commitments() {
+        let pseudo_out = generate_rand_points(1)[0];
+        let mut clsag = Clsag::with_pseudo_out(pseudo_out);
+        clsag.set_ring_policy(RingPolicy::permissive());
+        let metadata = MemberMetadata {
+            global_index: Some(42),
+            block_height: None,
+        };
+        let decoy = Member::new_decoy_with_commitment(generate_rand_points(1), pseudo_out);
+        clsag.add_member(decoy.with_metadata(metadata)).unwrap();
+
+        let blinding = Scalar::from(7u64);
+        let commitment = pseudo_out + blinding * crate::constants::BASEPOINT;
+        let signer_keys = generate_rand_scalars(1);
+        let signer =
+            || Member::new_signer_with_commitment(signer_keys.clone(), commitment, blinding);
+
+        let snapshot = ClsagSnapshot::from_bytes(&clsag.snapshot().to_bytes()).unwrap();
+        let resumed = snapshot.resume_with_signer(signer()).unwrap();
+        assert_eq!(&metadata, resumed.members()[0].metadata());
+        assert_eq!(Some(pseudo_out), resumed.members()[0].commitment());
+
+        clsag.add_member(signer()).unwrap();
+        assert_eq!(clsag.public_keys(), resumed.public_keys());
+        assert!(resumed.sign(b"hello world").is_ok());
+    }
+
+    #[test]
+    fn test_resume_builder() {
+        let builder = ClsagBuilder::new()
+            .ring_policy(RingPolicy::permissive())
+            .add_decoy(generate_decoy(1))
+            .unwrap();
+        let snapshot = builder.snapshot();
+        assert_eq!(None, snapshot.signer_index());
+
+        let ring = ClsagBuilder::resume(&snapshot)
+            .unwrap()
+            .add_decoy(generate_decoy(1))
+            .unwrap()
+            .signer(generate_signer(1))
+            .unwrap()
+            .finalize()
+            .unwrap();
+        assert_eq!(3, ring.public_keys().len());
+
+        let mut clsag = snapshot.resume().unwrap();
+        clsag.add_member(generate_signer(1)).unwrap();
+        match ClsagBuilder::resume(&clsag.snapshot()) {
+            Err(ClsagError::NotADecoy) => {}
+            _ => panic!("expected a snapshot with a signer to be rejected"),
+        }
+    }
+
+    #[test]
+    fn test_invalid_snapshot() {
+        let mut clsag = generate_clsag_with(2, 1);
+        clsag.add_member(generate_signer(1)).unwrap();
+        let bytes = clsag.snapshot().to_bytes();
+
+        let mut unknown_version = bytes.clone();
+        unknown_version[0] = VERSION + 1;
+        let mut unknown_flag = bytes.clone();
+        unknown_flag[1] |= 1 << 7;
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        let mut invalid_point = bytes.clone();
+        invalid_point[2..34].copy_from_slice(&[0xff; 32]);
+
+        for bytes in &[
+            unknown_version,
+            unknown_flag,
+            trailing,
+            invalid_point,
+            bytes[..bytes.len() - 1].to_vec(),
+            Vec::new(),
+        ] {
+            match ClsagSnapshot::from_bytes(bytes) {
+                Err(ClsagError::InvalidSnapshot) => {}
+                _ => panic!("expected the snapshot to be rejected"),
+            }
+        }
+
+        // A header claiming many decoys is rejected before anything is allocated
+        let mut bogus = bytes[..bytes.len() - 2 * (1 + 32)].to_vec();
+        let num_decoys = bogus.len() - 8;
+        bogus[num_decoys..num_decoys + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        match ClsagSnapshot::from_bytes(&bogus) {
+            Err(ClsagError::InvalidSnapshot) => {}
+            _ => panic!("expected the snapshot to be rejected"),
+        }
+    }
+}
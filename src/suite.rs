@@ -0,0 +1,395 @@
+// Cipher suites, which fix every hash a signature is made with, so that two implementations
+// agreeing on the suite agree on every key image and every challenge.
+//
+// A `CipherSuite` names the construction the first key of each member is hashed to a point
+// with and the transcript every challenge is derived from. A signature is made for a suite
+// with `Clsag::sign_with_suite` and only verifies with `Signature::verify_with_suite` under
+// the same suite. The suites are picked per call, in the same way as a transcript or a
+// hash-to-point construction is, rather than being a type parameter of `Clsag`, as the ring
+// arithmetic is over ristretto points throughout:
+//
+// - `Ristretto255Sha512` hashes to points with domain separated SHA-512 and derives challenges
+//   from a merlin transcript. This is what `Clsag::sign` and `Signature::verify` use.
+// - `Ristretto255Sha512Wide` derives challenges by hashing with SHA-512 and reducing the
+//   64 byte output modulo the group order, for systems without merlin.
+// - `Ristretto255Keccak256` hashes with the original Keccak-256, as used by Monero and
+//   Ethereum, and reduces the 32 byte output modulo the group order. As the output is only
+//   about 4 bits longer than the group order the challenges are slightly biased, which is
+//   harmless but means the suite should only be picked to match an existing system.
+//
+// Points and scalars are encoded in the same way under every suite, as compressed ristretto
+// points and canonical scalars. The challenge hashes are `ChallengeHash`es, which
+// `HashTranscript` frames messages for.
+use crate::clsag::{Clsag, PublicKeys};
+use crate::error::ClsagError;
+use crate::hash::{DomainSeparated, HashToPoint, HASH_TO_POINT_DOMAIN};
+use crate::keys::{KeyImage, PublicKey};
+use crate::ring::PreparedRing;
+use crate::signature::Signature;
+use crate::transcript::{default_transcript, SigningTranscript};
+use curve25519_dalek::ristretto::RistrettoPoint;
+use curve25519_dalek::scalar::Scalar;
+use merlin::Transcript;
+use sha2::{Digest, Sha512};
+
+// A hash function along with the reduction of its output to a scalar
+pub trait ChallengeHash: Clone + Default {
+    // The label every transcript of the hash starts with
+    const LABEL: &'static [u8];
+
+    // Absorbs the bytes into the hash
+    fn update(&mut self, bytes: &[u8]);
+
+    // Returns the hash of everything absorbed so far, reduced to a scalar
+    fn challenge(&self) -> Scalar;
+}
+
+// SHA-512, with the 64 byte output reduced modulo the group order
+#[derive(Clone, Default)]
+pub struct Sha512Wide(Sha512);
+
+impl ChallengeHash for Sha512Wide {
+    const LABEL: &'static [u8] = b"clsag_sha512_wide";
+
+    fn update(&mut self, bytes: &[u8]) {
+        self.0.input(bytes);
+    }
+
+    fn challenge(&self) -> Scalar {
+        Scalar::from_hash(self.0.clone())
+    }
+}
+
+// The number of bytes absorbed into the keccak state per permutation, for a 256 bit output
+const KECCAK_RATE: usize = 136;
+
+// Keccak-256, with the 32 byte output reduced modulo the group order.
+// This is the original Keccak padding, not the one of SHA3-256
+#[derive(Clone, Default)]
+pub struct Keccak256Narrow {
+    state: [u64; 25],
+
+    // The number of bytes absorbed into the current block
+    offset: usize,
+}
+
+impl Keccak256Narrow {
+    fn xor_byte(state: &mut [u64; 25], position: usize, byte: u8) {
+        state[position / 8] ^= u64::from(byte) << (8 * (position % 8));
+    }
+
+    // Returns the Keccak-256 hash of everything absorbed so far
+    fn finalize(&self) -> [u8; 32] {
+        let mut state = self.state;
+        Self::xor_byte(&mut state, self.offset, 0x01);
+        Self::xor_byte(&mut state, KECCAK_RATE - 1, 0x80);
+        keccak::f1600(&mut state);
+
+        let mut output = [0u8; 32];
+        for (chunk, lane) in output.chunks_mut(8).zip(state.iter()) {
+            chunk.copy_from_slice(&lane.to_le_bytes());
+        }
+        output
+    }
+}
+
+impl ChallengeHash for Keccak256Narrow {
+    const LABEL: &'static [u8] = b"clsag_keccak256_narrow";
+
+    fn update(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            Self::xor_byte(&mut self.state, self.offset, byte);
+            self.offset += 1;
+            if self.offset == KECCAK_RATE {
+                keccak::f1600(&mut self.state);
+                self.offset = 0;
+            }
+        }
+    }
+
+    fn challenge(&self) -> Scalar {
+        Scalar::from_bytes_mod_order(self.finalize())
+    }
+}
+
+// A transcript which derives every challenge with the hash `H`.
+// Messages are framed the same way as in `DigestTranscript`, each message and its label
+// being prefixed with their length
+#[derive(Clone, Default)]
+pub struct HashTranscript<H> {
+    hasher: H,
+}
+
+impl<H: ChallengeHash> HashTranscript<H> {
+    // Creates a transcript starting with the label of the hash
+    pub fn new() -> Self {
+        let mut transcript = HashTranscript {
+            hasher: H::default(),
+        };
+        transcript.append_message(b"protocol", H::LABEL);
+        transcript
+    }
+
+    fn append_message(&mut self, label: &[u8], message: &[u8]) {
+        self.hasher.update(&(label.len() as u64).to_le_bytes());
+        self.hasher.update(label);
+        self.hasher.update(&(message.len() as u64).to_le_bytes());
+        self.hasher.update(message);
+    }
+
+    fn append_u64(&mut self, label: &[u8], value: u64) {
+        self.append_message(label, &value.to_le_bytes());
+    }
+}
+
+impl<H: ChallengeHash> SigningTranscript for HashTranscript<H> {
+    fn append_domain(&mut self, domain: &'static [u8]) {
+        self.append_message(b"dom-sep", domain);
+    }
+
+    fn append_ring(&mut self, num_layers: usize, pubkey_matrix: &[u8]) {
+        self.append_u64(b"num_layers", num_layers as u64);
+        self.append_message(b"pubkey_matrix", pubkey_matrix);
+    }
+
+    fn append_key_images(&mut self, key_images: &[KeyImage]) {
+        self.append_u64(b"num_key_images", key_images.len() as u64);
+        for key_image in key_images {
+            self.append_message(b"key_image", key_image.as_bytes());
+        }
+    }
+
+    fn append_msg(&mut self, msg: &[u8]) {
+        self.append_message(b"msg", msg);
+    }
+
+    fn append_layer(&mut self, layer: usize) {
+        self.append_u64(b"layer", layer as u64);
+    }
+
+    fn append_context(&mut self, label: &'static [u8], context: &[u8]) {
+        self.append_message(label, context);
+    }
+
+    fn append_point(&mut self, label: &'static [u8], point: &RistrettoPoint) {
+        self.append_message(label, point.compress().as_bytes());
+    }
+
+    fn challenge_scalar(&mut self, label: &'static [u8]) -> Scalar {
+        self.append_message(b"challenge", label);
+
+        self.hasher.challenge()
+    }
+}
+
+// The hashes a signature is made with
+pub trait CipherSuite {
+    // The name of the suite
+    const ID: &'static str;
+
+    // The construction the first key of each member is hashed to a point with
+    type HashToPoint: HashToPoint + Default + Send + Sync + 'static;
+
+    // The transcript every challenge is derived from
+    type Transcript: SigningTranscript;
+
+    // Returns the transcript a signature starts from
+    fn transcript() -> Self::Transcript;
+}
+
+// Domain separated SHA-512 for hashing to points and merlin for challenges,
+// which is the suite used when none is given
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Ristretto255Sha512;
+
+impl CipherSuite for Ristretto255Sha512 {
+    const ID: &'static str = "ristretto255_sha512";
+    type HashToPoint = DomainSeparated<Sha512>;
+    type Transcript = Transcript;
+
+    fn transcript() -> Transcript {
+        default_transcript()
+    }
+}
+
+// Domain separated SHA-512 for hashing to points, and SHA-512 reduced from 64 bytes
+// for challenges
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Ristretto255Sha512Wide;
+
+impl CipherSuite for Ristretto255Sha512Wide {
+    const ID: &'static str = "ristretto255_sha512_wide";
+    type HashToPoint = DomainSeparated<Sha512>;
+    type Transcript = HashTranscript<Sha512Wide>;
+
+    fn transcript() -> Self::Transcript {
+        HashTranscript::new()
+    }
+}
+
+// Keccak-256 both for hashing to points, with `KeccakHashToPoint`,
+// and reduced from 32 bytes for challenges
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Ristretto255Keccak256;
+
+impl CipherSuite for Ristretto255Keccak256 {
+    const ID: &'static str = "ristretto255_keccak256";
+    type HashToPoint = KeccakHashToPoint;
+    type Transcript = HashTranscript<Keccak256Narrow>;
+
+    fn transcript() -> Self::Transcript {
+        HashTranscript::new()
+    }
+}
+
+// Hashes the domain tag and the bytes twice with Keccak-256, with a counter between them,
+// and maps the 64 bytes of both hashes to a point
+#[derive(Debug, Clone, Copy, Default)]
+pub struct KeccakHashToPoint;
+
+impl HashToPoint for KeccakHashToPoint {
+    fn hash_to_point(&self, bytes: &[u8]) -> RistrettoPoint {
+        let mut uniform = [0u8; 64];
+        for (counter, half) in uniform.chunks_mut(32).enumerate() {
+            let mut hasher = Keccak256Narrow::default();
+            hasher.update(HASH_TO_POINT_DOMAIN);
+            hasher.update(&[counter as u8]);
+            hasher.update(bytes);
+            half.copy_from_slice(&hasher.finalize());
+        }
+        RistrettoPoint::from_uniform_bytes(&uniform)
+    }
+}
+
+impl Clsag {
+    // sign_with_suite produces a clsag signature with the hashes of the cipher suite `CS`,
+    // hashing the members again if the ring was set up with another hash-to-point construction.
+    // The signature only verifies with `verify_with_suite` and the same suite
+    pub fn sign_with_suite<CS: CipherSuite>(
+        &self,
+        msg: &[u8],
+    ) -> Result<(Signature, PublicKeys), ClsagError> {
+        let mut clsag = self.clone();
+        clsag.set_hash_to_point(CS::HashToPoint::default());
+        clsag.sign_with_transcript(&CS::transcript(), msg)
+    }
+}
+
+impl Signature {
+    // Verifies a signature which was produced with `Clsag::sign_with_suite`
+    // under the cipher suite `CS`
+    pub fn verify_with_suite<CS: CipherSuite>(
+        &self,
+        public_keys: &[Vec<PublicKey>],
+        msg: &[u8],
+    ) -> Result<(), ClsagError> {
+        let ring = PreparedRing::with_hash_to_point(public_keys, &CS::HashToPoint::default())?;
+        self.verify_prepared_with_transcript(&CS::transcript(), &ring, msg)
+    }
+}
+
+// Returns the Keccak-256 hash of the bytes
+pub fn keccak256(bytes: &[u8]) -> [u8; 32] {
+    let mut hasher = Keccak256Narrow::default();
+    hasher.update(bytes);
+    hasher.finalize()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::*;
+
+    // Returns the challenge of the hash after appending a fixed message
+    fn fixed_challenge<H: ChallengeHash>() -> String {
+        let mut transcript = HashTranscript::<H>::new();
+        transcript.append_msg(b"hello world");
+        hex::encode(transcript.challenge_scalar(b"c").as_bytes())
+    }
+
+    #[test]
+    fn test_keccak256_vectors() {
+        assert_eq!(
+            "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470",
+            hex::encode(keccak256(b""))
+        );
+        assert_eq!(
+            "4e03657aea45a94fc7d47ba826c8d667c0d1e6e33a64a036ec44f58fa12d6c45",
+            hex::encode(keccak256(b"abc"))
+        );
+
+        // Absorbing in pieces across block boundaries gives the same hash
+        let long = vec![0xa5u8; 3 * KECCAK_RATE + 7];
+        let mut hasher = Keccak256Narrow::default();
+        for piece in long.chunks(KECCAK_RATE - 1) {
+            hasher.update(piece);
+        }
+        assert_eq!(keccak256(&long), hasher.finalize());
+    }
+
+    #[test]
+    fn test_challenge_hash_vectors() {
+        assert_eq!(
+            "2ae66207a2da92107844341e5400b47beb330481ce228e93c501b5e82d8d3702",
+            fixed_challenge::<Sha512Wide>()
+        );
+        assert_eq!(
+            "ac2254a8d4af6f000eb6cc6e2e7ead399732edd20abdbec1eb2fcd936261a309",
+            fixed_challenge::<Keccak256Narrow>()
+        );
+        assert_eq!(
+            "465e90073c6f0efc2d7273687e43fe58af6bfb3cb3fddc3b0048cc514aba9d5a",
+            hex::encode(
+                KeccakHashToPoint
+                    .hash_to_point(&[1u8; 32])
+                    .compress()
+                    .as_bytes()
+            )
+        );
+    }
+
+    #[test]
+    fn test_default_suite() {
+        let mut clsag = generate_clsag_with(3, 2);
+        clsag.add_member(generate_signer(2)).unwrap();
+        let msg = b"hello world";
+
+        // The default suite signs as `sign` does
+        let (signature, public_keys) = clsag.sign_with_suite::<Ristretto255Sha512>(msg).unwrap();
+        assert!(signature.verify(&public_keys, msg).is_ok());
+        let (signature, public_keys) = clsag.sign(msg).unwrap();
+        assert!(signature
+            .verify_with_suite::<Ristretto255Sha512>(&public_keys, msg)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_sign_with_suite() {
+        let mut clsag = generate_clsag_with(3, 2);
+        clsag.add_member(generate_signer(2)).unwrap();
+        let msg = b"hello world";
+
+        let (signature, public_keys) = clsag.sign_with_suite::<Ristretto255Keccak256>(msg).unwrap();
+        assert!(signature
+            .verify_with_suite::<Ristretto255Keccak256>(&public_keys, msg)
+            .is_ok());
+
+        // The signature does not verify under another suite
+        assert!(signature
+            .verify_with_suite::<Ristretto255Sha512Wide>(&public_keys, msg)
+            .is_err());
+        assert!(signature.verify(&public_keys, msg).is_err());
+
+        // The key images are made over the hash-to-point construction of the suite
+        let (other, public_keys) = clsag
+            .sign_with_suite::<Ristretto255Sha512Wide>(msg)
+            .unwrap();
+        assert_ne!(signature.key_images, other.key_images);
+        assert!(other
+            .verify_with_suite::<Ristretto255Sha512Wide>(&public_keys, msg)
+            .is_ok());
+        assert!(other
+            .verify_with_suite::<Ristretto255Sha512>(&public_keys, msg)
+            .is_err());
+    }
+}
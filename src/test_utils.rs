@@ -0,0 +1,244 @@
+// Fixtures for tests, behind the test-utils feature.
+//
+// The helpers generate random keys, decoys, signers and rings, so that the tests of this crate,
+// and the integration tests of crates built on it, do not each write their own. Every helper
+// draws from the thread rng, and has a `_with_rng` variant which draws from the given rng
+// instead. The `_seeded` variants draw from an rng seeded with the given seed, see `seeded_rng`,
+// and generate the same fixtures on every run, so that a case which fails in CI can be
+// reproduced from its seed, and a property test can shrink the seed along with its other inputs.
+//
+// The keys are random and the rings are not checked against any policy other than the
+// permissive one, so the fixtures must never be used outside of tests.
+use crate::clsag::Clsag;
+use crate::keys::{PrivateSet, PublicKey};
+use crate::member::Member;
+use crate::policy::RingPolicy;
+use curve25519_dalek::ristretto::RistrettoPoint;
+use curve25519_dalek::scalar::Scalar;
+use rand::{CryptoRng, RngCore, SeedableRng};
+use rand_chacha::ChaChaRng;
+
+// Returns an rng seeded with the seed, which generates the same fixtures every time.
+// ChaCha is used rather than `StdRng`, whose algorithm may change between versions of rand
+pub fn seeded_rng(seed: u64) -> ChaChaRng {
+    ChaChaRng::seed_from_u64(seed)
+}
+
+pub fn generate_rand_scalars(num: usize) -> Vec<Scalar> {
+    generate_rand_scalars_with_rng(&mut rand::thread_rng(), num)
+}
+pub fn generate_rand_scalars_with_rng<R: RngCore + CryptoRng>(
+    rng: &mut R,
+    num: usize,
+) -> Vec<Scalar> {
+    (0..num).map(|_| Scalar::random(rng)).collect()
+}
+
+pub fn generate_private_set(num: usize) -> PrivateSet {
+    generate_private_set_with_rng(&mut rand::thread_rng(), num)
+}
+pub fn generate_private_set_with_rng<R: RngCore + CryptoRng>(
+    rng: &mut R,
+    num: usize,
+) -> PrivateSet {
+    PrivateSet::generate(num, rng)
+}
+
+pub fn generate_rand_points(num: usize) -> Vec<RistrettoPoint> {
+    generate_rand_points_with_rng(&mut rand::thread_rng(), num)
+}
+pub fn generate_rand_points_with_rng<R: RngCore + CryptoRng>(
+    rng: &mut R,
+    num: usize,
+) -> Vec<RistrettoPoint> {
+    (0..num).map(|_| RistrettoPoint::random(rng)).collect()
+}
+
+pub fn generate_rand_public_keys(num: usize) -> Vec<PublicKey> {
+    generate_rand_public_keys_with_rng(&mut rand::thread_rng(), num)
+}
+pub fn generate_rand_public_keys_with_rng<R: RngCore + CryptoRng>(
+    rng: &mut R,
+    num: usize,
+) -> Vec<PublicKey> {
+    generate_rand_points_with_rng(rng, num)
+        .iter()
+        .map(PublicKey::from_point)
+        .collect()
+}
+
+pub fn generate_decoy(num_keys: usize) -> Member {
+    generate_decoy_with_rng(&mut rand::thread_rng(), num_keys)
+}
+// The response of the decoy is drawn from the thread rng, and replaced by one drawn
+// from the signing rng when it signs, see `Clsag::sign_with_rng`
+pub fn generate_decoy_with_rng<R: RngCore + CryptoRng>(rng: &mut R, num_keys: usize) -> Member {
+    Member::new_decoy(generate_rand_points_with_rng(rng, num_keys))
+}
+
+pub fn generate_decoys(num_decoys: usize, num_keys: usize) -> Vec<Member> {
+    generate_decoys_with_rng(&mut rand::thread_rng(), num_decoys, num_keys)
+}
+pub fn generate_decoys_with_rng<R: RngCore + CryptoRng>(
+    rng: &mut R,
+    num_decoys: usize,
+    num_keys: usize,
+) -> Vec<Member> {
+    (0..num_decoys)
+        .map(|_| generate_decoy_with_rng(rng, num_keys))
+        .collect()
+}
+
+pub fn generate_signer(num_keys: usize) -> Member {
+    generate_signer_with_rng(&mut rand::thread_rng(), num_keys)
+}
+// The nonce of the signer is drawn from the thread rng, and replaced by one drawn
+// from the signing rng when it signs, see `Clsag::sign_with_rng`
+pub fn generate_signer_with_rng<R: RngCore + CryptoRng>(rng: &mut R, num_keys: usize) -> Member {
+    Member::new_signer(generate_rand_scalars_with_rng(rng, num_keys))
+}
+
+// The returned clsag allows rings smaller than the default policy,
+// so that tests can sign over as few as two members
+pub fn generate_clsag_with(num_decoys: usize, num_keys: usize) -> Clsag {
+    generate_clsag_with_rng(&mut rand::thread_rng(), num_decoys, num_keys)
+}
+pub fn generate_clsag_with_rng<R: RngCore + CryptoRng>(
+    rng: &mut R,
+    num_decoys: usize,
+    num_keys: usize,
+) -> Clsag {
+    let mut clsag = Clsag::new();
+    clsag.set_ring_policy(RingPolicy::permissive());
+
+    for decoy in generate_decoys_with_rng(rng, num_decoys, num_keys) {
+        clsag.add_member(decoy).unwrap();
+    }
+
+    clsag
+}
+
+pub fn generate_private_set_seeded(seed: u64, num: usize) -> PrivateSet {
+    generate_private_set_with_rng(&mut seeded_rng(seed), num)
+}
+
+pub fn generate_decoys_seeded(seed: u64, num_decoys: usize, num_keys: usize) -> Vec<Member> {
+    generate_decoys_with_rng(&mut seeded_rng(seed), num_decoys, num_keys)
+}
+
+pub fn generate_signer_seeded(seed: u64, num_keys: usize) -> Member {
+    generate_signer_with_rng(&mut seeded_rng(seed), num_keys)
+}
+
+// Returns a ring of `ring_size` members, with the signer at `signer_index` and shuffling
+// turned off, so that the ring is signed in the order it was generated in. Signing it with
+// `Clsag::sign_with_rng` and a seeded rng gives the same signature on every run.
+// Panics if the signer index is not less than the ring size
+pub fn generate_ring_seeded(
+    seed: u64,
+    ring_size: usize,
+    num_keys: usize,
+    signer_index: usize,
+) -> Clsag {
+    let mut rng = seeded_rng(seed);
+    let mut clsag = generate_clsag_with_rng(&mut rng, signer_index, num_keys);
+    clsag
+        .add_member(generate_signer_with_rng(&mut rng, num_keys))
+        .unwrap();
+    for decoy in generate_decoys_with_rng(&mut rng, ring_size - signer_index - 1, num_keys) {
+        clsag.add_member(decoy).unwrap();
+    }
+    clsag.set_shuffle_members(false);
+
+    clsag
+}
+
+// Counts the allocations made by each thread, so that a test can check
+// that signing and verifying do not allocate
+#[cfg(test)]
+pub use counting::allocations;
+
+#[cfg(test)]
+mod counting {
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
+
+    struct CountingAllocator;
+
+    thread_local! {
+        static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            ALLOCATIONS.with(|count| count.set(count.get() + 1));
+            System.alloc(layout)
+        }
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    // Returns the number of allocations the current thread has made
+    pub fn allocations() -> usize {
+        ALLOCATIONS.with(Cell::get)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_seeded_fixtures() {
+        let keys = generate_rand_public_keys_with_rng(&mut seeded_rng(7), 3);
+        assert_eq!(
+            keys,
+            generate_rand_public_keys_with_rng(&mut seeded_rng(7), 3)
+        );
+        assert_ne!(
+            keys,
+            generate_rand_public_keys_with_rng(&mut seeded_rng(8), 3)
+        );
+
+        let mut rng = seeded_rng(7);
+        let clsag = generate_clsag_with_rng(&mut rng, 3, 2);
+        let signer = generate_signer_with_rng(&mut rng, 2);
+        let mut other_rng = seeded_rng(7);
+        let other_clsag = generate_clsag_with_rng(&mut other_rng, 3, 2);
+        let other_signer = generate_signer_with_rng(&mut other_rng, 2);
+        assert_eq!(clsag.public_keys(), other_clsag.public_keys());
+        assert_eq!(signer.public_set.keys(), other_signer.public_set.keys());
+    }
+    #[test]
+    fn test_seeded_ring() {
+        let ring = generate_ring_seeded(3, 5, 2, 1);
+        assert_eq!(5, ring.members().len());
+        assert_eq!(1, ring.find_signer().unwrap());
+        assert_eq!(
+            ring.public_keys(),
+            generate_ring_seeded(3, 5, 2, 1).public_keys()
+        );
+
+        let sign = || {
+            let ring = generate_ring_seeded(3, 5, 2, 1);
+            let (signature, _) = ring.sign_with_rng(&mut seeded_rng(4), b"hello").unwrap();
+            signature.to_bytes()
+        };
+        assert_eq!(sign(), sign());
+
+        let decoys = generate_decoys_seeded(5, 2, 1);
+        assert_eq!(
+            decoys[1].public_set.keys(),
+            generate_decoys_seeded(5, 2, 1)[1].public_set.keys()
+        );
+        assert_eq!(
+            generate_private_set_seeded(6, 2).0,
+            generate_private_set_seeded(6, 2).0
+        );
+        assert!(generate_signer_seeded(7, 2).is_signer());
+    }
+}
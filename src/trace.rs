@@ -0,0 +1,225 @@
+// Instrumentation of signing and verification, behind the trace feature.
+//
+// Every signature made by a `Clsag`, whether it is signed with `sign`, `sign_ct`,
+// `sign_deterministic`, `sign_adaptor`, `sign_with_commitment` or `sign_with_range_proof`,
+// or by a device or an anti-exfil session, and every signature verified over a `PreparedRing`,
+// which is what every verify method but the reference `Signature::verify` does, is reported
+// to the subscriber as a `Span`. A span holds the size of the ring, the number of keys of each member, how long
+// the operation took and the error it failed with, if it failed. Nothing secret is ever put
+// in a span, so spans can be logged by node operators diagnosing slow or failing
+// verifications. Spans of failed operations are at the debug level, the rest at the trace level.
+//
+// A subscriber is set for the whole process with `set_global_subscriber`, or for the
+// duration of a closure on the current thread with `with_subscriber`, which takes precedence.
+// When no subscriber is set, the operations are not timed. `StderrSubscriber` writes each
+// span on a line of its own, and can be replaced by a subscriber forwarding the spans
+// to the logging or tracing framework of the application.
+use crate::error::ClsagError;
+use std::cell::RefCell;
+use std::fmt;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+
+// The operation a span was recorded for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Sign,
+    Verify,
+}
+
+// The level of a span, from the least to the most important
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Trace,
+    Debug,
+}
+
+// The record of one signature being made or verified
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    pub operation: Operation,
+    // The number of members of the ring
+    pub ring_size: usize,
+    // The number of keys of each member, which is the number of key images
+    pub num_keys: usize,
+    pub elapsed: Duration,
+    // The error the operation failed with, if it failed
+    pub failure: Option<ClsagError>,
+}
+
+impl Span {
+    // Returns the level of the span, which is debug for failed operations and trace otherwise
+    pub fn level(&self) -> Level {
+        match self.failure {
+            Some(_) => Level::Debug,
+            None => Level::Trace,
+        }
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let level = match self.level() {
+            Level::Trace => "TRACE",
+            Level::Debug => "DEBUG",
+        };
+        let operation = match self.operation {
+            Operation::Sign => "sign",
+            Operation::Verify => "verify",
+        };
+        write!(
+            f,
+            "{} clsag::{}: ring_size={} num_keys={} elapsed={:?}",
+            level, operation, self.ring_size, self.num_keys, self.elapsed
+        )?;
+        if let Some(failure) = self.failure {
+            write!(f, " failure=\"{}\"", failure)?;
+        }
+        Ok(())
+    }
+}
+
+// A Subscriber receives a span for every signature which is made or verified
+pub trait Subscriber: Send + Sync {
+    fn on_span(&self, span: &Span);
+}
+
+// Writes every span at or above its level to standard error
+#[derive(Debug, Clone, Copy)]
+pub struct StderrSubscriber {
+    pub level: Level,
+}
+
+impl Subscriber for StderrSubscriber {
+    fn on_span(&self, span: &Span) {
+        if span.level() >= self.level {
+            eprintln!("{}", span);
+        }
+    }
+}
+
+static GLOBAL_SUBSCRIBER: OnceLock<Arc<dyn Subscriber>> = OnceLock::new();
+
+thread_local! {
+    static SCOPED_SUBSCRIBER: RefCell<Option<Arc<dyn Subscriber>>> = RefCell::new(None);
+}
+
+// Sets the subscriber for every thread of the process.
+// Returns false if a global subscriber has already been set, which is then kept
+pub fn set_global_subscriber(subscriber: Arc<dyn Subscriber>) -> bool {
+    GLOBAL_SUBSCRIBER.set(subscriber).is_ok()
+}
+
+// Runs the closure with the subscriber receiving the spans of the current thread,
+// in place of the global subscriber
+pub fn with_subscriber<T, F: FnOnce() -> T>(subscriber: Arc<dyn Subscriber>, f: F) -> T {
+    // The previous subscriber is restored when the guard is dropped,
+    // even if the closure panics
+    struct Restore(Option<Arc<dyn Subscriber>>);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let previous = self.0.take();
+            SCOPED_SUBSCRIBER.with(|scoped| *scoped.borrow_mut() = previous);
+        }
+    }
+
+    let previous = SCOPED_SUBSCRIBER.with(|scoped| scoped.borrow_mut().replace(subscriber));
+    let _restore = Restore(previous);
+    f()
+}
+
+fn current_subscriber() -> Option<Arc<dyn Subscriber>> {
+    SCOPED_SUBSCRIBER
+        .with(|scoped| scoped.borrow().clone())
+        .or_else(|| GLOBAL_SUBSCRIBER.get().cloned())
+}
+
+// Runs the operation and reports its span to the current subscriber, if there is one
+pub(crate) fn instrument<T, F: FnOnce() -> Result<T, ClsagError>>(
+    operation: Operation,
+    ring_size: usize,
+    num_keys: usize,
+    f: F,
+) -> Result<T, ClsagError> {
+    let subscriber = match current_subscriber() {
+        Some(subscriber) => subscriber,
+        None => return f(),
+    };
+
+    let start = Instant::now();
+    let result = f();
+    subscriber.on_span(&Span {
+        operation,
+        ring_size,
+        num_keys,
+        elapsed: start.elapsed(),
+        failure: result.as_ref().err().copied(),
+    });
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::*;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<Span>>);
+
+    impl Subscriber for Recorder {
+        fn on_span(&self, span: &Span) {
+            self.0.lock().unwrap().push(span.clone());
+        }
+    }
+
+    #[test]
+    fn test_spans_of_sign_and_verify() {
+        let recorder = Arc::new(Recorder::default());
+
+        let mut clsag = generate_clsag_with(3, 2);
+        clsag.add_member(generate_signer(2)).unwrap();
+        with_subscriber(recorder.clone(), || {
+            let (signature, public_keys) = clsag.sign(b"hello world").unwrap();
+            assert!(signature
+                .optimised_verify(&public_keys, b"hello world")
+                .is_ok());
+            assert!(signature
+                .optimised_verify(&public_keys, b"another")
+                .is_err());
+        });
+        // The subscriber only receives the spans of the closure
+        clsag.sign(b"hello world").unwrap();
+
+        let spans = recorder.0.lock().unwrap();
+        let operations: Vec<Operation> = spans.iter().map(|span| span.operation).collect();
+        assert_eq!(
+            vec![Operation::Sign, Operation::Verify, Operation::Verify],
+            operations
+        );
+        assert!(spans
+            .iter()
+            .all(|span| span.ring_size == 4 && span.num_keys == 2));
+        assert_eq!(Level::Trace, spans[1].level());
+        assert_eq!(Some(ClsagError::ChallengeMismatch), spans[2].failure);
+        assert_eq!(Level::Debug, spans[2].level());
+    }
+
+    #[test]
+    fn test_span_display() {
+        let span = Span {
+            operation: Operation::Verify,
+            ring_size: 11,
+            num_keys: 2,
+            elapsed: Duration::from_micros(1500),
+            failure: Some(ClsagError::ChallengeMismatch),
+        };
+        assert_eq!(
+            format!(
+                "DEBUG clsag::verify: ring_size=11 num_keys=2 elapsed=1.5ms failure=\"{}\"",
+                ClsagError::ChallengeMismatch
+            ),
+            span.to_string()
+        );
+    }
+}
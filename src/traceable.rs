@@ -0,0 +1,351 @@
+// Traceable ring signatures, following Fujisaki and Suzuki.
+//
+// A traceable signature is made over a tag, which is an issue, such as the id of an election,
+// together with the ring. Anyone can sign as many messages as they like over a tag, but
+// two signatures by the same signer over the same tag on different messages reveal the
+// public key of the signer, while signatures by different signers stay anonymous.
+// This makes the scheme suited to e-voting, where a voter who votes twice is identified.
+//
+// For a tag, let `h = H(tag)` and `A0 = H(tag, msg)`. The signer at index `i`, with the
+// secret key `x`, computes `sigma = x * h` and publishes `A1 = (sigma - A0) / (i + 1)`,
+// which defines `sigma_j = A0 + (j + 1) * A1` for every member `j`, so that `sigma_i = sigma`.
+// The signature proves that for some member `j`, `sigma_j` has the same discrete log with respect
+// to `h` as the members key has with respect to the basepoint. Two signatures by the same signer
+// on different messages give two lines through `sigma`, which cross only at the signers index.
+//
+// These signatures are a separate scheme from CLSAG, and are not verified by `Signature`.
+use crate::constants::BASEPOINT;
+use crate::error::ClsagError;
+use crate::keys::{PublicKey, SecretKey};
+use crate::transcript::SigningTranscript;
+use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
+use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::traits::VartimeMultiscalarMul;
+use merlin::Transcript;
+use rand::{CryptoRng, RngCore};
+use std::collections::HashSet;
+
+// The issue a signature is made over, together with the ring of public keys
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tag {
+    issue: Vec<u8>,
+    ring: Vec<PublicKey>,
+}
+
+// A traceable ring signature over a tag
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TraceableSignature {
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::serde_helpers::compressed_point")
+    )]
+    a1: CompressedRistretto,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::scalar"))]
+    challenge: Scalar,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::scalars"))]
+    responses: Vec<Scalar>,
+}
+
+impl Tag {
+    // Creates the tag for the issue and the ring.
+    // Returns an error if the ring has fewer than two members or has the same key twice
+    pub fn new(issue: &[u8], ring: Vec<PublicKey>) -> Result<Self, ClsagError> {
+        match ring.len() {
+            0 => return Err(ClsagError::EmptyRing),
+            1 => return Err(ClsagError::RingTooSmall),
+            _ => (),
+        }
+        let mut keys = HashSet::with_capacity(ring.len());
+        if let Some(index) = ring.iter().position(|key| !keys.insert(*key)) {
+            return Err(ClsagError::DuplicateKey(index));
+        }
+
+        Ok(Tag {
+            issue: issue.to_vec(),
+            ring,
+        })
+    }
+    // Returns the public keys of the ring, in ring order
+    pub fn ring(&self) -> &[PublicKey] {
+        &self.ring
+    }
+    // Signs the message over the tag with the secret key, whose public key must be in the ring.
+    // Returns an error if the public key of the secret key is not in the ring
+    pub fn sign<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        secret_key: &SecretKey,
+        msg: &[u8],
+    ) -> Result<TraceableSignature, ClsagError> {
+        let signer_index = self
+            .ring
+            .iter()
+            .position(|key| *key == secret_key.public_key())
+            .ok_or(ClsagError::NotASigner)?;
+        let points = self.points()?;
+        let n = self.ring.len();
+
+        let h = self.hashed_tag();
+        let a0 = self.hashed_msg(msg);
+        let sigma = secret_key.0 * h;
+        let a1 = (sigma - a0) * Scalar::from((signer_index + 1) as u64).invert();
+        let transcript = self.transcript(msg, &a1);
+
+        let nonce = Scalar::random(rng);
+        let mut responses: Vec<Scalar> = (0..n).map(|_| Scalar::random(rng)).collect();
+        let mut challenges = vec![Scalar::zero(); n];
+
+        // The challenge of the member after the signer is computed from the nonce,
+        // and each challenge after it from the response of the member before
+        let mut challenge = round_challenge(&transcript, &(nonce * BASEPOINT), &(nonce * h));
+        for offset in 1..n {
+            let j = (signer_index + offset) % n;
+            challenges[j] = challenge;
+            let sigma_j = a0 + Scalar::from((j + 1) as u64) * a1;
+            let (l, r) = commitments(&responses[j], &challenge, &points[j], &h, &sigma_j);
+            challenge = round_challenge(&transcript, &l, &r);
+        }
+        challenges[signer_index] = challenge;
+        responses[signer_index] = nonce - challenge * secret_key.0;
+
+        Ok(TraceableSignature {
+            a1: a1.compress(),
+            challenge: challenges[0],
+            responses,
+        })
+    }
+    // Verifies the signature on the message over the tag.
+    // Returns an error if the signature is not over a ring of this size,
+    // or if it does not verify
+    pub fn verify(&self, signature: &TraceableSignature, msg: &[u8]) -> Result<(), ClsagError> {
+        let sigmas = self.sigmas(signature, msg)?;
+        let points = self.points()?;
+        let h = self.hashed_tag();
+        let a1 = signature.a1.decompress().ok_or(ClsagError::InvalidPoint)?;
+        let transcript = self.transcript(msg, &a1);
+
+        let mut challenge = signature.challenge;
+        for ((response, point), sigma) in signature.responses.iter().zip(&points).zip(&sigmas) {
+            let (l, r) = commitments(response, &challenge, point, &h, sigma);
+            challenge = round_challenge(&transcript, &l, &r);
+        }
+
+        if challenge != signature.challenge {
+            return Err(ClsagError::ChallengeMismatch);
+        }
+        Ok(())
+    }
+    // Returns the public key of the signer if both signatures verify and were made by the
+    // same signer on different messages. Returns None if either signature does not verify,
+    // if they were made by different signers, or if they are on the same message
+    pub fn trace(
+        &self,
+        msg_a: &[u8],
+        signature_a: &TraceableSignature,
+        msg_b: &[u8],
+        signature_b: &TraceableSignature,
+    ) -> Option<PublicKey> {
+        self.verify(signature_a, msg_a).ok()?;
+        self.verify(signature_b, msg_b).ok()?;
+
+        let sigmas_a = self.sigmas(signature_a, msg_a).ok()?;
+        let sigmas_b = self.sigmas(signature_b, msg_b).ok()?;
+        let mut crossings = sigmas_a
+            .iter()
+            .zip(&sigmas_b)
+            .enumerate()
+            .filter(|(_, (a, b))| a == b)
+            .map(|(index, _)| index);
+
+        match (crossings.next(), crossings.next()) {
+            (Some(index), None) => Some(self.ring[index]),
+            _ => None,
+        }
+    }
+    // Returns true if both signatures verify and were made by the same signer,
+    // either on the same message or on different messages
+    pub fn is_linked(
+        &self,
+        msg_a: &[u8],
+        signature_a: &TraceableSignature,
+        msg_b: &[u8],
+        signature_b: &TraceableSignature,
+    ) -> bool {
+        if msg_a == msg_b {
+            return self.verify(signature_a, msg_a).is_ok()
+                && self.verify(signature_b, msg_b).is_ok()
+                && signature_a.a1 == signature_b.a1;
+        }
+        self.trace(msg_a, signature_a, msg_b, signature_b).is_some()
+    }
+
+    fn points(&self) -> Result<Vec<RistrettoPoint>, ClsagError> {
+        self.ring
+            .iter()
+            .map(|key| key.decompress().ok_or(ClsagError::InvalidPoint))
+            .collect()
+    }
+    // Returns `sigma_j = A0 + (j + 1) * A1` for every member of the ring
+    fn sigmas(
+        &self,
+        signature: &TraceableSignature,
+        msg: &[u8],
+    ) -> Result<Vec<RistrettoPoint>, ClsagError> {
+        if signature.responses.len() != self.ring.len() {
+            return Err(ClsagError::RingSizeMismatch);
+        }
+        let a1 = signature.a1.decompress().ok_or(ClsagError::InvalidPoint)?;
+        let a0 = self.hashed_msg(msg);
+
+        Ok((0..self.ring.len())
+            .map(|j| a0 + Scalar::from((j + 1) as u64) * a1)
+            .collect())
+    }
+    // Returns the transcript holding the tag, which every point of a signature is hashed with
+    fn tag_transcript(&self, label: &'static [u8]) -> Transcript {
+        let mut transcript = Transcript::new(label);
+        transcript.append_message(b"issue", &self.issue);
+        for key in &self.ring {
+            transcript.append_message(b"ring_key", key.as_bytes());
+        }
+        transcript
+    }
+    // Returns `h = H(tag)`
+    fn hashed_tag(&self) -> RistrettoPoint {
+        let mut bytes = [0u8; 64];
+        self.tag_transcript(b"clsag_traceable_tag")
+            .challenge_bytes(b"h", &mut bytes);
+        RistrettoPoint::from_uniform_bytes(&bytes)
+    }
+    // Returns `A0 = H(tag, msg)`
+    fn hashed_msg(&self, msg: &[u8]) -> RistrettoPoint {
+        let mut transcript = self.tag_transcript(b"clsag_traceable_msg");
+        transcript.append_message(b"msg", msg);
+        let mut bytes = [0u8; 64];
+        transcript.challenge_bytes(b"a0", &mut bytes);
+        RistrettoPoint::from_uniform_bytes(&bytes)
+    }
+    // Returns the transcript every challenge of a signature on the message is computed from
+    fn transcript(&self, msg: &[u8], a1: &RistrettoPoint) -> Transcript {
+        let mut transcript = self.tag_transcript(b"clsag_traceable");
+        transcript.append_msg(msg);
+        transcript.append_point(b"a1", a1);
+        transcript
+    }
+}
+
+impl TraceableSignature {
+    // Returns the number of members in the ring the signature was made over
+    pub fn len(&self) -> usize {
+        self.responses.len()
+    }
+    // Returns true if the signature has no responses, which a valid signature never does
+    pub fn is_empty(&self) -> bool {
+        self.responses.is_empty()
+    }
+}
+
+// Returns `L = response * G + challenge * key` and `R = response * h + challenge * sigma`
+fn commitments(
+    response: &Scalar,
+    challenge: &Scalar,
+    key: &RistrettoPoint,
+    h: &RistrettoPoint,
+    sigma: &RistrettoPoint,
+) -> (RistrettoPoint, RistrettoPoint) {
+    let l = RistrettoPoint::vartime_multiscalar_mul(&[*response, *challenge], &[BASEPOINT, *key]);
+    let r = RistrettoPoint::vartime_multiscalar_mul(&[*response, *challenge], &[*h, *sigma]);
+    (l, r)
+}
+
+fn round_challenge(transcript: &Transcript, l: &RistrettoPoint, r: &RistrettoPoint) -> Scalar {
+    let mut transcript = transcript.clone();
+    transcript.append_point(b"L", l);
+    transcript.append_point(b"R", r);
+    transcript.challenge_scalar(b"challenge")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn generate_tag(secret_keys: &[SecretKey]) -> Tag {
+        let ring = secret_keys.iter().map(SecretKey::public_key).collect();
+        Tag::new(b"election 1", ring).unwrap()
+    }
+
+    #[test]
+    fn test_sign_verify() {
+        let mut rng = rand::thread_rng();
+        let secret_keys: Vec<SecretKey> = (0..5).map(|_| SecretKey::random(&mut rng)).collect();
+        let tag = generate_tag(&secret_keys);
+
+        for secret_key in &secret_keys {
+            let signature = tag.sign(&mut rng, secret_key, b"yes").unwrap();
+            assert!(tag.verify(&signature, b"yes").is_ok());
+            assert!(tag.verify(&signature, b"no").is_err());
+
+            // The signature is bound to the issue
+            let other_tag = Tag::new(b"election 2", tag.ring().to_vec()).unwrap();
+            assert!(other_tag.verify(&signature, b"yes").is_err());
+        }
+
+        // A signer outside the ring cannot sign
+        let outsider = SecretKey::random(&mut rng);
+        match tag.sign(&mut rng, &outsider, b"yes") {
+            Err(ClsagError::NotASigner) => {}
+            _ => panic!("expected a not a signer error"),
+        }
+    }
+
+    #[test]
+    fn test_trace() {
+        let mut rng = rand::thread_rng();
+        let secret_keys: Vec<SecretKey> = (0..6).map(|_| SecretKey::random(&mut rng)).collect();
+        let tag = generate_tag(&secret_keys);
+
+        // The same signer on different messages is traced
+        let yes = tag.sign(&mut rng, &secret_keys[3], b"yes").unwrap();
+        let no = tag.sign(&mut rng, &secret_keys[3], b"no").unwrap();
+        assert_eq!(
+            Some(secret_keys[3].public_key()),
+            tag.trace(b"yes", &yes, b"no", &no)
+        );
+        assert!(tag.is_linked(b"yes", &yes, b"no", &no));
+
+        // The same signer on the same message is linked, but not traced
+        let yes_again = tag.sign(&mut rng, &secret_keys[3], b"yes").unwrap();
+        assert_eq!(None, tag.trace(b"yes", &yes, b"yes", &yes_again));
+        assert!(tag.is_linked(b"yes", &yes, b"yes", &yes_again));
+
+        // Different signers are neither traced nor linked
+        let other_no = tag.sign(&mut rng, &secret_keys[1], b"no").unwrap();
+        assert_eq!(None, tag.trace(b"yes", &yes, b"no", &other_no));
+        assert!(!tag.is_linked(b"yes", &yes, b"no", &other_no));
+        let other_yes = tag.sign(&mut rng, &secret_keys[1], b"yes").unwrap();
+        assert!(!tag.is_linked(b"yes", &yes, b"yes", &other_yes));
+
+        // Signatures over another issue are not traced
+        let other_tag = Tag::new(b"election 2", tag.ring().to_vec()).unwrap();
+        let other_issue = other_tag.sign(&mut rng, &secret_keys[3], b"no").unwrap();
+        assert_eq!(None, tag.trace(b"yes", &yes, b"no", &other_issue));
+    }
+
+    #[test]
+    fn test_new_rejects_bad_rings() {
+        let mut rng = rand::thread_rng();
+        let key = SecretKey::random(&mut rng).public_key();
+        match Tag::new(b"issue", vec![key]) {
+            Err(ClsagError::RingTooSmall) => {}
+            _ => panic!("expected a ring too small error"),
+        }
+        match Tag::new(
+            b"issue",
+            vec![key, SecretKey::random(&mut rng).public_key(), key],
+        ) {
+            Err(ClsagError::DuplicateKey(2)) => {}
+            _ => panic!("expected a duplicate key error"),
+        }
+    }
+}
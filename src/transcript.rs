@@ -1,75 +1,94 @@
+use crate::hash::DefaultHash;
+use crate::keys::KeyImage;
 use curve25519_dalek::ristretto::RistrettoPoint;
 use curve25519_dalek::scalar::Scalar;
 use merlin::Transcript;
-use sha2::Sha512;
+use sha2::digest::generic_array::typenum::U64;
+use sha2::Digest;
+
+/// Returns the transcript which all challenges are derived from by default
+pub fn default_transcript() -> Transcript {
+    Transcript::new(b"clsag")
+}
+
+/// Returns the default transcript with an application specific context appended to it
+pub fn context_transcript(context: &[u8]) -> Transcript {
+    let mut transcript = default_transcript();
+    transcript.append_context(b"context", context);
+    transcript
+}
+
+/// Returns the default transcript for signing the 64 byte SHA-512 digest of a message,
+/// so that a signature over a digest is never valid as a signature over the digest bytes
+pub fn digest_transcript() -> Transcript {
+    let mut transcript = default_transcript();
+    transcript.append_context(b"prehash", b"sha512");
+    transcript
+}
+
+/// A transcript used to derive the aggregation co-efficients and the round challenges.
+///
+/// Each part of the signature is appended with its own label, so that the ring,
+/// the key images and the message are domain separated from each other. The transcript
+/// is cloned for every challenge, so integrators can bind their own context into a
+/// signature by appending it to the transcript before signing and verifying.
+pub trait SigningTranscript: Clone {
+    /// Separates the challenges computed for different purposes
+    fn append_domain(&mut self, domain: &'static [u8]);
+
+    /// Appends the public keys of every member in the ring, along with the number of layers
+    fn append_ring(&mut self, num_layers: usize, pubkey_matrix: &[u8]);
+
+    /// Appends the key images of the signer
+    fn append_key_images(&mut self, key_images: &[KeyImage]);
+
+    /// Appends the message being signed
+    fn append_msg(&mut self, msg: &[u8]);
+
+    /// Appends the index of the layer an aggregation co-efficient is computed for
+    fn append_layer(&mut self, layer: usize);
+
+    /// Appends application specific context with a given label
+    fn append_context(&mut self, label: &'static [u8], context: &[u8]);
 
-/// Extension trait to the Merlin transcript API that allows committing scalars and points and
-/// generating challenges as scalars.
-pub trait TranscriptProtocol {
     /// Appends a `point` with a given label
     fn append_point(&mut self, label: &'static [u8], point: &RistrettoPoint);
 
-    /// Appends `scalar * point` with a given label
-    fn append_scalar_mult(&mut self, label: &'static [u8], scalar: &Scalar, point: &RistrettoPoint);
-
-    /// Appends `scalar * hash_to_point(point)` with a given label
-    fn append_scalar_hash_point(
-        &mut self,
-        label: &'static [u8],
-        scalar: &Scalar,
-        point: &RistrettoPoint,
-    );
-
-    /// Given (s_1, s_2) and (P_1, P_2)
-    /// Appends s_1 * P_1 + s_2 * P_2 with a given label
-    fn append_double_scalar_mult_add(
-        &mut self,
-        label: &'static [u8],
-        scalars: (&Scalar, &Scalar),
-        points: (&RistrettoPoint, &RistrettoPoint),
-    );
-
     /// Compute a `label`ed challenge variable.
     fn challenge_scalar(&mut self, label: &'static [u8]) -> Scalar;
 }
 
-impl TranscriptProtocol for Transcript {
-    fn append_point(&mut self, label: &'static [u8], point: &RistrettoPoint) {
-        self.append_message(label, point.compress().as_bytes());
+impl SigningTranscript for Transcript {
+    fn append_domain(&mut self, domain: &'static [u8]) {
+        self.append_message(b"dom-sep", domain);
     }
 
-    fn append_scalar_mult(
-        &mut self,
-        label: &'static [u8],
-        scalar: &Scalar,
-        point: &RistrettoPoint,
-    ) {
-        let new_point = scalar * point;
-        self.append_message(label, new_point.compress().as_bytes());
+    fn append_ring(&mut self, num_layers: usize, pubkey_matrix: &[u8]) {
+        self.append_u64(b"num_layers", num_layers as u64);
+        self.append_message(b"pubkey_matrix", pubkey_matrix);
     }
 
-    fn append_scalar_hash_point(
-        &mut self,
-        label: &'static [u8],
-        scalar: &Scalar,
-        point: &RistrettoPoint,
-    ) {
-        let hashed_point = RistrettoPoint::hash_from_bytes::<Sha512>(point.compress().as_bytes());
-        let scalar_hashed_point = scalar * hashed_point;
-        self.append_message(label, scalar_hashed_point.compress().as_bytes());
+    fn append_key_images(&mut self, key_images: &[KeyImage]) {
+        self.append_u64(b"num_key_images", key_images.len() as u64);
+        for key_image in key_images {
+            self.append_message(b"key_image", key_image.as_bytes());
+        }
     }
 
-    fn append_double_scalar_mult_add(
-        &mut self,
-        label: &'static [u8],
-        scalars: (&Scalar, &Scalar),
-        points: (&RistrettoPoint, &RistrettoPoint),
-    ) {
-        let left_scalar_mult = scalars.0 * points.0;
-        let right_scalar_mult = scalars.1 * points.1;
+    fn append_msg(&mut self, msg: &[u8]) {
+        self.append_message(b"msg", msg);
+    }
 
-        let new_point = left_scalar_mult + right_scalar_mult;
-        self.append_message(label, new_point.compress().as_bytes());
+    fn append_layer(&mut self, layer: usize) {
+        self.append_u64(b"layer", layer as u64);
+    }
+
+    fn append_context(&mut self, label: &'static [u8], context: &[u8]) {
+        self.append_message(label, context);
+    }
+
+    fn append_point(&mut self, label: &'static [u8], point: &RistrettoPoint) {
+        self.append_message(label, point.compress().as_bytes());
     }
 
     fn challenge_scalar(&mut self, label: &'static [u8]) -> Scalar {
@@ -79,3 +98,128 @@ impl TranscriptProtocol for Transcript {
         Scalar::from_bytes_mod_order_wide(&buf)
     }
 }
+
+/// A transcript which hashes every challenge with the 64 byte output hash function `D`,
+/// such as BLAKE2b or SHA3-512, instead of with merlin, for systems which are standardized
+/// on another hash function. SHA-512 is used when no hash function is given.
+///
+/// Every message is appended with its label, and both are prefixed with their length,
+/// so that messages cannot be split differently to give the same hash. The transcript
+/// is used by passing it to `sign_with_transcript` and `verify_with_transcript`.
+#[derive(Clone)]
+pub struct DigestTranscript<D = DefaultHash> {
+    hasher: D,
+}
+
+impl<D: Digest<OutputSize = U64> + Clone + Default> DigestTranscript<D> {
+    /// Creates a transcript for the protocol with the given label
+    pub fn new(label: &'static [u8]) -> Self {
+        let mut transcript = DigestTranscript { hasher: D::new() };
+        transcript.append_message(b"protocol", label);
+        transcript
+    }
+
+    fn append_message(&mut self, label: &[u8], message: &[u8]) {
+        self.hasher.input((label.len() as u64).to_le_bytes());
+        self.hasher.input(label);
+        self.hasher.input((message.len() as u64).to_le_bytes());
+        self.hasher.input(message);
+    }
+
+    fn append_u64(&mut self, label: &[u8], value: u64) {
+        self.append_message(label, &value.to_le_bytes());
+    }
+}
+
+impl<D: Digest<OutputSize = U64> + Clone + Default> SigningTranscript for DigestTranscript<D> {
+    fn append_domain(&mut self, domain: &'static [u8]) {
+        self.append_message(b"dom-sep", domain);
+    }
+
+    fn append_ring(&mut self, num_layers: usize, pubkey_matrix: &[u8]) {
+        self.append_u64(b"num_layers", num_layers as u64);
+        self.append_message(b"pubkey_matrix", pubkey_matrix);
+    }
+
+    fn append_key_images(&mut self, key_images: &[KeyImage]) {
+        self.append_u64(b"num_key_images", key_images.len() as u64);
+        for key_image in key_images {
+            self.append_message(b"key_image", key_image.as_bytes());
+        }
+    }
+
+    fn append_msg(&mut self, msg: &[u8]) {
+        self.append_message(b"msg", msg);
+    }
+
+    fn append_layer(&mut self, layer: usize) {
+        self.append_u64(b"layer", layer as u64);
+    }
+
+    fn append_context(&mut self, label: &'static [u8], context: &[u8]) {
+        self.append_message(label, context);
+    }
+
+    fn append_point(&mut self, label: &'static [u8], point: &RistrettoPoint) {
+        self.append_message(label, point.compress().as_bytes());
+    }
+
+    fn challenge_scalar(&mut self, label: &'static [u8]) -> Scalar {
+        self.append_message(b"challenge", label);
+
+        Scalar::from_hash(self.hasher.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::*;
+    use blake2::Blake2b;
+
+    #[test]
+    fn test_sign_with_digest_transcript() {
+        let mut clsag = generate_clsag_with(3, 2);
+        clsag.add_member(generate_signer(2)).unwrap();
+        let msg = b"hello world";
+
+        let transcript = DigestTranscript::<Blake2b>::new(b"clsag");
+        let (signature, public_keys) = clsag.sign_with_transcript(&transcript, msg).unwrap();
+        assert!(signature
+            .verify_with_transcript(&transcript, &public_keys, msg)
+            .is_ok());
+
+        // The signature does not verify with another hash function, nor with merlin
+        let sha512_transcript: DigestTranscript = DigestTranscript::new(b"clsag");
+        assert!(signature
+            .verify_with_transcript(&sha512_transcript, &public_keys, msg)
+            .is_err());
+        assert!(signature.verify(&public_keys, msg).is_err());
+
+        let (signature, public_keys) = clsag.sign_with_transcript(&sha512_transcript, msg).unwrap();
+        assert!(signature
+            .verify_with_transcript(&sha512_transcript, &public_keys, msg)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_digest_transcript_challenges() {
+        let mut first = DigestTranscript::<Blake2b>::new(b"clsag");
+        let mut second = first.clone();
+        assert_eq!(first.challenge_scalar(b"c"), second.challenge_scalar(b"c"));
+
+        // Each challenge depends on the challenges before it, and on how messages are split
+        assert_ne!(
+            first.challenge_scalar(b"c"),
+            DigestTranscript::<Blake2b>::new(b"clsag").challenge_scalar(b"c")
+        );
+        let mut split = DigestTranscript::<Blake2b>::new(b"clsag");
+        split.append_msg(b"ab");
+        let mut other_split = DigestTranscript::<Blake2b>::new(b"clsa");
+        other_split.append_msg(b"gab");
+        assert_ne!(
+            split.challenge_scalar(b"c"),
+            other_split.challenge_scalar(b"c")
+        );
+    }
+}
@@ -0,0 +1,466 @@
+// An experimental logarithmic size linkable ring signature in the style of Triptych,
+// behind the experimental feature.
+//
+// A Triptych proof shows that the signer knows the private key `r` of one of the `N` public
+// keys of the ring, and that the key image `J = r^-1 * U` was made with that key, where `U`
+// is a fixed generator. The signer proves that it knows the bits of its index in the ring,
+// committed to in the matrices `A`, `B`, `C` and `D`, and uses the polynomials in the
+// challenge built from those bits to select its key from the ring with the points `X` and `Y`.
+// The proof holds `2 * log2(N) + 4` points and `log2(N) + 3` scalars, against the `N + 1`
+// scalars of a clsag signature, at the cost of signing and verifying in `O(N log N)`.
+//
+// This follows the single key variant of the Triptych paper, with the ring size fixed to a
+// power of two. The key images are not the key images of clsag, so a key spent with both
+// does not link. Members holding commitments or more than one key are not supported, and
+// the module is meant for benchmarking a migration from clsag rather than for production.
+use crate::constants::BASEPOINT;
+use crate::error::ClsagError;
+use crate::hash::DefaultHash;
+use crate::keys::{zeroize_scalar, KeyImage, PublicKey};
+use crate::member::Member;
+use crate::transcript::SigningTranscript;
+use curve25519_dalek::ristretto::RistrettoPoint;
+use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::traits::{IsIdentity, VartimeMultiscalarMul};
+use merlin::Transcript;
+use rand::seq::SliceRandom;
+use rand::{CryptoRng, RngCore};
+
+// A Triptych proof over a ring, along with the key image of the signer
+#[derive(Debug, Clone)]
+pub struct TriptychProof {
+    // The commitments to the random matrix, the bits of the signers index,
+    // and the two matrices which prove that the committed values are bits
+    a: RistrettoPoint,
+    b: RistrettoPoint,
+    c: RistrettoPoint,
+    d: RistrettoPoint,
+
+    // The points which cancel the lower degree terms of the polynomials, one per bit
+    x: Vec<RistrettoPoint>,
+    y: Vec<RistrettoPoint>,
+
+    // The opening of each bit of the signers index under the challenge
+    f: Vec<Scalar>,
+
+    z_a: Scalar,
+    z_c: Scalar,
+    z: Scalar,
+
+    key_image: KeyImage,
+}
+
+// The generators the matrices are committed to with
+struct Generators {
+    // The blinding generator
+    h: RistrettoPoint,
+
+    // The generator of each entry of a matrix of `num_bits` rows and two columns
+    entries: Vec<RistrettoPoint>,
+}
+
+impl Generators {
+    fn new(num_bits: usize) -> Self {
+        let entries = (0..2 * num_bits)
+            .map(|index| {
+                let mut label = b"clsag_triptych_generator".to_vec();
+                label.extend_from_slice(&(index as u64).to_le_bytes());
+                RistrettoPoint::hash_from_bytes::<DefaultHash>(&label)
+            })
+            .collect();
+
+        Generators {
+            h: RistrettoPoint::hash_from_bytes::<DefaultHash>(b"clsag_triptych_h"),
+            entries,
+        }
+    }
+    // Commits to the entries of a matrix, in row order, with the blinding factor
+    fn commit(&self, matrix: &[Scalar], blinding: &Scalar) -> RistrettoPoint {
+        RistrettoPoint::vartime_multiscalar_mul(
+            matrix.iter().chain(Some(blinding)),
+            self.entries.iter().chain(Some(&self.h)),
+        )
+    }
+}
+
+// Returns the generator `U` which key images are computed from
+fn key_image_generator() -> RistrettoPoint {
+    RistrettoPoint::hash_from_bytes::<DefaultHash>(b"clsag_triptych_u")
+}
+
+// Returns the Triptych key image `r^-1 * U` of the private key
+pub fn key_image(private_key: &Scalar) -> KeyImage {
+    KeyImage((private_key.invert() * key_image_generator()).compress())
+}
+
+// Returns the number of bits of the index of a ring of the given size.
+// Returns an error if the size is not a power of two of at least two
+fn num_bits(ring_size: usize) -> Result<usize, ClsagError> {
+    if ring_size < 2 {
+        return Err(ClsagError::RingTooSmall);
+    }
+    if !ring_size.is_power_of_two() {
+        return Err(ClsagError::InvalidRingSize);
+    }
+    Ok(ring_size.trailing_zeros() as usize)
+}
+
+// Returns bit `j` of the index `k`
+fn bit(k: usize, j: usize) -> usize {
+    (k >> j) & 1
+}
+
+#[allow(clippy::too_many_arguments)]
+fn challenge(
+    ring: &[PublicKey],
+    key_image: &KeyImage,
+    a: &RistrettoPoint,
+    b: &RistrettoPoint,
+    c: &RistrettoPoint,
+    d: &RistrettoPoint,
+    x: &[RistrettoPoint],
+    y: &[RistrettoPoint],
+    msg: &[u8],
+) -> Scalar {
+    let mut transcript = Transcript::new(b"clsag_triptych");
+    for key in ring {
+        transcript.append_message(b"ring_key", key.as_bytes());
+    }
+    transcript.append_message(b"key_image", key_image.as_bytes());
+    for (label, point) in [(b"A", a), (b"B", b), (b"C", c), (b"D", d)].iter() {
+        SigningTranscript::append_point(&mut transcript, *label, point);
+    }
+    for (x_j, y_j) in x.iter().zip(y) {
+        SigningTranscript::append_point(&mut transcript, b"X", x_j);
+        SigningTranscript::append_point(&mut transcript, b"Y", y_j);
+    }
+    transcript.append_message(b"msg", msg);
+    transcript.challenge_scalar(b"challenge")
+}
+
+impl TriptychProof {
+    // Signs the message over the ring of members, one of which must be the signer, after
+    // putting the members in a random order. The public keys of the ring are returned
+    // alongside the proof, in the order they were signed in.
+    // Returns an error if there is not exactly one signer, if a member holds a commitment
+    // or more than one key, or if the ring size is not a power of two
+    pub fn sign<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        members: &[Member],
+        msg: &[u8],
+    ) -> Result<(TriptychProof, Vec<PublicKey>), ClsagError> {
+        if members
+            .iter()
+            .any(|member| member.num_keys() != 1 || member.commitment().is_some())
+        {
+            return Err(ClsagError::KeyCountMismatch);
+        }
+        match members.iter().filter(|member| member.is_signer()).count() {
+            0 => return Err(ClsagError::NoSigner),
+            1 => (),
+            _ => return Err(ClsagError::MultipleSignersNotSupported),
+        };
+
+        let mut order: Vec<&Member> = members.iter().collect();
+        order.shuffle(rng);
+        let signer_index = order
+            .iter()
+            .position(|member| member.is_signer())
+            .ok_or(ClsagError::NoSigner)?;
+        let ring: Vec<PublicKey> = order
+            .iter()
+            .map(|member| member.public_set.to_keys()[0])
+            .collect();
+        let private_key = order[signer_index]
+            .private_keys()
+            .ok_or(ClsagError::NoSigner)?[0];
+
+        let proof = TriptychProof::prove(rng, &ring, signer_index, &private_key, msg)?;
+        Ok((proof, ring))
+    }
+    // Proves that the signer knows the private key of the public key at `signer_index` of
+    // the ring, signing the message.
+    // Returns an error if the private key is not the key of the member at `signer_index`,
+    // or if the ring size is not a power of two
+    pub fn prove<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        ring: &[PublicKey],
+        signer_index: usize,
+        private_key: &Scalar,
+        msg: &[u8],
+    ) -> Result<TriptychProof, ClsagError> {
+        let m = num_bits(ring.len())?;
+        if ring.get(signer_index) != Some(&PublicKey::from_point(&(private_key * BASEPOINT))) {
+            return Err(ClsagError::NotASigner);
+        }
+        let points = ring
+            .iter()
+            .map(|key| key.decompress().ok_or(ClsagError::InvalidPoint))
+            .collect::<Result<Vec<RistrettoPoint>, ClsagError>>()?;
+        let generators = Generators::new(m);
+        let u = key_image_generator();
+        let j_point = private_key.invert() * u;
+        let key_image = KeyImage(j_point.compress());
+
+        // The entries of row `j` are for bit `j` of the index being zero and being one
+        let mut r_a = Scalar::random(rng);
+        let mut r_b = Scalar::random(rng);
+        let mut r_c = Scalar::random(rng);
+        let mut r_d = Scalar::random(rng);
+        let mut a_matrix: Vec<Scalar> = (0..m)
+            .flat_map(|_| {
+                let a_1 = Scalar::random(rng);
+                vec![-a_1, a_1]
+            })
+            .collect();
+        let sigma: Vec<Scalar> = (0..m)
+            .flat_map(|j| {
+                let one = Scalar::from(bit(signer_index, j) as u64);
+                vec![Scalar::one() - one, one]
+            })
+            .collect();
+        let c_matrix: Vec<Scalar> = a_matrix
+            .iter()
+            .zip(&sigma)
+            .map(|(a, s)| a * (Scalar::one() - s - s))
+            .collect();
+        let d_matrix: Vec<Scalar> = a_matrix.iter().map(|a| -(a * a)).collect();
+
+        let a = generators.commit(&a_matrix, &r_a);
+        let b = generators.commit(&sigma, &r_b);
+        let c = generators.commit(&c_matrix, &r_c);
+        let d = generators.commit(&d_matrix, &r_d);
+
+        // The coefficients of the polynomial `prod_j (sigma_j,k_j * x + a_j,k_j)` of each
+        // member `k`, which is `x^m` for the signer and of lower degree for every other member
+        let coefficients: Vec<Vec<Scalar>> = (0..ring.len())
+            .map(|k| {
+                let mut poly = vec![Scalar::one()];
+                for j in 0..m {
+                    let entry = 2 * j + bit(k, j);
+                    let mut next = vec![Scalar::zero(); poly.len() + 1];
+                    for (t, coefficient) in poly.iter().enumerate() {
+                        next[t] += coefficient * a_matrix[entry];
+                        next[t + 1] += coefficient * sigma[entry];
+                    }
+                    poly = next;
+                }
+                poly
+            })
+            .collect();
+
+        let mut rho: Vec<Scalar> = (0..m).map(|_| Scalar::random(rng)).collect();
+        let x: Vec<RistrettoPoint> = (0..m)
+            .map(|j| {
+                RistrettoPoint::vartime_multiscalar_mul(
+                    coefficients.iter().map(|poly| poly[j]).chain(Some(rho[j])),
+                    points.iter().chain(Some(&BASEPOINT)),
+                )
+            })
+            .collect();
+        let y: Vec<RistrettoPoint> = rho.iter().map(|rho_j| rho_j * j_point).collect();
+
+        let xi = challenge(ring, &key_image, &a, &b, &c, &d, &x, &y, msg);
+
+        let f = (0..m)
+            .map(|j| sigma[2 * j + 1] * xi + a_matrix[2 * j + 1])
+            .collect();
+        let z_a = r_a + xi * r_b;
+        let z_c = xi * r_c + r_d;
+        let mut xi_power = Scalar::one();
+        let mut blinding = Scalar::zero();
+        for rho_j in &rho {
+            blinding += rho_j * xi_power;
+            xi_power *= xi;
+        }
+        let z = private_key * xi_power - blinding;
+
+        for secret in a_matrix.iter_mut().chain(rho.iter_mut()).chain(vec![
+            &mut r_a,
+            &mut r_b,
+            &mut r_c,
+            &mut r_d,
+            &mut blinding,
+        ]) {
+            zeroize_scalar(secret);
+        }
+
+        Ok(TriptychProof {
+            a,
+            b,
+            c,
+            d,
+            x,
+            y,
+            f,
+            z_a,
+            z_c,
+            z,
+            key_image,
+        })
+    }
+    // Returns the key image of the signer, which links proofs made with the same key
+    pub fn key_image(&self) -> &KeyImage {
+        &self.key_image
+    }
+    // Returns the size of the proof in bytes, with every point compressed
+    pub fn size(&self) -> usize {
+        32 * (4 + self.x.len() + self.y.len() + self.f.len() + 3 + 1)
+    }
+    // Verifies the proof on the message over the ring, in the order it was signed in.
+    // Returns an error if the ring size is not a power of two, if the proof is not for a ring
+    // of that size, if the key image is invalid or if the proof does not verify
+    pub fn verify(&self, ring: &[PublicKey], msg: &[u8]) -> Result<(), ClsagError> {
+        let m = num_bits(ring.len())?;
+        if self.x.len() != m || self.y.len() != m || self.f.len() != m {
+            return Err(ClsagError::KeyCountMismatch);
+        }
+        let j_point = self.key_image.check()?;
+        let points = ring
+            .iter()
+            .map(|key| key.decompress().ok_or(ClsagError::InvalidPoint))
+            .collect::<Result<Vec<RistrettoPoint>, ClsagError>>()?;
+        let generators = Generators::new(m);
+
+        let xi = challenge(
+            ring,
+            &self.key_image,
+            &self.a,
+            &self.b,
+            &self.c,
+            &self.d,
+            &self.x,
+            &self.y,
+            msg,
+        );
+        let f_matrix: Vec<Scalar> = self.f.iter().flat_map(|f_1| vec![xi - f_1, *f_1]).collect();
+
+        // The openings commit to the bits of the index, and every committed value is a bit
+        let bits_open = self.a + xi * self.b == generators.commit(&f_matrix, &self.z_a);
+        let bits_valid = xi * self.c + self.d
+            == generators.commit(
+                &f_matrix
+                    .iter()
+                    .map(|f| f * (xi - f))
+                    .collect::<Vec<Scalar>>(),
+                &self.z_c,
+            );
+        if !bits_open || !bits_valid {
+            return Err(ClsagError::ChallengeMismatch);
+        }
+
+        // The polynomials in the challenge select the signers key from the ring
+        let weights: Vec<Scalar> = (0..ring.len())
+            .map(|k| (0..m).map(|j| f_matrix[2 * j + bit(k, j)]).product())
+            .collect();
+        let xi_powers: Vec<Scalar> = (0..=m)
+            .scan(Scalar::one(), |power, _| {
+                let current = *power;
+                *power *= xi;
+                Some(current)
+            })
+            .collect();
+
+        let key_selected = RistrettoPoint::vartime_multiscalar_mul(
+            weights
+                .iter()
+                .copied()
+                .chain(xi_powers[..m].iter().map(|power| -power))
+                .chain(Some(-self.z)),
+            points.iter().chain(self.x.iter()).chain(Some(&BASEPOINT)),
+        );
+        let key_image_selected = RistrettoPoint::vartime_multiscalar_mul(
+            Some(xi_powers[m])
+                .into_iter()
+                .chain(xi_powers[..m].iter().map(|power| -power))
+                .chain(Some(-self.z)),
+            Some(key_image_generator())
+                .iter()
+                .chain(self.y.iter())
+                .chain(Some(&j_point)),
+        );
+        if !key_selected.is_identity() || !key_image_selected.is_identity() {
+            return Err(ClsagError::ChallengeMismatch);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::*;
+
+    // Returns a ring of `ring_size` members with one key, including a signer
+    fn ring_with_signer(ring_size: usize) -> Vec<Member> {
+        let mut members = generate_decoys(ring_size - 1, 1);
+        members.push(generate_signer(1));
+        members
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let mut rng = rand::thread_rng();
+        let msg = b"hello world";
+
+        for &ring_size in &[2, 4, 16, 64] {
+            let members = ring_with_signer(ring_size);
+            let (proof, ring) = TriptychProof::sign(&mut rng, &members, msg).unwrap();
+            assert!(proof.verify(&ring, msg).is_ok());
+            assert!(proof.verify(&ring, b"another message").is_err());
+            assert_eq!(
+                key_image(&members[ring_size - 1].private_keys().unwrap()[0]),
+                *proof.key_image()
+            );
+
+            let mut tampered = proof.clone();
+            tampered.f[0] += Scalar::one();
+            assert!(tampered.verify(&ring, msg).is_err());
+
+            // The proof only verifies over the ring in the order it was signed in
+            let mut reversed = ring.clone();
+            reversed.reverse();
+            assert!(proof.verify(&reversed, msg).is_err());
+        }
+
+        // The proof is logarithmic in the size of the ring
+        let (small, _) = TriptychProof::sign(&mut rng, &ring_with_signer(16), msg).unwrap();
+        let (large, _) = TriptychProof::sign(&mut rng, &ring_with_signer(256), msg).unwrap();
+        assert_eq!(small.size() + 4 * 3 * 32, large.size());
+    }
+
+    #[test]
+    fn test_proof_links_and_rejects_bad_rings() {
+        let mut rng = rand::thread_rng();
+        let msg = b"hello world";
+        let members = ring_with_signer(8);
+
+        // Two proofs with the same key have the same key image
+        let (first, _) = TriptychProof::sign(&mut rng, &members, msg).unwrap();
+        let (second, _) = TriptychProof::sign(&mut rng, &members, b"another message").unwrap();
+        assert_eq!(first.key_image(), second.key_image());
+
+        match TriptychProof::sign(&mut rng, &members[1..], msg) {
+            Err(ClsagError::InvalidRingSize) => {}
+            _ => panic!("expected an invalid ring size error"),
+        }
+        match TriptychProof::sign(&mut rng, &generate_decoys(8, 1), msg) {
+            Err(ClsagError::NoSigner) => {}
+            _ => panic!("expected a no signer error"),
+        }
+        let mut multi_key = generate_decoys(7, 2);
+        multi_key.push(generate_signer(2));
+        match TriptychProof::sign(&mut rng, &multi_key, msg) {
+            Err(ClsagError::KeyCountMismatch) => {}
+            _ => panic!("expected a key count mismatch error"),
+        }
+
+        // A private key which is not the key at the index does not sign
+        let ring: Vec<PublicKey> = generate_rand_public_keys(4);
+        let private_key = generate_rand_scalars(1)[0];
+        match TriptychProof::prove(&mut rng, &ring, 1, &private_key, msg) {
+            Err(ClsagError::NotASigner) => {}
+            _ => panic!("expected a not a signer error"),
+        }
+    }
+}
@@ -0,0 +1,177 @@
+// A transaction-like bundle of signatures, one for each input, all made on the same message.
+//
+// A transaction spends several outputs, each hidden in its own ring, and signs the same
+// message for all of them, which is usually the hash of the rest of the transaction.
+// `TxProof` holds the signature and the ring of every input along with the message,
+// and verifies them together. Besides each signature verifying, the first key image of every
+// input must be different, as two inputs with the same key image spend the same output.
+// Key images seen in earlier transactions are checked with a `KeyImageStore`.
+use crate::clsag::{Clsag, PublicKeys};
+use crate::error::ClsagError;
+use crate::keys::{KeyImage, PublicKey};
+use crate::signature::Signature;
+use std::collections::HashSet;
+
+// The signature of an input, along with the ring it was made over
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TxInput {
+    signature: Signature,
+    public_keys: PublicKeys,
+}
+
+// The signatures of every input of a transaction on a shared message
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TxProof {
+    inputs: Vec<TxInput>,
+    msg: Vec<u8>,
+}
+
+impl TxInput {
+    pub fn new(signature: Signature, public_keys: PublicKeys) -> Self {
+        TxInput {
+            signature,
+            public_keys,
+        }
+    }
+    // Returns the signature of the input
+    pub fn signature(&self) -> &Signature {
+        &self.signature
+    }
+    // Returns the ring the signature of the input was made over
+    pub fn public_keys(&self) -> &[Vec<PublicKey>] {
+        &self.public_keys
+    }
+}
+
+impl TxProof {
+    // Bundles the signatures of the inputs on the message, without verifying them
+    pub fn new(inputs: Vec<TxInput>, msg: &[u8]) -> Self {
+        TxProof {
+            inputs,
+            msg: msg.to_vec(),
+        }
+    }
+    // Signs the message with the ring of every input, in order.
+    // Returns an error if there are no inputs, or if any of the rings cannot be signed
+    pub fn sign_all(rings: &[Clsag], msg: &[u8]) -> Result<TxProof, ClsagError> {
+        if rings.is_empty() {
+            return Err(ClsagError::EmptyRing);
+        }
+        let inputs = rings
+            .iter()
+            .map(|ring| {
+                let (signature, public_keys) = ring.sign(msg)?;
+                Ok(TxInput::new(signature, public_keys))
+            })
+            .collect::<Result<Vec<TxInput>, ClsagError>>()?;
+
+        Ok(TxProof::new(inputs, msg))
+    }
+    // Returns the inputs of the transaction
+    pub fn inputs(&self) -> &[TxInput] {
+        &self.inputs
+    }
+    // Returns the message every input is signed on
+    pub fn msg(&self) -> &[u8] {
+        &self.msg
+    }
+    // Returns the number of inputs
+    pub fn len(&self) -> usize {
+        self.inputs.len()
+    }
+    // Returns true if there are no inputs
+    pub fn is_empty(&self) -> bool {
+        self.inputs.is_empty()
+    }
+    // Returns the first key image of every input, in order
+    pub fn key_images(&self) -> Vec<KeyImage> {
+        self.inputs
+            .iter()
+            .filter_map(|input| input.signature.key_images.first().copied())
+            .collect()
+    }
+    // Verifies the signature of every input on the message over its ring, and checks that
+    // no two inputs have the same first key image.
+    // Returns an error if there are no inputs, if any signature does not verify,
+    // or the index of the first input whose key image repeats an earlier input
+    pub fn verify(&self) -> Result<(), ClsagError> {
+        if self.inputs.is_empty() {
+            return Err(ClsagError::EmptyRing);
+        }
+
+        let mut seen = HashSet::new();
+        for (index, input) in self.inputs.iter().enumerate() {
+            input
+                .signature
+                .optimised_verify(&input.public_keys, &self.msg)?;
+
+            if let Some(key_image) = input.signature.key_images.first() {
+                if !seen.insert(key_image.to_bytes()) {
+                    return Err(ClsagError::DuplicateKeyImage(index));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::keys::SecretKey;
+    use crate::member::Member;
+    use crate::test_utils::*;
+
+    #[test]
+    fn test_sign_all() {
+        let msg = b"the transaction prefix";
+        let rings: Vec<Clsag> = (0..3)
+            .map(|_| {
+                let mut clsag = generate_clsag_with(4, 2);
+                clsag.add_member(generate_signer(2)).unwrap();
+                clsag
+            })
+            .collect();
+
+        let tx = TxProof::sign_all(&rings, msg).unwrap();
+        assert_eq!(3, tx.len());
+        assert_eq!(3, tx.key_images().len());
+        assert!(tx.verify().is_ok());
+
+        // Every input must verify on the shared message
+        let mut inputs = tx.inputs;
+        let (other_signature, other_public_keys) = rings[1].sign(b"another message").unwrap();
+        inputs[1] = TxInput::new(other_signature, other_public_keys);
+        assert!(TxProof::new(inputs, msg).verify().is_err());
+
+        match TxProof::sign_all(&[], msg) {
+            Err(ClsagError::EmptyRing) => {}
+            _ => panic!("expected an empty ring error"),
+        }
+    }
+
+    #[test]
+    fn test_verify_rejects_repeated_key_images() {
+        let msg = b"the transaction prefix";
+        let mut rng = rand::thread_rng();
+        let secret_keys = vec![SecretKey::random(&mut rng)];
+
+        // The same output is spent in two rings with different decoys
+        let rings: Vec<Clsag> = (0..2)
+            .map(|_| {
+                let mut clsag = generate_clsag_with(4, 1);
+                let signer = Member::signer_from_secret_keys(&secret_keys).unwrap();
+                clsag.add_member(signer).unwrap();
+                clsag
+            })
+            .collect();
+
+        let tx = TxProof::sign_all(&rings, msg).unwrap();
+        match tx.verify() {
+            Err(ClsagError::DuplicateKeyImage(1)) => {}
+            _ => panic!("expected a duplicate key image error"),
+        }
+    }
+}
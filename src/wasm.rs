@@ -0,0 +1,180 @@
+// Bindings for using the ring signature scheme from javascript through wasm-bindgen.
+// All keys and signatures cross the boundary as byte arrays:
+// private keys are 32 byte canonical scalars, public keys are 32 byte compressed
+// ristretto points and signatures use the `Signature::to_bytes` encoding.
+// A list of keys is the concatenation of the encoding of each key.
+use crate::clsag::{Clsag, PublicKeys};
+use crate::error::ClsagError;
+use crate::keys::{PrivateSet, PublicKey};
+use crate::member::Member;
+use crate::signature::Signature;
+use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
+use curve25519_dalek::scalar::Scalar;
+use wasm_bindgen::prelude::*;
+
+const KEY_SIZE: usize = 32;
+
+// Splits a byte array into 32 byte chunks, refusing empty or partial input
+fn split_keys(bytes: &[u8]) -> Result<Vec<[u8; 32]>, &'static str> {
+    if bytes.is_empty() || !bytes.len().is_multiple_of(KEY_SIZE) {
+        return Err("key bytes must be a non-empty multiple of 32 bytes");
+    }
+
+    Ok(bytes
+        .chunks_exact(KEY_SIZE)
+        .map(|chunk| {
+            let mut key = [0u8; 32];
+            key.copy_from_slice(chunk);
+            key
+        })
+        .collect())
+}
+
+fn decode_private_keys(bytes: &[u8]) -> Result<Vec<Scalar>, &'static str> {
+    split_keys(bytes)?
+        .into_iter()
+        .map(|key| Scalar::from_canonical_bytes(key).ok_or("private key is not canonical"))
+        .collect()
+}
+
+fn decode_public_keys(bytes: &[u8]) -> Result<Vec<RistrettoPoint>, &'static str> {
+    split_keys(bytes)?
+        .into_iter()
+        .map(|key| {
+            CompressedRistretto(key)
+                .decompress()
+                .ok_or("public key is not a valid point")
+        })
+        .collect()
+}
+
+// Generates `num_keys` random private keys
+#[wasm_bindgen(js_name = generatePrivateKeys)]
+pub fn generate_private_keys(num_keys: usize) -> Vec<u8> {
+    let mut rng = rand::thread_rng();
+    (0..num_keys)
+        .flat_map(|_| Scalar::random(&mut rng).to_bytes().to_vec())
+        .collect()
+}
+
+// Returns the public keys corresponding to the given private keys
+#[wasm_bindgen(js_name = publicKeysFromPrivate)]
+pub fn public_keys_from_private(private_keys: &[u8]) -> Result<Vec<u8>, JsValue> {
+    let private_set =
+        PrivateSet::new(decode_private_keys(private_keys).map_err(JsValue::from_str)?);
+    Ok(private_set.to_public_set().to_bytes())
+}
+
+// Verifies the signature over the ring of public keys.
+// The public keys are the keys of each member in ring order
+#[wasm_bindgen]
+pub fn verify(signature: &[u8], public_keys: &[u8], msg: &[u8]) -> Result<bool, JsValue> {
+    let signature = Signature::from_bytes(signature)
+        .map_err(|e| JsValue::from_str(&format!("invalid signature: {}", e)))?;
+
+    let num_keys = signature.key_images.len();
+    let keys = split_keys(public_keys).map_err(JsValue::from_str)?;
+    if !keys.len().is_multiple_of(num_keys) {
+        return Err(JsValue::from_str(
+            "number of public keys is not a multiple of the number of keys per member",
+        ));
+    }
+
+    // A key which is not a valid point cannot be part of a ring the signature verifies over
+    let ring = match keys
+        .chunks(num_keys)
+        .map(|member_keys| member_keys.iter().map(PublicKey::from_bytes).collect())
+        .collect::<Result<PublicKeys, ClsagError>>()
+    {
+        Ok(ring) => ring,
+        Err(_) => return Ok(false),
+    };
+
+    Ok(signature.optimised_verify(&ring, msg).is_ok())
+}
+
+// A ring which decoys and a signer can be added to before signing
+#[wasm_bindgen(js_name = Clsag)]
+pub struct WasmClsag {
+    clsag: Clsag,
+}
+
+impl Default for WasmClsag {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[wasm_bindgen(js_class = Clsag)]
+impl WasmClsag {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> WasmClsag {
+        WasmClsag {
+            clsag: Clsag::new(),
+        }
+    }
+
+    // Adds a decoy with the given public keys to the ring
+    #[wasm_bindgen(js_name = addDecoy)]
+    pub fn add_decoy(&mut self, public_keys: &[u8]) -> Result<(), JsValue> {
+        let public_keys = decode_public_keys(public_keys).map_err(JsValue::from_str)?;
+        self.clsag
+            .add_member(Member::new_decoy(public_keys))
+            .map_err(|e| JsValue::from_str(&format!("could not add member: {}", e)))
+    }
+
+    // Adds the signer with the given private keys to the ring
+    #[wasm_bindgen(js_name = addSigner)]
+    pub fn add_signer(&mut self, private_keys: &[u8]) -> Result<(), JsValue> {
+        let private_keys = decode_private_keys(private_keys).map_err(JsValue::from_str)?;
+        self.clsag
+            .add_member(Member::new_signer(private_keys))
+            .map_err(|e| JsValue::from_str(&format!("could not add member: {}", e)))
+    }
+
+    // Returns the public keys of every member in the order they were added in
+    #[wasm_bindgen(js_name = publicKeys)]
+    pub fn public_keys(&self) -> Vec<u8> {
+        self.clsag.public_keys_bytes()
+    }
+
+    // Signs the message, returning the encoded signature along with
+    // the public keys of the ring in the shuffled order they were signed in
+    pub fn sign(&self, msg: &[u8]) -> Result<SignedRing, JsValue> {
+        let (signature, public_keys) = self
+            .clsag
+            .sign(msg)
+            .map_err(|e| JsValue::from_str(&format!("could not sign: {}", e)))?;
+
+        Ok(SignedRing {
+            signature: signature.to_bytes(),
+            public_keys: public_keys
+                .iter()
+                .flatten()
+                .flat_map(|key| key.to_bytes().to_vec())
+                .collect(),
+        })
+    }
+}
+
+// A signature together with the public keys of the ring it verifies against
+#[wasm_bindgen]
+pub struct SignedRing {
+    signature: Vec<u8>,
+    public_keys: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl SignedRing {
+    // Returns the encoded signature
+    #[wasm_bindgen(getter)]
+    pub fn signature(&self) -> Vec<u8> {
+        self.signature.clone()
+    }
+
+    // Returns the public keys of every member in the order the ring was signed in
+    #[wasm_bindgen(getter, js_name = publicKeys)]
+    pub fn public_keys(&self) -> Vec<u8> {
+        self.public_keys.clone()
+    }
+}
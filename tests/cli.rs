@@ -0,0 +1,85 @@
+#![cfg(feature = "cli")]
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+fn cli(args: &[&str]) -> (bool, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_clsag-cli"))
+        .args(args)
+        .output()
+        .unwrap();
+    (
+        output.status.success(),
+        String::from_utf8(output.stdout).unwrap(),
+    )
+}
+
+fn keygen(num_keys: usize) -> serde_json::Value {
+    let (ok, stdout) = cli(&["keygen", "--keys", &num_keys.to_string()]);
+    assert!(ok);
+    serde_json::from_str(&stdout).unwrap()
+}
+
+#[test]
+fn test_keygen_sign_verify() {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("cli");
+    fs::create_dir_all(&dir).unwrap();
+    let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+
+    let num_keys = 2;
+    let signer = keygen(num_keys);
+    let mut ring: Vec<serde_json::Value> = (0..10)
+        .map(|_| keygen(num_keys)["public_keys"].clone())
+        .collect();
+    ring.insert(3, signer["public_keys"].clone());
+
+    fs::write(path("ring.json"), serde_json::to_string(&ring).unwrap()).unwrap();
+    fs::write(path("key.hex"), signer["secret_key"].as_str().unwrap()).unwrap();
+    fs::write(path("msg"), b"hello world").unwrap();
+    fs::write(path("other_msg"), b"another message").unwrap();
+
+    let (ok, stdout) = cli(&[
+        "sign",
+        "--ring",
+        &path("ring.json"),
+        "--key",
+        &path("key.hex"),
+        "--msg",
+        &path("msg"),
+    ]);
+    assert!(ok);
+    let signed: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    fs::write(path("signed_ring.json"), signed["ring"].to_string()).unwrap();
+    fs::write(path("sig.hex"), signed["signature"].as_str().unwrap()).unwrap();
+
+    let verify = |msg: &str| {
+        cli(&[
+            "verify",
+            "--ring",
+            &path("signed_ring.json"),
+            "--signature",
+            &path("sig.hex"),
+            "--msg",
+            &path(msg),
+        ])
+    };
+    assert_eq!((true, "valid\n".to_string()), verify("msg"));
+    assert!(!verify("other_msg").0);
+
+    // A signer whose keys are not in the ring cannot sign
+    fs::write(
+        path("other_key.hex"),
+        keygen(num_keys)["secret_key"].as_str().unwrap(),
+    )
+    .unwrap();
+    let (ok, _) = cli(&[
+        "sign",
+        "--ring",
+        &path("ring.json"),
+        "--key",
+        &path("other_key.hex"),
+        "--msg",
+        &path("msg"),
+    ]);
+    assert!(!ok);
+}
@@ -0,0 +1,88 @@
+// Property based tests over random ring sizes, key counts, signer positions and messages
+extern crate clsag;
+
+use clsag::clsag::PublicKeys;
+use clsag::signature::Signature;
+use clsag::test_utils::*;
+use proptest::prelude::*;
+
+// The parameters of a ring: the seed its keys are drawn from, the number of members,
+// the number of keys each member has and the position of the signer
+fn ring_params() -> impl Strategy<Value = (u64, usize, usize, usize)> {
+    (any::<u64>(), 2..12usize, 1..4usize).prop_flat_map(|(seed, ring_size, num_keys)| {
+        (Just(seed), Just(ring_size), Just(num_keys), 0..ring_size)
+    })
+}
+
+// Signs over a ring built in order, with the signer at the given position
+fn sign(
+    seed: u64,
+    ring_size: usize,
+    num_keys: usize,
+    signer_index: usize,
+    msg: &[u8],
+) -> (Signature, PublicKeys) {
+    let clsag = generate_ring_seeded(seed, ring_size, num_keys, signer_index);
+    clsag
+        .sign_with_rng(&mut seeded_rng(seed.wrapping_add(1)), msg)
+        .unwrap()
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(32))]
+
+    #[test]
+    fn valid_signatures_verify(
+        (seed, ring_size, num_keys, signer_index) in ring_params(),
+        msg in prop::collection::vec(any::<u8>(), 0..64),
+    ) {
+        let (signature, public_keys) = sign(seed, ring_size, num_keys, signer_index, &msg);
+
+        prop_assert!(signature.verify(&public_keys, &msg).is_ok());
+        prop_assert!(signature.optimised_verify(&public_keys, &msg).is_ok());
+    }
+
+    #[test]
+    fn tampered_signatures_do_not_verify(
+        (seed, ring_size, num_keys, signer_index) in ring_params(),
+        msg in prop::collection::vec(any::<u8>(), 0..64),
+        element in any::<prop::sample::Index>(),
+        byte in 0..32usize,
+        xor in 1..=255u8,
+    ) {
+        let (signature, public_keys) = sign(seed, ring_size, num_keys, signer_index, &msg);
+
+        // Flip bits in one byte of the challenge, a response or a key image
+        let mut bytes = signature.to_bytes();
+        let num_elements = 1 + ring_size + num_keys;
+        let position = 8 + 32 * element.index(num_elements) + byte;
+        bytes[position] ^= xor;
+
+        // The tampered signature either no longer decodes or no longer verifies
+        if let Ok(tampered) = Signature::from_bytes(&bytes) {
+            prop_assert!(tampered.verify(&public_keys, &msg).is_err());
+            prop_assert!(tampered.optimised_verify(&public_keys, &msg).is_err());
+        }
+    }
+
+    #[test]
+    fn signatures_do_not_verify_over_other_rings(
+        (seed, ring_size, num_keys, signer_index) in ring_params(),
+        msg in prop::collection::vec(any::<u8>(), 0..64),
+        replaced in any::<prop::sample::Index>(),
+        other_seed in any::<u64>(),
+    ) {
+        prop_assume!(seed != other_seed);
+        let (signature, public_keys) = sign(seed, ring_size, num_keys, signer_index, &msg);
+
+        // Replace one member with a member of a ring drawn from another seed
+        let other_ring = generate_ring_seeded(other_seed, ring_size, num_keys, signer_index);
+        let index = replaced.index(ring_size);
+        let mut other_keys = public_keys.clone();
+        other_keys[index] = other_ring.public_keys()[index].clone();
+
+        prop_assert!(signature.verify(&other_keys, &msg).is_err());
+        prop_assert!(signature.optimised_verify(&other_keys, &msg).is_err());
+        prop_assert!(signature.optimised_verify(other_ring.public_keys(), &msg).is_err());
+    }
+}
@@ -2,7 +2,7 @@ extern crate clsag;
 extern crate curve25519_dalek;
 
 use clsag::clsag::Clsag;
-use clsag::tests_helper::*;
+use clsag::test_utils::*;
 
 #[test]
 fn test_protocol() {
@@ -17,15 +17,15 @@ fn test_protocol() {
     // Generate and add decoys
     let decoys = generate_decoys(num_decoys, num_keys);
     for decoy in decoys {
-        clsag.add_member(decoy);
+        clsag.add_member(decoy).unwrap();
     }
 
     // Generate and add signer
     let signer = generate_signer(num_keys);
-    clsag.add_member(signer);
+    clsag.add_member(signer).unwrap();
 
-    let signature = clsag.sign(msg).unwrap();
-    let res = signature.verify(&mut clsag.public_keys(), msg);
+    let (signature, public_keys) = clsag.sign(msg).unwrap();
+    let res = signature.verify(&public_keys, msg);
 
     assert!(res.is_ok())
 }
@@ -0,0 +1,196 @@
+// Known answer tests for signatures made with `Clsag::sign_deterministic`.
+// The vectors in `tests/data/test_vectors.json` hold every input to signing along with the
+// expected signature, so that other implementations can check they produce the same bytes.
+// Each vector has the secret keys of the signer, the public keys of every member in ring order,
+// the position of the signer, the message and the expected key images and signature,
+// all in hex. The signature uses the `Signature::to_bytes` encoding.
+//
+// Run with `CLSAG_REGENERATE_VECTORS=1` to rewrite the file after a deliberate change to signing.
+//
+// The keys derived by `PrivateSet::from_seed` are checked against the fixed answers in
+// `SEED_VECTORS`, as wallets restore their keys from seeds backed up with older versions.
+// These must never be regenerated.
+extern crate clsag;
+
+use clsag::clsag::Clsag;
+use clsag::constants::BASEPOINT;
+use clsag::keys::{PrivateSet, PublicKey};
+use clsag::member::Member;
+use clsag::policy::RingPolicy;
+use clsag::signature::Signature;
+use clsag::test_utils::generate_rand_scalars_with_rng;
+use curve25519_dalek::scalar::Scalar;
+use rand::SeedableRng;
+use rand_chacha::ChaChaRng;
+use serde_json::{json, Value};
+use std::env;
+use std::fs;
+use std::path::Path;
+
+const VECTORS_PATH: &str = "tests/data/test_vectors.json";
+
+// The ring size, number of keys, signer position and message of each vector
+const PARAMETERS: &[(usize, usize, usize, &[u8])] = &[
+    (2, 1, 0, b""),
+    (2, 1, 1, b"hello world"),
+    (4, 2, 2, b"hello world"),
+    (11, 1, 10, b"clsag test vector"),
+    (11, 2, 5, b"clsag test vector"),
+    (16, 3, 0, &[0, 1, 2, 3, 255]),
+];
+
+// The seed, the path and the public keys derived at the path, in hex
+const SEED_VECTORS: &[([u8; 32], &[u32], &[&str])] = &[
+    (
+        [0; 32],
+        &[],
+        &["12e94e41083a1cc1666f8e759459b3000e3675eead68dfd2a2ef9da70e5bb014"],
+    ),
+    (
+        [1; 32],
+        &[0],
+        &[
+            "0a08a7b0ad49345edca26e80878281e8312a7923e38b371358ca64183125d11c",
+            "c8827dd6c703510556863276f43364cce131e3503f6a3c679c1e4225ced7993b",
+        ],
+    ),
+    (
+        [0x42; 32],
+        &[44, 0, 7],
+        &[
+            "3267b9c13eb0f043a108250cc582bd2a423f33595d343cd653c725a8f818a619",
+            "d27c3352656960e21331c380236fbcf2b74bb507693e7eca2c7cdaf1adb6e80e",
+            "0abf488bb9978947f1c8a01cc5a485ce85bb3421d4826a35604a5ace26807c3d",
+        ],
+    ),
+];
+
+fn hex_list(items: impl Iterator<Item = [u8; 32]>) -> Vec<String> {
+    items.map(hex::encode).collect()
+}
+
+// Generates the vector with the given parameters, drawing every key from a rng seeded with `index`
+fn generate(
+    index: usize,
+    ring_size: usize,
+    num_keys: usize,
+    signer_index: usize,
+    msg: &[u8],
+) -> Value {
+    let mut rng = ChaChaRng::from_seed([index as u8; 32]);
+    let mut clsag = Clsag::new();
+    clsag.set_ring_policy(RingPolicy::permissive());
+
+    let mut signer_keys = Vec::new();
+    for member_index in 0..ring_size {
+        let private_keys = generate_rand_scalars_with_rng(&mut rng, num_keys);
+        let member = if member_index == signer_index {
+            signer_keys = hex_list(private_keys.iter().map(Scalar::to_bytes));
+            Member::new_signer(private_keys)
+        } else {
+            Member::new_decoy(private_keys.iter().map(|x| x * BASEPOINT).collect())
+        };
+        clsag.add_member(member).unwrap();
+    }
+
+    let signature = clsag.sign_deterministic(msg).unwrap();
+    let ring: Vec<Vec<String>> = clsag
+        .public_keys()
+        .iter()
+        .map(|keys| hex_list(keys.iter().map(|key| key.to_bytes())))
+        .collect();
+
+    json!({
+        "secret_keys": signer_keys,
+        "ring": ring,
+        "signer_index": signer_index,
+        "message": hex::encode(msg),
+        "key_images": hex_list(signature.key_images().iter().map(|key_image| key_image.to_bytes())),
+        "signature": hex::encode(signature.to_bytes()),
+    })
+}
+
+fn generate_all() -> Value {
+    PARAMETERS
+        .iter()
+        .enumerate()
+        .map(|(index, &(ring_size, num_keys, signer_index, msg))| {
+            generate(index, ring_size, num_keys, signer_index, msg)
+        })
+        .collect()
+}
+
+#[test]
+fn test_vectors_match() {
+    let generated = generate_all();
+    if env::var("CLSAG_REGENERATE_VECTORS").is_ok() {
+        let json = serde_json::to_string_pretty(&generated).unwrap();
+        fs::write(VECTORS_PATH, json + "\n").unwrap();
+    }
+
+    let expected: Value =
+        serde_json::from_str(&fs::read_to_string(Path::new(VECTORS_PATH)).unwrap()).unwrap();
+    assert_eq!(expected, generated);
+}
+
+#[test]
+fn test_vectors_verify() {
+    let vectors: Value =
+        serde_json::from_str(&fs::read_to_string(Path::new(VECTORS_PATH)).unwrap()).unwrap();
+
+    for vector in vectors.as_array().unwrap() {
+        let str_list = |value: &Value| -> Vec<String> {
+            value
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|x| x.as_str().unwrap().to_string())
+                .collect()
+        };
+        let ring: Vec<Vec<_>> = vector["ring"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|keys| {
+                str_list(keys)
+                    .iter()
+                    .map(|key| {
+                        let mut bytes = [0u8; 32];
+                        hex::decode_to_slice(key, &mut bytes).unwrap();
+                        PublicKey::from_bytes(&bytes).unwrap()
+                    })
+                    .collect()
+            })
+            .collect();
+        let msg = hex::decode(vector["message"].as_str().unwrap()).unwrap();
+        let signature =
+            Signature::from_bytes(&hex::decode(vector["signature"].as_str().unwrap()).unwrap())
+                .unwrap();
+
+        assert!(signature.verify(&ring, &msg).is_ok());
+        assert_eq!(
+            str_list(&vector["key_images"]),
+            hex_list(
+                signature
+                    .key_images()
+                    .iter()
+                    .map(|key_image| key_image.to_bytes())
+            )
+        );
+    }
+}
+
+#[test]
+fn test_seed_vectors() {
+    for (seed, path, expected) in SEED_VECTORS {
+        let private_set = PrivateSet::from_seed(seed, path, expected.len());
+        let derived = hex_list(
+            private_set
+                .to_public_set()
+                .to_keys()
+                .iter()
+                .map(PublicKey::to_bytes),
+        );
+        assert_eq!(expected.to_vec(), derived);
+    }
+}
@@ -0,0 +1,28 @@
+// Run with `wasm-pack test --node -- --features wasm`
+#![cfg(all(target_arch = "wasm32", feature = "wasm"))]
+extern crate clsag;
+
+use clsag::wasm::{generate_private_keys, public_keys_from_private, verify, WasmClsag};
+use wasm_bindgen_test::*;
+
+#[wasm_bindgen_test]
+fn test_sign_and_verify() {
+    let num_keys = 2;
+    let num_decoys = 10;
+    let msg = b"hello world";
+
+    let mut clsag = WasmClsag::new();
+    for _ in 0..num_decoys {
+        let decoy_keys = public_keys_from_private(&generate_private_keys(num_keys)).unwrap();
+        clsag.add_decoy(&decoy_keys).unwrap();
+    }
+    clsag.add_signer(&generate_private_keys(num_keys)).unwrap();
+
+    let signed = clsag.sign(msg).unwrap();
+    let signature = signed.signature();
+    let ring = signed.public_keys();
+
+    assert!(verify(&signature, &ring, msg).unwrap());
+    assert!(!verify(&signature, &ring, b"another message").unwrap());
+    assert!(verify(&signature[1..], &ring, msg).is_err());
+}